@@ -75,6 +75,19 @@ struct SudachiToken: Equatable {
     }
 }
 
+// MARK: - Kanji Details
+
+/// Readings, meanings and stroke count of a kanji (sudachi_kanji_info)
+struct KanjiInfo: Codable, Equatable {
+    let kanji: String
+    let strokes: Int
+    /// On readings in katakana
+    let on: [String]
+    /// Kun readings, okurigana after "."
+    let kun: [String]
+    let meanings: [String]
+}
+
 // MARK: - Error Types
 
 /// Errors that can occur during Sudachi operations
@@ -84,6 +97,7 @@ enum SudachiError: Error, LocalizedError {
     case tokenizationFailed
     case invalidInput
     case memoryAllocationFailed
+    case layoutMismatch(index: Int)
     
     var errorDescription: String? {
         switch self {
@@ -97,6 +111,8 @@ enum SudachiError: Error, LocalizedError {
             return "Invalid input text"
         case .memoryAllocationFailed:
             return "Memory allocation failed"
+        case .layoutMismatch(let index):
+            return "Sudachi header does not match the linked library (struct \(index))"
         }
     }
 }
@@ -29,6 +29,9 @@ final class SudachiBridge {
     init(dictionaryPath: String) throws {
         logger.info("Initializing Sudachi with dictionary: \(dictionaryPath)")
         
+        // Refuse a header from another build before reading any struct
+        try Self.checkLayout()
+        
         // Verify dictionary file exists
         guard FileManager.default.fileExists(atPath: dictionaryPath) else {
             logger.error("Dictionary file not found at: \(dictionaryPath)")
@@ -51,6 +54,18 @@ final class SudachiBridge {
         logger.info("✅ Sudachi initialized successfully")
     }
     
+    /// Compare the struct sizes of the imported header with the library
+    /// - Throws: SudachiError.layoutMismatch if a struct differs
+    private static func checkLayout() throws {
+        var sizes = [UInt](repeating: 0, count: Int(SudachiSUDACHI_LAYOUT_COUNT))
+        sizes[Int(SudachiSUDACHI_LAYOUT_TOKEN)] = UInt(MemoryLayout<SudachiSudachiToken>.stride)
+        
+        var mismatch: UInt = 0
+        guard sudachi_layout_check(sizes, UInt(sizes.count), &mismatch) else {
+            throw SudachiError.layoutMismatch(index: Int(mismatch))
+        }
+    }
+    
     deinit {
         if let tokenizer = tokenizer {
             logger.debug("Freeing Sudachi tokenizer")
@@ -82,75 +97,58 @@ final class SudachiBridge {
         }
         
         // Call C FFI
-        var count: UInt = 0
-        let tokensPtr = sudachi_tokenize(
+        guard let result = sudachi_tokenize_result(
             tokenizer,
             cText,
             mode.cValue,
-            &count
-        )
-        
-        guard tokensPtr != nil else {
-            logger.error("sudachi_tokenize returned NULL")
+            Self.fields
+        ) else {
+            logger.error("sudachi_tokenize_result returned NULL")
             throw SudachiError.tokenizationFailed
         }
+        defer { sudachi_free_result(result) }
         
         // Convert C tokens to Swift tokens
+        let count = sudachi_result_count(result)
         var tokens: [SudachiToken] = []
         tokens.reserveCapacity(Int(count))
         
-        for i in 0..<Int(count) {
-            guard let tokenPtr = tokensPtr?[i] else {
-                logger.warning("Token pointer at index \(i) is NULL")
-                continue
-            }
-            
-            let token = try convertCTokenToSwift(tokenPtr.pointee)
-            tokens.append(token)
+        for i in 0..<count {
+            tokens.append(try convertCTokenToSwift(result, index: i))
         }
         
-        // Free C memory
-        sudachi_free_tokens(tokensPtr, count)
-        
         logger.debug("Tokenized '\(text)' into \(tokens.count) tokens")
         return tokens
     }
     
     // MARK: - Private Helpers
     
-    /// Convert C token struct to Swift SudachiToken
-    private func convertCTokenToSwift(_ cToken: SudachiSudachiToken) throws -> SudachiToken {
-        // Extract surface (required)
-        guard let surfacePtr = cToken.surface else {
-            throw SudachiError.memoryAllocationFailed
-        }
-        let surface = String(cString: surfacePtr)
-        
-        // Extract reading (optional)
-        let reading: String? = if let readingPtr = cToken.reading {
-            String(cString: readingPtr)
-        } else {
-            nil
+    /// String fields read by convertCTokenToSwift
+    private static let fields = UInt32(
+        SudachiSUDACHI_FIELD_SURFACE | SudachiSUDACHI_FIELD_READING
+            | SudachiSUDACHI_FIELD_DICTIONARY_FORM | SudachiSUDACHI_FIELD_NORMALIZED_FORM
+            | SudachiSUDACHI_FIELD_POS
+    )
+    
+    /// Convert token index of a result to Swift SudachiToken
+    private func convertCTokenToSwift(_ result: OpaquePointer, index: UInt) throws -> SudachiToken {
+        let string = { (ptr: UnsafePointer<CChar>?) -> String? in
+            ptr.map { String(cString: $0) }
         }
         
-        // Extract dictionary form (optional)
-        let dictionaryForm: String? = if let dictPtr = cToken.dictionary_form {
-            String(cString: dictPtr)
-        } else {
-            nil
+        // Extract surface (required)
+        guard let surface = string(sudachi_token_surface(result, index)) else {
+            throw SudachiError.memoryAllocationFailed
         }
         
-        // Extract normalized form (optional)
-        let normalizedForm: String? = if let normPtr = cToken.normalized_form {
-            String(cString: normPtr)
-        } else {
-            nil
-        }
+        // Extract optional fields
+        let reading = string(sudachi_token_reading(result, index))
+        let dictionaryForm = string(sudachi_token_dictionary_form(result, index))
+        let normalizedForm = string(sudachi_token_normalized_form(result, index))
         
         // Extract POS tags (JSON array)
         var partOfSpeech: [String] = []
-        if let posPtr = cToken.pos {
-            let posJSON = String(cString: posPtr)
+        if let posJSON = string(sudachi_token_pos(result, index)) {
             if let data = posJSON.data(using: .utf8),
                let array = try? JSONDecoder().decode([String].self, from: data) {
                 partOfSpeech = array
@@ -165,12 +163,51 @@ final class SudachiBridge {
             dictionaryForm: dictionaryForm,
             normalizedForm: normalizedForm,
             partOfSpeech: partOfSpeech,
-            beginOffset: Int(cToken.begin),
-            endOffset: Int(cToken.end)
+            beginOffset: Int(sudachi_token_begin(result, index)),
+            endOffset: Int(sudachi_token_end(result, index))
         )
     }
 }
 
+// MARK: - Kanji Details
+
+extension SudachiBridge {
+    /// Load the kanji table used by kanjiInfo(for:)
+    static func loadKanjiTable(path: String) -> Bool {
+        sudachi_kanji_load(path)
+    }
+    
+    /// Details of a kanji, or nil if it is not in the loaded table
+    static func kanjiInfo(for kanji: Character) -> KanjiInfo? {
+        guard let scalar = kanji.unicodeScalars.first,
+              let json = sudachi_kanji_info(scalar.value) else {
+            return nil
+        }
+        defer { sudachi_free_string(json) }
+        return try? JSONDecoder().decode(KanjiInfo.self, from: Data(String(cString: json).utf8))
+    }
+    
+    /// Components of a kanji, empty if unknown
+    static func kanjiComponents(of kanji: Character) -> [Character] {
+        guard let scalar = kanji.unicodeScalars.first,
+              let components = sudachi_kanji_components(scalar.value) else {
+            return []
+        }
+        defer { sudachi_free_string(components) }
+        return Array(String(cString: components))
+    }
+    
+    /// Kanji containing all of the components, fewest strokes first
+    static func kanji(withComponents components: [Character]) -> [Character] {
+        let codepoints = components.compactMap { $0.unicodeScalars.first?.value }
+        guard let found = sudachi_kanji_by_components(codepoints, UInt(codepoints.count)) else {
+            return []
+        }
+        defer { sudachi_free_string(found) }
+        return Array(String(cString: found))
+    }
+}
+
 // MARK: - Version Info
 
 extension SudachiBridge {
@@ -0,0 +1,141 @@
+// FFI benchmarks
+// Measures the C entry points end to end (analysis plus marshalling and
+// freeing) over bundled sample texts: manga dialogue, OCR output with
+// misrecognized characters and broken lines, and long-form prose.
+//
+// Needs a system dictionary: SUDACHI_DICT, or Manglex/Resources/system.dic
+// as installed by `make dictionary`.
+//
+//   cargo bench --bench ffi
+
+use std::ffi::CString;
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use sudachi_ios::batch::{sudachi_free_token_batch, sudachi_tokenize_batch};
+use sudachi_ios::furigana::{sudachi_free_furigana, sudachi_furigana};
+use sudachi_ios::lookup::sudachi_lookup;
+use sudachi_ios::{
+    sudachi_free_tokenizer, sudachi_free_tokens, sudachi_init, sudachi_tokenize_fields,
+    SudachiTokenMode, SudachiTokenizer, SUDACHI_FIELD_ALL,
+};
+
+const CORPORA: [(&str, &str); 3] = [
+    ("dialogue", include_str!("corpus/dialogue.txt")),
+    ("ocr_noisy", include_str!("corpus/ocr_noisy.txt")),
+    ("long_form", include_str!("corpus/long_form.txt")),
+];
+
+const LOOKUP_WORDS: [&str; 6] = ["日本", "食べる", "約束", "すっげー", "ロボット", "喫茶店"];
+
+fn dictionary_path() -> Option<PathBuf> {
+    let path = std::env::var_os("SUDACHI_DICT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../Manglex/Resources/system.dic")
+        });
+    path.exists().then_some(path)
+}
+
+fn bench_tokenize(c: &mut Criterion, tokenizer: *mut SudachiTokenizer) {
+    let mut group = c.benchmark_group("tokenize");
+    for (name, text) in CORPORA {
+        let text = CString::new(text).unwrap();
+        group.throughput(Throughput::Bytes(text.as_bytes().len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut count = 0;
+                let tokens = sudachi_tokenize_fields(
+                    tokenizer,
+                    text.as_ptr(),
+                    SudachiTokenMode::C,
+                    SUDACHI_FIELD_ALL,
+                    &mut count,
+                );
+                sudachi_free_tokens(tokens, count);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_batch(c: &mut Criterion, tokenizer: *mut SudachiTokenizer) {
+    let mut group = c.benchmark_group("batch");
+    for (name, text) in CORPORA {
+        let lines: Vec<CString> = text
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| CString::new(l).unwrap())
+            .collect();
+        let ptrs: Vec<_> = lines.iter().map(|l| l.as_ptr()).collect();
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut counts = vec![0; ptrs.len()];
+                let batch = sudachi_tokenize_batch(
+                    tokenizer,
+                    ptrs.as_ptr(),
+                    ptrs.len(),
+                    SudachiTokenMode::C,
+                    0,
+                    counts.as_mut_ptr(),
+                );
+                sudachi_free_token_batch(batch, counts.as_ptr(), ptrs.len());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_furigana(c: &mut Criterion, tokenizer: *mut SudachiTokenizer) {
+    let mut group = c.benchmark_group("furigana");
+    for (name, text) in CORPORA {
+        let text = CString::new(text).unwrap();
+        group.throughput(Throughput::Bytes(text.as_bytes().len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut count = 0;
+                let ruby =
+                    sudachi_furigana(tokenizer, text.as_ptr(), SudachiTokenMode::C, &mut count);
+                sudachi_free_furigana(ruby, count);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_lookup(c: &mut Criterion, tokenizer: *mut SudachiTokenizer) {
+    let words: Vec<CString> = LOOKUP_WORDS
+        .iter()
+        .map(|w| CString::new(*w).unwrap())
+        .collect();
+    c.bench_function("lookup", |b| {
+        b.iter(|| {
+            for word in &words {
+                let mut count = 0;
+                let tokens = sudachi_lookup(tokenizer, word.as_ptr(), &mut count);
+                sudachi_free_tokens(tokens, count);
+            }
+        })
+    });
+}
+
+fn benches(c: &mut Criterion) {
+    let Some(path) = dictionary_path() else {
+        eprintln!("No dictionary found; set SUDACHI_DICT or run `make dictionary`");
+        return;
+    };
+    let path = CString::new(path.to_str().unwrap()).unwrap();
+    let tokenizer = sudachi_init(path.as_ptr());
+    assert!(!tokenizer.is_null(), "failed to load dictionary");
+
+    bench_tokenize(c, tokenizer);
+    bench_batch(c, tokenizer);
+    bench_furigana(c, tokenizer);
+    bench_lookup(c, tokenizer);
+
+    sudachi_free_tokenizer(tokenizer);
+}
+
+criterion_group!(ffi, benches);
+criterion_main!(ffi);
@@ -0,0 +1,48 @@
+// Generates the C header from the Rust sources on every build, so the
+// shipped header can never drift from the compiled struct layouts.
+// With the apple feature, also compiles the Objective-C wrapper against it
+// and the os_signpost shim.
+
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    #[cfg(feature = "napi")]
+    napi_build::setup();
+
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("Failed to read cbindgen.toml");
+
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(crate_dir.join("include/sudachi_ffi.h"));
+        }
+        Err(e) => panic!("Failed to generate C header: {}", e),
+    }
+
+    #[cfg(feature = "apple")]
+    build_objc(&crate_dir);
+}
+
+#[cfg(feature = "apple")]
+fn build_objc(crate_dir: &std::path::Path) {
+    println!("cargo:rerun-if-changed=objc");
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if target_os != "ios" && target_os != "macos" {
+        return;
+    }
+
+    cc::Build::new()
+        .file(crate_dir.join("objc/MGXTokenizer.m"))
+        .include(crate_dir.join("include"))
+        .flag("-fobjc-arc")
+        .compile("mgx_objc");
+    cc::Build::new()
+        .file(crate_dir.join("objc/MGXSignpost.c"))
+        .compile("mgx_signpost");
+    println!("cargo:rustc-link-lib=framework=Foundation");
+}
@@ -8,15 +8,21 @@ GREEN='\033[0;32m'
 BLUE='\033[0;34m'
 NC='\033[0m' # No Color
 
+# Optional cargo features, e.g. FEATURES=uniffi ./build.sh
+CARGO_FLAGS="--release"
+if [ -n "${FEATURES}" ]; then
+    CARGO_FLAGS="${CARGO_FLAGS} --features ${FEATURES}"
+fi
+
 # Build for all iOS architectures
 echo -e "${BLUE}Building for iOS device (arm64)...${NC}"
-cargo build --release --target aarch64-apple-ios
+cargo build ${CARGO_FLAGS} --target aarch64-apple-ios
 
 echo -e "${BLUE}Building for iOS simulator (arm64)...${NC}"
-cargo build --release --target aarch64-apple-ios-sim
+cargo build ${CARGO_FLAGS} --target aarch64-apple-ios-sim
 
 echo -e "${BLUE}Building for iOS simulator (x86_64)...${NC}"
-cargo build --release --target x86_64-apple-ios
+cargo build ${CARGO_FLAGS} --target x86_64-apple-ios
 
 # Create directories
 mkdir -p target/universal/release
@@ -31,18 +37,40 @@ lipo -create \
     ${TARGET_DIR}/x86_64-apple-ios/release/libsudachi_ios.a \
     -output target/universal/release/libsudachi_ios_sim.a
 
-# Generate C header
-echo -e "${BLUE}Generating C header...${NC}"
-cbindgen --config cbindgen.toml --crate sudachi-ios --output target/universal/release/sudachi_ios.h
+# Ship the C header generated by build.rs during the builds above, plus the
+# header-only C++ wrapper
+echo -e "${BLUE}Copying generated C header...${NC}"
+cp include/sudachi_ffi.h include/sudachi.hpp target/universal/release/
+if [[ "${FEATURES}" == *apple* ]]; then
+    cp objc/MGXTokenizer.h target/universal/release/
+fi
 
 # Create module maps for both architectures
 echo -e "${BLUE}Creating module maps...${NC}"
 cat > target/universal/release/module.modulemap << 'EOF'
 module SudachiFFI {
-    header "sudachi_ios.h"
+    header "sudachi_ffi.h"
+    export *
+}
+EOF
+
+# Generate the high-level Swift bindings when built with UniFFI
+if [[ "${FEATURES}" == *uniffi* ]]; then
+    echo -e "${BLUE}Generating Swift bindings...${NC}"
+    # Library mode reads the metadata of the cdylib built alongside the
+    # static library; the .a stays for the XCFramework
+    cargo run --features uniffi --bin uniffi-bindgen -- generate \
+        --library ${TARGET_DIR}/aarch64-apple-ios/release/libsudachi_ios.dylib \
+        --language swift \
+        --out-dir target/universal/swift
+    cp target/universal/swift/sudachi_iosFFI.h target/universal/release/
+    cat >> target/universal/release/module.modulemap << 'EOF'
+module sudachi_iosFFI {
+    header "sudachi_iosFFI.h"
     export *
 }
 EOF
+fi
 
 # Create XCFramework
 echo -e "${BLUE}Creating XCFramework...${NC}"
@@ -62,6 +90,9 @@ cp target/universal/release/module.modulemap SudachiFFI.xcframework/ios-arm64_x8
 
 echo -e "${GREEN}✅ Build complete!${NC}"
 echo "📦 Output: SudachiFFI.xcframework"
+if [[ "${FEATURES}" == *uniffi* ]]; then
+    echo "📦 Swift bindings: target/universal/swift/sudachi_ios.swift"
+fi
 echo ""
 echo "Next steps:"
 echo "1. Copy SudachiFFI.xcframework to your Xcode project's Frameworks folder"
@@ -0,0 +1,29 @@
+// Deinflect arbitrary text, checking offsets and that every entry has a
+// surface and dictionary form.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sudachi_ios::deinflect::{sudachi_deinflect, sudachi_free_deinflections};
+use sudachi_ios_fuzz::{tokenizer, Params};
+
+fuzz_target!(|data: &[u8]| {
+    let Some(params) = Params::parse(data) else {
+        return;
+    };
+    let Some(text) = params.c_text() else {
+        return;
+    };
+
+    let mut count = 0;
+    let words = sudachi_deinflect(tokenizer(), text.as_ptr(), params.mode, &mut count);
+    if words.is_null() {
+        return;
+    }
+    for word in unsafe { std::slice::from_raw_parts(words, count) } {
+        assert!(!word.surface.is_null() && !word.dictionary_form.is_null());
+        assert!(0 <= word.begin && word.begin <= word.end);
+        assert!(word.end as usize <= params.text.len());
+    }
+    sudachi_free_deinflections(words, count);
+});
@@ -0,0 +1,69 @@
+// Drive the result serializers (text output, packed buffers, columns) with
+// arbitrary text, field masks and buffer sizes.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sudachi_ios::columns::{sudachi_free_columns, sudachi_tokenize_columns};
+use sudachi_ios::format::{sudachi_format, SudachiOutputFormat};
+use sudachi_ios::packed::sudachi_tokenize_into;
+use sudachi_ios::sudachi_free_string;
+use sudachi_ios_fuzz::{tokenizer, Params};
+
+fuzz_target!(|data: &[u8]| {
+    let Some(params) = Params::parse(data) else {
+        return;
+    };
+    let Some(text) = params.c_text() else {
+        return;
+    };
+    let tokenizer = tokenizer();
+
+    let format = match params.flags % 3 {
+        0 => SudachiOutputFormat::Tsv,
+        1 => SudachiOutputFormat::TsvAll,
+        _ => SudachiOutputFormat::Wakati,
+    };
+    sudachi_free_string(sudachi_format(
+        tokenizer,
+        text.as_ptr(),
+        params.mode,
+        format,
+    ));
+
+    // Buffer sizes below, at and above the required size
+    let mut needed = 0;
+    sudachi_tokenize_into(
+        tokenizer,
+        text.as_ptr(),
+        params.mode,
+        std::ptr::null_mut(),
+        0,
+        &mut needed,
+    );
+    let len = match params.fields % 3 {
+        0 => needed.saturating_sub(1),
+        1 => needed,
+        _ => needed + 64,
+    };
+    let mut buf = vec![0u32; len.div_ceil(4)];
+    let written = sudachi_tokenize_into(
+        tokenizer,
+        text.as_ptr(),
+        params.mode,
+        buf.as_mut_ptr() as *mut u8,
+        len,
+        &mut needed,
+    );
+    assert_eq!(written, needed != 0 && len >= needed);
+
+    let columns = sudachi_tokenize_columns(tokenizer, text.as_ptr(), params.mode, params.fields);
+    if !columns.is_null() {
+        let c = unsafe { &*columns };
+        for i in 0..c.count {
+            let end = unsafe { *c.ends.add(i) } as usize;
+            assert!(end <= params.text.len());
+        }
+        sudachi_free_columns(columns);
+    }
+});
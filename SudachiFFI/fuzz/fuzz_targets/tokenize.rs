@@ -0,0 +1,52 @@
+// Tokenize arbitrary bytes with random modes, field masks and input flags
+// through both the C string and the byte-length entry points, then free the
+// results. Offsets are checked by the serialize target.
+
+#![no_main]
+
+use std::ptr;
+
+use libfuzzer_sys::fuzz_target;
+use sudachi_ios::status::SudachiStatus;
+use sudachi_ios::utf8::sudachi_tokenize_bytes;
+use sudachi_ios::{sudachi_free_tokens, sudachi_tokenize_fields};
+use sudachi_ios_fuzz::{tokenizer, Params};
+
+fuzz_target!(|data: &[u8]| {
+    let Some(params) = Params::parse(data) else {
+        return;
+    };
+    let tokenizer = tokenizer();
+
+    let mut tokens = ptr::null_mut();
+    let mut count = 0;
+    let mut invalid = 0;
+    let status = sudachi_tokenize_bytes(
+        tokenizer,
+        params.text.as_ptr(),
+        params.text.len(),
+        params.mode,
+        params.fields,
+        params.flags,
+        &mut tokens,
+        &mut count,
+        &mut invalid,
+    );
+    if status == SudachiStatus::Ok {
+        sudachi_free_tokens(tokens, count);
+    } else {
+        assert!(tokens.is_null());
+    }
+
+    if let Some(text) = params.c_text() {
+        let mut count = 0;
+        let tokens = sudachi_tokenize_fields(
+            tokenizer,
+            text.as_ptr(),
+            params.mode,
+            params.fields,
+            &mut count,
+        );
+        sudachi_free_tokens(tokens, count);
+    }
+});
@@ -0,0 +1,64 @@
+// Shared setup for the fuzz targets
+// Compiles the miniature dictionary in testdata/ in memory once per process
+// and splits fuzzer input into call parameters.
+
+use std::sync::OnceLock;
+
+use sudachi::dic::build::DictBuilder;
+use sudachi_ios::{sudachi_init_from_bytes, SudachiTokenMode, SudachiTokenizer};
+
+const LEXICON: &[u8] = include_bytes!("../../testdata/lex.csv");
+const MATRIX: &[u8] = include_bytes!("../../testdata/matrix.def");
+
+/// Tokenizer over the test dictionary, shared by all iterations
+pub fn tokenizer() -> *mut SudachiTokenizer {
+    // Stored as an address since raw pointers are not Sync
+    static TOKENIZER: OnceLock<usize> = OnceLock::new();
+    *TOKENIZER.get_or_init(|| {
+        let mut builder = DictBuilder::new_system();
+        builder.set_description("fuzz");
+        builder.read_conn(MATRIX).expect("matrix.def");
+        builder.read_lexicon(LEXICON).expect("lex.csv");
+        builder.resolve().expect("resolve");
+        let mut image = Vec::new();
+        builder.compile(&mut image).expect("compile");
+
+        let tokenizer = sudachi_init_from_bytes(image.as_ptr(), image.len());
+        assert!(!tokenizer.is_null(), "test dictionary failed to load");
+        tokenizer as usize
+    }) as *mut SudachiTokenizer
+}
+
+/// Call parameters taken from the front of the fuzzer input
+pub struct Params<'a> {
+    pub mode: SudachiTokenMode,
+    /// SUDACHI_FIELD_* mask, including undefined bits
+    pub fields: u32,
+    pub flags: u32,
+    pub text: &'a [u8],
+}
+
+impl<'a> Params<'a> {
+    /// Split off one mode byte, four field mask bytes and one flags byte
+    pub fn parse(data: &'a [u8]) -> Option<Params<'a>> {
+        if data.len() < 6 {
+            return None;
+        }
+        let mode = match data[0] % 3 {
+            0 => SudachiTokenMode::A,
+            1 => SudachiTokenMode::B,
+            _ => SudachiTokenMode::C,
+        };
+        Some(Params {
+            mode,
+            fields: u32::from_le_bytes([data[1], data[2], data[3], data[4]]),
+            flags: u32::from(data[5]),
+            text: &data[6..],
+        })
+    }
+
+    /// Text as a C string, or None if it contains a NUL byte
+    pub fn c_text(&self) -> Option<std::ffi::CString> {
+        std::ffi::CString::new(self.text).ok()
+    }
+}
@@ -0,0 +1,287 @@
+// Header-only C++ wrapper over the Sudachi C API
+// RAII handles own every library allocation and free it in their
+// destructors; string accessors return std::string_view into memory owned
+// by the result, valid until the result is destroyed.
+//
+// Requires C++17. Link against libsudachi_ios as for the C API.
+
+#ifndef SUDACHI_HPP
+#define SUDACHI_HPP
+
+#include <cstddef>
+#include <cstdint>
+#include <stdexcept>
+#include <string>
+#include <string_view>
+#include <utility>
+#include <vector>
+
+#include "sudachi_ffi.h"
+
+namespace sudachi {
+
+namespace c = ::sudachi_ios;
+
+/// Split mode: A is shortest units, C is longest (named entities)
+enum class Mode {
+    A = c::A,
+    B = c::B,
+    C = c::C,
+};
+
+/// SUDACHI_FIELD_* bits for Tokenizer::tokenize
+namespace field {
+constexpr uint32_t surface = SudachiSUDACHI_FIELD_SURFACE;
+constexpr uint32_t reading = SudachiSUDACHI_FIELD_READING;
+constexpr uint32_t dictionary_form = SudachiSUDACHI_FIELD_DICTIONARY_FORM;
+constexpr uint32_t normalized_form = SudachiSUDACHI_FIELD_NORMALIZED_FORM;
+constexpr uint32_t pos = SudachiSUDACHI_FIELD_POS;
+constexpr uint32_t dictionary_reading = SudachiSUDACHI_FIELD_DICTIONARY_READING;
+constexpr uint32_t lemma_key = SudachiSUDACHI_FIELD_LEMMA_KEY;
+constexpr uint32_t all = SudachiSUDACHI_FIELD_ALL;
+}  // namespace field
+
+/// Thrown when the library returns NULL
+class Error : public std::runtime_error {
+public:
+    using std::runtime_error::runtime_error;
+};
+
+namespace detail {
+
+inline std::string_view view(const char* s) {
+    return s ? std::string_view(s) : std::string_view();
+}
+
+inline c::SudachiSudachiTokenMode mode(Mode m) {
+    return static_cast<c::SudachiSudachiTokenMode>(m);
+}
+
+/// Move-only owner of a library-allocated array of count elements
+template <typename T, void (*Free)(T*, uintptr_t)>
+class Array {
+public:
+    Array() = default;
+    Array(T* items, size_t count) : items_(items), count_(count) {}
+    Array(Array&& other) noexcept
+        : items_(std::exchange(other.items_, nullptr)), count_(std::exchange(other.count_, 0)) {}
+    Array& operator=(Array&& other) noexcept {
+        if (this != &other) {
+            reset();
+            items_ = std::exchange(other.items_, nullptr);
+            count_ = std::exchange(other.count_, 0);
+        }
+        return *this;
+    }
+    Array(const Array&) = delete;
+    Array& operator=(const Array&) = delete;
+    ~Array() { reset(); }
+
+    size_t size() const { return count_; }
+    bool empty() const { return count_ == 0; }
+
+protected:
+    T* items_ = nullptr;
+    size_t count_ = 0;
+
+private:
+    void reset() {
+        if (items_) {
+            Free(items_, count_);
+            items_ = nullptr;
+            count_ = 0;
+        }
+    }
+};
+
+/// Forward iterator producing a view per element
+template <typename Owner, typename View>
+class Iterator {
+public:
+    Iterator(const Owner* owner, size_t i) : owner_(owner), i_(i) {}
+    View operator*() const { return (*owner_)[i_]; }
+    Iterator& operator++() {
+        ++i_;
+        return *this;
+    }
+    bool operator!=(const Iterator& other) const { return i_ != other.i_; }
+    bool operator==(const Iterator& other) const { return i_ == other.i_; }
+
+private:
+    const Owner* owner_;
+    size_t i_;
+};
+
+}  // namespace detail
+
+/// Borrowed view of one token; fields not requested are empty
+class Token {
+public:
+    explicit Token(const c::SudachiSudachiToken* token) : token_(token) {}
+
+    std::string_view surface() const { return detail::view(token_->surface); }
+    std::string_view reading() const { return detail::view(token_->reading); }
+    std::string_view dictionary_form() const { return detail::view(token_->dictionary_form); }
+    std::string_view dictionary_reading() const { return detail::view(token_->dictionary_reading); }
+    std::string_view normalized_form() const { return detail::view(token_->normalized_form); }
+    /// POS tags as a JSON array string
+    std::string_view pos_json() const { return detail::view(token_->pos); }
+    /// Byte offsets into the UTF-8 input
+    size_t begin() const { return static_cast<size_t>(token_->begin); }
+    size_t end() const { return static_cast<size_t>(token_->end); }
+    uint32_t word_id() const { return token_->word_id; }
+    uint16_t pos_id() const { return token_->pos_id; }
+    /// Stable across dictionary rebuilds; 0 for foreign runs
+    uint64_t lemma_key() const { return token_->lemma_key; }
+    c::SudachiSudachiScript script() const { return token_->script; }
+    c::SudachiSudachiTokenClass token_class() const { return token_->class_; }
+    uint8_t flags() const { return token_->flags; }
+
+private:
+    const c::SudachiSudachiToken* token_;
+};
+
+/// Owned token array (sudachi_tokenize, sudachi_lookup)
+class Tokens : public detail::Array<c::SudachiSudachiToken*, c::sudachi_free_tokens> {
+public:
+    using Array::Array;
+    Token operator[](size_t i) const { return Token(items_[i]); }
+    detail::Iterator<Tokens, Token> begin() const { return {this, 0}; }
+    detail::Iterator<Tokens, Token> end() const { return {this, count_}; }
+};
+
+/// Borrowed view of one furigana segment; ruby() is empty where none is needed
+class Ruby {
+public:
+    explicit Ruby(const c::SudachiSudachiRuby* ruby) : ruby_(ruby) {}
+
+    std::string_view text() const { return detail::view(ruby_->text); }
+    std::string_view ruby() const { return detail::view(ruby_->ruby); }
+    bool has_ruby() const { return ruby_->ruby != nullptr; }
+    size_t begin() const { return static_cast<size_t>(ruby_->begin); }
+    size_t end() const { return static_cast<size_t>(ruby_->end); }
+
+private:
+    const c::SudachiSudachiRuby* ruby_;
+};
+
+/// Owned furigana segments (sudachi_furigana)
+class Furigana : public detail::Array<c::SudachiSudachiRuby, c::sudachi_free_furigana> {
+public:
+    using Array::Array;
+    Ruby operator[](size_t i) const { return Ruby(&items_[i]); }
+    detail::Iterator<Furigana, Ruby> begin() const { return {this, 0}; }
+    detail::Iterator<Furigana, Ruby> end() const { return {this, count_}; }
+};
+
+/// Borrowed view of one deinflected word
+class Deinflection {
+public:
+    explicit Deinflection(const c::SudachiSudachiDeinflection* word) : word_(word) {}
+
+    std::string_view surface() const { return detail::view(word_->surface); }
+    std::string_view dictionary_form() const { return detail::view(word_->dictionary_form); }
+    std::string_view conjugation_type() const { return detail::view(word_->conjugation_type); }
+    std::string_view conjugation_form() const { return detail::view(word_->conjugation_form); }
+    size_t begin() const { return static_cast<size_t>(word_->begin); }
+    size_t end() const { return static_cast<size_t>(word_->end); }
+
+private:
+    const c::SudachiSudachiDeinflection* word_;
+};
+
+/// Owned deinflected words (sudachi_deinflect)
+class Deinflections
+    : public detail::Array<c::SudachiSudachiDeinflection, c::sudachi_free_deinflections> {
+public:
+    using Array::Array;
+    Deinflection operator[](size_t i) const { return Deinflection(&items_[i]); }
+    detail::Iterator<Deinflections, Deinflection> begin() const { return {this, 0}; }
+    detail::Iterator<Deinflections, Deinflection> end() const { return {this, count_}; }
+};
+
+/// Owning tokenizer handle
+/// Calls on one handle are serialized by the library.
+class Tokenizer {
+public:
+    explicit Tokenizer(const std::string& dict_path) : handle_(c::sudachi_init(dict_path.c_str())) {
+        if (!handle_) {
+            throw Error("failed to load dictionary: " + dict_path);
+        }
+    }
+    Tokenizer(Tokenizer&& other) noexcept : handle_(std::exchange(other.handle_, nullptr)) {}
+    Tokenizer& operator=(Tokenizer&& other) noexcept {
+        if (this != &other) {
+            c::sudachi_free_tokenizer(handle_);
+            handle_ = std::exchange(other.handle_, nullptr);
+        }
+        return *this;
+    }
+    Tokenizer(const Tokenizer&) = delete;
+    Tokenizer& operator=(const Tokenizer&) = delete;
+    ~Tokenizer() { c::sudachi_free_tokenizer(handle_); }
+
+    Tokens tokenize(std::string_view text, Mode mode = Mode::C, uint32_t fields = field::all) const {
+        std::string input(text);
+        uintptr_t count = 0;
+        auto tokens = c::sudachi_tokenize_fields(handle_, input.c_str(), detail::mode(mode), fields, &count);
+        if (!tokens) {
+            throw Error("tokenize failed");
+        }
+        return Tokens(tokens, count);
+    }
+
+    /// Dictionary entries whose surface is exactly word
+    Tokens lookup(std::string_view word) const {
+        std::string input(word);
+        uintptr_t count = 0;
+        auto tokens = c::sudachi_lookup(handle_, input.c_str(), &count);
+        if (!tokens) {
+            throw Error("lookup failed");
+        }
+        return Tokens(tokens, count);
+    }
+
+    Furigana furigana(std::string_view text, Mode mode = Mode::C) const {
+        std::string input(text);
+        uintptr_t count = 0;
+        auto ruby = c::sudachi_furigana(handle_, input.c_str(), detail::mode(mode), &count);
+        if (!ruby) {
+            throw Error("furigana failed");
+        }
+        return Furigana(ruby, count);
+    }
+
+    Deinflections deinflect(std::string_view text, Mode mode = Mode::C) const {
+        std::string input(text);
+        uintptr_t count = 0;
+        auto words = c::sudachi_deinflect(handle_, input.c_str(), detail::mode(mode), &count);
+        if (!words) {
+            throw Error("deinflect failed");
+        }
+        return Deinflections(words, count);
+    }
+
+    /// Token boundaries: every token begin followed by the last end
+    std::vector<uint32_t> wakati(std::string_view text, Mode mode = Mode::C) const {
+        std::string input(text);
+        uintptr_t count = 0;
+        uint32_t* offsets = c::sudachi_wakati(handle_, input.c_str(), detail::mode(mode), &count);
+        if (!offsets) {
+            throw Error("wakati failed");
+        }
+        std::vector<uint32_t> result(offsets, offsets + count);
+        c::sudachi_free_offsets(offsets, count);
+        return result;
+    }
+
+    /// Borrow the C handle for calls not wrapped here
+    c::SudachiSudachiTokenizer* get() const { return handle_; }
+
+private:
+    c::SudachiSudachiTokenizer* handle_;
+};
+
+}  // namespace sudachi
+
+#endif  // SUDACHI_HPP
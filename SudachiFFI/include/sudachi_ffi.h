@@ -0,0 +1,2818 @@
+#ifndef SUDACHI_FFI_H
+#define SUDACHI_FFI_H
+
+/* Warning: This file is auto-generated by cbindgen. Do not modify. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+namespace sudachi_ios {
+#endif  // __cplusplus
+
+/**
+ * An iteration mark (々, ゝ, ヽ, ゞ, ヾ) was resolved against the previous word
+ */
+#define SudachiSUDACHI_TOKEN_ITERATION (1 << 0)
+
+/**
+ * Unknown to the dictionary (an OOV word)
+ */
+#define SudachiSUDACHI_TOKEN_OOV (1 << 1)
+
+/**
+ * Reading or segmentation pinned by sudachi_set_overrides
+ */
+#define SudachiSUDACHI_TOKEN_OVERRIDE (1 << 2)
+
+/**
+ * A verb joined with the auxiliary verb after it (食べ始める)
+ */
+#define SudachiSUDACHI_TOKEN_COMPOUND (1 << 3)
+
+/**
+ * Covers a character fixed by OCR post-correction (sudachi_set_ocr_correction)
+ */
+#define SudachiSUDACHI_TOKEN_OCR (1 << 4)
+
+/**
+ * Reading guessed one kanji at a time for an unknown word
+ * (SUDACHI_READING_OOV_KANJI); low confidence
+ */
+#define SudachiSUDACHI_TOKEN_GUESSED (1 << 5)
+
+#define SudachiSUDACHI_FIELD_SURFACE (1 << 0)
+
+#define SudachiSUDACHI_FIELD_READING (1 << 1)
+
+#define SudachiSUDACHI_FIELD_DICTIONARY_FORM (1 << 2)
+
+#define SudachiSUDACHI_FIELD_NORMALIZED_FORM (1 << 3)
+
+#define SudachiSUDACHI_FIELD_POS (1 << 4)
+
+#define SudachiSUDACHI_FIELD_DICTIONARY_READING (1 << 5)
+
+/**
+ * Not a string: fills SudachiToken.lemma_key
+ */
+#define SudachiSUDACHI_FIELD_LEMMA_KEY (1 << 6)
+
+#define SudachiSUDACHI_FIELD_ALL ((((((SudachiSUDACHI_FIELD_SURFACE | SudachiSUDACHI_FIELD_READING) | SudachiSUDACHI_FIELD_DICTIONARY_FORM) | SudachiSUDACHI_FIELD_NORMALIZED_FORM) | SudachiSUDACHI_FIELD_POS) | SudachiSUDACHI_FIELD_DICTIONARY_READING) | SudachiSUDACHI_FIELD_LEMMA_KEY)
+
+/**
+ * Longest text in bytes the library accepts
+ * Offsets in results are i32 (u32 in packed results), so longer texts are
+ * rejected up front instead of producing wrapped offsets.
+ */
+#define SudachiSUDACHI_MAX_TEXT_BYTES (uintptr_t)INT32_MAX
+
+/**
+ * Revision of the C interface
+ * Bump whenever a struct layout or function signature changes incompatibly:
+ *   1 - initial token layout
+ *   2 - SudachiToken gained word_id and pos_id
+ *   3 - SudachiToken gained script
+ *   4 - SudachiToken gained class
+ *   5 - SudachiToken gained flags
+ *   6 - SudachiToken gained dictionary_reading, SudachiPackedToken and
+ *       SudachiColumns a dictionary reading string
+ *   7 - SudachiPackedHeader gained fingerprint, sudachi_cache_put the
+ *       producing tokenizer
+ *   8 - SudachiToken, SudachiPackedToken and SudachiColumns gained a lemma
+ *       key
+ *   9 - SudachiToken gained inline storage for surface and reading
+ */
+#define SudachiSUDACHI_ABI_VERSION 9
+
+/**
+ * Default chunk size of a handle in bytes
+ */
+#define SudachiSUDACHI_DEFAULT_CHUNK_BYTES (32 * 1024)
+
+/**
+ * Number of string index entries per token:
+ * surface, reading, dictionary form, normalized form, POS (JSON),
+ * dictionary reading
+ */
+#define SudachiSUDACHI_COLUMN_STRINGS 6
+
+/**
+ * Remove the spaces of letter-spaced runs (ス　ペ　ー　ス -> スペース)
+ */
+#define SudachiSUDACHI_NORMALIZE_LETTER_SPACING (1 << 2)
+
+/**
+ * Collapse emphatic repetition (すごーーーい -> すごーい, ！！！ -> ！)
+ */
+#define SudachiSUDACHI_NORMALIZE_REPETITION (1 << 3)
+
+/**
+ * Read a wave dash after kana as a long vowel (ね〜 -> ねー)
+ */
+#define SudachiSUDACHI_NORMALIZE_WAVE_DASH (1 << 4)
+
+/**
+ * All dialogue flags
+ */
+#define SudachiSUDACHI_NORMALIZE_DIALOGUE ((SudachiSUDACHI_NORMALIZE_LETTER_SPACING | SudachiSUDACHI_NORMALIZE_REPETITION) | SudachiSUDACHI_NORMALIZE_WAVE_DASH)
+
+/**
+ * Most user dictionaries a word id can address
+ */
+#define SudachiSUDACHI_MAX_USER_DICTIONARIES 14
+
+/**
+ * Size of the inline buffers of a token, including the NUL
+ */
+#define SudachiSUDACHI_INLINE_BYTES 24
+
+/**
+ * Default share of foreign letters at which a line is skipped
+ */
+#define SudachiSUDACHI_DEFAULT_LANGUAGE_GATE 0.9
+
+/**
+ * word_id of opaque tokens (their pos_id is 0xFFFF and POS is [])
+ */
+#define SudachiSUDACHI_OPAQUE_WORD_ID UINT32_MAX
+
+#define SudachiSUDACHI_LAYOUT_TOKEN 0
+
+#define SudachiSUDACHI_LAYOUT_OPTIONS 1
+
+#define SudachiSUDACHI_LAYOUT_INIT_OPTIONS 2
+
+#define SudachiSUDACHI_LAYOUT_PACKED_HEADER 3
+
+#define SudachiSUDACHI_LAYOUT_PACKED_TOKEN 4
+
+#define SudachiSUDACHI_LAYOUT_STR_REF 5
+
+#define SudachiSUDACHI_LAYOUT_FINGERPRINT 6
+
+#define SudachiSUDACHI_LAYOUT_COLUMNS 7
+
+#define SudachiSUDACHI_LAYOUT_CONTEXT_RESULT 8
+
+#define SudachiSUDACHI_LAYOUT_BYTE_RANGE 9
+
+#define SudachiSUDACHI_LAYOUT_RUBY 10
+
+#define SudachiSUDACHI_LAYOUT_DEINFLECTION 11
+
+#define SudachiSUDACHI_LAYOUT_BUNSETSU 12
+
+#define SudachiSUDACHI_LAYOUT_OVERRIDE 13
+
+#define SudachiSUDACHI_LAYOUT_CORRECTION 14
+
+#define SudachiSUDACHI_LAYOUT_OCR_CORRECTION 15
+
+#define SudachiSUDACHI_LAYOUT_NAME_READING 16
+
+#define SudachiSUDACHI_LAYOUT_SPEAKER_STATS 17
+
+#define SudachiSUDACHI_LAYOUT_SPEAKER_TERM 18
+
+#define SudachiSUDACHI_LAYOUT_STATS 19
+
+#define SudachiSUDACHI_LAYOUT_CORPUS_FREQUENCY 20
+
+#define SudachiSUDACHI_LAYOUT_CORPUS_TOTALS 21
+
+#define SudachiSUDACHI_LAYOUT_TOKEN_DIFF 22
+
+#define SudachiSUDACHI_LAYOUT_OCR_LINE 23
+
+#define SudachiSUDACHI_LAYOUT_ANALYSIS_STAMP 24
+
+#define SudachiSUDACHI_LAYOUT_PROGRESS_INFO 25
+
+#define SudachiSUDACHI_LAYOUT_TOKEN_INFO 26
+
+#define SudachiSUDACHI_LAYOUT_CONVERSION 27
+
+#define SudachiSUDACHI_LAYOUT_BUILD_PROGRESS 28
+
+#define SudachiSUDACHI_LAYOUT_GRAMMAR_INFO 29
+
+#define SudachiSUDACHI_LAYOUT_WORD_PARAM 30
+
+#define SudachiSUDACHI_LAYOUT_PATH_WORD 31
+
+#define SudachiSUDACHI_LAYOUT_PATH 32
+
+/**
+ * Number of SUDACHI_LAYOUT_* indices
+ */
+#define SudachiSUDACHI_LAYOUT_COUNT 33
+
+/**
+ * Version of SudachiOptions this library was built with
+ */
+#define SudachiSUDACHI_OPTIONS_VERSION 1
+
+/**
+ * Merge foreign script runs (sudachi_set_pass_through)
+ */
+#define SudachiSUDACHI_OPTION_PASS_THROUGH (1 << 0)
+
+/**
+ * Merge sound effects (sudachi_set_sfx)
+ */
+#define SudachiSUDACHI_OPTION_SFX (1 << 1)
+
+/**
+ * Join compound verbs (sudachi_set_compound_verbs)
+ */
+#define SudachiSUDACHI_OPTION_COMPOUND_VERBS (1 << 2)
+
+/**
+ * Fix OCR look-alikes (sudachi_set_ocr_correction)
+ */
+#define SudachiSUDACHI_OPTION_OCR (1 << 3)
+
+/**
+ * Keep whitespace runs as tokens (sudachi_set_preserve_whitespace)
+ */
+#define SudachiSUDACHI_OPTION_PRESERVE_WHITESPACE (1 << 4)
+
+/**
+ * Length value marking an absent string
+ */
+#define SudachiSUDACHI_PACKED_NULL UINT32_MAX
+
+/**
+ * Tokens per page when page_size is 0
+ */
+#define SudachiSUDACHI_DEFAULT_PAGE_TOKENS 4096
+
+/**
+ * Spell long vowels out: オー -> オウ, ネー -> ネイ, カー -> カア
+ */
+#define SudachiSUDACHI_READING_EXPAND_LONG_VOWELS (1 << 0)
+
+/**
+ * Write long vowels with ー: オウ -> オー, ネイ -> ネー (ignored when expanding)
+ */
+#define SudachiSUDACHI_READING_COLLAPSE_LONG_VOWELS (1 << 1)
+
+/**
+ * Replace iteration marks with the kana they repeat: ススム, スゞキ
+ */
+#define SudachiSUDACHI_READING_ITERATION_MARKS (1 << 2)
+
+/**
+ * Turn stretches of small vowels and ー into one ー: アァァ -> アー
+ */
+#define SudachiSUDACHI_READING_SMALL_KANA_STRETCH (1 << 3)
+
+/**
+ * Read unknown words written in kana as themselves, in katakana (oov.rs)
+ */
+#define SudachiSUDACHI_READING_OOV_KANA (1 << 4)
+
+/**
+ * Guess the reading of unknown words with kanji one kanji at a time,
+ * flagging them SUDACHI_TOKEN_GUESSED (oov.rs)
+ */
+#define SudachiSUDACHI_READING_OOV_KANJI (1 << 5)
+
+/**
+ * Most paths a callback can be given per chunk
+ */
+#define SudachiSUDACHI_MAX_PATHS 16
+
+/**
+ * Score that rules a path out
+ */
+#define SudachiSUDACHI_PATH_VETO INT64_MAX
+
+/**
+ * Never decode synonym group ids (TSV_ALL output prints them as [])
+ */
+#define SudachiSUDACHI_INIT_SKIP_SYNONYMS (1 << 0)
+
+/**
+ * Map the dictionary for random access: pages are faulted in one at a time
+ * as entries are read, with no readahead
+ */
+#define SudachiSUDACHI_INIT_RANDOM_ACCESS (1 << 1)
+
+/**
+ * Read the whole dictionary into memory instead of mapping the file, for
+ * environments that evict file-backed pages aggressively (mapping advice is
+ * then ignored)
+ */
+#define SudachiSUDACHI_INIT_COPY_TO_RAM (1 << 2)
+
+/**
+ * Line breaks are wrapping, not sentence boundaries (speech bubbles)
+ */
+#define SudachiSUDACHI_SENTENCE_SOFT_BREAKS (1 << 0)
+
+/**
+ * Revision of the analysis code
+ * Bumped whenever a change in this library, or in the Sudachi revision it
+ * builds against, can change the tokens of the same inputs.
+ */
+#define SudachiSUDACHI_ENGINE_VERSION 1
+
+/**
+ * Map entry for characters not covered by any token
+ */
+#define SudachiSUDACHI_TOKEN_MAP_NONE UINT32_MAX
+
+/**
+ * Replace invalid sequences with U+FFFD instead of failing
+ */
+#define SudachiSUDACHI_INPUT_LOSSY (1 << 0)
+
+#define SudachiSUDACHI_NORMALIZE_HALFWIDTH_KANA (1 << 0)
+
+#define SudachiSUDACHI_NORMALIZE_FULLWIDTH_ASCII (1 << 1)
+
+/**
+ * Script of a token, as a language hint
+ */
+typedef enum SudachiSudachiScript {
+  /**
+   * Japanese, digits, symbols or a mix of scripts
+   */
+  SudachiSudachiScript_Japanese = 0,
+  /**
+   * Latin letters, including fullwidth and accented forms
+   */
+  SudachiSudachiScript_Latin = 1,
+  SudachiSudachiScript_Cyrillic = 2,
+  /**
+   * Hangul syllables and jamo
+   */
+  SudachiSudachiScript_Hangul = 3,
+} SudachiSudachiScript;
+
+/**
+ * Broad class of a token
+ */
+typedef enum SudachiSudachiTokenClass {
+  /**
+   * Words, numbers and anything mixed
+   */
+  SudachiSudachiTokenClass_Word = 0,
+  /**
+   * Punctuation and symbols only
+   */
+  SudachiSudachiTokenClass_Symbol = 1,
+  /**
+   * Emoji (including ZWJ, flag and keycap sequences) and kaomoji
+   */
+  SudachiSudachiTokenClass_Emoji = 2,
+  /**
+   * Not analyzed (a line skipped by the language gate, or uncovered text)
+   */
+  SudachiSudachiTokenClass_Opaque = 3,
+  /**
+   * Spaces, tabs and line breaks only
+   */
+  SudachiSudachiTokenClass_Whitespace = 4,
+  /**
+   * Onomatopoeia, in SFX mode
+   */
+  SudachiSudachiTokenClass_Sfx = 5,
+} SudachiSudachiTokenClass;
+
+/**
+ * Segmentation granularity
+ */
+typedef enum SudachiSudachiTokenMode {
+  /**
+   * Short units
+   */
+  A = 0,
+  /**
+   * Medium units (default)
+   */
+  B = 1,
+  /**
+   * Long units
+   */
+  C = 2,
+} SudachiSudachiTokenMode;
+
+/**
+ * Stage of a dictionary build
+ */
+typedef enum SudachiSudachiBuildStage {
+  /**
+   * Reading the connection matrix
+   */
+  SudachiSudachiBuildStage_Matrix = 0,
+  /**
+   * Reading the lexicon CSVs
+   */
+  SudachiSudachiBuildStage_Lexicon = 1,
+  /**
+   * Resolving references between entries
+   */
+  SudachiSudachiBuildStage_Resolve = 2,
+  /**
+   * Compiling the dictionary image
+   */
+  SudachiSudachiBuildStage_Compile = 3,
+  /**
+   * Writing the output file
+   */
+  SudachiSudachiBuildStage_Write = 4,
+} SudachiSudachiBuildStage;
+
+/**
+ * Kind of difference between two token streams
+ */
+typedef enum SudachiSudachiDiffKind {
+  /**
+   * Only in the first result
+   */
+  SudachiSudachiDiffKind_Removed = 0,
+  /**
+   * Only in the second result
+   */
+  SudachiSudachiDiffKind_Inserted = 1,
+  /**
+   * Same range in both, with a different reading, form, POS or id
+   */
+  SudachiSudachiDiffKind_Changed = 2,
+} SudachiSudachiDiffKind;
+
+/**
+ * File format of an export
+ */
+typedef enum SudachiSudachiExportFormat {
+  /**
+   * Comma-separated, RFC 4180 quoting
+   */
+  SudachiSudachiExportFormat_Csv = 0,
+  /**
+   * Tab-separated, unquoted
+   */
+  SudachiSudachiExportFormat_Tsv = 1,
+} SudachiSudachiExportFormat;
+
+/**
+ * Text output format for sudachi_format
+ */
+typedef enum SudachiSudachiOutputFormat {
+  /**
+   * Surface, POS, normalized form (sudachi default)
+   */
+  Tsv = 0,
+  /**
+   * Adds dictionary form, reading, dictionary id, synonyms, OOV (sudachi -a)
+   */
+  TsvAll = 1,
+  /**
+   * Surfaces separated by spaces (sudachi -w / mecab -Owakati)
+   */
+  Wakati = 2,
+  /**
+   * CoNLL-U with UPOS and display-unit pseudo-heads
+   */
+  Conllu = 3,
+} SudachiSudachiOutputFormat;
+
+/**
+ * Access pattern hint for a region of the dictionary map
+ */
+typedef enum SudachiSudachiAdvice {
+  /**
+   * Leave the kernel default
+   */
+  SudachiSudachiAdvice_Normal = 0,
+  /**
+   * Fault in only the pages read, with no readahead
+   */
+  SudachiSudachiAdvice_Random = 1,
+  /**
+   * Read ahead aggressively
+   */
+  SudachiSudachiAdvice_Sequential = 2,
+  /**
+   * Start reading the whole region in now
+   */
+  SudachiSudachiAdvice_WillNeed = 3,
+} SudachiSudachiAdvice;
+
+/**
+ * Priority of a queued job
+ */
+typedef enum SudachiSudachiQueuePriority {
+  /**
+   * Work the user is not waiting for (QOS_CLASS_BACKGROUND)
+   */
+  SudachiSudachiQueuePriority_Background = 0,
+  /**
+   * Work with visible progress (QOS_CLASS_UTILITY)
+   */
+  SudachiSudachiQueuePriority_Utility = 1,
+  /**
+   * Work the user is waiting for (QOS_CLASS_USER_INITIATED)
+   */
+  SudachiSudachiQueuePriority_UserInitiated = 2,
+} SudachiSudachiQueuePriority;
+
+/**
+ * Ranked list of a speaker
+ */
+typedef enum SudachiSudachiSpeakerList {
+  /**
+   * Sentence endings (だろ, ですよね)
+   */
+  SudachiSudachiSpeakerList_Endings = 0,
+  /**
+   * Pronouns (俺, あんた)
+   */
+  SudachiSudachiSpeakerList_Pronouns = 1,
+} SudachiSudachiSpeakerList;
+
+/**
+ * Result of a status-returning call
+ */
+typedef enum SudachiSudachiStatus {
+  SudachiSudachiStatus_Ok = 0,
+  /**
+   * A required pointer was NULL or an argument was out of range
+   */
+  SudachiSudachiStatus_InvalidArgument = 1,
+  /**
+   * Text or path was not valid UTF-8
+   */
+  SudachiSudachiStatus_InvalidUtf8 = 2,
+  /**
+   * A result buffer could not be allocated
+   */
+  SudachiSudachiStatus_OutOfMemory = 3,
+  /**
+   * The dictionary could not be opened or is malformed
+   */
+  SudachiSudachiStatus_DictionaryError = 4,
+  /**
+   * Analysis failed inside Sudachi
+   */
+  SudachiSudachiStatus_AnalysisError = 5,
+  /**
+   * The library panicked; the handle should not be used again
+   * Only reported by builds with panic = "unwind" (release builds abort)
+   */
+  SudachiSudachiStatus_InternalPanic = 6,
+  /**
+   * A file could not be opened or read
+   */
+  SudachiSudachiStatus_IoError = 7,
+  /**
+   * Text longer than SUDACHI_MAX_TEXT_BYTES, whose offsets would overflow
+   */
+  SudachiSudachiStatus_TextTooLong = 8,
+} SudachiSudachiStatus;
+
+/**
+ * Index unit of a token map
+ */
+typedef enum SudachiSudachiTextUnit {
+  /**
+   * Unicode scalar values (Swift Character indices differ for clusters)
+   */
+  SudachiSudachiTextUnit_Char = 0,
+  /**
+   * UTF-16 code units, as used by NSString and NSRange
+   */
+  SudachiSudachiTextUnit_Utf16 = 1,
+} SudachiSudachiTextUnit;
+
+#if defined(SUDACHI_TRACING)
+/**
+ * Span boundary reported to a trace callback
+ */
+typedef enum SudachiSudachiTracePhase {
+#if defined(SUDACHI_TRACING)
+  SudachiSudachiTracePhase_Begin = 0,
+#endif
+#if defined(SUDACHI_TRACING)
+  SudachiSudachiTracePhase_End = 1,
+#endif
+} SudachiSudachiTracePhase;
+#endif
+
+/**
+ * Opaque handle to an analysis cache file
+ */
+typedef struct SudachiSudachiCache SudachiSudachiCache;
+
+/**
+ * Opaque handle to reusable analysis buffers
+ * Not thread-safe: use one context per thread or serial queue.
+ */
+typedef struct SudachiSudachiContext SudachiSudachiContext;
+
+/**
+ * Conversion table loaded with sudachi_converter_open
+ */
+typedef struct SudachiSudachiConverter SudachiSudachiConverter;
+
+/**
+ * Opaque handle to corpus frequency tables
+ */
+typedef struct SudachiSudachiCorpus SudachiSudachiCorpus;
+
+/**
+ * Correction store created with sudachi_corrections_create
+ */
+typedef struct SudachiSudachiCorrections SudachiSudachiCorrections;
+
+/**
+ * Opaque handle to a token table being written
+ */
+typedef struct SudachiSudachiExport SudachiSudachiExport;
+
+/**
+ * Level table loaded with sudachi_levels_open
+ */
+typedef struct SudachiSudachiLevels SudachiSudachiLevels;
+
+/**
+ * Name table loaded with sudachi_names_open
+ */
+typedef struct SudachiSudachiNames SudachiSudachiNames;
+
+/**
+ * Opaque handle to a tokenization read page by page
+ */
+typedef struct SudachiSudachiPagedResult SudachiSudachiPagedResult;
+
+/**
+ * Opaque handle to a tokenizer pool
+ */
+typedef struct SudachiSudachiPool SudachiSudachiPool;
+
+/**
+ * Opaque handle to batch progress reporting and control
+ */
+typedef struct SudachiSudachiProgress SudachiSudachiProgress;
+
+/**
+ * Opaque handle to an analysis queue
+ */
+typedef struct SudachiSudachiQueue SudachiSudachiQueue;
+
+/**
+ * Opaque handle to the tokens of one analysis
+ */
+typedef struct SudachiSudachiResult SudachiSudachiResult;
+
+/**
+ * Opaque handle to per-speaker statistics
+ */
+typedef struct SudachiSudachiSpeakers SudachiSudachiSpeakers;
+
+/**
+ * Tokenizer handle
+ * Created by sudachi_init and owned by the caller until released with
+ * sudachi_free_tokenizer. Calls on one handle are serialized internally.
+ */
+typedef struct SudachiSudachiTokenizer SudachiSudachiTokenizer;
+
+/**
+ * Opaque handle to a dictionary usage profile
+ */
+typedef struct SudachiSudachiUsage SudachiSudachiUsage;
+
+/**
+ * Headword index loaded with sudachi_word_index_open
+ */
+typedef struct SudachiSudachiWordIndex SudachiSudachiWordIndex;
+
+/**
+ * A single morpheme
+ * Tokens and their strings are allocated by the library; free a token with
+ * sudachi_free_token or a whole array with sudachi_free_tokens, never with
+ * free(). Offsets are byte offsets into the UTF-8 input. String fields are
+ * NULL when not requested or unavailable.
+ */
+typedef struct SudachiSudachiToken {
+  /**
+   * Surface form as it appears in the input
+   */
+  char *surface;
+  /**
+   * Reading in katakana
+   */
+  char *reading;
+  /**
+   * Dictionary (base) form
+   */
+  char *dictionary_form;
+  /**
+   * Normalized form
+   */
+  char *normalized_form;
+  /**
+   * POS tags as a JSON array string
+   */
+  char *pos;
+  /**
+   * Start byte offset in the input
+   */
+  int32_t begin;
+  /**
+   * End byte offset in the input (exclusive)
+   */
+  int32_t end;
+  /**
+   * Raw Sudachi word id (dictionary + index)
+   */
+  uint32_t word_id;
+  /**
+   * Index into the dictionary POS table
+   */
+  uint16_t pos_id;
+  /**
+   * Script of the surface, a hint for non-Japanese text
+   */
+  enum SudachiSudachiScript script;
+  /**
+   * Word, symbol, emoji or opaque
+   */
+  enum SudachiSudachiTokenClass class_;
+  /**
+   * Notes on how the token was formed (SUDACHI_TOKEN_* bits)
+   */
+  uint8_t flags;
+  /**
+   * Reading of the dictionary form in katakana
+   */
+  char *dictionary_reading;
+  /**
+   * Identifier of the lemma that stays the same across dictionary
+   * rebuilds, 0 if not requested or not a word (see sudachi_lemma_key)
+   */
+  uint64_t lemma_key;
+  /**
+   * Storage of a short surface; read the surface field, not this
+   */
+  char inline_surface[SudachiSUDACHI_INLINE_BYTES];
+  /**
+   * Storage of a short reading; read the reading field, not this
+   */
+  char inline_reading[SudachiSUDACHI_INLINE_BYTES];
+} SudachiSudachiToken;
+
+/**
+ * Allocate size bytes, like malloc (at least 16-byte aligned)
+ */
+typedef void *(*SudachiSudachiMallocFn)(uintptr_t size);
+
+/**
+ * Free a block from the matching SudachiMallocFn, like free
+ */
+typedef void (*SudachiSudachiFreeFn)(void *ptr);
+
+/**
+ * Progress of a dictionary build
+ */
+typedef struct SudachiSudachiBuildProgress {
+  enum SudachiSudachiBuildStage stage;
+  /**
+   * Lexicon CSVs read
+   */
+  uintptr_t files_done;
+  uintptr_t files_total;
+  /**
+   * Entries read from the CSVs so far
+   */
+  uintptr_t entries;
+} SudachiSudachiBuildProgress;
+
+/**
+ * Receives build progress on the thread running the build
+ */
+typedef void (*SudachiSudachiBuildCallback)(void *user_data, const struct SudachiSudachiBuildProgress *progress);
+
+/**
+ * Display unit
+ * surface and reading are owned by the result array.
+ */
+typedef struct SudachiSudachiBunsetsu {
+  char *surface;
+  /**
+   * Readings of the tokens joined, in katakana
+   */
+  char *reading;
+  /**
+   * Byte offsets into the input
+   */
+  int32_t begin;
+  int32_t end;
+  /**
+   * Index of the first token among the tokens of the same analysis, and
+   * number of tokens in the unit
+   */
+  uint32_t first_token;
+  uint32_t token_count;
+  /**
+   * Index of the unit this one modifies, or -1 (see README)
+   */
+  int32_t parent;
+} SudachiSudachiBunsetsu;
+
+/**
+ * Reference into the string area of a packed result
+ */
+typedef struct SudachiSudachiStrRef {
+  /**
+   * Byte offset from the start of the string area
+   */
+  uint32_t offset;
+  /**
+   * Byte length, or SUDACHI_PACKED_NULL if absent
+   */
+  uint32_t len;
+} SudachiSudachiStrRef;
+
+/**
+ * Struct-of-arrays result
+ * All arrays hold count entries and are owned by the result; free the whole
+ * result with sudachi_free_columns.
+ */
+typedef struct SudachiSudachiColumns {
+  uintptr_t count;
+  uint32_t *begins;
+  uint32_t *ends;
+  uint32_t *word_ids;
+  uint16_t *pos_ids;
+  /**
+   * 0 unless SUDACHI_FIELD_LEMMA_KEY is selected
+   */
+  uint64_t *lemma_keys;
+  /**
+   * String blob (strings are not NUL-terminated)
+   */
+  uint8_t *strings;
+  uintptr_t string_bytes;
+  /**
+   * count * SUDACHI_COLUMN_STRINGS references into strings
+   */
+  struct SudachiSudachiStrRef *string_index;
+} SudachiSudachiColumns;
+
+/**
+ * Options for one call of the *_options functions
+ * Zero-initialize, set version to SUDACHI_OPTIONS_VERSION, then set what
+ * differs from the defaults. A NULL pointer is the same as all zero.
+ */
+typedef struct SudachiSudachiOptions {
+  /**
+   * SUDACHI_OPTIONS_VERSION the caller was built with (0 reads as 1)
+   * Later versions only append fields.
+   */
+  uint32_t version;
+  /**
+   * Segmentation mode
+   */
+  enum SudachiSudachiTokenMode mode;
+  /**
+   * SUDACHI_FIELD_* bits to leave out (0 fills every field)
+   */
+  uint32_t omit_fields;
+  /**
+   * SUDACHI_NORMALIZE_* flags, added to the handle's
+   */
+  uint32_t normalization;
+  /**
+   * SUDACHI_READING_* flags, added to the handle's
+   */
+  uint32_t reading;
+  /**
+   * SUDACHI_OPTION_* toggles to switch on for this call
+   */
+  uint32_t enable;
+  /**
+   * SUDACHI_OPTION_* toggles to switch off; wins over enable
+   */
+  uint32_t disable;
+  /**
+   * Drop tokens of these classes (bit 1 << SudachiTokenClass)
+   */
+  uint32_t exclude_classes;
+  /**
+   * Drop tokens with any of these SUDACHI_TOKEN_* flags
+   */
+  uint32_t exclude_flags;
+  /**
+   * Fail on texts longer than this many bytes (0 for no limit)
+   */
+  uintptr_t max_bytes;
+  /**
+   * Return at most this many tokens (0 for no limit)
+   */
+  uintptr_t max_tokens;
+} SudachiSudachiOptions;
+
+/**
+ * Fixed-size token record of a packed result
+ */
+typedef struct SudachiSudachiPackedToken {
+  uint32_t begin;
+  uint32_t end;
+  uint32_t word_id;
+  uint16_t pos_id;
+  uint16_t reserved;
+  struct SudachiSudachiStrRef surface;
+  struct SudachiSudachiStrRef reading;
+  struct SudachiSudachiStrRef dictionary_form;
+  struct SudachiSudachiStrRef normalized_form;
+  /**
+   * POS tags as a JSON array string
+   */
+  struct SudachiSudachiStrRef pos;
+  struct SudachiSudachiStrRef dictionary_reading;
+  uint64_t lemma_key;
+} SudachiSudachiPackedToken;
+
+/**
+ * Result of sudachi_tokenize_context, owned by the context
+ * Valid until the next call on the context or sudachi_context_free.
+ */
+typedef struct SudachiSudachiContextResult {
+  /**
+   * Token records, as in a packed result
+   */
+  const struct SudachiSudachiPackedToken *tokens;
+  uintptr_t count;
+  /**
+   * String area addressed by the SudachiStrRef fields of the tokens
+   */
+  const uint8_t *strings;
+  uintptr_t string_bytes;
+} SudachiSudachiContextResult;
+
+/**
+ * Conversion candidate of a kana string
+ * text is owned by the result array.
+ */
+typedef struct SudachiSudachiConversion {
+  /**
+   * Converted text
+   */
+  char *text;
+  /**
+   * Total word and connection cost; lower is more likely
+   * Saturates at INT32_MAX for very long input.
+   */
+  int32_t cost;
+} SudachiSudachiConversion;
+
+/**
+ * Counts of one lemma across the corpus
+ */
+typedef struct SudachiSudachiCorpusFrequency {
+  /**
+   * Documents containing the lemma
+   */
+  uint64_t documents;
+  /**
+   * Occurrences in all documents
+   */
+  uint64_t occurrences;
+} SudachiSudachiCorpusFrequency;
+
+/**
+ * Totals of a corpus
+ */
+typedef struct SudachiSudachiCorpusTotals {
+  uint64_t documents;
+  /**
+   * Words counted in all documents
+   */
+  uint64_t tokens;
+  /**
+   * Distinct lemmas
+   */
+  uint64_t lemmas;
+} SudachiSudachiCorpusTotals;
+
+/**
+ * Correction recorded by the user
+ * surface and reading are '|'-separated per segment, as in SudachiOverride.
+ */
+typedef struct SudachiSudachiCorrection {
+  const char *surface;
+  const char *reading;
+  /**
+   * Segmentation and readings that analysis gave, '|'-separated, or NULL
+   */
+  const char *wrong_surface;
+  const char *wrong_reading;
+} SudachiSudachiCorrection;
+
+/**
+ * Deinflected word
+ * All strings are owned by the result array; the conjugation fields are
+ * NULL for words that do not inflect.
+ */
+typedef struct SudachiSudachiDeinflection {
+  char *surface;
+  char *dictionary_form;
+  char *conjugation_type;
+  char *conjugation_form;
+  /**
+   * Byte offsets into the input
+   */
+  int32_t begin;
+  int32_t end;
+} SudachiSudachiDeinflection;
+
+/**
+ * One difference; index_a and index_b are -1 where the token is absent
+ */
+typedef struct SudachiSudachiTokenDiff {
+  enum SudachiSudachiDiffKind kind;
+  int32_t index_a;
+  int32_t index_b;
+} SudachiSudachiTokenDiff;
+
+/**
+ * Identity of a dictionary image
+ * All zero for results stored without one, which match no dictionary.
+ */
+typedef struct SudachiSudachiFingerprint {
+  /**
+   * FNV-1a hash of the dictionary headers and image lengths
+   */
+  uint64_t hash;
+  /**
+   * Format version from the system dictionary header
+   */
+  uint64_t version;
+} SudachiSudachiFingerprint;
+
+/**
+ * Ruby segment
+ * text and ruby are owned by the result array; ruby is NULL for segments
+ * that need no reading (kana, punctuation, latin).
+ */
+typedef struct SudachiSudachiRuby {
+  char *text;
+  /**
+   * Hiragana reading, or NULL
+   */
+  char *ruby;
+  /**
+   * Byte offsets into the input
+   */
+  int32_t begin;
+  int32_t end;
+} SudachiSudachiRuby;
+
+/**
+ * Sizes of a dictionary's grammar
+ */
+typedef struct SudachiSudachiGrammarInfo {
+  /**
+   * Right ids a connection can start from (first index of the matrix)
+   */
+  uint32_t num_right_ids;
+  /**
+   * Left ids a connection can lead to (second index of the matrix)
+   */
+  uint32_t num_left_ids;
+  /**
+   * Entries of the POS table (valid pos_id values)
+   */
+  uint32_t pos_count;
+} SudachiSudachiGrammarInfo;
+
+/**
+ * Connection ids and cost of a dictionary entry
+ */
+typedef struct SudachiSudachiWordParam {
+  uint16_t left_id;
+  uint16_t right_id;
+  /**
+   * Word cost; lower is more likely
+   */
+  int16_t cost;
+} SudachiSudachiWordParam;
+
+/**
+ * Byte range in the UTF-8 input
+ */
+typedef struct SudachiSudachiByteRange {
+  int32_t begin;
+  int32_t end;
+} SudachiSudachiByteRange;
+
+/**
+ * Options for sudachi_init_with_options
+ * All zero is the same as sudachi_init.
+ */
+typedef struct SudachiSudachiInitOptions {
+  /**
+   * SUDACHI_INIT_* flags
+   */
+  uint32_t flags;
+  /**
+   * Advice for the connection matrix
+   */
+  enum SudachiSudachiAdvice matrix;
+  /**
+   * Advice for the trie
+   */
+  enum SudachiSudachiAdvice trie;
+  /**
+   * Advice for the word id table and word parameters
+   */
+  enum SudachiSudachiAdvice entries;
+  /**
+   * Advice for the word infos (forms, readings, splits)
+   */
+  enum SudachiSudachiAdvice word_infos;
+  /**
+   * Lock the connection matrix in memory
+   */
+  bool lock_matrix;
+  /**
+   * Threads to load the dictionary files with (0 or 1 loads on the
+   * calling thread)
+   */
+  uint32_t threads;
+} SudachiSudachiInitOptions;
+
+/**
+ * Candidate reading of a name
+ * reading is owned by the result array.
+ */
+typedef struct SudachiSudachiNameReading {
+  /**
+   * Reading in katakana
+   */
+  char *reading;
+  /**
+   * Share of the returned candidates (0 to 1), highest first
+   */
+  float score;
+} SudachiSudachiNameReading;
+
+/**
+ * One OCR correction
+ * original and corrected are owned by the result array.
+ */
+typedef struct SudachiSudachiOcrCorrection {
+  /**
+   * Byte offsets of the character in the input
+   */
+  int32_t begin;
+  int32_t end;
+  char *original;
+  char *corrected;
+} SudachiSudachiOcrCorrection;
+
+/**
+ * Pinned reading and segmentation of a surface
+ * surface and reading are '|'-separated per segment ("七つ|の|大罪" and
+ * "ナナツ|ノ|タイザイ"), with the same number of segments.
+ */
+typedef struct SudachiSudachiOverride {
+  const char *surface;
+  const char *reading;
+  /**
+   * Leading POS columns joined by ',' ("名詞,固有名詞") that analysis must
+   * assign, or NULL to match anywhere in the text before analysis
+   */
+  const char *pos;
+} SudachiSudachiOverride;
+
+/**
+ * Progress of a running batch
+ */
+typedef struct SudachiSudachiProgressInfo {
+  uintptr_t items_done;
+  uintptr_t items_total;
+  /**
+   * UTF-8 bytes of the texts done
+   */
+  uintptr_t bytes_done;
+  uintptr_t bytes_total;
+} SudachiSudachiProgressInfo;
+
+/**
+ * Receives progress after each text, on the worker thread that finished it
+ * Calls never overlap, and counts only grow within a batch.
+ */
+typedef void (*SudachiSudachiProgressCallback)(void *user_data, const struct SudachiSudachiProgressInfo *info);
+
+/**
+ * Receives the result of a job on the worker thread that ran it
+ * tokens is NULL (and count 0) if the analysis failed; otherwise the callback
+ * owns the array and must free it with sudachi_free_tokens.
+ */
+typedef void (*SudachiSudachiQueueCallback)(void *user_data, uint64_t job, struct SudachiSudachiToken **tokens, uintptr_t count);
+
+/**
+ * One line of OCR output
+ * Boxes use a top-left origin with y growing downward (flip Vision's
+ * normalized rects); any unit works as long as it is the same for all
+ * lines.
+ */
+typedef struct SudachiSudachiOcrLine {
+  /**
+   * UTF-8 text of the line
+   */
+  const char *text;
+  /**
+   * Text block the line belongs to; lines of different blocks are never
+   * joined
+   */
+  uint32_t block;
+  float x;
+  float y;
+  float width;
+  float height;
+  /**
+   * Set in vertical text; a block is vertical if any of its lines is
+   */
+  bool vertical;
+} SudachiSudachiOcrLine;
+
+/**
+ * A word of a candidate path
+ * Offsets are byte offsets into the text passed to the callback.
+ */
+typedef struct SudachiSudachiPathWord {
+  uintptr_t begin;
+  uintptr_t end;
+  uint32_t word_id;
+  uint16_t pos_id;
+} SudachiSudachiPathWord;
+
+/**
+ * A candidate segmentation of a chunk
+ */
+typedef struct SudachiSudachiPath {
+  const struct SudachiSudachiPathWord *words;
+  uintptr_t count;
+  /**
+   * Word and connection costs, unknown words counting 0
+   */
+  int64_t cost;
+} SudachiSudachiPath;
+
+/**
+ * Scores the candidate paths of a chunk
+ * text is the chunk (UTF-8, not NUL-terminated); scores holds count costs
+ * on entry and the callback's scores on return. Called on the analyzing
+ * thread; paths and text are valid only during the call.
+ */
+typedef void (*SudachiSudachiRerankCallback)(void *user_data, const uint8_t *text, uintptr_t text_len, const struct SudachiSudachiPath *paths, uintptr_t count, int64_t *scores);
+
+/**
+ * Numeric fields of a token of a SudachiResult (see SudachiToken)
+ */
+typedef struct SudachiSudachiTokenInfo {
+  /**
+   * Start byte offset in the input
+   */
+  int32_t begin;
+  /**
+   * End byte offset in the input (exclusive)
+   */
+  int32_t end;
+  /**
+   * Raw Sudachi word id (dictionary + index)
+   */
+  uint32_t word_id;
+  /**
+   * Index into the dictionary POS table
+   */
+  uint16_t pos_id;
+  /**
+   * Script of the surface, a hint for non-Japanese text
+   */
+  enum SudachiSudachiScript script;
+  /**
+   * Word, symbol, emoji or opaque
+   */
+  enum SudachiSudachiTokenClass class_;
+  /**
+   * Notes on how the token was formed (SUDACHI_TOKEN_* bits)
+   */
+  uint8_t flags;
+  /**
+   * Identifier of the lemma, 0 if not requested or not a word
+   */
+  uint64_t lemma_key;
+} SudachiSudachiTokenInfo;
+
+/**
+ * Counts of one speaker
+ */
+typedef struct SudachiSudachiSpeakerStats {
+  /**
+   * Lines added for the speaker
+   */
+  uint64_t texts;
+  uint64_t sentences;
+  /**
+   * Sentences ending in です or ます
+   */
+  uint64_t polite_sentences;
+  /**
+   * Words, without symbols, emoji and whitespace
+   */
+  uint64_t tokens;
+  /**
+   * Distinct lemmas among the words
+   */
+  uint64_t lemmas;
+} SudachiSudachiSpeakerStats;
+
+/**
+ * Entry of a ranked list
+ */
+typedef struct SudachiSudachiSpeakerTerm {
+  char *text;
+  uint64_t count;
+} SudachiSudachiSpeakerTerm;
+
+/**
+ * Everything besides the call's arguments that analysis output depends on
+ */
+typedef struct SudachiSudachiAnalysisStamp {
+  struct SudachiSudachiFingerprint dictionary;
+  /**
+   * Hash of the handle's analysis settings, overrides and corrections
+   */
+  uint64_t settings;
+  /**
+   * SUDACHI_ENGINE_VERSION of the library that made the stamp
+   */
+  uint32_t engine;
+} SudachiSudachiAnalysisStamp;
+
+/**
+ * Snapshot of a tokenizer's counters since creation or the last reset
+ * Batch tokenization uses its own analyzers and is not counted.
+ */
+typedef struct SudachiSudachiStats {
+  /**
+   * Analysis calls on the handle
+   */
+  uint64_t calls;
+  /**
+   * Calls that failed during analysis
+   */
+  uint64_t errors;
+  /**
+   * Time spent analyzing, including waiting for the handle's lock
+   */
+  uint64_t total_latency_ns;
+  uint64_t average_latency_ns;
+  /**
+   * Tokens produced over all calls
+   */
+  uint64_t tokens;
+  /**
+   * Most tokens produced by a single call
+   */
+  uint64_t peak_tokens;
+  /**
+   * Heap allocations made for results (token lists and string fields)
+   */
+  uint64_t allocations;
+  /**
+   * Analysis cache lookups across all caches in the process
+   */
+  uint64_t cache_hits;
+  uint64_t cache_misses;
+  /**
+   * cache_hits / (cache_hits + cache_misses), 0 before any lookup
+   */
+  double cache_hit_rate;
+} SudachiSudachiStats;
+
+/**
+ * Receives the tokens of a run of whole sentences
+ * The tokens are only valid during the call (copy what you keep); offsets
+ * are byte offsets into the file. Return false to stop reading.
+ */
+typedef bool (*SudachiSudachiSinkCallback)(void *user_data, const struct SudachiSudachiToken *const *tokens, uintptr_t count);
+
+/**
+ * Decides whether a lexicon row is kept; row is the CSV line
+ */
+typedef bool (*SudachiSudachiExportFilter)(void *user_data, const char *row);
+
+#if defined(SUDACHI_TRACING)
+/**
+ * One span event
+ * name is not NUL-terminated and stays valid for the process lifetime.
+ */
+typedef struct SudachiSudachiTraceEvent {
+  const uint8_t *name;
+  uintptr_t name_len;
+  enum SudachiSudachiTracePhase phase;
+  /**
+   * Identifies the span; shared by its Begin and End events
+   */
+  uint64_t span_id;
+  /**
+   * Nanoseconds since the callback was first registered
+   */
+  uint64_t timestamp_ns;
+  /**
+   * Span duration for End events, 0 for Begin
+   */
+  uint64_t duration_ns;
+} SudachiSudachiTraceEvent;
+#endif
+
+#if defined(SUDACHI_TRACING)
+/**
+ * Receives span events on the thread that ran the span
+ */
+typedef void (*SudachiSudachiTraceCallback)(void *user_data, const struct SudachiSudachiTraceEvent *event);
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Initialize Sudachi tokenizer with dictionary path
+ * Returns NULL on failure
+ */
+struct SudachiSudachiTokenizer *sudachi_init(const char *DictPath);
+
+/**
+ * Initialize Sudachi tokenizer from a dictionary image in memory
+ * The bytes are copied, so data may be freed once this returns.
+ * Returns NULL on failure
+ */
+struct SudachiSudachiTokenizer *sudachi_init_from_bytes(const uint8_t *Data, uintptr_t Len);
+
+/**
+ * Tokenize text using Sudachi
+ * Returns array of tokens (caller must free with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_tokenize(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uintptr_t *OutCount);
+
+/**
+ * Tokenize text, filling only the string fields selected by a
+ * SUDACHI_FIELD_* mask. Offsets and ids are always filled, so a mask of 0
+ * returns offsets and ids only without allocating any strings.
+ * Returns array of tokens (caller must free with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_tokenize_fields(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uint32_t Fields, uintptr_t *OutCount);
+
+/**
+ * Free a token
+ */
+void sudachi_free_token(struct SudachiSudachiToken *Token);
+
+/**
+ * Free array of tokens
+ * count is only a hint: the array's own length is freed. An array that was
+ * already freed, or not handed out by this library, is ignored.
+ */
+void sudachi_free_tokens(struct SudachiSudachiToken **Tokens, uintptr_t Count);
+
+/**
+ * Free tokenizer
+ */
+void sudachi_free_tokenizer(struct SudachiSudachiTokenizer *Tokenizer);
+
+/**
+ * Free a string returned by this library
+ */
+void sudachi_free_string(char *S);
+
+/**
+ * Get version string
+ */
+const char *sudachi_version(void);
+
+/**
+ * Get the ABI version of the C interface
+ */
+uint32_t sudachi_abi_version(void);
+
+/**
+ * Check whether this build includes an optional subsystem
+ * Returns false for unknown names (e.g. "jmdict", "pitch-accent" when
+ * built without them)
+ */
+bool sudachi_has_feature(const char *Name);
+
+/**
+ * Use malloc and free for all memory of the library
+ * Must be called before any other function of the library, as it can only
+ * take effect before the first allocation; both functions are then used for
+ * the life of the process. malloc must return blocks aligned like malloc
+ * does; a NULL return aborts the process, as an out-of-memory condition.
+ * Returns false if an argument is NULL, the library already allocated or
+ * it was built without the allocator-hooks feature.
+ */
+bool sudachi_set_allocator(SudachiSudachiMallocFn Malloc, SudachiSudachiFreeFn Free);
+
+/**
+ * Tokenize many texts in parallel
+ * Returns one token array per text (an entry is NULL if that text failed),
+ * with the token counts written to out_counts[0..text_count].
+ * Pass threads = 0 to use one worker per available core.
+ * Caller must free with sudachi_free_token_batch
+ */
+struct SudachiSudachiToken ***sudachi_tokenize_batch(struct SudachiSudachiTokenizer *Tokenizer, const char *const *Texts, uintptr_t TextCount, enum SudachiSudachiTokenMode Mode, uintptr_t Threads, uintptr_t *OutCounts);
+
+/**
+ * Tokenize many texts in parallel, reporting to a progress handle
+ * Same as sudachi_tokenize_batch; progress (may be NULL) receives a report
+ * after each text and can pause the batch.
+ * Caller must free with sudachi_free_token_batch
+ */
+struct SudachiSudachiToken ***sudachi_tokenize_batch_with_progress(struct SudachiSudachiTokenizer *Tokenizer, const char *const *Texts, uintptr_t TextCount, enum SudachiSudachiTokenMode Mode, uintptr_t Threads, struct SudachiSudachiProgress *Progress, uintptr_t *OutCounts);
+
+/**
+ * Free the result of sudachi_tokenize_batch
+ */
+void sudachi_free_token_batch(struct SudachiSudachiToken ***Batch, const uintptr_t *Counts, uintptr_t TextCount);
+
+/**
+ * Build a system dictionary from count lexicon CSVs and a connection
+ * matrix (matrix.def), as the sudachi CLI does, and write it to out_path
+ * progress may be NULL; it is called on this thread at the start of each
+ * stage and after each CSV.
+ * Returns false if an argument is NULL, count is 0, a file cannot be read
+ * or written, or the sources do not build (details on stderr).
+ */
+bool sudachi_build_system_dict(const char *const *CsvPaths, uintptr_t Count, const char *MatrixPath, const char *OutPath, SudachiSudachiBuildCallback Progress, void *UserData);
+
+/**
+ * Analyze text into display units
+ * Units cover all tokens in order; token indices match sudachi_tokenize on
+ * the same handle and mode.
+ * Returns NULL on failure (caller must free with sudachi_free_bunsetsu)
+ */
+struct SudachiSudachiBunsetsu *sudachi_bunsetsu(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uintptr_t *OutCount);
+
+/**
+ * Find line break opportunities for kinsoku-aware wrapping
+ * Returns byte offsets in the same layout as sudachi_wakati: the begin of
+ * every display unit a line may start at, followed by the end of the text.
+ * Returns NULL on failure (caller must free with sudachi_free_offsets)
+ */
+uint32_t *sudachi_line_breaks(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uintptr_t *OutCount);
+
+/**
+ * Free the result of sudachi_bunsetsu
+ */
+void sudachi_free_bunsetsu(struct SudachiSudachiBunsetsu *Units, uintptr_t Count);
+
+/**
+ * Open (or create) an analysis cache file
+ * Returns NULL on failure
+ */
+struct SudachiSudachiCache *sudachi_cache_open(const char *Path);
+
+/**
+ * Append the tokens of one page under key
+ * tokenizer is the handle that produced the tokens; pages stored with NULL
+ * are never reported compatible. Returns false on failure
+ */
+bool sudachi_cache_put(struct SudachiSudachiCache *Cache, const char *Key, struct SudachiSudachiToken *const *Tokens, uintptr_t Count, const struct SudachiSudachiTokenizer *Tokenizer);
+
+/**
+ * Check whether a page is cached
+ */
+bool sudachi_cache_contains(const struct SudachiSudachiCache *Cache, const char *Key);
+
+/**
+ * Check whether a cached page was produced with a handle's dictionary
+ * Returns false if the page is not cached, was produced with another
+ * dictionary (re-run and put it again), or on NULL arguments
+ */
+bool sudachi_cache_compatible(const struct SudachiSudachiCache *Cache, const char *Key, const struct SudachiSudachiTokenizer *Tokenizer);
+
+/**
+ * Decode all tokens of a cached page
+ * Returns NULL if the page is not cached or its record is malformed (caller
+ * must free with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_cache_get(const struct SudachiSudachiCache *Cache, const char *Key, uintptr_t *OutCount);
+
+/**
+ * Decode only the token of a cached page covering a byte offset
+ * Returns NULL if the page is not cached, its record is malformed or no
+ * token covers the offset (caller must free with sudachi_free_token)
+ */
+struct SudachiSudachiToken *sudachi_cache_token_at(const struct SudachiSudachiCache *Cache, const char *Key, int32_t Offset);
+
+/**
+ * Close an analysis cache
+ */
+void sudachi_cache_close(struct SudachiSudachiCache *Cache);
+
+/**
+ * Set the chunk size used for long texts on a handle
+ * Texts longer than bytes are analyzed in sentence-aligned chunks; 0
+ * analyzes every text in one piece. Returns false if tokenizer is NULL.
+ */
+bool sudachi_set_chunk_bytes(struct SudachiSudachiTokenizer *Tokenizer, uintptr_t Bytes);
+
+/**
+ * Tokenize text into parallel arrays
+ * Only the string fields selected by the SUDACHI_FIELD_* mask are stored
+ * in the blob; unselected entries have len = SUDACHI_PACKED_NULL.
+ * Returns NULL on failure (caller must free with sudachi_free_columns)
+ */
+struct SudachiSudachiColumns *sudachi_tokenize_columns(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uint32_t Fields);
+
+/**
+ * Free a struct-of-arrays result
+ */
+void sudachi_free_columns(struct SudachiSudachiColumns *Columns);
+
+/**
+ * Enable or disable joining compound verbs on a handle
+ * Applies in modes B and C; disabled by default. Returns false if tokenizer
+ * is NULL.
+ */
+bool sudachi_set_compound_verbs(struct SudachiSudachiTokenizer *Tokenizer, bool Enabled);
+
+/**
+ * Create an empty context
+ * Caller must free with sudachi_context_free
+ */
+struct SudachiSudachiContext *sudachi_context_create(void);
+
+/**
+ * Tokenize text with the buffers of a context
+ * Uses the handle's dictionary and settings with options on top (NULL for
+ * the defaults, see SudachiOptions). The result replaces the previous one
+ * of the context. Returns false if an argument is NULL, text is not valid
+ * UTF-8 or longer than options->max_bytes, or the analysis fails or its
+ * strings overflow the packed layout.
+ */
+bool sudachi_tokenize_context(struct SudachiSudachiTokenizer *Tokenizer, struct SudachiSudachiContext *Context, const char *Text, const struct SudachiSudachiOptions *Options, struct SudachiSudachiContextResult *OutResult);
+
+/**
+ * Free a context
+ * Results of the context become invalid.
+ */
+void sudachi_context_free(struct SudachiSudachiContext *Context);
+
+/**
+ * Load a conversion table for the dictionary of a handle (see README for
+ * the format)
+ * The converter keeps the dictionary alive and may outlive the handle.
+ * Returns NULL on failure (caller must free with sudachi_converter_close)
+ */
+struct SudachiSudachiConverter *sudachi_converter_open(const struct SudachiSudachiTokenizer *Tokenizer, const char *Path);
+
+/**
+ * Free a converter
+ */
+void sudachi_converter_close(struct SudachiSudachiConverter *Converter);
+
+/**
+ * Convert a kana string to up to max candidates, most likely first
+ * Kana without a spelling in the table are kept as typed.
+ * Returns NULL if an argument is NULL, max is 0, or kana is empty or not
+ * all kana (caller must free with sudachi_free_conversions)
+ */
+struct SudachiSudachiConversion *sudachi_convert(const struct SudachiSudachiConverter *Converter, const char *Kana, uintptr_t Max, uintptr_t *OutCount);
+
+/**
+ * Free the result of sudachi_convert
+ */
+void sudachi_free_conversions(struct SudachiSudachiConversion *Conversions, uintptr_t Count);
+
+/**
+ * Open the corpus stored at path, or start an empty one if there is no file
+ * Returns NULL if path is NULL or the file can't be read or is not a corpus
+ * (caller must free with sudachi_corpus_free)
+ */
+struct SudachiSudachiCorpus *sudachi_corpus_open(const char *Path);
+
+/**
+ * Analyze a document and add its words to the corpus
+ * Every call counts as one document, so adding the same text twice counts it
+ * twice. May be called from several threads. Returns false if an argument is
+ * NULL, text is not valid UTF-8 or the analysis fails.
+ */
+bool sudachi_corpus_add(struct SudachiSudachiCorpus *Corpus, struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode);
+
+/**
+ * Get the counts of a lemma (see sudachi_lemma_key)
+ * Lemmas never seen have zero counts. Returns false if an argument is NULL.
+ */
+bool sudachi_corpus_frequency(const struct SudachiSudachiCorpus *Corpus, uint64_t LemmaKey, struct SudachiSudachiCorpusFrequency *OutFrequency);
+
+/**
+ * Get the totals of a corpus
+ * Returns false if an argument is NULL
+ */
+bool sudachi_corpus_totals(const struct SudachiSudachiCorpus *Corpus, struct SudachiSudachiCorpusTotals *OutTotals);
+
+/**
+ * Write the corpus to the path it was opened from
+ * The file is replaced atomically, so a crash mid-save keeps the previous
+ * tables. Returns false if corpus is NULL or the file can't be written.
+ */
+bool sudachi_corpus_save(const struct SudachiSudachiCorpus *Corpus);
+
+/**
+ * Free a corpus without saving it
+ */
+void sudachi_corpus_free(struct SudachiSudachiCorpus *Corpus);
+
+/**
+ * Create an empty correction store
+ * Returns NULL on failure (caller must free with sudachi_corrections_free)
+ */
+struct SudachiSudachiCorrections *sudachi_corrections_create(void);
+
+/**
+ * Free a correction store
+ * Attached handles keep the corrections recorded so far.
+ */
+void sudachi_corrections_free(struct SudachiSudachiCorrections *Corrections);
+
+/**
+ * Record a user correction
+ * Recording a surface again replaces its segmentation and readings. Returns
+ * false if any argument is NULL or the correction is invalid.
+ */
+bool sudachi_corrections_record(struct SudachiSudachiCorrections *Corrections, const struct SudachiSudachiCorrection *Correction);
+
+/**
+ * Export the store as TSV (see README for the format)
+ * Returns NULL on failure (caller must free with sudachi_free_string)
+ */
+char *sudachi_corrections_export(const struct SudachiSudachiCorrections *Corrections);
+
+/**
+ * Add corrections exported by sudachi_corrections_export
+ * Counts of surfaces already in the store are summed. Returns false, leaving
+ * the store unchanged, if any line is invalid.
+ */
+bool sudachi_corrections_import(struct SudachiSudachiCorrections *Corrections, const char *Tsv);
+
+/**
+ * Attach a correction store to a handle, or detach it with NULL
+ * Several handles may share one store. Returns false if tokenizer is NULL.
+ */
+bool sudachi_set_corrections(struct SudachiSudachiTokenizer *Tokenizer, const struct SudachiSudachiCorrections *Corrections);
+
+/**
+ * Load the cost overrides of a handle from a table file (see README), or
+ * clear them if path is NULL
+ * Returns false if tokenizer is NULL, the file cannot be read or a line is
+ * invalid (reported on stderr), leaving the overrides unchanged.
+ */
+bool sudachi_set_cost_overrides(struct SudachiSudachiTokenizer *Tokenizer, const char *Path);
+
+/**
+ * Analyze text into dictionary forms with conjugation info
+ * Punctuation and whitespace tokens are skipped.
+ * Returns NULL on failure (caller must free with sudachi_free_deinflections)
+ */
+struct SudachiSudachiDeinflection *sudachi_deinflect(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uintptr_t *OutCount);
+
+/**
+ * Free the result of sudachi_deinflect
+ */
+void sudachi_free_deinflections(struct SudachiSudachiDeinflection *Words, uintptr_t Count);
+
+/**
+ * Compare two builds of a system dictionary and write the added, removed
+ * and changed entries to out_path as JSON Lines (see README for the
+ * format)
+ * Returns false if an argument is NULL, a dictionary cannot be loaded or
+ * the report cannot be written.
+ */
+bool sudachi_dict_diff(const char *PathA, const char *PathB, const char *OutPath);
+
+/**
+ * Initialize a tokenizer with user dictionaries
+ * user_paths lists user_count dictionary files (at most
+ * SUDACHI_MAX_USER_DICTIONARIES); the first is dictionary 1, the system
+ * dictionary is dictionary 0.
+ * Returns NULL on failure
+ */
+struct SudachiSudachiTokenizer *sudachi_init_with_user_dictionaries(const char *DictPath, const char *const *UserPaths, uintptr_t UserCount);
+
+/**
+ * Set the cost bias of a dictionary on a handle
+ * Negative values favor entries of the dictionary over entries of the same
+ * surface in other dictionaries; all biases start at 0. Returns false if
+ * tokenizer is NULL or dictionary is out of range.
+ */
+bool sudachi_set_dictionary_bias(struct SudachiSudachiTokenizer *Tokenizer, uint32_t Dictionary, int32_t Bias);
+
+/**
+ * Get the dictionary a token came from
+ * Returns 0 for the system dictionary, 1 and up for user dictionaries in
+ * load order, and -1 for unknown words, pinned tokens and NULL
+ */
+int32_t sudachi_token_dictionary(const struct SudachiSudachiToken *Token);
+
+/**
+ * Compare two results of analyzing the same text
+ * Both must come from sudachi_tokenize or sudachi_tokenize_fields with the
+ * same field mask, or unselected fields count as changes. Unchanged tokens
+ * are not reported; an empty result sets out_count to 0.
+ * Returns NULL on failure (caller must free with sudachi_free_diff)
+ */
+struct SudachiSudachiTokenDiff *sudachi_diff(struct SudachiSudachiToken *const *TokensA, uintptr_t CountA, struct SudachiSudachiToken *const *TokensB, uintptr_t CountB, uintptr_t *OutCount);
+
+/**
+ * Free the result of sudachi_diff
+ */
+void sudachi_free_diff(struct SudachiSudachiTokenDiff *Diffs, uintptr_t Count);
+
+/**
+ * Analyze the XHTML chapters of a book into one JSON bundle
+ * chapters are in spine order; each is stripped to its text, tokenized,
+ * given furigana and searched for vocabulary. Chapters are analyzed on
+ * threads workers (0 uses one per available core). A chapter that is NULL,
+ * not valid UTF-8 or fails to analyze has "failed": true and no tokens.
+ * Returns NULL if tokenizer or chapters is NULL (caller must free with
+ * sudachi_free_string)
+ */
+char *sudachi_document_analyze(struct SudachiSudachiTokenizer *Tokenizer, const char *const *Chapters, uintptr_t ChapterCount, enum SudachiSudachiTokenMode Mode, uintptr_t Threads);
+
+/**
+ * Create the file at path and write the header row
+ * An existing file is replaced. Returns NULL if path is NULL or the file
+ * can't be written (caller must close with sudachi_export_close)
+ */
+struct SudachiSudachiExport *sudachi_export_open(const char *Path, enum SudachiSudachiExportFormat Format);
+
+/**
+ * Analyze a document and append its tokens
+ * document is the id written in the first column. Rows of one document are
+ * written together, so documents may be added from several threads. Returns
+ * false if an argument is NULL, not valid UTF-8, the analysis fails or a
+ * write failed (later documents are then rejected too).
+ */
+bool sudachi_export_document(struct SudachiSudachiExport *Export, struct SudachiSudachiTokenizer *Tokenizer, const char *Document, const char *Text, enum SudachiSudachiTokenMode Mode);
+
+/**
+ * Flush and close an export
+ * Returns false if export is NULL or any write failed
+ */
+bool sudachi_export_close(struct SudachiSudachiExport *Export);
+
+/**
+ * Get the fingerprint of a handle's dictionary
+ * Returns false if any argument is NULL
+ */
+bool sudachi_dictionary_fingerprint(const struct SudachiSudachiTokenizer *Tokenizer, struct SudachiSudachiFingerprint *OutFingerprint);
+
+/**
+ * Analyze text and render it in a sudachi CLI compatible text format
+ * Returns NULL on failure (caller must free with sudachi_free_string)
+ */
+char *sudachi_format(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, enum SudachiSudachiOutputFormat Format);
+
+/**
+ * Analyze text into furigana segments
+ * Segments cover the whole input in order; okurigana are split off so ruby
+ * sits on the kanji only.
+ * Returns NULL on failure (caller must free with sudachi_free_furigana)
+ */
+struct SudachiSudachiRuby *sudachi_furigana(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uintptr_t *OutCount);
+
+/**
+ * Analyze text into furigana segments with per-call options
+ * options may be NULL for the defaults (see SudachiOptions). Fields and
+ * token filters don't apply, since segments cover the whole input;
+ * max_tokens cuts the analysis before segments are made.
+ * Returns NULL on failure or if text is longer than options->max_bytes
+ * (caller must free with sudachi_free_furigana)
+ */
+struct SudachiSudachiRuby *sudachi_furigana_options(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, const struct SudachiSudachiOptions *Options, uintptr_t *OutCount);
+
+/**
+ * Split a token into its stem and trailing okurigana
+ * Writes the byte offsets where the okurigana begin in surface and in
+ * reading (食べた/タベタ -> 3, 3); without okurigana they are the full
+ * lengths. Needs SUDACHI_FIELD_SURFACE and SUDACHI_FIELD_READING.
+ * Returns false if the token has no kanji, lacks either field, or its
+ * reading does not end with the okurigana.
+ */
+bool sudachi_token_okurigana(const struct SudachiSudachiToken *Token, int32_t *OutSurface, int32_t *OutReading);
+
+/**
+ * Free the result of sudachi_furigana
+ */
+void sudachi_free_furigana(struct SudachiSudachiRuby *Ruby, uintptr_t Count);
+
+/**
+ * Load the shared tokenizer from a dictionary file
+ * Only the first successful call loads; later calls return true without
+ * reloading, whatever path they pass. Returns false on failure, after which
+ * init may be retried.
+ */
+bool sudachi_global_init(const char *DictPath);
+
+/**
+ * Whether sudachi_global_init has succeeded
+ */
+bool sudachi_global_is_initialized(void);
+
+/**
+ * Tokenize text with the shared tokenizer
+ * Same as sudachi_tokenize_fields. Returns NULL before sudachi_global_init
+ * or on failure (caller must free with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_global_tokenize(const char *Text, enum SudachiSudachiTokenMode Mode, uint32_t Fields, uintptr_t *OutCount);
+
+/**
+ * Get the sizes of the connection matrix and the POS table
+ * Returns false if an argument is NULL
+ */
+bool sudachi_grammar_info(const struct SudachiSudachiTokenizer *Tokenizer, struct SudachiSudachiGrammarInfo *OutInfo);
+
+/**
+ * Get the cost of connecting a word with right_id to a following word
+ * with left_id; lower is more plausible
+ * Returns false if an argument is NULL or an id is out of range (see
+ * sudachi_grammar_info).
+ */
+bool sudachi_connection_cost(const struct SudachiSudachiTokenizer *Tokenizer, uint16_t RightId, uint16_t LeftId, int16_t *OutCost);
+
+/**
+ * Get the connection ids and cost of the dictionary entry word_id (from a
+ * token of this handle or sudachi_lookup)
+ * Returns false if an argument is NULL or word_id is an unknown word or an
+ * opaque line, which have no dictionary entry.
+ */
+bool sudachi_word_param(const struct SudachiSudachiTokenizer *Tokenizer, uint32_t WordId, struct SudachiSudachiWordParam *OutParam);
+
+/**
+ * POS of a pos_id as a JSON array of its six columns, e.g.
+ * ["名詞","普通名詞","一般","*","*","*"]
+ * Returns NULL if tokenizer is NULL or pos_id is out of range (caller must
+ * free with sudachi_free_string)
+ */
+char *sudachi_pos_string(const struct SudachiSudachiTokenizer *Tokenizer, uint16_t PosId);
+
+/**
+ * Find the pos_id of a POS given as its six comma-separated columns
+ * ("名詞,普通名詞,一般,*,*,*")
+ * Returns -1 if an argument is NULL, pos does not have six columns or the
+ * dictionary has no such POS.
+ */
+int32_t sudachi_pos_id(const struct SudachiSudachiTokenizer *Tokenizer, const char *Pos);
+
+/**
+ * Token ranges adjusted to grapheme cluster boundaries
+ * tokens must be the result of analyzing text; entry i is the range of
+ * token i, which may be empty if the token lies inside another token's
+ * cluster. out_count receives count.
+ * Returns NULL on failure (caller must free with sudachi_free_grapheme_ranges)
+ */
+struct SudachiSudachiByteRange *sudachi_grapheme_ranges(const char *Text, struct SudachiSudachiToken *const *Tokens, uintptr_t Count, uintptr_t *OutCount);
+
+/**
+ * Free the result of sudachi_grapheme_ranges
+ */
+void sudachi_free_grapheme_ranges(struct SudachiSudachiByteRange *Ranges, uintptr_t Count);
+
+/**
+ * Check whether a byte offset is a grapheme cluster boundary of text
+ * The start and end of text are boundaries. Returns false for NULL or
+ * invalid UTF-8 text and offsets past the end.
+ */
+bool sudachi_is_grapheme_boundary(const char *Text, uintptr_t Offset);
+
+/**
+ * Re-rank candidates for the text at cursor (a byte offset into
+ * context_text) by how well each fits the text around it
+ * Writes the candidate indices, most plausible first, to out_order, which
+ * must hold count entries. Candidates that are NULL or not UTF-8 go last;
+ * equally plausible ones keep their order. Returns false if an argument is
+ * NULL or cursor is not a character boundary of context_text.
+ */
+bool sudachi_rank_candidates(struct SudachiSudachiTokenizer *Tokenizer, const char *const *Candidates, uintptr_t Count, const char *ContextText, uintptr_t Cursor, uintptr_t *OutOrder);
+
+/**
+ * Load the kanji table, replacing any loaded before
+ * Returns false if path is NULL or the file cannot be read, keeping the
+ * previous table.
+ */
+bool sudachi_kanji_load(const char *Path);
+
+/**
+ * Details of a kanji as a JSON object:
+ * {"kanji":"食","strokes":9,"on":["ショク","ジキ"],"kun":["く.う","た.べる"],
+ *  "meanings":["eat","food"]}
+ * Returns NULL if no table is loaded or the kanji is not in it (caller
+ * must free with sudachi_free_string)
+ */
+char *sudachi_kanji_info(uint32_t Codepoint);
+
+/**
+ * Components of a kanji, in table order, as a UTF-8 string ("口艹田")
+ * Returns NULL if no table is loaded or the kanji is not in it, and an
+ * empty string if the table lists no components for it (caller must free
+ * with sudachi_free_string)
+ */
+char *sudachi_kanji_components(uint32_t Codepoint);
+
+/**
+ * Kanji that contain every one of count components (codepoints), fewest
+ * strokes first, as a UTF-8 string
+ * Returns an empty string if none match, and NULL if no table is loaded,
+ * components is NULL or count is 0 (caller must free with
+ * sudachi_free_string)
+ */
+char *sudachi_kanji_by_components(const uint32_t *Components, uintptr_t Count);
+
+/**
+ * Set the share of foreign-script letters at which a line is skipped
+ * Lines of text whose letters are at least threshold (0 to 1) Latin,
+ * Cyrillic or Hangul are returned as one opaque token instead of being
+ * analyzed; 0 analyzes everything. Returns false if tokenizer is NULL.
+ */
+bool sudachi_set_language_gate(struct SudachiSudachiTokenizer *Tokenizer, float Threshold);
+
+/**
+ * Compare struct sizes seen by the caller with the library's
+ * sizes[i] is sizeof the struct of SUDACHI_LAYOUT_* index i as compiled by
+ * the caller (MemoryLayout.stride in Swift), or 0 to skip it; count may be
+ * less than SUDACHI_LAYOUT_COUNT. Returns false if a size differs or an
+ * index is unknown to the library (a newer header), with that index in
+ * *out_mismatch if not NULL, or if sizes is NULL.
+ */
+bool sudachi_layout_check(const uintptr_t *Sizes, uintptr_t Count, uintptr_t *OutMismatch);
+
+#if defined(SUDACHI_LEAK_CHECK)
+/**
+ * Number of results handed to C and not yet freed
+ */
+uintptr_t sudachi_outstanding_allocations(void);
+#endif
+
+#if defined(SUDACHI_LEAK_CHECK)
+/**
+ * Write every outstanding allocation with its creation backtrace to
+ * stderr, oldest first
+ * Returns the number of allocations written
+ */
+uintptr_t sudachi_dump_allocations(void);
+#endif
+
+/**
+ * Compute the lemma key of a word outside analysis
+ * pos is the first POS column ("動詞"); the result equals lemma_key of
+ * tokens of that word. Returns 0 if any argument is NULL or not UTF-8.
+ */
+uint64_t sudachi_lemma_key(const char *NormalizedForm, const char *DictionaryReading, const char *Pos);
+
+/**
+ * Look up the dictionary entries of a word
+ * Only exact surface matches are returned (an empty array if none); begin
+ * and end span the whole word.
+ * Returns NULL on failure (caller must free with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_lookup(struct SudachiSudachiTokenizer *Tokenizer, const char *Word, uintptr_t *OutCount);
+
+/**
+ * Look up the dictionary entries of a word with per-call options
+ * options may be NULL for the defaults (see SudachiOptions). Only the
+ * field mask, filters and limits apply; entries don't depend on mode or
+ * normalization.
+ * Returns NULL on failure or if word is longer than options->max_bytes
+ * (caller must free with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_lookup_options(struct SudachiSudachiTokenizer *Tokenizer, const char *Word, const struct SudachiSudachiOptions *Options, uintptr_t *OutCount);
+
+/**
+ * Initialize Sudachi tokenizer with mapping options
+ * options may be NULL for defaults.
+ * Returns NULL on failure
+ */
+struct SudachiSudachiTokenizer *sudachi_init_with_options(const char *DictPath, const struct SudachiSudachiInitOptions *Options);
+
+/**
+ * Load a name table (see README for the format)
+ * Returns NULL on failure (caller must free with sudachi_names_close)
+ */
+struct SudachiSudachiNames *sudachi_names_open(const char *Path);
+
+/**
+ * Free a name table
+ */
+void sudachi_names_close(struct SudachiSudachiNames *Names);
+
+/**
+ * Guess up to max readings of a token that may be a personal name
+ * Only tokens flagged SUDACHI_TOKEN_OOV or tagged 人名 are guessed; the
+ * dictionary reading of a 人名 token is one of the candidates.
+ * Returns NULL if the token is not a name candidate or no reading can be
+ * built (caller must free with sudachi_free_name_readings)
+ */
+struct SudachiSudachiNameReading *sudachi_guess_name_readings(const struct SudachiSudachiNames *Names, const struct SudachiSudachiToken *Token, uintptr_t Max, uintptr_t *OutCount);
+
+/**
+ * Free the result of sudachi_guess_name_readings
+ */
+void sudachi_free_name_readings(struct SudachiSudachiNameReading *Readings, uintptr_t Count);
+
+/**
+ * Enable or disable OCR post-correction on a handle
+ * Applied after width normalization. Token offsets and surfaces still refer
+ * to the original text; tokens covering a correction have
+ * SUDACHI_TOKEN_OCR set. Disabled by default. Returns false if tokenizer is
+ * NULL.
+ */
+bool sudachi_set_ocr_correction(struct SudachiSudachiTokenizer *Tokenizer, bool Enabled);
+
+/**
+ * Find the OCR confusions in text, whether or not correction is enabled
+ * on the handle (the handle's width normalization is applied first)
+ * Returns NULL on failure (caller must free with
+ * sudachi_free_ocr_corrections)
+ */
+struct SudachiSudachiOcrCorrection *sudachi_ocr_corrections(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, uintptr_t *OutCount);
+
+/**
+ * Free the result of sudachi_ocr_corrections
+ */
+void sudachi_free_ocr_corrections(struct SudachiSudachiOcrCorrection *Corrections, uintptr_t Count);
+
+/**
+ * Tokenize text with per-call options
+ * options may be NULL for the defaults (see SudachiOptions).
+ * Returns NULL on failure or if text is longer than options->max_bytes
+ * (caller must free with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_tokenize_options(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, const struct SudachiSudachiOptions *Options, uintptr_t *OutCount);
+
+/**
+ * Replace the reading overrides of a handle
+ * entries is an array of n overrides (n may be 0 to clear them); they are
+ * copied, so the caller keeps and persists its own list. Returns false if
+ * tokenizer is NULL or any entry is invalid, leaving the overrides unchanged.
+ */
+bool sudachi_set_overrides(struct SudachiSudachiTokenizer *Tokenizer, const struct SudachiSudachiOverride *Entries, uintptr_t N);
+
+/**
+ * Tokenize text into a caller-owned buffer
+ * The required size is always written to out_needed. Returns false without
+ * writing if buf_len is too small (out_needed > buf_len) or on failure
+ * (out_needed = 0).
+ */
+bool sudachi_tokenize_into(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uint8_t *OutBuf, uintptr_t BufLen, uintptr_t *OutNeeded);
+
+/**
+ * Check whether a packed result was produced with a handle's dictionary
+ * result and len are the buffer filled by sudachi_tokenize_into. Returns
+ * false if the dictionary differs (re-run the analysis) or on NULL
+ * arguments.
+ */
+bool sudachi_result_compatible(const uint8_t *Result, uintptr_t Len, const struct SudachiSudachiTokenizer *Tokenizer);
+
+/**
+ * Tokenize text page by page
+ * The text is copied and analyzed as pages are requested with
+ * sudachi_result_next_page; page_size is the most tokens per page (0 for
+ * SUDACHI_DEFAULT_PAGE_TOKENS). options may be NULL for the defaults (see
+ * SudachiOptions); max_tokens limits the whole result. Uses the handle's
+ * settings as of this call; the handle may be freed while the result lives.
+ * Returns NULL if an argument is NULL, text is not valid UTF-8 or longer
+ * than options->max_bytes (caller must free with sudachi_free_paged_result)
+ */
+struct SudachiSudachiPagedResult *sudachi_tokenize_paged(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, const struct SudachiSudachiOptions *Options, uintptr_t PageSize);
+
+/**
+ * Next page of a paged tokenization
+ * Pages follow each other in text order with offsets into the whole text.
+ * Returns an empty page (out_count 0) once every token was returned, and
+ * NULL if an argument is NULL or the analysis failed, after which every
+ * call returns NULL (caller must free each page with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_result_next_page(struct SudachiSudachiPagedResult *Result, uintptr_t *OutCount);
+
+/**
+ * Free a paged result
+ * Pages already returned stay valid.
+ */
+void sudachi_free_paged_result(struct SudachiSudachiPagedResult *Result);
+
+/**
+ * Create a pool of size tokenizers over one dictionary file
+ * Returns NULL on failure or if size is 0 (caller must free with
+ * sudachi_pool_free)
+ */
+struct SudachiSudachiPool *sudachi_pool_create(const char *DictPath, uintptr_t Size);
+
+/**
+ * Check out a tokenizer, blocking until one is free
+ * The tokenizer stays owned by the pool: never free it, return it with
+ * sudachi_pool_checkin. Returns NULL if pool is NULL.
+ */
+struct SudachiSudachiTokenizer *sudachi_pool_checkout(struct SudachiSudachiPool *Pool);
+
+/**
+ * Check out a tokenizer if one is free
+ * Returns NULL without blocking if all are checked out.
+ */
+struct SudachiSudachiTokenizer *sudachi_pool_try_checkout(struct SudachiSudachiPool *Pool);
+
+/**
+ * Return a checked-out tokenizer to the pool
+ * Returns false if tokenizer does not belong to pool or is already checked in
+ */
+bool sudachi_pool_checkin(struct SudachiSudachiPool *Pool, struct SudachiSudachiTokenizer *Tokenizer);
+
+/**
+ * Number of tokenizers currently checked in
+ */
+uintptr_t sudachi_pool_idle_count(struct SudachiSudachiPool *Pool);
+
+/**
+ * Free a pool and all its tokenizers
+ * Every checked-out tokenizer must have been checked in first.
+ */
+void sudachi_pool_free(struct SudachiSudachiPool *Pool);
+
+/**
+ * Fill options with a named preset
+ * Names: READER_FURIGANA, SEARCH_INDEXING, VOCAB_EXTRACTION and
+ * FAST_BOUNDARIES. The result can be adjusted before use.
+ * Returns false if an argument is NULL or the name is unknown
+ */
+bool sudachi_options_preset(const char *Name, struct SudachiSudachiOptions *Options);
+
+/**
+ * Create a progress handle for sudachi_tokenize_batch_with_progress
+ * callback may be NULL to use the handle for pausing only.
+ * Caller must free with sudachi_progress_free
+ */
+struct SudachiSudachiProgress *sudachi_progress_create(SudachiSudachiProgressCallback Callback, void *UserData);
+
+/**
+ * Pause batches using this handle
+ * Workers finish the text they are on, then wait for
+ * sudachi_progress_resume; the batch call keeps blocking meanwhile.
+ * Returns false if progress is NULL.
+ */
+bool sudachi_progress_pause(struct SudachiSudachiProgress *Progress);
+
+/**
+ * Resume batches paused with sudachi_progress_pause
+ * Returns false if progress is NULL
+ */
+bool sudachi_progress_resume(struct SudachiSudachiProgress *Progress);
+
+/**
+ * Whether batches using this handle are paused
+ */
+bool sudachi_progress_is_paused(const struct SudachiSudachiProgress *Progress);
+
+/**
+ * Free a progress handle
+ * No batch may be using it.
+ */
+void sudachi_progress_free(struct SudachiSudachiProgress *Progress);
+
+/**
+ * Create a queue with threads workers over a handle's dictionary
+ * Workers use the handle's analysis settings as of this call; the handle
+ * may be freed while the queue lives. Pass threads = 0 for one worker.
+ * Returns NULL if tokenizer is NULL (caller must free with
+ * sudachi_queue_free)
+ */
+struct SudachiSudachiQueue *sudachi_queue_create(const struct SudachiSudachiTokenizer *Tokenizer, uintptr_t Threads);
+
+/**
+ * Queue text for analysis
+ * The text is copied. callback runs on a worker thread with user_data once
+ * the job is done. Returns the job id, or 0 if an argument is NULL or text
+ * is not valid UTF-8.
+ */
+uint64_t sudachi_queue_submit(struct SudachiSudachiQueue *Queue, const char *Text, enum SudachiSudachiTokenMode Mode, enum SudachiSudachiQueuePriority Priority, SudachiSudachiQueueCallback Callback, void *UserData);
+
+/**
+ * Remove a job that has not started yet
+ * Its callback is never called. Returns false if the job is running,
+ * finished or unknown.
+ */
+bool sudachi_queue_cancel(struct SudachiSudachiQueue *Queue, uint64_t Job);
+
+/**
+ * Number of jobs waiting for a worker
+ */
+uintptr_t sudachi_queue_pending(struct SudachiSudachiQueue *Queue);
+
+/**
+ * Free a queue
+ * Waits for running jobs to finish; jobs that have not started are dropped
+ * without calling their callbacks.
+ */
+void sudachi_queue_free(struct SudachiSudachiQueue *Queue);
+
+/**
+ * Open a level table
+ * Returns NULL if path is NULL or the file can't be read (caller must free
+ * with sudachi_levels_close)
+ */
+struct SudachiSudachiLevels *sudachi_levels_open(const char *Path);
+
+/**
+ * Free a level table
+ */
+void sudachi_levels_close(struct SudachiSudachiLevels *Levels);
+
+/**
+ * Build a readability report of many texts (e.g. the pages of a volume)
+ * Texts are analyzed on threads workers (0 uses one per available core).
+ * levels may be NULL to leave the level histogram empty. Texts that are
+ * NULL, not valid UTF-8 or fail to analyze are counted in failed_texts.
+ * Returns the report as JSON, or NULL if tokenizer or texts is NULL (caller
+ * must free with sudachi_free_string)
+ */
+char *sudachi_readability_report(struct SudachiSudachiTokenizer *Tokenizer, const char *const *Texts, uintptr_t TextCount, enum SudachiSudachiTokenMode Mode, const struct SudachiSudachiLevels *Levels, uintptr_t Threads);
+
+/**
+ * Set the reading post-processing of a handle
+ * flags is a mask of SUDACHI_READING_* values (0 by default, none).
+ * Furigana uses the processed readings too.
+ * Returns false if tokenizer is NULL.
+ */
+bool sudachi_set_reading_options(struct SudachiSudachiTokenizer *Tokenizer, uint32_t Flags);
+
+/**
+ * Rejoin OCR lines into logical text before tokenization
+ * Lines are grouped by block and put in reading order by their boxes; each
+ * block becomes one line of the result, in order of the block's first line.
+ * If out_offsets is not NULL it receives, for each of the count lines, the
+ * byte offset of its text in the result (-1 for empty lines).
+ * Returns NULL if lines is NULL or a line's text is NULL or not valid UTF-8
+ * (caller must free with sudachi_free_string)
+ */
+char *sudachi_rejoin_lines(const struct SudachiSudachiOcrLine *Lines, uintptr_t Count, int32_t *OutOffsets);
+
+/**
+ * Set the re-ranking hook of a handle, given the paths cheapest
+ * segmentations of each chunk, or clear it with a NULL callback
+ * paths must be 1..=SUDACHI_MAX_PATHS. Each chunk is then decoded a second
+ * time (see README). Returns false if tokenizer is NULL or paths is out of
+ * range, leaving the hook unchanged.
+ */
+bool sudachi_set_rerank(struct SudachiSudachiTokenizer *Tokenizer, uintptr_t Paths, SudachiSudachiRerankCallback Callback, void *UserData);
+
+/**
+ * Tokenize text into a result handle, filling the string fields selected
+ * by a SUDACHI_FIELD_* mask
+ * Returns NULL if an argument is NULL or the analysis failed (caller must
+ * free with sudachi_free_result)
+ */
+struct SudachiSudachiResult *sudachi_tokenize_result(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uint32_t Fields);
+
+/**
+ * Number of tokens of a result, 0 if result is NULL
+ */
+uintptr_t sudachi_result_count(const struct SudachiSudachiResult *Result);
+
+/**
+ * Copy the numeric fields of token index into *out_info
+ * Returns false if an argument is NULL or index is out of range
+ */
+bool sudachi_result_get(const struct SudachiSudachiResult *Result, uintptr_t Index, struct SudachiSudachiTokenInfo *OutInfo);
+
+/**
+ * String field of token index, one of the SUDACHI_FIELD_* bits except
+ * SUDACHI_FIELD_LEMMA_KEY (POS is a JSON array)
+ * The string is owned by the result and valid until sudachi_free_result.
+ * Returns NULL if result is NULL, index is out of range, field is not a
+ * single string field or was not selected.
+ */
+const char *sudachi_result_field(const struct SudachiSudachiResult *Result, uintptr_t Index, uint32_t Field);
+
+/**
+ * Surface form as it appears in the input, NULL if not selected
+ */
+const char *sudachi_token_surface(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Reading in katakana, NULL if not selected
+ */
+const char *sudachi_token_reading(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Dictionary (base) form, NULL if not selected
+ */
+const char *sudachi_token_dictionary_form(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Normalized form, NULL if not selected
+ */
+const char *sudachi_token_normalized_form(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * POS tags as a JSON array string, NULL if not selected
+ */
+const char *sudachi_token_pos(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Reading of the dictionary form in katakana, NULL if not selected
+ */
+const char *sudachi_token_dictionary_reading(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Start byte offset in the input, -1 if out of range
+ */
+int32_t sudachi_token_begin(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * End byte offset in the input (exclusive), -1 if out of range
+ */
+int32_t sudachi_token_end(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Raw Sudachi word id
+ */
+uint32_t sudachi_token_word_id(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Index into the dictionary POS table
+ */
+uint16_t sudachi_token_pos_id(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Script of the surface
+ */
+enum SudachiSudachiScript sudachi_token_script(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Class of the token
+ */
+enum SudachiSudachiTokenClass sudachi_token_class(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * SUDACHI_TOKEN_* flags of the token
+ */
+uint8_t sudachi_token_flags(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Lemma key of the token, 0 if not requested
+ */
+uint64_t sudachi_token_lemma_key(const struct SudachiSudachiResult *Result, uintptr_t Index);
+
+/**
+ * Free a result and every string read from it
+ */
+void sudachi_free_result(struct SudachiSudachiResult *Result);
+
+/**
+ * Enable or disable merging of foreign script runs on a handle
+ * Enabled by default. Tokens are tagged with their script either way.
+ * Returns false if tokenizer is NULL.
+ */
+bool sudachi_set_pass_through(struct SudachiSudachiTokenizer *Tokenizer, bool Enabled);
+
+/**
+ * Initialize Sudachi tokenizer with SUDACHI_INIT_* flags
+ * Returns NULL on failure
+ */
+struct SudachiSudachiTokenizer *sudachi_init_with_flags(const char *DictPath, uint32_t Flags);
+
+/**
+ * Split text into sentences
+ * flags is a mask of SUDACHI_SENTENCE_* values. Ranges cover the text up
+ * to the end of each sentence's last token other than whitespace.
+ * Returns NULL on failure (caller must free with sudachi_free_sentences)
+ */
+struct SudachiSudachiByteRange *sudachi_sentences(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uint32_t Flags, uintptr_t *OutCount);
+
+/**
+ * Free the result of sudachi_sentences
+ */
+void sudachi_free_sentences(struct SudachiSudachiByteRange *Sentences, uintptr_t Count);
+
+/**
+ * Enable or disable sound effect recognition on a handle
+ * Disabled by default. Returns false if tokenizer is NULL.
+ */
+bool sudachi_set_sfx(struct SudachiSudachiTokenizer *Tokenizer, bool Enabled);
+
+/**
+ * Write a warm-start snapshot of a handle's dictionary
+ * Call once the app has warmed up (e.g. after the first screen of text is
+ * analyzed). Returns false if an argument is NULL, the dictionary was not
+ * memory-mapped, or the file can't be written.
+ */
+bool sudachi_snapshot_save(const struct SudachiSudachiTokenizer *Tokenizer, const char *Path);
+
+/**
+ * Start reading in the pages recorded in a warm-start snapshot
+ * Returns immediately; pages are read in the background. Returns false if an
+ * argument is NULL, the file is missing or malformed, the dictionary was not
+ * memory-mapped, or the snapshot was taken with another dictionary or page
+ * size (save a new one once warmed up).
+ */
+bool sudachi_snapshot_apply(const struct SudachiSudachiTokenizer *Tokenizer, const char *Path);
+
+/**
+ * Create empty speaker statistics
+ * Returns NULL on failure (caller must free with sudachi_speakers_free)
+ */
+struct SudachiSudachiSpeakers *sudachi_speakers_create(void);
+
+/**
+ * Free speaker statistics
+ */
+void sudachi_speakers_free(struct SudachiSudachiSpeakers *Speakers);
+
+/**
+ * Analyze a line and add it to the statistics of speaker
+ * speaker is any id the app uses for a character. May be called from several
+ * threads. Returns false if an argument is NULL, not valid UTF-8 or the
+ * analysis fails.
+ */
+bool sudachi_speakers_add(struct SudachiSudachiSpeakers *Speakers, struct SudachiSudachiTokenizer *Tokenizer, const char *Speaker, const char *Text, enum SudachiSudachiTokenMode Mode);
+
+/**
+ * Get the counts of a speaker
+ * Returns false if an argument is NULL or the speaker has no lines
+ */
+bool sudachi_speakers_stats(const struct SudachiSudachiSpeakers *Speakers, const char *Speaker, struct SudachiSudachiSpeakerStats *OutStats);
+
+/**
+ * Number of times a speaker used a lemma (see sudachi_lemma_key)
+ * Returns 0 if an argument is NULL or the speaker is unknown
+ */
+uint64_t sudachi_speakers_lemma_count(const struct SudachiSudachiSpeakers *Speakers, const char *Speaker, uint64_t LemmaKey);
+
+/**
+ * Get up to limit entries of a speaker's list, most frequent first
+ * Ties are ordered by text. Returns NULL if an argument is NULL or the
+ * speaker is unknown (caller must free with sudachi_free_speaker_terms)
+ */
+struct SudachiSudachiSpeakerTerm *sudachi_speakers_top(const struct SudachiSudachiSpeakers *Speakers, const char *Speaker, enum SudachiSudachiSpeakerList List, uintptr_t Limit, uintptr_t *OutCount);
+
+/**
+ * Free the result of sudachi_speakers_top
+ */
+void sudachi_free_speaker_terms(struct SudachiSudachiSpeakerTerm *Terms, uintptr_t Count);
+
+/**
+ * Get the analysis stamp of a handle
+ * The stamp changes with any setter that changes analysis, and with every
+ * correction recorded in an attached store. Returns false if any argument is
+ * NULL
+ */
+bool sudachi_analysis_stamp(const struct SudachiSudachiTokenizer *Tokenizer, struct SudachiSudachiAnalysisStamp *OutStamp);
+
+/**
+ * Whether output made under stamp a matches output made under stamp b
+ * Compares field by field (the struct has padding, so don't memcmp it).
+ * Returns false if either is NULL or has no dictionary fingerprint.
+ */
+bool sudachi_analysis_stamp_equal(const struct SudachiSudachiAnalysisStamp *A, const struct SudachiSudachiAnalysisStamp *B);
+
+/**
+ * Read a tokenizer's counters
+ * Returns false if tokenizer or out_stats is NULL
+ */
+bool sudachi_stats(struct SudachiSudachiTokenizer *Tokenizer, struct SudachiSudachiStats *OutStats);
+
+/**
+ * Zero a tokenizer's counters and the process-wide cache counters
+ */
+void sudachi_stats_reset(struct SudachiSudachiTokenizer *Tokenizer);
+
+/**
+ * Initialize a tokenizer, writing the handle to out_tokenizer
+ * On failure *out_tokenizer is set to NULL
+ */
+enum SudachiSudachiStatus sudachi_init2(const char *DictPath, struct SudachiSudachiTokenizer **OutTokenizer);
+
+/**
+ * Tokenize text, filling the string fields selected by a SUDACHI_FIELD_*
+ * mask. On success the tokens are written to out_tokens (caller must free
+ * with sudachi_free_tokens); on failure *out_tokens is NULL and *out_count 0
+ */
+enum SudachiSudachiStatus sudachi_tokenize2(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uint32_t Fields, struct SudachiSudachiToken ***OutTokens, uintptr_t *OutCount);
+
+/**
+ * Look up the dictionary entries of a word (see sudachi_lookup)
+ * On failure *out_tokens is NULL and *out_count 0
+ */
+enum SudachiSudachiStatus sudachi_lookup2(struct SudachiSudachiTokenizer *Tokenizer, const char *Word, struct SudachiSudachiToken ***OutTokens, uintptr_t *OutCount);
+
+/**
+ * Get a static description of a status code
+ * Takes the code as an integer, so values from a newer header are safe to
+ * pass; they are described as "unknown status".
+ */
+const char *sudachi_status_message(int32_t Status);
+
+/**
+ * Tokenize a UTF-8 text file, streaming results to a sink
+ * The file is read incrementally and analyzed in sentence-aligned pieces of
+ * the handle's chunk size (32 KiB if chunking is off); callback is called
+ * with user_data for every piece, on the calling thread. A leading BOM is
+ * skipped. Token offsets are i32, so reading stops with TextTooLong at
+ * SUDACHI_MAX_TEXT_BYTES (2 GiB).
+ * Returns Ok when the whole file was read or the callback stopped it,
+ * IoError if the file can't be read, InvalidUtf8 if it is not UTF-8.
+ */
+enum SudachiSudachiStatus sudachi_tokenize_file(struct SudachiSudachiTokenizer *Tokenizer, const char *Path, enum SudachiSudachiTokenMode Mode, SudachiSudachiSinkCallback Callback, void *UserData);
+
+/**
+ * Write a system dictionary with the entries of a lexicon CSV whose
+ * surface is listed in words_path or that filter keeps, plus the entries
+ * they refer to, compiled with the connection matrix matrix_path
+ * words_path has one surface per line (# starts a comment). Either
+ * words_path or filter may be NULL, not both; filter is called once per
+ * row, on this thread.
+ * Returns false if an argument is NULL, a file cannot be read or written,
+ * or the subset does not build.
+ */
+bool sudachi_dict_export_subset(const char *LexiconPath, const char *MatrixPath, const char *WordsPath, SudachiSudachiExportFilter Filter, void *UserData, const char *OutPath);
+
+/**
+ * Map every character of text to the index of the token containing it
+ * tokens must be the result of analyzing text (any field mask). Entries
+ * are SUDACHI_TOKEN_MAP_NONE where no token covers the character; out_len
+ * receives the length of text in unit.
+ * Returns NULL on failure (caller must free with sudachi_free_token_map)
+ */
+uint32_t *sudachi_token_map(const char *Text, struct SudachiSudachiToken *const *Tokens, uintptr_t Count, enum SudachiSudachiTextUnit Unit, uintptr_t *OutLen);
+
+/**
+ * Free the result of sudachi_token_map
+ */
+void sudachi_free_token_map(uint32_t *Map, uintptr_t Len);
+
+#if defined(SUDACHI_TRACING)
+/**
+ * Register a callback for span events, or clear it with NULL
+ * The first registration installs the library's subscriber as the global
+ * default. Returns false if another global subscriber is already
+ * installed; spans then go to that subscriber instead.
+ */
+bool sudachi_trace_set_callback(SudachiSudachiTraceCallback Callback, void *UserData);
+#endif
+
+/**
+ * Open the usage profile stored at path, or start an empty one if there
+ * is no file
+ * Returns NULL if path is NULL or the file can't be read or is not a
+ * profile (caller must free with sudachi_usage_free)
+ */
+struct SudachiSudachiUsage *sudachi_usage_open(const char *Path);
+
+/**
+ * Analyze a text and count the system dictionary entries of its tokens
+ * May be called from several threads. Returns false if an argument is
+ * NULL, text is not valid UTF-8 or the analysis fails.
+ */
+bool sudachi_usage_add(struct SudachiSudachiUsage *Usage, struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode);
+
+/**
+ * Times a system dictionary entry was hit, by its lexicon line
+ * Returns 0 if usage is NULL or the entry was never hit.
+ */
+uint64_t sudachi_usage_hits(const struct SudachiSudachiUsage *Usage, uint32_t Line);
+
+/**
+ * Write the profile to the path it was opened from
+ * The file is replaced atomically, so a crash mid-save keeps the previous
+ * table. Returns false if usage is NULL or the file can't be written.
+ */
+bool sudachi_usage_save(const struct SudachiSudachiUsage *Usage);
+
+/**
+ * Write a system dictionary with the entries of lexicon_path hit at least
+ * min_hits times, plus the entries they refer to, compiled with the
+ * connection matrix matrix_path
+ * lexicon_path must be the lexicon CSV of the dictionary the profile was
+ * recorded with. Returns false if an argument is NULL, a file cannot be
+ * read or written, or the trimmed dictionary does not build.
+ */
+bool sudachi_usage_export(const struct SudachiSudachiUsage *Usage, const char *LexiconPath, const char *MatrixPath, uint64_t MinHits, const char *OutPath);
+
+/**
+ * Free a usage profile without saving it
+ */
+void sudachi_usage_free(struct SudachiSudachiUsage *Usage);
+
+/**
+ * Tokenize length-delimited bytes (no NUL terminator needed)
+ * Without SUDACHI_INPUT_LOSSY, invalid UTF-8 returns InvalidUtf8 and writes
+ * the byte offset of the first invalid sequence to out_invalid_offset (may
+ * be NULL). With it, invalid sequences are analyzed as U+FFFD and token
+ * offsets still refer to the original bytes. Returns TextTooLong if len is
+ * over SUDACHI_MAX_TEXT_BYTES.
+ * Tokens are freed with sudachi_free_tokens
+ */
+enum SudachiSudachiStatus sudachi_tokenize_bytes(struct SudachiSudachiTokenizer *Tokenizer, const uint8_t *Text, uintptr_t Len, enum SudachiSudachiTokenMode Mode, uint32_t Fields, uint32_t Flags, struct SudachiSudachiToken ***OutTokens, uintptr_t *OutCount, uintptr_t *OutInvalidOffset);
+
+/**
+ * Find the first invalid UTF-8 sequence in a NUL-terminated string
+ * Returns its byte offset, or -1 if the string is valid (or NULL)
+ */
+int64_t sudachi_invalid_utf8_offset(const char *Text);
+
+/**
+ * Segment text into token boundaries
+ * Returns byte offsets: the begin of every token followed by the end of the
+ * last one (count + 1 entries for count tokens, 0 entries for empty text).
+ * Returns NULL on failure (caller must free with sudachi_free_offsets)
+ */
+uint32_t *sudachi_wakati(struct SudachiSudachiTokenizer *Tokenizer, const char *Text, enum SudachiSudachiTokenMode Mode, uintptr_t *OutCount);
+
+/**
+ * Free an offset array returned by sudachi_wakati
+ */
+void sudachi_free_offsets(uint32_t *Offsets, uintptr_t Count);
+
+/**
+ * Enable or disable whitespace preservation on a handle
+ * When enabled, each run of whitespace is one token of class Whitespace and
+ * the surfaces of all tokens concatenate to the input. Disabled by default.
+ * Returns false if tokenizer is NULL.
+ */
+bool sudachi_set_preserve_whitespace(struct SudachiSudachiTokenizer *Tokenizer, bool Enabled);
+
+/**
+ * Set the width normalization applied before analysis on a handle
+ * flags is a mask of SUDACHI_NORMALIZE_* values (0 by default, none).
+ * Token offsets and surfaces still refer to the original text.
+ * Returns false if tokenizer is NULL.
+ */
+bool sudachi_set_normalization(struct SudachiSudachiTokenizer *Tokenizer, uint32_t Flags);
+
+/**
+ * Load a headword list for the dictionary of a handle (see README for the
+ * format)
+ * The index keeps the dictionary alive and may outlive the handle.
+ * Returns NULL on failure (caller must free with sudachi_word_index_close)
+ */
+struct SudachiSudachiWordIndex *sudachi_word_index_open(const struct SudachiSudachiTokenizer *Tokenizer, const char *Path);
+
+/**
+ * Free a headword index
+ */
+void sudachi_word_index_close(struct SudachiSudachiWordIndex *Index);
+
+/**
+ * Look up the entries whose surface or reading matches pattern, where ?
+ * is any one character and * any run of characters
+ * Returns up to max entries in headword list order (an empty array if none
+ * match), with every field filled and begin and end spanning the surface.
+ * Returns NULL if an argument is NULL, max is 0 or pattern is empty
+ * (caller must free with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_lookup_pattern(const struct SudachiSudachiWordIndex *Index, const char *Pattern, uintptr_t Max, uintptr_t *OutCount);
+
+/**
+ * Look up the entries whose surface or reading is within max_distance
+ * edits (insertions, deletions or substitutions of a character) of query
+ * Returns up to max entries, nearest first and then in headword list order
+ * (an empty array if none are close), with every field filled and begin
+ * and end spanning the surface. max_distance is capped at 3.
+ * Returns NULL if an argument is NULL, max is 0 or query is empty (caller
+ * must free with sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_lookup_fuzzy(const struct SudachiSudachiWordIndex *Index, const char *Query, uint32_t MaxDistance, uintptr_t Max, uintptr_t *OutCount);
+
+/**
+ * Look up the entries whose reading sounds like reading (kana), within
+ * max_distance mora edits
+ * Adding or dropping a long vowel or ッ counts half an edit and any other
+ * change of a mora one; ユウキ and ユーキ are the same.
+ * Returns up to max entries, nearest first and then in headword list order
+ * (an empty array if none are close), with every field filled and begin
+ * and end spanning the surface.
+ * Returns NULL if an argument is NULL, max is 0, max_distance is negative
+ * or reading is empty or not all kana (caller must free with
+ * sudachi_free_tokens)
+ */
+struct SudachiSudachiToken **sudachi_lookup_sounds_like(const struct SudachiSudachiWordIndex *Index, const char *Reading, float MaxDistance, uintptr_t Max, uintptr_t *OutCount);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#ifdef __cplusplus
+}  // namespace sudachi_ios
+#endif  // __cplusplus
+
+#endif  /* SUDACHI_FFI_H */
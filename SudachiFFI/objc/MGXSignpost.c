@@ -0,0 +1,50 @@
+// os_signpost shim for the Rust library
+// os_signpost names must be string literals, so each interval gets its own
+// begin/end pair selected by kind. Intervals are logged under the same
+// subsystem as the Swift tokenizer, in the Points of Interest category so
+// they show up in Instruments without extra configuration.
+
+#include <dispatch/dispatch.h>
+#include <os/log.h>
+#include <os/signpost.h>
+#include <stdint.h>
+
+enum {
+    MGX_SIGNPOST_DICTIONARY_LOAD = 0,
+    MGX_SIGNPOST_TOKENIZE = 1,
+};
+
+static os_log_t mgx_signpost_log(void) {
+    static os_log_t log;
+    static dispatch_once_t once;
+    dispatch_once(&once, ^{
+        log = os_log_create("com.manglex.sudachi", OS_LOG_CATEGORY_POINTS_OF_INTEREST);
+    });
+    return log;
+}
+
+uint64_t mgx_signpost_begin(uint32_t kind) {
+    os_log_t log = mgx_signpost_log();
+    os_signpost_id_t id = os_signpost_id_generate(log);
+    switch (kind) {
+    case MGX_SIGNPOST_DICTIONARY_LOAD:
+        os_signpost_interval_begin(log, id, "Dictionary Load");
+        break;
+    case MGX_SIGNPOST_TOKENIZE:
+        os_signpost_interval_begin(log, id, "Tokenize");
+        break;
+    }
+    return id;
+}
+
+void mgx_signpost_end(uint32_t kind, uint64_t id) {
+    os_log_t log = mgx_signpost_log();
+    switch (kind) {
+    case MGX_SIGNPOST_DICTIONARY_LOAD:
+        os_signpost_interval_end(log, id, "Dictionary Load");
+        break;
+    case MGX_SIGNPOST_TOKENIZE:
+        os_signpost_interval_end(log, id, "Tokenize");
+        break;
+    }
+}
@@ -0,0 +1,83 @@
+// Objective-C wrapper over the Sudachi C API (apple feature)
+// For the parts of the app that are still Objective-C and cannot use the
+// Swift wrapper. Objects own copies of all strings; nothing needs freeing.
+
+#import <Foundation/Foundation.h>
+
+NS_ASSUME_NONNULL_BEGIN
+
+extern NSErrorDomain const MGXTokenizerErrorDomain;
+
+typedef NS_ERROR_ENUM(MGXTokenizerErrorDomain, MGXTokenizerError) {
+    MGXTokenizerErrorDictionary = 1,
+    MGXTokenizerErrorAnalysis = 2,
+};
+
+/// Split mode: A is shortest units, C is longest (named entities)
+typedef NS_ENUM(NSInteger, MGXTokenMode) {
+    MGXTokenModeA = 0,
+    MGXTokenModeB = 1,
+    MGXTokenModeC = 2,
+};
+
+/// Script of a token's surface, a hint for non-Japanese text
+typedef NS_ENUM(NSInteger, MGXScript) {
+    MGXScriptJapanese = 0,
+    MGXScriptLatin = 1,
+    MGXScriptCyrillic = 2,
+    MGXScriptHangul = 3,
+};
+
+/// Broad class of a token
+typedef NS_ENUM(NSInteger, MGXTokenClass) {
+    MGXTokenClassWord = 0,
+    MGXTokenClassSymbol = 1,
+    MGXTokenClassEmoji = 2,
+    /// A line skipped by the language gate or uncovered text, not analyzed
+    MGXTokenClassOpaque = 3,
+    MGXTokenClassWhitespace = 4,
+    /// Onomatopoeia, in SFX mode
+    MGXTokenClassSfx = 5,
+};
+
+/// One morpheme of an analysis
+@interface MGXToken : NSObject
+
+@property (nonatomic, readonly, copy) NSString *surface;
+@property (nonatomic, readonly, copy) NSString *reading;
+@property (nonatomic, readonly, copy) NSString *dictionaryForm;
+/// Reading of the dictionary form (タベル for 食べた)
+@property (nonatomic, readonly, copy) NSString *dictionaryReading;
+@property (nonatomic, readonly, copy) NSString *normalizedForm;
+@property (nonatomic, readonly, copy) NSArray<NSString *> *partOfSpeech;
+/// Byte offsets into the UTF-8 input
+@property (nonatomic, readonly) NSUInteger begin;
+@property (nonatomic, readonly) NSUInteger end;
+@property (nonatomic, readonly) uint32_t wordId;
+@property (nonatomic, readonly) uint16_t posId;
+/// Stable across dictionary rebuilds, for keying saved words; 0 for foreign runs
+@property (nonatomic, readonly) uint64_t lemmaKey;
+@property (nonatomic, readonly) MGXScript script;
+@property (nonatomic, readonly) MGXTokenClass tokenClass;
+/// SUDACHI_TOKEN_* bits, e.g. whether an iteration mark was resolved
+@property (nonatomic, readonly) uint8_t flags;
+
+- (instancetype)init NS_UNAVAILABLE;
+
+@end
+
+/// A tokenizer over a system dictionary
+/// Safe to call from any thread; calls on one tokenizer are serialized.
+@interface MGXTokenizer : NSObject
+
+- (nullable instancetype)initWithDictionaryPath:(NSString *)path
+                                          error:(NSError **)error NS_DESIGNATED_INITIALIZER;
+- (instancetype)init NS_UNAVAILABLE;
+
+- (nullable NSArray<MGXToken *> *)tokenize:(NSString *)text
+                                      mode:(MGXTokenMode)mode
+                                     error:(NSError **)error;
+
+@end
+
+NS_ASSUME_NONNULL_END
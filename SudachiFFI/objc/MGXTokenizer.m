@@ -0,0 +1,96 @@
+// Objective-C wrapper over the Sudachi C API (apple feature)
+// Compiled with ARC by build.rs and linked into the static library.
+
+#import "MGXTokenizer.h"
+#import "sudachi_ffi.h"
+
+NSErrorDomain const MGXTokenizerErrorDomain = @"MGXTokenizerErrorDomain";
+
+static NSString *MGXString(const char *s) {
+    return s ? ([NSString stringWithUTF8String:s] ?: @"") : @"";
+}
+
+static NSArray<NSString *> *MGXPartOfSpeech(const char *json) {
+    if (!json) {
+        return @[];
+    }
+    NSData *data = [NSData dataWithBytesNoCopy:(void *)json length:strlen(json) freeWhenDone:NO];
+    id pos = [NSJSONSerialization JSONObjectWithData:data options:0 error:nil];
+    return [pos isKindOfClass:[NSArray class]] ? pos : @[];
+}
+
+@implementation MGXToken
+
+- (instancetype)initWithToken:(const SudachiSudachiToken *)token {
+    if ((self = [super init])) {
+        _surface = MGXString(token->surface);
+        _reading = MGXString(token->reading);
+        _dictionaryForm = MGXString(token->dictionary_form);
+        _dictionaryReading = MGXString(token->dictionary_reading);
+        _normalizedForm = MGXString(token->normalized_form);
+        _partOfSpeech = MGXPartOfSpeech(token->pos);
+        _begin = (NSUInteger)token->begin;
+        _end = (NSUInteger)token->end;
+        _wordId = token->word_id;
+        _posId = token->pos_id;
+        _lemmaKey = token->lemma_key;
+        _script = (MGXScript)token->script;
+        _tokenClass = (MGXTokenClass)token->class_;
+        _flags = token->flags;
+    }
+    return self;
+}
+
+- (NSString *)description {
+    return [NSString stringWithFormat:@"<MGXToken %@ %@>", _surface, _partOfSpeech];
+}
+
+@end
+
+@implementation MGXTokenizer {
+    SudachiSudachiTokenizer *_tokenizer;
+}
+
+- (nullable instancetype)initWithDictionaryPath:(NSString *)path error:(NSError **)error {
+    if ((self = [super init])) {
+        _tokenizer = sudachi_init(path.fileSystemRepresentation);
+        if (!_tokenizer) {
+            if (error) {
+                *error = [NSError errorWithDomain:MGXTokenizerErrorDomain
+                                             code:MGXTokenizerErrorDictionary
+                                         userInfo:@{NSFilePathErrorKey: path}];
+            }
+            return nil;
+        }
+    }
+    return self;
+}
+
+- (void)dealloc {
+    sudachi_free_tokenizer(_tokenizer);
+}
+
+- (nullable NSArray<MGXToken *> *)tokenize:(NSString *)text
+                                      mode:(MGXTokenMode)mode
+                                     error:(NSError **)error {
+    uintptr_t count = 0;
+    SudachiSudachiToken **tokens =
+        sudachi_tokenize(_tokenizer, text.UTF8String, (SudachiSudachiTokenMode)mode, &count);
+    if (!tokens) {
+        if (error) {
+            *error = [NSError errorWithDomain:MGXTokenizerErrorDomain
+                                         code:MGXTokenizerErrorAnalysis
+                                     userInfo:nil];
+        }
+        return nil;
+    }
+
+    NSMutableArray<MGXToken *> *result = [NSMutableArray arrayWithCapacity:count];
+    for (uintptr_t i = 0; i < count; i++) {
+        [result addObject:[[MGXToken alloc] initWithToken:tokens[i]]];
+    }
+    sudachi_free_tokens(tokens, count);
+    return result;
+}
+
+@end
@@ -0,0 +1,144 @@
+// ABI versioning and capability queries
+// Lets a binding detect at runtime which C interface revision and which
+// optional subsystems the linked library was built with, instead of failing
+// on a missing symbol.
+
+use std::os::raw::c_char;
+
+use crate::c_str;
+
+/// Revision of the C interface
+/// Bump whenever a struct layout or function signature changes incompatibly:
+///   1 - initial token layout
+///   2 - SudachiToken gained word_id and pos_id
+///   3 - SudachiToken gained script
+///   4 - SudachiToken gained class
+///   5 - SudachiToken gained flags
+///   6 - SudachiToken gained dictionary_reading, SudachiPackedToken and
+///       SudachiColumns a dictionary reading string
+///   7 - SudachiPackedHeader gained fingerprint, sudachi_cache_put the
+///       producing tokenizer
+///   8 - SudachiToken, SudachiPackedToken and SudachiColumns gained a lemma
+///       key
+///   9 - SudachiToken gained inline storage for surface and reading
+pub const SUDACHI_ABI_VERSION: u32 = 9;
+
+/// Subsystems that can be queried with sudachi_has_feature
+/// Optional subsystems are listed with whether this build includes them;
+/// any name not listed is reported as unavailable. Kept in alphabetical
+/// order.
+const FEATURES: &[(&str, bool)] = &[
+    ("allocator", cfg!(feature = "allocator-hooks")),
+    ("apple", cfg!(feature = "apple")),
+    ("batch", true),
+    ("build-system-dict", true),
+    ("bunsetsu", true),
+    ("cache", true),
+    ("chunking", true),
+    ("columns", true),
+    ("compound-verbs", true),
+    ("conllu", true),
+    ("context", true),
+    ("convert", true),
+    ("corpus", true),
+    ("corrections", true),
+    ("cost-overrides", true),
+    ("deinflect", true),
+    ("dialogue", true),
+    ("dict-diff", true),
+    ("dict-subset", true),
+    ("diff", true),
+    ("document", true),
+    ("export", true),
+    ("ffi-debug", cfg!(feature = "ffi-debug")),
+    ("fingerprint", true),
+    ("format", true),
+    ("furigana", true),
+    ("fuzzy-lookup", true),
+    ("global", true),
+    ("grammar", true),
+    ("grapheme", true),
+    ("handwriting", true),
+    ("inline", true),
+    ("iteration", true),
+    ("kanji", true),
+    ("language-gate", true),
+    ("layout", true),
+    ("leak-check", cfg!(feature = "leak-check")),
+    ("lemma-key", true),
+    ("lookup", true),
+    ("mapping", cfg!(unix)),
+    ("names", true),
+    ("napi", cfg!(feature = "napi")),
+    ("ocr", true),
+    ("oov-readings", true),
+    ("options", true),
+    ("overrides", true),
+    ("packed", true),
+    ("pages", true),
+    ("phonetic-lookup", true),
+    ("pool", true),
+    ("presets", true),
+    ("progress", true),
+    ("pyo3", cfg!(feature = "pyo3")),
+    ("queue", true),
+    ("readability", true),
+    ("reading", true),
+    ("rejoin", true),
+    ("rerank", true),
+    ("result", true),
+    ("script", true),
+    ("sections", true),
+    ("sentences", true),
+    ("sfx", true),
+    ("simd", true),
+    ("snapshot", true),
+    ("speakers", true),
+    ("stamp", true),
+    ("stats", true),
+    ("status", true),
+    ("stream", true),
+    ("symbols", true),
+    ("token-map", true),
+    ("tracing", cfg!(feature = "tracing")),
+    ("uniffi", cfg!(feature = "uniffi")),
+    ("usage-profile", true),
+    ("user-dictionaries", true),
+    ("utf8-lossy", true),
+    ("wakati", true),
+    ("wasm", cfg!(feature = "wasm")),
+    ("whitespace", true),
+    ("width", true),
+    ("wildcard", true),
+];
+
+pub(crate) fn has_feature(name: &str) -> bool {
+    FEATURES.iter().any(|&(n, enabled)| n == name && enabled)
+}
+
+/// Get the ABI version of the C interface
+#[no_mangle]
+pub extern "C" fn sudachi_abi_version() -> u32 {
+    SUDACHI_ABI_VERSION
+}
+
+/// Check whether this build includes an optional subsystem
+/// Returns false for unknown names (e.g. "jmdict", "pitch-accent" when
+/// built without them)
+#[no_mangle]
+pub extern "C" fn sudachi_has_feature(name: *const c_char) -> bool {
+    c_str(name).is_some_and(has_feature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_feature() {
+        assert!(has_feature("batch"));
+        assert!(!has_feature("pitch-accent"));
+        assert!(!has_feature(""));
+        assert!(FEATURES.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+}
@@ -0,0 +1,241 @@
+// Allocator hooks
+// Hosts that manage memory in pools, or want Instruments to attribute the
+// library's memory to their own zone, can supply malloc/free functions with
+// sudachi_set_allocator. The hooks back the library's global allocator, so
+// every result handed to C (tokens, strings, arrays, handles) comes from
+// them, along with the library's internal memory.
+//
+// Memory must be freed by the allocator that provided it, and the library
+// cannot tell which one that was for a block it already holds. So, like
+// sqlite3_config, the hooks can only be installed before the library first
+// allocates, and are fixed from then on. Until either happens allocations go
+// to the system allocator; checking which one is in use costs one atomic
+// load per allocation.
+//
+// Replacing the global allocator affects the whole binary the library is
+// linked into, so the hooks are only compiled in with the allocator-hooks
+// feature. Without it the system allocator is used and
+// sudachi_set_allocator always returns false.
+
+#[cfg(feature = "allocator-hooks")]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::os::raw::c_void;
+#[cfg(feature = "allocator-hooks")]
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+/// Allocate size bytes, like malloc (at least 16-byte aligned)
+pub type SudachiMallocFn = Option<extern "C" fn(size: usize) -> *mut c_void>;
+
+/// Free a block from the matching SudachiMallocFn, like free
+pub type SudachiFreeFn = Option<extern "C" fn(ptr: *mut c_void)>;
+
+// States of the global allocator
+#[cfg(feature = "allocator-hooks")]
+const UNDECIDED: u8 = 0;
+#[cfg(feature = "allocator-hooks")]
+const INSTALLING: u8 = 1;
+#[cfg(feature = "allocator-hooks")]
+const SYSTEM: u8 = 2;
+#[cfg(feature = "allocator-hooks")]
+const HOOKED: u8 = 3;
+
+#[cfg(feature = "allocator-hooks")]
+static STATE: AtomicU8 = AtomicU8::new(UNDECIDED);
+#[cfg(feature = "allocator-hooks")]
+static MALLOC: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "allocator-hooks")]
+static FREE: AtomicUsize = AtomicUsize::new(0);
+
+/// Alignment malloc guarantees on the supported platforms
+#[cfg(feature = "allocator-hooks")]
+const MALLOC_ALIGN: usize = 16;
+
+/// Malloc and free supplied by the host
+#[cfg(feature = "allocator-hooks")]
+#[derive(Clone, Copy)]
+struct Hooks {
+    malloc: extern "C" fn(usize) -> *mut c_void,
+    free: extern "C" fn(*mut c_void),
+}
+
+#[cfg(feature = "allocator-hooks")]
+impl Hooks {
+    fn installed() -> Hooks {
+        // Only read in the HOOKED state, after both were stored
+        unsafe {
+            Hooks {
+                malloc: std::mem::transmute::<usize, extern "C" fn(usize) -> *mut c_void>(
+                    MALLOC.load(Ordering::Acquire),
+                ),
+                free: std::mem::transmute::<usize, extern "C" fn(*mut c_void)>(
+                    FREE.load(Ordering::Acquire),
+                ),
+            }
+        }
+    }
+
+    /// Allocate for layout; alignments above malloc's are served from a
+    /// larger block, with the block's start stored just before the result
+    fn alloc(self, layout: Layout) -> *mut u8 {
+        if layout.align() <= MALLOC_ALIGN {
+            return (self.malloc)(layout.size().max(1)) as *mut u8;
+        }
+        let Some(size) = layout.size().checked_add(layout.align()) else {
+            return std::ptr::null_mut();
+        };
+        let block = (self.malloc)(size) as *mut u8;
+        if block.is_null() {
+            return block;
+        }
+        // At least MALLOC_ALIGN bytes are left before the aligned address
+        let offset = layout.align() - (block as usize) % layout.align();
+        unsafe {
+            let ptr = block.add(offset);
+            (ptr as *mut *mut u8).sub(1).write_unaligned(block);
+            ptr
+        }
+    }
+
+    fn dealloc(self, ptr: *mut u8, layout: Layout) {
+        let block = if layout.align() <= MALLOC_ALIGN {
+            ptr
+        } else {
+            unsafe { (ptr as *mut *mut u8).sub(1).read_unaligned() }
+        };
+        (self.free)(block as *mut c_void);
+    }
+}
+
+/// Global allocator: the system's, or the host's hooks
+#[cfg(feature = "allocator-hooks")]
+pub(crate) struct Allocator;
+
+#[cfg(feature = "allocator-hooks")]
+impl Allocator {
+    /// Hooks to use, deciding for the system allocator on first use
+    fn hooks() -> Option<Hooks> {
+        loop {
+            match STATE.load(Ordering::Acquire) {
+                HOOKED => return Some(Hooks::installed()),
+                SYSTEM => return None,
+                UNDECIDED => {
+                    let _ = STATE.compare_exchange(
+                        UNDECIDED,
+                        SYSTEM,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    );
+                }
+                _ => std::hint::spin_loop(),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "allocator-hooks")]
+unsafe impl GlobalAlloc for Allocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match Allocator::hooks() {
+            Some(hooks) => hooks.alloc(layout),
+            None => System.alloc(layout),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match Allocator::hooks() {
+            Some(hooks) => hooks.dealloc(ptr, layout),
+            None => System.dealloc(ptr, layout),
+        }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        match Allocator::hooks() {
+            Some(hooks) => {
+                let ptr = hooks.alloc(layout);
+                if !ptr.is_null() {
+                    ptr.write_bytes(0, layout.size());
+                }
+                ptr
+            }
+            None => System.alloc_zeroed(layout),
+        }
+    }
+}
+
+#[cfg(feature = "allocator-hooks")]
+#[global_allocator]
+static GLOBAL: Allocator = Allocator;
+
+/// Use malloc and free for all memory of the library
+/// Must be called before any other function of the library, as it can only
+/// take effect before the first allocation; both functions are then used for
+/// the life of the process. malloc must return blocks aligned like malloc
+/// does; a NULL return aborts the process, as an out-of-memory condition.
+/// Returns false if an argument is NULL, the library already allocated or
+/// it was built without the allocator-hooks feature.
+#[no_mangle]
+pub extern "C" fn sudachi_set_allocator(malloc: SudachiMallocFn, free: SudachiFreeFn) -> bool {
+    let (Some(malloc), Some(free)) = (malloc, free) else {
+        return false;
+    };
+    install(malloc, free)
+}
+
+#[cfg(feature = "allocator-hooks")]
+fn install(malloc: extern "C" fn(usize) -> *mut c_void, free: extern "C" fn(*mut c_void)) -> bool {
+    if STATE
+        .compare_exchange(UNDECIDED, INSTALLING, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        return false;
+    }
+    MALLOC.store(malloc as usize, Ordering::Release);
+    FREE.store(free as usize, Ordering::Release);
+    STATE.store(HOOKED, Ordering::Release);
+    true
+}
+
+#[cfg(not(feature = "allocator-hooks"))]
+fn install(
+    _malloc: extern "C" fn(usize) -> *mut c_void,
+    _free: extern "C" fn(*mut c_void),
+) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn test_malloc(size: usize) -> *mut c_void {
+        unsafe { libc::malloc(size) }
+    }
+
+    extern "C" fn test_free(ptr: *mut c_void) {
+        unsafe { libc::free(ptr) }
+    }
+
+    #[test]
+    fn test_hooks() {
+        // The test harness allocated long before this runs, and without the
+        // feature there is nothing to install
+        assert!(!sudachi_set_allocator(Some(test_malloc), Some(test_free)));
+        assert!(!sudachi_set_allocator(None, Some(test_free)));
+    }
+
+    #[cfg(feature = "allocator-hooks")]
+    #[test]
+    fn test_alignment() {
+        let hooks = Hooks {
+            malloc: test_malloc,
+            free: test_free,
+        };
+        for align in [1, 16, 64, 4096] {
+            let layout = Layout::from_size_align(100, align).unwrap();
+            let ptr = hooks.alloc(layout);
+            assert_eq!(ptr as usize % align, 0);
+            unsafe { ptr.write_bytes(0xAB, 100) };
+            hooks.dealloc(ptr, layout);
+        }
+    }
+}
@@ -0,0 +1,570 @@
+// Reusable analysis state
+// A StatefulTokenizer keeps its lattice and input buffers between calls and
+// the MorphemeList is refilled in place, so repeated analyses on the same
+// handle do not reallocate the analysis structures. Text can be width
+// normalized and OCR-corrected first (width.rs, ocr.rs), foreign lines are
+// skipped (language.rs), long texts are analyzed in chunks (chunk.rs),
+// overrides and user corrections pin readings (overrides.rs, corrections.rs),
+// cost overrides and re-ranking hooks decode chunks again (costs.rs,
+// rerank.rs), entries are re-ranked by dictionary bias (dictionaries.rs),
+// compound verbs are joined (compound.rs), iteration marks are resolved
+// (iteration.rs), and emoji and symbol fragments (symbol.rs), sound effects
+// (sfx.rs), foreign script runs (script.rs) and whitespace runs
+// (whitespace.rs) are merged. Unknown words can be read (oov.rs) and readings
+// post-processed (reading.rs).
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use sudachi::analysis::stateful_tokenizer::StatefulTokenizer;
+use sudachi::dic::dictionary::JapaneseDictionary;
+use sudachi::dic::subset::InfoSubset;
+use sudachi::prelude::*;
+
+use crate::chunk::{self, SUDACHI_DEFAULT_CHUNK_BYTES};
+use crate::corrections::Store;
+use crate::costs::{self, Costs, Word};
+use crate::fingerprint::{fnv, FNV_OFFSET};
+use crate::language::{self, Span, SUDACHI_DEFAULT_LANGUAGE_GATE};
+use crate::lookup::entry_token;
+use crate::overrides::Overrides;
+use crate::rerank::Rerank;
+use crate::{
+    compound, dictionaries, iteration, lemma, ocr, oov, reading, script, sfx, symbol, whitespace,
+    width,
+};
+use crate::{
+    trace, TokenData, SUDACHI_FIELD_DICTIONARY_FORM, SUDACHI_FIELD_DICTIONARY_READING,
+    SUDACHI_FIELD_LEMMA_KEY, SUDACHI_FIELD_NORMALIZED_FORM, SUDACHI_FIELD_READING,
+};
+
+/// Word info needed just to segment in a mode
+fn split_subset(mode: Mode) -> InfoSubset {
+    match mode {
+        Mode::A => InfoSubset::SPLIT_A,
+        Mode::B => InfoSubset::SPLIT_B,
+        Mode::C => InfoSubset::empty(),
+    }
+}
+
+/// Word info the analyzer has to decode for a mode and field mask
+/// The POS id is always needed; surfaces come from the input text
+fn info_subset(mode: Mode, fields: u32) -> InfoSubset {
+    let mut subset = split_subset(mode) | InfoSubset::POS_ID;
+    if fields & SUDACHI_FIELD_READING != 0 {
+        subset |= InfoSubset::READING_FORM;
+    }
+    if fields & SUDACHI_FIELD_DICTIONARY_FORM != 0 {
+        subset |= InfoSubset::DIC_FORM_WORD_ID;
+    }
+    if fields & SUDACHI_FIELD_DICTIONARY_READING != 0 {
+        subset |= InfoSubset::DIC_FORM_WORD_ID | InfoSubset::READING_FORM;
+    }
+    if fields & SUDACHI_FIELD_NORMALIZED_FORM != 0 {
+        subset |= InfoSubset::NORMALIZED_FORM;
+    }
+    subset
+}
+
+pub(crate) type Dictionary = Arc<JapaneseDictionary>;
+
+/// A word of an analysis with its POS and reading
+type AnalyzedWord<'a> = (TokenData, &'a [String], Cow<'a, str>);
+
+/// Word of a morpheme of the lattice's result
+fn lattice_word<'a>(
+    dictionary: &Dictionary,
+    morpheme: Morpheme<'a, Dictionary>,
+    fields: u32,
+) -> AnalyzedWord<'a> {
+    let token = TokenData::from_morpheme(dictionary, &morpheme, fields);
+    let reading = Cow::Borrowed(morpheme.reading_form());
+    (token, morpheme.part_of_speech(), reading)
+}
+
+/// Words of a path decoded under cost overrides, over the chunk morphemes
+/// were analyzed from
+fn entry_words<'a>(
+    morphemes: &'a MorphemeList<Dictionary>,
+    chunk: &str,
+    words: Vec<Word>,
+    fields: u32,
+) -> SudachiResult<Vec<AnalyzedWord<'a>>> {
+    let dictionary = morphemes.dict();
+    let mut items = Vec::with_capacity(words.len());
+    for word in words {
+        let (begin, end, word_id) = match word {
+            Word::Lattice(i) => {
+                items.push(lattice_word(dictionary, morphemes.get(i), fields));
+                continue;
+            }
+            Word::Entry {
+                begin,
+                end,
+                word_id,
+            } => (begin, end, word_id),
+        };
+        let mut token = entry_token(dictionary, word_id, &chunk[begin..end])?;
+        token.fields = fields;
+        token.begin = begin;
+        token.end = end;
+        let pos = dictionary.grammar().pos_list.get(token.pos_id as usize);
+        let reading = Cow::Owned(token.reading.clone());
+        items.push((token, pos.map_or(&[][..], Vec::as_slice), reading));
+    }
+    Ok(items)
+}
+
+/// Per-handle analysis settings
+#[derive(Clone)]
+pub(crate) struct Options {
+    /// Longest text analyzed in one piece (0 for no limit)
+    pub(crate) chunk_bytes: usize,
+    /// Merge foreign script runs into single tokens
+    pub(crate) pass_through: bool,
+    /// Share of foreign letters at which a line is skipped (0 for never)
+    pub(crate) language_gate: f32,
+    /// SUDACHI_NORMALIZE_* flags applied before analysis
+    pub(crate) normalization: u32,
+    /// Fix OCR look-alike confusions after width normalization
+    pub(crate) ocr: bool,
+    /// One token per whitespace run, covering all of the text
+    pub(crate) preserve_whitespace: bool,
+    /// SUDACHI_READING_* flags applied to readings after analysis
+    pub(crate) reading: u32,
+    /// Merge sound effects into tokens of class Sfx
+    pub(crate) sfx: bool,
+    /// Join verbs with the auxiliary verbs after them (modes B and C)
+    pub(crate) compound_verbs: bool,
+    /// Pinned readings and segmentations
+    pub(crate) overrides: Arc<Overrides>,
+    /// Store of user corrections, consulted after overrides
+    pub(crate) corrections: Option<Arc<Store>>,
+    /// Cost bias of each dictionary by dictionary id (missing ids are 0)
+    pub(crate) dictionary_bias: Vec<i32>,
+    /// Word and connection cost deltas, applied by decoding chunks again
+    pub(crate) costs: Arc<Costs>,
+    /// Callback choosing among the cheapest paths of each chunk
+    pub(crate) rerank: Option<Rerank>,
+    /// Word info sections the handle may decode
+    pub(crate) sections: InfoSubset,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            chunk_bytes: SUDACHI_DEFAULT_CHUNK_BYTES,
+            pass_through: true,
+            language_gate: SUDACHI_DEFAULT_LANGUAGE_GATE,
+            normalization: 0,
+            ocr: false,
+            preserve_whitespace: false,
+            reading: 0,
+            sfx: false,
+            compound_verbs: false,
+            overrides: Arc::default(),
+            corrections: None,
+            dictionary_bias: Vec::new(),
+            costs: Arc::default(),
+            rerank: None,
+            sections: InfoSubset::all(),
+        }
+    }
+}
+
+impl Options {
+    /// Hash of every setting that can change the tokens of a text
+    pub(crate) fn digest(&self) -> u64 {
+        let flags = [
+            self.pass_through,
+            self.preserve_whitespace,
+            self.sfx,
+            self.compound_verbs,
+            self.ocr,
+        ];
+        let corrections = self
+            .corrections
+            .as_ref()
+            .map_or(0, |c| c.overrides().digest());
+        let bias_len = self
+            .dictionary_bias
+            .iter()
+            .rposition(|&b| b != 0)
+            .map_or(0, |i| i + 1);
+        let mut hash = fnv(FNV_OFFSET, &(self.chunk_bytes as u64).to_le_bytes());
+        hash = fnv(hash, &flags.map(u8::from));
+        hash = fnv(hash, &self.language_gate.to_bits().to_le_bytes());
+        hash = fnv(hash, &self.normalization.to_le_bytes());
+        hash = fnv(hash, &self.reading.to_le_bytes());
+        hash = fnv(hash, &self.overrides.digest().to_le_bytes());
+        hash = fnv(hash, &corrections.to_le_bytes());
+        for bias in &self.dictionary_bias[..bias_len] {
+            hash = fnv(hash, &bias.to_le_bytes());
+        }
+        if !self.costs.is_empty() {
+            hash = fnv(hash, &self.costs.digest().to_le_bytes());
+        }
+        if let Some(rerank) = &self.rerank {
+            hash = rerank.hash(hash);
+        }
+        fnv(hash, &self.sections.bits().to_le_bytes())
+    }
+}
+
+pub(crate) struct Analyzer {
+    tokenizer: StatefulTokenizer<Dictionary>,
+    morphemes: MorphemeList<Dictionary>,
+    pub(crate) options: Options,
+}
+
+// SAFETY: MorphemeList shares its input buffer through an Rc, but every clone
+// of that Rc stays inside this Analyzer (results are only handed out as
+// borrows or owned TokenData), so moving the whole Analyzer to another thread
+// moves all references together.
+unsafe impl Send for Analyzer {}
+
+impl Analyzer {
+    pub(crate) fn new(dictionary: Dictionary) -> Analyzer {
+        Analyzer {
+            tokenizer: StatefulTokenizer::new(dictionary.clone(), Mode::C),
+            morphemes: MorphemeList::empty(dictionary),
+            options: Options::default(),
+        }
+    }
+
+    /// Overrides of the handle followed by the corrections of its store
+    fn overrides(&self) -> Vec<Arc<Overrides>> {
+        let mut overrides = vec![Arc::clone(&self.options.overrides)];
+        overrides.extend(self.options.corrections.as_ref().map(|c| c.overrides()));
+        overrides
+    }
+
+    /// Pieces of text to handle one at a time: foreign spans skipped by the
+    /// language gate, spans pinned by overrides, and the rest split into chunks
+    fn pieces<'a>(
+        options: &Options,
+        overrides: &'a [Arc<Overrides>],
+        text: &'a str,
+    ) -> Vec<Span<'a>> {
+        let mut pieces = Vec::new();
+        for span in language::spans(text, options.language_gate) {
+            match span {
+                Span::Japanese(offset, span) => {
+                    Self::pin(options, overrides, offset, span, &mut pieces)
+                }
+                other => pieces.push(other),
+            }
+        }
+        pieces
+    }
+
+    /// Split a Japanese span at the matches of each layer of overrides in
+    /// turn, and the rest into chunks
+    fn pin<'a>(
+        options: &Options,
+        overrides: &'a [Arc<Overrides>],
+        offset: usize,
+        span: &'a str,
+        pieces: &mut Vec<Span<'a>>,
+    ) {
+        let Some((layer, rest)) = overrides.split_first() else {
+            pieces.extend(
+                chunk::split(span, options.chunk_bytes)
+                    .map(|(at, chunk)| Span::Japanese(offset + at, chunk)),
+            );
+            return;
+        };
+        let mut end = 0;
+        for (at, entry) in layer.matches(span) {
+            if end < at {
+                Self::pin(options, rest, offset + end, &span[end..at], pieces);
+            }
+            pieces.push(Span::Override(offset + at, entry));
+            end = at + entry.len();
+        }
+        if end < span.len() || end == 0 {
+            Self::pin(options, rest, offset + end, &span[end..], pieces);
+        }
+    }
+
+    /// Segment text without decoding any word info beyond splits
+    /// Returns the begin offset of every token followed by the end of the last
+    pub(crate) fn boundaries(&mut self, text: &str, mode: Mode) -> SudachiResult<Vec<u32>> {
+        let Some(normalized) = width::normalize(text, self.options.normalization) else {
+            return self.segment_corrected(text, mode);
+        };
+        let boundaries = self.segment_corrected(&normalized.text, mode)?;
+        // Begins map to the start of their character and the final end to the
+        // end of the last one
+        let last = boundaries.len().saturating_sub(1);
+        Ok(boundaries
+            .into_iter()
+            .enumerate()
+            .map(|(i, b)| {
+                if i == last {
+                    normalized.source_end(b as usize) as u32
+                } else {
+                    normalized.source(b as usize) as u32
+                }
+            })
+            .collect())
+    }
+
+    fn segment_corrected(&mut self, text: &str, mode: Mode) -> SudachiResult<Vec<u32>> {
+        if !self.options.ocr {
+            return self.segment(text, mode);
+        }
+        // Corrections never move an offset, so boundaries need no mapping
+        let (corrected, _) = ocr::correct(self, text);
+        self.segment(&corrected, mode)
+    }
+
+    fn segment(&mut self, text: &str, mode: Mode) -> SudachiResult<Vec<u32>> {
+        let mut boundaries = Vec::new();
+        let mut end = None;
+        let overrides = self.overrides();
+        for piece in Analyzer::pieces(&self.options, &overrides, text) {
+            let (offset, chunk) = match piece {
+                Span::Japanese(offset, chunk) => (offset, chunk),
+                Span::Foreign(offset, span, _) => {
+                    boundaries.push(offset as u32);
+                    end = Some((offset + span.len()) as u32);
+                    continue;
+                }
+                Span::Override(offset, entry) => {
+                    boundaries.extend(entry.pinned(offset, 0).iter().map(|t| t.begin as u32));
+                    end = Some((offset + entry.len()) as u32);
+                    continue;
+                }
+            };
+            if let Some(words) = self.run_costed(chunk, mode, split_subset(mode))? {
+                let spans = words.iter().map(|w| w.span(&self.morphemes));
+                for (begin, word_end) in spans {
+                    boundaries.push((offset + begin) as u32);
+                    end = Some((offset + word_end) as u32);
+                }
+                continue;
+            }
+
+            boundaries.reserve(self.morphemes.len() + 1);
+            boundaries.extend(self.morphemes.iter().map(|m| (offset + m.begin()) as u32));
+            if !self.morphemes.is_empty() {
+                let last = self.morphemes.get(self.morphemes.len() - 1);
+                end = Some((offset + last.end()) as u32);
+            }
+        }
+        boundaries.extend(end);
+        Ok(boundaries)
+    }
+
+    /// Analyze text in one piece and leave the result in morphemes()
+    pub(crate) fn run(&mut self, text: &str, mode: Mode, subset: InfoSubset) -> SudachiResult<()> {
+        trace::span!("lattice_build");
+        self.tokenizer.set_mode(mode);
+        self.tokenizer.set_subset(subset & self.options.sections);
+        self.tokenizer.reset().push_str(text);
+        self.tokenizer.do_tokenize()?;
+        self.morphemes.collect_results(&mut self.tokenizer)
+    }
+
+    /// Analyze text as run() does, then decode it again under the cost
+    /// overrides and re-ranking hook of the handle
+    /// Returns the words of the new path, or None if morphemes() stands.
+    fn run_costed(
+        &mut self,
+        text: &str,
+        mode: Mode,
+        subset: InfoSubset,
+    ) -> SudachiResult<Option<Vec<Word>>> {
+        let rerank = self.options.rerank;
+        if self.options.costs.is_empty() && rerank.is_none() {
+            self.run(text, mode, subset)?;
+            return Ok(None);
+        }
+        self.run(text, Mode::C, subset)?;
+        let paths = rerank.map_or(1, |r| r.paths());
+        let words = costs::redecode(
+            &self.morphemes,
+            text,
+            &self.options.costs,
+            mode,
+            paths,
+            |candidates| rerank.map_or(Ok(0), |r| r.choose(&self.morphemes, text, candidates)),
+        )?;
+        if words.is_none() && mode != Mode::C {
+            self.run(text, mode, subset)?;
+        }
+        Ok(words)
+    }
+
+    /// Reading of plain text in mode C, None if it cannot be analyzed
+    fn read(&mut self, text: &str) -> Option<String> {
+        self.run(text, Mode::C, InfoSubset::READING_FORM).ok()?;
+        Some(
+            self.morphemes
+                .iter()
+                .map(|m| m.reading_form().to_string())
+                .collect(),
+        )
+    }
+
+    /// Result of the last run()
+    pub(crate) fn morphemes(&self) -> &MorphemeList<Dictionary> {
+        &self.morphemes
+    }
+
+    /// Analyze text, reusing the buffers of the previous call
+    pub(crate) fn analyze(
+        &mut self,
+        text: &str,
+        mode: Mode,
+        fields: u32,
+    ) -> SudachiResult<Vec<TokenData>> {
+        let sources = fields | lemma::SOURCE_FIELDS;
+        if fields & SUDACHI_FIELD_LEMMA_KEY == 0 || sources == fields {
+            return self.analyze_normalized(text, mode, fields);
+        }
+        let tokens = self.analyze_normalized(text, mode, sources)?;
+        Ok(tokens
+            .into_iter()
+            .map(|token| lemma::strip(token, fields))
+            .collect())
+    }
+
+    fn analyze_normalized(
+        &mut self,
+        text: &str,
+        mode: Mode,
+        fields: u32,
+    ) -> SudachiResult<Vec<TokenData>> {
+        let Some(normalized) = width::normalize(text, self.options.normalization) else {
+            return self.analyze_corrected(text, mode, fields);
+        };
+        let tokens = self.analyze_corrected(&normalized.text, mode, fields)?;
+        Ok(tokens
+            .into_iter()
+            .map(|token| normalized.restore(text, token))
+            .collect())
+    }
+
+    fn analyze_corrected(
+        &mut self,
+        text: &str,
+        mode: Mode,
+        fields: u32,
+    ) -> SudachiResult<Vec<TokenData>> {
+        if !self.options.ocr {
+            return self.analyze_pieces(text, mode, fields);
+        }
+        let (corrected, corrections) = ocr::correct(self, text);
+        if corrections.is_empty() {
+            return self.analyze_pieces(text, mode, fields);
+        }
+        let tokens = self.analyze_pieces(&corrected, mode, fields)?;
+        Ok(ocr::restore(text, &corrections, tokens))
+    }
+
+    fn analyze_pieces(
+        &mut self,
+        text: &str,
+        mode: Mode,
+        fields: u32,
+    ) -> SudachiResult<Vec<TokenData>> {
+        let mut tokens = Vec::new();
+        let overrides = self.overrides();
+        for piece in Analyzer::pieces(&self.options, &overrides, text) {
+            let (offset, chunk) = match piece {
+                Span::Japanese(offset, chunk) => (offset, chunk),
+                Span::Foreign(offset, span, script) => {
+                    tokens.push(language::opaque(offset, span, script, fields));
+                    continue;
+                }
+                Span::Override(offset, entry) => {
+                    tokens.extend(entry.pinned(offset, fields));
+                    continue;
+                }
+            };
+            let redecoded = self.run_costed(chunk, mode, info_subset(mode, fields))?;
+
+            trace::span!("marshal");
+            let dictionary = self.morphemes.dict();
+            let words: Box<dyn Iterator<Item = AnalyzedWord>> = match redecoded {
+                None => Box::new(
+                    self.morphemes
+                        .iter()
+                        .map(|m| lattice_word(dictionary, m, fields)),
+                ),
+                Some(words) => {
+                    Box::new(entry_words(&self.morphemes, chunk, words, fields)?.into_iter())
+                }
+            };
+            let compounds = self.options.compound_verbs && !matches!(mode, Mode::A);
+            let bias = &self.options.dictionary_bias;
+            let biased = bias.iter().any(|&b| b != 0);
+            let mut chunk_tokens = Vec::with_capacity(self.morphemes.len());
+            // Reading of the last token while it is a verb that can take an
+            // auxiliary
+            let mut verb: Option<String> = None;
+            for (mut token, pos, reading) in words {
+                if biased {
+                    if let Some(better) = dictionaries::rerank(dictionary, chunk, &token, bias) {
+                        token = better;
+                    }
+                }
+                let surface = &chunk[token.begin..token.end];
+                let entry = overrides
+                    .iter()
+                    .filter(|o| o.has_tagged())
+                    .find_map(|o| o.tagged(surface, pos));
+                if let Some(entry) = entry {
+                    chunk_tokens.extend(entry.split(token));
+                    verb = None;
+                    continue;
+                }
+
+                let joined = verb
+                    .take()
+                    .filter(|_| compound::is_auxiliary(pos))
+                    .and_then(|stem| Some((stem, chunk_tokens.pop()?)));
+                match joined {
+                    Some((stem, prev)) => {
+                        chunk_tokens.push(compound::join(chunk, prev, &stem, token, pos));
+                        if compound::is_continuative(pos) {
+                            verb = Some(stem + &reading);
+                        }
+                    }
+                    None => {
+                        chunk_tokens.push(token);
+                        if compounds && compound::is_continuative(pos) {
+                            verb = Some(reading.into_owned());
+                        }
+                    }
+                }
+            }
+            chunk_tokens = iteration::resolve(chunk, chunk_tokens, |plain| self.read(plain));
+            chunk_tokens = symbol::merge_symbols(chunk, chunk_tokens);
+            if self.options.sfx {
+                chunk_tokens = sfx::tag(chunk, chunk_tokens);
+            }
+            if self.options.pass_through {
+                chunk_tokens = script::merge_runs(chunk, chunk_tokens);
+            }
+            tokens.extend(chunk_tokens.into_iter().map(|mut token| {
+                token.begin += offset;
+                token.end += offset;
+                token
+            }));
+        }
+        if self.options.preserve_whitespace {
+            tokens = whitespace::preserve(text, tokens, fields);
+        }
+        if fields & SUDACHI_FIELD_LEMMA_KEY != 0 {
+            lemma::assign(&mut tokens);
+        }
+        if self.options.reading != 0 {
+            oov::fill(self.morphemes.dict(), &mut tokens, self.options.reading);
+            reading::process_tokens(&mut tokens, self.options.reading);
+        }
+        Ok(tokens)
+    }
+}
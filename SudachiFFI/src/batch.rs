@@ -0,0 +1,188 @@
+// Batch tokenization
+// Spreads many independent texts (sentences, pages) across a pool of worker
+// threads. Each worker owns an analyzer over the shared dictionary; conversion
+// to C tokens happens on the calling thread once all texts are done.
+
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::analyzer::Analyzer;
+use crate::progress::{SudachiProgress, Tracker};
+use crate::{
+    c_str, handle, into_token_array, leak, sudachi_free_tokens, SudachiToken, SudachiTokenMode,
+    SudachiTokenizer, TokenData, SUDACHI_FIELD_ALL,
+};
+
+/// Resolve the worker count for a batch of n texts
+/// 0 means one worker per available core
+fn worker_count(threads: usize, n: usize) -> usize {
+    let threads = if threads == 0 {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        threads
+    };
+    threads.clamp(1, n.max(1))
+}
+
+/// Analyze texts on up to threads workers, each with its own analyzer over
+/// the handle's dictionary and settings
+/// Returns one result per text, None for a text that is missing or failed.
+pub(crate) fn analyze_all(
+    tokenizer: &SudachiTokenizer,
+    texts: &[Option<&str>],
+    mode: SudachiTokenMode,
+    fields: u32,
+    threads: usize,
+    tracker: &Tracker,
+) -> Vec<Option<Vec<TokenData>>> {
+    let dictionary = &tokenizer.dictionary;
+    let options = tokenizer.options();
+    let results: Vec<Mutex<Option<Vec<TokenData>>>> =
+        texts.iter().map(|_| Mutex::new(None)).collect();
+
+    // Workers pull the next unclaimed text so long and short texts balance out
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..worker_count(threads, texts.len()) {
+            scope.spawn(|| {
+                let mut analyzer = Analyzer::new(dictionary.clone());
+                analyzer.options = options.clone();
+                loop {
+                    tracker.checkpoint();
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= texts.len() {
+                        break;
+                    }
+                    let tokens =
+                        texts[i].and_then(|text| analyzer.analyze(text, mode.into(), fields).ok());
+                    *results[i].lock().unwrap() = tokens;
+                    tracker.done(texts[i].map_or(0, str::len));
+                }
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap())
+        .collect()
+}
+
+/// Tokenize many texts in parallel
+/// Returns one token array per text (an entry is NULL if that text failed),
+/// with the token counts written to out_counts[0..text_count].
+/// Pass threads = 0 to use one worker per available core.
+/// Caller must free with sudachi_free_token_batch
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_batch(
+    tokenizer: *mut SudachiTokenizer,
+    texts: *const *const c_char,
+    text_count: usize,
+    mode: SudachiTokenMode,
+    threads: usize,
+    out_counts: *mut usize,
+) -> *mut *mut *mut SudachiToken {
+    sudachi_tokenize_batch_with_progress(
+        tokenizer,
+        texts,
+        text_count,
+        mode,
+        threads,
+        ptr::null_mut(),
+        out_counts,
+    )
+}
+
+/// Tokenize many texts in parallel, reporting to a progress handle
+/// Same as sudachi_tokenize_batch; progress (may be NULL) receives a report
+/// after each text and can pause the batch.
+/// Caller must free with sudachi_free_token_batch
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_batch_with_progress(
+    tokenizer: *mut SudachiTokenizer,
+    texts: *const *const c_char,
+    text_count: usize,
+    mode: SudachiTokenMode,
+    threads: usize,
+    progress: *mut SudachiProgress,
+    out_counts: *mut usize,
+) -> *mut *mut *mut SudachiToken {
+    if tokenizer.is_null() || texts.is_null() || out_counts.is_null() {
+        return ptr::null_mut();
+    }
+
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let texts: Vec<Option<&str>> = unsafe { std::slice::from_raw_parts(texts, text_count) }
+        .iter()
+        .map(|&t| c_str(t))
+        .collect();
+    let bytes_total = texts.iter().flatten().map(|t| t.len()).sum();
+    let tracker = Tracker::new(unsafe { progress.as_ref() }, text_count, bytes_total);
+    let results = analyze_all(
+        tokenizer,
+        &texts,
+        mode,
+        SUDACHI_FIELD_ALL,
+        threads,
+        &tracker,
+    );
+
+    let out_counts = unsafe { std::slice::from_raw_parts_mut(out_counts, text_count) };
+    let arrays: Vec<*mut *mut SudachiToken> = results
+        .into_iter()
+        .zip(out_counts.iter_mut())
+        .map(|(result, count)| {
+            *count = 0;
+            match result {
+                Some(tokens) => {
+                    let tokens = tokens.into_iter().filter_map(TokenData::into_raw).collect();
+                    into_token_array(tokens, count)
+                }
+                None => ptr::null_mut(),
+            }
+        })
+        .collect();
+
+    let mut arrays = arrays.into_boxed_slice();
+    let ptr = arrays.as_mut_ptr();
+    Box::leak(arrays);
+    leak::track(ptr, "token batch");
+    ptr
+}
+
+/// Free the result of sudachi_tokenize_batch
+#[no_mangle]
+pub extern "C" fn sudachi_free_token_batch(
+    batch: *mut *mut *mut SudachiToken,
+    counts: *const usize,
+    text_count: usize,
+) {
+    if batch.is_null() || counts.is_null() {
+        return;
+    }
+
+    leak::release(batch);
+    unsafe {
+        let arrays = std::slice::from_raw_parts_mut(batch, text_count);
+        let counts = std::slice::from_raw_parts(counts, text_count);
+        for (tokens, count) in arrays.iter().zip(counts) {
+            sudachi_free_tokens(*tokens, *count);
+        }
+        let _ = Box::from_raw(arrays as *mut [*mut *mut SudachiToken]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_count() {
+        assert_eq!(worker_count(4, 2), 2);
+        assert_eq!(worker_count(4, 0), 1);
+        assert_eq!(worker_count(3, 100), 3);
+        assert!(worker_count(0, 100) >= 1);
+    }
+}
@@ -0,0 +1,231 @@
+// System dictionary builds
+// The content pipeline and power users build dictionaries from Sudachi's
+// lexicon CSVs and connection matrix. The same builder the sudachi CLI uses
+// is exposed here, so no separate Java or Rust tooling is needed. A build
+// reads the matrix, then each CSV in order (word ids continue across files,
+// so references in a later file count the lines of the earlier ones), then
+// resolves the references between entries and compiles the image. Progress
+// is reported at the start of each stage and after each CSV, on the calling
+// thread, so a long build can drive a progress bar.
+
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+
+use sudachi::dic::build::DictBuilder;
+
+use crate::c_str;
+
+/// Stage of a dictionary build
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SudachiBuildStage {
+    /// Reading the connection matrix
+    Matrix = 0,
+    /// Reading the lexicon CSVs
+    Lexicon = 1,
+    /// Resolving references between entries
+    Resolve = 2,
+    /// Compiling the dictionary image
+    Compile = 3,
+    /// Writing the output file
+    Write = 4,
+}
+
+/// Progress of a dictionary build
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SudachiBuildProgress {
+    pub stage: SudachiBuildStage,
+    /// Lexicon CSVs read
+    pub files_done: usize,
+    pub files_total: usize,
+    /// Entries read from the CSVs so far
+    pub entries: usize,
+}
+
+/// Receives build progress on the thread running the build
+pub type SudachiBuildCallback =
+    Option<extern "C" fn(user_data: *mut c_void, progress: *const SudachiBuildProgress)>;
+
+/// Bytes of the description field in a dictionary header
+const DESCRIPTION_LEN: usize = 256;
+
+/// Description of a build from the CSVs at paths: their file names, or their
+/// count if the names do not fit the header
+fn description(paths: &[&str]) -> String {
+    let names: Vec<_> = paths
+        .iter()
+        .map(|&path| {
+            Path::new(path)
+                .file_name()
+                .map_or(path.into(), |name| name.to_string_lossy())
+        })
+        .collect();
+    let description = names.join(", ");
+    if description.len() <= DESCRIPTION_LEN {
+        description
+    } else {
+        format!("Built from {} lexicon CSVs", paths.len())
+    }
+}
+
+/// Compile a system dictionary from a connection matrix and lexicon CSVs,
+/// reporting each step to report
+/// Errors are reported on stderr.
+pub(crate) fn build(
+    description: &str,
+    matrix: &[u8],
+    lexicons: &[Vec<u8>],
+    mut report: impl FnMut(SudachiBuildProgress),
+) -> Option<Vec<u8>> {
+    let mut progress = SudachiBuildProgress {
+        stage: SudachiBuildStage::Matrix,
+        files_done: 0,
+        files_total: lexicons.len(),
+        entries: 0,
+    };
+    let mut builder = DictBuilder::new_system();
+    builder.set_description(description);
+    report(progress);
+    if let Err(e) = builder.read_conn(matrix) {
+        eprintln!("Failed to read connection matrix: {:?}", e);
+        return None;
+    }
+    progress.stage = SudachiBuildStage::Lexicon;
+    report(progress);
+    for lexicon in lexicons {
+        match builder.read_lexicon(lexicon) {
+            Ok(entries) => progress.entries += entries,
+            Err(e) => {
+                eprintln!("Failed to read lexicon {}: {:?}", progress.files_done, e);
+                return None;
+            }
+        }
+        progress.files_done += 1;
+        report(progress);
+    }
+    progress.stage = SudachiBuildStage::Resolve;
+    report(progress);
+    if let Err(e) = builder.resolve() {
+        eprintln!("Failed to resolve dictionary: {:?}", e);
+        return None;
+    }
+    progress.stage = SudachiBuildStage::Compile;
+    report(progress);
+    let mut image = Vec::new();
+    if let Err(e) = builder.compile(&mut image) {
+        eprintln!("Failed to compile dictionary: {:?}", e);
+        return None;
+    }
+    Some(image)
+}
+
+/// Build a system dictionary from count lexicon CSVs and a connection
+/// matrix (matrix.def), as the sudachi CLI does, and write it to out_path
+/// progress may be NULL; it is called on this thread at the start of each
+/// stage and after each CSV.
+/// Returns false if an argument is NULL, count is 0, a file cannot be read
+/// or written, or the sources do not build (details on stderr).
+#[no_mangle]
+pub extern "C" fn sudachi_build_system_dict(
+    csv_paths: *const *const c_char,
+    count: usize,
+    matrix_path: *const c_char,
+    out_path: *const c_char,
+    progress: SudachiBuildCallback,
+    user_data: *mut c_void,
+) -> bool {
+    if csv_paths.is_null() || count == 0 {
+        return false;
+    }
+    let (Some(matrix_path), Some(out_path)) = (c_str(matrix_path), c_str(out_path)) else {
+        return false;
+    };
+    let paths: Option<Vec<&str>> = unsafe { std::slice::from_raw_parts(csv_paths, count) }
+        .iter()
+        .map(|&path| c_str(path))
+        .collect();
+    let Some(paths) = paths else {
+        return false;
+    };
+
+    let read = |path: &str| {
+        std::fs::read(path)
+            .map_err(|e| eprintln!("Failed to read {}: {}", path, e))
+            .ok()
+    };
+    let Some(matrix) = read(matrix_path) else {
+        return false;
+    };
+    let Some(lexicons) = paths
+        .iter()
+        .map(|&path| read(path))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return false;
+    };
+
+    let report = |info: &SudachiBuildProgress| {
+        if let Some(callback) = progress {
+            callback(user_data, info);
+        }
+    };
+    let mut last = None;
+    let image = build(&description(&paths), &matrix, &lexicons, |info| {
+        report(&info);
+        last = Some(info);
+    });
+    let (Some(image), Some(last)) = (image, last) else {
+        return false;
+    };
+    report(&SudachiBuildProgress {
+        stage: SudachiBuildStage::Write,
+        ..last
+    });
+    match std::fs::write(out_path, image) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", out_path, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dictionary_from_storage, testdict};
+    use sudachi::dic::storage::Storage;
+
+    #[test]
+    fn test_description() {
+        assert_eq!(
+            description(&["/data/small_lex.csv", "notes/user.csv"]),
+            "small_lex.csv, user.csv"
+        );
+        let long = format!("/data/{}.csv", "語".repeat(100));
+        assert_eq!(description(&[&long]), "Built from 1 lexicon CSVs");
+    }
+
+    #[test]
+    fn test_build() {
+        let mut stages = Vec::new();
+        let lexicons = [testdict::LEXICON.to_vec()];
+        let image = build("test", testdict::MATRIX, &lexicons, |info| {
+            stages.push((info.stage, info.files_done));
+        })
+        .expect("test dictionary builds");
+        assert_eq!(
+            stages,
+            [
+                (SudachiBuildStage::Matrix, 0),
+                (SudachiBuildStage::Lexicon, 0),
+                (SudachiBuildStage::Lexicon, 1),
+                (SudachiBuildStage::Resolve, 1),
+                (SudachiBuildStage::Compile, 1),
+            ]
+        );
+        assert!(dictionary_from_storage(Storage::Owned(image)).is_ok());
+    }
+}
@@ -0,0 +1,350 @@
+// Display units
+// Tap selection in the reader should pick 食べていました as one unit, not five
+// tokens. Tokens are grouped into display bunsetsu: a content word with the
+// particles, auxiliaries, suffixes and auxiliary verbs after it, and any
+// prefixes before it. Symbols, emoji, whitespace and skipped foreign spans
+// stand alone. Line breaking uses the unit boundaries as break opportunities,
+// minus those that kinsoku rules forbid.
+//
+// Each unit also gets the unit it modifies, from a few particle-driven rules
+// rather than a parser: Japanese is head-final, so a unit ending in の or in
+// an attributive form modifies the next noun, a unit ending in a case or
+// conjunctive particle the next predicate, and a topic (は, も) the last
+// predicate of the sentence. Heads never cross a full stop.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    c_str, handle, into_c_string, leak, SudachiTokenMode, SudachiTokenizer, TokenData,
+    SUDACHI_FIELD_POS, SUDACHI_FIELD_READING, SUDACHI_FIELD_SURFACE,
+};
+
+/// Fields grouping needs from the analyzer
+const BUNSETSU_FIELDS: u32 = SUDACHI_FIELD_SURFACE | SUDACHI_FIELD_READING | SUDACHI_FIELD_POS;
+
+/// Token that attaches to the content word before it
+fn is_dependent(pos: &[String]) -> bool {
+    match pos.first().map(String::as_str) {
+        Some("助詞" | "助動詞" | "接尾辞") => true,
+        // いる, しまう, ください after て; ない, よい after a stem
+        Some("動詞" | "形容詞") => pos.get(1).is_some_and(|p| p == "非自立可能"),
+        _ => false,
+    }
+}
+
+pub(crate) fn is_prefix(pos: &[String]) -> bool {
+    pos.first().is_some_and(|p| p == "接頭辞")
+}
+
+/// Characters that must not start a line (行頭禁則)
+const NO_START: &str = "、。，．,.・：；:;？！?!ー～…‥」』）〕］｝〉》】〙〗)]}ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ々ゝゞヽヾ";
+/// Characters that must not end a line (行末禁則)
+const NO_END: &str = "「『（〔［｛〈《【〘〖([{";
+
+/// Tokens of one unit as (first token index, token count)
+pub(crate) fn group(tokens: &[TokenData]) -> Vec<(usize, usize)> {
+    let mut units: Vec<(usize, usize)> = Vec::new();
+    // Whether the open unit takes dependents, and whether it still waits
+    // for the word after a prefix
+    let mut open = false;
+    let mut prefixed = false;
+    for (i, token) in tokens.iter().enumerate() {
+        let word = token.class == SudachiTokenClass::Word;
+        let attach = word && (prefixed || (open && is_dependent(&token.pos)));
+        match units.last_mut() {
+            Some(unit) if attach => unit.1 += 1,
+            _ => units.push((i, 1)),
+        }
+        prefixed = word && is_prefix(&token.pos);
+        open = word;
+    }
+    units
+}
+
+/// Content word of a unit: its first token that is not a prefix
+fn content(unit: &[TokenData]) -> &[String] {
+    unit.iter()
+        .find(|t| !is_prefix(&t.pos))
+        .map_or(&[], |t| &t.pos[..])
+}
+
+fn is_nominal(unit: &[TokenData]) -> bool {
+    matches!(
+        content(unit).first().map(String::as_str),
+        Some("名詞" | "代名詞")
+    )
+}
+
+fn is_predicate(unit: &[TokenData]) -> bool {
+    matches!(
+        content(unit).first().map(String::as_str),
+        Some("動詞" | "形容詞" | "形状詞")
+    ) || unit
+        .iter()
+        .any(|t| t.pos.first().is_some_and(|p| p == "助動詞"))
+}
+
+/// What a unit modifies, judged from its last token
+enum Attach {
+    Noun,
+    Predicate,
+    LastPredicate,
+    Next,
+}
+
+fn attach(unit: &[TokenData]) -> Attach {
+    let last = &unit[unit.len() - 1];
+    let pos = |i: usize| last.pos.get(i).map_or("", String::as_str);
+    match pos(0) {
+        "助詞" if last.surface == "の" && pos(1) == "格助詞" => Attach::Noun,
+        "助詞" if pos(1) == "係助詞" => Attach::LastPredicate,
+        "助詞" if matches!(pos(1), "格助詞" | "接続助詞" | "副助詞") => {
+            Attach::Predicate
+        }
+        "副詞" => Attach::Predicate,
+        "連体詞" => Attach::Noun,
+        _ if pos(5).starts_with("連体形") => Attach::Noun,
+        _ if pos(5).starts_with("連用形") => Attach::Predicate,
+        _ => Attach::Next,
+    }
+}
+
+fn is_full_stop(unit: &[TokenData]) -> bool {
+    unit[0].pos.get(1).is_some_and(|p| p == "句点")
+}
+
+/// Index of the unit each unit modifies, or -1 for the last unit of a
+/// sentence and for units that are not words
+pub(crate) fn heads(tokens: &[TokenData], units: &[(usize, usize)]) -> Vec<i32> {
+    let unit = |i: usize| &tokens[units[i].0..units[i].0 + units[i].1];
+    let word = |i: usize| unit(i)[0].class == SudachiTokenClass::Word;
+    (0..units.len())
+        .map(|i| {
+            if !word(i) {
+                return -1;
+            }
+            // Candidates are the later word units of the same sentence
+            let sentence: Vec<usize> = (i + 1..units.len())
+                .take_while(|&j| !is_full_stop(unit(j)))
+                .filter(|&j| word(j))
+                .collect();
+            let first =
+                |f: fn(&[TokenData]) -> bool| sentence.iter().copied().find(|&j| f(unit(j)));
+            let head = match attach(unit(i)) {
+                Attach::Noun => first(is_nominal),
+                Attach::Predicate => first(is_predicate),
+                Attach::LastPredicate => sentence.iter().copied().rfind(|&j| is_predicate(unit(j))),
+                Attach::Next => None,
+            };
+            head.or_else(|| sentence.first().copied())
+                .map_or(-1, |j| j as i32)
+        })
+        .collect()
+}
+
+/// Line break opportunities: the begin of every unit that kinsoku rules
+/// allow a line to start at, followed by the end of the text
+fn breaks(text: &str, tokens: &[TokenData]) -> Vec<u32> {
+    let mut breaks: Vec<u32> = group(tokens)
+        .into_iter()
+        .map(|(first, _)| tokens[first].begin)
+        .filter(|&at| {
+            let next = text[at..].chars().next();
+            let prev = text[..at].chars().next_back();
+            at == 0
+                || !(next.is_some_and(|c| NO_START.contains(c))
+                    || prev.is_some_and(|c| NO_END.contains(c)))
+        })
+        .map(|at| at as u32)
+        .collect();
+    if let Some(last) = tokens.last() {
+        breaks.push(last.end as u32);
+    }
+    breaks
+}
+
+/// Display unit
+/// surface and reading are owned by the result array.
+#[repr(C)]
+pub struct SudachiBunsetsu {
+    pub surface: *mut c_char,
+    /// Readings of the tokens joined, in katakana
+    pub reading: *mut c_char,
+    /// Byte offsets into the input
+    pub begin: i32,
+    pub end: i32,
+    /// Index of the first token among the tokens of the same analysis, and
+    /// number of tokens in the unit
+    pub first_token: u32,
+    pub token_count: u32,
+    /// Index of the unit this one modifies, or -1 (see README)
+    pub parent: i32,
+}
+
+/// Analyze text into display units
+/// Units cover all tokens in order; token indices match sudachi_tokenize on
+/// the same handle and mode.
+/// Returns NULL on failure (caller must free with sudachi_free_bunsetsu)
+#[no_mangle]
+pub extern "C" fn sudachi_bunsetsu(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    out_count: *mut usize,
+) -> *mut SudachiBunsetsu {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+    let tokens = match tokenizer.analyze(text, mode.into(), BUNSETSU_FIELDS) {
+        Ok(tokens) => tokens,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let groups = group(&tokens);
+    let parents = heads(&tokens, &groups);
+    let units: Vec<SudachiBunsetsu> = groups
+        .into_iter()
+        .zip(parents)
+        .map(|((first, count), parent)| {
+            let unit = &tokens[first..first + count];
+            let begin = unit[0].begin;
+            let end = unit[count - 1].end;
+            let reading: String = unit.iter().map(|t| t.reading.as_str()).collect();
+            SudachiBunsetsu {
+                surface: into_c_string(&text[begin..end]),
+                reading: into_c_string(&reading),
+                begin: begin as i32,
+                end: end as i32,
+                first_token: first as u32,
+                token_count: count as u32,
+                parent,
+            }
+        })
+        .collect();
+
+    unsafe {
+        *out_count = units.len();
+    }
+    let units = Box::leak(units.into_boxed_slice()).as_mut_ptr();
+    leak::track(units, "bunsetsu");
+    units
+}
+
+/// Find line break opportunities for kinsoku-aware wrapping
+/// Returns byte offsets in the same layout as sudachi_wakati: the begin of
+/// every display unit a line may start at, followed by the end of the text.
+/// Returns NULL on failure (caller must free with sudachi_free_offsets)
+#[no_mangle]
+pub extern "C" fn sudachi_line_breaks(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    out_count: *mut usize,
+) -> *mut u32 {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+    let tokens = match tokenizer.analyze(text, mode.into(), SUDACHI_FIELD_POS) {
+        Ok(tokens) => tokens,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let breaks = breaks(text, &tokens);
+    unsafe {
+        *out_count = breaks.len();
+    }
+    let offsets = Box::leak(breaks.into_boxed_slice()).as_mut_ptr();
+    leak::track(offsets, "offsets");
+    offsets
+}
+
+/// Free the result of sudachi_bunsetsu
+#[no_mangle]
+pub extern "C" fn sudachi_free_bunsetsu(units: *mut SudachiBunsetsu, count: usize) {
+    if units.is_null() {
+        return;
+    }
+
+    leak::release(units);
+    unsafe {
+        let units = Box::from_raw(ptr::slice_from_raw_parts_mut(units, count));
+        for unit in units.iter() {
+            for s in [unit.surface, unit.reading] {
+                if !s.is_null() {
+                    let _ = std::ffi::CString::from_raw(s);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+
+    fn tokens_of(words: &[(&str, &str)]) -> Vec<TokenData> {
+        let mut begin = 0;
+        words
+            .iter()
+            .map(|&(surface, pos)| {
+                let mut token =
+                    language::opaque(begin, surface, SudachiScript::Japanese, BUNSETSU_FIELDS);
+                token.class = crate::symbol::token_class(surface);
+                token.pos = pos.split(',').map(str::to_string).collect();
+                begin += surface.len();
+                token
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_group() {
+        let tokens = tokens_of(&[
+            ("猫", "名詞,普通名詞"),
+            ("が", "助詞,格助詞"),
+            ("お", "接頭辞"),
+            ("魚", "名詞,普通名詞"),
+            ("を", "助詞,格助詞"),
+            ("食べ", "動詞,一般"),
+            ("て", "助詞,接続助詞"),
+            ("い", "動詞,非自立可能"),
+            ("まし", "助動詞"),
+            ("た", "助動詞"),
+            ("。", "補助記号,句点"),
+            ("よ", "助詞,終助詞"),
+        ]);
+        assert_eq!(group(&tokens), [(0, 2), (2, 3), (5, 5), (10, 1), (11, 1)]);
+
+        let text = "猫がお魚を食べていました。よ";
+        assert_eq!(breaks(text, &tokens), [0, 6, 15, 39, 42]);
+        let units = group(&tokens);
+        assert_eq!(heads(&tokens, &units), [2, 2, -1, -1, -1]);
+        let tokens = tokens_of(&[
+            ("私", "代名詞"),
+            ("の", "助詞,格助詞"),
+            ("猫", "名詞,普通名詞"),
+            ("は", "助詞,係助詞"),
+            ("魚", "名詞,普通名詞"),
+            ("を", "助詞,格助詞"),
+            ("食べる", "動詞,一般,*,*,下一段-バ行,終止形-一般"),
+        ]);
+        assert_eq!(heads(&tokens, &group(&tokens)), [1, 3, 3, -1]);
+
+        let quoted = tokens_of(&[("「", "補助記号"), ("猫", "名詞"), ("」", "補助記号")]);
+        assert_eq!(breaks("「猫」", &quoted), [0, 9]);
+    }
+}
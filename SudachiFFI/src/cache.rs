@@ -0,0 +1,663 @@
+// On-disk analysis cache
+// Appends the analysis of many pages to a single file and serves lookups
+// straight from a memory map, so answering a tap never decodes a whole chapter.
+//
+// File layout (all integers little-endian):
+//   header:  b"MGXC" | u32 format version
+//   record:  u32 record length (bytes after this field)
+//            u32 key length | key bytes
+//            u64 dictionary hash | u64 dictionary version
+//            u32 token count
+//            token entries (fixed size, sorted by begin offset)
+//            string area
+//   entry:   i32 begin | i32 end | u32 word id | u16 POS id
+//            u8 script | u8 class | u8 flags | 3 reserved bytes
+//            u64 lemma key
+//            6 x (u32 offset, u32 length) string refs
+//
+// Records are append-only; a later record with the same key supersedes
+// earlier ones. Each record keeps the fingerprint of the dictionary that
+// produced it, so pages analyzed before a dictionary update can be detected
+// with sudachi_cache_compatible and re-run. Every read of the map is bounds
+// checked; a record whose entries point outside it is treated as a miss.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::raw::c_char;
+use std::ptr;
+
+use memmap2::Mmap;
+
+use crate::fingerprint::SudachiFingerprint;
+use crate::inline;
+use crate::script::SudachiScript;
+use crate::symbol::SudachiTokenClass;
+use crate::{c_str, handle, into_token_array, leak, stats, SudachiToken, SudachiTokenizer};
+
+const MAGIC: &[u8; 4] = b"MGXC";
+const FORMAT_VERSION: u32 = 6;
+const HEADER_LEN: usize = 8;
+const FINGERPRINT_LEN: usize = 16;
+const STRING_FIELDS: usize = 6;
+const FIXED_LEN: usize = 28;
+const ENTRY_LEN: usize = FIXED_LEN + STRING_FIELDS * 8;
+const NULL_LEN: u32 = u32::MAX;
+
+/// Location of one cached page inside the file
+#[derive(Clone, Copy)]
+struct Page {
+    entries: usize,
+    count: usize,
+    strings: usize,
+    end: usize,
+    fingerprint: SudachiFingerprint,
+}
+
+/// Fixed fields and strings of one cached token
+struct Entry<'a> {
+    begin: i32,
+    end: i32,
+    word_id: u32,
+    pos_id: u16,
+    script: u8,
+    class: u8,
+    flags: u8,
+    lemma_key: u64,
+    fields: [Option<&'a [u8]>; STRING_FIELDS],
+}
+
+/// Opaque handle to an analysis cache file
+pub struct SudachiCache {
+    file: File,
+    len: u64,
+    map: Option<Mmap>,
+    index: HashMap<String, Page>,
+}
+
+impl SudachiCache {
+    fn open(path: &str) -> std::io::Result<SudachiCache> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let mut len = file.metadata()?.len();
+        if len == 0 {
+            file.write_all(MAGIC)?;
+            file.write_all(&FORMAT_VERSION.to_le_bytes())?;
+            len = HEADER_LEN as u64;
+        } else {
+            let mut header = [0u8; HEADER_LEN];
+            file.seek(SeekFrom::Start(0))?;
+            file.read_exact(&mut header)?;
+            if &header[..4] != MAGIC || read_u32(&header, 4) != Some(FORMAT_VERSION) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "not an analysis cache file",
+                ));
+            }
+        }
+
+        let mut cache = SudachiCache {
+            file,
+            len,
+            map: None,
+            index: HashMap::new(),
+        };
+        cache.rebuild_index()?;
+        Ok(cache)
+    }
+
+    /// Scan record headers to rebuild the key index
+    /// A record cut short by an interrupted append is discarded
+    fn rebuild_index(&mut self) -> std::io::Result<()> {
+        self.remap()?;
+        let data = self.data();
+        let mut index = HashMap::new();
+        let mut pos = HEADER_LEN;
+
+        while let Some((key, page, next)) = parse_record(data, pos) {
+            index.insert(key, page);
+            pos = next;
+        }
+
+        self.index = index;
+        if (pos as u64) < self.len {
+            eprintln!("Discarding truncated analysis cache record at {}", pos);
+            self.map = None;
+            self.file.set_len(pos as u64)?;
+            self.len = pos as u64;
+            self.remap()?;
+        }
+        Ok(())
+    }
+
+    fn remap(&mut self) -> std::io::Result<()> {
+        let mapped = self.map.as_ref().map_or(0, |m| m.len() as u64);
+        if mapped != self.len {
+            self.map = Some(unsafe { Mmap::map(&self.file)? });
+        }
+        Ok(())
+    }
+
+    fn data(&self) -> &[u8] {
+        self.map.as_deref().unwrap_or(&[])
+    }
+
+    fn put(
+        &mut self,
+        key: &str,
+        tokens: &[&SudachiToken],
+        fingerprint: SudachiFingerprint,
+    ) -> std::io::Result<()> {
+        let mut sorted = tokens.to_vec();
+        sorted.sort_by_key(|t| (t.begin, t.end));
+
+        let mut entries = Vec::with_capacity(sorted.len() * ENTRY_LEN);
+        let mut strings = Vec::new();
+        for token in &sorted {
+            entries.extend_from_slice(&token.begin.to_le_bytes());
+            entries.extend_from_slice(&token.end.to_le_bytes());
+            entries.extend_from_slice(&token.word_id.to_le_bytes());
+            entries.extend_from_slice(&token.pos_id.to_le_bytes());
+            entries.push(token.script as u8);
+            entries.push(token.class as u8);
+            entries.extend_from_slice(&[token.flags, 0, 0, 0]);
+            entries.extend_from_slice(&token.lemma_key.to_le_bytes());
+            for field in token_fields(token) {
+                let (offset, len) = if field.is_null() {
+                    (0, NULL_LEN)
+                } else {
+                    let bytes = unsafe { std::ffi::CStr::from_ptr(field) }.to_bytes();
+                    let offset = strings.len() as u32;
+                    strings.extend_from_slice(bytes);
+                    (offset, bytes.len() as u32)
+                };
+                entries.extend_from_slice(&offset.to_le_bytes());
+                entries.extend_from_slice(&len.to_le_bytes());
+            }
+        }
+
+        let body_len = 4 + key.len() + FINGERPRINT_LEN + 4 + entries.len() + strings.len();
+        // Record lengths and string offsets are u32
+        let Ok(body_len) = u32::try_from(body_len) else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "page too large for the cache",
+            ));
+        };
+        let body_len = body_len as usize;
+        let mut record = Vec::with_capacity(4 + body_len);
+        record.extend_from_slice(&(body_len as u32).to_le_bytes());
+        record.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        record.extend_from_slice(key.as_bytes());
+        record.extend_from_slice(&fingerprint.hash.to_le_bytes());
+        record.extend_from_slice(&fingerprint.version.to_le_bytes());
+        record.extend_from_slice(&(sorted.len() as u32).to_le_bytes());
+        record.extend_from_slice(&entries);
+        record.extend_from_slice(&strings);
+
+        let start = self.len as usize;
+        self.file.write_all(&record)?;
+        self.file.flush()?;
+        self.len += record.len() as u64;
+        self.remap()?;
+
+        let entries_at = start + 4 + 4 + key.len() + FINGERPRINT_LEN + 4;
+        self.index.insert(
+            key.to_string(),
+            Page {
+                entries: entries_at,
+                count: sorted.len(),
+                strings: entries_at + sorted.len() * ENTRY_LEN,
+                end: start + record.len(),
+                fingerprint,
+            },
+        );
+        Ok(())
+    }
+
+    /// Read entry i of a page, None if it reaches outside the record
+    fn entry(&self, page: Page, i: usize) -> Option<Entry<'_>> {
+        let data = self.data().get(..page.end)?;
+        let at = page.entries.checked_add(i.checked_mul(ENTRY_LEN)?)?;
+        let entry = data.get(at..at.checked_add(ENTRY_LEN)?)?;
+        let mut fields: [Option<&[u8]>; STRING_FIELDS] = [None; STRING_FIELDS];
+        for (f, field) in fields.iter_mut().enumerate() {
+            let at = FIXED_LEN + f * 8;
+            let len = read_u32(entry, at + 4)?;
+            if len == NULL_LEN {
+                continue;
+            }
+            let start = page.strings.checked_add(read_u32(entry, at)? as usize)?;
+            *field = Some(data.get(start..start.checked_add(len as usize)?)?);
+        }
+        Some(Entry {
+            begin: read_i32(entry, 0)?,
+            end: read_i32(entry, 4)?,
+            word_id: read_u32(entry, 8)?,
+            pos_id: read_u16(entry, 12)?,
+            script: entry[14],
+            class: entry[15],
+            flags: entry[16],
+            lemma_key: read_u64(entry, 20)?,
+            fields,
+        })
+    }
+
+    /// Read all entries of a page, None if any is malformed
+    fn entries(&self, page: Page) -> Option<Vec<Entry<'_>>> {
+        (0..page.count).map(|i| self.entry(page, i)).collect()
+    }
+
+    fn page(&self, key: &str) -> Option<Page> {
+        self.index.get(key).copied()
+    }
+
+    /// Binary search the page entries for the token covering offset
+    /// None if the page does not fit the map
+    fn find(&self, page: Page, offset: i32) -> Option<Option<usize>> {
+        let data = self.data().get(..page.end)?;
+        data.get(page.entries..page.strings)?;
+        let begin = |i: usize| read_i32(data, page.entries + i * ENTRY_LEN);
+        let end = |i: usize| read_i32(data, page.entries + i * ENTRY_LEN + 4);
+
+        let (mut lo, mut hi) = (0, page.count);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if begin(mid)? <= offset {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        if lo > 0 && offset < end(lo - 1)? {
+            Some(Some(lo - 1))
+        } else {
+            Some(None)
+        }
+    }
+}
+
+/// Allocate the token of an entry
+fn decode(entry: Entry) -> *mut SudachiToken {
+    let heap = |field: Option<&[u8]>| {
+        field
+            .and_then(|bytes| CString::new(bytes).ok())
+            .map_or(ptr::null_mut(), CString::into_raw)
+    };
+
+    let [surface, reading, dictionary_form, normalized_form, pos, dictionary_reading] =
+        entry.fields;
+    let token = handle::into_raw(SudachiToken {
+        surface: ptr::null_mut(),
+        reading: ptr::null_mut(),
+        dictionary_form: heap(dictionary_form),
+        normalized_form: heap(normalized_form),
+        pos: heap(pos),
+        begin: entry.begin,
+        end: entry.end,
+        word_id: entry.word_id,
+        pos_id: entry.pos_id,
+        script: SudachiScript::from_tag(entry.script),
+        class: SudachiTokenClass::from_tag(entry.class),
+        flags: entry.flags,
+        dictionary_reading: heap(dictionary_reading),
+        lemma_key: entry.lemma_key,
+        inline_surface: inline::EMPTY,
+        inline_reading: inline::EMPTY,
+    });
+    let t = unsafe { &mut *token };
+    if let Some(surface) = surface {
+        t.surface = inline::store(&mut t.inline_surface, surface);
+    }
+    if let Some(reading) = reading {
+        t.reading = inline::store(&mut t.inline_reading, reading);
+    }
+    token
+}
+
+fn token_fields(token: &SudachiToken) -> [*mut c_char; STRING_FIELDS] {
+    [
+        token.surface,
+        token.reading,
+        token.dictionary_form,
+        token.normalized_form,
+        token.pos,
+        token.dictionary_reading,
+    ]
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(at..at.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(at..at.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_i32(data: &[u8], at: usize) -> Option<i32> {
+    Some(i32::from_le_bytes(
+        data.get(at..at.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(at..at.checked_add(8)?)?.try_into().ok()?,
+    ))
+}
+
+/// Parse the record starting at pos, returning its key, page and the next
+/// record position. Returns None at end of data or on a malformed record.
+fn parse_record(data: &[u8], pos: usize) -> Option<(String, Page, usize)> {
+    let body = pos.checked_add(4)?;
+    let next = body.checked_add(read_u32(data, pos)? as usize)?;
+    let record = data.get(..next)?;
+
+    let key_len = read_u32(record, body)? as usize;
+    let key_end = (body + 4).checked_add(key_len)?;
+    let count_at = key_end.checked_add(FINGERPRINT_LEN)?;
+    let key = std::str::from_utf8(record.get(body + 4..key_end)?).ok()?;
+    let fingerprint = SudachiFingerprint {
+        hash: read_u64(record, key_end)?,
+        version: read_u64(record, key_end + 8)?,
+    };
+    let count = read_u32(record, count_at)? as usize;
+    let entries = count_at + 4;
+    let strings = entries.checked_add(count.checked_mul(ENTRY_LEN)?)?;
+    if strings > next {
+        return None;
+    }
+
+    let page = Page {
+        entries,
+        count,
+        strings,
+        end: next,
+        fingerprint,
+    };
+    Some((key.to_string(), page, next))
+}
+
+/// Open (or create) an analysis cache file
+/// Returns NULL on failure
+#[no_mangle]
+pub extern "C" fn sudachi_cache_open(path: *const c_char) -> *mut SudachiCache {
+    let path = match c_str(path) {
+        Some(p) => p,
+        None => return ptr::null_mut(),
+    };
+
+    match SudachiCache::open(path) {
+        Ok(cache) => {
+            let cache = Box::into_raw(Box::new(cache));
+            leak::track(cache, "SudachiCache");
+            cache
+        }
+        Err(e) => {
+            eprintln!("Failed to open analysis cache {}: {}", path, e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Append the tokens of one page under key
+/// tokenizer is the handle that produced the tokens; pages stored with NULL
+/// are never reported compatible. Returns false on failure
+#[no_mangle]
+pub extern "C" fn sudachi_cache_put(
+    cache: *mut SudachiCache,
+    key: *const c_char,
+    tokens: *const *mut SudachiToken,
+    count: usize,
+    tokenizer: *const SudachiTokenizer,
+) -> bool {
+    if cache.is_null() || (tokens.is_null() && count > 0) {
+        return false;
+    }
+    let key = match c_str(key) {
+        Some(k) => k,
+        None => return false,
+    };
+
+    let cache = unsafe { &mut *cache };
+    let tokens: Vec<&SudachiToken> = if count == 0 {
+        Vec::new()
+    } else {
+        unsafe { std::slice::from_raw_parts(tokens, count) }
+            .iter()
+            .filter(|t| !t.is_null())
+            .map(|&t| unsafe { handle::borrow(t) })
+            .collect()
+    };
+
+    let fingerprint = if tokenizer.is_null() {
+        SudachiFingerprint::default()
+    } else {
+        unsafe { handle::borrow(tokenizer) }.fingerprint
+    };
+    match cache.put(key, &tokens, fingerprint) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to write analysis cache: {}", e);
+            false
+        }
+    }
+}
+
+/// Check whether a page is cached
+#[no_mangle]
+pub extern "C" fn sudachi_cache_contains(cache: *const SudachiCache, key: *const c_char) -> bool {
+    if cache.is_null() {
+        return false;
+    }
+    let cache = unsafe { &*cache };
+    c_str(key).is_some_and(|k| cache.index.contains_key(k))
+}
+
+/// Check whether a cached page was produced with a handle's dictionary
+/// Returns false if the page is not cached, was produced with another
+/// dictionary (re-run and put it again), or on NULL arguments
+#[no_mangle]
+pub extern "C" fn sudachi_cache_compatible(
+    cache: *const SudachiCache,
+    key: *const c_char,
+    tokenizer: *const SudachiTokenizer,
+) -> bool {
+    if cache.is_null() || tokenizer.is_null() {
+        return false;
+    }
+    let cache = unsafe { &*cache };
+    let fingerprint = unsafe { handle::borrow(tokenizer) }.fingerprint;
+    c_str(key)
+        .and_then(|k| cache.index.get(k))
+        .is_some_and(|page| page.fingerprint.matches(fingerprint))
+}
+
+/// Decode all tokens of a cached page
+/// Returns NULL if the page is not cached or its record is malformed (caller
+/// must free with sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_cache_get(
+    cache: *const SudachiCache,
+    key: *const c_char,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    if cache.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let cache = unsafe { &*cache };
+    let entries = c_str(key)
+        .and_then(|k| cache.page(k))
+        .and_then(|page| cache.entries(page));
+    stats::record_cache_lookup(entries.is_some());
+    let Some(entries) = entries else {
+        return ptr::null_mut();
+    };
+
+    let tokens = entries.into_iter().map(decode).collect();
+    into_token_array(tokens, out_count)
+}
+
+/// Decode only the token of a cached page covering a byte offset
+/// Returns NULL if the page is not cached, its record is malformed or no
+/// token covers the offset (caller must free with sudachi_free_token)
+#[no_mangle]
+pub extern "C" fn sudachi_cache_token_at(
+    cache: *const SudachiCache,
+    key: *const c_char,
+    offset: i32,
+) -> *mut SudachiToken {
+    if cache.is_null() {
+        return ptr::null_mut();
+    }
+    let cache = unsafe { &*cache };
+    let found =
+        c_str(key)
+            .and_then(|k| cache.page(k))
+            .and_then(|page| match cache.find(page, offset)? {
+                Some(i) => cache.entry(page, i).map(Some),
+                None => Some(None),
+            });
+    stats::record_cache_lookup(found.is_some());
+    found.flatten().map_or(ptr::null_mut(), decode)
+}
+
+/// Close an analysis cache
+#[no_mangle]
+pub extern "C" fn sudachi_cache_close(cache: *mut SudachiCache) {
+    if !cache.is_null() {
+        leak::release(cache);
+        unsafe {
+            let _ = Box::from_raw(cache);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{into_c_string, sudachi_free_token, sudachi_free_tokens};
+
+    fn token(surface: &str, begin: i32, end: i32) -> SudachiToken {
+        SudachiToken {
+            surface: into_c_string(surface),
+            reading: ptr::null_mut(),
+            dictionary_form: into_c_string(surface),
+            normalized_form: into_c_string(surface),
+            pos: into_c_string("[]"),
+            begin,
+            end,
+            word_id: 7,
+            pos_id: 3,
+            script: SudachiScript::Japanese,
+            class: SudachiTokenClass::Word,
+            flags: 0,
+            dictionary_reading: ptr::null_mut(),
+            lemma_key: 0,
+            inline_surface: inline::EMPTY,
+            inline_reading: inline::EMPTY,
+        }
+    }
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let path = std::env::temp_dir().join(format!("mgx-cache-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let key = CString::new("page-1").unwrap();
+
+        let tokens: Vec<*mut SudachiToken> = vec![
+            handle::into_raw(token("今日", 0, 6)),
+            handle::into_raw(token("は", 6, 9)),
+        ];
+        let cache = sudachi_cache_open(path.as_ptr());
+        assert!(sudachi_cache_put(
+            cache,
+            key.as_ptr(),
+            tokens.as_ptr(),
+            tokens.len(),
+            ptr::null()
+        ));
+        let fingerprint = SudachiFingerprint {
+            hash: 0x5eed,
+            version: 2,
+        };
+        let stamped = [unsafe { &*tokens[0] }];
+        let other = CString::new("page-2").unwrap();
+        unsafe { &mut *cache }
+            .put("page-2", &stamped, fingerprint)
+            .unwrap();
+        sudachi_cache_close(cache);
+
+        let cache = sudachi_cache_open(path.as_ptr());
+        assert!(sudachi_cache_contains(cache, key.as_ptr()));
+        let index = &unsafe { &*cache }.index;
+        assert!(!index["page-1"].fingerprint.matches(fingerprint));
+        assert!(index["page-2"].fingerprint.matches(fingerprint));
+        assert!(sudachi_cache_contains(cache, other.as_ptr()));
+
+        let hit = sudachi_cache_token_at(cache, key.as_ptr(), 7);
+        let hit_ref = unsafe { &*hit };
+        assert_eq!((hit_ref.begin, hit_ref.end), (6, 9));
+        assert_eq!((hit_ref.word_id, hit_ref.pos_id), (7, 3));
+        assert_eq!(c_str(hit_ref.surface), Some("は"));
+        assert!(hit_ref.reading.is_null());
+        sudachi_free_token(hit);
+        assert!(sudachi_cache_token_at(cache, key.as_ptr(), 9).is_null());
+
+        let mut count = 0;
+        let all = sudachi_cache_get(cache, key.as_ptr(), &mut count);
+        assert_eq!(count, 2);
+        sudachi_free_tokens(all, count);
+
+        sudachi_cache_close(cache);
+        for t in tokens {
+            sudachi_free_token(t);
+        }
+        let _ = std::fs::remove_file(path.to_str().unwrap());
+    }
+
+    #[test]
+    fn test_cache_malformed() {
+        let path = std::env::temp_dir().join(format!("mgx-cache-bad-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let key = CString::new("page").unwrap();
+
+        let token = handle::into_raw(token("今日", 0, 6));
+        let cache = sudachi_cache_open(c_path.as_ptr());
+        assert!(sudachi_cache_put(
+            cache,
+            key.as_ptr(),
+            &token,
+            1,
+            ptr::null()
+        ));
+        sudachi_cache_close(cache);
+        sudachi_free_token(token);
+
+        // Point the surface string far past the end of the record
+        let mut bytes = std::fs::read(&path).unwrap();
+        let entry = HEADER_LEN + 4 + 4 + 4 + FINGERPRINT_LEN + 4;
+        bytes[entry + FIXED_LEN..entry + FIXED_LEN + 4]
+            .copy_from_slice(&0x7fff_0000u32.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+
+        let cache = sudachi_cache_open(c_path.as_ptr());
+        assert!(sudachi_cache_contains(cache, key.as_ptr()));
+        let mut count = 0;
+        assert!(sudachi_cache_get(cache, key.as_ptr(), &mut count).is_null());
+        assert!(sudachi_cache_token_at(cache, key.as_ptr(), 0).is_null());
+        sudachi_cache_close(cache);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,115 @@
+// Chunked analysis of long texts
+// Lattice memory grows with the length of the input, so a whole chapter
+// passed in one call can exhaust memory. Texts longer than the handle's chunk
+// size are analyzed piece by piece instead: chunks end after a sentence
+// terminator (plus any closing brackets) where possible, and token offsets
+// are shifted back so results look like a single analysis of the whole text.
+
+use crate::{handle, SudachiTokenizer};
+
+/// Default chunk size of a handle in bytes
+pub const SUDACHI_DEFAULT_CHUNK_BYTES: usize = 32 * 1024;
+
+const TERMINATORS: &[char] = &['。', '．', '！', '？', '!', '?', '\n'];
+const CLOSERS: &[char] = &['」', '』', '）', ')', '】', '》', '〉', '”', '’', '"'];
+
+/// Pieces of a text with their byte offsets, see split()
+pub(crate) struct Chunks<'a> {
+    text: &'a str,
+    max: usize,
+    offset: usize,
+}
+
+/// Split text into chunks of at most max bytes
+/// A max of 0 disables splitting. A chunk ends mid-sentence only when a
+/// single sentence is longer than max, and always holds at least one char.
+pub(crate) fn split(text: &str, max: usize) -> Chunks<'_> {
+    Chunks {
+        text,
+        max,
+        offset: 0,
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        let rest = &self.text[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+        let len = if self.max == 0 || rest.len() <= self.max {
+            rest.len()
+        } else {
+            cut(rest, self.max)
+        };
+        let chunk = (self.offset, &rest[..len]);
+        self.offset += len;
+        Some(chunk)
+    }
+}
+
+/// Length of the first chunk of a text longer than max
+pub(crate) fn cut(text: &str, max: usize) -> usize {
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    match sentence_end(&text[..end]) {
+        Some(end) => end,
+        None if end > 0 => end,
+        None => text.chars().next().map_or(text.len(), char::len_utf8),
+    }
+}
+
+/// End of the last complete sentence in window, including closing brackets
+fn sentence_end(window: &str) -> Option<usize> {
+    let (i, c) = window
+        .char_indices()
+        .rev()
+        .find(|(_, c)| TERMINATORS.contains(c))?;
+    let end = i + c.len_utf8();
+    let after = &window[end..];
+    Some(end + after.len() - after.trim_start_matches(CLOSERS).len())
+}
+
+/// Set the chunk size used for long texts on a handle
+/// Texts longer than bytes are analyzed in sentence-aligned chunks; 0
+/// analyzes every text in one piece. Returns false if tokenizer is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_chunk_bytes(tokenizer: *mut SudachiTokenizer, bytes: usize) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.chunk_bytes = bytes);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let text = "今日は晴れ。「明日は？」雨だろう。";
+        let chunks: Vec<_> = split(text, 24).collect();
+        assert_eq!(
+            chunks,
+            [
+                (0, "今日は晴れ。"),
+                (18, "「明日は？」"),
+                (36, "雨だろう。")
+            ]
+        );
+
+        // No terminator within the limit: cut on a char boundary
+        let chunks: Vec<_> = split("あいうえお", 7).collect();
+        assert_eq!(chunks, [(0, "あい"), (6, "うえ"), (12, "お")]);
+        assert_eq!(split("あ", 1).collect::<Vec<_>>(), [(0, "あ")]);
+
+        assert_eq!(split(text, 0).count(), 1);
+        assert_eq!(split(text, text.len()).count(), 1);
+        assert_eq!(split("", 16).count(), 0);
+    }
+}
@@ -0,0 +1,168 @@
+// Struct-of-arrays results
+// Offsets and ids come back as parallel arrays and all strings share one
+// blob addressed by an index table, so bridging a large result is a handful
+// of bulk copies instead of thousands of small structs with six pointers.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::packed::{StringBlob, SudachiStrRef};
+use crate::{c_str, handle, SudachiTokenMode, SudachiTokenizer, TokenData};
+
+/// Number of string index entries per token:
+/// surface, reading, dictionary form, normalized form, POS (JSON),
+/// dictionary reading
+pub const SUDACHI_COLUMN_STRINGS: usize = 6;
+
+/// Struct-of-arrays result
+/// All arrays hold count entries and are owned by the result; free the whole
+/// result with sudachi_free_columns.
+#[repr(C)]
+pub struct SudachiColumns {
+    pub count: usize,
+    pub begins: *mut u32,
+    pub ends: *mut u32,
+    pub word_ids: *mut u32,
+    pub pos_ids: *mut u16,
+    /// 0 unless SUDACHI_FIELD_LEMMA_KEY is selected
+    pub lemma_keys: *mut u64,
+    /// String blob (strings are not NUL-terminated)
+    pub strings: *mut u8,
+    pub string_bytes: usize,
+    /// count * SUDACHI_COLUMN_STRINGS references into strings
+    pub string_index: *mut SudachiStrRef,
+}
+
+fn leak<T>(v: Vec<T>) -> *mut T {
+    Box::leak(v.into_boxed_slice()).as_mut_ptr()
+}
+
+unsafe fn free<T>(ptr: *mut T, len: usize) {
+    if !ptr.is_null() {
+        let _ = Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len));
+    }
+}
+
+/// Lay tokens out as parallel arrays, None if the blob outgrows 32-bit
+/// offsets
+fn columns(tokens: &[TokenData]) -> Option<SudachiColumns> {
+    let mut blob = StringBlob::default();
+    let mut string_index = Vec::with_capacity(tokens.len() * SUDACHI_COLUMN_STRINGS);
+    for token in tokens {
+        string_index.extend(blob.push_token(token));
+    }
+    if !blob.addressable() {
+        return None;
+    }
+
+    Some(SudachiColumns {
+        count: tokens.len(),
+        begins: leak(tokens.iter().map(|t| t.begin as u32).collect()),
+        ends: leak(tokens.iter().map(|t| t.end as u32).collect()),
+        word_ids: leak(tokens.iter().map(|t| t.word_id).collect()),
+        pos_ids: leak(tokens.iter().map(|t| t.pos_id).collect()),
+        lemma_keys: leak(tokens.iter().map(|t| t.lemma_key).collect()),
+        string_bytes: blob.bytes.len(),
+        strings: leak(blob.bytes),
+        string_index: leak(string_index),
+    })
+}
+
+/// Tokenize text into parallel arrays
+/// Only the string fields selected by the SUDACHI_FIELD_* mask are stored
+/// in the blob; unselected entries have len = SUDACHI_PACKED_NULL.
+/// Returns NULL on failure (caller must free with sudachi_free_columns)
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_columns(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    fields: u32,
+) -> *mut SudachiColumns {
+    if tokenizer.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+    let tokens = match tokenizer.analyze(text, mode.into(), fields) {
+        Ok(tokens) => tokens,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let Some(columns) = columns(&tokens) else {
+        return ptr::null_mut();
+    };
+    let columns = Box::into_raw(Box::new(columns));
+    crate::leak::track(columns, "columns");
+    columns
+}
+
+/// Free a struct-of-arrays result
+#[no_mangle]
+pub extern "C" fn sudachi_free_columns(columns: *mut SudachiColumns) {
+    if columns.is_null() {
+        return;
+    }
+
+    crate::leak::release(columns);
+    unsafe {
+        let c = Box::from_raw(columns);
+        free(c.begins, c.count);
+        free(c.ends, c.count);
+        free(c.word_ids, c.count);
+        free(c.pos_ids, c.count);
+        free(c.lemma_keys, c.count);
+        free(c.strings, c.string_bytes);
+        free(c.string_index, c.count * SUDACHI_COLUMN_STRINGS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packed::SUDACHI_PACKED_NULL;
+    use crate::{testtoken, SUDACHI_FIELD_ALL, SUDACHI_FIELD_POS, SUDACHI_FIELD_READING};
+
+    #[test]
+    fn test_columns() {
+        let tokens = vec![
+            testtoken::word(0, "猫"),
+            TokenData {
+                fields: SUDACHI_FIELD_ALL & !SUDACHI_FIELD_READING & !SUDACHI_FIELD_POS,
+                word_id: 9,
+                ..testtoken::word(3, "が")
+            },
+        ];
+        let columns = Box::into_raw(Box::new(columns(&tokens).unwrap()));
+        let c = unsafe { &*columns };
+        assert_eq!(c.count, 2);
+        let array = |ptr: *mut u32| unsafe { std::slice::from_raw_parts(ptr, c.count) };
+        assert_eq!(array(c.begins), [0, 3]);
+        assert_eq!(array(c.ends), [3, 6]);
+        assert_eq!(array(c.word_ids), [0, 9]);
+
+        let strings = unsafe { std::slice::from_raw_parts(c.strings, c.string_bytes) };
+        let index =
+            unsafe { std::slice::from_raw_parts(c.string_index, c.count * SUDACHI_COLUMN_STRINGS) };
+        let string = |i: usize| {
+            let r = index[i];
+            let range = r.offset as usize..(r.offset + r.len) as usize;
+            std::str::from_utf8(&strings[range]).unwrap()
+        };
+        // Surface, reading, dictionary form, normalized form, POS, dictionary
+        // reading for each token in turn
+        assert_eq!(string(0), "猫");
+        assert_eq!(string(2), "猫");
+        assert_eq!(string(4), "[]");
+        assert_eq!(string(SUDACHI_COLUMN_STRINGS), "が");
+        assert_eq!(index[SUDACHI_COLUMN_STRINGS + 1].len, SUDACHI_PACKED_NULL);
+        assert_eq!(index[SUDACHI_COLUMN_STRINGS + 4].len, SUDACHI_PACKED_NULL);
+        assert_eq!(string(SUDACHI_COLUMN_STRINGS + 3), "が");
+
+        sudachi_free_columns(columns);
+        sudachi_free_columns(ptr::null_mut());
+    }
+}
@@ -0,0 +1,115 @@
+// Compound verbs
+// Sudachi splits most compound verbs into the first verb in its continuative
+// form and an auxiliary verb (食べ + 始める, 飛び + 込む), even in B mode, so the
+// popup looks up 始める instead of 食べ始める. With the option on, a verb in
+// 連用形 followed by a verb tagged 非自立可能 is joined into one token whose
+// dictionary form is the compound lemma, flagged SUDACHI_TOKEN_COMPOUND.
+
+use crate::{
+    handle, SudachiTokenizer, TokenData, SUDACHI_FIELD_DICTIONARY_FORM,
+    SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_NORMALIZED_FORM, SUDACHI_FIELD_POS,
+    SUDACHI_FIELD_READING, SUDACHI_FIELD_SURFACE, SUDACHI_TOKEN_COMPOUND,
+};
+
+/// Verb in its continuative form (食べ, 飛び), which can take an auxiliary
+pub(crate) fn is_continuative(pos: &[String]) -> bool {
+    pos.first().is_some_and(|p| p == "動詞") && pos.get(5).is_some_and(|f| f.starts_with("連用形"))
+}
+
+/// Verb that can follow another verb as its auxiliary (始める, 込む, 出す)
+pub(crate) fn is_auxiliary(pos: &[String]) -> bool {
+    pos.first().is_some_and(|p| p == "動詞") && pos.get(1).is_some_and(|p| p == "非自立可能")
+}
+
+/// Join an auxiliary verb onto the verb before it
+/// Offsets are relative to text; stem is the reading of verb. Keeps the word
+/// info of verb; the dictionary and normalized forms become the surface of
+/// verb followed by those of auxiliary, and the conjugation columns of the
+/// POS come from pos, the POS of auxiliary.
+pub(crate) fn join(
+    text: &str,
+    verb: TokenData,
+    stem: &str,
+    auxiliary: TokenData,
+    pos: &[String],
+) -> TokenData {
+    let head = &text[verb.begin..verb.end];
+    let mut token = TokenData {
+        flags: verb.flags | auxiliary.flags | SUDACHI_TOKEN_COMPOUND,
+        end: auxiliary.end,
+        ..verb
+    };
+    let has = |bit: u32| token.fields & bit != 0;
+    if has(SUDACHI_FIELD_SURFACE) {
+        token.surface = text[token.begin..token.end].to_string();
+    }
+    if has(SUDACHI_FIELD_READING) {
+        token.reading += &auxiliary.reading;
+    }
+    if has(SUDACHI_FIELD_DICTIONARY_FORM) {
+        token.dictionary_form = format!("{}{}", head, auxiliary.dictionary_form);
+    }
+    if has(SUDACHI_FIELD_DICTIONARY_READING) {
+        token.dictionary_reading = format!("{}{}", stem, auxiliary.dictionary_reading);
+    }
+    if has(SUDACHI_FIELD_NORMALIZED_FORM) {
+        token.normalized_form = format!("{}{}", head, auxiliary.normalized_form);
+    }
+    if has(SUDACHI_FIELD_POS) {
+        token.pos.truncate(4);
+        token.pos.extend(pos.iter().skip(4).cloned());
+    }
+    token
+}
+
+/// Enable or disable joining compound verbs on a handle
+/// Applies in modes B and C; disabled by default. Returns false if tokenizer
+/// is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_compound_verbs(
+    tokenizer: *mut SudachiTokenizer,
+    enabled: bool,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.compound_verbs = enabled);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testtoken::word;
+
+    fn token(begin: usize, surface: &str, reading: &str, base: &str, pos: &[&str]) -> TokenData {
+        let mut token = word(begin, surface);
+        token.reading = reading.to_string();
+        token.dictionary_form = base.to_string();
+        token.normalized_form = base.to_string();
+        token.dictionary_reading = reading.to_string();
+        token.pos = pos.iter().map(|p| p.to_string()).collect();
+        token
+    }
+
+    #[test]
+    fn test_join() {
+        let verb = ["動詞", "一般", "*", "*", "下一段-バ行", "連用形-一般"];
+        let auxiliary = ["動詞", "非自立可能", "*", "*", "下一段-マ行", "連用形-一般"];
+        let text = "食べ始めた";
+        let first = token(0, "食べ", "タベ", "食べる", &verb);
+        let second = token(6, "始め", "ハジメ", "始める", &auxiliary);
+        let pos = second.pos.clone();
+        assert!(is_continuative(&first.pos) && is_auxiliary(&pos));
+        assert!(!is_auxiliary(&first.pos));
+
+        let joined = join(text, first, "タベ", second, &pos);
+        assert_eq!(joined.surface, "食べ始め");
+        assert_eq!(joined.dictionary_form, "食べ始める");
+        assert_eq!(joined.reading, "タベハジメ");
+        assert_eq!(joined.pos[1], "一般");
+        assert_eq!(joined.pos[4], "下一段-マ行");
+        assert_eq!((joined.begin, joined.end), (0, 12));
+        assert!(joined.flags & SUDACHI_TOKEN_COMPOUND != 0);
+    }
+}
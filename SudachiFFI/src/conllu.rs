@@ -0,0 +1,242 @@
+// CoNLL-U output
+// Lets standard NLP tooling read our analysis and compare it against the UD
+// Japanese treebanks. Each sentence (split as in sentences.rs) becomes a
+// CoNLL-U block; whitespace tokens are dropped and mark SpaceAfter on the
+// token before them.
+//
+// UPOS follows the UD Japanese mapping of UniDic-style POS. Auxiliary-like
+// verbs and adjectives (いる, ない after a stem) are AUX when they attach to
+// a content word, VERB or ADJ when they head a display unit.
+//
+// Heads are pseudo-heads derived from display units, not a parse: function
+// tokens and prefixes attach to the content word of their unit (case, aux,
+// mark, compound by UPOS), the content word attaches to the content word of
+// the unit it modifies (dep), punctuation to the root (punct), and the last
+// word unit of the sentence is the root.
+
+use std::fmt::Write;
+
+use crate::bunsetsu::{group, heads, is_prefix};
+use crate::sentences::sentences;
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    TokenData, SUDACHI_FIELD_DICTIONARY_FORM, SUDACHI_FIELD_POS, SUDACHI_FIELD_READING,
+    SUDACHI_FIELD_SURFACE,
+};
+
+/// Fields CoNLL-U output needs from the analyzer
+pub(crate) const CONLLU_FIELDS: u32 = SUDACHI_FIELD_SURFACE
+    | SUDACHI_FIELD_READING
+    | SUDACHI_FIELD_DICTIONARY_FORM
+    | SUDACHI_FIELD_POS;
+
+/// Universal POS tag of a token; content is whether it heads its unit
+fn upos(token: &TokenData, content: bool) -> &'static str {
+    match token.class {
+        SudachiTokenClass::Emoji => return "SYM",
+        SudachiTokenClass::Opaque => return "X",
+        _ => {}
+    }
+    let pos = |i: usize| token.pos.get(i).map_or("", String::as_str);
+    match (pos(0), pos(1)) {
+        ("名詞", "固有名詞") => "PROPN",
+        ("名詞", "数詞") => "NUM",
+        ("名詞", _) | ("接頭辞", _) => "NOUN",
+        ("代名詞", _) => "PRON",
+        ("形状詞", "助動詞語幹") => "AUX",
+        ("形状詞", _) => "ADJ",
+        ("連体詞", _) => "DET",
+        ("副詞", _) => "ADV",
+        ("接続詞", _) => "CCONJ",
+        ("感動詞", _) => "INTJ",
+        ("動詞" | "形容詞", "非自立可能") if !content => "AUX",
+        ("動詞", _) => "VERB",
+        ("形容詞", _) => "ADJ",
+        ("助動詞", _) => "AUX",
+        ("助詞", "接続助詞" | "準体助詞") => "SCONJ",
+        ("助詞", "終助詞") => "PART",
+        ("助詞", _) => "ADP",
+        ("接尾辞", "形状詞的" | "形容詞的") => "ADJ",
+        ("接尾辞", "動詞的") => "VERB",
+        ("接尾辞", _) => "NOUN",
+        ("補助記号", "句点" | "読点" | "括弧開" | "括弧閉") => "PUNCT",
+        _ => "SYM",
+    }
+}
+
+/// Relation of a token to the content word of its own unit
+fn function_deprel(upos: &str) -> &'static str {
+    match upos {
+        "ADP" => "case",
+        "AUX" => "aux",
+        "SCONJ" | "PART" => "mark",
+        _ => "compound",
+    }
+}
+
+/// Sudachi POS columns joined with '-', without unset columns
+fn xpos(token: &TokenData) -> String {
+    let columns: Vec<&str> = token.pos[..token.pos.len().min(4)]
+        .iter()
+        .map(String::as_str)
+        .filter(|p| *p != "*")
+        .collect();
+    if columns.is_empty() {
+        "_".to_string()
+    } else {
+        columns.join("-")
+    }
+}
+
+/// CoNLL-U field: "_" for empty, spaces and tabs replaced
+fn field(s: &str) -> String {
+    if s.is_empty() {
+        "_".to_string()
+    } else {
+        s.replace(['\t', ' '], "_")
+    }
+}
+
+/// Write one sentence; tokens must not contain whitespace
+fn write_sentence(out: &mut String, text: &str, tokens: &[TokenData], id: usize) {
+    let units = group(tokens);
+    let unit_heads = heads(tokens, &units);
+    let word = |u: usize| tokens[units[u].0].class == SudachiTokenClass::Word;
+    // Content token of each unit: its first token that is not a prefix
+    let content: Vec<usize> = units
+        .iter()
+        .map(|&(first, count)| {
+            (first..first + count)
+                .find(|&i| !is_prefix(&tokens[i].pos))
+                .unwrap_or(first)
+        })
+        .collect();
+    let root_unit = (0..units.len())
+        .rev()
+        .find(|&u| word(u) && unit_heads[u] < 0)
+        .unwrap_or(0);
+    let root = content[root_unit];
+
+    let begin = tokens.first().map_or(0, |t| t.begin);
+    let end = tokens.last().map_or(0, |t| t.end);
+    let _ = writeln!(out, "# sent_id = {}", id);
+    let _ = writeln!(out, "# text = {}", text[begin..end].replace('\n', " "));
+    for (u, &(first, count)) in units.iter().enumerate() {
+        for (i, token) in tokens.iter().enumerate().skip(first).take(count) {
+            let is_content = i == content[u];
+            let upos = upos(token, is_content);
+            let (head, deprel) = if i == root {
+                (0, "root")
+            } else if !is_content {
+                (content[u] + 1, function_deprel(upos))
+            } else if !word(u) {
+                (root + 1, if upos == "PUNCT" { "punct" } else { "dep" })
+            } else if unit_heads[u] >= 0 {
+                (content[unit_heads[u] as usize] + 1, "dep")
+            } else {
+                (root + 1, "dep")
+            };
+            let space_after = text[token.end..]
+                .chars()
+                .next()
+                .is_some_and(char::is_whitespace);
+            let _ = writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t_\t{}\t{}\t_\t{}BunsetuBILabel={}{}",
+                i + 1,
+                field(&token.surface),
+                field(&token.dictionary_form),
+                upos,
+                xpos(token),
+                head,
+                deprel,
+                if token.reading.is_empty() {
+                    String::new()
+                } else {
+                    format!("Reading={}|", field(&token.reading))
+                },
+                if i == first { "B" } else { "I" },
+                if space_after { "" } else { "|SpaceAfter=No" },
+            );
+        }
+    }
+    out.push('\n');
+}
+
+/// Write the sentences of one analyzed line, numbering them from *id
+pub(crate) fn write_line(out: &mut String, text: &str, tokens: Vec<TokenData>, id: &mut usize) {
+    for range in sentences(&tokens, 0) {
+        let sentence: Vec<TokenData> = tokens[range]
+            .iter()
+            .filter(|t| t.class != SudachiTokenClass::Whitespace)
+            .cloned()
+            .collect();
+        if !sentence.is_empty() {
+            *id += 1;
+            write_sentence(out, text, &sentence, *id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+
+    fn tokens_of(words: &[(&str, &str)]) -> Vec<TokenData> {
+        let mut begin = 0;
+        words
+            .iter()
+            .map(|&(surface, pos)| {
+                let mut token =
+                    language::opaque(begin, surface, SudachiScript::Japanese, CONLLU_FIELDS);
+                token.class = crate::symbol::token_class(surface);
+                token.pos = pos.split(',').map(str::to_string).collect();
+                begin += surface.len();
+                token
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_line() {
+        let tokens = tokens_of(&[
+            ("猫", "名詞,普通名詞,一般,*,*,*"),
+            ("が", "助詞,格助詞,*,*,*,*"),
+            ("魚", "名詞,普通名詞,一般,*,*,*"),
+            ("を", "助詞,格助詞,*,*,*,*"),
+            ("食べ", "動詞,一般,*,*,下一段-バ行,連用形-一般"),
+            ("て", "助詞,接続助詞,*,*,*,*"),
+            ("いる", "動詞,非自立可能,*,*,上一段-ア行,終止形-一般"),
+            ("。", "補助記号,句点,*,*,*,*"),
+        ]);
+        let mut out = String::new();
+        let mut id = 0;
+        write_line(&mut out, "猫が魚を食べている。", tokens, &mut id);
+        let rows: Vec<Vec<&str>> = out
+            .lines()
+            .filter(|l| !l.starts_with('#') && !l.is_empty())
+            .map(|l| l.split('\t').collect())
+            .collect();
+        assert!(out.starts_with("# sent_id = 1\n# text = 猫が魚を食べている。\n"));
+        // (UPOS, HEAD, DEPREL) of every token
+        let columns: Vec<(&str, &str, &str)> = rows.iter().map(|r| (r[3], r[6], r[7])).collect();
+        assert_eq!(
+            columns,
+            [
+                ("NOUN", "5", "dep"),
+                ("ADP", "1", "case"),
+                ("NOUN", "5", "dep"),
+                ("ADP", "3", "case"),
+                ("VERB", "0", "root"),
+                ("SCONJ", "5", "mark"),
+                ("AUX", "5", "aux"),
+                ("PUNCT", "5", "punct"),
+            ]
+        );
+        assert_eq!(rows[0][4], "名詞-普通名詞-一般");
+        assert_eq!(rows[1][9], "BunsetuBILabel=I|SpaceAfter=No");
+        assert_eq!(id, 1);
+    }
+}
@@ -0,0 +1,175 @@
+// Caller-held analysis contexts
+// The reader tokenizes the visible lines on every frame while scrolling fast,
+// and each call used to allocate its result: one struct and up to six C
+// strings per token, freed again a frame later. A context keeps the scratch
+// memory of such calls alive between them, owned by the caller (one per queue
+// or thread): its own lattice and morpheme buffers over the dictionary of the
+// handle it is used with, and staging for the output, a packed token array
+// and one string arena in the layout of sudachi_tokenize_into. Once the
+// buffers have grown to the largest text seen, a call allocates only the
+// analyzer's intermediate token list.
+//
+// Results point into the context and stay valid until its next call, so
+// nothing has to be freed per call. Since the context has its own analyzer,
+// calls don't take the handle's lock except to copy its settings.
+
+use std::os::raw::c_char;
+use std::sync::Arc;
+
+use crate::analyzer::{Analyzer, Dictionary};
+use crate::leak;
+use crate::options::SudachiOptions;
+use crate::packed::{self, StringBlob, SudachiPackedToken};
+use crate::{c_str, handle, SudachiTokenizer, TokenData, SUDACHI_FIELD_ALL};
+
+/// Opaque handle to reusable analysis buffers
+/// Not thread-safe: use one context per thread or serial queue.
+pub struct SudachiContext {
+    /// Analyzer over the dictionary of the last handle used
+    analyzer: Option<(Dictionary, Analyzer)>,
+    tokens: Vec<SudachiPackedToken>,
+    strings: StringBlob,
+}
+
+impl SudachiContext {
+    fn new() -> SudachiContext {
+        SudachiContext {
+            analyzer: None,
+            tokens: Vec::new(),
+            strings: StringBlob::default(),
+        }
+    }
+
+    /// Replace the staged result with tokens, keeping the buffers
+    /// Returns false if the strings overflow the u32 offsets of the layout
+    fn stage(&mut self, tokens: &[TokenData]) -> bool {
+        self.tokens.clear();
+        self.strings.clear();
+        packed::pack(tokens, &mut self.tokens, &mut self.strings);
+        self.strings.addressable()
+    }
+}
+
+/// Result of sudachi_tokenize_context, owned by the context
+/// Valid until the next call on the context or sudachi_context_free.
+#[repr(C)]
+pub struct SudachiContextResult {
+    /// Token records, as in a packed result
+    pub tokens: *const SudachiPackedToken,
+    pub count: usize,
+    /// String area addressed by the SudachiStrRef fields of the tokens
+    pub strings: *const u8,
+    pub string_bytes: usize,
+}
+
+/// Create an empty context
+/// Caller must free with sudachi_context_free
+#[no_mangle]
+pub extern "C" fn sudachi_context_create() -> *mut SudachiContext {
+    let context = Box::into_raw(Box::new(SudachiContext::new()));
+    leak::track(context, "SudachiContext");
+    context
+}
+
+/// Tokenize text with the buffers of a context
+/// Uses the handle's dictionary and settings with options on top (NULL for
+/// the defaults, see SudachiOptions). The result replaces the previous one
+/// of the context. Returns false if an argument is NULL, text is not valid
+/// UTF-8 or longer than options->max_bytes, or the analysis fails or its
+/// strings overflow the packed layout.
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_context(
+    tokenizer: *mut SudachiTokenizer,
+    context: *mut SudachiContext,
+    text: *const c_char,
+    options: *const SudachiOptions,
+    out_result: *mut SudachiContextResult,
+) -> bool {
+    if tokenizer.is_null() || context.is_null() || out_result.is_null() {
+        return false;
+    }
+    let Some(text) = c_str(text) else {
+        return false;
+    };
+    let options = SudachiOptions::read(options);
+    if !options.fits(text) {
+        return false;
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let context = unsafe { &mut *context };
+
+    // A handle with another dictionary needs another lattice
+    let current = context
+        .analyzer
+        .as_ref()
+        .is_some_and(|(dictionary, _)| Arc::ptr_eq(dictionary, &tokenizer.dictionary));
+    if !current {
+        let dictionary = tokenizer.dictionary.clone();
+        context.analyzer = Some((dictionary.clone(), Analyzer::new(dictionary)));
+    }
+    let Some((_, analyzer)) = &mut context.analyzer else {
+        return false;
+    };
+    tokenizer.copy_options(&mut analyzer.options);
+    options.adjust(&mut analyzer.options);
+    let fields = options.fields(SUDACHI_FIELD_ALL);
+    let Ok(tokens) = analyzer.analyze(text, options.mode.into(), fields) else {
+        return false;
+    };
+
+    if !context.stage(&options.filter(tokens)) {
+        return false;
+    }
+    unsafe {
+        *out_result = SudachiContextResult {
+            tokens: context.tokens.as_ptr(),
+            count: context.tokens.len(),
+            strings: context.strings.bytes.as_ptr(),
+            string_bytes: context.strings.bytes.len(),
+        };
+    }
+    true
+}
+
+/// Free a context
+/// Results of the context become invalid.
+#[no_mangle]
+pub extern "C" fn sudachi_context_free(context: *mut SudachiContext) {
+    if !context.is_null() {
+        leak::release(context);
+        unsafe {
+            let _ = Box::from_raw(context);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+    use crate::SUDACHI_FIELD_SURFACE;
+
+    #[test]
+    fn test_stage() {
+        let token = |begin, surface| {
+            language::opaque(
+                begin,
+                surface,
+                SudachiScript::Japanese,
+                SUDACHI_FIELD_SURFACE,
+            )
+        };
+        let mut context = SudachiContext::new();
+        assert!(context.stage(&[token(0, "猫が"), token(6, "鳴く")]));
+        let (tokens, strings) = (context.tokens.as_ptr(), context.strings.bytes.as_ptr());
+
+        // A smaller result reuses the buffers and starts the arena over
+        assert!(context.stage(&[token(0, "犬")]));
+        assert_eq!(context.tokens.len(), 1);
+        assert_eq!(context.tokens[0].surface.offset, 0);
+        assert_eq!(context.strings.bytes, "犬".as_bytes());
+        assert_eq!(context.tokens.as_ptr(), tokens);
+        assert_eq!(context.strings.bytes.as_ptr(), strings);
+    }
+}
@@ -0,0 +1,336 @@
+// Kana-to-kanji conversion
+// The note editor wants to turn typed kana into kanji without the system
+// IME. Sudachi's lexicon is indexed by surface only, so the caller supplies
+// a table of readings and their spellings (e.g. the reading and surface
+// columns of the Sudachi lexicon CSVs). Each spelling is looked up in the
+// handle's dictionary at load and keeps the word cost and connection ids of
+// its cheapest entry with that reading; spellings the dictionary does not
+// know are dropped. Conversion is then a beam search over the kana: a path
+// is a sequence of spellings whose readings cover the input, costing the
+// word costs plus the connection costs between neighbors, as in Sudachi's
+// own lattice. A kana with no matching spelling is kept as typed at a high
+// cost, so every input converts. Costs are summed in i64, as a long input
+// can exceed the range of i32, and saturate when reported.
+//
+// Table format (UTF-8, one spelling per line, # starts a comment):
+//   reading <TAB> surface
+// Readings may be hiragana or katakana.
+
+use std::collections::{HashMap, HashSet};
+use std::os::raw::c_char;
+use std::ptr;
+
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
+use sudachi::dic::subset::InfoSubset;
+
+use crate::analyzer::Dictionary;
+use crate::kana::{hiragana_to_katakana, is_kana};
+use crate::{c_str, handle, into_c_string, leak, SudachiTokenizer};
+
+/// Cost of a kana kept as typed, about that of an unknown word
+const FALLBACK_COST: i32 = 10_000;
+/// Connection id of the beginning and end of the text
+const BOUNDARY_ID: u16 = 0;
+/// Paths kept per position beyond the number of candidates requested
+const BEAM_SLACK: usize = 8;
+
+/// A spelling of a reading with its dictionary parameters
+#[derive(Debug, Clone)]
+struct Node {
+    surface: String,
+    left_id: u16,
+    right_id: u16,
+    cost: i32,
+}
+
+/// Partial conversion: spelling so far, total cost, right id of the last
+/// node
+type Path = (String, i64, u16);
+
+/// Conversion table loaded with sudachi_converter_open
+pub struct SudachiConverter {
+    dictionary: Dictionary,
+    /// Spellings by katakana reading
+    entries: HashMap<String, Vec<Node>>,
+    /// Longest reading, in characters
+    max_chars: usize,
+}
+
+/// Cheapest lexicon entry of surface read as reading (katakana)
+fn entry_node<D: DictionaryAccess>(dictionary: &D, reading: &str, surface: &str) -> Option<Node> {
+    let lexicon = dictionary.lexicon();
+    let node = lexicon
+        .lookup(surface.as_bytes(), 0)
+        .filter(|entry| entry.end == surface.len())
+        .filter(|entry| {
+            lexicon
+                .get_word_info_subset(entry.word_id, InfoSubset::READING_FORM)
+                .is_ok_and(|info| info.reading_form() == reading)
+        })
+        .map(|entry| {
+            let (left_id, right_id, cost) = lexicon.get_word_param(entry.word_id);
+            Node {
+                surface: surface.to_string(),
+                left_id: left_id as u16,
+                right_id: right_id as u16,
+                cost: cost as i32,
+            }
+        })
+        .min_by_key(|node| node.cost);
+    node
+}
+
+fn parse<D: DictionaryAccess>(dictionary: &D, table: &str) -> HashMap<String, Vec<Node>> {
+    let mut entries: HashMap<String, Vec<Node>> = HashMap::new();
+    for line in table.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((reading, surface)) = line.split_once('\t') else {
+            continue;
+        };
+        if reading.is_empty() || !reading.chars().all(is_kana) {
+            continue;
+        }
+        let reading = hiragana_to_katakana(reading);
+        let Some(node) = entry_node(dictionary, &reading, surface) else {
+            continue;
+        };
+        let spellings = entries.entry(reading).or_default();
+        if !spellings.iter().any(|n| n.surface == node.surface) {
+            spellings.push(node);
+        }
+    }
+    entries
+}
+
+/// Best conversions of kana, cheapest first, with their costs
+/// connect gives the cost between a right id and the next left id.
+fn convert(
+    entries: &HashMap<String, Vec<Node>>,
+    max_chars: usize,
+    kana: &str,
+    max: usize,
+    connect: impl Fn(u16, u16) -> i32,
+) -> Vec<(String, i64)> {
+    let chars: Vec<char> = kana.chars().collect();
+    let key: Vec<char> = hiragana_to_katakana(kana).chars().collect();
+    let n = chars.len();
+    let beam = max + BEAM_SLACK;
+    let mut paths: Vec<Vec<Path>> = vec![Vec::new(); n + 1];
+    paths[0].push((String::new(), 0, BOUNDARY_ID));
+
+    for i in 0..n {
+        prune(&mut paths[i], beam);
+        if paths[i].is_empty() {
+            continue;
+        }
+        let fallback = Node {
+            surface: chars[i].to_string(),
+            left_id: BOUNDARY_ID,
+            right_id: BOUNDARY_ID,
+            cost: FALLBACK_COST,
+        };
+        let mut nodes: Vec<(usize, &Node)> = vec![(i + 1, &fallback)];
+        for j in i + 1..=n.min(i + max_chars) {
+            let reading: String = key[i..j].iter().collect();
+            if let Some(spellings) = entries.get(&reading) {
+                nodes.extend(spellings.iter().map(|node| (j, node)));
+            }
+        }
+        let prefixes = std::mem::take(&mut paths[i]);
+        for (prefix, cost, right_id) in &prefixes {
+            for &(j, node) in &nodes {
+                let total =
+                    cost + i64::from(connect(*right_id, node.left_id)) + i64::from(node.cost);
+                paths[j].push((prefix.clone() + &node.surface, total, node.right_id));
+            }
+        }
+        paths[i] = prefixes;
+    }
+
+    let mut done: Vec<Path> = std::mem::take(&mut paths[n])
+        .into_iter()
+        .map(|(text, cost, right_id)| {
+            let cost = cost + i64::from(connect(right_id, BOUNDARY_ID));
+            (text, cost, right_id)
+        })
+        .collect();
+    prune(&mut done, max);
+    done.into_iter()
+        .map(|(text, cost, _)| (text, cost))
+        .collect()
+}
+
+/// Sort paths cheapest first, drop duplicate spellings and keep size
+fn prune(paths: &mut Vec<Path>, size: usize) {
+    paths.sort_by_key(|p| p.1);
+    let mut seen = HashSet::new();
+    paths.retain(|p| seen.insert(p.0.clone()));
+    paths.truncate(size);
+}
+
+/// Cost of a candidate as reported, saturating at the range of i32
+fn reported_cost(cost: i64) -> i32 {
+    cost.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+}
+
+/// Conversion candidate of a kana string
+/// text is owned by the result array.
+#[repr(C)]
+pub struct SudachiConversion {
+    /// Converted text
+    pub text: *mut c_char,
+    /// Total word and connection cost; lower is more likely
+    /// Saturates at INT32_MAX for very long input.
+    pub cost: i32,
+}
+
+/// Load a conversion table for the dictionary of a handle (see README for
+/// the format)
+/// The converter keeps the dictionary alive and may outlive the handle.
+/// Returns NULL on failure (caller must free with sudachi_converter_close)
+#[no_mangle]
+pub extern "C" fn sudachi_converter_open(
+    tokenizer: *const SudachiTokenizer,
+    path: *const c_char,
+) -> *mut SudachiConverter {
+    if tokenizer.is_null() {
+        return ptr::null_mut();
+    }
+    let Some(path) = c_str(path) else {
+        return ptr::null_mut();
+    };
+    let dictionary = unsafe { handle::borrow(tokenizer) }.dictionary.clone();
+
+    match std::fs::read_to_string(path) {
+        Ok(table) => {
+            let entries = parse(&dictionary, &table);
+            let max_chars = entries.keys().map(|r| r.chars().count()).max().unwrap_or(0);
+            let converter = Box::into_raw(Box::new(SudachiConverter {
+                dictionary,
+                entries,
+                max_chars,
+            }));
+            leak::track(converter, "SudachiConverter");
+            converter
+        }
+        Err(e) => {
+            eprintln!("Failed to open conversion table {}: {}", path, e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a converter
+#[no_mangle]
+pub extern "C" fn sudachi_converter_close(converter: *mut SudachiConverter) {
+    if !converter.is_null() {
+        leak::release(converter);
+        unsafe {
+            let _ = Box::from_raw(converter);
+        }
+    }
+}
+
+/// Convert a kana string to up to max candidates, most likely first
+/// Kana without a spelling in the table are kept as typed.
+/// Returns NULL if an argument is NULL, max is 0, or kana is empty or not
+/// all kana (caller must free with sudachi_free_conversions)
+#[no_mangle]
+pub extern "C" fn sudachi_convert(
+    converter: *const SudachiConverter,
+    kana: *const c_char,
+    max: usize,
+    out_count: *mut usize,
+) -> *mut SudachiConversion {
+    if converter.is_null() || out_count.is_null() || max == 0 {
+        return ptr::null_mut();
+    }
+    let converter = unsafe { &*converter };
+    let Some(kana) = c_str(kana).filter(|k| !k.is_empty() && k.chars().all(is_kana)) else {
+        return ptr::null_mut();
+    };
+
+    let matrix = converter.dictionary.grammar().conn_matrix();
+    let candidates = convert(
+        &converter.entries,
+        converter.max_chars,
+        kana,
+        max,
+        |right, left| matrix.cost(right, left) as i32,
+    );
+    let conversions: Vec<SudachiConversion> = candidates
+        .into_iter()
+        .map(|(text, cost)| SudachiConversion {
+            text: into_c_string(&text),
+            cost: reported_cost(cost),
+        })
+        .collect();
+    unsafe {
+        *out_count = conversions.len();
+    }
+    let conversions = Box::leak(conversions.into_boxed_slice()).as_mut_ptr();
+    leak::track(conversions, "conversions");
+    conversions
+}
+
+/// Free the result of sudachi_convert
+#[no_mangle]
+pub extern "C" fn sudachi_free_conversions(conversions: *mut SudachiConversion, count: usize) {
+    if conversions.is_null() {
+        return;
+    }
+
+    leak::release(conversions);
+    unsafe {
+        let conversions = Box::from_raw(ptr::slice_from_raw_parts_mut(conversions, count));
+        for conversion in conversions.iter() {
+            if !conversion.text.is_null() {
+                let _ = std::ffi::CString::from_raw(conversion.text);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let node = |surface: &str, id: u16, cost: i32| Node {
+            surface: surface.to_string(),
+            left_id: id,
+            right_id: id,
+            cost,
+        };
+        let mut entries = HashMap::new();
+        entries.insert(
+            "キ".to_string(),
+            vec![node("木", 1, 3000), node("気", 1, 2000)],
+        );
+        entries.insert("ガ".to_string(), vec![node("が", 2, 100)]);
+        entries.insert("キガ".to_string(), vec![node("飢餓", 1, 4000)]);
+        // A noun before が is cheap, anything else costs 1000
+        let connect = |right: u16, left: u16| if right == 1 && left == 2 { 0 } else { 1000 };
+
+        let candidates = convert(&entries, 2, "きが", 3, connect);
+        let texts: Vec<&str> = candidates.iter().map(|c| c.0.as_str()).collect();
+        assert_eq!(texts, ["気が", "木が", "飢餓"]);
+        assert_eq!(candidates[0].1, 1000 + 2000 + 100 + 1000);
+
+        // Unknown kana are kept as typed
+        let candidates = convert(&entries, 2, "ぬき", 1, connect);
+        assert_eq!(candidates[0].0, "ぬ気");
+
+        // Totals past the range of i32 are summed exactly and saturate
+        entries.insert("ヌ".to_string(), vec![node("沼", 3, i32::MAX)]);
+        let candidates = convert(&entries, 2, "ぬぬ", 4, connect);
+        let swamp = candidates.iter().find(|c| c.0 == "沼沼").unwrap();
+        assert_eq!(swamp.1, 2 * i64::from(i32::MAX) + 3000);
+        assert_eq!(reported_cost(swamp.1), i32::MAX);
+        assert_eq!(reported_cost(i64::MIN), i32::MIN);
+    }
+}
@@ -0,0 +1,287 @@
+// Corpus frequencies
+// "How common is this word in what you read" needs counts over the user's
+// whole library, not the dictionary's frequency list. A corpus keeps, per
+// lemma key, the number of documents that contain the lemma and the number
+// of times it occurs, plus totals to turn those into rates. Documents are
+// analyzed through a handle; only words count (symbols, emoji, whitespace and
+// foreign runs are skipped). Tables live in memory and are written to the
+// corpus file on save, so a library import costs one write.
+//
+// File layout (all integers little-endian):
+//   header:  b"MGXF" | u32 format version
+//            u64 documents | u64 tokens | u64 lemma count
+//   lemma:   u64 lemma key | u64 documents | u64 occurrences
+// Lemmas are sorted by key, so equal tables give equal files.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    c_str, handle, leak, SudachiTokenMode, SudachiTokenizer, TokenData, SUDACHI_FIELD_LEMMA_KEY,
+};
+
+const MAGIC: &[u8; 4] = b"MGXF";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: usize = 8 + 24;
+const LEMMA_LEN: usize = 24;
+
+/// Counts of one lemma across the corpus
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SudachiCorpusFrequency {
+    /// Documents containing the lemma
+    pub documents: u64,
+    /// Occurrences in all documents
+    pub occurrences: u64,
+}
+
+/// Totals of a corpus
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SudachiCorpusTotals {
+    pub documents: u64,
+    /// Words counted in all documents
+    pub tokens: u64,
+    /// Distinct lemmas
+    pub lemmas: u64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Tables {
+    documents: u64,
+    tokens: u64,
+    lemmas: HashMap<u64, SudachiCorpusFrequency>,
+}
+
+impl Tables {
+    /// Count one document given the lemma keys of its words
+    fn add(&mut self, keys: impl IntoIterator<Item = u64>) {
+        let mut counts: HashMap<u64, u64> = HashMap::new();
+        for key in keys {
+            *counts.entry(key).or_default() += 1;
+        }
+        self.documents += 1;
+        for (key, n) in counts {
+            let lemma = self.lemmas.entry(key).or_default();
+            lemma.documents += 1;
+            lemma.occurrences += n;
+            self.tokens += n;
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut lemmas: Vec<_> = self.lemmas.iter().collect();
+        lemmas.sort_unstable_by_key(|&(&key, _)| key);
+        let mut bytes = Vec::with_capacity(HEADER_LEN + lemmas.len() * LEMMA_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        for n in [self.documents, self.tokens, lemmas.len() as u64] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        for (&key, lemma) in lemmas {
+            for n in [key, lemma.documents, lemma.occurrences] {
+                bytes.extend_from_slice(&n.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// None if bytes are not a corpus of this format
+    fn decode(bytes: &[u8]) -> Option<Tables> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return None;
+        }
+        if u32::from_le_bytes(bytes[4..8].try_into().ok()?) != FORMAT_VERSION {
+            return None;
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let count = usize::try_from(u64_at(24)).ok()?;
+        if count.checked_mul(LEMMA_LEN) != Some(bytes.len() - HEADER_LEN) {
+            return None;
+        }
+        let lemmas = (0..count)
+            .map(|i| {
+                let at = HEADER_LEN + i * LEMMA_LEN;
+                let frequency = SudachiCorpusFrequency {
+                    documents: u64_at(at + 8),
+                    occurrences: u64_at(at + 16),
+                };
+                (u64_at(at), frequency)
+            })
+            .collect();
+        Some(Tables {
+            documents: u64_at(8),
+            tokens: u64_at(16),
+            lemmas,
+        })
+    }
+}
+
+/// Whether a token counts as a word of the document
+fn counted(token: &TokenData) -> bool {
+    token.lemma_key != 0
+        && matches!(
+            token.class,
+            SudachiTokenClass::Word | SudachiTokenClass::Sfx
+        )
+}
+
+/// Opaque handle to corpus frequency tables
+pub struct SudachiCorpus {
+    path: PathBuf,
+    tables: Mutex<Tables>,
+}
+
+impl SudachiCorpus {
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Open the corpus stored at path, or start an empty one if there is no file
+/// Returns NULL if path is NULL or the file can't be read or is not a corpus
+/// (caller must free with sudachi_corpus_free)
+#[no_mangle]
+pub extern "C" fn sudachi_corpus_open(path: *const c_char) -> *mut SudachiCorpus {
+    let Some(path) = c_str(path) else {
+        return ptr::null_mut();
+    };
+    let tables = match std::fs::read(path) {
+        Ok(bytes) => match Tables::decode(&bytes) {
+            Some(tables) => tables,
+            None => return ptr::null_mut(),
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => Tables::default(),
+        Err(_) => return ptr::null_mut(),
+    };
+    let corpus = Box::into_raw(Box::new(SudachiCorpus {
+        path: PathBuf::from(path),
+        tables: Mutex::new(tables),
+    }));
+    leak::track(corpus, "SudachiCorpus");
+    corpus
+}
+
+/// Analyze a document and add its words to the corpus
+/// Every call counts as one document, so adding the same text twice counts it
+/// twice. May be called from several threads. Returns false if an argument is
+/// NULL, text is not valid UTF-8 or the analysis fails.
+#[no_mangle]
+pub extern "C" fn sudachi_corpus_add(
+    corpus: *mut SudachiCorpus,
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+) -> bool {
+    if corpus.is_null() || tokenizer.is_null() {
+        return false;
+    }
+    let Some(text) = c_str(text) else {
+        return false;
+    };
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Ok(tokens) = tokenizer.analyze(text, mode.into(), SUDACHI_FIELD_LEMMA_KEY) else {
+        return false;
+    };
+    let keys = tokens.iter().filter(|t| counted(t)).map(|t| t.lemma_key);
+    unsafe { &*corpus }.tables().add(keys);
+    true
+}
+
+/// Get the counts of a lemma (see sudachi_lemma_key)
+/// Lemmas never seen have zero counts. Returns false if an argument is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_corpus_frequency(
+    corpus: *const SudachiCorpus,
+    lemma_key: u64,
+    out_frequency: *mut SudachiCorpusFrequency,
+) -> bool {
+    if corpus.is_null() || out_frequency.is_null() {
+        return false;
+    }
+    let tables = unsafe { &*corpus }.tables();
+    let frequency = tables.lemmas.get(&lemma_key).copied().unwrap_or_default();
+    unsafe {
+        *out_frequency = frequency;
+    }
+    true
+}
+
+/// Get the totals of a corpus
+/// Returns false if an argument is NULL
+#[no_mangle]
+pub extern "C" fn sudachi_corpus_totals(
+    corpus: *const SudachiCorpus,
+    out_totals: *mut SudachiCorpusTotals,
+) -> bool {
+    if corpus.is_null() || out_totals.is_null() {
+        return false;
+    }
+    let tables = unsafe { &*corpus }.tables();
+    unsafe {
+        *out_totals = SudachiCorpusTotals {
+            documents: tables.documents,
+            tokens: tables.tokens,
+            lemmas: tables.lemmas.len() as u64,
+        };
+    }
+    true
+}
+
+/// Write the corpus to the path it was opened from
+/// The file is replaced atomically, so a crash mid-save keeps the previous
+/// tables. Returns false if corpus is NULL or the file can't be written.
+#[no_mangle]
+pub extern "C" fn sudachi_corpus_save(corpus: *const SudachiCorpus) -> bool {
+    if corpus.is_null() {
+        return false;
+    }
+    let corpus = unsafe { &*corpus };
+    let bytes = corpus.tables().encode();
+    let mut temp = corpus.path.clone().into_os_string();
+    temp.push(".tmp");
+    std::fs::write(&temp, bytes).is_ok() && std::fs::rename(&temp, &corpus.path).is_ok()
+}
+
+/// Free a corpus without saving it
+#[no_mangle]
+pub extern "C" fn sudachi_corpus_free(corpus: *mut SudachiCorpus) {
+    if !corpus.is_null() {
+        leak::release(corpus);
+        unsafe {
+            let _ = Box::from_raw(corpus);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables() {
+        let mut tables = Tables::default();
+        tables.add([7, 9, 7]);
+        tables.add([7]);
+        tables.add([]);
+        assert_eq!((tables.documents, tables.tokens), (3, 4));
+        assert_eq!(
+            tables.lemmas[&7],
+            SudachiCorpusFrequency {
+                documents: 2,
+                occurrences: 3,
+            }
+        );
+        assert_eq!(tables.lemmas[&9].documents, 1);
+
+        let bytes = tables.encode();
+        assert_eq!(bytes.len(), HEADER_LEN + 2 * LEMMA_LEN);
+        assert_eq!(Tables::decode(&bytes), Some(tables));
+        assert_eq!(Tables::decode(&bytes[..bytes.len() - 1]), None);
+    }
+}
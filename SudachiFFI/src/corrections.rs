@@ -0,0 +1,278 @@
+// Correction store
+// When a user fixes furigana in the app, the fix has to stick in every later
+// analysis, not just the sentence it was made in. A store collects corrections
+// (the corrected segmentation and readings of a surface, plus the analysis
+// that was wrong); handles attached to it pin every recorded surface before
+// dictionary lookup, like overrides without a POS. Recording is seen by all
+// attached handles at once. The store exports to TSV, both to persist it and
+// to collect dictionary improvements.
+//
+// Export format (UTF-8, one correction per line, # starts a comment):
+//   surface <TAB> reading <TAB> wrong surface <TAB> wrong reading <TAB> count
+// Surfaces and readings are '|'-separated per segment; the wrong columns may
+// be empty, and count is how often the correction was recorded.
+
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::overrides::{Entry, Overrides};
+use crate::{c_str, handle, into_c_string, leak, SudachiTokenizer};
+
+/// Correction recorded by the user
+/// surface and reading are '|'-separated per segment, as in SudachiOverride.
+#[repr(C)]
+pub struct SudachiCorrection {
+    pub surface: *const c_char,
+    pub reading: *const c_char,
+    /// Segmentation and readings that analysis gave, '|'-separated, or NULL
+    pub wrong_surface: *const c_char,
+    pub wrong_reading: *const c_char,
+}
+
+struct Record {
+    surface: String,
+    reading: String,
+    wrong_surface: String,
+    wrong_reading: String,
+    count: u32,
+}
+
+impl Record {
+    /// Surface without segment separators
+    fn key(&self) -> String {
+        self.surface.replace('|', "")
+    }
+
+    fn entry(&self) -> Option<Entry> {
+        Entry::new(&self.surface, &self.reading, None)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    records: Vec<Record>,
+    /// Entries of the records, rebuilt on every change
+    overrides: Arc<Overrides>,
+}
+
+/// Corrections shared by the handles attached to a store
+#[derive(Default)]
+pub(crate) struct Store {
+    state: Mutex<State>,
+}
+
+impl Store {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Current corrections as overrides
+    pub(crate) fn overrides(&self) -> Arc<Overrides> {
+        Arc::clone(&self.state().overrides)
+    }
+
+    /// Add records, replacing the segmentation and readings of surfaces that
+    /// are already corrected; false if any record is invalid
+    fn record(&self, records: Vec<Record>) -> bool {
+        if records.iter().any(|r| r.entry().is_none()) {
+            return false;
+        }
+        let mut state = self.state();
+        for record in records {
+            let key = record.key();
+            match state.records.iter_mut().find(|r| r.key() == key) {
+                Some(existing) => {
+                    existing.count += record.count;
+                    existing.surface = record.surface;
+                    existing.reading = record.reading;
+                    if !record.wrong_surface.is_empty() {
+                        existing.wrong_surface = record.wrong_surface;
+                        existing.wrong_reading = record.wrong_reading;
+                    }
+                }
+                None => state.records.push(record),
+            }
+        }
+        let entries = state.records.iter().filter_map(Record::entry).collect();
+        state.overrides = Arc::new(Overrides::new(entries));
+        true
+    }
+
+    fn export(&self) -> String {
+        let mut tsv = String::from("# surface\treading\twrong surface\twrong reading\tcount\n");
+        for r in &self.state().records {
+            tsv += &format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                r.surface, r.reading, r.wrong_surface, r.wrong_reading, r.count
+            );
+        }
+        tsv
+    }
+}
+
+/// Parse exported corrections; None if any line is malformed
+fn parse(tsv: &str) -> Option<Vec<Record>> {
+    let mut records = Vec::new();
+    for line in tsv.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let columns: Vec<&str> = line.split('\t').collect();
+        let [surface, reading, wrong_surface, wrong_reading, count] = columns[..] else {
+            return None;
+        };
+        records.push(Record {
+            surface: surface.to_string(),
+            reading: reading.to_string(),
+            wrong_surface: wrong_surface.to_string(),
+            wrong_reading: wrong_reading.to_string(),
+            count: count.parse().ok()?,
+        });
+    }
+    Some(records)
+}
+
+/// Correction store created with sudachi_corrections_create
+pub struct SudachiCorrections {
+    store: Arc<Store>,
+}
+
+/// Create an empty correction store
+/// Returns NULL on failure (caller must free with sudachi_corrections_free)
+#[no_mangle]
+pub extern "C" fn sudachi_corrections_create() -> *mut SudachiCorrections {
+    let corrections = Box::into_raw(Box::new(SudachiCorrections {
+        store: Arc::default(),
+    }));
+    leak::track(corrections, "SudachiCorrections");
+    corrections
+}
+
+/// Free a correction store
+/// Attached handles keep the corrections recorded so far.
+#[no_mangle]
+pub extern "C" fn sudachi_corrections_free(corrections: *mut SudachiCorrections) {
+    if !corrections.is_null() {
+        leak::release(corrections);
+        unsafe {
+            let _ = Box::from_raw(corrections);
+        }
+    }
+}
+
+/// Record a user correction
+/// Recording a surface again replaces its segmentation and readings. Returns
+/// false if any argument is NULL or the correction is invalid.
+#[no_mangle]
+pub extern "C" fn sudachi_corrections_record(
+    corrections: *mut SudachiCorrections,
+    correction: *const SudachiCorrection,
+) -> bool {
+    if corrections.is_null() || correction.is_null() {
+        return false;
+    }
+    let corrections = unsafe { &*corrections };
+    let correction = unsafe { &*correction };
+    let (Some(surface), Some(reading)) = (c_str(correction.surface), c_str(correction.reading))
+    else {
+        return false;
+    };
+    let wrong = |s: *const c_char| if s.is_null() { Some("") } else { c_str(s) };
+    let (Some(wrong_surface), Some(wrong_reading)) = (
+        wrong(correction.wrong_surface),
+        wrong(correction.wrong_reading),
+    ) else {
+        return false;
+    };
+    if !wrong_surface.is_empty() && wrong_surface.replace('|', "") != surface.replace('|', "") {
+        return false;
+    }
+
+    corrections.store.record(vec![Record {
+        surface: surface.to_string(),
+        reading: reading.to_string(),
+        wrong_surface: wrong_surface.to_string(),
+        wrong_reading: wrong_reading.to_string(),
+        count: 1,
+    }])
+}
+
+/// Export the store as TSV (see README for the format)
+/// Returns NULL on failure (caller must free with sudachi_free_string)
+#[no_mangle]
+pub extern "C" fn sudachi_corrections_export(
+    corrections: *const SudachiCorrections,
+) -> *mut c_char {
+    if corrections.is_null() {
+        return ptr::null_mut();
+    }
+    let tsv = into_c_string(&unsafe { &*corrections }.store.export());
+    leak::track(tsv, "string");
+    tsv
+}
+
+/// Add corrections exported by sudachi_corrections_export
+/// Counts of surfaces already in the store are summed. Returns false, leaving
+/// the store unchanged, if any line is invalid.
+#[no_mangle]
+pub extern "C" fn sudachi_corrections_import(
+    corrections: *mut SudachiCorrections,
+    tsv: *const c_char,
+) -> bool {
+    if corrections.is_null() {
+        return false;
+    }
+    match c_str(tsv).and_then(parse) {
+        Some(records) => unsafe { &*corrections }.store.record(records),
+        None => false,
+    }
+}
+
+/// Attach a correction store to a handle, or detach it with NULL
+/// Several handles may share one store. Returns false if tokenizer is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_corrections(
+    tokenizer: *mut SudachiTokenizer,
+    corrections: *const SudachiCorrections,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    let store = (!corrections.is_null()).then(|| Arc::clone(&unsafe { &*corrections }.store));
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.corrections = store);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(surface: &str, reading: &str) -> Record {
+        Record {
+            surface: surface.to_string(),
+            reading: reading.to_string(),
+            wrong_surface: String::new(),
+            wrong_reading: String::new(),
+            count: 1,
+        }
+    }
+
+    #[test]
+    fn test_store() {
+        let store = Store::default();
+        assert!(store.record(vec![record("大罪", "タイザイ")]));
+        assert!(store.record(vec![record("七つ|の|大罪", "ナナツ|ノ|タイザイ")]));
+        assert!(!store.record(vec![record("七つ|の", "ナナツ")]));
+        assert!(store.record(vec![record("大|罪", "ダイ|ザイ")]));
+        assert_eq!(store.overrides().matches("七つの大罪").len(), 1);
+
+        let tsv = store.export();
+        assert!(tsv.contains("大|罪\tダイ|ザイ\t\t\t2\n"));
+        let imported = Store::default();
+        assert!(imported.record(parse(&tsv).unwrap()));
+        assert_eq!(imported.export(), tsv);
+        assert!(parse("大罪\tタイザイ").is_none());
+    }
+}
@@ -0,0 +1,571 @@
+// Cost overrides
+// Some segmentations come out wrong the same way again and again (な-adjectives
+// split as noun + particle, a rare entry winning over a common one), and the
+// fix belongs in the costs, not in a rebuilt dictionary. A handle can load a
+// table of deltas: on the word cost of an entry, or on the connection cost
+// between words of two parts of speech. Sudachi's lattice cannot be
+// reweighted from outside, so the handle decodes each chunk a second time:
+// the lattice runs in mode C, then the dictionary entries of the text
+// (together with the words the lattice chose) are searched again for the
+// cheapest paths under the table, with the same costs as Sudachi's plus the
+// deltas. Spans the lattice read as unknown words are kept as they are, and
+// the words on either side of them connect as at the ends of the text. Ties
+// go to the lattice's words, so without a matching delta the result is
+// unchanged; a different path is then split for modes A and B with the
+// entries' own splits. The same search gives the n-best paths of a
+// re-ranking hook (rerank.rs).
+//
+// Table format (UTF-8, tab-separated, # starts a comment):
+//   word <TAB> word_id <TAB> delta
+//   pos <TAB> left POS <TAB> right POS <TAB> delta
+// word_id is a token's word_id. A POS is its leading columns joined by ','
+// ("形状詞" or "名詞,普通名詞"), and must match some POS of the dictionary;
+// a pos row applies when a word of the left POS is followed by one of the
+// right POS. Deltas of every matching row add up; negative deltas favor.
+
+use std::collections::HashMap;
+use std::os::raw::c_char;
+use std::sync::Arc;
+
+use sudachi::analysis::mlist::MorphemeList;
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
+use sudachi::dic::subset::InfoSubset;
+use sudachi::dic::word_id::WordId;
+use sudachi::prelude::*;
+
+use crate::fingerprint::{fnv, FNV_OFFSET};
+use crate::language::SUDACHI_OPAQUE_WORD_ID;
+use crate::{c_str, handle, SudachiTokenizer};
+
+/// Connection id of the ends of a decoded span
+const BOUNDARY_ID: u16 = 0;
+
+/// A row of a cost table
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Row {
+    Word(u32, i32),
+    Pos(Vec<String>, Vec<String>, i32),
+}
+
+/// Rows of a cost table with their line numbers, or the line number of the
+/// first invalid line
+fn parse(table: &str) -> Result<Vec<(usize, Row)>, usize> {
+    let pos = |pos: &str| -> Option<Vec<String>> {
+        let columns: Vec<String> = pos.split(',').map(|c| c.trim().to_string()).collect();
+        columns.iter().all(|c| !c.is_empty()).then_some(columns)
+    };
+    let mut rows = Vec::new();
+    for (n, line) in table.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let columns: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let row = match columns[..] {
+            ["word", id, delta] => match (id.parse(), delta.parse()) {
+                (Ok(id), Ok(delta)) if id != SUDACHI_OPAQUE_WORD_ID => Some(Row::Word(id, delta)),
+                _ => None,
+            },
+            ["pos", left, right, delta] => match (pos(left), pos(right), delta.parse()) {
+                (Some(left), Some(right), Ok(delta)) => Some(Row::Pos(left, right, delta)),
+                _ => None,
+            },
+            _ => None,
+        };
+        rows.push((n + 1, row.ok_or(n + 1)?));
+    }
+    Ok(rows)
+}
+
+/// Cost deltas of a handle, resolved against its dictionary's POS table
+#[derive(Debug, Default)]
+pub(crate) struct Costs {
+    /// Word cost delta by word id
+    words: HashMap<u32, i32>,
+    /// Delta of each pos row
+    pairs: Vec<i32>,
+    /// Pos rows whose left (right) POS each pos id matches, ascending
+    left: HashMap<u16, Vec<usize>>,
+    right: HashMap<u16, Vec<usize>>,
+    digest: u64,
+}
+
+impl Costs {
+    /// Resolve rows against pos_list, or give the line of a pos row whose POS
+    /// matches nothing
+    fn new(rows: Vec<(usize, Row)>, pos_list: &[Vec<String>]) -> Result<Costs, usize> {
+        let mut costs = Costs::default();
+        let mut hash = FNV_OFFSET;
+        let matching = |prefix: &[String]| -> Vec<u16> {
+            (0..pos_list.len())
+                .filter(|&id| pos_list[id].starts_with(prefix))
+                .map(|id| id as u16)
+                .collect()
+        };
+        for (line, row) in rows {
+            match row {
+                Row::Word(id, delta) => {
+                    *costs.words.entry(id).or_default() += delta;
+                    hash = fnv(hash, &id.to_le_bytes());
+                    hash = fnv(hash, &delta.to_le_bytes());
+                }
+                Row::Pos(left, right, delta) => {
+                    let (lefts, rights) = (matching(&left), matching(&right));
+                    if lefts.is_empty() || rights.is_empty() {
+                        return Err(line);
+                    }
+                    let pair = costs.pairs.len();
+                    costs.pairs.push(delta);
+                    for id in lefts {
+                        costs.left.entry(id).or_default().push(pair);
+                    }
+                    for id in rights {
+                        costs.right.entry(id).or_default().push(pair);
+                    }
+                    for column in left.iter().chain([&String::new()]).chain(&right) {
+                        hash = fnv(hash, column.as_bytes());
+                        hash = fnv(hash, &[0]);
+                    }
+                    hash = fnv(hash, &delta.to_le_bytes());
+                }
+            }
+        }
+        costs.digest = hash;
+        Ok(costs)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.pairs.is_empty()
+    }
+
+    /// Hash of the table, for settings digests
+    pub(crate) fn digest(&self) -> u64 {
+        self.digest
+    }
+
+    /// Delta on the word cost of an entry
+    fn word(&self, id: u32) -> i32 {
+        self.words.get(&id).copied().unwrap_or(0)
+    }
+
+    /// Delta on the connection from a word of POS left to one of POS right
+    fn connection(&self, left: u16, right: u16) -> i32 {
+        let (Some(lefts), Some(rights)) = (self.left.get(&left), self.right.get(&right)) else {
+            return 0;
+        };
+        lefts
+            .iter()
+            .filter(|pair| rights.binary_search(pair).is_ok())
+            .map(|&pair| self.pairs[pair])
+            .sum()
+    }
+}
+
+/// A word of a decoded path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Word {
+    /// The morpheme at this index of the lattice's result
+    Lattice(usize),
+    /// A dictionary entry covering begin..end of the text
+    Entry {
+        begin: usize,
+        end: usize,
+        word_id: WordId,
+    },
+}
+
+impl Word {
+    /// Begin and end of the word in the text
+    pub(crate) fn span<D: DictionaryAccess>(&self, morphemes: &MorphemeList<D>) -> (usize, usize) {
+        match *self {
+            Word::Lattice(i) => {
+                let m = morphemes.get(i);
+                (m.begin(), m.end())
+            }
+            Word::Entry { begin, end, .. } => (begin, end),
+        }
+    }
+}
+
+/// A word of the lattice's result: begin, end and word id
+type Span = (usize, usize, WordId);
+
+/// POS of unknown words for pos rows, matched by none
+const NO_POS: u16 = u16::MAX;
+
+/// Score of a partial path: total cost, then words the lattice did not choose
+type Score = (i64, u32);
+
+/// A way into a node: its score and the node and way before, if any
+type Way = (Score, Option<(usize, usize)>);
+
+/// A candidate path of a chunk in mode C
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Path {
+    pub(crate) words: Vec<Word>,
+    /// Word and connection costs with their deltas; unknown words count 0
+    pub(crate) cost: i64,
+}
+
+/// A word that can follow at an offset
+struct Candidate {
+    word: Word,
+    end: usize,
+    left_id: u16,
+    right_id: u16,
+    pos_id: u16,
+    /// Word cost with its delta
+    cost: i64,
+    /// 1 if the lattice did not choose the word
+    deviation: u32,
+}
+
+/// Node of the second decoding
+struct Node {
+    word: Word,
+    right_id: u16,
+    pos_id: u16,
+    /// Cheapest ways in, best first
+    ways: Vec<Way>,
+}
+
+/// Up to n cheapest paths over text under costs, cheapest first
+/// lattice is the lattice's result with whether each word is unknown; its
+/// dictionary words are candidates even if the lexicon finds them only in
+/// Sudachi's normalized input.
+fn decode<D: DictionaryAccess>(
+    dictionary: &D,
+    text: &str,
+    lattice: &[(Span, bool)],
+    costs: &Costs,
+    n: usize,
+) -> SudachiResult<Vec<Path>> {
+    let (Some(first), Some(last)) = (lattice.first(), lattice.last()) else {
+        return Ok(Vec::new());
+    };
+    let (begin, end) = (first.0 .0, last.0 .1);
+    let lexicon = dictionary.lexicon();
+    let matrix = dictionary.grammar().conn_matrix();
+    let pos_id = |id: WordId| -> SudachiResult<u16> {
+        if costs.pairs.is_empty() {
+            return Ok(0);
+        }
+        Ok(lexicon
+            .get_word_info_subset(id, InfoSubset::POS_ID)?
+            .pos_id())
+    };
+    // Unknown words by begin, in order
+    let unknown: Vec<(usize, usize, usize)> = lattice
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, oov))| *oov)
+        .map(|(i, &((b, e, _), _))| (b, e, i))
+        .collect();
+    let overlaps_unknown = |b: usize, e: usize| {
+        let next = unknown.partition_point(|&(_, end, _)| end <= b);
+        unknown.get(next).is_some_and(|&(begin, _, _)| begin < e)
+    };
+
+    let mut nodes: Vec<Node> = Vec::new();
+    // Nodes ending at each offset of the chunk
+    let mut ends: Vec<Vec<usize>> = vec![Vec::new(); end - begin + 1];
+    for (at, _) in text[begin..end].char_indices() {
+        let at = begin + at;
+        if at != begin && ends[at - begin].is_empty() {
+            continue;
+        }
+        // Unknown words are kept and connect as the ends of the text do
+        let mut candidates = Vec::new();
+        if let Some(&(_, e, i)) = unknown.iter().find(|&&(b, _, _)| b == at) {
+            candidates.push(Candidate {
+                word: Word::Lattice(i),
+                end: e,
+                left_id: BOUNDARY_ID,
+                right_id: BOUNDARY_ID,
+                pos_id: NO_POS,
+                cost: 0,
+                deviation: 0,
+            });
+        } else {
+            let mut entries: Vec<(usize, WordId)> = lexicon
+                .lookup(text.as_bytes(), at)
+                .filter(|entry| entry.end <= end && text.is_char_boundary(entry.end))
+                .map(|entry| (entry.end, entry.word_id))
+                .collect();
+            for &((b, e, id), oov) in lattice {
+                if b == at && !oov && !entries.contains(&(e, id)) {
+                    entries.push((e, id));
+                }
+            }
+            for (e, id) in entries {
+                if overlaps_unknown(at, e) {
+                    continue;
+                }
+                let (left_id, right_id, cost) = lexicon.get_word_param(id);
+                candidates.push(Candidate {
+                    word: Word::Entry {
+                        begin: at,
+                        end: e,
+                        word_id: id,
+                    },
+                    end: e,
+                    left_id: left_id as u16,
+                    right_id: right_id as u16,
+                    pos_id: pos_id(id)?,
+                    cost: i64::from(cost) + i64::from(costs.word(id.as_raw())),
+                    deviation: u32::from(!lattice.contains(&((at, e, id), false))),
+                });
+            }
+        }
+
+        for candidate in candidates {
+            let left_id = candidate.left_id;
+            let mut ways: Vec<Way> = if at == begin {
+                vec![((i64::from(matrix.cost(BOUNDARY_ID, left_id)), 0), None)]
+            } else {
+                let mut ways = Vec::new();
+                for &p in &ends[at - begin] {
+                    let prev = &nodes[p];
+                    let connect = i64::from(matrix.cost(prev.right_id, left_id))
+                        + i64::from(costs.connection(prev.pos_id, candidate.pos_id));
+                    for (k, &((cost, deviations), _)) in prev.ways.iter().enumerate() {
+                        ways.push(((cost + connect, deviations), Some((p, k))));
+                    }
+                }
+                ways
+            };
+            ways.sort_by_key(|&(score, _)| score);
+            ways.truncate(n);
+            for ((cost, deviations), _) in &mut ways {
+                *cost += candidate.cost;
+                *deviations += candidate.deviation;
+            }
+            nodes.push(Node {
+                word: candidate.word,
+                right_id: candidate.right_id,
+                pos_id: candidate.pos_id,
+                ways,
+            });
+            ends[candidate.end - begin].push(nodes.len() - 1);
+        }
+    }
+
+    let mut finals: Vec<(Score, (usize, usize))> = Vec::new();
+    for &node in &ends[end - begin] {
+        let connect = i64::from(matrix.cost(nodes[node].right_id, BOUNDARY_ID));
+        for (k, &((cost, deviations), _)) in nodes[node].ways.iter().enumerate() {
+            finals.push(((cost + connect, deviations), (node, k)));
+        }
+    }
+    finals.sort_by_key(|&(score, _)| score);
+    finals.truncate(n);
+    Ok(finals
+        .into_iter()
+        .map(|((cost, _), mut at)| {
+            let mut words = vec![nodes[at.0].word];
+            while let Some(prev) = nodes[at.0].ways[at.1].1 {
+                words.push(nodes[prev.0].word);
+                at = prev;
+            }
+            words.reverse();
+            Path { words, cost }
+        })
+        .collect())
+}
+
+/// Entry split into the units of mode, with the offsets of each part
+fn split<D: DictionaryAccess>(dictionary: &D, span: Span, mode: Mode) -> SudachiResult<Vec<Word>> {
+    let (begin, end, word_id) = span;
+    let whole = Word::Entry {
+        begin,
+        end,
+        word_id,
+    };
+    let subset = match mode {
+        Mode::A => InfoSubset::SPLIT_A,
+        Mode::B => InfoSubset::SPLIT_B,
+        Mode::C => return Ok(vec![whole]),
+    };
+    let lexicon = dictionary.lexicon();
+    let info = lexicon.get_word_info_subset(word_id, subset)?;
+    let parts = match mode {
+        Mode::A => info.a_unit_split(),
+        _ => info.b_unit_split(),
+    };
+    if parts.len() <= 1 {
+        return Ok(vec![whole]);
+    }
+    let mut words = Vec::with_capacity(parts.len());
+    let mut at = begin;
+    for &part in parts {
+        let length = lexicon
+            .get_word_info_subset(part, InfoSubset::HEAD_WORD_LENGTH)?
+            .head_word_length();
+        let part_end = (at + length).min(end);
+        words.push(Word::Entry {
+            begin: at,
+            end: part_end,
+            word_id: part,
+        });
+        at = part_end;
+    }
+    Ok(words)
+}
+
+/// Words of text under costs, given the lattice's mode C result for it
+/// choose picks one of the n cheapest paths by index (see rerank.rs).
+/// Returns None if the lattice's path stands; otherwise the words for mode,
+/// where unknown words refer to morphemes.
+pub(crate) fn redecode<D: DictionaryAccess>(
+    morphemes: &MorphemeList<D>,
+    text: &str,
+    costs: &Costs,
+    mode: Mode,
+    n: usize,
+    choose: impl FnOnce(&[Path]) -> SudachiResult<usize>,
+) -> SudachiResult<Option<Vec<Word>>> {
+    let dictionary = morphemes.dict();
+    let lattice: Vec<(Span, bool)> = morphemes
+        .iter()
+        .map(|m| ((m.begin(), m.end(), m.word_id()), m.is_oov()))
+        .collect();
+    let paths = decode(dictionary, text, &lattice, costs, n.max(1))?;
+    if paths.is_empty() {
+        return Ok(None);
+    }
+    let Some(path) = paths.get(choose(&paths)?) else {
+        return Ok(None);
+    };
+
+    let unchanged = path.words.len() == lattice.len()
+        && path
+            .words
+            .iter()
+            .zip(&lattice)
+            .all(|(word, &(span, oov))| match *word {
+                Word::Lattice(_) => oov,
+                Word::Entry {
+                    begin,
+                    end,
+                    word_id,
+                } => !oov && span == (begin, end, word_id),
+            });
+    if unchanged {
+        return Ok(None);
+    }
+
+    let mut words = Vec::with_capacity(path.words.len());
+    for &word in &path.words {
+        match word {
+            Word::Entry {
+                begin,
+                end,
+                word_id,
+            } => words.extend(split(dictionary, (begin, end, word_id), mode)?),
+            lattice => words.push(lattice),
+        }
+    }
+    Ok(Some(words))
+}
+
+/// Load the cost overrides of a handle from a table file (see README), or
+/// clear them if path is NULL
+/// Returns false if tokenizer is NULL, the file cannot be read or a line is
+/// invalid (reported on stderr), leaving the overrides unchanged.
+#[no_mangle]
+pub extern "C" fn sudachi_set_cost_overrides(
+    tokenizer: *mut SudachiTokenizer,
+    path: *const c_char,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    if path.is_null() {
+        tokenizer.set_options(|o| o.costs = Arc::default());
+        return true;
+    }
+    let Some(path) = c_str(path) else {
+        return false;
+    };
+    let table = match std::fs::read_to_string(path) {
+        Ok(table) => table,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            return false;
+        }
+    };
+    let costs = parse(&table).and_then(|rows| {
+        let grammar = tokenizer.dictionary.grammar();
+        Costs::new(rows, &grammar.pos_list)
+    });
+    match costs {
+        Ok(costs) => {
+            let costs = Arc::new(costs);
+            tokenizer.set_options(|o| o.costs = costs);
+            true
+        }
+        Err(line) => {
+            eprintln!("{}:{}: invalid cost override", path, line);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(columns: &str) -> Vec<String> {
+        columns.split(',').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let table = "# fixes\nword\t42\t-1000\n\npos\t形状詞\t助動詞\t-500\n";
+        assert_eq!(
+            parse(table),
+            Ok(vec![
+                (2, Row::Word(42, -1000)),
+                (4, Row::Pos(pos("形状詞"), pos("助動詞"), -500)),
+            ])
+        );
+        assert_eq!(parse("word\t42\n"), Err(1));
+        assert_eq!(parse("word\t42\t1\npos\t名詞,\t助詞\t1\n"), Err(2));
+        assert_eq!(parse(&format!("word\t{}\t1\n", u32::MAX)), Err(1));
+    }
+
+    #[test]
+    fn test_costs() {
+        let pos_list = vec![
+            pos("名詞,普通名詞,一般,*,*,*"),
+            pos("形状詞,一般,*,*,*,*"),
+            pos("助動詞,*,*,*,助動詞-ダ,連体形-一般"),
+            pos("助詞,格助詞,*,*,*,*"),
+        ];
+        let rows = vec![
+            (1, Row::Word(7, -100)),
+            (2, Row::Word(7, -50)),
+            (3, Row::Pos(pos("形状詞"), pos("助動詞"), -500)),
+            (4, Row::Pos(pos("形状詞,一般"), pos("助詞"), 20)),
+        ];
+        let costs = Costs::new(rows.clone(), &pos_list).unwrap();
+        assert!(!costs.is_empty());
+        assert_eq!(costs.word(7), -150);
+        assert_eq!(costs.connection(1, 2), -500);
+        assert_eq!(costs.connection(1, 3), 20);
+        assert_eq!(costs.connection(0, 2), 0);
+        assert_eq!(costs.connection(2, 1), 0);
+        assert_eq!(
+            Costs::new(rows, &pos_list).unwrap().digest(),
+            costs.digest()
+        );
+
+        let unknown = vec![
+            (1, Row::Word(1, 1)),
+            (5, Row::Pos(pos("動詞"), pos("助詞"), 1)),
+        ];
+        assert_eq!(Costs::new(unknown, &pos_list).err(), Some(5));
+        assert!(Costs::default().is_empty());
+    }
+}
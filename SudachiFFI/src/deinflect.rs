@@ -0,0 +1,178 @@
+// Deinflection
+// Maps each inflected token to its dictionary form and conjugation, the way
+// the app resolves words for JMdict lookup: punctuation is dropped and the
+// conjugation type/form come from POS columns 5 and 6.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::{
+    c_str, handle, into_c_string, leak, SudachiTokenMode, SudachiTokenizer, TokenData,
+    SUDACHI_FIELD_DICTIONARY_FORM, SUDACHI_FIELD_POS, SUDACHI_FIELD_SURFACE,
+};
+
+/// Fields deinflection needs from the analyzer
+pub(crate) const DEINFLECT_FIELDS: u32 =
+    SUDACHI_FIELD_SURFACE | SUDACHI_FIELD_DICTIONARY_FORM | SUDACHI_FIELD_POS;
+
+const PUNCTUATION: &str = "。、！？!?…・「」『』（）()[]【】〈〉《》〔〕{}～ー";
+
+pub(crate) struct Deinflection {
+    pub(crate) surface: String,
+    pub(crate) dictionary_form: String,
+    /// 活用型, e.g. 下一段-バ行
+    pub(crate) conjugation_type: Option<String>,
+    /// 活用形, e.g. 連用形-一般
+    pub(crate) conjugation_form: Option<String>,
+    pub(crate) begin: usize,
+    pub(crate) end: usize,
+}
+
+/// 補助記号 / 空白 tokens, or surfaces made only of punctuation
+fn is_punctuation(token: &TokenData) -> bool {
+    if let Some(major) = token.pos.first() {
+        if major.contains("補助記号") || major.contains("空白") {
+            return true;
+        }
+    }
+    token.surface.chars().all(|c| PUNCTUATION.contains(c))
+}
+
+/// POS column unless unset ("*")
+fn pos_column(pos: &[String], i: usize) -> Option<String> {
+    if pos.len() < 6 {
+        return None;
+    }
+    pos.get(i).filter(|p| *p != "*").cloned()
+}
+
+/// Deinflect every non-punctuation token
+pub(crate) fn deinflect(tokens: Vec<TokenData>) -> Vec<Deinflection> {
+    tokens
+        .into_iter()
+        .filter(|t| !is_punctuation(t))
+        .map(|t| Deinflection {
+            conjugation_type: pos_column(&t.pos, 4),
+            conjugation_form: pos_column(&t.pos, 5),
+            surface: t.surface,
+            dictionary_form: t.dictionary_form,
+            begin: t.begin,
+            end: t.end,
+        })
+        .collect()
+}
+
+/// Deinflected word
+/// All strings are owned by the result array; the conjugation fields are
+/// NULL for words that do not inflect.
+#[repr(C)]
+pub struct SudachiDeinflection {
+    pub surface: *mut c_char,
+    pub dictionary_form: *mut c_char,
+    pub conjugation_type: *mut c_char,
+    pub conjugation_form: *mut c_char,
+    /// Byte offsets into the input
+    pub begin: i32,
+    pub end: i32,
+}
+
+/// Analyze text into dictionary forms with conjugation info
+/// Punctuation and whitespace tokens are skipped.
+/// Returns NULL on failure (caller must free with sudachi_free_deinflections)
+#[no_mangle]
+pub extern "C" fn sudachi_deinflect(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    out_count: *mut usize,
+) -> *mut SudachiDeinflection {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+    let tokens = match tokenizer.analyze(text, mode.into(), DEINFLECT_FIELDS) {
+        Ok(tokens) => tokens,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    let optional = |s: &Option<String>| s.as_deref().map_or(ptr::null_mut(), into_c_string);
+    let words: Vec<SudachiDeinflection> = deinflect(tokens)
+        .iter()
+        .map(|d| SudachiDeinflection {
+            surface: into_c_string(&d.surface),
+            dictionary_form: into_c_string(&d.dictionary_form),
+            conjugation_type: optional(&d.conjugation_type),
+            conjugation_form: optional(&d.conjugation_form),
+            begin: d.begin as i32,
+            end: d.end as i32,
+        })
+        .collect();
+
+    unsafe {
+        *out_count = words.len();
+    }
+    let words = Box::leak(words.into_boxed_slice()).as_mut_ptr();
+    leak::track(words, "deinflections");
+    words
+}
+
+/// Free the result of sudachi_deinflect
+#[no_mangle]
+pub extern "C" fn sudachi_free_deinflections(words: *mut SudachiDeinflection, count: usize) {
+    if words.is_null() {
+        return;
+    }
+
+    leak::release(words);
+    unsafe {
+        let words = Box::from_raw(ptr::slice_from_raw_parts_mut(words, count));
+        for word in words.iter() {
+            for s in [
+                word.surface,
+                word.dictionary_form,
+                word.conjugation_type,
+                word.conjugation_form,
+            ] {
+                if !s.is_null() {
+                    let _ = std::ffi::CString::from_raw(s);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testtoken::word;
+
+    fn token(surface: &str, dictionary_form: &str, pos: &[&str]) -> TokenData {
+        TokenData {
+            fields: DEINFLECT_FIELDS,
+            dictionary_form: dictionary_form.to_string(),
+            pos: pos.iter().map(|p| p.to_string()).collect(),
+            ..word(0, surface)
+        }
+    }
+
+    #[test]
+    fn test_deinflect() {
+        let words = deinflect(vec![
+            token(
+                "食べ",
+                "食べる",
+                &["動詞", "一般", "*", "*", "下一段-バ行", "連用形-一般"],
+            ),
+            token("。", "。", &["補助記号", "句点", "*", "*", "*", "*"]),
+            token("猫", "猫", &["名詞", "普通名詞", "一般", "*", "*", "*"]),
+        ]);
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].dictionary_form, "食べる");
+        assert_eq!(words[0].conjugation_form.as_deref(), Some("連用形-一般"));
+        assert_eq!(words[1].conjugation_type, None);
+    }
+}
@@ -0,0 +1,132 @@
+// Dialogue normalization
+// Speech bubbles stylize text in ways the dictionary never sees: letters
+// spaced out for effect (ス　ペ　ー　ス), stretched sounds (すごーーーい,
+// ねぇぇぇ, なにっっっ！！！) and wave dashes for a drawn-out vowel (ね〜).
+// These flags of sudachi_set_normalization undo them before analysis, on top
+// of width normalization; offsets and surfaces still refer to the original
+// text (see width.rs for the offset map).
+
+use crate::kana::{is_hiragana, is_kana, is_kanji};
+
+// Flags for sudachi_set_normalization
+/// Remove the spaces of letter-spaced runs (ス　ペ　ー　ス -> スペース)
+pub const SUDACHI_NORMALIZE_LETTER_SPACING: u32 = 1 << 2;
+/// Collapse emphatic repetition (すごーーーい -> すごーい, ！！！ -> ！)
+pub const SUDACHI_NORMALIZE_REPETITION: u32 = 1 << 3;
+/// Read a wave dash after kana as a long vowel (ね〜 -> ねー)
+pub const SUDACHI_NORMALIZE_WAVE_DASH: u32 = 1 << 4;
+/// All dialogue flags
+pub const SUDACHI_NORMALIZE_DIALOGUE: u32 =
+    SUDACHI_NORMALIZE_LETTER_SPACING | SUDACHI_NORMALIZE_REPETITION | SUDACHI_NORMALIZE_WAVE_DASH;
+
+/// Marks that are doubled for emphasis but mean the same once
+const STRETCHERS: &str = "ー〜っッぁぃぅぇぉァィゥェォ!！?？";
+/// Wave dash look-alikes (the full-width tilde may already be folded to ~)
+const WAVE_DASHES: &str = "〜～〰∼~";
+
+/// A character of the text being normalized and its source byte range
+pub(crate) type Mapped = (char, usize, usize);
+
+fn is_japanese(c: char) -> bool {
+    is_kana(c) || is_kanji(c)
+}
+
+fn is_space(c: char) -> bool {
+    c == ' ' || c == '\u{3000}'
+}
+
+/// Remove the single spaces between the letters of runs of three or more
+/// single Japanese characters
+fn letter_spacing(chars: &mut Vec<Mapped>) {
+    let japanese = |i: usize, chars: &[Mapped]| chars.get(i).is_some_and(|m| is_japanese(m.0));
+    let mut spaces = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !japanese(i, chars) || (i > 0 && japanese(i - 1, chars)) {
+            i += 1;
+            continue;
+        }
+        let mut end = i;
+        while chars.get(end + 1).is_some_and(|m| is_space(m.0)) && japanese(end + 2, chars) {
+            end += 2;
+        }
+        if end - i >= 4 && !japanese(end + 1, chars) {
+            spaces.extend((i + 1..end).step_by(2));
+        }
+        i = end + 1;
+    }
+    for &i in spaces.iter().rev() {
+        chars.remove(i);
+    }
+}
+
+/// Turn wave dashes after kana into the prolonged sound mark
+fn wave_dash(chars: &mut [Mapped]) {
+    for i in 1..chars.len() {
+        if WAVE_DASHES.contains(chars[i].0) && is_kana(chars[i - 1].0) {
+            chars[i].0 = 'ー';
+        }
+    }
+}
+
+/// Keep one of a run of the same stretcher, or of three or more of the same
+/// hiragana; the kept character covers the whole run
+/// Katakana runs are left for sound effect recognition (ドドドド).
+fn repetition(chars: &mut Vec<Mapped>) {
+    let mut out: Vec<Mapped> = Vec::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let (c, begin, _) = chars[i];
+        let run = chars[i..].iter().take_while(|m| m.0 == c).count();
+        let collapse = (run >= 2 && STRETCHERS.contains(c)) || (run >= 3 && is_hiragana(c));
+        if collapse {
+            out.push((c, begin, chars[i + run - 1].2));
+        } else {
+            out.extend_from_slice(&chars[i..i + run]);
+        }
+        i += run;
+    }
+    *chars = out;
+}
+
+/// Apply the dialogue flags of a SUDACHI_NORMALIZE_* mask
+pub(crate) fn apply(chars: &mut Vec<Mapped>, flags: u32) {
+    if flags & SUDACHI_NORMALIZE_LETTER_SPACING != 0 {
+        letter_spacing(chars);
+    }
+    if flags & SUDACHI_NORMALIZE_WAVE_DASH != 0 {
+        wave_dash(chars);
+    }
+    if flags & SUDACHI_NORMALIZE_REPETITION != 0 {
+        repetition(chars);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(text: &str, flags: u32) -> String {
+        let mut chars: Vec<Mapped> = text
+            .char_indices()
+            .map(|(i, c)| (c, i, i + c.len_utf8()))
+            .collect();
+        apply(&mut chars, flags);
+        chars.into_iter().map(|m| m.0).collect()
+    }
+
+    #[test]
+    fn test_apply() {
+        let all = SUDACHI_NORMALIZE_DIALOGUE;
+        assert_eq!(normalize("ス　ペ　ー　ス！", all), "スペース！");
+        assert_eq!(normalize("今日は　い　い　天　気", all), "今日は　いい天気");
+        // Two words, not a spaced run
+        assert_eq!(normalize("はい　そう", all), "はい　そう");
+        assert_eq!(normalize("すごーーーい！！！", all), "すごーい！");
+        assert_eq!(normalize("ね〜〜", all), "ねー");
+        assert_eq!(normalize("1〜3", all), "1〜3");
+        assert_eq!(normalize("ううう…ドドドド", all), "う…ドドドド");
+        assert_eq!(normalize("いい", all), "いい");
+        assert_eq!(normalize("ね〜〜", SUDACHI_NORMALIZE_WAVE_DASH), "ねーー");
+    }
+}
@@ -0,0 +1,298 @@
+// Dictionary diffs
+// Dictionary updates ship with a changelog of what changed for users. Two
+// compiled system dictionaries are compared entry by entry: the lexicon
+// header gives the number of entries, and each word id's info and
+// parameters are read through the lexicon. Entries are matched by surface,
+// part of speech and reading; an entry whose other columns differ is
+// changed, and one found on only one side is added or removed. Columns that
+// refer to other entries (splits, word structure) are compared by the
+// surfaces they point to, so entries that only moved are not reported.
+//
+// The report is JSON Lines, one object per difference:
+//   {"change":"removed","surface":"…","pos":[…],"reading":"…"}
+//   {"change":"changed","surface":"…","pos":[…],"reading":"…",
+//    "columns":{"cost":["5000","4500"]}}
+//   {"change":"added","surface":"…","pos":[…],"reading":"…"}
+// then one summary line:
+//   {"summary":{"added":1,"removed":1,"changed":1,"unchanged":120}}
+// Only the keys of the new build are held in memory. Removed and changed
+// entries are written while the old build is walked, in word id order;
+// added entries follow, in the word id order of the new build.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::os::raw::c_char;
+
+use memmap2::Mmap;
+use serde_json::{json, Map, Value};
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
+use sudachi::dic::dictionary::JapaneseDictionary;
+use sudachi::dic::storage::Storage;
+use sudachi::dic::subset::InfoSubset;
+use sudachi::dic::word_id::WordId;
+use sudachi::prelude::*;
+
+use crate::{c_str, dictionary_from_storage, mapping, LoadedDictionary};
+
+/// An entry of a build: its identity and the columns compared
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    surface: String,
+    pos: Vec<String>,
+    reading: String,
+    columns: Vec<(&'static str, String)>,
+}
+
+impl Entry {
+    /// Identity of the entry: surface, POS and reading
+    fn key(&self) -> String {
+        let mut key = vec![self.surface.as_str()];
+        key.extend(self.pos.iter().map(String::as_str));
+        key.push(&self.reading);
+        key.join("\u{1f}")
+    }
+}
+
+/// Counts of a diff
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Summary {
+    added: usize,
+    removed: usize,
+    changed: usize,
+    unchanged: usize,
+}
+
+/// Surfaces of the entries ids point to, joined with '/'
+fn surfaces<D: DictionaryAccess>(dictionary: &D, ids: &[WordId]) -> String {
+    ids.iter()
+        .map(|&id| {
+            dictionary
+                .lexicon()
+                .get_word_info_subset(id, InfoSubset::SURFACE)
+                .map_or_else(|_| id.word().to_string(), |i| i.surface().to_string())
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Read entry word of the system dictionary
+fn entry<D: DictionaryAccess>(dictionary: &D, word: u32) -> SudachiResult<Entry> {
+    let id = WordId::new(0, word);
+    let lexicon = dictionary.lexicon();
+    let info = lexicon.get_word_info(id)?;
+    let (left_id, right_id, cost) = lexicon.get_word_param(id);
+    let pos = dictionary
+        .grammar()
+        .pos_list
+        .get(usize::from(info.pos_id()))
+        .cloned()
+        .unwrap_or_default();
+    let synonyms: Vec<String> = info
+        .synonym_group_ids()
+        .iter()
+        .map(u32::to_string)
+        .collect();
+    Ok(Entry {
+        surface: info.surface().to_string(),
+        pos,
+        reading: info.reading_form().to_string(),
+        columns: vec![
+            ("left_id", left_id.to_string()),
+            ("right_id", right_id.to_string()),
+            ("cost", cost.to_string()),
+            ("headword_length", info.head_word_length().to_string()),
+            ("normalized_form", info.normalized_form().to_string()),
+            ("dictionary_form", info.dictionary_form().to_string()),
+            ("a_split", surfaces(dictionary, info.a_unit_split())),
+            ("b_split", surfaces(dictionary, info.b_unit_split())),
+            (
+                "word_structure",
+                surfaces(dictionary, info.word_structure()),
+            ),
+            ("synonym_groups", synonyms.join("/")),
+        ],
+    })
+}
+
+/// JSON object of a change to entry
+fn change(change: &str, entry: &Entry) -> Map<String, Value> {
+    let mut object = Map::new();
+    object.insert("change".into(), json!(change));
+    object.insert("surface".into(), json!(entry.surface));
+    object.insert("pos".into(), json!(entry.pos));
+    object.insert("reading".into(), json!(entry.reading));
+    object
+}
+
+/// Write the differences from build a (words_a entries) to build b to out
+/// as JSON Lines
+fn diff(
+    words_a: u32,
+    mut a: impl FnMut(u32) -> io::Result<Entry>,
+    words_b: u32,
+    mut b: impl FnMut(u32) -> io::Result<Entry>,
+    out: &mut impl Write,
+) -> io::Result<Summary> {
+    // Word ids of b by key; entries sharing a key pair up in order
+    let mut by_key: HashMap<String, Vec<u32>> = HashMap::new();
+    for word in (0..words_b).rev() {
+        by_key.entry(b(word)?.key()).or_default().push(word);
+    }
+    let mut matched = vec![false; words_b as usize];
+    let mut summary = Summary::default();
+
+    let mut write = |object: Map<String, Value>| writeln!(out, "{}", Value::Object(object));
+    for word in 0..words_a {
+        let old = a(word)?;
+        let Some(other) = by_key.get_mut(&old.key()).and_then(Vec::pop) else {
+            summary.removed += 1;
+            write(change("removed", &old))?;
+            continue;
+        };
+        matched[other as usize] = true;
+        let new = b(other)?;
+        let mut columns = Map::new();
+        for ((name, old), (_, new)) in old.columns.iter().zip(&new.columns) {
+            if old != new {
+                columns.insert((*name).into(), json!([old, new]));
+            }
+        }
+        if columns.is_empty() {
+            summary.unchanged += 1;
+        } else {
+            summary.changed += 1;
+            let mut object = change("changed", &old);
+            object.insert("columns".into(), Value::Object(columns));
+            write(object)?;
+        }
+    }
+    for word in (0..words_b).filter(|&w| !matched[w as usize]) {
+        summary.added += 1;
+        write(change("added", &b(word)?))?;
+    }
+    let totals = json!({
+        "summary": {
+            "added": summary.added,
+            "removed": summary.removed,
+            "changed": summary.changed,
+            "unchanged": summary.unchanged,
+        }
+    });
+    writeln!(out, "{}", totals)?;
+    Ok(summary)
+}
+
+/// Map a system dictionary and count its entries
+fn open(path: &str) -> Result<(LoadedDictionary, u32), String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mapping =
+        unsafe { Mmap::map(&file) }.map_err(|e| format!("Failed to map {}: {}", path, e))?;
+    let words = mapping::word_count(&mapping)
+        .and_then(|w| u32::try_from(w).ok())
+        .ok_or_else(|| format!("Not a system dictionary: {}", path))?;
+    Ok((dictionary_from_storage(Storage::File(mapping))?, words))
+}
+
+/// Compare two builds of a system dictionary and write the added, removed
+/// and changed entries to out_path as JSON Lines (see README for the
+/// format)
+/// Returns false if an argument is NULL, a dictionary cannot be loaded or
+/// the report cannot be written.
+#[no_mangle]
+pub extern "C" fn sudachi_dict_diff(
+    path_a: *const c_char,
+    path_b: *const c_char,
+    out_path: *const c_char,
+) -> bool {
+    let (Some(path_a), Some(path_b), Some(out_path)) =
+        (c_str(path_a), c_str(path_b), c_str(out_path))
+    else {
+        return false;
+    };
+    let (a, words_a, b, words_b) = match (open(path_a), open(path_b)) {
+        (Ok((a, words_a)), Ok((b, words_b))) => (a.dictionary, words_a, b.dictionary, words_b),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    let read = |dictionary: &JapaneseDictionary, word| {
+        entry(dictionary, word).map_err(|e| io::Error::other(e.to_string()))
+    };
+    let written = File::create(out_path).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        diff(
+            words_a,
+            |word| read(&a, word),
+            words_b,
+            |word| read(&b, word),
+            &mut out,
+        )?;
+        out.flush()
+    });
+    match written {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", out_path, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noun(surface: &str, reading: &str, cost: i16) -> Entry {
+        Entry {
+            surface: surface.to_string(),
+            pos: ["名詞", "普通名詞", "一般", "*", "*", "*"]
+                .map(String::from)
+                .to_vec(),
+            reading: reading.to_string(),
+            columns: vec![("cost", cost.to_string()), ("a_split", String::new())],
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = [
+            noun("猫", "ネコ", 5000),
+            noun("食べる", "タベル", 4000),
+            noun("食べ", "タベ", 4000),
+            noun("犬", "イヌ", 5000),
+        ];
+        let b = [
+            noun("鳥", "トリ", 5000),
+            noun("猫", "ネコ", 4500),
+            noun("食べる", "タベル", 4000),
+            noun("食べ", "タベ", 4000),
+        ];
+        let get = |entries: &[Entry], word: u32| Ok(entries[word as usize].clone());
+        let mut out = Vec::new();
+        let summary = diff(4, |w| get(&a, w), 4, |w| get(&b, w), &mut out).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                added: 1,
+                removed: 1,
+                changed: 1,
+                unchanged: 2,
+            }
+        );
+        let lines: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["change"], "changed");
+        assert_eq!(lines[0]["columns"], json!({"cost": ["5000", "4500"]}));
+        assert_eq!(lines[1]["change"], "removed");
+        assert_eq!(lines[1]["surface"], "犬");
+        assert_eq!(lines[2]["change"], "added");
+        assert_eq!(lines[2]["reading"], "トリ");
+        assert_eq!(lines[3]["summary"]["unchanged"], 2);
+    }
+}
@@ -0,0 +1,155 @@
+// User dictionaries
+// App-specific vocabulary ships as user dictionaries next to the system
+// dictionary. Sudachi picks among entries by cost alone, so a stale entry of
+// the general dictionary can win over the app's own. Each dictionary gets a
+// cost bias: after analysis, a token is swapped for the entry of the same
+// span with the lowest word cost plus the bias of its dictionary. The bias
+// re-ranks entries of the same surface only; segmentation is left to the
+// lattice.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
+use sudachi::dic::word_id::WordId;
+
+use crate::lookup::entry_token;
+use crate::mapping::SudachiInitOptions;
+use crate::{
+    c_str, handle, load_dictionaries, SudachiToken, SudachiTokenizer, TokenData, SUDACHI_TOKEN_OOV,
+    SUDACHI_TOKEN_OVERRIDE,
+};
+
+/// Most user dictionaries a word id can address
+pub const SUDACHI_MAX_USER_DICTIONARIES: usize = 14;
+
+/// Word cost of an entry adjusted by the bias of its dictionary
+fn biased(cost: i16, dictionary: u8, bias: &[i32]) -> i32 {
+    cost as i32 + bias.get(dictionary as usize).copied().unwrap_or(0)
+}
+
+/// Entry of the same span as token that wins under bias, if not the one the
+/// lattice chose
+/// Ties go to the lattice's choice, then to the lowest word id.
+/// text is the analyzed text token's offsets point into.
+pub(crate) fn rerank<D: DictionaryAccess>(
+    dictionary: &D,
+    text: &str,
+    token: &TokenData,
+    bias: &[i32],
+) -> Option<TokenData> {
+    let chosen = WordId::from_raw(token.word_id);
+    if chosen.is_oov() || token.flags & SUDACHI_TOKEN_OOV != 0 {
+        return None;
+    }
+    let lexicon = dictionary.lexicon();
+    let score = |id: WordId| biased(lexicon.get_word_param(id).2, id.dic(), bias);
+
+    let best = lexicon
+        .lookup(text.as_bytes(), token.begin)
+        .filter(|entry| entry.end == token.end)
+        .map(|entry| (score(entry.word_id), entry.word_id))
+        .min_by_key(|&(score, id)| (score, id.as_raw()))?;
+    if best.1 == chosen || best.0 >= score(chosen) {
+        return None;
+    }
+
+    let mut better = entry_token(dictionary, best.1, &text[token.begin..token.end]).ok()?;
+    better.fields = token.fields;
+    better.begin = token.begin;
+    better.end = token.end;
+    better.flags = token.flags;
+    Some(better)
+}
+
+/// Initialize a tokenizer with user dictionaries
+/// user_paths lists user_count dictionary files (at most
+/// SUDACHI_MAX_USER_DICTIONARIES); the first is dictionary 1, the system
+/// dictionary is dictionary 0.
+/// Returns NULL on failure
+#[no_mangle]
+pub extern "C" fn sudachi_init_with_user_dictionaries(
+    dict_path: *const c_char,
+    user_paths: *const *const c_char,
+    user_count: usize,
+) -> *mut SudachiTokenizer {
+    if (user_paths.is_null() && user_count > 0) || user_count > SUDACHI_MAX_USER_DICTIONARIES {
+        return ptr::null_mut();
+    }
+    let Some(path) = c_str(dict_path) else {
+        return ptr::null_mut();
+    };
+    let users: Option<Vec<&str>> = if user_count == 0 {
+        Some(Vec::new())
+    } else {
+        unsafe { std::slice::from_raw_parts(user_paths, user_count) }
+            .iter()
+            .map(|&p| c_str(p))
+            .collect()
+    };
+    let Some(users) = users else {
+        return ptr::null_mut();
+    };
+
+    match load_dictionaries(path, &users, &SudachiInitOptions::default()) {
+        Ok(loaded) => handle::into_raw(SudachiTokenizer::new(loaded)),
+        Err(e) => {
+            eprintln!("{}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Set the cost bias of a dictionary on a handle
+/// Negative values favor entries of the dictionary over entries of the same
+/// surface in other dictionaries; all biases start at 0. Returns false if
+/// tokenizer is NULL or dictionary is out of range.
+#[no_mangle]
+pub extern "C" fn sudachi_set_dictionary_bias(
+    tokenizer: *mut SudachiTokenizer,
+    dictionary: u32,
+    bias: i32,
+) -> bool {
+    let dictionary = dictionary as usize;
+    if tokenizer.is_null() || dictionary > SUDACHI_MAX_USER_DICTIONARIES {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| {
+        if o.dictionary_bias.len() <= dictionary {
+            o.dictionary_bias.resize(dictionary + 1, 0);
+        }
+        o.dictionary_bias[dictionary] = bias;
+    });
+    true
+}
+
+/// Get the dictionary a token came from
+/// Returns 0 for the system dictionary, 1 and up for user dictionaries in
+/// load order, and -1 for unknown words, pinned tokens and NULL
+#[no_mangle]
+pub extern "C" fn sudachi_token_dictionary(token: *const SudachiToken) -> i32 {
+    if token.is_null() {
+        return -1;
+    }
+    let token = unsafe { handle::borrow(token) };
+    let word_id = WordId::from_raw(token.word_id);
+    if token.flags & (SUDACHI_TOKEN_OOV | SUDACHI_TOKEN_OVERRIDE) != 0 || word_id.is_oov() {
+        return -1;
+    }
+    word_id.dic() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_biased() {
+        // App vocabulary (dictionary 1) at cost 5000 loses to a general
+        // entry at 4000 until biased
+        let bias = [0, -2000];
+        assert!(biased(5000, 1, &[]) > biased(4000, 0, &[]));
+        assert!(biased(5000, 1, &bias) < biased(4000, 0, &bias));
+        assert_eq!(biased(100, 7, &bias), 100);
+    }
+}
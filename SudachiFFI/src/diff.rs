@@ -0,0 +1,191 @@
+// Token stream diff
+// After a dictionary update or an override edit the app shows what changed,
+// and cache tests check that an invalidated entry really differs. Two
+// results of the same text are aligned by byte range: tokens with the same
+// range are compared field by field, and tokens whose range only appears in
+// one result were removed or inserted (a resegmented span shows up as both).
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::{handle, leak, SudachiToken};
+
+/// Kind of difference between two token streams
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SudachiDiffKind {
+    /// Only in the first result
+    Removed = 0,
+    /// Only in the second result
+    Inserted = 1,
+    /// Same range in both, with a different reading, form, POS or id
+    Changed = 2,
+}
+
+/// One difference; index_a and index_b are -1 where the token is absent
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SudachiTokenDiff {
+    pub kind: SudachiDiffKind,
+    pub index_a: i32,
+    pub index_b: i32,
+}
+
+/// Differences between two streams of byte ranges, in text order
+/// Both streams must be sorted by offset; same tells whether the tokens at
+/// two indices with equal ranges are equal.
+fn align(
+    a: &[(i32, i32)],
+    b: &[(i32, i32)],
+    same: impl Fn(usize, usize) -> bool,
+) -> Vec<SudachiTokenDiff> {
+    let diff = |kind, index_a: Option<usize>, index_b: Option<usize>| SudachiTokenDiff {
+        kind,
+        index_a: index_a.map_or(-1, |i| i as i32),
+        index_b: index_b.map_or(-1, |j| j as i32),
+    };
+    let mut diffs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        match (a.get(i), b.get(j)) {
+            (Some(x), Some(y)) if x == y => {
+                if !same(i, j) {
+                    diffs.push(diff(SudachiDiffKind::Changed, Some(i), Some(j)));
+                }
+                i += 1;
+                j += 1;
+            }
+            (Some(x), Some(y)) if x < y => {
+                diffs.push(diff(SudachiDiffKind::Removed, Some(i), None));
+                i += 1;
+            }
+            (Some(_), None) => {
+                diffs.push(diff(SudachiDiffKind::Removed, Some(i), None));
+                i += 1;
+            }
+            _ => {
+                diffs.push(diff(SudachiDiffKind::Inserted, None, Some(j)));
+                j += 1;
+            }
+        }
+    }
+    diffs
+}
+
+fn same_string(a: *const c_char, b: *const c_char) -> bool {
+    match (a.is_null(), b.is_null()) {
+        (true, true) => true,
+        (false, false) => unsafe { CStr::from_ptr(a) == CStr::from_ptr(b) },
+        _ => false,
+    }
+}
+
+fn same_token(a: &SudachiToken, b: &SudachiToken) -> bool {
+    a.word_id == b.word_id
+        && a.pos_id == b.pos_id
+        && a.class == b.class
+        && a.flags == b.flags
+        && [
+            (a.surface, b.surface),
+            (a.reading, b.reading),
+            (a.dictionary_form, b.dictionary_form),
+            (a.normalized_form, b.normalized_form),
+            (a.pos, b.pos),
+            (a.dictionary_reading, b.dictionary_reading),
+        ]
+        .into_iter()
+        .all(|(x, y)| same_string(x, y))
+}
+
+/// Borrow a token array, or None if it holds a NULL token
+fn tokens<'a>(tokens: *const *mut SudachiToken, count: usize) -> Option<Vec<&'a SudachiToken>> {
+    if count == 0 {
+        return Some(Vec::new());
+    }
+    let tokens = unsafe { std::slice::from_raw_parts(tokens, count) };
+    tokens
+        .iter()
+        .map(|&t| (!t.is_null()).then(|| unsafe { handle::borrow(t) }))
+        .collect()
+}
+
+/// Compare two results of analyzing the same text
+/// Both must come from sudachi_tokenize or sudachi_tokenize_fields with the
+/// same field mask, or unselected fields count as changes. Unchanged tokens
+/// are not reported; an empty result sets out_count to 0.
+/// Returns NULL on failure (caller must free with sudachi_free_diff)
+#[no_mangle]
+pub extern "C" fn sudachi_diff(
+    tokens_a: *const *mut SudachiToken,
+    count_a: usize,
+    tokens_b: *const *mut SudachiToken,
+    count_b: usize,
+    out_count: *mut usize,
+) -> *mut SudachiTokenDiff {
+    if (tokens_a.is_null() && count_a > 0)
+        || (tokens_b.is_null() && count_b > 0)
+        || out_count.is_null()
+    {
+        return ptr::null_mut();
+    }
+    let (Some(a), Some(b)) = (tokens(tokens_a, count_a), tokens(tokens_b, count_b)) else {
+        return ptr::null_mut();
+    };
+
+    let ranges = |tokens: &[&SudachiToken]| -> Vec<(i32, i32)> {
+        tokens.iter().map(|t| (t.begin, t.end)).collect()
+    };
+    let diffs = align(&ranges(&a), &ranges(&b), |i, j| same_token(a[i], b[j]));
+
+    unsafe {
+        *out_count = diffs.len();
+    }
+    let diffs = Box::leak(diffs.into_boxed_slice()).as_mut_ptr();
+    leak::track(diffs, "diff");
+    diffs
+}
+
+/// Free the result of sudachi_diff
+#[no_mangle]
+pub extern "C" fn sudachi_free_diff(diffs: *mut SudachiTokenDiff, count: usize) {
+    if !diffs.is_null() {
+        leak::release(diffs);
+        unsafe {
+            let _ = Box::from_raw(ptr::slice_from_raw_parts_mut(diffs, count));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align() {
+        use SudachiDiffKind::*;
+        let diff = |kind, index_a, index_b| SudachiTokenDiff {
+            kind,
+            index_a,
+            index_b,
+        };
+
+        // 七つ|の|大|罪 resegmented as 七つ|の|大罪, with の read differently
+        let a = [(0, 6), (6, 9), (9, 12), (12, 15)];
+        let b = [(0, 6), (6, 9), (9, 15)];
+        let diffs = align(&a, &b, |i, _| i != 1);
+        assert_eq!(
+            diffs,
+            [
+                diff(Changed, 1, 1),
+                diff(Removed, 2, -1),
+                diff(Inserted, -1, 2),
+                diff(Removed, 3, -1),
+            ]
+        );
+
+        assert!(align(&a, &a, |_, _| true).is_empty());
+        assert_eq!(align(&[], &b[..1], |_, _| true), [diff(Inserted, -1, 0)]);
+    }
+}
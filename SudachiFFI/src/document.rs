@@ -0,0 +1,403 @@
+// EPUB chapter analysis
+// Opening a book used to take five round trips per chapter from Swift: strip
+// the XHTML, tokenize, build furigana, collect vocabulary, then stitch the
+// results together. A document takes the chapters of a book in spine order,
+// does all of that in one pass per chapter (chapters are analyzed in parallel
+// like a batch) and returns one JSON bundle.
+//
+// Text extraction keeps what a reader sees: <head>, <script> and <style> are
+// dropped, and so are <rt> and <rp>, so the book's own ruby doesn't end up in
+// the text (furigana are generated from the analysis instead). Block elements
+// and <br> end a line. Runs of markup whitespace become one space between
+// ASCII characters and disappear next to Japanese text, where they are only
+// line wrapping in the source.
+//
+// Vocabulary is the content words of the book (as in readability reports),
+// identified by lemma key, in order of first appearance.
+//
+// Bundle layout:
+//   { "chapters": [ { "index", "text", "failed",
+//                     "tokens": [ { "begin", "end", "surface",
+//                                   "dictionary_form", "normalized_form",
+//                                   "reading", "pos", "class", "oov",
+//                                   "lemma_key" } ],
+//                     "furigana": [ { "begin", "end", "ruby" } ],
+//                     "vocabulary": [ { "lemma_key", "count" } ] } ],
+//     "vocabulary": [ { "lemma_key", "dictionary_form", "reading", "pos",
+//                       "count", "chapters", "first_chapter" } ] }
+// Offsets are byte offsets into the chapter's extracted text.
+
+use std::collections::HashMap;
+use std::os::raw::c_char;
+use std::ptr;
+
+use serde_json::{json, Value};
+
+use crate::batch::analyze_all;
+use crate::furigana::furigana;
+use crate::progress::Tracker;
+use crate::readability::is_content;
+use crate::{
+    c_str, handle, into_c_string, leak, SudachiTokenMode, SudachiTokenizer, TokenData,
+    SUDACHI_FIELD_ALL, SUDACHI_TOKEN_OOV,
+};
+
+/// Elements whose content is not part of the text
+const SKIPPED: [&str; 5] = ["head", "script", "style", "rt", "rp"];
+/// Elements that start and end a line
+const BLOCKS: [&str; 22] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dt",
+    "figcaption",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "p",
+    "section",
+    "tr",
+];
+
+/// Visible text being collected from markup
+#[derive(Default)]
+struct Extracted {
+    text: String,
+    /// Whitespace seen since the last character
+    space: bool,
+}
+
+impl Extracted {
+    fn push(&mut self, s: &str) {
+        for c in s.chars() {
+            if c.is_ascii_whitespace() {
+                self.space = true;
+                continue;
+            }
+            if self.space && c.is_ascii() && self.text.chars().last().is_some_and(|p| p.is_ascii())
+            {
+                self.text.push(' ');
+            }
+            self.space = false;
+            self.text.push(c);
+        }
+    }
+
+    fn break_line(&mut self) {
+        self.space = false;
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+}
+
+/// Decode character references in a run of text
+fn decode(text: &str, out: &mut Extracted) {
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').and_then(|semi| {
+            let name = &rest[1..semi];
+            let c = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => {
+                    let code =
+                        if let Some(hex) = name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                            u32::from_str_radix(hex, 16).ok()
+                        } else {
+                            name.strip_prefix('#').and_then(|d| d.parse().ok())
+                        };
+                    code.and_then(char::from_u32)
+                }
+            };
+            c.map(|c| (c, semi + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c.encode_utf8(&mut [0; 4]));
+                rest = &rest[len..];
+            }
+            None => {
+                out.push("&");
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push(rest);
+}
+
+/// End of the tag starting at markup[0] ('<'), skipping quoted attributes
+fn tag_end(markup: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in markup.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Visible text of an XHTML document, one line per block
+pub(crate) fn extract(xhtml: &str) -> String {
+    let mut out = Extracted::default();
+    // Depth inside skipped elements
+    let mut skip = 0usize;
+    let mut rest = xhtml;
+    while let Some(lt) = rest.find('<') {
+        if skip == 0 {
+            decode(&rest[..lt], &mut out);
+        }
+        rest = &rest[lt..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").unwrap_or(cdata.len());
+            if skip == 0 {
+                out.push(&cdata[..end]);
+            }
+            rest = cdata.get(end + 3..).unwrap_or("");
+            continue;
+        }
+        let Some(end) = tag_end(rest) else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with(['!', '?']) {
+            continue;
+        }
+
+        let closing = tag.starts_with('/');
+        let self_closing = tag.ends_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_ascii_whitespace() || c == '/')
+            .next()
+            .unwrap_or("");
+        // Drop a namespace prefix (xhtml:p)
+        let name = name.rsplit(':').next().unwrap_or(name).to_ascii_lowercase();
+
+        if SKIPPED.contains(&name.as_str()) {
+            if closing {
+                skip = skip.saturating_sub(1);
+            } else if !self_closing {
+                skip += 1;
+            }
+        } else if skip == 0 && BLOCKS.contains(&name.as_str()) {
+            out.break_line();
+        }
+    }
+    if skip == 0 {
+        decode(rest, &mut out);
+    }
+    let mut text = out.text;
+    text.truncate(text.trim_end().len());
+    text
+}
+
+/// A content word of the book
+struct Entry {
+    token: TokenData,
+    count: u64,
+    chapters: u64,
+    first_chapter: usize,
+}
+
+/// Vocabulary of a whole book, in order of first appearance
+#[derive(Default)]
+struct Vocabulary {
+    entries: Vec<Entry>,
+    index: HashMap<u64, usize>,
+}
+
+impl Vocabulary {
+    /// Count the content words of one chapter
+    /// Returns (lemma key, count) of each of them, in order of appearance
+    fn add(&mut self, chapter: usize, tokens: &[TokenData]) -> Vec<(u64, u64)> {
+        let mut counts: Vec<(u64, u64)> = Vec::new();
+        let mut seen: HashMap<u64, usize> = HashMap::new();
+        for token in tokens {
+            if token.lemma_key == 0 || !is_content(token) {
+                continue;
+            }
+            match seen.get(&token.lemma_key) {
+                Some(&i) => counts[i].1 += 1,
+                None => {
+                    seen.insert(token.lemma_key, counts.len());
+                    counts.push((token.lemma_key, 1));
+                }
+            }
+            let i = *self.index.entry(token.lemma_key).or_insert_with(|| {
+                self.entries.push(Entry {
+                    token: token.clone(),
+                    count: 0,
+                    chapters: 0,
+                    first_chapter: chapter,
+                });
+                self.entries.len() - 1
+            });
+            self.entries[i].count += 1;
+        }
+        for (key, _) in &counts {
+            self.entries[self.index[key]].chapters += 1;
+        }
+        counts
+    }
+
+    fn json(&self) -> Value {
+        self.entries
+            .iter()
+            .map(|e| {
+                json!({
+                    "lemma_key": e.token.lemma_key,
+                    "dictionary_form": e.token.dictionary_form,
+                    "reading": e.token.dictionary_reading,
+                    "pos": e.token.pos,
+                    "count": e.count,
+                    "chapters": e.chapters,
+                    "first_chapter": e.first_chapter,
+                })
+            })
+            .collect()
+    }
+}
+
+fn token_json(token: &TokenData) -> Value {
+    json!({
+        "begin": token.begin,
+        "end": token.end,
+        "surface": token.surface,
+        "dictionary_form": token.dictionary_form,
+        "normalized_form": token.normalized_form,
+        "reading": token.reading,
+        "pos": token.pos,
+        "class": format!("{:?}", token.class),
+        "oov": token.flags & SUDACHI_TOKEN_OOV != 0,
+        "lemma_key": token.lemma_key,
+    })
+}
+
+/// Bundle entry of one chapter; text or tokens is None if it failed
+fn chapter_json(
+    index: usize,
+    text: Option<&str>,
+    tokens: Option<&[TokenData]>,
+    vocabulary: &mut Vocabulary,
+) -> Value {
+    let failed = text.is_none() || tokens.is_none();
+    let tokens = tokens.unwrap_or_default();
+    let ruby: Vec<Value> = furigana(tokens)
+        .into_iter()
+        .filter_map(|r| {
+            r.reading
+                .map(|ruby| json!({ "begin": r.begin, "end": r.end, "ruby": ruby }))
+        })
+        .collect();
+    let words: Vec<Value> = vocabulary
+        .add(index, tokens)
+        .into_iter()
+        .map(|(key, count)| json!({ "lemma_key": key, "count": count }))
+        .collect();
+    json!({
+        "index": index,
+        "text": text.unwrap_or(""),
+        "failed": failed,
+        "tokens": tokens.iter().map(token_json).collect::<Vec<_>>(),
+        "furigana": ruby,
+        "vocabulary": words,
+    })
+}
+
+/// Analyze the XHTML chapters of a book into one JSON bundle
+/// chapters are in spine order; each is stripped to its text, tokenized,
+/// given furigana and searched for vocabulary. Chapters are analyzed on
+/// threads workers (0 uses one per available core). A chapter that is NULL,
+/// not valid UTF-8 or fails to analyze has "failed": true and no tokens.
+/// Returns NULL if tokenizer or chapters is NULL (caller must free with
+/// sudachi_free_string)
+#[no_mangle]
+pub extern "C" fn sudachi_document_analyze(
+    tokenizer: *mut SudachiTokenizer,
+    chapters: *const *const c_char,
+    chapter_count: usize,
+    mode: SudachiTokenMode,
+    threads: usize,
+) -> *mut c_char {
+    if tokenizer.is_null() || chapters.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let texts: Vec<Option<String>> = unsafe { std::slice::from_raw_parts(chapters, chapter_count) }
+        .iter()
+        .map(|&chapter| c_str(chapter).map(extract))
+        .collect();
+    let borrowed: Vec<Option<&str>> = texts.iter().map(Option::as_deref).collect();
+    let tracker = Tracker::new(None, 0, 0);
+    let results = analyze_all(
+        tokenizer,
+        &borrowed,
+        mode,
+        SUDACHI_FIELD_ALL,
+        threads,
+        &tracker,
+    );
+
+    let mut vocabulary = Vocabulary::default();
+    let chapters: Vec<Value> = results
+        .iter()
+        .enumerate()
+        .map(|(i, tokens)| chapter_json(i, borrowed[i], tokens.as_deref(), &mut vocabulary))
+        .collect();
+    let bundle = json!({ "chapters": chapters, "vocabulary": vocabulary.json() });
+    let json = into_c_string(&bundle.to_string());
+    leak::track(json, "string");
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let xhtml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>第一章</title><style>p { margin: 0 }</style></head>
+<body>
+  <h1 class="title">第一章</h1>
+  <p>　<ruby>吾輩<rp>(</rp><rt>わがはい</rt><rp>)</rp></ruby>は猫で
+  ある。<!-- note --></p>
+  <p title="a > b">Tom &amp; Jerry<br/>&#x3042;&#12356;&unknown;</p>
+</body>
+</html>"#;
+        assert_eq!(
+            extract(xhtml),
+            "第一章\n　吾輩は猫である。\nTom & Jerry\nあい&unknown;"
+        );
+    }
+}
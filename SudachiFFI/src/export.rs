@@ -0,0 +1,280 @@
+// Token table export
+// Users who study their reading data in pandas or R want one row per token,
+// not an API. An export streams the tokens of a document set into a CSV or
+// TSV file: documents are analyzed and written one at a time, so a whole
+// library never has to be held in memory. Each row carries the document id,
+// the sentence index within the document (sentences are split as in
+// sentences.rs), the token index, byte offsets, the forms, the reading, the six
+// POS columns, the OOV flag and the lemma key. Whitespace tokens are not
+// written.
+//
+// CSV follows RFC 4180 (fields with commas, quotes or line breaks are
+// quoted). TSV is unquoted; tabs and line breaks inside fields, which only
+// document ids can contain, are written as spaces.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::sentences::sentences;
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    c_str, handle, leak, SudachiTokenMode, SudachiTokenizer, TokenData, SUDACHI_FIELD_ALL,
+    SUDACHI_TOKEN_OOV,
+};
+
+/// Column names, in order
+const COLUMNS: [&str; 18] = [
+    "document",
+    "sentence",
+    "token",
+    "begin",
+    "end",
+    "surface",
+    "dictionary_form",
+    "normalized_form",
+    "reading",
+    "dictionary_reading",
+    "pos1",
+    "pos2",
+    "pos3",
+    "pos4",
+    "conjugation_type",
+    "conjugation_form",
+    "oov",
+    "lemma_key",
+];
+
+/// File format of an export
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SudachiExportFormat {
+    /// Comma-separated, RFC 4180 quoting
+    Csv = 0,
+    /// Tab-separated, unquoted
+    Tsv = 1,
+}
+
+impl SudachiExportFormat {
+    fn write_field(self, out: &mut String, field: &str) {
+        match self {
+            SudachiExportFormat::Csv if field.contains([',', '"', '\n', '\r']) => {
+                out.push('"');
+                out.push_str(&field.replace('"', "\"\""));
+                out.push('"');
+            }
+            SudachiExportFormat::Csv => out.push_str(field),
+            SudachiExportFormat::Tsv => {
+                out.extend(field.chars().map(|c| match c {
+                    '\t' | '\n' | '\r' => ' ',
+                    c => c,
+                }));
+            }
+        }
+    }
+
+    fn write_row<'a>(self, out: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+        let separator = match self {
+            SudachiExportFormat::Csv => ',',
+            SudachiExportFormat::Tsv => '\t',
+        };
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                out.push(separator);
+            }
+            self.write_field(out, field);
+        }
+        out.push('\n');
+    }
+}
+
+/// Rows of one document
+fn rows(format: SudachiExportFormat, document: &str, tokens: &[TokenData]) -> String {
+    let mut out = String::new();
+    let mut sentence = 0;
+    let mut index = 0;
+    for range in sentences(tokens, 0) {
+        // Sentences of only whitespace have no rows and no index
+        let mut open = false;
+        for token in &tokens[range] {
+            if token.class != SudachiTokenClass::Whitespace {
+                let numbers = [
+                    sentence.to_string(),
+                    index.to_string(),
+                    token.begin.to_string(),
+                    token.end.to_string(),
+                ];
+                let pos = (0..6).map(|i| token.pos.get(i).map_or("", String::as_str));
+                let oov = if token.flags & SUDACHI_TOKEN_OOV != 0 {
+                    "1"
+                } else {
+                    "0"
+                };
+                let lemma_key = token.lemma_key.to_string();
+                let fields = [document]
+                    .into_iter()
+                    .chain(numbers.iter().map(String::as_str))
+                    .chain([
+                        token.surface.as_str(),
+                        &token.dictionary_form,
+                        &token.normalized_form,
+                        &token.reading,
+                        &token.dictionary_reading,
+                    ])
+                    .chain(pos)
+                    .chain([oov, &lemma_key]);
+                format.write_row(&mut out, fields);
+                index += 1;
+                open = true;
+            }
+        }
+        if open {
+            sentence += 1;
+        }
+    }
+    out
+}
+
+/// Opaque handle to a token table being written
+pub struct SudachiExport {
+    format: SudachiExportFormat,
+    /// None once a write failed
+    writer: Mutex<Option<BufWriter<File>>>,
+}
+
+impl SudachiExport {
+    fn writer(&self) -> MutexGuard<'_, Option<BufWriter<File>>> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Write text, dropping the writer on failure
+    fn write(&self, text: &str) -> bool {
+        let mut writer = self.writer();
+        let ok = writer
+            .as_mut()
+            .is_some_and(|w| w.write_all(text.as_bytes()).is_ok());
+        if !ok {
+            *writer = None;
+        }
+        ok
+    }
+}
+
+/// Create the file at path and write the header row
+/// An existing file is replaced. Returns NULL if path is NULL or the file
+/// can't be written (caller must close with sudachi_export_close)
+#[no_mangle]
+pub extern "C" fn sudachi_export_open(
+    path: *const c_char,
+    format: SudachiExportFormat,
+) -> *mut SudachiExport {
+    let Some(path) = c_str(path) else {
+        return ptr::null_mut();
+    };
+    let Ok(file) = File::create(path) else {
+        return ptr::null_mut();
+    };
+    let export = SudachiExport {
+        format,
+        writer: Mutex::new(Some(BufWriter::new(file))),
+    };
+    let mut header = String::new();
+    format.write_row(&mut header, COLUMNS);
+    if !export.write(&header) {
+        return ptr::null_mut();
+    }
+
+    let export = Box::into_raw(Box::new(export));
+    leak::track(export, "SudachiExport");
+    export
+}
+
+/// Analyze a document and append its tokens
+/// document is the id written in the first column. Rows of one document are
+/// written together, so documents may be added from several threads. Returns
+/// false if an argument is NULL, not valid UTF-8, the analysis fails or a
+/// write failed (later documents are then rejected too).
+#[no_mangle]
+pub extern "C" fn sudachi_export_document(
+    export: *mut SudachiExport,
+    tokenizer: *mut SudachiTokenizer,
+    document: *const c_char,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+) -> bool {
+    if export.is_null() || tokenizer.is_null() {
+        return false;
+    }
+    let (Some(document), Some(text)) = (c_str(document), c_str(text)) else {
+        return false;
+    };
+    let export = unsafe { &*export };
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Ok(tokens) = tokenizer.analyze(text, mode.into(), SUDACHI_FIELD_ALL) else {
+        return false;
+    };
+    export.write(&rows(export.format, document, &tokens))
+}
+
+/// Flush and close an export
+/// Returns false if export is NULL or any write failed
+#[no_mangle]
+pub extern "C" fn sudachi_export_close(export: *mut SudachiExport) -> bool {
+    if export.is_null() {
+        return false;
+    }
+    leak::release(export);
+    let export = unsafe { Box::from_raw(export) };
+    let writer = export.writer().take();
+    writer.is_some_and(|mut w| w.flush().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+
+    fn tokens_of(words: &[(&str, &str)]) -> Vec<TokenData> {
+        let mut begin = 0;
+        words
+            .iter()
+            .map(|&(surface, pos)| {
+                let mut token =
+                    language::opaque(begin, surface, SudachiScript::Japanese, SUDACHI_FIELD_ALL);
+                token.class = crate::symbol::token_class(surface);
+                token.pos = pos.split(',').map(str::to_string).collect();
+                begin += surface.len();
+                token
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rows() {
+        let tokens = tokens_of(&[
+            ("猫", "名詞,普通名詞,一般,*,*,*"),
+            ("。", "補助記号,句点,*,*,*,*"),
+            ("\n", "空白,*,*,*,*,*"),
+            ("a,b", "名詞,普通名詞,一般,*,*,*"),
+        ]);
+        let csv = rows(SudachiExportFormat::Csv, "vol\"1", &tokens);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "\"vol\"\"1\",0,0,0,3,猫,猫,猫,,,名詞,普通名詞,一般,*,*,*,0,0"
+        );
+        assert!(lines[1].starts_with("\"vol\"\"1\",0,1,3,6,。,"));
+        assert!(lines[2].starts_with("\"vol\"\"1\",1,2,7,10,\"a,b\","));
+
+        let tsv = rows(SudachiExportFormat::Tsv, "vol\t1", &tokens[..1]);
+        assert_eq!(
+            tsv,
+            "vol 1\t0\t0\t0\t3\t猫\t猫\t猫\t\t\t名詞\t普通名詞\t一般\t*\t*\t*\t0\t0\n"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+// Dictionary fingerprints
+// Word ids and POS ids only mean something against the dictionary that
+// produced them, so results kept across launches (packed buffers, cache
+// pages) are stamped with a fingerprint of that dictionary. After a
+// dictionary update the stamps no longer match and the caller re-runs the
+// analysis instead of mixing ids from two dictionaries. The fingerprint
+// hashes the header (format version, build time, description) and length of
+// the system dictionary and every user dictionary, which is cheap on a memory
+// map since the lexicons are never touched.
+
+use crate::{handle, SudachiTokenizer};
+
+/// Sudachi header: u64 version | u64 build time | 256-byte description
+const HEADER_LEN: usize = 8 + 8 + 256;
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Continue an FNV-1a hash over bytes
+pub(crate) fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, &b| (hash ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Identity of a dictionary image
+/// All zero for results stored without one, which match no dictionary.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SudachiFingerprint {
+    /// FNV-1a hash of the dictionary headers and image lengths
+    pub hash: u64,
+    /// Format version from the system dictionary header
+    pub version: u64,
+}
+
+impl SudachiFingerprint {
+    /// Fingerprint of the system dictionary image followed by user
+    /// dictionary images
+    pub(crate) fn of(images: &[&[u8]]) -> SudachiFingerprint {
+        let version = images
+            .first()
+            .and_then(|image| image.get(..8))
+            .map_or(0, |v| u64::from_le_bytes(v.try_into().unwrap()));
+        let mut hash = FNV_OFFSET;
+        for image in images {
+            hash = fnv(hash, &image[..image.len().min(HEADER_LEN)]);
+            hash = fnv(hash, &(image.len() as u64).to_le_bytes());
+        }
+        SudachiFingerprint { hash, version }
+    }
+
+    /// Whether a result stamped with self was produced by dictionary
+    pub(crate) fn matches(self, dictionary: SudachiFingerprint) -> bool {
+        self != SudachiFingerprint::default() && self == dictionary
+    }
+}
+
+/// Get the fingerprint of a handle's dictionary
+/// Returns false if any argument is NULL
+#[no_mangle]
+pub extern "C" fn sudachi_dictionary_fingerprint(
+    tokenizer: *const SudachiTokenizer,
+    out_fingerprint: *mut SudachiFingerprint,
+) -> bool {
+    if tokenizer.is_null() || out_fingerprint.is_null() {
+        return false;
+    }
+    unsafe {
+        *out_fingerprint = handle::borrow(tokenizer).fingerprint;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        let mut image = vec![0u8; 1024];
+        image[..8].copy_from_slice(&7u64.to_le_bytes());
+        let original = SudachiFingerprint::of(&[&image[..]]);
+        assert_eq!(original.version, 7);
+        assert!(original.matches(SudachiFingerprint::of(&[&image[..]])));
+
+        // A rebuilt dictionary has a new build time; a grown one a new length
+        image[8] = 1;
+        assert!(!original.matches(SudachiFingerprint::of(&[&image[..]])));
+        image[8] = 0;
+        image.push(0);
+        assert!(!original.matches(SudachiFingerprint::of(&[&image[..]])));
+
+        // Adding a user dictionary changes it too
+        let user = [0u8; 64];
+        assert!(!original.matches(SudachiFingerprint::of(&[&image[..1024], &user])));
+
+        assert!(!SudachiFingerprint::default().matches(SudachiFingerprint::default()));
+        assert_eq!(SudachiFingerprint::of(&[&[]]).version, 0);
+    }
+}
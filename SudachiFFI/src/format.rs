@@ -0,0 +1,185 @@
+// Text output formats
+// Mirrors the output of the sudachi command line tool so batch jobs and
+// scripts that consume MeCab-style output can use this library unchanged.
+// Input is analyzed line by line, as the CLI does. CoNLL-U output, for UD
+// tooling, is built from tokens instead (see conllu.rs).
+
+use std::fmt::Write;
+use std::os::raw::c_char;
+use std::ptr;
+
+use sudachi::dic::subset::InfoSubset;
+use sudachi::prelude::*;
+
+use crate::analyzer::{Analyzer, Dictionary};
+use crate::conllu::{self, CONLLU_FIELDS};
+use crate::{c_str, handle, into_c_string, leak, SudachiTokenMode, SudachiTokenizer};
+
+/// Text output format for sudachi_format
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum SudachiOutputFormat {
+    /// Surface, POS, normalized form (sudachi default)
+    Tsv = 0,
+    /// Adds dictionary form, reading, dictionary id, synonyms, OOV (sudachi -a)
+    TsvAll = 1,
+    /// Surfaces separated by spaces (sudachi -w / mecab -Owakati)
+    Wakati = 2,
+    /// CoNLL-U with UPOS and display-unit pseudo-heads
+    Conllu = 3,
+}
+
+impl SudachiOutputFormat {
+    fn subset(self) -> InfoSubset {
+        match self {
+            SudachiOutputFormat::Tsv => InfoSubset::POS_ID | InfoSubset::NORMALIZED_FORM,
+            SudachiOutputFormat::TsvAll => {
+                InfoSubset::POS_ID
+                    | InfoSubset::NORMALIZED_FORM
+                    | InfoSubset::DIC_FORM_WORD_ID
+                    | InfoSubset::READING_FORM
+                    | InfoSubset::SYNONYM_GROUP_ID
+            }
+            SudachiOutputFormat::Wakati | SudachiOutputFormat::Conllu => InfoSubset::empty(),
+        }
+    }
+}
+
+fn write_tsv(out: &mut String, morphemes: &MorphemeList<Dictionary>, all: bool) {
+    for m in morphemes.iter() {
+        let _ = write!(
+            out,
+            "{}\t{}\t{}",
+            &*m.surface(),
+            m.part_of_speech().join(","),
+            m.normalized_form()
+        );
+        if all {
+            let synonyms: Vec<String> = m
+                .synonym_group_ids()
+                .iter()
+                .map(|id| id.to_string())
+                .collect();
+            let _ = write!(
+                out,
+                "\t{}\t{}\t{}\t[{}]",
+                m.dictionary_form(),
+                m.reading_form(),
+                m.dictionary_id(),
+                synonyms.join(",")
+            );
+            if m.is_oov() {
+                out.push_str("\t(OOV)");
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("EOS\n");
+}
+
+fn write_wakati(out: &mut String, morphemes: &MorphemeList<Dictionary>) {
+    for (i, m) in morphemes.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        out.push_str(&m.surface());
+    }
+    out.push('\n');
+}
+
+/// Format the analysis of every line of text
+pub(crate) fn format(
+    analyzer: &mut Analyzer,
+    text: &str,
+    mode: Mode,
+    format: SudachiOutputFormat,
+) -> SudachiResult<String> {
+    let mut out = String::with_capacity(text.len() * 4);
+    if format == SudachiOutputFormat::Conllu {
+        let mut sentence = 0;
+        for line in text.lines() {
+            let tokens = analyzer.analyze(line, mode, CONLLU_FIELDS)?;
+            conllu::write_line(&mut out, line, tokens, &mut sentence);
+        }
+        return Ok(out);
+    }
+    for line in text.lines() {
+        analyzer.run(line, mode, format.subset())?;
+        match format {
+            SudachiOutputFormat::Tsv => write_tsv(&mut out, analyzer.morphemes(), false),
+            SudachiOutputFormat::TsvAll => write_tsv(&mut out, analyzer.morphemes(), true),
+            SudachiOutputFormat::Wakati => write_wakati(&mut out, analyzer.morphemes()),
+            SudachiOutputFormat::Conllu => unreachable!(),
+        }
+    }
+    Ok(out)
+}
+
+/// Analyze text and render it in a sudachi CLI compatible text format
+/// Returns NULL on failure (caller must free with sudachi_free_string)
+#[no_mangle]
+pub extern "C" fn sudachi_format(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    format: SudachiOutputFormat,
+) -> *mut c_char {
+    if tokenizer.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+
+    match tokenizer.with_analyzer(|a| self::format(a, text, mode.into(), format)) {
+        Ok(out) => {
+            let out = into_c_string(&out);
+            leak::track(out, "string");
+            out
+        }
+        Err(_) => {
+            tokenizer.stats.record_error();
+            ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sudachi_free_string, sudachi_free_tokenizer, testdict};
+    use std::ffi::{CStr, CString};
+
+    fn render(tokenizer: *mut SudachiTokenizer, text: &str, format: SudachiOutputFormat) -> String {
+        let text = CString::new(text).unwrap();
+        let out = sudachi_format(tokenizer, text.as_ptr(), SudachiTokenMode::C, format);
+        assert!(!out.is_null());
+        let s = unsafe { CStr::from_ptr(out) }.to_str().unwrap().to_string();
+        sudachi_free_string(out);
+        s
+    }
+
+    #[test]
+    fn test_tsv_layout() {
+        let tokenizer = testdict::tokenizer();
+        assert_eq!(
+            render(tokenizer, "猫が食べた。", SudachiOutputFormat::Tsv),
+            "猫\t名詞,普通名詞,一般,*,*,*\t猫\n\
+             が\t助詞,格助詞,*,*,*,*\tが\n\
+             食べ\t動詞,一般,*,*,下一段-バ行,連用形-一般\t食べる\n\
+             た\t助動詞,*,*,*,助動詞-タ,終止形-一般\tた\n\
+             。\t補助記号,句点,*,*,*,*\t。\n\
+             EOS\n"
+        );
+        // Dictionary form, reading, dictionary id and synonyms follow; each
+        // line of input ends with its own EOS
+        assert_eq!(
+            render(tokenizer, "猫\n猫", SudachiOutputFormat::TsvAll),
+            "猫\t名詞,普通名詞,一般,*,*,*\t猫\t猫\tネコ\t0\t[]\nEOS\n\
+             猫\t名詞,普通名詞,一般,*,*,*\t猫\t猫\tネコ\t0\t[]\nEOS\n"
+        );
+        sudachi_free_tokenizer(tokenizer);
+    }
+}
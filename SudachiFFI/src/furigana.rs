@@ -0,0 +1,338 @@
+// Furigana
+// Aligns each token's reading against its surface so ruby is attached to the
+// kanji runs only, leaving okurigana bare: 食べる/タベル -> 食[た]べる. The
+// split between a token's stem and its trailing okurigana is exposed on its
+// own for ruby layout and flashcard formatting.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::kana::{is_kana, is_kanji, katakana_to_hiragana};
+use crate::options::SudachiOptions;
+use crate::{
+    c_str, handle, into_c_string, leak, SudachiToken, SudachiTokenMode, SudachiTokenizer,
+    TokenData, SUDACHI_FIELD_READING, SUDACHI_FIELD_SURFACE,
+};
+
+/// Fields furigana needs from the analyzer
+pub(crate) const FURIGANA_FIELDS: u32 = SUDACHI_FIELD_SURFACE | SUDACHI_FIELD_READING;
+
+/// One ruby segment: a run of the input and its hiragana reading, if any
+pub(crate) struct Ruby {
+    pub(crate) text: String,
+    pub(crate) reading: Option<String>,
+    pub(crate) begin: usize,
+    pub(crate) end: usize,
+}
+
+/// Match surface runs against the reading, assigning a reading to each
+/// non-kana run. Kana runs must appear literally in the reading.
+fn align<'a>(runs: &[(bool, &str)], reading: &'a str, out: &mut Vec<&'a str>) -> bool {
+    let Some(&(kana, text)) = runs.first() else {
+        return reading.is_empty();
+    };
+
+    if kana {
+        let text = katakana_to_hiragana(text);
+        return reading.starts_with(&text) && align(&runs[1..], &reading[text.len()..], out);
+    }
+
+    // Shortest reading first, so a following kana run anchors the split
+    for (i, c) in reading.char_indices() {
+        out.push(&reading[..i + c.len_utf8()]);
+        if align(&runs[1..], &reading[i + c.len_utf8()..], out) {
+            return true;
+        }
+        out.pop();
+    }
+    false
+}
+
+/// Split a surface into alternating kana / non-kana runs
+fn runs(surface: &str) -> Vec<(bool, &str)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut current = None;
+    for (i, c) in surface.char_indices() {
+        let kana = is_kana(c);
+        match current {
+            Some(kind) if kind != kana => {
+                runs.push((kind, &surface[start..i]));
+                start = i;
+            }
+            _ => {}
+        }
+        current = Some(kana);
+    }
+    if let Some(kind) = current {
+        runs.push((kind, &surface[start..]));
+    }
+    runs
+}
+
+/// Append the ruby segments of one token
+fn token_ruby(token: &TokenData, out: &mut Vec<Ruby>) {
+    let surface = token.surface.as_str();
+    let reading = katakana_to_hiragana(&token.reading);
+    let segment = |text: &str, reading: Option<&str>, offset: usize| Ruby {
+        text: text.to_string(),
+        reading: reading
+            .filter(|_| text.chars().any(is_kanji))
+            .map(str::to_string),
+        begin: token.begin + offset,
+        end: token.begin + offset + text.len(),
+    };
+
+    if reading.is_empty() || !surface.chars().any(is_kanji) {
+        out.push(segment(surface, None, 0));
+        return;
+    }
+
+    let runs = runs(surface);
+    let mut readings = Vec::with_capacity(runs.len());
+    if !align(&runs, &reading, &mut readings) {
+        // Irregular reading (ateji, gikun): ruby over the whole token
+        out.push(segment(surface, Some(&reading), 0));
+        return;
+    }
+
+    let mut readings = readings.into_iter();
+    let mut offset = 0;
+    for (kana, text) in runs {
+        let reading = if kana { None } else { readings.next() };
+        out.push(segment(text, reading, offset));
+        offset += text.len();
+    }
+}
+
+/// Byte offsets where the trailing okurigana begin in surface and in reading
+/// The stem runs up to the last non-kana character; without okurigana both
+/// offsets are the full lengths. None if the surface has no kanji or the
+/// reading does not end with the okurigana.
+pub(crate) fn okurigana(surface: &str, reading: &str) -> Option<(usize, usize)> {
+    if !surface.chars().any(is_kanji) {
+        return None;
+    }
+    let stem = surface.trim_end_matches(is_kana).len();
+    let tail = katakana_to_hiragana(&surface[stem..]);
+    let reading = katakana_to_hiragana(reading);
+    reading
+        .strip_suffix(tail.as_str())
+        .filter(|stem| !stem.is_empty())
+        .map(|reading_stem| (stem, reading_stem.len()))
+}
+
+/// Ruby segments covering all tokens, in input order
+pub(crate) fn furigana(tokens: &[TokenData]) -> Vec<Ruby> {
+    let mut out = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        token_ruby(token, &mut out);
+    }
+    out
+}
+
+/// Ruby segment
+/// text and ruby are owned by the result array; ruby is NULL for segments
+/// that need no reading (kana, punctuation, latin).
+#[repr(C)]
+pub struct SudachiRuby {
+    pub text: *mut c_char,
+    /// Hiragana reading, or NULL
+    pub ruby: *mut c_char,
+    /// Byte offsets into the input
+    pub begin: i32,
+    pub end: i32,
+}
+
+/// Analyze text into furigana segments
+/// Segments cover the whole input in order; okurigana are split off so ruby
+/// sits on the kanji only.
+/// Returns NULL on failure (caller must free with sudachi_free_furigana)
+#[no_mangle]
+pub extern "C" fn sudachi_furigana(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    out_count: *mut usize,
+) -> *mut SudachiRuby {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+    let tokens = match tokenizer.analyze(text, mode.into(), FURIGANA_FIELDS) {
+        Ok(tokens) => tokens,
+        Err(_) => return ptr::null_mut(),
+    };
+    into_ruby_array(&tokens, out_count)
+}
+
+/// Analyze text into furigana segments with per-call options
+/// options may be NULL for the defaults (see SudachiOptions). Fields and
+/// token filters don't apply, since segments cover the whole input;
+/// max_tokens cuts the analysis before segments are made.
+/// Returns NULL on failure or if text is longer than options->max_bytes
+/// (caller must free with sudachi_free_furigana)
+#[no_mangle]
+pub extern "C" fn sudachi_furigana_options(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    options: *const SudachiOptions,
+    out_count: *mut usize,
+) -> *mut SudachiRuby {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let Some(text) = c_str(text) else {
+        return ptr::null_mut();
+    };
+    let options = SudachiOptions::read(options);
+    if !options.fits(text) {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Ok(mut tokens) =
+        tokenizer.analyze_with(text, options.mode.into(), FURIGANA_FIELDS, &options)
+    else {
+        return ptr::null_mut();
+    };
+    if options.max_tokens != 0 {
+        tokens.truncate(options.max_tokens);
+    }
+    into_ruby_array(&tokens, out_count)
+}
+
+fn into_ruby_array(tokens: &[TokenData], out_count: *mut usize) -> *mut SudachiRuby {
+    let ruby: Vec<SudachiRuby> = furigana(tokens)
+        .into_iter()
+        .map(|r| SudachiRuby {
+            text: into_c_string(&r.text),
+            ruby: r.reading.as_deref().map_or(ptr::null_mut(), into_c_string),
+            begin: r.begin as i32,
+            end: r.end as i32,
+        })
+        .collect();
+
+    unsafe {
+        *out_count = ruby.len();
+    }
+    let ruby = Box::leak(ruby.into_boxed_slice()).as_mut_ptr();
+    leak::track(ruby, "furigana");
+    ruby
+}
+
+/// Split a token into its stem and trailing okurigana
+/// Writes the byte offsets where the okurigana begin in surface and in
+/// reading (食べた/タベタ -> 3, 3); without okurigana they are the full
+/// lengths. Needs SUDACHI_FIELD_SURFACE and SUDACHI_FIELD_READING.
+/// Returns false if the token has no kanji, lacks either field, or its
+/// reading does not end with the okurigana.
+#[no_mangle]
+pub extern "C" fn sudachi_token_okurigana(
+    token: *const SudachiToken,
+    out_surface: *mut i32,
+    out_reading: *mut i32,
+) -> bool {
+    if token.is_null() || out_surface.is_null() || out_reading.is_null() {
+        return false;
+    }
+    let token = unsafe { handle::borrow(token) };
+    if token.surface.is_null() || token.reading.is_null() {
+        return false;
+    }
+    let Ok(surface) = unsafe { CStr::from_ptr(token.surface) }.to_str() else {
+        return false;
+    };
+    let Ok(reading) = unsafe { CStr::from_ptr(token.reading) }.to_str() else {
+        return false;
+    };
+
+    match okurigana(surface, reading) {
+        Some((stem, reading_stem)) => {
+            unsafe {
+                *out_surface = stem as i32;
+                *out_reading = reading_stem as i32;
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Free the result of sudachi_furigana
+#[no_mangle]
+pub extern "C" fn sudachi_free_furigana(ruby: *mut SudachiRuby, count: usize) {
+    if ruby.is_null() {
+        return;
+    }
+
+    leak::release(ruby);
+    unsafe {
+        let segments = Box::from_raw(ptr::slice_from_raw_parts_mut(ruby, count));
+        for segment in segments.iter() {
+            for s in [segment.text, segment.ruby] {
+                if !s.is_null() {
+                    let _ = std::ffi::CString::from_raw(s);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testtoken::word;
+
+    fn token(surface: &str, reading: &str, begin: usize) -> TokenData {
+        TokenData {
+            reading: reading.to_string(),
+            ..word(begin, surface)
+        }
+    }
+
+    fn render(tokens: &[TokenData]) -> String {
+        furigana(tokens)
+            .iter()
+            .map(|r| match &r.reading {
+                Some(reading) => format!("{}[{}]", r.text, reading),
+                None => r.text.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_furigana_alignment() {
+        assert_eq!(render(&[token("食べる", "タベル", 0)]), "食[た]べる");
+        assert_eq!(
+            render(&[token("取り扱い", "トリアツカイ", 0)]),
+            "取[と]り扱[あつか]い"
+        );
+        assert_eq!(render(&[token("今日", "キョウ", 0)]), "今日[きょう]");
+        assert_eq!(render(&[token("ねこ", "ネコ", 0)]), "ねこ");
+        assert_eq!(
+            render(&[token("大人しい", "オトナシイ", 0)]),
+            "大人[おとな]しい"
+        );
+        // A reading that does not fit the kana of the surface covers the
+        // whole token
+        assert_eq!(render(&[token("お土産", "ミヤゲ", 0)]), "お土産[みやげ]");
+
+        let segments = furigana(&[token("猫", "ネコ", 0), token("が", "ガ", 3)]);
+        assert_eq!((segments[1].begin, segments[1].end), (3, 6));
+    }
+
+    #[test]
+    fn test_okurigana() {
+        assert_eq!(okurigana("食べました", "タベマシタ"), Some((3, 3)));
+        assert_eq!(okurigana("取り扱い", "トリアツカイ"), Some((9, 15)));
+        assert_eq!(okurigana("今日", "キョウ"), Some((6, 9)));
+        assert_eq!(okurigana("お土産", "オミヤゲ"), Some((9, 12)));
+        assert_eq!(okurigana("ねこ", "ネコ"), None);
+        assert_eq!(okurigana("大人しい", "オトナ"), None);
+    }
+}
@@ -0,0 +1,109 @@
+// Process-wide tokenizer
+// A single shared tokenizer for app extensions (share sheet, keyboard) that
+// want to tokenize without managing handle lifetimes. It is loaded once by
+// sudachi_global_init and lives until the process exits.
+
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::{Mutex, OnceLock};
+
+use crate::{
+    c_str, into_token_array, load_dictionary, SudachiToken, SudachiTokenMode, SudachiTokenizer,
+    TokenData,
+};
+
+static GLOBAL: OnceLock<SudachiTokenizer> = OnceLock::new();
+
+/// Serializes loading so concurrent first calls map the dictionary once
+static INIT: Mutex<()> = Mutex::new(());
+
+/// Load the shared tokenizer from a dictionary file
+/// Only the first successful call loads; later calls return true without
+/// reloading, whatever path they pass. Returns false on failure, after which
+/// init may be retried.
+#[no_mangle]
+pub extern "C" fn sudachi_global_init(dict_path: *const c_char) -> bool {
+    if GLOBAL.get().is_some() {
+        return true;
+    }
+    let path = match c_str(dict_path) {
+        Some(p) => p,
+        None => return false,
+    };
+
+    let _guard = INIT.lock().unwrap_or_else(|e| e.into_inner());
+    if GLOBAL.get().is_some() {
+        return true;
+    }
+    match load_dictionary(path) {
+        Ok(dictionary) => {
+            let _ = GLOBAL.set(SudachiTokenizer::new(dictionary));
+            true
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+/// Whether sudachi_global_init has succeeded
+#[no_mangle]
+pub extern "C" fn sudachi_global_is_initialized() -> bool {
+    GLOBAL.get().is_some()
+}
+
+/// Tokenize text with the shared tokenizer
+/// Same as sudachi_tokenize_fields. Returns NULL before sudachi_global_init
+/// or on failure (caller must free with sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_global_tokenize(
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    fields: u32,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    if out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = match GLOBAL.get() {
+        Some(t) => t,
+        None => {
+            eprintln!("sudachi_global_tokenize called before sudachi_global_init");
+            return ptr::null_mut();
+        }
+    };
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+
+    let tokens = match tokenizer.analyze(text, mode.into(), fields) {
+        Ok(tokens) => tokens,
+        Err(_) => return ptr::null_mut(),
+    };
+    let tokens = tokens.into_iter().filter_map(TokenData::into_raw).collect();
+    into_token_array(tokens, out_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SUDACHI_FIELD_ALL;
+
+    #[test]
+    fn test_global_uninitialized() {
+        assert!(!sudachi_global_init(ptr::null()));
+        assert!(!sudachi_global_is_initialized());
+
+        let text = std::ffi::CString::new("猫").unwrap();
+        let mut count = 0;
+        let tokens = sudachi_global_tokenize(
+            text.as_ptr(),
+            SudachiTokenMode::C,
+            SUDACHI_FIELD_ALL,
+            &mut count,
+        );
+        assert!(tokens.is_null());
+    }
+}
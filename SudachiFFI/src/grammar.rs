@@ -0,0 +1,179 @@
+// Grammar access
+// Re-ranking layers (handwriting, conversion, the app's own) reason about how
+// plausible one word is after another, which in Sudachi is the connection
+// cost between the right id of the first and the left id of the second, plus
+// each word's own cost. These are exposed read-only together with the sizes
+// of the matrix and the POS table, so callers don't have to parse the
+// dictionary binary. A token's ids come from its word_id, which is valid for
+// entries of the handle's dictionaries only.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use serde_json::json;
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
+use sudachi::dic::word_id::WordId;
+
+use crate::language::SUDACHI_OPAQUE_WORD_ID;
+use crate::{c_str, handle, into_c_string, SudachiTokenizer};
+
+/// Number of POS columns
+const POS_COLUMNS: usize = 6;
+
+/// Sizes of a dictionary's grammar
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SudachiGrammarInfo {
+    /// Right ids a connection can start from (first index of the matrix)
+    pub num_right_ids: u32,
+    /// Left ids a connection can lead to (second index of the matrix)
+    pub num_left_ids: u32,
+    /// Entries of the POS table (valid pos_id values)
+    pub pos_count: u32,
+}
+
+/// Connection ids and cost of a dictionary entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SudachiWordParam {
+    pub left_id: u16,
+    pub right_id: u16,
+    /// Word cost; lower is more likely
+    pub cost: i16,
+}
+
+/// POS columns of "名詞,普通名詞,一般,*,*,*"
+fn pos_columns(pos: &str) -> Option<Vec<&str>> {
+    let columns: Vec<&str> = pos.split(',').map(str::trim).collect();
+    (columns.len() == POS_COLUMNS && columns.iter().all(|c| !c.is_empty())).then_some(columns)
+}
+
+/// Get the sizes of the connection matrix and the POS table
+/// Returns false if an argument is NULL
+#[no_mangle]
+pub extern "C" fn sudachi_grammar_info(
+    tokenizer: *const SudachiTokenizer,
+    out_info: *mut SudachiGrammarInfo,
+) -> bool {
+    if tokenizer.is_null() || out_info.is_null() {
+        return false;
+    }
+    let grammar = unsafe { handle::borrow(tokenizer) }.dictionary.grammar();
+    let matrix = grammar.conn_matrix();
+    unsafe {
+        *out_info = SudachiGrammarInfo {
+            num_right_ids: matrix.num_left() as u32,
+            num_left_ids: matrix.num_right() as u32,
+            pos_count: grammar.pos_list.len() as u32,
+        };
+    }
+    true
+}
+
+/// Get the cost of connecting a word with right_id to a following word
+/// with left_id; lower is more plausible
+/// Returns false if an argument is NULL or an id is out of range (see
+/// sudachi_grammar_info).
+#[no_mangle]
+pub extern "C" fn sudachi_connection_cost(
+    tokenizer: *const SudachiTokenizer,
+    right_id: u16,
+    left_id: u16,
+    out_cost: *mut i16,
+) -> bool {
+    if tokenizer.is_null() || out_cost.is_null() {
+        return false;
+    }
+    let matrix = unsafe { handle::borrow(tokenizer) }
+        .dictionary
+        .grammar()
+        .conn_matrix();
+    if usize::from(right_id) >= matrix.num_left() || usize::from(left_id) >= matrix.num_right() {
+        return false;
+    }
+    unsafe {
+        *out_cost = matrix.cost(right_id, left_id);
+    }
+    true
+}
+
+/// Get the connection ids and cost of the dictionary entry word_id (from a
+/// token of this handle or sudachi_lookup)
+/// Returns false if an argument is NULL or word_id is an unknown word or an
+/// opaque line, which have no dictionary entry.
+#[no_mangle]
+pub extern "C" fn sudachi_word_param(
+    tokenizer: *const SudachiTokenizer,
+    word_id: u32,
+    out_param: *mut SudachiWordParam,
+) -> bool {
+    if tokenizer.is_null() || out_param.is_null() || word_id == SUDACHI_OPAQUE_WORD_ID {
+        return false;
+    }
+    let word_id = WordId::from_raw(word_id);
+    if word_id.is_oov() {
+        return false;
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let (left_id, right_id, cost) = tokenizer.dictionary.lexicon().get_word_param(word_id);
+    unsafe {
+        *out_param = SudachiWordParam {
+            left_id: left_id as u16,
+            right_id: right_id as u16,
+            cost,
+        };
+    }
+    true
+}
+
+/// POS of a pos_id as a JSON array of its six columns, e.g.
+/// ["名詞","普通名詞","一般","*","*","*"]
+/// Returns NULL if tokenizer is NULL or pos_id is out of range (caller must
+/// free with sudachi_free_string)
+#[no_mangle]
+pub extern "C" fn sudachi_pos_string(
+    tokenizer: *const SudachiTokenizer,
+    pos_id: u16,
+) -> *mut c_char {
+    if tokenizer.is_null() {
+        return ptr::null_mut();
+    }
+    let grammar = unsafe { handle::borrow(tokenizer) }.dictionary.grammar();
+    match grammar.pos_list.get(usize::from(pos_id)) {
+        Some(pos) => into_c_string(&json!(pos).to_string()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Find the pos_id of a POS given as its six comma-separated columns
+/// ("名詞,普通名詞,一般,*,*,*")
+/// Returns -1 if an argument is NULL, pos does not have six columns or the
+/// dictionary has no such POS.
+#[no_mangle]
+pub extern "C" fn sudachi_pos_id(tokenizer: *const SudachiTokenizer, pos: *const c_char) -> i32 {
+    if tokenizer.is_null() {
+        return -1;
+    }
+    let Some(columns) = c_str(pos).and_then(pos_columns) else {
+        return -1;
+    };
+    let grammar = unsafe { handle::borrow(tokenizer) }.dictionary.grammar();
+    grammar
+        .get_part_of_speech_id(&columns)
+        .map_or(-1, i32::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pos_columns() {
+        assert_eq!(
+            pos_columns("名詞, 普通名詞,一般,*,*,*"),
+            Some(vec!["名詞", "普通名詞", "一般", "*", "*", "*"])
+        );
+        assert_eq!(pos_columns("名詞,普通名詞"), None);
+        assert_eq!(pos_columns("名詞,,一般,*,*,*"), None);
+    }
+}
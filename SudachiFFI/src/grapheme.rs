@@ -0,0 +1,146 @@
+// Grapheme-cluster-safe offsets
+// Sudachi segments by code point, so a token boundary can fall inside an
+// extended grapheme cluster (an emoji ZWJ sequence, a base letter and its
+// combining marks). Swift String APIs reject such mid-cluster ranges.
+// Token offsets stay raw; sudachi_grapheme_ranges returns adjusted ranges
+// in which every boundary inside a cluster is moved to the end of it.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{c_str, handle, leak, SudachiToken};
+
+/// Byte range in the UTF-8 input
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SudachiByteRange {
+    pub begin: i32,
+    pub end: i32,
+}
+
+/// Move every range boundary that splits a grapheme cluster of text to the
+/// end of that cluster
+/// Adjacent ranges stay adjacent; a range inside a single cluster becomes
+/// empty, and the cluster belongs to the range it starts in.
+fn adjust(text: &str, ranges: impl Iterator<Item = (i32, i32)>) -> Vec<SudachiByteRange> {
+    let mut boundaries: Vec<usize> = text.grapheme_indices(true).map(|(i, _)| i).collect();
+    boundaries.push(text.len());
+
+    let snap = |offset: i32| {
+        let offset = (offset.max(0) as usize).min(text.len());
+        match boundaries.binary_search(&offset) {
+            Ok(_) => offset as i32,
+            Err(next) => boundaries[next] as i32,
+        }
+    };
+    ranges
+        .map(|(begin, end)| SudachiByteRange {
+            begin: snap(begin),
+            end: snap(end),
+        })
+        .collect()
+}
+
+/// Token ranges adjusted to grapheme cluster boundaries
+/// tokens must be the result of analyzing text; entry i is the range of
+/// token i, which may be empty if the token lies inside another token's
+/// cluster. out_count receives count.
+/// Returns NULL on failure (caller must free with sudachi_free_grapheme_ranges)
+#[no_mangle]
+pub extern "C" fn sudachi_grapheme_ranges(
+    text: *const c_char,
+    tokens: *const *mut SudachiToken,
+    count: usize,
+    out_count: *mut usize,
+) -> *mut SudachiByteRange {
+    if (tokens.is_null() && count > 0) || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+    let tokens: &[*mut SudachiToken] = if count == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(tokens, count) }
+    };
+    if tokens.iter().any(|t| t.is_null()) {
+        return ptr::null_mut();
+    }
+
+    let ranges = adjust(
+        text,
+        tokens.iter().map(|&t| {
+            let token = unsafe { handle::borrow(t) };
+            (token.begin, token.end)
+        }),
+    );
+
+    unsafe {
+        *out_count = ranges.len();
+    }
+    let ranges = Box::leak(ranges.into_boxed_slice()).as_mut_ptr();
+    leak::track(ranges, "grapheme ranges");
+    ranges
+}
+
+/// Free the result of sudachi_grapheme_ranges
+#[no_mangle]
+pub extern "C" fn sudachi_free_grapheme_ranges(ranges: *mut SudachiByteRange, count: usize) {
+    if !ranges.is_null() {
+        leak::release(ranges);
+        unsafe {
+            let _ = Box::from_raw(ptr::slice_from_raw_parts_mut(ranges, count));
+        }
+    }
+}
+
+/// Check whether a byte offset is a grapheme cluster boundary of text
+/// The start and end of text are boundaries. Returns false for NULL or
+/// invalid UTF-8 text and offsets past the end.
+#[no_mangle]
+pub extern "C" fn sudachi_is_grapheme_boundary(text: *const c_char, offset: usize) -> bool {
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return false,
+    };
+    offset == text.len()
+        || (text.is_char_boundary(offset) && text.grapheme_indices(true).any(|(i, _)| i == offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(v: &[SudachiByteRange]) -> Vec<(i32, i32)> {
+        v.iter().map(|r| (r.begin, r.end)).collect()
+    }
+
+    #[test]
+    fn test_adjust() {
+        // Family emoji: 👨 ZWJ 👩 ZWJ 👧 (4 + 3 + 4 + 3 + 4 bytes), then が
+        let text = "👨\u{200d}👩\u{200d}👧が";
+        let split = [(0, 4), (4, 7), (7, 18), (18, 21)];
+        assert_eq!(
+            ranges(&adjust(text, split.into_iter())),
+            [(0, 18), (18, 18), (18, 18), (18, 21)]
+        );
+
+        // e + combining acute accent split from its base letter
+        let text = "cafe\u{301}だ";
+        let split = [(0, 4), (4, 6), (6, 9)];
+        assert_eq!(
+            ranges(&adjust(text, split.into_iter())),
+            [(0, 6), (6, 6), (6, 9)]
+        );
+
+        // Cluster-aligned ranges are unchanged
+        assert_eq!(
+            ranges(&adjust("猫が", [(0, 3), (3, 6)].into_iter())),
+            [(0, 3), (3, 6)]
+        );
+    }
+}
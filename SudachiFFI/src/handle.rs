@@ -0,0 +1,269 @@
+// Handle allocation and validation
+// Tokenizers, tokens and results cross the FFI boundary only through these
+// helpers.
+// In debug builds and with the ffi-debug feature every allocation is prefixed
+// with a magic value and a generation counter that is checked on each use.
+// On free the magic becomes a tombstone and the value's bytes are overwritten
+// with a poison pattern, and the allocation is kept (not returned to the
+// allocator), so use-after-free, double free and passing the wrong pointer
+// type abort with a clear message instead of corrupting memory. A stale
+// pointer read directly from C (token->surface) finds 0xDB bytes, which no
+// valid pointer or string is made of.
+//
+// ffi-debug keeps every freed allocation. Plain debug builds keep the most
+// recent QUARANTINE of them and release older ones, so a long debugging
+// session does not grow without bound; misuse of a handle freed long before
+// may then go unnoticed.
+
+use crate::result::SudachiResult;
+use crate::{leak, SudachiToken, SudachiTokenizer};
+
+/// Types handed to C as owned pointers
+#[cfg_attr(not(any(feature = "ffi-debug", debug_assertions)), allow(dead_code))]
+pub(crate) trait Handle {
+    /// Tag identifying live allocations of this type
+    const MAGIC: u64;
+    const NAME: &'static str;
+}
+
+impl Handle for SudachiTokenizer {
+    const MAGIC: u64 = u64::from_be_bytes(*b"MGXTOKZR");
+    const NAME: &'static str = "SudachiTokenizer";
+}
+
+impl Handle for SudachiToken {
+    const MAGIC: u64 = u64::from_be_bytes(*b"MGXTOKEN");
+    const NAME: &'static str = "SudachiToken";
+}
+
+impl Handle for SudachiResult {
+    const MAGIC: u64 = u64::from_be_bytes(*b"MGXRSULT");
+    const NAME: &'static str = "SudachiResult";
+}
+
+/// Move value to the heap and hand out the pointer
+pub(crate) fn into_raw<T: Handle>(value: T) -> *mut T {
+    let ptr = imp::into_raw(value);
+    leak::track(ptr, T::NAME);
+    ptr
+}
+
+/// Borrow a non-NULL handle
+/// # Safety
+/// ptr must come from into_raw and not have been freed (checked in debug)
+pub(crate) unsafe fn borrow<'a, T: Handle>(ptr: *const T) -> &'a T {
+    imp::check(ptr);
+    &*ptr
+}
+
+/// Take back ownership of a non-NULL handle
+/// # Safety
+/// ptr must come from into_raw and not have been freed (checked in debug)
+pub(crate) unsafe fn from_raw<T: Handle>(ptr: *mut T) -> T {
+    leak::release(ptr);
+    imp::from_raw(ptr)
+}
+
+#[cfg(not(any(feature = "ffi-debug", debug_assertions)))]
+mod imp {
+    use super::Handle;
+
+    pub(super) fn into_raw<T: Handle>(value: T) -> *mut T {
+        Box::into_raw(Box::new(value))
+    }
+
+    pub(super) fn check<T: Handle>(_ptr: *const T) {}
+
+    pub(super) unsafe fn from_raw<T: Handle>(ptr: *mut T) -> T {
+        *Box::from_raw(ptr)
+    }
+}
+
+#[cfg(any(feature = "ffi-debug", debug_assertions))]
+mod imp {
+    use std::alloc::Layout;
+    use std::mem::{align_of, size_of};
+    use std::ptr;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::Handle;
+
+    const FREED: u64 = u64::from_be_bytes(*b"MGXFREED");
+
+    /// Byte written over freed values
+    pub(super) const POISON: u8 = 0xDB;
+
+    /// Freed allocations kept by builds without ffi-debug
+    #[cfg(not(feature = "ffi-debug"))]
+    const QUARANTINE: usize = 1 << 16;
+
+    /// Freed allocations still kept, oldest first
+    #[cfg(not(feature = "ffi-debug"))]
+    static FREED_BLOCKS: std::sync::Mutex<std::collections::VecDeque<(usize, Layout)>> =
+        std::sync::Mutex::new(std::collections::VecDeque::new());
+
+    /// Allocation counter, so a report can tell which handle went stale
+    static GENERATION: AtomicU64 = AtomicU64::new(1);
+
+    #[repr(C)]
+    struct Header {
+        magic: u64,
+        generation: u64,
+    }
+
+    #[repr(C)]
+    struct Tracked<T> {
+        header: Header,
+        value: T,
+    }
+
+    /// Offset of Tracked::value, i.e. of the pointer handed to C
+    fn value_offset<T>() -> usize {
+        size_of::<Header>().next_multiple_of(align_of::<T>())
+    }
+
+    unsafe fn header<T>(ptr: *const T) -> *mut Header {
+        (ptr as *mut u8).sub(value_offset::<T>()) as *mut Header
+    }
+
+    fn fail(message: String) -> ! {
+        eprintln!("sudachi ffi-debug: {}", message);
+        std::process::abort()
+    }
+
+    pub(super) fn into_raw<T: Handle>(value: T) -> *mut T {
+        let tracked = Box::into_raw(Box::new(Tracked {
+            header: Header {
+                magic: T::MAGIC,
+                generation: GENERATION.fetch_add(1, Ordering::Relaxed),
+            },
+            value,
+        }));
+        unsafe { ptr::addr_of_mut!((*tracked).value) }
+    }
+
+    pub(super) fn check<T: Handle>(ptr: *const T) {
+        let header = unsafe { &*header(ptr) };
+        match header.magic {
+            m if m == T::MAGIC => {}
+            FREED => fail(format!(
+                "use of freed {} {:p} (generation {})",
+                T::NAME,
+                ptr,
+                header.generation
+            )),
+            m => fail(format!(
+                "{:p} is not a {} (magic {:#018x}); wrong pointer type or corrupted memory",
+                ptr,
+                T::NAME,
+                m
+            )),
+        }
+    }
+
+    pub(super) unsafe fn from_raw<T: Handle>(ptr: *mut T) -> T {
+        check(ptr);
+        // Tombstone and poison, and keep the allocation so later use is caught
+        (*header(ptr)).magic = FREED;
+        let value = ptr::read(ptr);
+        ptr::write_bytes(ptr as *mut u8, POISON, size_of::<T>());
+        quarantine(header(ptr) as usize, Layout::new::<Tracked<T>>());
+        value
+    }
+
+    /// Keep a freed allocation, releasing the oldest beyond QUARANTINE
+    #[cfg(not(feature = "ffi-debug"))]
+    fn quarantine(block: usize, layout: Layout) {
+        let mut blocks = FREED_BLOCKS
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        blocks.push_back((block, layout));
+        if blocks.len() > QUARANTINE {
+            if let Some((oldest, layout)) = blocks.pop_front() {
+                unsafe { std::alloc::dealloc(oldest as *mut u8, layout) };
+            }
+        }
+    }
+
+    /// Keep every freed allocation
+    #[cfg(feature = "ffi-debug")]
+    fn quarantine(_block: usize, _layout: Layout) {}
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::script::SudachiScript;
+        use crate::symbol::SudachiTokenClass;
+        use crate::SudachiToken;
+
+        #[test]
+        fn test_tracked_layout() {
+            let token = into_raw(SudachiToken {
+                surface: ptr::null_mut(),
+                reading: ptr::null_mut(),
+                dictionary_form: ptr::null_mut(),
+                normalized_form: ptr::null_mut(),
+                pos: ptr::null_mut(),
+                begin: 1,
+                end: 2,
+                word_id: 3,
+                pos_id: 4,
+                script: SudachiScript::Japanese,
+                class: SudachiTokenClass::Word,
+                flags: 0,
+                dictionary_reading: ptr::null_mut(),
+                lemma_key: 0,
+                inline_surface: crate::inline::EMPTY,
+                inline_reading: crate::inline::EMPTY,
+            });
+            check(token);
+            let value = unsafe { from_raw(token) };
+            assert_eq!((value.begin, value.pos_id), (1, 4));
+            assert_eq!(unsafe { (*header(token)).magic }, FREED);
+            // A stale read of the surface pointer sees the poison pattern
+            let stale = unsafe { ptr::read(token as *const usize) };
+            assert_eq!(stale, usize::from_ne_bytes([POISON; size_of::<usize>()]));
+        }
+
+        #[test]
+        fn test_double_free_aborts() {
+            // The abort is observed from a child running just this test
+            if std::env::var_os("MGX_DOUBLE_FREE_CHILD").is_some() {
+                let token = into_raw(SudachiToken {
+                    surface: ptr::null_mut(),
+                    reading: ptr::null_mut(),
+                    dictionary_form: ptr::null_mut(),
+                    normalized_form: ptr::null_mut(),
+                    pos: ptr::null_mut(),
+                    begin: 0,
+                    end: 0,
+                    word_id: 0,
+                    pos_id: 0,
+                    script: SudachiScript::Japanese,
+                    class: SudachiTokenClass::Word,
+                    flags: 0,
+                    dictionary_reading: ptr::null_mut(),
+                    lemma_key: 0,
+                    inline_surface: crate::inline::EMPTY,
+                    inline_reading: crate::inline::EMPTY,
+                });
+                crate::sudachi_free_token(token);
+                crate::sudachi_free_token(token);
+                unreachable!("double free was not detected");
+            }
+
+            let output = std::process::Command::new(std::env::current_exe().unwrap())
+                .args([
+                    "--exact",
+                    "handle::imp::tests::test_double_free_aborts",
+                    "--nocapture",
+                ])
+                .env("MGX_DOUBLE_FREE_CHILD", "1")
+                .output()
+                .unwrap();
+            assert!(!output.status.success());
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains("use of freed SudachiToken"), "{}", stderr);
+        }
+    }
+}
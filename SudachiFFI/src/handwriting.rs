@@ -0,0 +1,117 @@
+// Handwriting candidate ranking
+// A handwriting recognizer ranks kanji by shape alone, so 末 and 未, or 土
+// and 士, come back in an order that ignores the sentence being written. The
+// dictionary costs are an implicit language model: each candidate is put at
+// the cursor, the text around it is analyzed, and candidates are ordered by
+// the unknown words and then the path cost of that window, as OCR correction
+// does for its look-alikes (ocr.rs). The sort is stable, so candidates that
+// score the same keep the recognizer's order.
+
+use std::os::raw::c_char;
+
+use sudachi::dic::subset::InfoSubset;
+use sudachi::prelude::*;
+
+use crate::analyzer::Analyzer;
+use crate::{c_str, handle, SudachiTokenizer};
+
+/// Characters of context analyzed on each side of the cursor
+const WINDOW: usize = 8;
+
+/// Context around the cursor, up to WINDOW characters on each side within
+/// the line
+fn window(text: &str, cursor: usize) -> (&str, &str) {
+    let before = &text[..cursor];
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| c != '\n')
+        .take(WINDOW)
+        .last()
+        .map_or(cursor, |(i, _)| i);
+    let after = &text[cursor..];
+    let end = after
+        .char_indices()
+        .take_while(|&(_, c)| c != '\n')
+        .take(WINDOW)
+        .last()
+        .map_or(cursor, |(i, c)| cursor + i + c.len_utf8());
+    (&text[start..cursor], &text[cursor..end])
+}
+
+/// Unknown words and path cost of text
+fn score(analyzer: &mut Analyzer, text: &str) -> Option<(usize, i32)> {
+    analyzer.run(text, Mode::C, InfoSubset::empty()).ok()?;
+    let morphemes = analyzer.morphemes();
+    let oov = morphemes.iter().filter(|m| m.is_oov()).count();
+    Some((oov, morphemes.get_internal_cost()))
+}
+
+/// Indices of candidates, most plausible first, given their scores
+/// Candidates without a score go last.
+fn order(scores: &[Option<(usize, i32)>]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by_key(|&i| (scores[i].is_none(), scores[i]));
+    order
+}
+
+/// Re-rank candidates for the text at cursor (a byte offset into
+/// context_text) by how well each fits the text around it
+/// Writes the candidate indices, most plausible first, to out_order, which
+/// must hold count entries. Candidates that are NULL or not UTF-8 go last;
+/// equally plausible ones keep their order. Returns false if an argument is
+/// NULL or cursor is not a character boundary of context_text.
+#[no_mangle]
+pub extern "C" fn sudachi_rank_candidates(
+    tokenizer: *mut SudachiTokenizer,
+    candidates: *const *const c_char,
+    count: usize,
+    context_text: *const c_char,
+    cursor: usize,
+    out_order: *mut usize,
+) -> bool {
+    if tokenizer.is_null() || candidates.is_null() || out_order.is_null() {
+        return false;
+    }
+    let Some(text) = c_str(context_text) else {
+        return false;
+    };
+    if !text.is_char_boundary(cursor) {
+        return false;
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let candidates = unsafe { std::slice::from_raw_parts(candidates, count) };
+
+    let (before, after) = window(text, cursor);
+    let scores: Vec<Option<(usize, i32)>> = tokenizer.with_analyzer(|analyzer| {
+        candidates
+            .iter()
+            .map(|&candidate| {
+                let candidate = c_str(candidate).filter(|c| !c.is_empty())?;
+                score(analyzer, &format!("{}{}{}", before, candidate, after))
+            })
+            .collect()
+    });
+    let out = unsafe { std::slice::from_raw_parts_mut(out_order, count) };
+    out.copy_from_slice(&order(&scores));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window() {
+        let text = "一二三四五六七八九十\n今日は|晴れ";
+        let cursor = text.find('|').unwrap();
+        assert_eq!(window(text, cursor), ("今日は", "|晴れ"));
+        let text = "あいうえおかきくけこ";
+        assert_eq!(window(text, 15), ("あいうえお", "かきくけこ"));
+        assert_eq!(window(text, 0), ("", "あいうえおかきく"));
+        assert_eq!(window("", 0), ("", ""));
+
+        let scores = [Some((1, 100)), None, Some((0, 900)), Some((1, 50))];
+        assert_eq!(order(&scores), [2, 3, 0, 1]);
+    }
+}
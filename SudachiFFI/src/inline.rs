@@ -0,0 +1,108 @@
+// Inline token strings
+// Every SudachiToken used to own one CString per selected field, and
+// allocating (and later freeing) them dominated marshalling time, though
+// most Japanese surfaces and readings are a few characters (under 12 bytes).
+// A token now carries fixed buffers for its surface and reading; a string
+// that fits, with its NUL, is copied there and the field points into the
+// token itself, and only longer ones spill to the heap. Readers are
+// unaffected, since the fields stay plain C string pointers, and a token
+// already lives at a fixed address until sudachi_free_token, which frees
+// only the strings that spilled.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::SudachiToken;
+
+/// Size of the inline buffers of a token, including the NUL
+pub const SUDACHI_INLINE_BYTES: usize = 24;
+
+/// Inline buffer of a token
+pub(crate) type Inline = [c_char; SUDACHI_INLINE_BYTES];
+
+/// Empty inline buffer
+pub(crate) const EMPTY: Inline = [0; SUDACHI_INLINE_BYTES];
+
+/// C string of bytes, copied into buf if it fits and spilled to the heap
+/// otherwise
+/// Returns NULL if bytes contain a NUL.
+pub(crate) fn store(buf: &mut Inline, bytes: &[u8]) -> *mut c_char {
+    if bytes.contains(&0) {
+        return ptr::null_mut();
+    }
+    if bytes.len() >= SUDACHI_INLINE_BYTES {
+        return CString::new(bytes)
+            .map(CString::into_raw)
+            .unwrap_or(ptr::null_mut());
+    }
+    for (out, &b) in buf.iter_mut().zip(bytes) {
+        *out = b as c_char;
+    }
+    buf[bytes.len()] = 0;
+    buf.as_mut_ptr()
+}
+
+impl SudachiToken {
+    /// String fields that were allocated separately from the token
+    pub(crate) fn heap_strings(&self) -> [*mut c_char; 6] {
+        let spilled = |s: *mut c_char, buf: &Inline| {
+            if ptr::eq(s, buf.as_ptr()) {
+                ptr::null_mut()
+            } else {
+                s
+            }
+        };
+        [
+            spilled(self.surface, &self.inline_surface),
+            spilled(self.reading, &self.inline_reading),
+            self.dictionary_form,
+            self.normalized_form,
+            self.pos,
+            self.dictionary_reading,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::SudachiScript;
+    use crate::symbol::SudachiTokenClass;
+    use crate::{c_str, sudachi_free_token, TokenData, SUDACHI_FIELD_ALL};
+
+    #[test]
+    fn test_inline() {
+        let data = TokenData {
+            fields: SUDACHI_FIELD_ALL,
+            surface: "猫".to_string(),
+            reading: "ネコネコネコネコ".to_string(),
+            dictionary_form: "猫".to_string(),
+            dictionary_reading: "ネコ".to_string(),
+            normalized_form: "猫".to_string(),
+            pos: vec!["名詞".to_string()],
+            begin: 0,
+            end: 3,
+            word_id: 0,
+            pos_id: 0,
+            script: SudachiScript::Japanese,
+            class: SudachiTokenClass::Word,
+            flags: 0,
+            lemma_key: 0,
+        };
+        let token = data.into_raw().unwrap();
+        let t = unsafe { &*token };
+        // 23 bytes fit with the NUL, 24 spill
+        assert!(ptr::eq(t.surface, t.inline_surface.as_ptr()));
+        assert!(!ptr::eq(t.reading, t.inline_reading.as_ptr()));
+        assert_eq!(c_str(t.surface), Some("猫"));
+        assert_eq!(c_str(t.reading), Some("ネコネコネコネコ"));
+        assert!(t.heap_strings()[0].is_null() && !t.heap_strings()[1].is_null());
+        sudachi_free_token(token);
+
+        let mut buf = EMPTY;
+        let s = store(&mut buf, "ネコネコネコネ".as_bytes());
+        assert!(ptr::eq(s, buf.as_ptr()));
+        assert!(store(&mut buf, b"a\0b").is_null());
+    }
+}
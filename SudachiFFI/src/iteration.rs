@@ -0,0 +1,182 @@
+// Iteration marks
+// 々 repeats the kanji before it (人々, 時々) and ゝ/ゞ (ヽ/ヾ in katakana)
+// the kana before it, voiced for ゞ. Words the dictionary does not list with
+// the mark come out of analysis as the previous word plus a stray mark, or as
+// an unknown word without a usable reading. Stray marks are merged into the
+// token before them, the reading is rebuilt from what the marks repeat, and
+// the token is flagged with SUDACHI_TOKEN_ITERATION.
+
+use crate::kana::{is_kana, to_hiragana, to_katakana, voiced};
+use crate::{
+    TokenData, SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_READING, SUDACHI_TOKEN_ITERATION,
+};
+
+const MARKS: [char; 5] = ['々', 'ゝ', 'ゞ', 'ヽ', 'ヾ'];
+
+fn is_mark(c: char) -> bool {
+    MARKS.contains(&c)
+}
+
+/// Reading of a repeated kanji: the first kana is voiced (rendaku) unless
+/// the reading looks Sino-Japanese, so 人々 is ヒトビト but 少々 ショウショウ
+fn repeat(reading: &str) -> String {
+    let sino = matches!(
+        reading.chars().last().map(to_hiragana),
+        Some('ん' | 'う' | 'い' | 'く' | 'つ')
+    );
+    let mut chars = reading.chars();
+    match chars.next() {
+        Some(first) if !sino => format!("{}{}", voiced(first), chars.as_str()),
+        _ => reading.to_string(),
+    }
+}
+
+/// Reading of a surface containing iteration marks
+/// read gives the reading of plain text; None if any part has none.
+fn read_marked(surface: &str, read: &mut impl FnMut(&str) -> Option<String>) -> Option<String> {
+    let mut reading = String::new();
+    let mut plain = 0;
+    let mut last = None;
+    for (i, c) in surface.char_indices() {
+        if !is_mark(c) {
+            last = Some(c);
+            continue;
+        }
+        let prev = last?;
+        if plain < i {
+            reading += &read(&surface[plain..i])?;
+        }
+        let unit = if is_kana(prev) {
+            to_katakana(prev).to_string()
+        } else {
+            repeat(&read(prev.encode_utf8(&mut [0; 4]))?)
+        };
+        if matches!(c, 'ゞ' | 'ヾ') {
+            let mut chars = unit.chars();
+            reading.extend(chars.next().map(voiced));
+            reading += chars.as_str();
+        } else {
+            reading += &unit;
+        }
+        plain = i + c.len_utf8();
+    }
+    if plain < surface.len() {
+        reading += &read(&surface[plain..])?;
+    }
+    Some(reading)
+}
+
+/// Merge stray iteration marks into the previous token and resolve readings
+/// Offsets of tokens are relative to text. read gives the reading of plain
+/// text and is only called when readings are selected.
+pub(crate) fn resolve(
+    text: &str,
+    tokens: Vec<TokenData>,
+    mut read: impl FnMut(&str) -> Option<String>,
+) -> Vec<TokenData> {
+    if !text.contains(MARKS) {
+        return tokens;
+    }
+
+    let mut resolved: Vec<TokenData> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let surface = &text[token.begin..token.end];
+        let stray = !surface.is_empty() && surface.chars().all(is_mark);
+        let mut token = match resolved.pop() {
+            Some(prev) if stray && prev.end == token.begin => TokenData {
+                flags: prev.flags | SUDACHI_TOKEN_ITERATION,
+                ..prev.extend_to(text, token.end)
+            },
+            prev => {
+                resolved.extend(prev);
+                token
+            }
+        };
+
+        let surface = &text[token.begin..token.end];
+        let unreadable = token.fields & SUDACHI_FIELD_READING != 0
+            && (token.reading == surface || token.reading.contains(MARKS));
+        let merged = token.flags & SUDACHI_TOKEN_ITERATION != 0;
+        if surface.contains(MARKS) && (merged || unreadable) {
+            if token.fields & (SUDACHI_FIELD_READING | SUDACHI_FIELD_DICTIONARY_READING) != 0 {
+                if let Some(reading) = read_marked(surface, &mut read) {
+                    if token.fields & SUDACHI_FIELD_DICTIONARY_READING != 0 {
+                        token.dictionary_reading = reading.clone();
+                    }
+                    if token.fields & SUDACHI_FIELD_READING != 0 {
+                        token.reading = reading;
+                    }
+                }
+            }
+            token.flags |= SUDACHI_TOKEN_ITERATION;
+        }
+        resolved.push(token);
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+    use crate::SUDACHI_FIELD_ALL;
+
+    /// Tokens over the given surfaces, read from a tiny dictionary
+    fn tokens(text: &str, surfaces: &[&str]) -> Vec<TokenData> {
+        let mut begin = 0;
+        surfaces
+            .iter()
+            .map(|&surface| {
+                let mut token =
+                    language::opaque(begin, surface, SudachiScript::Japanese, SUDACHI_FIELD_ALL);
+                token.reading = read(surface).unwrap_or_else(|| surface.to_string());
+                begin += surface.len();
+                assert_eq!(&text[token.begin..token.end], surface);
+                token
+            })
+            .collect()
+    }
+
+    fn read(text: &str) -> Option<String> {
+        let reading = match text {
+            "人" => "ヒト",
+            "時" => "トキ",
+            "少" => "ショウ",
+            "が" => "ガ",
+            "い" => "イ",
+            "す" => "ス",
+            "人々" => "ヒトビト",
+            _ => return None,
+        };
+        Some(reading.to_string())
+    }
+
+    #[test]
+    fn test_resolve() {
+        let text = "時々少々人々がいすゞ";
+        let surfaces = ["時", "々", "少々", "人々", "が", "い", "す", "ゞ"];
+        let resolved = resolve(text, tokens(text, &surfaces), read);
+        let readings: Vec<(&str, &str, bool)> = resolved
+            .iter()
+            .map(|t| {
+                let expanded = t.flags & SUDACHI_TOKEN_ITERATION != 0;
+                (t.surface.as_str(), t.reading.as_str(), expanded)
+            })
+            .collect();
+        assert_eq!(
+            readings,
+            [
+                ("時々", "トキドキ", true),
+                ("少々", "ショウショウ", true),
+                ("人々", "ヒトビト", false),
+                ("が", "ガ", false),
+                ("い", "イ", false),
+                ("すゞ", "スズ", true),
+            ]
+        );
+
+        let untouched = tokens("人が", &["人", "が"]);
+        assert_eq!(resolve("人が", untouched, read).len(), 2);
+    }
+}
@@ -0,0 +1,84 @@
+// Kana and script helpers
+// Character classification and katakana/hiragana conversion shared by
+// furigana alignment, reading post-processing and iteration marks.
+
+use crate::simd;
+
+/// Offset between a katakana code point and its hiragana counterpart
+const KANA_OFFSET: u32 = 0x60;
+
+pub(crate) fn is_hiragana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{3096}' | '\u{309D}'..='\u{309E}')
+}
+
+pub(crate) fn is_katakana(c: char) -> bool {
+    matches!(c, '\u{30A1}'..='\u{30FA}' | '\u{30FD}'..='\u{30FE}')
+}
+
+/// Hiragana, katakana or the prolonged sound mark
+pub(crate) fn is_kana(c: char) -> bool {
+    is_hiragana(c) || is_katakana(c) || c == 'ー'
+}
+
+/// CJK ideograph or the iteration mark 々
+pub(crate) fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}' | '\u{20000}'..='\u{2FA1F}' | '々')
+}
+
+/// Convert a katakana character to hiragana, leaving anything else as is
+/// ヷ-ヺ have no hiragana form and are kept.
+pub(crate) fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' | '\u{30FD}'..='\u{30FE}' => {
+            char::from_u32(c as u32 - KANA_OFFSET).unwrap_or(c)
+        }
+        _ => c,
+    }
+}
+
+/// Convert a hiragana character to katakana, leaving anything else as is
+pub(crate) fn to_katakana(c: char) -> char {
+    match c {
+        '\u{3041}'..='\u{3096}' | '\u{309D}'..='\u{309E}' => {
+            char::from_u32(c as u32 + KANA_OFFSET).unwrap_or(c)
+        }
+        _ => c,
+    }
+}
+
+/// Voiced form of a kana (か -> が, ウ -> ヴ), or the kana itself
+pub(crate) fn voiced(c: char) -> char {
+    let h = to_hiragana(c);
+    if "かきくけこさしすせそたちつてとはひふへほ".contains(h) {
+        char::from_u32(c as u32 + 1).unwrap_or(c)
+    } else if c == 'う' {
+        'ゔ'
+    } else if c == 'ウ' {
+        'ヴ'
+    } else {
+        c
+    }
+}
+
+/// to_hiragana over a string (SIMD, see simd.rs)
+pub(crate) fn katakana_to_hiragana(s: &str) -> String {
+    simd::to_hiragana(s)
+}
+
+/// to_katakana over a string (SIMD, see simd.rs)
+pub(crate) fn hiragana_to_katakana(s: &str) -> String {
+    simd::to_katakana(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_katakana_to_hiragana() {
+        assert_eq!(katakana_to_hiragana("タベル"), "たべる");
+        assert_eq!(katakana_to_hiragana("ヴァイオリン・ー"), "ゔぁいおりん・ー");
+        assert_eq!(hiragana_to_katakana("たべる"), "タベル");
+        assert!(is_kanji('々') && !is_kana('々'));
+    }
+}
@@ -0,0 +1,251 @@
+// Kanji details
+// The kanji sheet of the app shows the readings, meanings and stroke count
+// of a character. That table lives here next to the analysis, so the app
+// ships one native data layer instead of a separate database. The table is
+// built by the caller (e.g. from KANJIDIC) and loaded once per process with
+// sudachi_kanji_load; sudachi_kanji_info returns one entry as JSON, since
+// the sheet decodes it into a Swift struct anyway.
+//
+// The table can also list the components of each kanji (from KRADFILE), for
+// the screen that finds a kanji by its parts: an index from each component
+// to the kanji containing it is built at load, and a search filters the
+// shortest list of the selected components by the others.
+//
+// Table format (UTF-8, one kanji per line, # starts a comment):
+//   kanji <TAB> strokes <TAB> on readings <TAB> kun readings <TAB> meanings
+//         [<TAB> components]
+// Readings and components are separated by spaces and meanings by ';'. On
+// readings may be hiragana or katakana and are returned in katakana; kun
+// readings are kept as written (KANJIDIC marks okurigana with '.', e.g.
+// た.べる). Components are single characters.
+
+use std::collections::HashMap;
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::{PoisonError, RwLock};
+
+use serde_json::json;
+
+use crate::c_str;
+use crate::kana::hiragana_to_katakana;
+
+struct Entry {
+    strokes: u32,
+    on: Vec<String>,
+    kun: Vec<String>,
+    meanings: Vec<String>,
+    components: Vec<char>,
+}
+
+struct Table {
+    entries: HashMap<char, Entry>,
+    /// Kanji containing each component, fewest strokes first
+    by_component: HashMap<char, Vec<char>>,
+}
+
+/// Loaded table
+static TABLE: RwLock<Option<Table>> = RwLock::new(None);
+
+fn parse(table: &str) -> Table {
+    let mut entries = HashMap::new();
+    for line in table.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut columns = line.split('\t');
+        let mut chars = columns.next().unwrap_or_default().chars();
+        let (Some(kanji), None) = (chars.next(), chars.next()) else {
+            continue;
+        };
+        let Some(strokes) = columns.next().and_then(|s| s.trim().parse().ok()) else {
+            continue;
+        };
+        let list = |column: Option<&str>, separator: char| -> Vec<String> {
+            column
+                .unwrap_or_default()
+                .split(separator)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let on = list(columns.next(), ' ')
+            .iter()
+            .map(|r| hiragana_to_katakana(r))
+            .collect();
+        let kun = list(columns.next(), ' ');
+        let meanings = list(columns.next(), ';');
+        let mut components = Vec::new();
+        for c in list(columns.next(), ' ')
+            .iter()
+            .filter_map(|c| c.chars().next())
+        {
+            if !components.contains(&c) {
+                components.push(c);
+            }
+        }
+        entries.insert(
+            kanji,
+            Entry {
+                strokes,
+                on,
+                kun,
+                meanings,
+                components,
+            },
+        );
+    }
+
+    let mut by_component: HashMap<char, Vec<char>> = HashMap::new();
+    for (&kanji, entry) in &entries {
+        for &component in &entry.components {
+            by_component.entry(component).or_default().push(kanji);
+        }
+    }
+    for list in by_component.values_mut() {
+        list.sort_by_key(|k| (entries[k].strokes, *k));
+    }
+    Table {
+        entries,
+        by_component,
+    }
+}
+
+impl Table {
+    /// Kanji containing every component, fewest strokes first
+    fn search(&self, components: &[char]) -> Vec<char> {
+        let shortest = components
+            .iter()
+            .map(|c| self.by_component.get(c).map_or(0, Vec::len))
+            .zip(components)
+            .min();
+        let Some((_, component)) = shortest else {
+            return Vec::new();
+        };
+        let Some(list) = self.by_component.get(component) else {
+            return Vec::new();
+        };
+        list.iter()
+            .copied()
+            .filter(|kanji| {
+                let entry = &self.entries[kanji];
+                components.iter().all(|c| entry.components.contains(c))
+            })
+            .collect()
+    }
+}
+
+/// Load the kanji table, replacing any loaded before
+/// Returns false if path is NULL or the file cannot be read, keeping the
+/// previous table.
+#[no_mangle]
+pub extern "C" fn sudachi_kanji_load(path: *const c_char) -> bool {
+    let Some(path) = c_str(path) else {
+        return false;
+    };
+    match std::fs::read_to_string(path) {
+        Ok(table) => {
+            let table = parse(&table);
+            *TABLE.write().unwrap_or_else(PoisonError::into_inner) = Some(table);
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to open kanji table {}: {}", path, e);
+            false
+        }
+    }
+}
+
+/// Details of a kanji as a JSON object:
+/// {"kanji":"食","strokes":9,"on":["ショク","ジキ"],"kun":["く.う","た.べる"],
+///  "meanings":["eat","food"]}
+/// Returns NULL if no table is loaded or the kanji is not in it (caller
+/// must free with sudachi_free_string)
+#[no_mangle]
+pub extern "C" fn sudachi_kanji_info(codepoint: u32) -> *mut c_char {
+    let Some(kanji) = char::from_u32(codepoint) else {
+        return ptr::null_mut();
+    };
+    let table = TABLE.read().unwrap_or_else(PoisonError::into_inner);
+    let Some(entry) = table.as_ref().and_then(|t| t.entries.get(&kanji)) else {
+        return ptr::null_mut();
+    };
+    let info = json!({
+        "kanji": kanji.to_string(),
+        "strokes": entry.strokes,
+        "on": entry.on,
+        "kun": entry.kun,
+        "meanings": entry.meanings,
+    });
+    crate::into_c_string(&info.to_string())
+}
+
+/// Components of a kanji, in table order, as a UTF-8 string ("口艹田")
+/// Returns NULL if no table is loaded or the kanji is not in it, and an
+/// empty string if the table lists no components for it (caller must free
+/// with sudachi_free_string)
+#[no_mangle]
+pub extern "C" fn sudachi_kanji_components(codepoint: u32) -> *mut c_char {
+    let Some(kanji) = char::from_u32(codepoint) else {
+        return ptr::null_mut();
+    };
+    let table = TABLE.read().unwrap_or_else(PoisonError::into_inner);
+    let Some(entry) = table.as_ref().and_then(|t| t.entries.get(&kanji)) else {
+        return ptr::null_mut();
+    };
+    crate::into_c_string(&entry.components.iter().collect::<String>())
+}
+
+/// Kanji that contain every one of count components (codepoints), fewest
+/// strokes first, as a UTF-8 string
+/// Returns an empty string if none match, and NULL if no table is loaded,
+/// components is NULL or count is 0 (caller must free with
+/// sudachi_free_string)
+#[no_mangle]
+pub extern "C" fn sudachi_kanji_by_components(components: *const u32, count: usize) -> *mut c_char {
+    if components.is_null() || count == 0 {
+        return ptr::null_mut();
+    }
+    let components: Option<Vec<char>> = unsafe { std::slice::from_raw_parts(components, count) }
+        .iter()
+        .map(|&c| char::from_u32(c))
+        .collect();
+    let table = TABLE.read().unwrap_or_else(PoisonError::into_inner);
+    let Some(table) = table.as_ref() else {
+        return ptr::null_mut();
+    };
+    let found = components.map_or(Vec::new(), |c| table.search(&c));
+    crate::into_c_string(&found.into_iter().collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let table = parse(
+            "# kanji table\n\
+             食\t9\tしょく ジキ\tく.う た.べる\teat; food\n\
+             猫\t11\tビョウ\tねこ\tcat\r\n\
+             食べ\t9\t\t\t\n\
+             木\tmany\tボク\tき\ttree\n\
+             休\t6\tキュウ\tやす.む\trest\t化 木 木\n\
+             体\t7\tタイ\tからだ\tbody\t化 木 一\n",
+        );
+        assert_eq!(table.entries.len(), 4);
+        let eat = &table.entries[&'食'];
+        assert_eq!(eat.strokes, 9);
+        assert_eq!(eat.on, ["ショク", "ジキ"]);
+        assert_eq!(eat.kun, ["く.う", "た.べる"]);
+        assert_eq!(eat.meanings, ["eat", "food"]);
+        assert_eq!(table.entries[&'猫'].meanings, ["cat"]);
+
+        assert_eq!(table.entries[&'休'].components, ['化', '木']);
+        assert_eq!(table.search(&['木']), ['休', '体']);
+        assert_eq!(table.search(&['化', '一']), ['体']);
+        assert!(table.search(&['木', '口']).is_empty());
+        assert!(table.search(&[]).is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+// Language detection gate
+// Bilingual releases interleave whole English (or Korean, Russian) lines
+// with the Japanese text. Analyzing them is slow and yields garbage tokens,
+// so before analysis each line is classified by the share of its letters in
+// a foreign script; lines at or above the handle's threshold are not
+// analyzed and come back as one opaque token each.
+
+use crate::kana::{is_kana, is_kanji};
+use crate::overrides::Entry;
+use crate::script::{script_of, SudachiScript};
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    handle, SudachiTokenizer, TokenData, SUDACHI_FIELD_DICTIONARY_FORM,
+    SUDACHI_FIELD_NORMALIZED_FORM, SUDACHI_FIELD_SURFACE,
+};
+
+/// Default share of foreign letters at which a line is skipped
+pub const SUDACHI_DEFAULT_LANGUAGE_GATE: f32 = 0.9;
+
+/// word_id of opaque tokens (their pos_id is 0xFFFF and POS is [])
+pub const SUDACHI_OPAQUE_WORD_ID: u32 = u32::MAX;
+
+/// Part of a text: analyzed normally, skipped as a foreign span, or pinned
+/// by an override
+#[derive(Debug, PartialEq)]
+pub(crate) enum Span<'a> {
+    Japanese(usize, &'a str),
+    Foreign(usize, &'a str, SudachiScript),
+    Override(usize, &'a Entry),
+}
+
+/// Dominant foreign script of line, if its share of the letters reaches
+/// threshold
+fn foreign_script(line: &str, threshold: f32) -> Option<SudachiScript> {
+    let mut japanese = 0;
+    let mut foreign = [0usize; 4];
+    for c in line.chars() {
+        if let Some(script) = script_of(c) {
+            foreign[script as usize] += 1;
+        } else if is_kana(c) || is_kanji(c) {
+            japanese += 1;
+        }
+    }
+    let total: usize = foreign.iter().sum();
+    if total == 0 || (total as f32) < threshold * (total + japanese) as f32 {
+        return None;
+    }
+    let dominant = (1..foreign.len()).max_by_key(|&i| foreign[i])?;
+    Some(SudachiScript::from_tag(dominant as u8))
+}
+
+/// Split text into analyzed and foreign spans, line by line
+/// A threshold of 0 (or below) disables the gate. Line breaks always stay
+/// in Japanese spans, and consecutive analyzed lines form one span.
+pub(crate) fn spans(text: &str, threshold: f32) -> Vec<Span<'_>> {
+    if threshold <= 0.0 {
+        return vec![Span::Japanese(0, text)];
+    }
+
+    let mut spans = Vec::new();
+    let mut pending = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        if let Some(script) = foreign_script(content, threshold) {
+            if pending < offset {
+                spans.push(Span::Japanese(pending, &text[pending..offset]));
+            }
+            spans.push(Span::Foreign(offset, content, script));
+            pending = offset + content.len();
+        }
+        offset += line.len();
+    }
+    if pending < text.len() || spans.is_empty() {
+        spans.push(Span::Japanese(pending, &text[pending..]));
+    }
+    spans
+}
+
+/// Unanalyzed token covering a foreign span
+pub(crate) fn opaque(offset: usize, text: &str, script: SudachiScript, fields: u32) -> TokenData {
+    let field = |bit: u32| {
+        if fields & bit != 0 {
+            text.to_string()
+        } else {
+            String::new()
+        }
+    };
+    TokenData {
+        fields,
+        surface: field(SUDACHI_FIELD_SURFACE),
+        reading: String::new(),
+        dictionary_form: field(SUDACHI_FIELD_DICTIONARY_FORM),
+        dictionary_reading: String::new(),
+        normalized_form: field(SUDACHI_FIELD_NORMALIZED_FORM),
+        pos: Vec::new(),
+        begin: offset,
+        end: offset + text.len(),
+        word_id: SUDACHI_OPAQUE_WORD_ID,
+        pos_id: u16::MAX,
+        script,
+        class: SudachiTokenClass::Opaque,
+        flags: 0,
+        lemma_key: 0,
+    }
+}
+
+/// Set the share of foreign-script letters at which a line is skipped
+/// Lines of text whose letters are at least threshold (0 to 1) Latin,
+/// Cyrillic or Hangul are returned as one opaque token instead of being
+/// analyzed; 0 analyzes everything. Returns false if tokenizer is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_language_gate(
+    tokenizer: *mut SudachiTokenizer,
+    threshold: f32,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.language_gate = threshold);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans() {
+        let text = "ONE PIECEを読む。\nI'm reading ONE PIECE.\r\n안녕하세요\n";
+        assert_eq!(
+            spans(text, SUDACHI_DEFAULT_LANGUAGE_GATE),
+            [
+                Span::Japanese(0, "ONE PIECEを読む。\n"),
+                Span::Foreign(22, "I'm reading ONE PIECE.", SudachiScript::Latin),
+                Span::Japanese(44, "\r\n"),
+                Span::Foreign(46, "안녕하세요", SudachiScript::Hangul),
+                Span::Japanese(61, "\n"),
+            ]
+        );
+
+        assert_eq!(spans(text, 0.0), [Span::Japanese(0, text)]);
+        assert_eq!(spans("", 0.9), [Span::Japanese(0, "")]);
+        assert_eq!(
+            spans("BOOM", 0.9),
+            [Span::Foreign(0, "BOOM", SudachiScript::Latin)]
+        );
+    }
+}
@@ -0,0 +1,230 @@
+// Struct layout checks
+// The Swift and Kotlin bindings read structs through their own copy of the
+// header, and a header from another build than the linked library (a stale
+// xcframework, a cached module map) makes them read fields at the wrong
+// offsets, which corrupts memory long before anything fails visibly. The
+// layouts are pinned here at compile time, so a change to a struct has to
+// touch this file (and SUDACHI_ABI_VERSION), and sudachi_layout_check lets a
+// binding compare the sizes it sees against the library at startup.
+//
+// Sizes are asserted for 64-bit targets; the packed result structs have no
+// pointers and are stored across launches, so they are pinned everywhere.
+
+use std::mem::{align_of, offset_of, size_of};
+
+use crate::builder::SudachiBuildProgress;
+use crate::bunsetsu::SudachiBunsetsu;
+use crate::columns::SudachiColumns;
+use crate::context::SudachiContextResult;
+use crate::convert::SudachiConversion;
+use crate::corpus::{SudachiCorpusFrequency, SudachiCorpusTotals};
+use crate::corrections::SudachiCorrection;
+use crate::deinflect::SudachiDeinflection;
+use crate::diff::SudachiTokenDiff;
+use crate::fingerprint::SudachiFingerprint;
+use crate::furigana::SudachiRuby;
+use crate::grammar::{SudachiGrammarInfo, SudachiWordParam};
+use crate::grapheme::SudachiByteRange;
+use crate::mapping::SudachiInitOptions;
+use crate::names::SudachiNameReading;
+use crate::ocr::SudachiOcrCorrection;
+use crate::options::SudachiOptions;
+use crate::overrides::SudachiOverride;
+use crate::packed::{SudachiPackedHeader, SudachiPackedToken, SudachiStrRef};
+use crate::progress::SudachiProgressInfo;
+use crate::rejoin::SudachiOcrLine;
+use crate::rerank::{SudachiPath, SudachiPathWord};
+use crate::result::SudachiTokenInfo;
+use crate::speakers::{SudachiSpeakerStats, SudachiSpeakerTerm};
+use crate::stamp::SudachiAnalysisStamp;
+use crate::stats::SudachiStats;
+use crate::SudachiToken;
+
+// Indices into the sizes passed to sudachi_layout_check
+// New structs are appended; an index never changes meaning.
+pub const SUDACHI_LAYOUT_TOKEN: usize = 0;
+pub const SUDACHI_LAYOUT_OPTIONS: usize = 1;
+pub const SUDACHI_LAYOUT_INIT_OPTIONS: usize = 2;
+pub const SUDACHI_LAYOUT_PACKED_HEADER: usize = 3;
+pub const SUDACHI_LAYOUT_PACKED_TOKEN: usize = 4;
+pub const SUDACHI_LAYOUT_STR_REF: usize = 5;
+pub const SUDACHI_LAYOUT_FINGERPRINT: usize = 6;
+pub const SUDACHI_LAYOUT_COLUMNS: usize = 7;
+pub const SUDACHI_LAYOUT_CONTEXT_RESULT: usize = 8;
+pub const SUDACHI_LAYOUT_BYTE_RANGE: usize = 9;
+pub const SUDACHI_LAYOUT_RUBY: usize = 10;
+pub const SUDACHI_LAYOUT_DEINFLECTION: usize = 11;
+pub const SUDACHI_LAYOUT_BUNSETSU: usize = 12;
+pub const SUDACHI_LAYOUT_OVERRIDE: usize = 13;
+pub const SUDACHI_LAYOUT_CORRECTION: usize = 14;
+pub const SUDACHI_LAYOUT_OCR_CORRECTION: usize = 15;
+pub const SUDACHI_LAYOUT_NAME_READING: usize = 16;
+pub const SUDACHI_LAYOUT_SPEAKER_STATS: usize = 17;
+pub const SUDACHI_LAYOUT_SPEAKER_TERM: usize = 18;
+pub const SUDACHI_LAYOUT_STATS: usize = 19;
+pub const SUDACHI_LAYOUT_CORPUS_FREQUENCY: usize = 20;
+pub const SUDACHI_LAYOUT_CORPUS_TOTALS: usize = 21;
+pub const SUDACHI_LAYOUT_TOKEN_DIFF: usize = 22;
+pub const SUDACHI_LAYOUT_OCR_LINE: usize = 23;
+pub const SUDACHI_LAYOUT_ANALYSIS_STAMP: usize = 24;
+pub const SUDACHI_LAYOUT_PROGRESS_INFO: usize = 25;
+pub const SUDACHI_LAYOUT_TOKEN_INFO: usize = 26;
+pub const SUDACHI_LAYOUT_CONVERSION: usize = 27;
+pub const SUDACHI_LAYOUT_BUILD_PROGRESS: usize = 28;
+pub const SUDACHI_LAYOUT_GRAMMAR_INFO: usize = 29;
+pub const SUDACHI_LAYOUT_WORD_PARAM: usize = 30;
+pub const SUDACHI_LAYOUT_PATH_WORD: usize = 31;
+pub const SUDACHI_LAYOUT_PATH: usize = 32;
+/// Number of SUDACHI_LAYOUT_* indices
+pub const SUDACHI_LAYOUT_COUNT: usize = 33;
+
+/// Sizes of the structs by SUDACHI_LAYOUT_* index
+const SIZES: [usize; SUDACHI_LAYOUT_COUNT] = [
+    size_of::<SudachiToken>(),
+    size_of::<SudachiOptions>(),
+    size_of::<SudachiInitOptions>(),
+    size_of::<SudachiPackedHeader>(),
+    size_of::<SudachiPackedToken>(),
+    size_of::<SudachiStrRef>(),
+    size_of::<SudachiFingerprint>(),
+    size_of::<SudachiColumns>(),
+    size_of::<SudachiContextResult>(),
+    size_of::<SudachiByteRange>(),
+    size_of::<SudachiRuby>(),
+    size_of::<SudachiDeinflection>(),
+    size_of::<SudachiBunsetsu>(),
+    size_of::<SudachiOverride>(),
+    size_of::<SudachiCorrection>(),
+    size_of::<SudachiOcrCorrection>(),
+    size_of::<SudachiNameReading>(),
+    size_of::<SudachiSpeakerStats>(),
+    size_of::<SudachiSpeakerTerm>(),
+    size_of::<SudachiStats>(),
+    size_of::<SudachiCorpusFrequency>(),
+    size_of::<SudachiCorpusTotals>(),
+    size_of::<SudachiTokenDiff>(),
+    size_of::<SudachiOcrLine>(),
+    size_of::<SudachiAnalysisStamp>(),
+    size_of::<SudachiProgressInfo>(),
+    size_of::<SudachiTokenInfo>(),
+    size_of::<SudachiConversion>(),
+    size_of::<SudachiBuildProgress>(),
+    size_of::<SudachiGrammarInfo>(),
+    size_of::<SudachiWordParam>(),
+    size_of::<SudachiPathWord>(),
+    size_of::<SudachiPath>(),
+];
+
+/// Assert the size and alignment of a struct at compile time
+macro_rules! assert_layout {
+    ($ty:ty, $size:literal, $align:literal) => {
+        const _: () = assert!(size_of::<$ty>() == $size && align_of::<$ty>() == $align);
+    };
+}
+
+// Stored in caller buffers and across launches
+assert_layout!(SudachiPackedHeader, 24, 8);
+assert_layout!(SudachiPackedToken, 72, 8);
+assert_layout!(SudachiStrRef, 8, 4);
+assert_layout!(SudachiFingerprint, 16, 8);
+const _: () = assert!(offset_of!(SudachiPackedToken, surface) == 16);
+const _: () = assert!(offset_of!(SudachiPackedToken, lemma_key) == 64);
+
+#[cfg(target_pointer_width = "64")]
+mod pointer_64 {
+    use super::*;
+
+    assert_layout!(SudachiToken, 136, 8);
+    assert_layout!(SudachiOptions, 56, 8);
+    assert_layout!(SudachiInitOptions, 28, 4);
+    assert_layout!(SudachiColumns, 72, 8);
+    assert_layout!(SudachiContextResult, 32, 8);
+    assert_layout!(SudachiByteRange, 8, 4);
+    assert_layout!(SudachiRuby, 24, 8);
+    assert_layout!(SudachiDeinflection, 40, 8);
+    assert_layout!(SudachiBunsetsu, 40, 8);
+    assert_layout!(SudachiOverride, 24, 8);
+    assert_layout!(SudachiCorrection, 32, 8);
+    assert_layout!(SudachiOcrCorrection, 24, 8);
+    assert_layout!(SudachiNameReading, 16, 8);
+    assert_layout!(SudachiSpeakerStats, 40, 8);
+    assert_layout!(SudachiSpeakerTerm, 16, 8);
+    assert_layout!(SudachiStats, 80, 8);
+    assert_layout!(SudachiCorpusFrequency, 16, 8);
+    assert_layout!(SudachiCorpusTotals, 24, 8);
+    assert_layout!(SudachiTokenDiff, 12, 4);
+    assert_layout!(SudachiOcrLine, 32, 8);
+    assert_layout!(SudachiAnalysisStamp, 32, 8);
+    assert_layout!(SudachiProgressInfo, 32, 8);
+    assert_layout!(SudachiTokenInfo, 40, 8);
+    assert_layout!(SudachiConversion, 16, 8);
+    assert_layout!(SudachiBuildProgress, 32, 8);
+    assert_layout!(SudachiGrammarInfo, 12, 4);
+    assert_layout!(SudachiWordParam, 6, 2);
+    assert_layout!(SudachiPathWord, 24, 8);
+    assert_layout!(SudachiPath, 24, 8);
+
+    // Fields read most, in the order the header declares them
+    const _: () = assert!(offset_of!(SudachiToken, begin) == 40);
+    const _: () = assert!(offset_of!(SudachiToken, dictionary_reading) == 72);
+    const _: () = assert!(offset_of!(SudachiToken, lemma_key) == 80);
+    const _: () = assert!(offset_of!(SudachiToken, inline_surface) == 88);
+}
+
+/// Index of the first entry of sizes that differs from the library, where 0
+/// means not checked
+fn mismatch(sizes: &[usize]) -> Option<usize> {
+    sizes
+        .iter()
+        .enumerate()
+        .position(|(i, &size)| size != 0 && SIZES.get(i) != Some(&size))
+}
+
+/// Compare struct sizes seen by the caller with the library's
+/// sizes[i] is sizeof the struct of SUDACHI_LAYOUT_* index i as compiled by
+/// the caller (MemoryLayout.stride in Swift), or 0 to skip it; count may be
+/// less than SUDACHI_LAYOUT_COUNT. Returns false if a size differs or an
+/// index is unknown to the library (a newer header), with that index in
+/// *out_mismatch if not NULL, or if sizes is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_layout_check(
+    sizes: *const usize,
+    count: usize,
+    out_mismatch: *mut usize,
+) -> bool {
+    if sizes.is_null() {
+        return false;
+    }
+    let sizes = unsafe { std::slice::from_raw_parts(sizes, count) };
+    let Some(index) = mismatch(sizes) else {
+        return true;
+    };
+    if !out_mismatch.is_null() {
+        unsafe {
+            *out_mismatch = index;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mismatch() {
+        assert_eq!(mismatch(&SIZES), None);
+        assert_eq!(mismatch(&SIZES[..3]), None);
+
+        let mut sizes = SIZES.to_vec();
+        sizes[SUDACHI_LAYOUT_OPTIONS] = 0;
+        assert_eq!(mismatch(&sizes), None);
+        sizes[SUDACHI_LAYOUT_PACKED_TOKEN] += 8;
+        assert_eq!(mismatch(&sizes), Some(SUDACHI_LAYOUT_PACKED_TOKEN));
+
+        // A struct the library does not know
+        let mut newer = SIZES.to_vec();
+        newer.push(16);
+        assert_eq!(mismatch(&newer), Some(SUDACHI_LAYOUT_COUNT));
+    }
+}
@@ -0,0 +1,123 @@
+// Leak checking
+// With the leak-check feature every result handed to C (tokenizers, tokens,
+// token arrays, furigana, deinflections, offsets, columns, strings, caches and
+// pools) is recorded together with the backtrace of its creation until the
+// matching free function is called. sudachi_outstanding_allocations and
+// sudachi_dump_allocations let the host check for leaks, e.g. at the end of a
+// test or when a screen is dismissed. Without the feature tracking is a no-op.
+
+pub(crate) use imp::{release, track};
+
+#[cfg(feature = "leak-check")]
+pub use imp::{sudachi_dump_allocations, sudachi_outstanding_allocations};
+
+#[cfg(not(feature = "leak-check"))]
+mod imp {
+    pub(crate) fn track<T>(_ptr: *const T, _kind: &'static str) {}
+
+    pub(crate) fn release<T>(_ptr: *const T) {}
+}
+
+#[cfg(feature = "leak-check")]
+mod imp {
+    use std::backtrace::Backtrace;
+    use std::collections::BTreeMap;
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    struct Allocation {
+        kind: &'static str,
+        sequence: u64,
+        backtrace: Backtrace,
+    }
+
+    struct Live {
+        next: u64,
+        allocations: BTreeMap<usize, Allocation>,
+    }
+
+    static LIVE: Mutex<Live> = Mutex::new(Live {
+        next: 0,
+        allocations: BTreeMap::new(),
+    });
+
+    fn live() -> MutexGuard<'static, Live> {
+        LIVE.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Record ptr as handed to C
+    pub(crate) fn track<T>(ptr: *const T, kind: &'static str) {
+        if ptr.is_null() {
+            return;
+        }
+        let backtrace = Backtrace::force_capture();
+        let mut live = live();
+        let sequence = live.next;
+        live.next += 1;
+        live.allocations.insert(
+            ptr as usize,
+            Allocation {
+                kind,
+                sequence,
+                backtrace,
+            },
+        );
+    }
+
+    /// Forget ptr once C has freed it
+    pub(crate) fn release<T>(ptr: *const T) {
+        if !ptr.is_null() {
+            live().allocations.remove(&(ptr as usize));
+        }
+    }
+
+    /// Number of results handed to C and not yet freed
+    #[no_mangle]
+    pub extern "C" fn sudachi_outstanding_allocations() -> usize {
+        live().allocations.len()
+    }
+
+    /// Write every outstanding allocation with its creation backtrace to
+    /// stderr, oldest first
+    /// Returns the number of allocations written
+    #[no_mangle]
+    pub extern "C" fn sudachi_dump_allocations() -> usize {
+        let live = live();
+        let mut allocations: Vec<_> = live.allocations.iter().collect();
+        allocations.sort_by_key(|(_, a)| a.sequence);
+
+        eprintln!(
+            "sudachi leak-check: {} outstanding allocations",
+            allocations.len()
+        );
+        for (address, allocation) in &allocations {
+            eprintln!(
+                "#{} {} at {:#x} created at:\n{}",
+                allocation.sequence, allocation.kind, address, allocation.backtrace
+            );
+        }
+        allocations.len()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_track_release() {
+            // Other tests allocate concurrently, so only look at our own entry
+            let value = Box::new(0u8);
+            let ptr: *const u8 = &*value;
+            let kind = |ptr: *const u8| live().allocations.get(&(ptr as usize)).map(|a| a.kind);
+
+            track(ptr, "test value");
+            assert_eq!(kind(ptr), Some("test value"));
+            assert!(sudachi_outstanding_allocations() >= 1);
+
+            release(ptr);
+            assert_eq!(kind(ptr), None);
+
+            track(std::ptr::null::<u8>(), "null");
+            assert_eq!(kind(std::ptr::null()), None);
+        }
+    }
+}
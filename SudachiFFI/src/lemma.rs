@@ -0,0 +1,97 @@
+// Lemma keys
+// Word ids index one build of the dictionary and change with every rebuild,
+// so they can't key a study database. A lemma key hashes what identifies a
+// word to the learner instead: its normalized form, the reading of its
+// dictionary form and its coarse POS (first column). Inflected forms share
+// the key of their lemma (食べた, 食べます -> 食べる), and a key stays the same
+// across rebuilds as long as those three do.
+//
+// key = FNV-1a 64 of UTF-8 normalized form | 0x00 | reading | 0x00 | POS,
+// with 0 mapped to 1 so that 0 can mean no key. Keys are taken before reading
+// post-processing, so handle settings don't change them.
+
+use std::os::raw::c_char;
+
+use crate::fingerprint::{fnv, FNV_OFFSET};
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    c_str, TokenData, SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_NORMALIZED_FORM,
+    SUDACHI_FIELD_POS,
+};
+
+/// Fields a lemma key is computed from
+pub(crate) const SOURCE_FIELDS: u32 =
+    SUDACHI_FIELD_NORMALIZED_FORM | SUDACHI_FIELD_DICTIONARY_READING | SUDACHI_FIELD_POS;
+
+pub(crate) fn key(normalized_form: &str, reading: &str, pos: &str) -> u64 {
+    let mut hash = fnv(FNV_OFFSET, normalized_form.as_bytes());
+    hash = fnv(hash, &[0]);
+    hash = fnv(hash, reading.as_bytes());
+    hash = fnv(hash, &[0]);
+    hash = fnv(hash, pos.as_bytes());
+    hash.max(1)
+}
+
+/// Set the lemma key of tokens analyzed with SOURCE_FIELDS
+/// Tokens without a POS (foreign runs, whitespace) keep 0.
+pub(crate) fn assign(tokens: &mut [TokenData]) {
+    for token in tokens {
+        token.lemma_key = match token.pos.first() {
+            Some(pos) if token.class != SudachiTokenClass::Opaque => {
+                key(&token.normalized_form, &token.dictionary_reading, pos)
+            }
+            _ => 0,
+        };
+    }
+}
+
+/// Clear the source fields of a token that the caller did not select
+pub(crate) fn strip(mut token: TokenData, fields: u32) -> TokenData {
+    let dropped = SOURCE_FIELDS & !fields;
+    if dropped & SUDACHI_FIELD_NORMALIZED_FORM != 0 {
+        token.normalized_form.clear();
+    }
+    if dropped & SUDACHI_FIELD_DICTIONARY_READING != 0 {
+        token.dictionary_reading.clear();
+    }
+    if dropped & SUDACHI_FIELD_POS != 0 {
+        token.pos.clear();
+    }
+    token.fields = fields;
+    token
+}
+
+/// Compute the lemma key of a word outside analysis
+/// pos is the first POS column ("動詞"); the result equals lemma_key of
+/// tokens of that word. Returns 0 if any argument is NULL or not UTF-8.
+#[no_mangle]
+pub extern "C" fn sudachi_lemma_key(
+    normalized_form: *const c_char,
+    dictionary_reading: *const c_char,
+    pos: *const c_char,
+) -> u64 {
+    match (
+        c_str(normalized_form),
+        c_str(dictionary_reading),
+        c_str(pos),
+    ) {
+        (Some(normalized_form), Some(reading), Some(pos)) => key(normalized_form, reading, pos),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key() {
+        // Part of the interface: keys are stored by apps and must never change
+        assert_eq!(key("食べる", "タベル", "動詞"), 0x15e0_5fcb_6200_b72f);
+        assert_ne!(
+            key("食べる", "タベル", "名詞"),
+            key("食べる", "タベル", "動詞")
+        );
+        assert_ne!(key("ab", "c", "名詞"), key("a", "bc", "名詞"));
+    }
+}
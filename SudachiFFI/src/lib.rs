@@ -1,43 +1,315 @@
 // sudachi-ios FFI Library
 // This provides a C-compatible interface to Sudachi for use in iOS apps
 
+// Every export takes raw pointers from C and validates them itself
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+use memmap2::Mmap;
+use std::collections::BTreeSet;
 use std::ffi::{CStr, CString};
 use std::fs::File;
 use std::os::raw::c_char;
 use std::path::PathBuf;
 use std::ptr;
-use std::sync::Arc;
-use memmap2::Mmap;
-use sudachi::analysis::stateless_tokenizer::StatelessTokenizer;
-use sudachi::analysis::Tokenize;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
 use sudachi::config::Config;
 use sudachi::dic::dictionary::JapaneseDictionary;
+use sudachi::dic::lexicon::word_infos::WordInfo;
 use sudachi::dic::storage::{Storage, SudachiDicData};
+use sudachi::dic::subset::InfoSubset;
+use sudachi::dic::word_id::WordId;
 use sudachi::prelude::*;
 
+use analyzer::{Analyzer, Options};
+use fingerprint::SudachiFingerprint;
+use inline::SUDACHI_INLINE_BYTES;
+use mapping::SudachiInitOptions;
+use options::SudachiOptions;
+use script::SudachiScript;
+use signpost::Signpost;
+use stats::Stats;
+use symbol::SudachiTokenClass;
+
+pub mod abi;
+pub mod allocator;
+mod analyzer;
+pub mod batch;
+pub mod builder;
+pub mod bunsetsu;
+pub mod cache;
+pub mod chunk;
+pub mod columns;
+pub mod compound;
+pub mod conllu;
+pub mod context;
+pub mod convert;
+pub mod corpus;
+pub mod corrections;
+pub mod costs;
+pub mod deinflect;
+pub mod dialogue;
+pub mod dictdiff;
+pub mod dictionaries;
+pub mod diff;
+pub mod document;
+pub mod export;
+pub mod fingerprint;
+pub mod format;
+pub mod furigana;
+pub mod global;
+pub mod grammar;
+pub mod grapheme;
+mod handle;
+pub mod handwriting;
+pub mod inline;
+mod iteration;
+mod kana;
+pub mod kanji;
+pub mod language;
+pub mod layout;
+pub mod leak;
+pub mod lemma;
+pub mod lookup;
+pub mod mapping;
+pub mod names;
+#[cfg(feature = "napi")]
+pub mod node;
+pub mod ocr;
+pub mod oov;
+pub mod options;
+pub mod overrides;
+pub mod packed;
+pub mod pages;
+pub mod phonetic;
+pub mod pool;
+pub mod presets;
+pub mod progress;
+#[cfg(feature = "pyo3")]
+pub mod python;
+pub mod queue;
+pub mod readability;
+pub mod reading;
+pub mod rejoin;
+pub mod rerank;
+pub mod result;
+pub mod script;
+pub mod sections;
+pub mod sentences;
+pub mod sfx;
+mod signpost;
+mod simd;
+pub mod snapshot;
+pub mod speakers;
+pub mod stamp;
+pub mod stats;
+pub mod status;
+pub mod stream;
+pub mod subset;
+#[cfg(feature = "uniffi")]
+pub mod swift;
+pub mod symbol;
+#[cfg(test)]
+mod testdict;
+#[cfg(test)]
+mod testtoken;
+pub mod token_map;
+pub mod trace;
+pub mod usage;
+pub mod utf8;
+pub mod wakati;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod whitespace;
+pub mod width;
+pub mod wildcard;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
 // Opaque pointer types for safer FFI
+
+/// Tokenizer handle
+/// Created by sudachi_init and owned by the caller until released with
+/// sudachi_free_tokenizer. Calls on one handle are serialized internally.
 pub struct SudachiTokenizer {
-    dictionary: Arc<JapaneseDictionary>,
-    tokenizer: StatelessTokenizer<Arc<JapaneseDictionary>>,
+    pub(crate) dictionary: Arc<JapaneseDictionary>,
+    pub(crate) fingerprint: SudachiFingerprint,
+    pub(crate) image: mapping::Image,
+    analyzer: Mutex<Analyzer>,
+    pub(crate) stats: Stats,
+}
+
+impl SudachiTokenizer {
+    pub(crate) fn new(loaded: LoadedDictionary) -> SudachiTokenizer {
+        SudachiTokenizer {
+            analyzer: Mutex::new(Analyzer::new(loaded.dictionary.clone())),
+            dictionary: loaded.dictionary,
+            fingerprint: loaded.fingerprint,
+            image: loaded.image,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Analyze text with this handle's reusable analyzer
+    /// Only the string fields selected by the SUDACHI_FIELD_* mask are filled
+    pub(crate) fn analyze(
+        &self,
+        text: &str,
+        mode: Mode,
+        fields: u32,
+    ) -> SudachiResult<Vec<TokenData>> {
+        let result = self.with_analyzer(|analyzer| analyzer.analyze(text, mode, fields));
+        self.record(&result, fields);
+        result
+    }
+
+    /// Analyze text with per-call options on top of this handle's settings
+    pub(crate) fn analyze_with(
+        &self,
+        text: &str,
+        mode: Mode,
+        fields: u32,
+        options: &SudachiOptions,
+    ) -> SudachiResult<Vec<TokenData>> {
+        let result = self.with_analyzer(|analyzer| {
+            let saved = analyzer.options.clone();
+            options.adjust(&mut analyzer.options);
+            let result = analyzer.analyze(text, mode, fields);
+            analyzer.options = saved;
+            result
+        });
+        self.record(&result, fields);
+        result
+    }
+
+    fn record(&self, result: &SudachiResult<Vec<TokenData>>, fields: u32) {
+        match result {
+            // One allocation per selected string field of each token, plus the list
+            Ok(tokens) => self.stats.record_tokens(
+                tokens.len(),
+                1 + tokens.len()
+                    * (fields & SUDACHI_FIELD_ALL & !SUDACHI_FIELD_LEMMA_KEY).count_ones() as usize,
+            ),
+            Err(_) => self.stats.record_error(),
+        }
+    }
+
+    /// Analysis settings of this handle
+    pub(crate) fn options(&self) -> Options {
+        self.lock_analyzer().options.clone()
+    }
+
+    /// Copy the analysis settings of this handle into options
+    pub(crate) fn copy_options(&self, options: &mut Options) {
+        options.clone_from(&self.lock_analyzer().options);
+    }
+
+    pub(crate) fn set_options(&self, f: impl FnOnce(&mut Options)) {
+        f(&mut self.lock_analyzer().options);
+    }
+
+    fn lock_analyzer(&self) -> MutexGuard<'_, Analyzer> {
+        self.analyzer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run f with exclusive access to this handle's analyzer
+    /// Counted as one call in the handle's stats
+    pub(crate) fn with_analyzer<R>(&self, f: impl FnOnce(&mut Analyzer) -> R) -> R {
+        let _signpost = signpost::interval(Signpost::Tokenize);
+        let start = Instant::now();
+        let result = f(&mut self.lock_analyzer());
+        self.stats.record_call(start.elapsed());
+        result
+    }
 }
 
+/// A single morpheme
+/// Tokens and their strings are allocated by the library; free a token with
+/// sudachi_free_token or a whole array with sudachi_free_tokens, never with
+/// free(). Offsets are byte offsets into the UTF-8 input. String fields are
+/// NULL when not requested or unavailable.
 #[repr(C)]
 pub struct SudachiToken {
-    surface: *mut c_char,
-    reading: *mut c_char,
-    dictionary_form: *mut c_char,
-    normalized_form: *mut c_char,
-    pos: *mut c_char,  // JSON array string
-    begin: i32,
-    end: i32,
+    /// Surface form as it appears in the input
+    pub(crate) surface: *mut c_char,
+    /// Reading in katakana
+    pub(crate) reading: *mut c_char,
+    /// Dictionary (base) form
+    pub(crate) dictionary_form: *mut c_char,
+    /// Normalized form
+    pub(crate) normalized_form: *mut c_char,
+    /// POS tags as a JSON array string
+    pub(crate) pos: *mut c_char,
+    /// Start byte offset in the input
+    pub(crate) begin: i32,
+    /// End byte offset in the input (exclusive)
+    pub(crate) end: i32,
+    /// Raw Sudachi word id (dictionary + index)
+    pub(crate) word_id: u32,
+    /// Index into the dictionary POS table
+    pub(crate) pos_id: u16,
+    /// Script of the surface, a hint for non-Japanese text
+    pub(crate) script: SudachiScript,
+    /// Word, symbol, emoji or opaque
+    pub(crate) class: SudachiTokenClass,
+    /// Notes on how the token was formed (SUDACHI_TOKEN_* bits)
+    pub(crate) flags: u8,
+    /// Reading of the dictionary form in katakana
+    pub(crate) dictionary_reading: *mut c_char,
+    /// Identifier of the lemma that stays the same across dictionary
+    /// rebuilds, 0 if not requested or not a word (see sudachi_lemma_key)
+    pub(crate) lemma_key: u64,
+    /// Storage of a short surface; read the surface field, not this
+    pub(crate) inline_surface: [c_char; SUDACHI_INLINE_BYTES],
+    /// Storage of a short reading; read the reading field, not this
+    pub(crate) inline_reading: [c_char; SUDACHI_INLINE_BYTES],
 }
 
+// Bits of SudachiToken.flags
+/// An iteration mark (々, ゝ, ヽ, ゞ, ヾ) was resolved against the previous word
+pub const SUDACHI_TOKEN_ITERATION: u8 = 1 << 0;
+/// Unknown to the dictionary (an OOV word)
+pub const SUDACHI_TOKEN_OOV: u8 = 1 << 1;
+/// Reading or segmentation pinned by sudachi_set_overrides
+pub const SUDACHI_TOKEN_OVERRIDE: u8 = 1 << 2;
+/// A verb joined with the auxiliary verb after it (食べ始める)
+pub const SUDACHI_TOKEN_COMPOUND: u8 = 1 << 3;
+/// Covers a character fixed by OCR post-correction (sudachi_set_ocr_correction)
+pub const SUDACHI_TOKEN_OCR: u8 = 1 << 4;
+/// Reading guessed one kanji at a time for an unknown word
+/// (SUDACHI_READING_OOV_KANJI); low confidence
+pub const SUDACHI_TOKEN_GUESSED: u8 = 1 << 5;
+
+// Field mask for sudachi_tokenize_fields
+// Unselected string fields are left NULL; offsets and ids are always filled
+pub const SUDACHI_FIELD_SURFACE: u32 = 1 << 0;
+pub const SUDACHI_FIELD_READING: u32 = 1 << 1;
+pub const SUDACHI_FIELD_DICTIONARY_FORM: u32 = 1 << 2;
+pub const SUDACHI_FIELD_NORMALIZED_FORM: u32 = 1 << 3;
+pub const SUDACHI_FIELD_POS: u32 = 1 << 4;
+pub const SUDACHI_FIELD_DICTIONARY_READING: u32 = 1 << 5;
+/// Not a string: fills SudachiToken.lemma_key
+pub const SUDACHI_FIELD_LEMMA_KEY: u32 = 1 << 6;
+pub const SUDACHI_FIELD_ALL: u32 = SUDACHI_FIELD_SURFACE
+    | SUDACHI_FIELD_READING
+    | SUDACHI_FIELD_DICTIONARY_FORM
+    | SUDACHI_FIELD_NORMALIZED_FORM
+    | SUDACHI_FIELD_POS
+    | SUDACHI_FIELD_DICTIONARY_READING
+    | SUDACHI_FIELD_LEMMA_KEY;
+
+/// Segmentation granularity
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub enum SudachiTokenMode {
-    A = 0,  // Short units
-    B = 1,  // Medium units (default)
-    C = 2,  // Long units
+    /// Short units
+    A = 0,
+    /// Medium units (default)
+    B = 1,
+    /// Long units
+    C = 2,
 }
 
 impl From<SudachiTokenMode> for Mode {
@@ -50,6 +322,367 @@ impl From<SudachiTokenMode> for Mode {
     }
 }
 
+/// Longest text in bytes the library accepts
+/// Offsets in results are i32 (u32 in packed results), so longer texts are
+/// rejected up front instead of producing wrapped offsets.
+pub const SUDACHI_MAX_TEXT_BYTES: usize = i32::MAX as usize;
+
+/// Borrow a C string as UTF-8
+/// Returns None for NULL, invalid UTF-8 or more than SUDACHI_MAX_TEXT_BYTES
+pub(crate) fn c_str<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+    let s = unsafe { CStr::from_ptr(ptr) };
+    if s.to_bytes().len() > SUDACHI_MAX_TEXT_BYTES {
+        return None;
+    }
+    s.to_str().ok()
+}
+
+/// Copy a string into a C string owned by the caller
+/// Returns NULL if the string contains an interior NUL byte
+pub(crate) fn into_c_string(s: &str) -> *mut c_char {
+    CString::new(s)
+        .map(|s| s.into_raw())
+        .unwrap_or(ptr::null_mut())
+}
+
+/// Hand a token pointer array over to the caller
+/// Pair with sudachi_free_tokens
+pub(crate) fn into_token_array(
+    tokens: Vec<*mut SudachiToken>,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    unsafe {
+        *out_count = tokens.len();
+    }
+
+    // Empty arrays share a dangling pointer and need no free
+    if tokens.is_empty() {
+        return ptr::NonNull::dangling().as_ptr();
+    }
+    // The length is stored in a slot before the array, so
+    // sudachi_free_tokens frees what was allocated whatever count it is given
+    let mut slots = Vec::with_capacity(tokens.len() + 1);
+    slots.push(ptr::without_provenance_mut(tokens.len()));
+    slots.extend(tokens);
+    let ptr = unsafe { Box::leak(slots.into_boxed_slice()).as_mut_ptr().add(1) };
+    token_arrays(ptr).insert(ptr as usize);
+    leak::track(ptr, "token array");
+    ptr
+}
+
+/// Shards of the token array registry
+const TOKEN_ARRAY_SHARDS: usize = 16;
+
+/// Token arrays handed to C and not yet freed, by address
+/// sudachi_free_tokens ignores arrays it does not know (freed or foreign)
+/// instead of reading their length slot. Sharded so threads freeing
+/// different arrays rarely share a lock.
+static TOKEN_ARRAYS: [Mutex<BTreeSet<usize>>; TOKEN_ARRAY_SHARDS] =
+    [const { Mutex::new(BTreeSet::new()) }; TOKEN_ARRAY_SHARDS];
+
+/// Shard of the token array registry that holds ptr
+fn token_arrays<T>(ptr: *const T) -> MutexGuard<'static, BTreeSet<usize>> {
+    // Low bits are alignment, the same for every array
+    let shard = (ptr as usize >> 4) % TOKEN_ARRAY_SHARDS;
+    TOKEN_ARRAYS[shard]
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Owned analysis of a single morpheme
+/// Built independently of the FFI layout so it can cross threads before
+/// being converted into a SudachiToken
+#[derive(Clone)]
+pub(crate) struct TokenData {
+    pub(crate) fields: u32,
+    pub(crate) surface: String,
+    pub(crate) reading: String,
+    pub(crate) dictionary_form: String,
+    pub(crate) dictionary_reading: String,
+    pub(crate) normalized_form: String,
+    pub(crate) pos: Vec<String>,
+    pub(crate) begin: usize,
+    pub(crate) end: usize,
+    pub(crate) word_id: u32,
+    pub(crate) pos_id: u16,
+    pub(crate) script: SudachiScript,
+    pub(crate) class: SudachiTokenClass,
+    pub(crate) flags: u8,
+    pub(crate) lemma_key: u64,
+}
+
+/// Reading of the dictionary form of a word, looked up in the lexicon when
+/// the word is inflected (食べた -> タベル)
+pub(crate) fn dictionary_reading<T: DictionaryAccess>(
+    dictionary: &T,
+    word_id: WordId,
+    info: &WordInfo,
+) -> String {
+    let id = info.dictionary_form_word_id();
+    if id < 0 || word_id.is_oov() || id as u32 == word_id.word() {
+        return info.reading_form().to_string();
+    }
+    dictionary
+        .lexicon()
+        .get_word_info_subset(
+            WordId::new(word_id.dic(), id as u32),
+            InfoSubset::READING_FORM,
+        )
+        .map(|base| base.reading_form().to_string())
+        .unwrap_or_else(|_| info.reading_form().to_string())
+}
+
+impl TokenData {
+    /// Copy the fields selected by the SUDACHI_FIELD_* mask out of a morpheme
+    /// of an analysis on dictionary
+    pub(crate) fn from_morpheme<T: DictionaryAccess>(
+        dictionary: &T,
+        morpheme: &Morpheme<T>,
+        fields: u32,
+    ) -> TokenData {
+        let field = |bit: u32, value: &str| {
+            if fields & bit != 0 {
+                value.to_string()
+            } else {
+                String::new()
+            }
+        };
+
+        let surface = morpheme.surface();
+        TokenData {
+            fields,
+            surface: field(SUDACHI_FIELD_SURFACE, &surface),
+            reading: field(SUDACHI_FIELD_READING, morpheme.reading_form()),
+            dictionary_form: field(SUDACHI_FIELD_DICTIONARY_FORM, morpheme.dictionary_form()),
+            dictionary_reading: if fields & SUDACHI_FIELD_DICTIONARY_READING != 0 {
+                dictionary_reading(dictionary, morpheme.word_id(), morpheme.get_word_info())
+            } else {
+                String::new()
+            },
+            normalized_form: field(SUDACHI_FIELD_NORMALIZED_FORM, morpheme.normalized_form()),
+            pos: if fields & SUDACHI_FIELD_POS != 0 {
+                morpheme.part_of_speech().to_vec()
+            } else {
+                Vec::new()
+            },
+            begin: morpheme.begin(),
+            end: morpheme.end(),
+            word_id: morpheme.word_id().as_raw(),
+            pos_id: morpheme.part_of_speech_id(),
+            script: script::token_script(&surface),
+            class: symbol::token_class(&surface),
+            flags: if morpheme.is_oov() {
+                SUDACHI_TOKEN_OOV
+            } else {
+                0
+            },
+            lemma_key: 0,
+        }
+    }
+
+    /// Merge this token with the ones after it, up to end in text
+    /// Keeps this token's word info; its selected string fields all become
+    /// the merged surface.
+    pub(crate) fn extend_to(self, text: &str, end: usize) -> TokenData {
+        let surface = &text[self.begin..end];
+        let field = |bit: u32| {
+            if self.fields & bit != 0 {
+                surface.to_string()
+            } else {
+                String::new()
+            }
+        };
+        TokenData {
+            surface: field(SUDACHI_FIELD_SURFACE),
+            reading: field(SUDACHI_FIELD_READING),
+            dictionary_form: field(SUDACHI_FIELD_DICTIONARY_FORM),
+            dictionary_reading: field(SUDACHI_FIELD_DICTIONARY_READING),
+            normalized_form: field(SUDACHI_FIELD_NORMALIZED_FORM),
+            end,
+            ..self
+        }
+    }
+
+    /// Convert into a C token (free with sudachi_free_token)
+    /// Returns None if a selected surface cannot be represented as a C string
+    pub(crate) fn into_raw(self) -> Option<*mut SudachiToken> {
+        let has = |bit: u32| self.fields & bit != 0;
+        if has(SUDACHI_FIELD_SURFACE) && self.surface.contains('\0') {
+            return None;
+        }
+        let selected = |bit: u32, value: &str| {
+            if has(bit) {
+                into_c_string(value)
+            } else {
+                ptr::null_mut()
+            }
+        };
+
+        // Serialize POS tags as JSON array
+        let pos = if has(SUDACHI_FIELD_POS) {
+            serde_json::to_string(&self.pos)
+                .map(|json| into_c_string(&json))
+                .unwrap_or(ptr::null_mut())
+        } else {
+            ptr::null_mut()
+        };
+
+        let token = handle::into_raw(SudachiToken {
+            surface: ptr::null_mut(),
+            reading: ptr::null_mut(),
+            dictionary_form: selected(SUDACHI_FIELD_DICTIONARY_FORM, &self.dictionary_form),
+            normalized_form: selected(SUDACHI_FIELD_NORMALIZED_FORM, &self.normalized_form),
+            pos,
+            begin: self.begin as i32,
+            end: self.end as i32,
+            word_id: self.word_id,
+            pos_id: self.pos_id,
+            script: self.script,
+            class: self.class,
+            flags: self.flags,
+            dictionary_reading: selected(
+                SUDACHI_FIELD_DICTIONARY_READING,
+                &self.dictionary_reading,
+            ),
+            lemma_key: self.lemma_key,
+            inline_surface: inline::EMPTY,
+            inline_reading: inline::EMPTY,
+        });
+        // Inline strings point into the token, so fill them in place
+        let t = unsafe { &mut *token };
+        if has(SUDACHI_FIELD_SURFACE) {
+            t.surface = inline::store(&mut t.inline_surface, self.surface.as_bytes());
+        }
+        if has(SUDACHI_FIELD_READING) {
+            t.reading = inline::store(&mut t.inline_reading, self.reading.as_bytes());
+        }
+        Some(token)
+    }
+}
+
+/// Dictionary with the fingerprint of the image it was built from
+#[derive(Clone)]
+pub(crate) struct LoadedDictionary {
+    pub(crate) dictionary: Arc<JapaneseDictionary>,
+    pub(crate) fingerprint: SudachiFingerprint,
+    pub(crate) image: mapping::Image,
+}
+
+/// Map a dictionary file and build a JapaneseDictionary from it
+pub(crate) fn load_dictionary(path: &str) -> Result<LoadedDictionary, String> {
+    load_dictionaries(path, &[], &SudachiInitOptions::default())
+}
+
+/// Map a system dictionary and user dictionaries, in priority order, and
+/// build one JapaneseDictionary from them
+pub(crate) fn load_dictionaries(
+    path: &str,
+    users: &[&str],
+    options: &SudachiInitOptions,
+) -> Result<LoadedDictionary, String> {
+    let (system, users) = if options.threads > 1 && !users.is_empty() {
+        std::thread::scope(|scope| {
+            let users: Vec<_> = users
+                .iter()
+                .map(|user| scope.spawn(move || map_dictionary(user, options)))
+                .collect();
+            let system = map_dictionary(path, options);
+            let users = users
+                .into_iter()
+                .map(|u| {
+                    u.join()
+                        .unwrap_or_else(|_| Err("Dictionary load panicked".into()))
+                })
+                .collect::<Result<Vec<_>, _>>();
+            (system, users)
+        })
+    } else {
+        let system = map_dictionary(path, options);
+        let users = users
+            .iter()
+            .map(|user| map_dictionary(user, options))
+            .collect::<Result<Vec<_>, _>>();
+        (system, users)
+    };
+    dictionary_from_storages(system?, users?)
+}
+
+fn map_dictionary(path: &str, options: &SudachiInitOptions) -> Result<Storage, String> {
+    // We only have the dictionary file, no config or char.def
+    // So we need to load the dictionary directly without Config
+    let dict_pathbuf = PathBuf::from(path);
+
+    if options.flags & sections::SUDACHI_INIT_COPY_TO_RAM != 0 {
+        let bytes = mapping::read(&dict_pathbuf, options.threads)
+            .map_err(|_| format!("Failed to read dictionary file: {}", path))?;
+        return Ok(Storage::Owned(bytes));
+    }
+
+    // Try to load dictionary directly from file
+    let file = File::open(&dict_pathbuf)
+        .map_err(|_| format!("Failed to open dictionary file: {}", path))?;
+
+    let mapping = unsafe { Mmap::map(&file) }
+        .map_err(|_| "Failed to memory map dictionary file".to_string())?;
+    mapping::advise(&mapping, options);
+
+    Ok(Storage::File(mapping))
+}
+
+/// Build a JapaneseDictionary from system dictionary bytes
+pub(crate) fn dictionary_from_storage(storage: Storage) -> Result<LoadedDictionary, String> {
+    dictionary_from_storages(storage, Vec::new())
+}
+
+fn dictionary_from_storages(
+    system: Storage,
+    users: Vec<Storage>,
+) -> Result<LoadedDictionary, String> {
+    trace::span!("dictionary_load");
+    let _signpost = signpost::interval(Signpost::DictionaryLoad);
+    let images: Vec<&[u8]> = std::iter::once(system.as_ref())
+        .chain(users.iter().map(AsRef::as_ref))
+        .collect();
+    let fingerprint = SudachiFingerprint::of(&images);
+    let image = match &system {
+        Storage::File(mapping) => mapping::Image {
+            addr: mapping.as_ptr() as usize,
+            len: mapping.len(),
+        },
+        _ => mapping::Image::default(),
+    };
+    let mut dic_data = SudachiDicData::new(system);
+    for user in users {
+        dic_data.add_user(user);
+    }
+
+    // Create minimal config for plugins
+    // Use embedded chardef method - doesn't need external char.def file
+    let config = Config {
+        // Add minimal OOV provider plugin (required by Sudachi)
+        oov_provider_plugins: vec![serde_json::json!({
+            "class": "com.worksap.nlp.sudachi.SimpleOovPlugin",
+            "oovPOS": ["名詞", "普通名詞", "一般", "*", "*", "*"],
+            "leftId": 0,
+            "rightId": 0,
+            "cost": 30000
+        })],
+        ..Config::default()
+    };
+
+    // Use the embedded chardef variant - doesn't require external char.def file
+    JapaneseDictionary::from_cfg_storage_with_embedded_chardef(&config, dic_data)
+        .map(|dictionary| LoadedDictionary {
+            dictionary: Arc::new(dictionary),
+            fingerprint,
+            image,
+        })
+        .map_err(|e| format!("Failed to create dictionary: {:?}", e))
+}
+
 /// Initialize Sudachi tokenizer with dictionary path
 /// Returns NULL on failure
 #[no_mangle]
@@ -65,59 +698,36 @@ pub extern "C" fn sudachi_init(dict_path: *const c_char) -> *mut SudachiTokenize
         }
     };
 
-    // Create dictionary from path
-    // We only have the dictionary file, no config or char.def
-    // So we need to load the dictionary directly without Config
-    let dict_pathbuf = PathBuf::from(path);
-    
-    // Try to load dictionary directly from file
-    let file = match File::open(&dict_pathbuf) {
-        Ok(f) => f,
-        Err(_) => {
-            eprintln!("Failed to open dictionary file: {}", path);
-            return ptr::null_mut();
-        }
-    };
-    
-    let mapping = match unsafe { Mmap::map(&file) } {
-        Ok(m) => m,
-        Err(_) => {
-            eprintln!("Failed to memory map dictionary file");
+    let dictionary = match load_dictionary(path) {
+        Ok(dict) => dict,
+        Err(e) => {
+            eprintln!("{}", e);
             return ptr::null_mut();
         }
     };
-    
-    let storage = Storage::File(mapping);
-    let dic_data = SudachiDicData::new(storage);
-    
-    // Create minimal config for plugins
-    // Use embedded chardef method - doesn't need external char.def file
-    let mut config = Config::default();
-    
-    // Add minimal OOV provider plugin (required by Sudachi)
-    config.oov_provider_plugins = vec![serde_json::json!({
-        "class": "com.worksap.nlp.sudachi.SimpleOovPlugin",
-        "oovPOS": ["名詞", "普通名詞", "一般", "*", "*", "*"],
-        "leftId": 0,
-        "rightId": 0,
-        "cost": 30000
-    })];
-    
-    // Use the embedded chardef variant - doesn't require external char.def file
-    let dictionary = match JapaneseDictionary::from_cfg_storage_with_embedded_chardef(&config, dic_data) {
-        Ok(dict) => Arc::new(dict),
+
+    handle::into_raw(SudachiTokenizer::new(dictionary))
+}
+
+/// Initialize Sudachi tokenizer from a dictionary image in memory
+/// The bytes are copied, so data may be freed once this returns.
+/// Returns NULL on failure
+#[no_mangle]
+pub extern "C" fn sudachi_init_from_bytes(data: *const u8, len: usize) -> *mut SudachiTokenizer {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+
+    let bytes = unsafe { std::slice::from_raw_parts(data, len) }.to_vec();
+    let dictionary = match dictionary_from_storage(Storage::Owned(bytes)) {
+        Ok(dict) => dict,
         Err(e) => {
-            eprintln!("Failed to create dictionary: {:?}", e);
+            eprintln!("{}", e);
             return ptr::null_mut();
         }
     };
 
-    let tokenizer = StatelessTokenizer::new(dictionary.clone());
-
-    Box::into_raw(Box::new(SudachiTokenizer {
-        dictionary,
-        tokenizer,
-    }))
+    handle::into_raw(SudachiTokenizer::new(dictionary))
 }
 
 /// Tokenize text using Sudachi
@@ -128,82 +738,48 @@ pub extern "C" fn sudachi_tokenize(
     text: *const c_char,
     mode: SudachiTokenMode,
     out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    sudachi_tokenize_fields(tokenizer, text, mode, SUDACHI_FIELD_ALL, out_count)
+}
+
+/// Tokenize text, filling only the string fields selected by a
+/// SUDACHI_FIELD_* mask. Offsets and ids are always filled, so a mask of 0
+/// returns offsets and ids only without allocating any strings.
+/// Returns array of tokens (caller must free with sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_fields(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    fields: u32,
+    out_count: *mut usize,
 ) -> *mut *mut SudachiToken {
     if tokenizer.is_null() || text.is_null() || out_count.is_null() {
         return ptr::null_mut();
     }
 
-    let tokenizer = unsafe { &*tokenizer };
-    let text_str = unsafe {
-        match CStr::from_ptr(text).to_str() {
-            Ok(s) => s,
-            Err(_) => return ptr::null_mut(),
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let text = unsafe { CStr::from_ptr(text) };
+    if text.to_bytes().len() > SUDACHI_MAX_TEXT_BYTES {
+        return ptr::null_mut();
+    }
+    let text_str = match text.to_str() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Invalid UTF-8 in input at byte {}", e.valid_up_to());
+            return ptr::null_mut();
         }
     };
 
-    let mode: Mode = mode.into();
-
-    // Tokenize
-    let morphemes = match tokenizer.tokenizer.tokenize(text_str, mode, false) {
-        Ok(morphemes) => morphemes,
+    let tokens = match tokenizer.analyze(text_str, mode.into(), fields) {
+        Ok(tokens) => tokens,
         Err(_) => return ptr::null_mut(),
     };
 
-        // Convert to C-compatible tokens
-        let tokens: Vec<*mut SudachiToken> = morphemes
-            .iter()
-            .filter_map(|morpheme| {
-                let surface = match CString::new(&*morpheme.surface()) {
-                    Ok(s) => s.into_raw(),
-                    Err(_) => return None,
-                };
-
-                let reading = CString::new(&*morpheme.reading_form())
-                    .ok()
-                    .map(|s| s.into_raw())
-                    .unwrap_or(ptr::null_mut());
-
-                let dict_form = CString::new(&*morpheme.dictionary_form())
-                    .ok()
-                    .map(|s| s.into_raw())
-                    .unwrap_or(ptr::null_mut());
-
-                let normalized = CString::new(&*morpheme.normalized_form())
-                    .ok()
-                    .map(|s| s.into_raw())
-                    .unwrap_or(ptr::null_mut());
-
-                // Serialize POS tags as JSON array
-                let pos_tags = morpheme.part_of_speech();
-                let pos_json = serde_json::to_string(&pos_tags).ok()
-                    .and_then(|json| CString::new(json).ok())
-                    .map(|s| s.into_raw())
-                    .unwrap_or(ptr::null_mut());
-
-                let begin = morpheme.begin() as i32;
-                let end = morpheme.end() as i32;
-
-                Some(Box::into_raw(Box::new(SudachiToken {
-                    surface,
-                    reading,
-                    dictionary_form: dict_form,
-                    normalized_form: normalized,
-                    pos: pos_json,
-                    begin,
-                    end,
-                })))
-            })
-            .collect();
-
-    unsafe {
-        *out_count = tokens.len();
-    }
-
-    // Convert Vec to C array
-    let mut result_array = tokens.into_boxed_slice();
-    let ptr = result_array.as_mut_ptr();
-    Box::leak(result_array);
-    ptr
+    // Convert to C-compatible tokens
+    trace::span!("marshal");
+    let tokens = tokens.into_iter().filter_map(TokenData::into_raw).collect();
+    into_token_array(tokens, out_count)
 }
 
 /// Free a token
@@ -213,34 +789,38 @@ pub extern "C" fn sudachi_free_token(token: *mut SudachiToken) {
         return;
     }
 
+    // Inline strings go with the token
+    let strings = unsafe { handle::borrow(token) }.heap_strings();
     unsafe {
-        let token = Box::from_raw(token);
-        
-        if !token.surface.is_null() {
-            let _ = CString::from_raw(token.surface);
-        }
-        if !token.reading.is_null() {
-            let _ = CString::from_raw(token.reading);
-        }
-        if !token.dictionary_form.is_null() {
-            let _ = CString::from_raw(token.dictionary_form);
-        }
-        if !token.normalized_form.is_null() {
-            let _ = CString::from_raw(token.normalized_form);
-        }
-        if !token.pos.is_null() {
-            let _ = CString::from_raw(token.pos);
+        let _ = handle::from_raw(token);
+        for s in strings.into_iter().filter(|s| !s.is_null()) {
+            let _ = CString::from_raw(s);
         }
     }
 }
 
 /// Free array of tokens
+/// count is only a hint: the array's own length is freed. An array that was
+/// already freed, or not handed out by this library, is ignored.
 #[no_mangle]
 pub extern "C" fn sudachi_free_tokens(tokens: *mut *mut SudachiToken, count: usize) {
-    if tokens.is_null() {
+    if tokens.is_null() || tokens == ptr::NonNull::dangling().as_ptr() {
         return;
     }
+    if !token_arrays(tokens).remove(&(tokens as usize)) {
+        eprintln!("sudachi_free_tokens: unknown or already freed array");
+        return;
+    }
+    let len = unsafe { (*tokens.sub(1)).addr() };
+    if len != count {
+        eprintln!(
+            "sudachi_free_tokens: count {} for an array of {} tokens",
+            count, len
+        );
+    }
+    let count = len;
 
+    leak::release(tokens);
     unsafe {
         let tokens_slice = std::slice::from_raw_parts_mut(tokens, count);
         for token_ptr in tokens_slice.iter() {
@@ -248,7 +828,7 @@ pub extern "C" fn sudachi_free_tokens(tokens: *mut *mut SudachiToken, count: usi
                 sudachi_free_token(*token_ptr);
             }
         }
-        let _ = Box::from_raw(std::slice::from_raw_parts_mut(tokens, count));
+        let _ = Box::from_raw(ptr::slice_from_raw_parts_mut(tokens.sub(1), count + 1));
     }
 }
 
@@ -257,7 +837,18 @@ pub extern "C" fn sudachi_free_tokens(tokens: *mut *mut SudachiToken, count: usi
 pub extern "C" fn sudachi_free_tokenizer(tokenizer: *mut SudachiTokenizer) {
     if !tokenizer.is_null() {
         unsafe {
-            let _ = Box::from_raw(tokenizer);
+            drop(handle::from_raw(tokenizer));
+        }
+    }
+}
+
+/// Free a string returned by this library
+#[no_mangle]
+pub extern "C" fn sudachi_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        leak::release(s);
+        unsafe {
+            let _ = CString::from_raw(s);
         }
     }
 }
@@ -272,10 +863,115 @@ pub extern "C" fn sudachi_version() -> *const c_char {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lookup::sudachi_lookup;
+
+    #[test]
+    fn test_free_tokens_count() {
+        let raw = ["猫", "が", "いる"]
+            .iter()
+            .filter_map(|s| testtoken::word(0, s).into_raw())
+            .collect();
+        let mut count = 0;
+        let array = into_token_array(raw, &mut count);
+        assert_eq!(count, 3);
+        // The allocated length wins over the count given
+        sudachi_free_tokens(array, 1);
+        // A second free is ignored rather than reading freed memory
+        sudachi_free_tokens(array, 3);
+
+        // Arrays the library did not hand out are ignored
+        let mut foreign = [ptr::null_mut(); 2];
+        sudachi_free_tokens(foreign.as_mut_ptr(), 2);
+        let empty = into_token_array(Vec::new(), &mut count);
+        sudachi_free_tokens(empty, 5);
+    }
+
+    fn tokens<'a>(tokens: *mut *mut SudachiToken, count: usize) -> Vec<&'a SudachiToken> {
+        assert!(!tokens.is_null());
+        unsafe { std::slice::from_raw_parts(tokens, count) }
+            .iter()
+            .map(|&t| unsafe { &*t })
+            .collect()
+    }
 
     #[test]
     fn test_basic_ffi() {
-        // This would need a valid dictionary path to run
-        // Just ensure it compiles
+        assert!(!sudachi_version().is_null());
+
+        let tokenizer = testdict::tokenizer();
+        let text = CString::new("猫が食べた。").unwrap();
+        let mut count = 0;
+        let result = sudachi_tokenize(tokenizer, text.as_ptr(), SudachiTokenMode::C, &mut count);
+        let list = tokens(result, count);
+
+        let surfaces: Vec<_> = list.iter().map(|t| c_str(t.surface).unwrap()).collect();
+        assert_eq!(surfaces, ["猫", "が", "食べ", "た", "。"]);
+        assert_eq!(c_str(list[0].reading), Some("ネコ"));
+        assert_eq!(c_str(list[2].dictionary_form), Some("食べる"));
+        assert_eq!(
+            c_str(list[0].pos),
+            Some(r#"["名詞","普通名詞","一般","*","*","*"]"#)
+        );
+        for pair in list.windows(2) {
+            assert_eq!(pair[0].end, pair[1].begin);
+        }
+        assert_eq!(list.last().unwrap().end as usize, text.as_bytes().len());
+
+        sudachi_free_tokens(result, count);
+        sudachi_free_tokenizer(tokenizer);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_field_selection_and_lookup() {
+        let tokenizer = testdict::tokenizer();
+        let text = CString::new("日本語").unwrap();
+        let mut count = 0;
+        let result = sudachi_tokenize_fields(
+            tokenizer,
+            text.as_ptr(),
+            SudachiTokenMode::C,
+            SUDACHI_FIELD_SURFACE,
+            &mut count,
+        );
+        let list = tokens(result, count);
+        assert_eq!(list.len(), 1);
+        assert_eq!(c_str(list[0].surface), Some("日本語"));
+        assert!(list[0].reading.is_null() && list[0].pos.is_null());
+        sudachi_free_tokens(result, count);
+
+        let word = CString::new("日本").unwrap();
+        let result = sudachi_lookup(tokenizer, word.as_ptr(), &mut count);
+        let mut readings: Vec<_> = tokens(result, count)
+            .iter()
+            .map(|t| c_str(t.reading).unwrap())
+            .collect();
+        readings.sort_unstable();
+        assert_eq!(readings, ["ニッポン", "ニホン"]);
+        sudachi_free_tokens(result, count);
+
+        sudachi_free_tokenizer(tokenizer);
+    }
+
+    #[test]
+    fn test_null_arguments() {
+        let text = CString::new("猫").unwrap();
+        let mut count = 0;
+        let mode = SudachiTokenMode::C;
+        assert!(sudachi_init(ptr::null()).is_null());
+        assert!(sudachi_init_from_bytes(ptr::null(), 0).is_null());
+        let missing = CString::new("/nonexistent/system.dic").unwrap();
+        assert!(sudachi_init(missing.as_ptr()).is_null());
+        assert!(sudachi_tokenize(ptr::null_mut(), text.as_ptr(), mode, &mut count).is_null());
+
+        let tokenizer = testdict::tokenizer();
+        assert!(sudachi_tokenize(tokenizer, ptr::null(), mode, &mut count).is_null());
+        assert!(sudachi_tokenize(tokenizer, text.as_ptr(), mode, ptr::null_mut()).is_null());
+        sudachi_free_tokenizer(tokenizer);
+
+        // Freeing NULL is a no-op
+        sudachi_free_token(ptr::null_mut());
+        sudachi_free_tokens(ptr::null_mut(), 3);
+        sudachi_free_tokenizer(ptr::null_mut());
+        sudachi_free_string(ptr::null_mut());
+    }
+}
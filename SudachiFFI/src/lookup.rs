@@ -0,0 +1,147 @@
+// Dictionary lookup
+// Exact-match lexicon lookup of a word, independent of analysis context, for
+// popups and search where the user already selected the headword.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
+use sudachi::dic::subset::InfoSubset;
+use sudachi::dic::word_id::WordId;
+use sudachi::prelude::*;
+
+use crate::options::SudachiOptions;
+use crate::{
+    c_str, dictionary_reading, handle, into_token_array, lemma, script, symbol, SudachiToken,
+    SudachiTokenizer, TokenData, SUDACHI_FIELD_ALL, SUDACHI_FIELD_LEMMA_KEY,
+};
+
+/// Every lexicon entry whose surface is exactly word
+pub(crate) fn lookup<D: DictionaryAccess>(
+    dictionary: &D,
+    word: &str,
+) -> SudachiResult<Vec<TokenData>> {
+    let lexicon = dictionary.lexicon();
+    let mut entries = Vec::new();
+    for entry in lexicon.lookup(word.as_bytes(), 0) {
+        if entry.end != word.len() {
+            continue;
+        }
+        entries.push(entry_token(dictionary, entry.word_id, word)?);
+    }
+    Ok(entries)
+}
+
+/// Token for the lexicon entry word_id, with every field filled and begin
+/// and end spanning surface
+pub(crate) fn entry_token<D: DictionaryAccess>(
+    dictionary: &D,
+    word_id: WordId,
+    surface: &str,
+) -> SudachiResult<TokenData> {
+    let subset = InfoSubset::POS_ID
+        | InfoSubset::NORMALIZED_FORM
+        | InfoSubset::DIC_FORM_WORD_ID
+        | InfoSubset::READING_FORM;
+    let info = dictionary.lexicon().get_word_info_subset(word_id, subset)?;
+    let mut token = TokenData {
+        fields: SUDACHI_FIELD_ALL,
+        surface: surface.to_string(),
+        reading: info.reading_form().to_string(),
+        dictionary_form: info.dictionary_form().to_string(),
+        dictionary_reading: dictionary_reading(dictionary, word_id, &info),
+        normalized_form: info.normalized_form().to_string(),
+        pos: dictionary
+            .grammar()
+            .pos_list
+            .get(info.pos_id() as usize)
+            .cloned()
+            .unwrap_or_default(),
+        begin: 0,
+        end: surface.len(),
+        word_id: word_id.as_raw(),
+        pos_id: info.pos_id(),
+        script: script::token_script(surface),
+        class: symbol::token_class(surface),
+        flags: 0,
+        lemma_key: 0,
+    };
+    lemma::assign(std::slice::from_mut(&mut token));
+    Ok(token)
+}
+
+/// Look up the dictionary entries of a word
+/// Only exact surface matches are returned (an empty array if none); begin
+/// and end span the whole word.
+/// Returns NULL on failure (caller must free with sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_lookup(
+    tokenizer: *mut SudachiTokenizer,
+    word: *const c_char,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let word = match c_str(word) {
+        Some(w) => w,
+        None => return ptr::null_mut(),
+    };
+
+    let entries = match lookup(&tokenizer.dictionary, word) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Lookup failed: {:?}", e);
+            return ptr::null_mut();
+        }
+    };
+    let tokens = entries
+        .into_iter()
+        .filter_map(TokenData::into_raw)
+        .collect();
+    into_token_array(tokens, out_count)
+}
+
+/// Look up the dictionary entries of a word with per-call options
+/// options may be NULL for the defaults (see SudachiOptions). Only the
+/// field mask, filters and limits apply; entries don't depend on mode or
+/// normalization.
+/// Returns NULL on failure or if word is longer than options->max_bytes
+/// (caller must free with sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_lookup_options(
+    tokenizer: *mut SudachiTokenizer,
+    word: *const c_char,
+    options: *const SudachiOptions,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let Some(word) = c_str(word) else {
+        return ptr::null_mut();
+    };
+    let options = SudachiOptions::read(options);
+    if !options.fits(word) {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Ok(entries) = lookup(&tokenizer.dictionary, word) else {
+        return ptr::null_mut();
+    };
+
+    let fields = options.fields(SUDACHI_FIELD_ALL);
+    let tokens = options
+        .filter(entries)
+        .into_iter()
+        .filter_map(|mut token| {
+            if fields & SUDACHI_FIELD_LEMMA_KEY == 0 {
+                token.lemma_key = 0;
+            }
+            token.fields = fields;
+            token.into_raw()
+        })
+        .collect();
+    into_token_array(tokens, out_count)
+}
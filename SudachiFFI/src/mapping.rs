@@ -0,0 +1,350 @@
+// Mapping tuning
+// First lookups on older devices stall on page faults in the mapped
+// dictionary: readahead pulls in neighbours of every trie node, and the
+// connection matrix, which every analysis reads all over, is faulted in one
+// page at a time. The dictionary image is split into its regions (connection
+// matrix, trie, entry tables, word infos) and each region gets its own
+// madvise advice; the matrix can also be locked in memory. Both are Unix
+// only; other targets ignore them (the "mapping" feature reports false).
+//
+// System dictionary layout (all integers little-endian):
+//   header:   u64 version | u64 build time | 256-byte description
+//   grammar:  u16 POS count | POS count x 6 strings
+//             i16 left size | i16 right size | left x right i16 costs
+//   lexicon:  u32 trie units | trie units x u32
+//             u32 word id table bytes | table
+//             u32 word count | word count x 3 i16 params
+//             word infos (rest of the image)
+// Strings are a u8 length (two bytes if the high bit is set) followed by
+// that many UTF-16 code units.
+//
+// Building the dictionary (grammar parse, lexicon setup, plugins) is a single
+// call into Sudachi and stays on the calling thread. What init spends on
+// I/O can be spread over threads: dictionary files are opened side by side,
+// and reads for SUDACHI_INIT_COPY_TO_RAM are split into chunks. Mapped
+// images are not touched ahead of Sudachi, so only the pages it reads
+// become resident.
+
+use std::io;
+use std::ops::Range;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr;
+
+use memmap2::Mmap;
+
+use crate::sections::{sections, SUDACHI_INIT_RANDOM_ACCESS};
+use crate::{c_str, handle, load_dictionaries, SudachiTokenizer};
+
+/// Access pattern hint for a region of the dictionary map
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SudachiAdvice {
+    /// Leave the kernel default
+    #[default]
+    Normal = 0,
+    /// Fault in only the pages read, with no readahead
+    Random = 1,
+    /// Read ahead aggressively
+    Sequential = 2,
+    /// Start reading the whole region in now
+    WillNeed = 3,
+}
+
+/// Options for sudachi_init_with_options
+/// All zero is the same as sudachi_init.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SudachiInitOptions {
+    /// SUDACHI_INIT_* flags
+    pub flags: u32,
+    /// Advice for the connection matrix
+    pub matrix: SudachiAdvice,
+    /// Advice for the trie
+    pub trie: SudachiAdvice,
+    /// Advice for the word id table and word parameters
+    pub entries: SudachiAdvice,
+    /// Advice for the word infos (forms, readings, splits)
+    pub word_infos: SudachiAdvice,
+    /// Lock the connection matrix in memory
+    pub lock_matrix: bool,
+    /// Threads to load the dictionary files with (0 or 1 loads on the
+    /// calling thread)
+    pub threads: u32,
+}
+
+/// Address range of a memory-mapped system dictionary
+/// Empty for dictionaries read into memory. Stays valid as long as the
+/// dictionary built from the map is alive.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Image {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) addr: usize,
+    pub(crate) len: usize,
+}
+
+/// Byte ranges of the regions of a dictionary image
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Debug, PartialEq)]
+struct Regions {
+    matrix: Range<usize>,
+    trie: Range<usize>,
+    entries: Range<usize>,
+    word_infos: Range<usize>,
+    /// Entries of the lexicon (word ids 0..words)
+    words: usize,
+}
+
+const HEADER_LEN: usize = 8 + 8 + 256;
+
+fn read_u16(image: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(image.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(image: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(image.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Offset just past the string at at
+fn skip_string(image: &[u8], at: usize) -> Option<usize> {
+    let first = *image.get(at)? as usize;
+    let (units, start) = if first & 0x80 != 0 {
+        (((first & 0x7f) << 8) | *image.get(at + 1)? as usize, at + 2)
+    } else {
+        (first, at + 1)
+    };
+    Some(start + units * 2)
+}
+
+/// Find the regions of a dictionary image, None if it is malformed
+fn regions(image: &[u8]) -> Option<Regions> {
+    let mut at = HEADER_LEN;
+    let pos_count = read_u16(image, at)?;
+    at += 2;
+    for _ in 0..pos_count as usize * 6 {
+        at = skip_string(image, at)?;
+    }
+    let left = read_u16(image, at)? as usize;
+    let right = read_u16(image, at + 2)? as usize;
+    let matrix = at + 4..at + 4 + left * right * 2;
+
+    let trie_units = read_u32(image, matrix.end)? as usize;
+    let trie = matrix.end + 4..matrix.end + 4 + trie_units * 4;
+    let table = read_u32(image, trie.end)? as usize;
+    let params = trie.end + 4 + table;
+    let words = read_u32(image, params)? as usize;
+    let entries = trie.end..params + 4 + words * 6;
+    if entries.end > image.len() {
+        return None;
+    }
+    Some(Regions {
+        matrix,
+        word_infos: entries.end..image.len(),
+        trie,
+        entries,
+        words,
+    })
+}
+
+/// Number of entries in the lexicon of a system dictionary image, None if
+/// it is malformed
+pub(crate) fn word_count(image: &[u8]) -> Option<usize> {
+    regions(image).map(|r| r.words)
+}
+
+#[cfg(unix)]
+fn madvice(advice: SudachiAdvice) -> Option<memmap2::Advice> {
+    match advice {
+        SudachiAdvice::Normal => None,
+        SudachiAdvice::Random => Some(memmap2::Advice::Random),
+        SudachiAdvice::Sequential => Some(memmap2::Advice::Sequential),
+        SudachiAdvice::WillNeed => Some(memmap2::Advice::WillNeed),
+    }
+}
+
+/// Apply the mapping options to a dictionary map
+/// Failures only cost performance, so they are logged and ignored.
+#[cfg(unix)]
+pub(crate) fn advise(mapping: &Mmap, options: &SudachiInitOptions) {
+    if options.flags & SUDACHI_INIT_RANDOM_ACCESS != 0 {
+        if let Err(e) = mapping.advise(memmap2::Advice::Random) {
+            eprintln!("Failed to advise dictionary mapping: {}", e);
+        }
+    }
+    let Some(regions) = regions(mapping) else {
+        return;
+    };
+    for (range, region) in [
+        (&regions.matrix, options.matrix),
+        (&regions.trie, options.trie),
+        (&regions.entries, options.entries),
+        (&regions.word_infos, options.word_infos),
+    ] {
+        let Some(advice) = madvice(region) else {
+            continue;
+        };
+        if range.is_empty() {
+            continue;
+        }
+        if let Err(e) = mapping.advise_range(advice, range.start, range.len()) {
+            eprintln!("Failed to advise dictionary region: {}", e);
+        }
+    }
+    if options.lock_matrix && !regions.matrix.is_empty() {
+        let start = unsafe { mapping.as_ptr().add(regions.matrix.start) };
+        if unsafe { libc::mlock(start as *const libc::c_void, regions.matrix.len()) } != 0 {
+            eprintln!(
+                "Failed to lock connection matrix: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// Mapping advice and locking need madvise and mlock; elsewhere a request
+/// for either is logged as unsupported and the map is left as is.
+#[cfg(not(unix))]
+pub(crate) fn advise(_mapping: &Mmap, options: &SudachiInitOptions) {
+    let advised = options.flags & SUDACHI_INIT_RANDOM_ACCESS != 0
+        || [
+            options.matrix,
+            options.trie,
+            options.entries,
+            options.word_infos,
+        ]
+        .iter()
+        .any(|&a| a != SudachiAdvice::Normal);
+    if advised || options.lock_matrix {
+        eprintln!("Dictionary mapping advice is unsupported on this platform");
+    }
+}
+
+/// Split len bytes into at most threads page-aligned chunks
+#[cfg_attr(not(unix), allow(dead_code))]
+fn chunks(len: usize, threads: u32) -> Vec<Range<usize>> {
+    const PAGE: usize = 4096;
+    let pages = len.div_ceil(PAGE);
+    let per = pages.div_ceil(threads.max(1) as usize).max(1) * PAGE;
+    (0..len)
+        .step_by(per)
+        .map(|start| start..len.min(start + per))
+        .collect()
+}
+
+/// Read a whole file, in parallel chunks when threads is above 1
+#[cfg(unix)]
+pub(crate) fn read(path: &Path, threads: u32) -> io::Result<Vec<u8>> {
+    use std::os::unix::fs::FileExt;
+
+    if threads <= 1 {
+        return std::fs::read(path);
+    }
+    let file = std::fs::File::open(path)?;
+    let mut bytes = vec![0u8; file.metadata()?.len() as usize];
+    let ranges = chunks(bytes.len(), threads);
+    std::thread::scope(|scope| {
+        let mut rest = bytes.as_mut_slice();
+        let mut workers = Vec::with_capacity(ranges.len());
+        for range in &ranges {
+            let (chunk, tail) = rest.split_at_mut(range.len());
+            rest = tail;
+            let file = &file;
+            let offset = range.start as u64;
+            workers.push(scope.spawn(move || file.read_exact_at(chunk, offset)));
+        }
+        workers.into_iter().try_for_each(|w| {
+            w.join()
+                .unwrap_or_else(|_| Err(io::ErrorKind::Other.into()))
+        })
+    })?;
+    Ok(bytes)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn read(path: &Path, _threads: u32) -> io::Result<Vec<u8>> {
+    std::fs::read(path)
+}
+
+/// Initialize Sudachi tokenizer with mapping options
+/// options may be NULL for defaults.
+/// Returns NULL on failure
+#[no_mangle]
+pub extern "C" fn sudachi_init_with_options(
+    dict_path: *const c_char,
+    options: *const SudachiInitOptions,
+) -> *mut SudachiTokenizer {
+    let Some(path) = c_str(dict_path) else {
+        return ptr::null_mut();
+    };
+    let options = if options.is_null() {
+        SudachiInitOptions::default()
+    } else {
+        unsafe { *options }
+    };
+    let loaded = match load_dictionaries(path, &[], &options) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    let tokenizer = SudachiTokenizer::new(loaded);
+    tokenizer.set_options(|o| o.sections = sections(options.flags));
+    handle::into_raw(tokenizer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(image: &mut Vec<u8>, s: &str) {
+        let units: Vec<u16> = s.encode_utf16().collect();
+        image.push(units.len() as u8);
+        units
+            .iter()
+            .for_each(|u| image.extend_from_slice(&u.to_le_bytes()));
+    }
+
+    #[test]
+    fn test_regions() {
+        let mut image = vec![0u8; HEADER_LEN];
+        image.extend_from_slice(&1u16.to_le_bytes());
+        for s in ["名詞", "普通名詞", "一般", "*", "*", "*"] {
+            string(&mut image, s);
+        }
+        let matrix_at = image.len() + 4;
+        image.extend_from_slice(&2u16.to_le_bytes());
+        image.extend_from_slice(&3u16.to_le_bytes());
+        image.extend_from_slice(&[0; 12]);
+        let trie_at = image.len();
+        image.extend_from_slice(&2u32.to_le_bytes());
+        image.extend_from_slice(&[0; 8]);
+        let entries_at = image.len();
+        image.extend_from_slice(&5u32.to_le_bytes());
+        image.extend_from_slice(&[0; 5]);
+        image.extend_from_slice(&1u32.to_le_bytes());
+        image.extend_from_slice(&[0; 6]);
+        let infos_at = image.len();
+        image.extend_from_slice(&[0; 10]);
+
+        let found = regions(&image).unwrap();
+        assert_eq!(found.matrix, matrix_at..matrix_at + 12);
+        assert_eq!(found.trie, trie_at + 4..trie_at + 12);
+        assert_eq!(found.entries, entries_at..infos_at);
+        assert_eq!(found.word_infos, infos_at..image.len());
+        assert_eq!(word_count(&image), Some(1));
+
+        assert!(regions(&image[..infos_at - 1]).is_none());
+        assert!(regions(&[]).is_none());
+    }
+
+    #[test]
+    fn test_chunks() {
+        assert_eq!(chunks(10_000, 1), vec![0..10_000]);
+        assert_eq!(chunks(10_000, 2), vec![0..8192, 8192..10_000]);
+        assert_eq!(chunks(10_000, 8), vec![0..4096, 4096..8192, 8192..10_000]);
+        assert!(chunks(0, 4).is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+// Personal name readings
+// Character names are mostly unknown to Sudachi, or tagged 人名 with a single
+// reading that is often wrong for the character. A name table built by the
+// caller from JMnedict (full names) and KANJIDIC nanori (single kanji), with
+// frequency weights, lets the reading be guessed: the name is split into
+// table entries and kana, each piece contributes the share of its weight
+// among the readings of that spelling, and extra pieces and rendaku (島 ->
+// じま after the first piece) cost a fixed factor. The best candidates are
+// kept in a small beam at every character.
+//
+// Table format (UTF-8, one entry per line, # starts a comment):
+//   surface <TAB> reading <TAB> weight
+// The weight is optional (1 by default); readings may be hiragana or katakana.
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::kana::{hiragana_to_katakana, is_kana, to_katakana, voiced};
+use crate::{c_str, into_c_string, leak, SudachiToken, SUDACHI_TOKEN_OOV};
+
+/// Candidates kept per character position
+const BEAM: usize = 8;
+/// Factor for every piece after the first
+const SEGMENT_PENALTY: f32 = 0.1;
+/// Factor for a voiced (rendaku) reading of a non-initial piece
+const RENDAKU_PENALTY: f32 = 0.5;
+
+/// Name table loaded with sudachi_names_open
+pub struct SudachiNames {
+    /// Readings of each spelling with their share of its weight
+    entries: HashMap<String, Vec<(String, f32)>>,
+    /// Longest spelling, in characters
+    max_chars: usize,
+}
+
+impl SudachiNames {
+    fn parse(table: &str) -> SudachiNames {
+        let mut weights: HashMap<String, Vec<(String, f32)>> = HashMap::new();
+        for line in table.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut columns = line.split('\t');
+            let (Some(surface), Some(reading)) = (columns.next(), columns.next()) else {
+                continue;
+            };
+            let weight = columns.next().and_then(|w| w.parse().ok()).unwrap_or(1.0);
+            if surface.is_empty() || reading.is_empty() || weight <= 0.0 {
+                continue;
+            }
+            let reading = hiragana_to_katakana(reading);
+            weights
+                .entry(surface.to_string())
+                .or_default()
+                .push((reading, weight));
+        }
+
+        let mut max_chars = 0;
+        for (surface, readings) in weights.iter_mut() {
+            let total: f32 = readings.iter().map(|r| r.1).sum();
+            for reading in readings.iter_mut() {
+                reading.1 /= total;
+            }
+            max_chars = max_chars.max(surface.chars().count());
+        }
+        SudachiNames {
+            entries: weights,
+            max_chars,
+        }
+    }
+
+    /// Candidate readings of a name with their log scores, best first
+    fn guess(&self, name: &str) -> Vec<(String, f32)> {
+        let bounds: Vec<usize> = name
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(name.len()))
+            .collect();
+        let n = bounds.len() - 1;
+        // (reading, log score, pieces) of the best readings of name[..i]
+        let mut beams: Vec<Vec<(String, f32, usize)>> = vec![Vec::new(); n + 1];
+        beams[0].push((String::new(), 0.0, 0));
+
+        for i in 0..n {
+            prune(&mut beams[i]);
+            if beams[i].is_empty() {
+                continue;
+            }
+
+            let mut pieces: Vec<(usize, String, f32)> = Vec::new();
+            for j in i + 1..=n.min(i + self.max_chars) {
+                let Some(readings) = self.entries.get(&name[bounds[i]..bounds[j]]) else {
+                    continue;
+                };
+                for (reading, share) in readings {
+                    pieces.push((j, reading.clone(), share.ln()));
+                    let rendaku = rendaku(reading);
+                    if i > 0 && rendaku != *reading {
+                        pieces.push((j, rendaku, (share * RENDAKU_PENALTY).ln()));
+                    }
+                }
+            }
+            let c = name[bounds[i]..].chars().next().unwrap_or_default();
+            if is_kana(c) {
+                pieces.push((i + 1, to_katakana(c).to_string(), 0.0));
+            }
+
+            let prefixes = beams[i].clone();
+            for (prefix, score, count) in prefixes {
+                let penalty = if count > 0 { SEGMENT_PENALTY.ln() } else { 0.0 };
+                for (j, reading, piece) in &pieces {
+                    beams[*j].push((prefix.clone() + reading, score + piece + penalty, count + 1));
+                }
+            }
+        }
+
+        let mut candidates = std::mem::take(&mut beams[n]);
+        prune(&mut candidates);
+        candidates.into_iter().map(|(r, s, _)| (r, s)).collect()
+    }
+}
+
+/// Reading with its first kana voiced
+fn rendaku(reading: &str) -> String {
+    let mut chars = reading.chars();
+    chars
+        .next()
+        .map(|c| format!("{}{}", voiced(c), chars.as_str()))
+        .unwrap_or_default()
+}
+
+/// Sort candidates best first, drop duplicate readings and keep the beam
+fn prune(candidates: &mut Vec<(String, f32, usize)>) {
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut seen = std::collections::HashSet::new();
+    candidates.retain(|c| seen.insert(c.0.clone()));
+    candidates.truncate(BEAM);
+}
+
+/// Candidate reading of a name
+/// reading is owned by the result array.
+#[repr(C)]
+pub struct SudachiNameReading {
+    /// Reading in katakana
+    pub reading: *mut c_char,
+    /// Share of the returned candidates (0 to 1), highest first
+    pub score: f32,
+}
+
+/// Load a name table (see README for the format)
+/// Returns NULL on failure (caller must free with sudachi_names_close)
+#[no_mangle]
+pub extern "C" fn sudachi_names_open(path: *const c_char) -> *mut SudachiNames {
+    let path = match c_str(path) {
+        Some(p) => p,
+        None => return ptr::null_mut(),
+    };
+
+    match std::fs::read_to_string(path) {
+        Ok(table) => {
+            let names = Box::into_raw(Box::new(SudachiNames::parse(&table)));
+            leak::track(names, "SudachiNames");
+            names
+        }
+        Err(e) => {
+            eprintln!("Failed to open name table {}: {}", path, e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a name table
+#[no_mangle]
+pub extern "C" fn sudachi_names_close(names: *mut SudachiNames) {
+    if !names.is_null() {
+        leak::release(names);
+        unsafe {
+            let _ = Box::from_raw(names);
+        }
+    }
+}
+
+/// Whether a token may be a personal name: unknown, or tagged 人名
+/// (the latter needs SUDACHI_FIELD_POS)
+fn is_name_candidate(token: &SudachiToken) -> bool {
+    token.flags & SUDACHI_TOKEN_OOV != 0
+        || (!token.pos.is_null()
+            && unsafe { CStr::from_ptr(token.pos) }
+                .to_str()
+                .is_ok_and(|pos| pos.contains("人名")))
+}
+
+/// Guess up to max readings of a token that may be a personal name
+/// Only tokens flagged SUDACHI_TOKEN_OOV or tagged 人名 are guessed; the
+/// dictionary reading of a 人名 token is one of the candidates.
+/// Returns NULL if the token is not a name candidate or no reading can be
+/// built (caller must free with sudachi_free_name_readings)
+#[no_mangle]
+pub extern "C" fn sudachi_guess_name_readings(
+    names: *const SudachiNames,
+    token: *const SudachiToken,
+    max: usize,
+    out_count: *mut usize,
+) -> *mut SudachiNameReading {
+    if names.is_null() || token.is_null() || out_count.is_null() || max == 0 {
+        return ptr::null_mut();
+    }
+    let names = unsafe { &*names };
+    let token = unsafe { crate::handle::borrow(token) };
+    if !is_name_candidate(token) || token.surface.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(surface) = unsafe { CStr::from_ptr(token.surface) }.to_str() else {
+        return ptr::null_mut();
+    };
+
+    let mut candidates = names.guess(surface);
+    if token.flags & SUDACHI_TOKEN_OOV == 0 && !token.reading.is_null() {
+        if let Ok(reading) = unsafe { CStr::from_ptr(token.reading) }.to_str() {
+            // The dictionary reading counts as one more exact entry
+            if !candidates.iter().any(|c| c.0 == reading) {
+                candidates.push((reading.to_string(), RENDAKU_PENALTY.ln()));
+                candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+            }
+        }
+    }
+    candidates.truncate(max);
+    if candidates.is_empty() {
+        return ptr::null_mut();
+    }
+
+    let total: f32 = candidates.iter().map(|c| c.1.exp()).sum();
+    let readings: Vec<SudachiNameReading> = candidates
+        .into_iter()
+        .map(|(reading, score)| SudachiNameReading {
+            reading: into_c_string(&reading),
+            score: score.exp() / total,
+        })
+        .collect();
+    unsafe {
+        *out_count = readings.len();
+    }
+    let readings = Box::leak(readings.into_boxed_slice()).as_mut_ptr();
+    leak::track(readings, "name readings");
+    readings
+}
+
+/// Free the result of sudachi_guess_name_readings
+#[no_mangle]
+pub extern "C" fn sudachi_free_name_readings(readings: *mut SudachiNameReading, count: usize) {
+    if readings.is_null() {
+        return;
+    }
+
+    leak::release(readings);
+    unsafe {
+        let readings = Box::from_raw(ptr::slice_from_raw_parts_mut(readings, count));
+        for reading in readings.iter() {
+            if !reading.reading.is_null() {
+                let _ = CString::from_raw(reading.reading);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "# surface\treading\tweight
+田中\tたなか\t100
+田中\tでんちゅう\t1
+翔\tしょう\t5
+翔\tかける\t3
+太\tた\t2
+太\tふと\t0.5
+郎\tろう
+中\tなか\t5
+島\tしま\t5
+子\tこ\t4
+";
+
+    fn best(names: &SudachiNames, name: &str) -> Vec<String> {
+        names.guess(name).into_iter().map(|c| c.0).take(3).collect()
+    }
+
+    #[test]
+    fn test_guess() {
+        let names = SudachiNames::parse(TABLE);
+        assert_eq!(best(&names, "田中"), ["タナカ", "デンチュウ"]);
+        assert_eq!(
+            best(&names, "翔太郎"),
+            ["ショウタロウ", "カケルタロウ", "ショウダロウ"]
+        );
+        assert_eq!(best(&names, "中島")[..2], ["ナカシマ", "ナカジマ"]);
+        assert_eq!(best(&names, "さくら子"), ["サクラコ", "サクラゴ"]);
+        assert!(names.guess("山").is_empty());
+    }
+}
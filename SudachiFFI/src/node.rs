@@ -0,0 +1,135 @@
+// Node bindings (napi feature)
+// Used by the content-preparation pipeline to pre-analyze chapters server
+// side with the same engine as the app. Results are plain JS objects.
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use sudachi::prelude::Mode;
+
+use crate::analyzer::{Analyzer, Dictionary};
+use crate::furigana::{furigana, Ruby as RubyData, FURIGANA_FIELDS};
+use crate::{load_dictionary, lookup, TokenData, SUDACHI_FIELD_ALL};
+
+/// Split mode: A is shortest units, C is longest (named entities)
+#[napi]
+pub enum TokenMode {
+    A,
+    B,
+    C,
+}
+
+impl From<TokenMode> for Mode {
+    fn from(mode: TokenMode) -> Self {
+        match mode {
+            TokenMode::A => Mode::A,
+            TokenMode::B => Mode::B,
+            TokenMode::C => Mode::C,
+        }
+    }
+}
+
+/// One morpheme; begin/end are UTF-8 byte offsets as in the C API
+#[napi(object)]
+pub struct Token {
+    pub surface: String,
+    pub reading: String,
+    pub dictionary_form: String,
+    pub dictionary_reading: String,
+    pub normalized_form: String,
+    pub pos: Vec<String>,
+    pub begin: u32,
+    pub end: u32,
+    pub word_id: u32,
+    pub pos_id: u32,
+    /// Stable identifier of the lemma, 0n for foreign runs
+    pub lemma_key: BigInt,
+}
+
+impl From<TokenData> for Token {
+    fn from(t: TokenData) -> Self {
+        Token {
+            surface: t.surface,
+            reading: t.reading,
+            dictionary_form: t.dictionary_form,
+            dictionary_reading: t.dictionary_reading,
+            normalized_form: t.normalized_form,
+            pos: t.pos,
+            begin: t.begin as u32,
+            end: t.end as u32,
+            word_id: t.word_id,
+            pos_id: t.pos_id as u32,
+            lemma_key: BigInt::from(t.lemma_key),
+        }
+    }
+}
+
+/// Furigana segment; reading is undefined where no ruby is needed
+#[napi(object)]
+pub struct Ruby {
+    pub text: String,
+    pub reading: Option<String>,
+    pub begin: u32,
+    pub end: u32,
+}
+
+impl From<RubyData> for Ruby {
+    fn from(r: RubyData) -> Self {
+        Ruby {
+            text: r.text,
+            reading: r.reading,
+            begin: r.begin as u32,
+            end: r.end as u32,
+        }
+    }
+}
+
+fn analysis_error(e: sudachi::prelude::SudachiError) -> Error {
+    Error::new(Status::GenericFailure, e.to_string())
+}
+
+#[napi]
+pub struct Tokenizer {
+    dictionary: Dictionary,
+    analyzer: Analyzer,
+}
+
+#[napi]
+impl Tokenizer {
+    /// Open a system dictionary file
+    #[napi(constructor)]
+    pub fn new(path: String) -> Result<Self> {
+        let dictionary = load_dictionary(&path)
+            .map_err(|e| Error::new(Status::InvalidArg, e))?
+            .dictionary;
+        Ok(Tokenizer {
+            analyzer: Analyzer::new(dictionary.clone()),
+            dictionary,
+        })
+    }
+
+    #[napi]
+    pub fn tokenize(&mut self, text: String, mode: TokenMode) -> Result<Vec<Token>> {
+        let tokens = self
+            .analyzer
+            .analyze(&text, mode.into(), SUDACHI_FIELD_ALL)
+            .map_err(analysis_error)?;
+        Ok(tokens.into_iter().map(Token::from).collect())
+    }
+
+    /// Dictionary entries whose surface is exactly word
+    #[napi]
+    pub fn lookup(&self, word: String) -> Result<Vec<Token>> {
+        let entries = lookup::lookup(&self.dictionary, &word).map_err(analysis_error)?;
+        Ok(entries.into_iter().map(Token::from).collect())
+    }
+
+    /// Furigana segments covering the whole text
+    #[napi]
+    pub fn furigana(&mut self, text: String, mode: TokenMode) -> Result<Vec<Ruby>> {
+        let tokens = self
+            .analyzer
+            .analyze(&text, mode.into(), FURIGANA_FIELDS)
+            .map_err(analysis_error)?;
+        Ok(furigana(&tokens).into_iter().map(Ruby::from).collect())
+    }
+}
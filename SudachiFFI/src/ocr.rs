@@ -0,0 +1,312 @@
+// OCR post-correction
+// Scanned manga OCR confuses characters that look alike: the prolonged sound
+// mark ー and 一 (one), katakana カ and 力 (power), ロ and 口 (mouth), and
+// big and small つ/っ (ツ/ッ). One wrong character splits a word into garbage
+// tokens (ス一パ一, 協カ, 行つた). When enabled on a handle, every character
+// of a confusable pair whose neighbors make the other one plausible is
+// checked against the dictionary: the text around it is analyzed with and
+// without the swap, and the swap is kept only if the new character then sits
+// inside a dictionary word and the window has fewer unknown words or a
+// clearly cheaper path. Checks run left to right, so later ones see earlier
+// corrections.
+//
+// Both characters of every pair are three bytes in UTF-8, so a correction
+// never moves an offset.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use sudachi::dic::subset::InfoSubset;
+use sudachi::prelude::*;
+
+use crate::analyzer::Analyzer;
+use crate::kana::{is_kana, is_kanji, is_katakana, to_hiragana};
+use crate::{
+    c_str, handle, into_c_string, leak, width, SudachiTokenizer, TokenData, SUDACHI_FIELD_SURFACE,
+    SUDACHI_TOKEN_OCR,
+};
+
+/// Look-alike pairs, kana first
+const LOOKALIKES: [(char, char); 3] = [('ー', '一'), ('カ', '力'), ('ロ', '口')];
+/// Big and small forms of the same kana
+const SIZES: [(char, char); 2] = [('つ', 'っ'), ('ツ', 'ッ')];
+/// Characters analyzed on each side of a candidate
+const WINDOW: usize = 6;
+/// Path cost a swap must save when unknown words don't decide
+const COST_MARGIN: i32 = 1500;
+
+/// A character replaced by its look-alike
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Correction {
+    /// Byte offset in the text
+    pub(crate) offset: usize,
+    pub(crate) corrected: char,
+}
+
+/// Kana that a small っ can double (k, s, t and p rows, and the voiced rows
+/// that loanwords double)
+fn geminable(c: char) -> bool {
+    "かきくけこがぎぐげごさしすせそざじずぜぞたちつてとだぢづでどはひふへほばびぶべぼぱぴぷぺぽ"
+        .contains(to_hiragana(c))
+}
+
+/// The look-alike worth trying for c between prev and next, if any
+fn candidate(prev: Option<char>, c: char, next: Option<char>) -> Option<char> {
+    let katakana = |c: Option<char>| c.is_some_and(|c| is_katakana(c) || c == 'ー');
+    let kanji = |c: Option<char>| c.is_some_and(is_kanji);
+    let by_katakana = katakana(prev) || katakana(next);
+    let by_kanji = kanji(prev) || kanji(next);
+    for (kana, ideograph) in LOOKALIKES {
+        if c == kana && by_kanji && !by_katakana {
+            return Some(ideograph);
+        }
+        if c == ideograph && by_katakana && !by_kanji {
+            return Some(kana);
+        }
+    }
+    let after_word = prev.is_some_and(|p| is_kana(p) || is_kanji(p));
+    for (big, small) in SIZES {
+        // 行つた -> 行った
+        if c == big && after_word && next.is_some_and(geminable) {
+            return Some(small);
+        }
+        // A small っ can't double a vowel
+        if c == small && after_word && next.is_some_and(|n| is_kana(n) && !geminable(n)) {
+            return Some(big);
+        }
+    }
+    None
+}
+
+/// Unknown words and path cost of text, and whether the character at offset
+/// is inside a dictionary word longer than itself
+fn score(
+    analyzer: &mut Analyzer,
+    text: &str,
+    offset: usize,
+    len: usize,
+) -> Option<(usize, i32, bool)> {
+    analyzer.run(text, Mode::C, InfoSubset::empty()).ok()?;
+    let morphemes = analyzer.morphemes();
+    let oov = morphemes.iter().filter(|m| m.is_oov()).count();
+    let word = morphemes.iter().any(|m| {
+        m.begin() <= offset && offset + len <= m.end() && m.end() - m.begin() > len && !m.is_oov()
+    });
+    Some((oov, morphemes.get_internal_cost(), word))
+}
+
+/// Correct the OCR confusions in text
+/// Returns the corrected text and the corrections, in text order.
+pub(crate) fn correct(analyzer: &mut Analyzer, text: &str) -> (String, Vec<Correction>) {
+    let offsets: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    let mut chars: Vec<char> = text.chars().collect();
+    let mut corrections = Vec::new();
+    for i in 0..chars.len() {
+        let c = chars[i];
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        let Some(swap) = candidate(prev, c, chars.get(i + 1).copied()) else {
+            continue;
+        };
+        let mut lo = i;
+        while lo > 0 && i - lo < WINDOW && chars[lo - 1] != '\n' {
+            lo -= 1;
+        }
+        let mut hi = i + 1;
+        while hi < chars.len() && hi - i <= WINDOW && chars[hi] != '\n' {
+            hi += 1;
+        }
+        let original: String = chars[lo..hi].iter().collect();
+        chars[i] = swap;
+        let swapped: String = chars[lo..hi].iter().collect();
+        chars[i] = c;
+
+        let at = offsets[i] - offsets[lo];
+        let before = score(analyzer, &original, at, c.len_utf8());
+        let after = score(analyzer, &swapped, at, swap.len_utf8());
+        let better = match (before, after) {
+            (Some((oov, cost, _)), Some((swap_oov, swap_cost, true))) => {
+                swap_oov < oov || (swap_oov == oov && swap_cost + COST_MARGIN < cost)
+            }
+            _ => false,
+        };
+        if better {
+            chars[i] = swap;
+            corrections.push(Correction {
+                offset: offsets[i],
+                corrected: swap,
+            });
+        }
+    }
+    (chars.into_iter().collect(), corrections)
+}
+
+/// Point tokens of the corrected text back at the original
+/// Surfaces are taken from the original text and tokens covering a
+/// correction are flagged.
+pub(crate) fn restore(
+    original: &str,
+    corrections: &[Correction],
+    tokens: Vec<TokenData>,
+) -> Vec<TokenData> {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            let first = corrections.partition_point(|c| c.offset < token.begin);
+            if corrections.get(first).is_some_and(|c| c.offset < token.end) {
+                token.flags |= SUDACHI_TOKEN_OCR;
+                if token.fields & SUDACHI_FIELD_SURFACE != 0 {
+                    token.surface = original[token.begin..token.end].to_string();
+                }
+            }
+            token
+        })
+        .collect()
+}
+
+/// Enable or disable OCR post-correction on a handle
+/// Applied after width normalization. Token offsets and surfaces still refer
+/// to the original text; tokens covering a correction have
+/// SUDACHI_TOKEN_OCR set. Disabled by default. Returns false if tokenizer is
+/// NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_ocr_correction(
+    tokenizer: *mut SudachiTokenizer,
+    enabled: bool,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.ocr = enabled);
+    true
+}
+
+/// One OCR correction
+/// original and corrected are owned by the result array.
+#[repr(C)]
+pub struct SudachiOcrCorrection {
+    /// Byte offsets of the character in the input
+    pub begin: i32,
+    pub end: i32,
+    pub original: *mut c_char,
+    pub corrected: *mut c_char,
+}
+
+/// Find the OCR confusions in text, whether or not correction is enabled
+/// on the handle (the handle's width normalization is applied first)
+/// Returns NULL on failure (caller must free with
+/// sudachi_free_ocr_corrections)
+#[no_mangle]
+pub extern "C" fn sudachi_ocr_corrections(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    out_count: *mut usize,
+) -> *mut SudachiOcrCorrection {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let Some(text) = c_str(text) else {
+        return ptr::null_mut();
+    };
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let corrections: Vec<SudachiOcrCorrection> = tokenizer.with_analyzer(|analyzer| {
+        let normalized = width::normalize(text, analyzer.options.normalization);
+        let input = normalized.as_ref().map_or(text, |n| n.text.as_str());
+        let source = |offset: usize| normalized.as_ref().map_or(offset, |n| n.source(offset));
+        let source_end =
+            |offset: usize| normalized.as_ref().map_or(offset, |n| n.source_end(offset));
+        correct(analyzer, input)
+            .1
+            .into_iter()
+            .map(|c| {
+                let begin = source(c.offset);
+                let end = source_end(c.offset + c.corrected.len_utf8());
+                SudachiOcrCorrection {
+                    begin: begin as i32,
+                    end: end as i32,
+                    original: into_c_string(&text[begin..end]),
+                    corrected: into_c_string(c.corrected.encode_utf8(&mut [0; 4])),
+                }
+            })
+            .collect()
+    });
+
+    unsafe {
+        *out_count = corrections.len();
+    }
+    let corrections = Box::leak(corrections.into_boxed_slice()).as_mut_ptr();
+    leak::track(corrections, "ocr corrections");
+    corrections
+}
+
+/// Free the result of sudachi_ocr_corrections
+#[no_mangle]
+pub extern "C" fn sudachi_free_ocr_corrections(
+    corrections: *mut SudachiOcrCorrection,
+    count: usize,
+) {
+    if corrections.is_null() {
+        return;
+    }
+
+    leak::release(corrections);
+    unsafe {
+        let corrections = Box::from_raw(ptr::slice_from_raw_parts_mut(corrections, count));
+        for correction in corrections.iter() {
+            for s in [correction.original, correction.corrected] {
+                if !s.is_null() {
+                    let _ = std::ffi::CString::from_raw(s);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+
+    #[test]
+    fn test_candidate() {
+        // Between katakana, 一 is the prolonged sound mark
+        assert_eq!(candidate(Some('ス'), '一', Some('パ')), Some('ー'));
+        assert_eq!(candidate(Some('パ'), '一', None), Some('ー'));
+        // Next to kanji, katakana look-alikes are kanji
+        assert_eq!(candidate(Some('協'), 'カ', None), Some('力'));
+        assert_eq!(candidate(None, 'ロ', Some('調')), Some('口'));
+        assert_eq!(candidate(Some('統'), 'ー', Some('。')), Some('一'));
+        // Mixed context is left to the text
+        assert_eq!(candidate(Some('ス'), 'ー', Some('パ')), None);
+        assert_eq!(candidate(Some('日'), '一', Some('ー')), None);
+
+        assert_eq!(candidate(Some('行'), 'つ', Some('た')), Some('っ'));
+        assert_eq!(candidate(Some('ま'), 'っ', Some('あ')), Some('つ'));
+        assert_eq!(candidate(Some('あ'), 'っ', Some('！')), None);
+        assert_eq!(candidate(None, 'つ', Some('か')), None);
+        assert_eq!(candidate(Some('ロ'), 'ッ', Some('ク')), None);
+        assert_eq!(candidate(Some('ガ'), 'ツ', Some('ト')), Some('ッ'));
+    }
+
+    #[test]
+    fn test_restore() {
+        let original = "協カする";
+        let corrections = [Correction {
+            offset: 3,
+            corrected: '力',
+        }];
+        let token = |begin, surface| {
+            language::opaque(
+                begin,
+                surface,
+                SudachiScript::Japanese,
+                SUDACHI_FIELD_SURFACE,
+            )
+        };
+        let tokens = vec![token(0, "協力"), token(6, "する")];
+        let tokens = restore(original, &corrections, tokens);
+        assert_eq!(tokens[0].surface, "協カ");
+        assert_ne!(tokens[0].flags & SUDACHI_TOKEN_OCR, 0);
+        assert_eq!(tokens[1].flags & SUDACHI_TOKEN_OCR, 0);
+    }
+}
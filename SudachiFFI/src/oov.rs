@@ -0,0 +1,116 @@
+// Readings of unknown words
+// Sudachi gives words missing from the dictionary (OOV) an empty reading,
+// so furigana and text-to-speech skip them. Two opt-in reading flags fill
+// them in: a word written only in kana is read as itself in katakana, which
+// is exact, and a word with kanji is read character by character, each kanji
+// by the first dictionary entry of that single character (々 repeats the one
+// before). The latter ignores rendaku and compound readings, so it is only a
+// guess and such tokens are flagged SUDACHI_TOKEN_GUESSED.
+
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
+use sudachi::dic::subset::InfoSubset;
+
+use crate::kana::{is_kana, is_kanji, to_katakana};
+use crate::reading::{SUDACHI_READING_OOV_KANA, SUDACHI_READING_OOV_KANJI};
+use crate::{
+    TokenData, SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_READING, SUDACHI_TOKEN_GUESSED,
+    SUDACHI_TOKEN_OOV,
+};
+
+/// Reading of the first dictionary entry of a single kanji
+fn kanji_reading<D: DictionaryAccess>(dictionary: &D, kanji: char) -> Option<String> {
+    let mut buf = [0; 4];
+    let kanji = kanji.encode_utf8(&mut buf).as_bytes();
+    let lexicon = dictionary.lexicon();
+    let reading = lexicon
+        .lookup(kanji, 0)
+        .filter(|entry| entry.end == kanji.len())
+        .find_map(|entry| {
+            let info = lexicon
+                .get_word_info_subset(entry.word_id, InfoSubset::READING_FORM)
+                .ok()?;
+            let reading = info.reading_form();
+            (!reading.is_empty() && reading.chars().all(is_kana)).then(|| reading.to_string())
+        });
+    reading
+}
+
+/// Reading of surface in katakana, with kanji read by read_kanji
+/// None if a character is neither kana nor kanji, or a kanji has no reading
+fn guess(surface: &str, mut read_kanji: impl FnMut(char) -> Option<String>) -> Option<String> {
+    let mut reading = String::with_capacity(surface.len());
+    let mut last: Option<String> = None;
+    for c in surface.chars() {
+        if is_kana(c) {
+            reading.push(to_katakana(c));
+            last = None;
+        } else if c == '々' {
+            reading.push_str(last.as_deref()?);
+        } else if is_kanji(c) {
+            let kanji = read_kanji(c)?;
+            reading.push_str(&kanji);
+            last = Some(kanji);
+        } else {
+            return None;
+        }
+    }
+    Some(reading)
+}
+
+/// Fill the empty readings of OOV tokens as selected by the
+/// SUDACHI_READING_OOV_* flags (needs the surface field)
+pub(crate) fn fill<D: DictionaryAccess>(dictionary: &D, tokens: &mut [TokenData], flags: u32) {
+    let wanted = SUDACHI_FIELD_READING | SUDACHI_FIELD_DICTIONARY_READING;
+    for token in tokens {
+        if token.flags & SUDACHI_TOKEN_OOV == 0
+            || token.fields & wanted == 0
+            || token.surface.is_empty()
+            || !token.reading.is_empty()
+        {
+            continue;
+        }
+        let kana = token.surface.chars().all(is_kana);
+        let reading = if kana && flags & SUDACHI_READING_OOV_KANA != 0 {
+            guess(&token.surface, |_| None)
+        } else if !kana && flags & SUDACHI_READING_OOV_KANJI != 0 {
+            let reading = guess(&token.surface, |c| kanji_reading(dictionary, c));
+            if reading.is_some() {
+                token.flags |= SUDACHI_TOKEN_GUESSED;
+            }
+            reading
+        } else {
+            None
+        };
+        let Some(reading) = reading else {
+            continue;
+        };
+        if token.fields & SUDACHI_FIELD_DICTIONARY_READING != 0 {
+            token.dictionary_reading = reading.clone();
+        }
+        if token.fields & SUDACHI_FIELD_READING != 0 {
+            token.reading = reading;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guess() {
+        assert_eq!(guess("ぴえん", |_| None).as_deref(), Some("ピエン"));
+        assert_eq!(guess("ヤバー", |_| None).as_deref(), Some("ヤバー"));
+
+        let read = |c| match c {
+            '森' => Some("モリ".to_string()),
+            '木' => Some("キ".to_string()),
+            _ => None,
+        };
+        assert_eq!(guess("森々木", read).as_deref(), Some("モリモリキ"));
+        assert_eq!(guess("森の木", read).as_deref(), Some("モリノキ"));
+        assert_eq!(guess("森林", read), None);
+        assert_eq!(guess("森A", read), None);
+        assert_eq!(guess("々", read), None);
+    }
+}
@@ -0,0 +1,237 @@
+// Per-call analysis options
+// Every setting so far came as another sudachi_set_* toggle on the handle or
+// another parameter on a new entry point (sudachi_tokenize_fields), and
+// each one meant a signature change in every binding layer. SudachiOptions
+// gathers the per-call settings in one struct instead: mode, field mask,
+// normalization and toggles, token filters and limits. New settings are
+// appended as fields, so the signatures of the *_options calls never change.
+//
+// All zero is a valid struct: mode A, every field, the handle's own settings,
+// no filters and no limits. Normalization and reading flags are added to the
+// handle's; toggles can be switched either way for the call. The handle's
+// settings are restored after the call.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::analyzer::Options;
+use crate::{
+    c_str, handle, into_token_array, SudachiToken, SudachiTokenMode, SudachiTokenizer, TokenData,
+    SUDACHI_FIELD_ALL,
+};
+
+/// Version of SudachiOptions this library was built with
+pub const SUDACHI_OPTIONS_VERSION: u32 = 1;
+
+// Toggles for SudachiOptions.enable and SudachiOptions.disable
+/// Merge foreign script runs (sudachi_set_pass_through)
+pub const SUDACHI_OPTION_PASS_THROUGH: u32 = 1 << 0;
+/// Merge sound effects (sudachi_set_sfx)
+pub const SUDACHI_OPTION_SFX: u32 = 1 << 1;
+/// Join compound verbs (sudachi_set_compound_verbs)
+pub const SUDACHI_OPTION_COMPOUND_VERBS: u32 = 1 << 2;
+/// Fix OCR look-alikes (sudachi_set_ocr_correction)
+pub const SUDACHI_OPTION_OCR: u32 = 1 << 3;
+/// Keep whitespace runs as tokens (sudachi_set_preserve_whitespace)
+pub const SUDACHI_OPTION_PRESERVE_WHITESPACE: u32 = 1 << 4;
+
+/// Options for one call of the *_options functions
+/// Zero-initialize, set version to SUDACHI_OPTIONS_VERSION, then set what
+/// differs from the defaults. A NULL pointer is the same as all zero.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SudachiOptions {
+    /// SUDACHI_OPTIONS_VERSION the caller was built with (0 reads as 1)
+    /// Later versions only append fields.
+    pub version: u32,
+    /// Segmentation mode
+    pub mode: SudachiTokenMode,
+    /// SUDACHI_FIELD_* bits to leave out (0 fills every field)
+    pub omit_fields: u32,
+    /// SUDACHI_NORMALIZE_* flags, added to the handle's
+    pub normalization: u32,
+    /// SUDACHI_READING_* flags, added to the handle's
+    pub reading: u32,
+    /// SUDACHI_OPTION_* toggles to switch on for this call
+    pub enable: u32,
+    /// SUDACHI_OPTION_* toggles to switch off; wins over enable
+    pub disable: u32,
+    /// Drop tokens of these classes (bit 1 << SudachiTokenClass)
+    pub exclude_classes: u32,
+    /// Drop tokens with any of these SUDACHI_TOKEN_* flags
+    pub exclude_flags: u32,
+    /// Fail on texts longer than this many bytes (0 for no limit)
+    pub max_bytes: usize,
+    /// Return at most this many tokens (0 for no limit)
+    pub max_tokens: usize,
+}
+
+impl Default for SudachiOptions {
+    fn default() -> SudachiOptions {
+        SudachiOptions {
+            version: SUDACHI_OPTIONS_VERSION,
+            mode: SudachiTokenMode::A,
+            omit_fields: 0,
+            normalization: 0,
+            reading: 0,
+            enable: 0,
+            disable: 0,
+            exclude_classes: 0,
+            exclude_flags: 0,
+            max_bytes: 0,
+            max_tokens: 0,
+        }
+    }
+}
+
+impl SudachiOptions {
+    /// Options behind a pointer, the defaults for NULL
+    pub(crate) fn read(options: *const SudachiOptions) -> SudachiOptions {
+        if options.is_null() {
+            return SudachiOptions::default();
+        }
+        // Every version so far starts with the fields of version 1
+        unsafe { *options }
+    }
+
+    /// Field mask out of the fields a call wants
+    pub(crate) fn fields(&self, wanted: u32) -> u32 {
+        wanted & !self.omit_fields
+    }
+
+    /// Whether text is within the byte limit
+    pub(crate) fn fits(&self, text: &str) -> bool {
+        self.max_bytes == 0 || text.len() <= self.max_bytes
+    }
+
+    /// Apply the settings to a copy of the handle's options
+    pub(crate) fn adjust(&self, options: &mut Options) {
+        options.normalization |= self.normalization;
+        options.reading |= self.reading;
+        let toggles = [
+            (SUDACHI_OPTION_PASS_THROUGH, &mut options.pass_through),
+            (SUDACHI_OPTION_SFX, &mut options.sfx),
+            (SUDACHI_OPTION_COMPOUND_VERBS, &mut options.compound_verbs),
+            (SUDACHI_OPTION_OCR, &mut options.ocr),
+            (
+                SUDACHI_OPTION_PRESERVE_WHITESPACE,
+                &mut options.preserve_whitespace,
+            ),
+        ];
+        for (bit, value) in toggles {
+            if self.enable & bit != 0 {
+                *value = true;
+            }
+            if self.disable & bit != 0 {
+                *value = false;
+            }
+        }
+    }
+
+    /// Whether a token passes the class and flag filters
+    pub(crate) fn keep(&self, token: &TokenData) -> bool {
+        self.exclude_classes & (1 << token.class as u32) == 0
+            && self.exclude_flags & u32::from(token.flags) == 0
+    }
+
+    /// Token limit, usize::MAX for none
+    pub(crate) fn limit(&self) -> usize {
+        if self.max_tokens == 0 {
+            usize::MAX
+        } else {
+            self.max_tokens
+        }
+    }
+
+    /// Drop the excluded tokens and cut the rest to the token limit
+    pub(crate) fn filter(&self, tokens: Vec<TokenData>) -> Vec<TokenData> {
+        tokens
+            .into_iter()
+            .filter(|t| self.keep(t))
+            .take(self.limit())
+            .collect()
+    }
+}
+
+/// Tokenize text with per-call options
+/// options may be NULL for the defaults (see SudachiOptions).
+/// Returns NULL on failure or if text is longer than options->max_bytes
+/// (caller must free with sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_options(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    options: *const SudachiOptions,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let Some(text) = c_str(text) else {
+        return ptr::null_mut();
+    };
+    let options = SudachiOptions::read(options);
+    if !options.fits(text) {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let fields = options.fields(SUDACHI_FIELD_ALL);
+    let Ok(tokens) = tokenizer.analyze_with(text, options.mode.into(), fields, &options) else {
+        return ptr::null_mut();
+    };
+
+    let tokens = options
+        .filter(tokens)
+        .into_iter()
+        .filter_map(TokenData::into_raw)
+        .collect();
+    into_token_array(tokens, out_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+    use crate::symbol::{self, SudachiTokenClass};
+    use crate::{SUDACHI_FIELD_SURFACE, SUDACHI_TOKEN_OOV};
+
+    #[test]
+    fn test_options() {
+        let mut options = Options::default();
+        let call = SudachiOptions {
+            normalization: 1,
+            enable: SUDACHI_OPTION_SFX | SUDACHI_OPTION_OCR,
+            disable: SUDACHI_OPTION_PASS_THROUGH | SUDACHI_OPTION_OCR,
+            ..SudachiOptions::default()
+        };
+        call.adjust(&mut options);
+        assert!(options.sfx && !options.ocr && !options.pass_through);
+        assert_eq!(options.normalization, 1);
+
+        let mut tokens: Vec<TokenData> = ["猫", " ", "が", "。", "犬"]
+            .into_iter()
+            .map(|surface| {
+                let fields = SUDACHI_FIELD_SURFACE;
+                let mut token = language::opaque(0, surface, SudachiScript::Japanese, fields);
+                token.class = symbol::token_class(surface);
+                token
+            })
+            .collect();
+        tokens[2].flags |= SUDACHI_TOKEN_OOV;
+        let call = SudachiOptions {
+            exclude_classes: 1 << SudachiTokenClass::Whitespace as u32
+                | 1 << SudachiTokenClass::Symbol as u32,
+            exclude_flags: SUDACHI_TOKEN_OOV as u32,
+            max_tokens: 2,
+            ..SudachiOptions::default()
+        };
+        let surfaces: Vec<String> = call.filter(tokens).into_iter().map(|t| t.surface).collect();
+        assert_eq!(surfaces, ["猫", "犬"]);
+        assert!(!SudachiOptions {
+            max_bytes: 3,
+            ..SudachiOptions::default()
+        }
+        .fits("猫が"));
+    }
+}
@@ -0,0 +1,310 @@
+// Reading overrides
+// Series-specific names and invented terms are missing from the dictionary
+// or read wrong, and the user's corrections have to win over analysis. Each
+// override pins a surface to a segmentation and readings. Overrides without a
+// POS are matched in the text before analysis (longest first), so the
+// dictionary never sees the pinned span; overrides with a POS only replace
+// tokens that analysis returns with that surface and POS.
+
+use std::collections::HashMap;
+use std::os::raw::c_char;
+use std::sync::Arc;
+
+use crate::fingerprint::{fnv, FNV_OFFSET};
+use crate::language;
+use crate::script::{self, SudachiScript};
+use crate::symbol;
+use crate::{
+    c_str, handle, SudachiTokenizer, TokenData, SUDACHI_FIELD_DICTIONARY_FORM,
+    SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_NORMALIZED_FORM, SUDACHI_FIELD_POS,
+    SUDACHI_FIELD_READING, SUDACHI_FIELD_SURFACE, SUDACHI_TOKEN_OVERRIDE,
+};
+
+/// POS of tokens pinned before analysis
+const PINNED_POS: [&str; 6] = ["名詞", "固有名詞", "一般", "*", "*", "*"];
+
+/// Pinned reading and segmentation of a surface
+/// surface and reading are '|'-separated per segment ("七つ|の|大罪" and
+/// "ナナツ|ノ|タイザイ"), with the same number of segments.
+#[repr(C)]
+pub struct SudachiOverride {
+    pub surface: *const c_char,
+    pub reading: *const c_char,
+    /// Leading POS columns joined by ',' ("名詞,固有名詞") that analysis must
+    /// assign, or NULL to match anywhere in the text before analysis
+    pub pos: *const c_char,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Entry {
+    surface: String,
+    /// Byte length and reading of each segment
+    segments: Vec<(usize, String)>,
+    pos: Vec<String>,
+}
+
+impl Entry {
+    /// Byte length of the whole surface
+    pub(crate) fn len(&self) -> usize {
+        self.surface.len()
+    }
+
+    /// Continue an FNV-1a hash over the entry
+    fn hash(&self, hash: u64) -> u64 {
+        let mut hash = fnv(hash, self.surface.as_bytes());
+        for (len, reading) in &self.segments {
+            hash = fnv(hash, &(*len as u64).to_le_bytes());
+            hash = fnv(hash, reading.as_bytes());
+            hash = fnv(hash, &[0]);
+        }
+        for column in &self.pos {
+            hash = fnv(hash, column.as_bytes());
+            hash = fnv(hash, &[0]);
+        }
+        fnv(hash, &[1])
+    }
+
+    pub(crate) fn new(surface: &str, reading: &str, pos: Option<&str>) -> Option<Entry> {
+        let parts: Vec<&str> = surface.split('|').collect();
+        let readings: Vec<&str> = reading.split('|').collect();
+        if parts.len() != readings.len() || parts.iter().any(|p| p.is_empty()) {
+            return None;
+        }
+        Some(Entry {
+            surface: parts.concat(),
+            segments: parts
+                .iter()
+                .zip(readings)
+                .map(|(part, reading)| (part.len(), reading.to_string()))
+                .collect(),
+            pos: pos
+                .map(|pos| pos.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Tokens of the segments, starting at offset, built on template
+    fn segment_tokens(
+        &self,
+        offset: usize,
+        template: impl Fn(usize, &str) -> TokenData,
+    ) -> Vec<TokenData> {
+        let mut at = 0;
+        self.segments
+            .iter()
+            .map(|(len, reading)| {
+                let text = &self.surface[at..at + len];
+                let mut token = template(offset + at, text);
+                at += len;
+                if token.fields & SUDACHI_FIELD_READING != 0 {
+                    token.reading = reading.clone();
+                }
+                if token.fields & SUDACHI_FIELD_DICTIONARY_READING != 0 {
+                    token.dictionary_reading = reading.clone();
+                }
+                token.script = script::token_script(text);
+                token.class = symbol::token_class(text);
+                token.flags |= SUDACHI_TOKEN_OVERRIDE;
+                token
+            })
+            .collect()
+    }
+
+    /// Tokens for a match at offset found before analysis
+    pub(crate) fn pinned(&self, offset: usize, fields: u32) -> Vec<TokenData> {
+        self.segment_tokens(offset, |begin, text| {
+            let mut token = language::opaque(begin, text, SudachiScript::Japanese, fields);
+            if fields & SUDACHI_FIELD_POS != 0 {
+                token.pos = PINNED_POS.iter().map(|p| p.to_string()).collect();
+            }
+            token
+        })
+    }
+
+    /// Split an analyzed token into the segments, keeping its word info
+    pub(crate) fn split(&self, token: TokenData) -> Vec<TokenData> {
+        if self.segments.len() == 1 {
+            return self.segment_tokens(token.begin, |_, _| token.clone());
+        }
+        self.segment_tokens(token.begin, |begin, text| {
+            let field = |bit: u32| {
+                if token.fields & bit != 0 {
+                    text.to_string()
+                } else {
+                    String::new()
+                }
+            };
+            TokenData {
+                surface: field(SUDACHI_FIELD_SURFACE),
+                dictionary_form: field(SUDACHI_FIELD_DICTIONARY_FORM),
+                normalized_form: field(SUDACHI_FIELD_NORMALIZED_FORM),
+                begin,
+                end: begin + text.len(),
+                ..token.clone()
+            }
+        })
+    }
+}
+
+/// Overrides of a handle
+#[derive(Default)]
+pub(crate) struct Overrides {
+    /// Entries without a POS by first character, longest first
+    anywhere: HashMap<char, Vec<Entry>>,
+    /// Entries with a POS by surface
+    tagged: HashMap<String, Vec<Entry>>,
+    /// Hash of the entries in the order given (0 for none)
+    digest: u64,
+}
+
+impl Overrides {
+    pub(crate) fn new(entries: Vec<Entry>) -> Overrides {
+        let mut overrides = Overrides::default();
+        if !entries.is_empty() {
+            overrides.digest = entries.iter().fold(FNV_OFFSET, |hash, e| e.hash(hash));
+        }
+        for entry in entries {
+            if !entry.pos.is_empty() {
+                overrides
+                    .tagged
+                    .entry(entry.surface.clone())
+                    .or_default()
+                    .push(entry);
+            } else if let Some(c) = entry.surface.chars().next() {
+                overrides.anywhere.entry(c).or_default().push(entry);
+            }
+        }
+        for entries in overrides.anywhere.values_mut() {
+            entries.sort_by_key(|e| std::cmp::Reverse(e.surface.len()));
+        }
+        overrides
+    }
+
+    /// Matches of entries without a POS in text as (offset, entry), leftmost
+    /// longest and not overlapping
+    pub(crate) fn matches<'a>(&'a self, text: &str) -> Vec<(usize, &'a Entry)> {
+        let mut matches = Vec::new();
+        if self.anywhere.is_empty() {
+            return matches;
+        }
+        let mut next = 0;
+        for (i, c) in text.char_indices() {
+            if i < next {
+                continue;
+            }
+            let found = self
+                .anywhere
+                .get(&c)
+                .and_then(|entries| entries.iter().find(|e| text[i..].starts_with(&e.surface)));
+            if let Some(entry) = found {
+                matches.push((i, entry));
+                next = i + entry.surface.len();
+            }
+        }
+        matches
+    }
+
+    /// Entry with a POS for an analyzed token
+    pub(crate) fn tagged(&self, surface: &str, pos: &[String]) -> Option<&Entry> {
+        self.tagged
+            .get(surface)?
+            .iter()
+            .find(|e| e.pos.len() <= pos.len() && e.pos.iter().zip(pos).all(|(a, b)| a == b))
+    }
+
+    pub(crate) fn has_tagged(&self) -> bool {
+        !self.tagged.is_empty()
+    }
+
+    /// Hash of the entries, equal for handles with the same overrides
+    pub(crate) fn digest(&self) -> u64 {
+        self.digest
+    }
+}
+
+/// Replace the reading overrides of a handle
+/// entries is an array of n overrides (n may be 0 to clear them); they are
+/// copied, so the caller keeps and persists its own list. Returns false if
+/// tokenizer is NULL or any entry is invalid, leaving the overrides unchanged.
+#[no_mangle]
+pub extern "C" fn sudachi_set_overrides(
+    tokenizer: *mut SudachiTokenizer,
+    entries: *const SudachiOverride,
+    n: usize,
+) -> bool {
+    if tokenizer.is_null() || (entries.is_null() && n > 0) {
+        return false;
+    }
+    let raw = if n == 0 {
+        &[][..]
+    } else {
+        unsafe { std::slice::from_raw_parts(entries, n) }
+    };
+
+    let mut parsed = Vec::with_capacity(n);
+    for entry in raw {
+        let (Some(surface), Some(reading)) = (c_str(entry.surface), c_str(entry.reading)) else {
+            return false;
+        };
+        let pos = if entry.pos.is_null() {
+            None
+        } else {
+            match c_str(entry.pos) {
+                Some(pos) if !pos.is_empty() => Some(pos),
+                _ => return false,
+            }
+        };
+        match Entry::new(surface, reading, pos) {
+            Some(entry) => parsed.push(entry),
+            None => return false,
+        }
+    }
+
+    let overrides = Arc::new(Overrides::new(parsed));
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.overrides = overrides);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SUDACHI_FIELD_ALL;
+
+    #[test]
+    fn test_overrides() {
+        let overrides = Overrides::new(vec![
+            Entry::new("七つ|の|大罪", "ナナツ|ノ|タイザイ", None).unwrap(),
+            Entry::new("七つ", "ナナツ", None).unwrap(),
+            Entry::new("炭治郎", "タンジロウ", Some("名詞,固有名詞")).unwrap(),
+        ]);
+        assert!(Entry::new("七つ|の", "ナナツ", None).is_none());
+
+        let text = "七つの大罪と七つ";
+        let matches: Vec<(usize, &str)> = overrides
+            .matches(text)
+            .into_iter()
+            .map(|(i, e)| (i, e.surface.as_str()))
+            .collect();
+        assert_eq!(matches, [(0, "七つの大罪"), (18, "七つ")]);
+
+        let pinned = overrides.matches(text)[0].1.pinned(0, SUDACHI_FIELD_ALL);
+        let segments: Vec<(&str, &str, usize)> = pinned
+            .iter()
+            .map(|t| (t.surface.as_str(), t.reading.as_str(), t.begin))
+            .collect();
+        assert_eq!(
+            segments,
+            [
+                ("七つ", "ナナツ", 0),
+                ("の", "ノ", 6),
+                ("大罪", "タイザイ", 9)
+            ]
+        );
+        assert!(pinned.iter().all(|t| t.flags & SUDACHI_TOKEN_OVERRIDE != 0));
+
+        let noun = ["名詞", "固有名詞", "人名", "名", "*", "*"].map(String::from);
+        let verb = ["動詞", "一般", "*", "*", "*", "*"].map(String::from);
+        assert!(overrides.tagged("炭治郎", &noun).is_some());
+        assert!(overrides.tagged("炭治郎", &verb).is_none());
+    }
+}
@@ -0,0 +1,404 @@
+// Packed results in caller-owned memory
+// sudachi_tokenize_into writes the whole result into a buffer supplied by the
+// caller, so a single buffer can be reused across calls with no result-side
+// allocations crossing the FFI boundary.
+//
+// Buffer layout (native endianness):
+//   SudachiPackedHeader
+//   SudachiPackedToken[count]
+//   string area (string_bytes bytes, strings are not NUL-terminated)
+//
+// Short strings and POS tags are interned: the same particles, readings and
+// POS arrays come back thousands of times per chapter, so each distinct one
+// is stored once per result and shared by every token that uses it.
+//
+// Structs are written unaligned; read them in place only from an 8-byte
+// aligned buffer. The header carries the fingerprint of the dictionary, so a
+// buffer kept across launches can be checked with sudachi_result_compatible.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::mem::size_of;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::fingerprint::SudachiFingerprint;
+use crate::{
+    c_str, handle, SudachiTokenMode, SudachiTokenizer, TokenData, SUDACHI_FIELD_ALL,
+    SUDACHI_FIELD_DICTIONARY_FORM, SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_NORMALIZED_FORM,
+    SUDACHI_FIELD_POS, SUDACHI_FIELD_READING, SUDACHI_FIELD_SURFACE,
+};
+
+/// Length value marking an absent string
+pub const SUDACHI_PACKED_NULL: u32 = u32::MAX;
+
+/// Longest string interned besides POS tags (four kana or kanji)
+const INTERN_MAX_BYTES: usize = 12;
+
+/// Start of a packed result
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SudachiPackedHeader {
+    pub count: u32,
+    pub string_bytes: u32,
+    /// Dictionary that produced the result
+    pub fingerprint: SudachiFingerprint,
+}
+
+/// Reference into the string area of a packed result
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SudachiStrRef {
+    /// Byte offset from the start of the string area
+    pub offset: u32,
+    /// Byte length, or SUDACHI_PACKED_NULL if absent
+    pub len: u32,
+}
+
+/// Fixed-size token record of a packed result
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SudachiPackedToken {
+    pub begin: u32,
+    pub end: u32,
+    pub word_id: u32,
+    pub pos_id: u16,
+    pub reserved: u16,
+    pub surface: SudachiStrRef,
+    pub reading: SudachiStrRef,
+    pub dictionary_form: SudachiStrRef,
+    pub normalized_form: SudachiStrRef,
+    /// POS tags as a JSON array string
+    pub pos: SudachiStrRef,
+    pub dictionary_reading: SudachiStrRef,
+    pub lemma_key: u64,
+}
+
+/// Concatenated string area addressed by SudachiStrRef
+#[derive(Default)]
+pub(crate) struct StringBlob {
+    pub(crate) bytes: Vec<u8>,
+    /// Interned strings (offset, length) by hash of their bytes; keys own
+    /// nothing, so a cleared blob is refilled without allocating
+    interned: HashMap<u64, (u32, u32)>,
+    hasher: RandomState,
+}
+
+impl StringBlob {
+    /// Whether every offset into the area fits a SudachiStrRef
+    pub(crate) fn addressable(&self) -> bool {
+        u32::try_from(self.bytes.len()).is_ok()
+    }
+
+    /// Empty the area, keeping its capacity
+    pub(crate) fn clear(&mut self) {
+        self.bytes.clear();
+        self.interned.clear();
+    }
+
+    pub(crate) fn push(&mut self, selected: bool, s: &str) -> SudachiStrRef {
+        if !selected {
+            return SudachiStrRef {
+                offset: 0,
+                len: SUDACHI_PACKED_NULL,
+            };
+        }
+        let offset = self.bytes.len();
+        self.bytes.extend_from_slice(s.as_bytes());
+        if s.len() <= INTERN_MAX_BYTES {
+            self.intern(offset)
+        } else {
+            self.str_ref(offset)
+        }
+    }
+
+    /// Append POS tags as a JSON array, written in place
+    fn push_pos(&mut self, selected: bool, pos: &[String]) -> SudachiStrRef {
+        if !selected {
+            return self.push(false, "");
+        }
+        let offset = self.bytes.len();
+        if serde_json::to_writer(&mut self.bytes, pos).is_err() {
+            self.bytes.truncate(offset);
+        }
+        self.intern(offset)
+    }
+
+    /// Reference to the bytes from offset to the end
+    fn str_ref(&self, offset: usize) -> SudachiStrRef {
+        SudachiStrRef {
+            offset: offset as u32,
+            len: (self.bytes.len() - offset) as u32,
+        }
+    }
+
+    /// Reference to an earlier copy of the bytes just appended from offset,
+    /// dropping them, or to them if they are new
+    fn intern(&mut self, offset: usize) -> SudachiStrRef {
+        let bytes = &self.bytes[offset..];
+        let hash = self.hasher.hash_one(bytes);
+        match self.interned.get(&hash) {
+            Some(&(earlier, len)) => {
+                let start = earlier as usize;
+                // On a hash collision the new string is simply not shared
+                if self.bytes[start..start + len as usize] == *bytes {
+                    self.bytes.truncate(offset);
+                    return SudachiStrRef {
+                        offset: earlier,
+                        len,
+                    };
+                }
+            }
+            None => {
+                let len = bytes.len() as u32;
+                self.interned.insert(hash, (offset as u32, len));
+            }
+        }
+        self.str_ref(offset)
+    }
+
+    /// Append the string fields of a token selected by its field mask, in
+    /// the order surface, reading, dictionary form, normalized form, POS,
+    /// dictionary reading
+    pub(crate) fn push_token(&mut self, t: &TokenData) -> [SudachiStrRef; 6] {
+        let has = |bit: u32| t.fields & bit != 0;
+        [
+            self.push(has(SUDACHI_FIELD_SURFACE), &t.surface),
+            self.push(has(SUDACHI_FIELD_READING), &t.reading),
+            self.push(has(SUDACHI_FIELD_DICTIONARY_FORM), &t.dictionary_form),
+            self.push(has(SUDACHI_FIELD_NORMALIZED_FORM), &t.normalized_form),
+            self.push_pos(has(SUDACHI_FIELD_POS), &t.pos),
+            self.push(has(SUDACHI_FIELD_DICTIONARY_READING), &t.dictionary_reading),
+        ]
+    }
+}
+
+/// Packed representation of an analysis, built before copying out
+pub(crate) struct Packed {
+    tokens: Vec<SudachiPackedToken>,
+    strings: Vec<u8>,
+    fingerprint: SudachiFingerprint,
+}
+
+/// Append the packed records of tokens to out and their strings to blob
+pub(crate) fn pack(tokens: &[TokenData], out: &mut Vec<SudachiPackedToken>, blob: &mut StringBlob) {
+    out.extend(tokens.iter().map(|t| {
+        let [surface, reading, dictionary_form, normalized_form, pos, dictionary_reading] =
+            blob.push_token(t);
+        SudachiPackedToken {
+            begin: t.begin as u32,
+            end: t.end as u32,
+            word_id: t.word_id,
+            pos_id: t.pos_id,
+            reserved: 0,
+            surface,
+            reading,
+            dictionary_form,
+            normalized_form,
+            pos,
+            dictionary_reading,
+            lemma_key: t.lemma_key,
+        }
+    }));
+}
+
+impl Packed {
+    /// None if the strings overflow the u32 offsets of the layout
+    pub(crate) fn new(tokens: &[TokenData], fingerprint: SudachiFingerprint) -> Option<Packed> {
+        let mut blob = StringBlob::default();
+        let mut packed = Vec::with_capacity(tokens.len());
+        pack(tokens, &mut packed, &mut blob);
+        blob.addressable().then_some(Packed {
+            tokens: packed,
+            strings: blob.bytes,
+            fingerprint,
+        })
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        size_of::<SudachiPackedHeader>()
+            + self.tokens.len() * size_of::<SudachiPackedToken>()
+            + self.strings.len()
+    }
+
+    /// Copy into buf, which must hold at least size() bytes
+    unsafe fn write(&self, buf: *mut u8) {
+        let header = SudachiPackedHeader {
+            count: self.tokens.len() as u32,
+            string_bytes: self.strings.len() as u32,
+            fingerprint: self.fingerprint,
+        };
+        ptr::write_unaligned(buf as *mut SudachiPackedHeader, header);
+
+        let mut at = buf.add(size_of::<SudachiPackedHeader>());
+        for token in &self.tokens {
+            ptr::write_unaligned(at as *mut SudachiPackedToken, *token);
+            at = at.add(size_of::<SudachiPackedToken>());
+        }
+        ptr::copy_nonoverlapping(self.strings.as_ptr(), at, self.strings.len());
+    }
+}
+
+/// Tokenize text into a caller-owned buffer
+/// The required size is always written to out_needed. Returns false without
+/// writing if buf_len is too small (out_needed > buf_len) or on failure
+/// (out_needed = 0).
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_into(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    out_buf: *mut u8,
+    buf_len: usize,
+    out_needed: *mut usize,
+) -> bool {
+    if out_needed.is_null() {
+        return false;
+    }
+    unsafe {
+        *out_needed = 0;
+    }
+    if tokenizer.is_null() {
+        return false;
+    }
+
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return false,
+    };
+    let tokens = match tokenizer.analyze(text, mode.into(), SUDACHI_FIELD_ALL) {
+        Ok(tokens) => tokens,
+        Err(_) => return false,
+    };
+
+    let Some(packed) = Packed::new(&tokens, tokenizer.fingerprint) else {
+        return false;
+    };
+    let needed = packed.size();
+    unsafe {
+        *out_needed = needed;
+    }
+    if out_buf.is_null() || buf_len < needed {
+        return false;
+    }
+
+    unsafe { packed.write(out_buf) };
+    true
+}
+
+/// Whether a packed result at buf was stamped with fingerprint
+fn compatible(buf: &[u8], fingerprint: SudachiFingerprint) -> bool {
+    if buf.len() < size_of::<SudachiPackedHeader>() {
+        return false;
+    }
+    let header = unsafe { ptr::read_unaligned(buf.as_ptr() as *const SudachiPackedHeader) };
+    header.fingerprint.matches(fingerprint)
+}
+
+/// Check whether a packed result was produced with a handle's dictionary
+/// result and len are the buffer filled by sudachi_tokenize_into. Returns
+/// false if the dictionary differs (re-run the analysis) or on NULL
+/// arguments.
+#[no_mangle]
+pub extern "C" fn sudachi_result_compatible(
+    result: *const u8,
+    len: usize,
+    tokenizer: *const SudachiTokenizer,
+) -> bool {
+    if result.is_null() || tokenizer.is_null() {
+        return false;
+    }
+    let buf = unsafe { std::slice::from_raw_parts(result, len) };
+    compatible(buf, unsafe { handle::borrow(tokenizer) }.fingerprint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::SudachiScript;
+    use crate::symbol::SudachiTokenClass;
+
+    #[test]
+    fn test_packed_layout() {
+        let tokens = vec![TokenData {
+            fields: SUDACHI_FIELD_ALL & !SUDACHI_FIELD_NORMALIZED_FORM,
+            surface: "猫".to_string(),
+            reading: "ネコ".to_string(),
+            dictionary_form: "猫".to_string(),
+            dictionary_reading: "ネコ".to_string(),
+            normalized_form: "猫".to_string(),
+            pos: vec!["名詞".to_string()],
+            begin: 0,
+            end: 3,
+            word_id: 42,
+            pos_id: 1,
+            script: SudachiScript::Japanese,
+            class: SudachiTokenClass::Word,
+            flags: 0,
+            lemma_key: 0,
+        }];
+        let fingerprint = SudachiFingerprint {
+            hash: 0x5eed,
+            version: 2,
+        };
+        let packed = Packed::new(&tokens, fingerprint).unwrap();
+        let mut buf = vec![0u64; packed.size().div_ceil(8)];
+        unsafe { packed.write(buf.as_mut_ptr() as *mut u8) };
+
+        let base = buf.as_ptr() as *const u8;
+        let header = unsafe { &*(base as *const SudachiPackedHeader) };
+        assert_eq!(header.count, 1);
+        let bytes = unsafe { std::slice::from_raw_parts(base, packed.size()) };
+        assert!(compatible(bytes, fingerprint));
+        assert!(!compatible(bytes, SudachiFingerprint::default()));
+        assert!(!compatible(&bytes[..8], fingerprint));
+        let token =
+            unsafe { &*(base.add(size_of::<SudachiPackedHeader>()) as *const SudachiPackedToken) };
+        assert_eq!((token.end, token.word_id), (3, 42));
+        assert_eq!(token.normalized_form.len, SUDACHI_PACKED_NULL);
+
+        let strings =
+            unsafe { base.add(size_of::<SudachiPackedHeader>() + size_of::<SudachiPackedToken>()) };
+        let reading = unsafe {
+            std::slice::from_raw_parts(
+                strings.add(token.reading.offset as usize),
+                token.reading.len as usize,
+            )
+        };
+        assert_eq!(reading, "ネコ".as_bytes());
+    }
+
+    #[test]
+    fn test_interning() {
+        let mut blob = StringBlob::default();
+        let pos = vec!["助詞".to_string(), "係助詞".to_string()];
+        let first = (blob.push(true, "は"), blob.push_pos(true, &pos));
+        let long = "ながいながいなまえ";
+        let (a, b) = (blob.push(true, long), blob.push(true, long));
+        let second = (blob.push(true, "は"), blob.push_pos(true, &pos));
+        assert_eq!(
+            (first.0.offset, first.1.offset),
+            (second.0.offset, second.1.offset)
+        );
+        assert_eq!(second.0.len, 3);
+        assert_ne!(a.offset, b.offset);
+        assert_eq!(b.offset + b.len, blob.bytes.len() as u32);
+
+        blob.clear();
+        assert_eq!(blob.push(true, "の").offset, 0);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_addressable() {
+        // Zeroed pages are mapped lazily, so these are never touched
+        let mut blob = StringBlob {
+            bytes: vec![0; u32::MAX as usize],
+            ..StringBlob::default()
+        };
+        assert!(blob.addressable());
+        blob.bytes = vec![0; u32::MAX as usize + 1];
+        assert!(!blob.addressable());
+    }
+}
@@ -0,0 +1,239 @@
+// Paged results
+// A pathological input (a whole book pasted into one field, a log file) can
+// produce millions of tokens, and sudachi_tokenize holds all of them as C
+// structs at once, on top of the Swift copies made from them. A paged result
+// analyzes the text lazily instead: each sudachi_result_next_page call
+// analyzes sentence-aligned chunks (chunk.rs) only until a page is full, so
+// at most about one page and one chunk of tokens are alive inside the
+// library at a time.
+//
+// The result owns a copy of the text and its own analyzer over the handle's
+// dictionary, with the handle's settings as of creation (like a queue), so
+// paging never holds the handle's lock between calls.
+
+use std::collections::VecDeque;
+use std::os::raw::c_char;
+use std::ptr;
+
+use sudachi::prelude::*;
+
+use crate::analyzer::Analyzer;
+use crate::chunk::{self, SUDACHI_DEFAULT_CHUNK_BYTES};
+use crate::options::SudachiOptions;
+use crate::{
+    c_str, handle, into_token_array, leak, SudachiToken, SudachiTokenizer, TokenData,
+    SUDACHI_FIELD_ALL,
+};
+
+/// Tokens per page when page_size is 0
+pub const SUDACHI_DEFAULT_PAGE_TOKENS: usize = 4096;
+
+/// Tokens analyzed but not yet returned
+struct Buffer {
+    pending: VecDeque<TokenData>,
+    /// Tokens that may still be returned under max_tokens
+    remaining: usize,
+}
+
+impl Buffer {
+    fn new(limit: usize) -> Buffer {
+        Buffer {
+            pending: VecDeque::new(),
+            remaining: limit,
+        }
+    }
+
+    /// Queue the tokens of a chunk that pass the filters, shifted by offset
+    fn push(&mut self, options: &SudachiOptions, offset: usize, tokens: Vec<TokenData>) {
+        for mut token in tokens.into_iter().filter(|t| options.keep(t)) {
+            if self.remaining == 0 {
+                break;
+            }
+            self.remaining -= 1;
+            token.begin += offset;
+            token.end += offset;
+            self.pending.push_back(token);
+        }
+    }
+
+    /// Take up to size tokens
+    fn page(&mut self, size: usize) -> Vec<TokenData> {
+        let n = size.min(self.pending.len());
+        self.pending.drain(..n).collect()
+    }
+}
+
+/// Opaque handle to a tokenization read page by page
+pub struct SudachiPagedResult {
+    analyzer: Analyzer,
+    text: String,
+    /// Start of the text not analyzed yet
+    offset: usize,
+    chunk_bytes: usize,
+    page_size: usize,
+    options: SudachiOptions,
+    buffer: Buffer,
+    /// An analysis failed; no more pages
+    failed: bool,
+}
+
+impl SudachiPagedResult {
+    fn next_page(&mut self) -> SudachiResult<Vec<TokenData>> {
+        let fields = self.options.fields(SUDACHI_FIELD_ALL);
+        while self.buffer.pending.len() < self.page_size
+            && self.buffer.remaining > 0
+            && self.offset < self.text.len()
+        {
+            let rest = &self.text[self.offset..];
+            let len = if rest.len() <= self.chunk_bytes {
+                rest.len()
+            } else {
+                chunk::cut(rest, self.chunk_bytes)
+            };
+            let tokens = self
+                .analyzer
+                .analyze(&rest[..len], self.options.mode.into(), fields)?;
+            self.buffer.push(&self.options, self.offset, tokens);
+            self.offset += len;
+        }
+        Ok(self.buffer.page(self.page_size))
+    }
+}
+
+/// Tokenize text page by page
+/// The text is copied and analyzed as pages are requested with
+/// sudachi_result_next_page; page_size is the most tokens per page (0 for
+/// SUDACHI_DEFAULT_PAGE_TOKENS). options may be NULL for the defaults (see
+/// SudachiOptions); max_tokens limits the whole result. Uses the handle's
+/// settings as of this call; the handle may be freed while the result lives.
+/// Returns NULL if an argument is NULL, text is not valid UTF-8 or longer
+/// than options->max_bytes (caller must free with sudachi_free_paged_result)
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_paged(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    options: *const SudachiOptions,
+    page_size: usize,
+) -> *mut SudachiPagedResult {
+    if tokenizer.is_null() {
+        return ptr::null_mut();
+    }
+    let Some(text) = c_str(text) else {
+        return ptr::null_mut();
+    };
+    let options = SudachiOptions::read(options);
+    if !options.fits(text) {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let mut analyzer = Analyzer::new(tokenizer.dictionary.clone());
+    analyzer.options = tokenizer.options();
+    options.adjust(&mut analyzer.options);
+    // Chunks are the unit of work, so a handle that analyzes in one piece
+    // still pages in default-sized chunks
+    let chunk_bytes = match analyzer.options.chunk_bytes {
+        0 => SUDACHI_DEFAULT_CHUNK_BYTES,
+        bytes => bytes,
+    };
+
+    let result = Box::into_raw(Box::new(SudachiPagedResult {
+        analyzer,
+        text: text.to_owned(),
+        offset: 0,
+        chunk_bytes,
+        page_size: match page_size {
+            0 => SUDACHI_DEFAULT_PAGE_TOKENS,
+            size => size,
+        },
+        buffer: Buffer::new(options.limit()),
+        options,
+        failed: false,
+    }));
+    leak::track(result, "SudachiPagedResult");
+    result
+}
+
+/// Next page of a paged tokenization
+/// Pages follow each other in text order with offsets into the whole text.
+/// Returns an empty page (out_count 0) once every token was returned, and
+/// NULL if an argument is NULL or the analysis failed, after which every
+/// call returns NULL (caller must free each page with sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_result_next_page(
+    result: *mut SudachiPagedResult,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    if result.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let result = unsafe { &mut *result };
+    if result.failed {
+        return ptr::null_mut();
+    }
+    let Ok(tokens) = result.next_page() else {
+        result.failed = true;
+        return ptr::null_mut();
+    };
+    let tokens = tokens.into_iter().filter_map(TokenData::into_raw).collect();
+    into_token_array(tokens, out_count)
+}
+
+/// Free a paged result
+/// Pages already returned stay valid.
+#[no_mangle]
+pub extern "C" fn sudachi_free_paged_result(result: *mut SudachiPagedResult) {
+    if !result.is_null() {
+        leak::release(result);
+        unsafe {
+            let _ = Box::from_raw(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+    use crate::symbol::{self, SudachiTokenClass};
+    use crate::SUDACHI_FIELD_SURFACE;
+
+    fn tokens(surfaces: &[&str]) -> Vec<TokenData> {
+        let mut begin = 0;
+        surfaces
+            .iter()
+            .map(|surface| {
+                let mut token = language::opaque(
+                    begin,
+                    surface,
+                    SudachiScript::Japanese,
+                    SUDACHI_FIELD_SURFACE,
+                );
+                token.class = symbol::token_class(surface);
+                begin += surface.len();
+                token
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_buffer() {
+        let options = SudachiOptions {
+            exclude_classes: 1 << SudachiTokenClass::Symbol as u32,
+            max_tokens: 4,
+            ..SudachiOptions::default()
+        };
+        let mut buffer = Buffer::new(options.limit());
+        buffer.push(&options, 0, tokens(&["猫", "が", "。"]));
+        buffer.push(&options, 9, tokens(&["犬", "も", "鳥"]));
+        let page: Vec<(String, usize)> = buffer
+            .page(3)
+            .into_iter()
+            .map(|t| (t.surface, t.begin))
+            .collect();
+        assert_eq!(page, [("猫".into(), 0), ("が".into(), 3), ("犬".into(), 9)]);
+        assert_eq!(buffer.page(3).len(), 1);
+        assert_eq!(buffer.remaining, 0);
+        assert!(buffer.page(3).is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+// Phonetic similarity
+// Learners who study by listening look words up by how they sound, and mishear
+// vowel length and double consonants first: しゅじん for しゅうじん, きて for
+// きって. Readings are compared mora by mora (キャ is one mora, not two
+// characters) with an edit distance where adding or dropping a long vowel or
+// a geminate ッ costs half an edit, and any other insertion, deletion or
+// substitution of a mora a whole one. Before comparing, a vowel that only
+// lengthens the mora before it is written ー, since ユウキ and ユーキ sound
+// the same; after an o-row mora ウ lengthens, after an e-row mora イ, and
+// after any mora the same vowel.
+//
+// The readings of the headword index (wildcard.rs) are grouped by mora count.
+// Every mora of difference costs at least half an edit, so a search only
+// compares readings whose length is within twice the distance of the query.
+
+use std::collections::BTreeMap;
+
+/// Cost of adding or dropping a long vowel or ッ
+const LIGHT: f32 = 0.5;
+/// Cost of any other edit of a mora
+const FULL: f32 = 1.0;
+
+/// Long vowel mark
+const LONG: &str = "ー";
+/// Geminate consonant mark
+const GEMINATE: &str = "ッ";
+
+/// Small kana that join the mora before them
+const SMALL: &str = "ァィゥェォャュョヮ";
+
+/// Katakana by the vowel they end in
+const VOWELS: [(char, &str); 5] = [
+    ('a', "アァカガサザタダナハバパマヤャラワヮ"),
+    ('i', "イィキギシジチヂニヒビピミリヰ"),
+    ('u', "ウゥクグスズツヅヌフブプムユュルヴ"),
+    ('e', "エェケゲセゼテデネヘベペメレヱ"),
+    ('o', "オォコゴソゾトドノホボポモヨョロヲ"),
+];
+
+/// Vowel a mora ends in
+fn vowel(mora: &str) -> Option<char> {
+    let last = mora.chars().last()?;
+    VOWELS
+        .iter()
+        .find(|(_, kana)| kana.contains(last))
+        .map(|&(v, _)| v)
+}
+
+/// Morae of a katakana reading, with lengthening vowels written ー
+pub(crate) fn morae(reading: &str) -> Vec<String> {
+    let mut morae: Vec<String> = Vec::new();
+    for c in reading.chars() {
+        match morae.last_mut() {
+            Some(last) if SMALL.contains(c) && last != LONG && last != GEMINATE => last.push(c),
+            _ => morae.push(c.to_string()),
+        }
+    }
+    // Vowel of the sound before each mora, carried across ー
+    let mut previous: Option<char> = None;
+    for mora in &mut morae {
+        let own = vowel(mora);
+        let lengthens = matches!(
+            (previous, mora.as_str()),
+            (Some('a'), "ア")
+                | (Some('i'), "イ")
+                | (Some('u'), "ウ")
+                | (Some('e'), "エ" | "イ")
+                | (Some('o'), "オ" | "ウ")
+        );
+        if lengthens {
+            *mora = LONG.to_string();
+        } else if mora != LONG {
+            previous = own;
+        }
+    }
+    morae
+}
+
+/// Cost of adding or dropping a mora
+fn indel(mora: &str) -> f32 {
+    if mora == LONG || mora == GEMINATE {
+        LIGHT
+    } else {
+        FULL
+    }
+}
+
+/// Weighted edit distance between two mora sequences
+pub(crate) fn distance(a: &[String], b: &[String]) -> f32 {
+    let mut row: Vec<f32> = Vec::with_capacity(b.len() + 1);
+    row.push(0.0);
+    for m in b {
+        row.push(row[row.len() - 1] + indel(m));
+    }
+    for x in a {
+        let mut next = Vec::with_capacity(row.len());
+        next.push(row[0] + indel(x));
+        for (j, y) in b.iter().enumerate() {
+            let substitute = if x == y { 0.0 } else { FULL };
+            let cell = (row[j + 1] + indel(x))
+                .min(next[j] + indel(y))
+                .min(row[j] + substitute);
+            next.push(cell);
+        }
+        row = next;
+    }
+    row[b.len()]
+}
+
+/// Readings of a headword index grouped by mora count
+pub(crate) struct Sounds {
+    by_length: BTreeMap<usize, Vec<(Vec<String>, u32)>>,
+}
+
+impl Sounds {
+    /// Index katakana readings and the entries they belong to
+    pub(crate) fn new(readings: &[(String, u32)]) -> Sounds {
+        let mut by_length: BTreeMap<usize, Vec<(Vec<String>, u32)>> = BTreeMap::new();
+        for (reading, entry) in readings {
+            let morae = morae(reading);
+            by_length
+                .entry(morae.len())
+                .or_default()
+                .push((morae, *entry));
+        }
+        Sounds { by_length }
+    }
+
+    /// Entries with a reading within max_distance of reading (katakana),
+    /// nearest first and then in entry order, with their distances
+    pub(crate) fn search(&self, reading: &str, max_distance: f32) -> Vec<(f32, u32)> {
+        let query = morae(reading);
+        let spread = (max_distance / LIGHT) as usize;
+        let lengths = query.len().saturating_sub(spread)..=query.len() + spread;
+        let mut found: Vec<(f32, u32)> = self
+            .by_length
+            .range(lengths)
+            .flat_map(|(_, readings)| readings)
+            .filter_map(|(morae, entry)| {
+                let d = distance(&query, morae);
+                (d <= max_distance).then_some((d, *entry))
+            })
+            .collect();
+        // Keep the nearest reading of each entry
+        found.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.total_cmp(&b.0)));
+        found.dedup_by_key(|&mut (_, e)| e);
+        found.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(morae("キャッシュ"), ["キャ", "ッ", "シュ"]);
+        assert_eq!(morae("ユウキ"), ["ユ", "ー", "キ"]);
+        assert_eq!(morae("オオキイ"), ["オ", "ー", "キ", "ー"]);
+        assert_eq!(morae("センセイ"), ["セ", "ン", "セ", "ー"]);
+        assert_eq!(morae("カイ"), ["カ", "イ"]);
+
+        let d = |a: &str, b: &str| distance(&morae(a), &morae(b));
+        assert_eq!(d("ユウキ", "ユーキ"), 0.0);
+        assert_eq!(d("シュジン", "シュウジン"), 0.5);
+        assert_eq!(d("キテ", "キッテ"), 0.5);
+        assert_eq!(d("カタ", "カダ"), 1.0);
+        assert_eq!(d("キャク", "キク"), 1.0);
+
+        let readings = [
+            ("シュウジン", 0),
+            ("ジュウジ", 1),
+            ("シュジン", 2),
+            ("キッテ", 3),
+        ]
+        .map(|(r, e)| (r.to_string(), e));
+        let sounds = Sounds::new(&readings);
+        assert_eq!(sounds.search("シュジン", 0.5), [(0.0, 2), (0.5, 0)]);
+        assert_eq!(sounds.search("キテ", 1.0), [(0.5, 3)]);
+    }
+}
@@ -0,0 +1,156 @@
+// Tokenizer pool
+// A fixed set of warm tokenizers sharing one dictionary, for servers that
+// tokenize on many threads at once. A thread checks out a tokenizer, uses it
+// with the regular API and checks it back in; each tokenizer is used by one
+// thread at a time, so its analyzer lock is never contended.
+
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use crate::{c_str, handle, leak, load_dictionary, SudachiTokenizer};
+
+/// Opaque handle to a tokenizer pool
+pub struct SudachiPool {
+    /// Every tokenizer owned by the pool
+    tokenizers: Vec<*mut SudachiTokenizer>,
+    /// Tokenizers currently checked in
+    idle: Mutex<Vec<*mut SudachiTokenizer>>,
+    available: Condvar,
+}
+
+impl SudachiPool {
+    fn idle(&self) -> MutexGuard<'_, Vec<*mut SudachiTokenizer>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for SudachiPool {
+    fn drop(&mut self) {
+        for &tokenizer in &self.tokenizers {
+            drop(unsafe { handle::from_raw(tokenizer) });
+        }
+    }
+}
+
+/// Create a pool of size tokenizers over one dictionary file
+/// Returns NULL on failure or if size is 0 (caller must free with
+/// sudachi_pool_free)
+#[no_mangle]
+pub extern "C" fn sudachi_pool_create(dict_path: *const c_char, size: usize) -> *mut SudachiPool {
+    if size == 0 {
+        return ptr::null_mut();
+    }
+    let path = match c_str(dict_path) {
+        Some(p) => p,
+        None => return ptr::null_mut(),
+    };
+    let dictionary = match load_dictionary(path) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    let tokenizers: Vec<_> = (0..size)
+        .map(|_| handle::into_raw(SudachiTokenizer::new(dictionary.clone())))
+        .collect();
+    let pool = Box::into_raw(Box::new(SudachiPool {
+        idle: Mutex::new(tokenizers.clone()),
+        tokenizers,
+        available: Condvar::new(),
+    }));
+    leak::track(pool, "SudachiPool");
+    pool
+}
+
+/// Check out a tokenizer, blocking until one is free
+/// The tokenizer stays owned by the pool: never free it, return it with
+/// sudachi_pool_checkin. Returns NULL if pool is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_pool_checkout(pool: *mut SudachiPool) -> *mut SudachiTokenizer {
+    if pool.is_null() {
+        return ptr::null_mut();
+    }
+    let pool = unsafe { &*pool };
+
+    let mut idle = pool.idle();
+    loop {
+        if let Some(tokenizer) = idle.pop() {
+            return tokenizer;
+        }
+        idle = pool.available.wait(idle).unwrap_or_else(|e| e.into_inner());
+    }
+}
+
+/// Check out a tokenizer if one is free
+/// Returns NULL without blocking if all are checked out.
+#[no_mangle]
+pub extern "C" fn sudachi_pool_try_checkout(pool: *mut SudachiPool) -> *mut SudachiTokenizer {
+    if pool.is_null() {
+        return ptr::null_mut();
+    }
+    let pool = unsafe { &*pool };
+    pool.idle().pop().unwrap_or(ptr::null_mut())
+}
+
+/// Return a checked-out tokenizer to the pool
+/// Returns false if tokenizer does not belong to pool or is already checked in
+#[no_mangle]
+pub extern "C" fn sudachi_pool_checkin(
+    pool: *mut SudachiPool,
+    tokenizer: *mut SudachiTokenizer,
+) -> bool {
+    if pool.is_null() || tokenizer.is_null() {
+        return false;
+    }
+    let pool = unsafe { &*pool };
+
+    let mut idle = pool.idle();
+    if !pool.tokenizers.contains(&tokenizer) || idle.contains(&tokenizer) {
+        eprintln!(
+            "sudachi_pool_checkin: {:p} is not checked out from this pool",
+            tokenizer
+        );
+        return false;
+    }
+    idle.push(tokenizer);
+    drop(idle);
+    pool.available.notify_one();
+    true
+}
+
+/// Number of tokenizers currently checked in
+#[no_mangle]
+pub extern "C" fn sudachi_pool_idle_count(pool: *mut SudachiPool) -> usize {
+    if pool.is_null() {
+        return 0;
+    }
+    unsafe { &*pool }.idle().len()
+}
+
+/// Free a pool and all its tokenizers
+/// Every checked-out tokenizer must have been checked in first.
+#[no_mangle]
+pub extern "C" fn sudachi_pool_free(pool: *mut SudachiPool) {
+    if !pool.is_null() {
+        leak::release(pool);
+        unsafe {
+            let _ = Box::from_raw(pool);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_null() {
+        assert!(sudachi_pool_create(ptr::null(), 4).is_null());
+        assert!(sudachi_pool_checkout(ptr::null_mut()).is_null());
+        assert!(!sudachi_pool_checkin(ptr::null_mut(), ptr::null_mut()));
+        assert_eq!(sudachi_pool_idle_count(ptr::null_mut()), 0);
+    }
+}
@@ -0,0 +1,143 @@
+// Option presets
+// Every binding layer ended up with its own copy of the same few option
+// combinations, and the tuning behind them (mode C segments fastest, search
+// wants short units and normalized forms, vocabulary lists want sound
+// effects and unknown words out) drifted between copies. Presets keep those
+// combinations here, by name, as ordinary SudachiOptions that callers can
+// adjust further.
+
+use std::os::raw::c_char;
+
+use crate::dialogue::SUDACHI_NORMALIZE_DIALOGUE;
+use crate::options::{
+    SudachiOptions, SUDACHI_OPTION_COMPOUND_VERBS, SUDACHI_OPTION_OCR, SUDACHI_OPTION_SFX,
+};
+use crate::symbol::SudachiTokenClass;
+use crate::width::{SUDACHI_NORMALIZE_FULLWIDTH_ASCII, SUDACHI_NORMALIZE_HALFWIDTH_KANA};
+use crate::{
+    c_str, SudachiTokenMode, SUDACHI_FIELD_ALL, SUDACHI_FIELD_DICTIONARY_FORM,
+    SUDACHI_FIELD_LEMMA_KEY, SUDACHI_FIELD_NORMALIZED_FORM, SUDACHI_FIELD_POS,
+    SUDACHI_FIELD_READING, SUDACHI_FIELD_SURFACE, SUDACHI_TOKEN_OOV,
+};
+
+const WIDTH: u32 = SUDACHI_NORMALIZE_HALFWIDTH_KANA | SUDACHI_NORMALIZE_FULLWIDTH_ASCII;
+
+/// Bits of exclude_classes for classes
+const fn classes(classes: &[SudachiTokenClass]) -> u32 {
+    let mut bits = 0;
+    let mut i = 0;
+    while i < classes.len() {
+        bits |= 1 << classes[i] as u32;
+        i += 1;
+    }
+    bits
+}
+
+/// Classes that are never words
+const NON_WORDS: u32 = classes(&[
+    SudachiTokenClass::Symbol,
+    SudachiTokenClass::Emoji,
+    SudachiTokenClass::Opaque,
+    SudachiTokenClass::Whitespace,
+]);
+
+/// Options of a preset, None for unknown names
+pub(crate) fn preset(name: &str) -> Option<SudachiOptions> {
+    let defaults = SudachiOptions::default();
+    let options = match name {
+        // Medium units with surfaces and readings, nothing dropped, so ruby
+        // covers the whole text
+        "READER_FURIGANA" => SudachiOptions {
+            mode: SudachiTokenMode::B,
+            omit_fields: SUDACHI_FIELD_ALL & !(SUDACHI_FIELD_SURFACE | SUDACHI_FIELD_READING),
+            normalization: WIDTH,
+            ..defaults
+        },
+        // Short units for recall, normalized forms to match spelling
+        // variants, and only words
+        "SEARCH_INDEXING" => SudachiOptions {
+            mode: SudachiTokenMode::A,
+            omit_fields: SUDACHI_FIELD_ALL
+                & !(SUDACHI_FIELD_SURFACE | SUDACHI_FIELD_NORMALIZED_FORM | SUDACHI_FIELD_POS),
+            normalization: WIDTH,
+            exclude_classes: NON_WORDS,
+            ..defaults
+        },
+        // Dictionary words with what a word list needs; sound effects and
+        // unknown words are left out
+        "VOCAB_EXTRACTION" => SudachiOptions {
+            mode: SudachiTokenMode::B,
+            omit_fields: SUDACHI_FIELD_ALL
+                & !(SUDACHI_FIELD_SURFACE
+                    | SUDACHI_FIELD_READING
+                    | SUDACHI_FIELD_DICTIONARY_FORM
+                    | SUDACHI_FIELD_POS
+                    | SUDACHI_FIELD_LEMMA_KEY),
+            normalization: WIDTH | SUDACHI_NORMALIZE_DIALOGUE,
+            enable: SUDACHI_OPTION_SFX | SUDACHI_OPTION_COMPOUND_VERBS,
+            exclude_classes: NON_WORDS | classes(&[SudachiTokenClass::Sfx]),
+            exclude_flags: SUDACHI_TOKEN_OOV as u32,
+            ..defaults
+        },
+        // Offsets only; mode C decodes no splits and OCR correction would
+        // analyze every candidate twice
+        "FAST_BOUNDARIES" => SudachiOptions {
+            mode: SudachiTokenMode::C,
+            omit_fields: SUDACHI_FIELD_ALL,
+            disable: SUDACHI_OPTION_OCR,
+            ..defaults
+        },
+        _ => return None,
+    };
+    Some(options)
+}
+
+/// Fill options with a named preset
+/// Names: READER_FURIGANA, SEARCH_INDEXING, VOCAB_EXTRACTION and
+/// FAST_BOUNDARIES. The result can be adjusted before use.
+/// Returns false if an argument is NULL or the name is unknown
+#[no_mangle]
+pub extern "C" fn sudachi_options_preset(
+    name: *const c_char,
+    options: *mut SudachiOptions,
+) -> bool {
+    if options.is_null() {
+        return false;
+    }
+    let Some(preset) = c_str(name).and_then(preset) else {
+        return false;
+    };
+    unsafe {
+        *options = preset;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset() {
+        let search = preset("SEARCH_INDEXING").unwrap();
+        assert_eq!(
+            search.fields(SUDACHI_FIELD_ALL),
+            SUDACHI_FIELD_SURFACE | SUDACHI_FIELD_NORMALIZED_FORM | SUDACHI_FIELD_POS
+        );
+        assert_ne!(
+            search.exclude_classes & 1 << SudachiTokenClass::Whitespace as u32,
+            0
+        );
+        assert_eq!(
+            search.exclude_classes & 1 << SudachiTokenClass::Word as u32,
+            0
+        );
+        assert_eq!(
+            preset("FAST_BOUNDARIES").unwrap().fields(SUDACHI_FIELD_ALL),
+            0
+        );
+        assert!(preset("READER_FURIGANA").is_some());
+        assert!(preset("VOCAB_EXTRACTION").is_some());
+        assert!(preset("reader_furigana").is_none());
+    }
+}
@@ -0,0 +1,195 @@
+// Batch progress
+// Importing a book runs a batch over thousands of paragraphs for several
+// seconds. A progress handle passed to the batch reports items and bytes done
+// after every text, so the import screen can show a real progress bar, and
+// can pause the batch (workers finish the text they are on, then wait) while
+// the app needs the cores for something else.
+
+use std::os::raw::c_void;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use crate::leak;
+
+/// Progress of a running batch
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SudachiProgressInfo {
+    pub items_done: usize,
+    pub items_total: usize,
+    /// UTF-8 bytes of the texts done
+    pub bytes_done: usize,
+    pub bytes_total: usize,
+}
+
+/// Receives progress after each text, on the worker thread that finished it
+/// Calls never overlap, and counts only grow within a batch.
+pub type SudachiProgressCallback =
+    Option<extern "C" fn(user_data: *mut c_void, info: *const SudachiProgressInfo)>;
+
+type Callback = extern "C" fn(*mut c_void, *const SudachiProgressInfo);
+
+/// Opaque handle to batch progress reporting and control
+pub struct SudachiProgress {
+    callback: Option<Callback>,
+    user_data: usize,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl SudachiProgress {
+    fn paused(&self) -> MutexGuard<'_, bool> {
+        self.paused.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Progress of one batch run, reported through an optional handle
+pub(crate) struct Tracker<'a> {
+    progress: Option<&'a SudachiProgress>,
+    info: Mutex<SudachiProgressInfo>,
+}
+
+impl<'a> Tracker<'a> {
+    pub(crate) fn new(
+        progress: Option<&'a SudachiProgress>,
+        items_total: usize,
+        bytes_total: usize,
+    ) -> Tracker<'a> {
+        Tracker {
+            progress,
+            info: Mutex::new(SudachiProgressInfo {
+                items_total,
+                bytes_total,
+                ..SudachiProgressInfo::default()
+            }),
+        }
+    }
+
+    /// Block while the batch is paused
+    pub(crate) fn checkpoint(&self) {
+        let Some(progress) = self.progress else {
+            return;
+        };
+        let mut paused = progress.paused();
+        while *paused {
+            paused = progress
+                .resumed
+                .wait(paused)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Record one finished text of bytes bytes
+    pub(crate) fn done(&self, bytes: usize) {
+        let mut info = self.info.lock().unwrap_or_else(|e| e.into_inner());
+        info.items_done += 1;
+        info.bytes_done += bytes;
+        if let Some(progress) = self.progress {
+            if let Some(callback) = progress.callback {
+                // Called under the lock so reports arrive in order
+                callback(progress.user_data as *mut c_void, &*info);
+            }
+        }
+    }
+}
+
+/// Create a progress handle for sudachi_tokenize_batch_with_progress
+/// callback may be NULL to use the handle for pausing only.
+/// Caller must free with sudachi_progress_free
+#[no_mangle]
+pub extern "C" fn sudachi_progress_create(
+    callback: SudachiProgressCallback,
+    user_data: *mut c_void,
+) -> *mut SudachiProgress {
+    let progress = Box::into_raw(Box::new(SudachiProgress {
+        callback,
+        user_data: user_data as usize,
+        paused: Mutex::new(false),
+        resumed: Condvar::new(),
+    }));
+    leak::track(progress, "SudachiProgress");
+    progress
+}
+
+/// Pause batches using this handle
+/// Workers finish the text they are on, then wait for
+/// sudachi_progress_resume; the batch call keeps blocking meanwhile.
+/// Returns false if progress is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_progress_pause(progress: *mut SudachiProgress) -> bool {
+    if progress.is_null() {
+        return false;
+    }
+    *unsafe { &*progress }.paused() = true;
+    true
+}
+
+/// Resume batches paused with sudachi_progress_pause
+/// Returns false if progress is NULL
+#[no_mangle]
+pub extern "C" fn sudachi_progress_resume(progress: *mut SudachiProgress) -> bool {
+    if progress.is_null() {
+        return false;
+    }
+    let progress = unsafe { &*progress };
+    *progress.paused() = false;
+    progress.resumed.notify_all();
+    true
+}
+
+/// Whether batches using this handle are paused
+#[no_mangle]
+pub extern "C" fn sudachi_progress_is_paused(progress: *const SudachiProgress) -> bool {
+    !progress.is_null() && *unsafe { &*progress }.paused()
+}
+
+/// Free a progress handle
+/// No batch may be using it.
+#[no_mangle]
+pub extern "C" fn sudachi_progress_free(progress: *mut SudachiProgress) {
+    if !progress.is_null() {
+        leak::release(progress);
+        unsafe {
+            let _ = Box::from_raw(progress);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    static REPORTS: Mutex<Vec<SudachiProgressInfo>> = Mutex::new(Vec::new());
+
+    extern "C" fn record(_user_data: *mut c_void, info: *const SudachiProgressInfo) {
+        REPORTS.lock().unwrap().push(unsafe { *info });
+    }
+
+    #[test]
+    fn test_tracker() {
+        let progress = sudachi_progress_create(Some(record), ptr::null_mut());
+        let tracker = Tracker::new(Some(unsafe { &*progress }), 2, 30);
+        tracker.checkpoint();
+        tracker.done(10);
+        tracker.done(20);
+        let reports = REPORTS.lock().unwrap().clone();
+        assert_eq!(reports.len(), 2);
+        assert_eq!((reports[0].items_done, reports[0].bytes_done), (1, 10));
+        assert_eq!(
+            reports[1],
+            SudachiProgressInfo {
+                items_done: 2,
+                items_total: 2,
+                bytes_done: 30,
+                bytes_total: 30,
+            }
+        );
+
+        assert!(sudachi_progress_pause(progress));
+        assert!(sudachi_progress_is_paused(progress));
+        assert!(sudachi_progress_resume(progress));
+        tracker.checkpoint();
+        sudachi_progress_free(progress);
+        assert!(!sudachi_progress_is_paused(ptr::null()));
+    }
+}
@@ -0,0 +1,171 @@
+// Python bindings (pyo3 feature)
+// Lets dataset tooling (frequency lists, difficulty calibration) run the
+// production tokenizer and deinflector. Build the wheel with maturin.
+
+use std::sync::{Mutex, PoisonError};
+
+use pyo3::exceptions::{PyOSError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use sudachi::prelude::{Mode, SudachiError};
+
+use crate::analyzer::{Analyzer, Dictionary};
+use crate::deinflect::{deinflect, Deinflection as DeinflectionData, DEINFLECT_FIELDS};
+use crate::furigana::{furigana, FURIGANA_FIELDS};
+use crate::{load_dictionary, lookup, TokenData, SUDACHI_FIELD_ALL};
+
+fn parse_mode(mode: &str) -> PyResult<Mode> {
+    match mode {
+        "A" | "a" => Ok(Mode::A),
+        "B" | "b" => Ok(Mode::B),
+        "C" | "c" => Ok(Mode::C),
+        _ => Err(PyValueError::new_err(format!("invalid mode: {}", mode))),
+    }
+}
+
+fn analysis_error(e: SudachiError) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+/// One morpheme; begin/end are UTF-8 byte offsets as in the C API
+#[pyclass(get_all, frozen)]
+pub struct Token {
+    surface: String,
+    reading: String,
+    dictionary_form: String,
+    dictionary_reading: String,
+    normalized_form: String,
+    pos: Vec<String>,
+    begin: usize,
+    end: usize,
+    word_id: u32,
+    pos_id: u16,
+    lemma_key: u64,
+}
+
+impl From<TokenData> for Token {
+    fn from(t: TokenData) -> Self {
+        Token {
+            surface: t.surface,
+            reading: t.reading,
+            dictionary_form: t.dictionary_form,
+            dictionary_reading: t.dictionary_reading,
+            normalized_form: t.normalized_form,
+            pos: t.pos,
+            begin: t.begin,
+            end: t.end,
+            word_id: t.word_id,
+            pos_id: t.pos_id,
+            lemma_key: t.lemma_key,
+        }
+    }
+}
+
+#[pymethods]
+impl Token {
+    fn __repr__(&self) -> String {
+        format!("Token({:?}, {:?})", self.surface, self.pos)
+    }
+}
+
+/// Dictionary form and conjugation of a non-punctuation token
+#[pyclass(get_all, frozen)]
+pub struct Deinflection {
+    surface: String,
+    dictionary_form: String,
+    conjugation_type: Option<String>,
+    conjugation_form: Option<String>,
+    begin: usize,
+    end: usize,
+}
+
+impl From<DeinflectionData> for Deinflection {
+    fn from(d: DeinflectionData) -> Self {
+        Deinflection {
+            surface: d.surface,
+            dictionary_form: d.dictionary_form,
+            conjugation_type: d.conjugation_type,
+            conjugation_form: d.conjugation_form,
+            begin: d.begin,
+            end: d.end,
+        }
+    }
+}
+
+#[pymethods]
+impl Deinflection {
+    fn __repr__(&self) -> String {
+        format!(
+            "Deinflection({:?} -> {:?})",
+            self.surface, self.dictionary_form
+        )
+    }
+}
+
+#[pyclass]
+pub struct Tokenizer {
+    dictionary: Dictionary,
+    analyzer: Mutex<Analyzer>,
+}
+
+impl Tokenizer {
+    fn analyze(&self, text: &str, mode: &str, fields: u32) -> PyResult<Vec<TokenData>> {
+        let mode = parse_mode(mode)?;
+        let mut analyzer = self.analyzer.lock().unwrap_or_else(PoisonError::into_inner);
+        analyzer.analyze(text, mode, fields).map_err(analysis_error)
+    }
+}
+
+#[pymethods]
+impl Tokenizer {
+    /// Open a system dictionary file
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        let dictionary = load_dictionary(path)
+            .map_err(PyOSError::new_err)?
+            .dictionary;
+        Ok(Tokenizer {
+            analyzer: Mutex::new(Analyzer::new(dictionary.clone())),
+            dictionary,
+        })
+    }
+
+    #[pyo3(signature = (text, mode = "C"))]
+    fn tokenize(&self, text: &str, mode: &str) -> PyResult<Vec<Token>> {
+        let tokens = self.analyze(text, mode, SUDACHI_FIELD_ALL)?;
+        Ok(tokens.into_iter().map(Token::from).collect())
+    }
+
+    /// Dictionary forms and conjugations, punctuation skipped
+    #[pyo3(signature = (text, mode = "C"))]
+    fn deinflect(&self, text: &str, mode: &str) -> PyResult<Vec<Deinflection>> {
+        let tokens = self.analyze(text, mode, DEINFLECT_FIELDS)?;
+        Ok(deinflect(tokens)
+            .into_iter()
+            .map(Deinflection::from)
+            .collect())
+    }
+
+    /// Dictionary entries whose surface is exactly word
+    fn lookup(&self, word: &str) -> PyResult<Vec<Token>> {
+        let entries = lookup::lookup(&self.dictionary, word).map_err(analysis_error)?;
+        Ok(entries.into_iter().map(Token::from).collect())
+    }
+
+    /// Furigana segments as (text, reading or None) pairs
+    #[pyo3(signature = (text, mode = "C"))]
+    fn furigana(&self, text: &str, mode: &str) -> PyResult<Vec<(String, Option<String>)>> {
+        let tokens = self.analyze(text, mode, FURIGANA_FIELDS)?;
+        Ok(furigana(&tokens)
+            .into_iter()
+            .map(|r| (r.text, r.reading))
+            .collect())
+    }
+}
+
+#[pymodule]
+fn sudachi_ios(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Tokenizer>()?;
+    m.add_class::<Token>()?;
+    m.add_class::<Deinflection>()?;
+    Ok(())
+}
@@ -0,0 +1,298 @@
+// Analysis queue
+// A job queue for analysis that can wait, like pre-analyzing a whole volume
+// while the reader is open. Jobs run on the queue's own worker threads, each
+// with its own analyzer over the shared dictionary, so they never take the
+// lock of the handle that serves tap lookups. Higher priority jobs are picked
+// first, and on Apple platforms a worker runs each job at the QoS class of its
+// priority, so background work yields the cores to the UI.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::os::raw::c_char;
+use std::os::raw::c_void;
+use std::ptr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use crate::analyzer::{Analyzer, Dictionary, Options};
+use crate::{
+    c_str, handle, into_token_array, leak, SudachiToken, SudachiTokenMode, SudachiTokenizer,
+    TokenData, SUDACHI_FIELD_ALL,
+};
+
+/// Priority of a queued job
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SudachiQueuePriority {
+    /// Work the user is not waiting for (QOS_CLASS_BACKGROUND)
+    Background = 0,
+    /// Work with visible progress (QOS_CLASS_UTILITY)
+    Utility = 1,
+    /// Work the user is waiting for (QOS_CLASS_USER_INITIATED)
+    UserInitiated = 2,
+}
+
+/// Receives the result of a job on the worker thread that ran it
+/// tokens is NULL (and count 0) if the analysis failed; otherwise the callback
+/// owns the array and must free it with sudachi_free_tokens.
+pub type SudachiQueueCallback = Option<
+    extern "C" fn(user_data: *mut c_void, job: u64, tokens: *mut *mut SudachiToken, count: usize),
+>;
+
+type Callback = extern "C" fn(*mut c_void, u64, *mut *mut SudachiToken, usize);
+
+struct Job {
+    id: u64,
+    priority: SudachiQueuePriority,
+    text: String,
+    mode: SudachiTokenMode,
+    callback: Callback,
+    user_data: usize,
+}
+
+impl Job {
+    /// Higher priority first, then submission order
+    fn rank(&self) -> (SudachiQueuePriority, Reverse<u64>) {
+        (self.priority, Reverse(self.id))
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Job) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Job) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Job) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    jobs: BinaryHeap<Job>,
+    last_id: u64,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for the next job; None once the queue is closed
+    fn next(&self) -> Option<Job> {
+        let mut state = self.state();
+        loop {
+            if state.closed {
+                return None;
+            }
+            if let Some(job) = state.jobs.pop() {
+                return Some(job);
+            }
+            state = self.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Opaque handle to an analysis queue
+pub struct SudachiQueue {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Drop for SudachiQueue {
+    fn drop(&mut self) {
+        self.shared.state().closed = true;
+        self.shared.ready.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Run the calling thread at the QoS class of priority
+#[cfg(any(target_os = "ios", target_os = "macos"))]
+fn set_qos(priority: SudachiQueuePriority) {
+    use libc::qos_class_t::*;
+    let class = match priority {
+        SudachiQueuePriority::Background => QOS_CLASS_BACKGROUND,
+        SudachiQueuePriority::Utility => QOS_CLASS_UTILITY,
+        SudachiQueuePriority::UserInitiated => QOS_CLASS_USER_INITIATED,
+    };
+    unsafe {
+        libc::pthread_set_qos_class_self_np(class, 0);
+    }
+}
+
+#[cfg(not(any(target_os = "ios", target_os = "macos")))]
+fn set_qos(_priority: SudachiQueuePriority) {}
+
+fn work(shared: &Shared, dictionary: Dictionary, options: Options) {
+    let mut analyzer = Analyzer::new(dictionary);
+    analyzer.options = options;
+    let mut qos = None;
+    while let Some(job) = shared.next() {
+        if qos != Some(job.priority) {
+            set_qos(job.priority);
+            qos = Some(job.priority);
+        }
+        let mut count = 0;
+        let tokens = match analyzer.analyze(&job.text, job.mode.into(), SUDACHI_FIELD_ALL) {
+            Ok(tokens) => {
+                let tokens = tokens.into_iter().filter_map(TokenData::into_raw).collect();
+                into_token_array(tokens, &mut count)
+            }
+            Err(_) => ptr::null_mut(),
+        };
+        (job.callback)(job.user_data as *mut c_void, job.id, tokens, count);
+    }
+}
+
+/// Create a queue with threads workers over a handle's dictionary
+/// Workers use the handle's analysis settings as of this call; the handle
+/// may be freed while the queue lives. Pass threads = 0 for one worker.
+/// Returns NULL if tokenizer is NULL (caller must free with
+/// sudachi_queue_free)
+#[no_mangle]
+pub extern "C" fn sudachi_queue_create(
+    tokenizer: *const SudachiTokenizer,
+    threads: usize,
+) -> *mut SudachiQueue {
+    if tokenizer.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let shared = Arc::new(Shared::default());
+    let workers = (0..threads.max(1))
+        .map(|_| {
+            let shared = shared.clone();
+            let dictionary = tokenizer.dictionary.clone();
+            let options = tokenizer.options();
+            thread::spawn(move || work(&shared, dictionary, options))
+        })
+        .collect();
+
+    let queue = Box::into_raw(Box::new(SudachiQueue { shared, workers }));
+    leak::track(queue, "SudachiQueue");
+    queue
+}
+
+/// Queue text for analysis
+/// The text is copied. callback runs on a worker thread with user_data once
+/// the job is done. Returns the job id, or 0 if an argument is NULL or text
+/// is not valid UTF-8.
+#[no_mangle]
+pub extern "C" fn sudachi_queue_submit(
+    queue: *mut SudachiQueue,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    priority: SudachiQueuePriority,
+    callback: SudachiQueueCallback,
+    user_data: *mut c_void,
+) -> u64 {
+    if queue.is_null() {
+        return 0;
+    }
+    let (Some(text), Some(callback)) = (c_str(text), callback) else {
+        return 0;
+    };
+    let queue = unsafe { &*queue };
+
+    let mut state = queue.shared.state();
+    state.last_id += 1;
+    let id = state.last_id;
+    state.jobs.push(Job {
+        id,
+        priority,
+        text: text.to_owned(),
+        mode,
+        callback,
+        user_data: user_data as usize,
+    });
+    drop(state);
+    queue.shared.ready.notify_one();
+    id
+}
+
+/// Remove a job that has not started yet
+/// Its callback is never called. Returns false if the job is running,
+/// finished or unknown.
+#[no_mangle]
+pub extern "C" fn sudachi_queue_cancel(queue: *mut SudachiQueue, job: u64) -> bool {
+    if queue.is_null() {
+        return false;
+    }
+    let mut state = unsafe { &*queue }.shared.state();
+    let before = state.jobs.len();
+    state.jobs.retain(|queued| queued.id != job);
+    state.jobs.len() != before
+}
+
+/// Number of jobs waiting for a worker
+#[no_mangle]
+pub extern "C" fn sudachi_queue_pending(queue: *mut SudachiQueue) -> usize {
+    if queue.is_null() {
+        return 0;
+    }
+    unsafe { &*queue }.shared.state().jobs.len()
+}
+
+/// Free a queue
+/// Waits for running jobs to finish; jobs that have not started are dropped
+/// without calling their callbacks.
+#[no_mangle]
+pub extern "C" fn sudachi_queue_free(queue: *mut SudachiQueue) {
+    if !queue.is_null() {
+        leak::release(queue);
+        unsafe {
+            let _ = Box::from_raw(queue);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn ignore(_: *mut c_void, _: u64, _: *mut *mut SudachiToken, _: usize) {}
+
+    fn job(id: u64, priority: SudachiQueuePriority) -> Job {
+        Job {
+            id,
+            priority,
+            text: String::new(),
+            mode: SudachiTokenMode::C,
+            callback: ignore,
+            user_data: 0,
+        }
+    }
+
+    #[test]
+    fn test_job_order() {
+        let mut jobs = BinaryHeap::new();
+        jobs.push(job(1, SudachiQueuePriority::Background));
+        jobs.push(job(2, SudachiQueuePriority::UserInitiated));
+        jobs.push(job(3, SudachiQueuePriority::Background));
+        jobs.push(job(4, SudachiQueuePriority::UserInitiated));
+        jobs.push(job(5, SudachiQueuePriority::Utility));
+        let order: Vec<u64> = std::iter::from_fn(|| jobs.pop().map(|j| j.id)).collect();
+        assert_eq!(order, vec![2, 4, 5, 1, 3]);
+    }
+}
@@ -0,0 +1,326 @@
+// Readability reports
+// The volume detail screen shows how hard a book is: how long its sentences
+// are, how much kanji it uses, which levels its vocabulary comes from and how
+// much of it the dictionary does not know. Collecting that in Swift meant
+// bridging every token of the volume; the report is built here in one call,
+// with the texts analyzed in parallel like a batch, and returned as JSON.
+//
+// Levels come from a table the caller supplies (JLPT lists, school grades),
+// matched against the dictionary form and its reading, so inflected forms
+// count for their word. Only content words are graded; particles, auxiliary
+// verbs and affixes are not.
+//
+// Level table format (UTF-8, one word per line, # starts a comment):
+//   word <TAB> reading <TAB> level
+// The reading may be empty to match the word in any reading; readings may be
+// hiragana or katakana, and levels are integers from 0 to 255.
+
+use std::collections::{BTreeMap, HashMap};
+use std::os::raw::c_char;
+use std::ptr;
+
+use serde_json::{json, Value};
+
+use crate::batch::analyze_all;
+use crate::kana::{hiragana_to_katakana, is_kanji};
+use crate::progress::Tracker;
+use crate::sentences::sentences;
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    c_str, handle, into_c_string, leak, SudachiTokenMode, SudachiTokenizer, TokenData,
+    SUDACHI_FIELD_DICTIONARY_FORM, SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_POS,
+    SUDACHI_FIELD_SURFACE, SUDACHI_TOKEN_OOV,
+};
+
+/// Fields a report needs from the analyzer
+const REPORT_FIELDS: u32 = SUDACHI_FIELD_SURFACE
+    | SUDACHI_FIELD_DICTIONARY_FORM
+    | SUDACHI_FIELD_DICTIONARY_READING
+    | SUDACHI_FIELD_POS;
+/// Words per sentence length bucket
+const BUCKET_WORDS: usize = 5;
+/// Sentence length buckets; the last one is open-ended
+const BUCKETS: usize = 9;
+
+/// Level table loaded with sudachi_levels_open
+pub struct SudachiLevels {
+    /// Levels by word and katakana reading
+    readings: HashMap<(String, String), u8>,
+    /// Levels of words listed without a reading
+    words: HashMap<String, u8>,
+}
+
+impl SudachiLevels {
+    fn parse(table: &str) -> SudachiLevels {
+        let mut levels = SudachiLevels {
+            readings: HashMap::new(),
+            words: HashMap::new(),
+        };
+        for line in table.lines() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let columns: Vec<&str> = line.split('\t').collect();
+            let [word, reading, level] = columns[..] else {
+                continue;
+            };
+            let Ok(level) = level.trim().parse::<u8>() else {
+                continue;
+            };
+            if word.is_empty() {
+                continue;
+            }
+            // The first entry of a word wins
+            if reading.is_empty() {
+                levels.words.entry(word.to_string()).or_insert(level);
+            } else {
+                let reading = hiragana_to_katakana(reading);
+                levels
+                    .readings
+                    .entry((word.to_string(), reading))
+                    .or_insert(level);
+            }
+        }
+        levels
+    }
+
+    fn level(&self, token: &TokenData) -> Option<u8> {
+        let word = &token.dictionary_form;
+        self.readings
+            .get(&(word.clone(), token.dictionary_reading.clone()))
+            .or_else(|| self.words.get(word))
+            .copied()
+    }
+}
+
+/// Open a level table
+/// Returns NULL if path is NULL or the file can't be read (caller must free
+/// with sudachi_levels_close)
+#[no_mangle]
+pub extern "C" fn sudachi_levels_open(path: *const c_char) -> *mut SudachiLevels {
+    let Some(path) = c_str(path) else {
+        return ptr::null_mut();
+    };
+    match std::fs::read_to_string(path) {
+        Ok(table) => {
+            let levels = Box::into_raw(Box::new(SudachiLevels::parse(&table)));
+            leak::track(levels, "SudachiLevels");
+            levels
+        }
+        Err(e) => {
+            eprintln!("Failed to read level table: {}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a level table
+#[no_mangle]
+pub extern "C" fn sudachi_levels_close(levels: *mut SudachiLevels) {
+    if !levels.is_null() {
+        leak::release(levels);
+        unsafe {
+            let _ = Box::from_raw(levels);
+        }
+    }
+}
+
+fn is_word(token: &TokenData) -> bool {
+    token.class == SudachiTokenClass::Word
+}
+
+/// Word that carries meaning, as opposed to grammar
+pub(crate) fn is_content(token: &TokenData) -> bool {
+    is_word(token)
+        && !matches!(
+            token.pos.first().map_or("", String::as_str),
+            "助詞" | "助動詞" | "接頭辞" | "接尾辞" | "補助記号" | "空白"
+        )
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    texts: usize,
+    failed: usize,
+    /// Words of every sentence
+    sentences: Vec<usize>,
+    words: u64,
+    oov: u64,
+    /// Characters of words, and kanji among them
+    characters: u64,
+    kanji: u64,
+    content_words: u64,
+    levels: BTreeMap<u8, u64>,
+}
+
+impl Report {
+    fn add(&mut self, tokens: &[TokenData], levels: Option<&SudachiLevels>) {
+        self.texts += 1;
+        for range in sentences(tokens, 0) {
+            let words = tokens[range].iter().filter(|t| is_word(t)).count();
+            if words > 0 {
+                self.sentences.push(words);
+            }
+        }
+        for token in tokens.iter().filter(|t| is_word(t)) {
+            self.words += 1;
+            if token.flags & SUDACHI_TOKEN_OOV != 0 {
+                self.oov += 1;
+            }
+            for c in token.surface.chars() {
+                self.characters += 1;
+                self.kanji += u64::from(is_kanji(c));
+            }
+            if is_content(token) {
+                self.content_words += 1;
+                if let Some(level) = levels.and_then(|l| l.level(token)) {
+                    *self.levels.entry(level).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    fn json(&self) -> Value {
+        let rate = |n: u64, total: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                n as f64 / total as f64
+            }
+        };
+        let mut lengths = self.sentences.clone();
+        lengths.sort_unstable();
+        let percentile = |p: usize| {
+            lengths
+                .get((lengths.len() * p / 100).min(lengths.len().saturating_sub(1)))
+                .copied()
+                .unwrap_or(0)
+        };
+        let mut histogram = [0u64; BUCKETS];
+        for &n in &lengths {
+            histogram[((n - 1) / BUCKET_WORDS).min(BUCKETS - 1)] += 1;
+        }
+        let listed: u64 = self.levels.values().sum();
+        json!({
+            "texts": self.texts,
+            "failed_texts": self.failed,
+            "sentences": lengths.len(),
+            "words": self.words,
+            "sentence_length": {
+                "mean": rate(self.words_in_sentences(), lengths.len() as u64),
+                "median": percentile(50),
+                "p90": percentile(90),
+                "max": lengths.last().copied().unwrap_or(0),
+                "bucket_words": BUCKET_WORDS,
+                "histogram": histogram,
+            },
+            "kanji_density": rate(self.kanji, self.characters),
+            "oov_rate": rate(self.oov, self.words),
+            "levels": self.levels.iter()
+                .map(|(&level, &words)| json!({ "level": level, "words": words }))
+                .collect::<Vec<_>>(),
+            "unlisted_words": self.content_words - listed,
+        })
+    }
+
+    fn words_in_sentences(&self) -> u64 {
+        self.sentences.iter().map(|&n| n as u64).sum()
+    }
+}
+
+/// Build a readability report of many texts (e.g. the pages of a volume)
+/// Texts are analyzed on threads workers (0 uses one per available core).
+/// levels may be NULL to leave the level histogram empty. Texts that are
+/// NULL, not valid UTF-8 or fail to analyze are counted in failed_texts.
+/// Returns the report as JSON, or NULL if tokenizer or texts is NULL (caller
+/// must free with sudachi_free_string)
+#[no_mangle]
+pub extern "C" fn sudachi_readability_report(
+    tokenizer: *mut SudachiTokenizer,
+    texts: *const *const c_char,
+    text_count: usize,
+    mode: SudachiTokenMode,
+    levels: *const SudachiLevels,
+    threads: usize,
+) -> *mut c_char {
+    if tokenizer.is_null() || texts.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let levels = unsafe { levels.as_ref() };
+    let texts: Vec<Option<&str>> = unsafe { std::slice::from_raw_parts(texts, text_count) }
+        .iter()
+        .map(|&t| c_str(t))
+        .collect();
+    let tracker = Tracker::new(None, 0, 0);
+    let results = analyze_all(tokenizer, &texts, mode, REPORT_FIELDS, threads, &tracker);
+
+    let mut report = Report::default();
+    for result in &results {
+        match result {
+            Some(tokens) => report.add(tokens, levels),
+            None => report.failed += 1,
+        }
+    }
+    let json = into_c_string(&report.json().to_string());
+    leak::track(json, "string");
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+
+    fn tokens_of(words: &[(&str, &str, &str)]) -> Vec<TokenData> {
+        let mut begin = 0;
+        words
+            .iter()
+            .map(|&(surface, reading, pos)| {
+                let mut token =
+                    language::opaque(begin, surface, SudachiScript::Japanese, REPORT_FIELDS);
+                token.class = crate::symbol::token_class(surface);
+                token.dictionary_form = surface.to_string();
+                token.dictionary_reading = reading.to_string();
+                token.pos = pos.split(',').map(str::to_string).collect();
+                begin += surface.len();
+                token
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_report() {
+        let levels =
+            SudachiLevels::parse("# word\treading\tlevel\n猫\tねこ\t5\n魚\t\t4\n猫\tびょう\t1\n");
+        let mut report = Report::default();
+        report.add(
+            &tokens_of(&[
+                ("猫", "ネコ", "名詞,普通名詞"),
+                ("が", "ガ", "助詞,格助詞"),
+                ("魚", "サカナ", "名詞,普通名詞"),
+                ("を", "ヲ", "助詞,格助詞"),
+                ("食べる", "タベル", "動詞,一般"),
+                ("。", "。", "補助記号,句点"),
+                ("猫", "ネコ", "名詞,普通名詞"),
+            ]),
+            Some(&levels),
+        );
+        report.failed += 1;
+
+        let json = report.json();
+        assert_eq!(json["sentences"], 2);
+        assert_eq!(json["words"], 6);
+        assert_eq!(json["failed_texts"], 1);
+        assert_eq!(json["sentence_length"]["median"], 5);
+        assert_eq!(json["sentence_length"]["histogram"][0], 2);
+        assert_eq!(json["kanji_density"], 4.0 / 8.0);
+        assert_eq!(
+            json["levels"],
+            json!([{ "level": 4, "words": 1 }, { "level": 5, "words": 2 }])
+        );
+        assert_eq!(json["unlisted_words"], 1);
+    }
+}
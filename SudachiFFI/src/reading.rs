@@ -0,0 +1,212 @@
+// Reading post-processing
+// Readings come from the dictionary (or are the surface itself for unknown
+// words), so stylized manga text leaves iteration marks and stretched vowels
+// (あぁぁ, すごーーい) in them, and long vowels are spelled either way. These
+// opt-in passes clean readings up for furigana and text-to-speech. Unknown
+// words, which have no reading at all, can be read first (oov.rs).
+
+use crate::kana::{is_katakana, to_hiragana, voiced};
+use crate::{
+    handle, SudachiTokenizer, TokenData, SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_READING,
+};
+
+// Flags for sudachi_set_reading_options
+/// Spell long vowels out: オー -> オウ, ネー -> ネイ, カー -> カア
+pub const SUDACHI_READING_EXPAND_LONG_VOWELS: u32 = 1 << 0;
+/// Write long vowels with ー: オウ -> オー, ネイ -> ネー (ignored when expanding)
+pub const SUDACHI_READING_COLLAPSE_LONG_VOWELS: u32 = 1 << 1;
+/// Replace iteration marks with the kana they repeat: ススム, スゞキ
+pub const SUDACHI_READING_ITERATION_MARKS: u32 = 1 << 2;
+/// Turn stretches of small vowels and ー into one ー: アァァ -> アー
+pub const SUDACHI_READING_SMALL_KANA_STRETCH: u32 = 1 << 3;
+/// Read unknown words written in kana as themselves, in katakana (oov.rs)
+pub const SUDACHI_READING_OOV_KANA: u32 = 1 << 4;
+/// Guess the reading of unknown words with kanji one kanji at a time,
+/// flagging them SUDACHI_TOKEN_GUESSED (oov.rs)
+pub const SUDACHI_READING_OOV_KANJI: u32 = 1 << 5;
+
+/// Vowel rows in hiragana, あ to お
+const ROWS: [&str; 5] = [
+    "あぁかがさざただなはばぱまやゃらわゎ",
+    "いぃきぎしじちぢにひびぴみりゐ",
+    "うぅくぐすずつづぬふぶぷむゆゅるゔ",
+    "えぇけげせぜてでねへべぺめれゑ",
+    "おぉこごそぞとどのほぼぽもよょろを",
+];
+
+const VOWELS: [char; 5] = ['あ', 'い', 'う', 'え', 'お'];
+const SMALL_VOWELS: [char; 5] = ['ぁ', 'ぃ', 'ぅ', 'ぇ', 'ぉ'];
+
+/// Vowel row (0 for あ to 4 for お) of a kana
+fn vowel(c: char) -> Option<usize> {
+    let c = to_hiragana(c);
+    ROWS.iter().position(|row| row.contains(c))
+}
+
+/// Hiragana c in the script of like
+fn same_script(c: char, like: char) -> char {
+    if is_katakana(like) {
+        char::from_u32(c as u32 + 0x60).unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+/// The kana that lengthens a vowel row in spelled-out form (えい, おう)
+fn long_vowel(row: usize) -> char {
+    match row {
+        3 => 'い',
+        4 => 'う',
+        _ => VOWELS[row],
+    }
+}
+
+fn iteration_marks(reading: &str) -> String {
+    let mut out = String::with_capacity(reading.len());
+    let mut last = None;
+    for c in reading.chars() {
+        let c = match (c, last) {
+            ('ゝ' | 'ヽ' | '々', Some(prev)) => prev,
+            ('ゞ' | 'ヾ', Some(prev)) => voiced(prev),
+            _ => c,
+        };
+        out.push(c);
+        last = Some(c);
+    }
+    out
+}
+
+fn small_kana_stretch(reading: &str) -> String {
+    let mut out = String::with_capacity(reading.len());
+    let mut row = None;
+    let mut stretched = false;
+    for c in reading.chars() {
+        let small = SMALL_VOWELS
+            .iter()
+            .position(|&s| s == to_hiragana(c))
+            .filter(|&r| row == Some(r));
+        if c == 'ー' || small.is_some() {
+            if !stretched && row.is_some() {
+                out.push('ー');
+                stretched = true;
+            } else if row.is_none() {
+                out.push(c);
+            }
+            continue;
+        }
+        out.push(c);
+        row = vowel(c);
+        stretched = false;
+    }
+    out
+}
+
+fn expand_long_vowels(reading: &str) -> String {
+    let mut out = String::with_capacity(reading.len());
+    let mut last: Option<(char, usize)> = None;
+    for c in reading.chars() {
+        match (c, last) {
+            ('ー', Some((prev, row))) => out.push(same_script(long_vowel(row), prev)),
+            _ => {
+                out.push(c);
+                last = vowel(c).map(|row| (c, row));
+            }
+        }
+    }
+    out
+}
+
+fn collapse_long_vowels(reading: &str) -> String {
+    let mut out = String::with_capacity(reading.len());
+    let mut row = None;
+    for c in reading.chars() {
+        let h = to_hiragana(c);
+        if matches!(row, Some(r) if h == long_vowel(r) || h == VOWELS[r]) {
+            out.push('ー');
+            row = None;
+        } else {
+            out.push(c);
+            row = vowel(c);
+        }
+    }
+    out
+}
+
+/// Apply SUDACHI_READING_* flags to a reading
+pub(crate) fn process(reading: &str, flags: u32) -> String {
+    let mut reading = reading.to_string();
+    if flags & SUDACHI_READING_ITERATION_MARKS != 0 {
+        reading = iteration_marks(&reading);
+    }
+    if flags & SUDACHI_READING_SMALL_KANA_STRETCH != 0 {
+        reading = small_kana_stretch(&reading);
+    }
+    if flags & SUDACHI_READING_EXPAND_LONG_VOWELS != 0 {
+        reading = expand_long_vowels(&reading);
+    } else if flags & SUDACHI_READING_COLLAPSE_LONG_VOWELS != 0 {
+        reading = collapse_long_vowels(&reading);
+    }
+    reading
+}
+
+/// Apply SUDACHI_READING_* flags to the readings and dictionary readings of
+/// tokens
+pub(crate) fn process_tokens(tokens: &mut [TokenData], flags: u32) {
+    for token in tokens {
+        if token.fields & SUDACHI_FIELD_READING != 0 {
+            token.reading = process(&token.reading, flags);
+        }
+        if token.fields & SUDACHI_FIELD_DICTIONARY_READING != 0 {
+            token.dictionary_reading = process(&token.dictionary_reading, flags);
+        }
+    }
+}
+
+/// Set the reading post-processing of a handle
+/// flags is a mask of SUDACHI_READING_* values (0 by default, none).
+/// Furigana uses the processed readings too.
+/// Returns false if tokenizer is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_reading_options(
+    tokenizer: *mut SudachiTokenizer,
+    flags: u32,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.reading = flags);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process() {
+        let expand = SUDACHI_READING_EXPAND_LONG_VOWELS;
+        let collapse = SUDACHI_READING_COLLAPSE_LONG_VOWELS;
+        assert_eq!(process("オトーサン", expand), "オトウサン");
+        assert_eq!(process("ネーサン", expand), "ネイサン");
+        assert_eq!(process("すごーい", expand), "すごうい");
+        assert_eq!(process("オトウサン", collapse), "オトーサン");
+        assert_eq!(process("センセイ", collapse), "センセー");
+        assert_eq!(process("オカアサン", collapse), "オカーサン");
+        assert_eq!(process("オトウサン", expand | collapse), "オトウサン");
+
+        let marks = SUDACHI_READING_ITERATION_MARKS;
+        assert_eq!(process("スヽム", marks), "ススム");
+        assert_eq!(process("いすゞ", marks), "いすず");
+        assert_eq!(process("ヽ", marks), "ヽ");
+
+        let stretch = SUDACHI_READING_SMALL_KANA_STRETCH;
+        assert_eq!(process("あぁぁ", stretch), "あー");
+        assert_eq!(process("スゴーーーイ", stretch), "スゴーイ");
+        assert_eq!(process("ねぇぇー", stretch), "ねー");
+        assert_eq!(process("ファイト", stretch), "ファイト");
+        assert_eq!(process("ーあ", stretch), "ーあ");
+        assert_eq!(process("あぁぁ", stretch | expand), "ああ");
+
+        assert_eq!(process("トウキョウ", 0), "トウキョウ");
+    }
+}
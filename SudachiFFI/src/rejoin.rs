@@ -0,0 +1,259 @@
+// OCR line rejoining
+// OCR returns text one line at a time: one column at a time for vertical
+// text, which is how manga set most dialogue. Tokenizing each line on its own
+// breaks every word that runs across a column. Lines are regrouped into their
+// text blocks (speech bubbles, captions) and joined in reading order first:
+// columns right to left for vertical blocks, rows top to bottom otherwise.
+//
+// Line boxes decide the order, not the OCR's output order, which is often
+// wrong for vertical text. A line belongs to the column (row) whose span
+// contains its center, so tate-chū-yoko — the two or three upright digits or
+// marks set across a vertical column (12, !?) that OCR reports as a separate
+// horizontal fragment — lands inside its column instead of starting one.
+// Fragments of a column are read top to bottom.
+//
+// Japanese is joined without separators. Latin text gets a space between
+// words that meet at a line break, unless the line ends in a hyphen between
+// letters, which is dropped (hyphen-/ation -> hyphenation).
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::{c_str, into_c_string, leak};
+
+/// One line of OCR output
+/// Boxes use a top-left origin with y growing downward (flip Vision's
+/// normalized rects); any unit works as long as it is the same for all
+/// lines.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SudachiOcrLine {
+    /// UTF-8 text of the line
+    pub text: *const c_char,
+    /// Text block the line belongs to; lines of different blocks are never
+    /// joined
+    pub block: u32,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Set in vertical text; a block is vertical if any of its lines is
+    pub vertical: bool,
+}
+
+/// A line with its text borrowed
+#[derive(Debug, Clone, Copy)]
+struct Line<'a> {
+    text: &'a str,
+    block: u32,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    vertical: bool,
+}
+
+impl Line<'_> {
+    /// Center and extent across the reading direction of the block
+    /// (horizontal in vertical text)
+    fn across(&self, vertical: bool) -> (f32, f32, f32) {
+        if vertical {
+            (self.x + self.width / 2.0, self.x, self.x + self.width)
+        } else {
+            (self.y + self.height / 2.0, self.y, self.y + self.height)
+        }
+    }
+
+    /// Position along the reading direction
+    fn along(&self, vertical: bool) -> f32 {
+        if vertical {
+            self.y
+        } else {
+            self.x
+        }
+    }
+}
+
+fn is_hyphen(c: char) -> bool {
+    matches!(c, '-' | '\u{2010}' | '\u{00AD}')
+}
+
+/// Append a line to a block's text, joining it to what came before
+/// Returns the offset of the line's text.
+fn append(out: &mut String, start: usize, text: &str) -> usize {
+    let next = text.chars().next();
+    let letter = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphabetic());
+    let mut prev = out[start..].chars().rev();
+    let last = prev.next();
+    if last.is_some_and(is_hyphen) && letter(prev.next()) && letter(next) {
+        out.truncate(out.len() - last.map_or(0, char::len_utf8));
+    } else if last.is_some_and(|c| c.is_ascii_alphanumeric() || ",.;:!?".contains(c))
+        && next.is_some_and(|c| c.is_ascii_alphanumeric())
+    {
+        out.push(' ');
+    }
+    let offset = out.len();
+    out.push_str(text);
+    offset
+}
+
+/// Join lines into one line of text per block, blocks in order of their
+/// first line
+/// Returns the text and the offset of each line in it (None for lines that
+/// are empty).
+fn rejoin(lines: &[Line]) -> (String, Vec<Option<usize>>) {
+    let mut blocks: Vec<u32> = Vec::new();
+    for line in lines {
+        if !blocks.contains(&line.block) {
+            blocks.push(line.block);
+        }
+    }
+
+    let mut out = String::new();
+    let mut offsets = vec![None; lines.len()];
+    for block in blocks {
+        let mut members: Vec<usize> = (0..lines.len())
+            .filter(|&i| lines[i].block == block && !lines[i].text.trim().is_empty())
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        let vertical = members.iter().any(|&i| lines[i].vertical);
+        // Columns right to left, rows top to bottom
+        members.sort_by(|&a, &b| {
+            let (a, b) = (lines[a].across(vertical).0, lines[b].across(vertical).0);
+            if vertical {
+                b.total_cmp(&a)
+            } else {
+                a.total_cmp(&b)
+            }
+        });
+
+        let mut columns: Vec<Vec<usize>> = Vec::new();
+        // Extent of the current column
+        let mut span = (0.0, 0.0);
+        for i in members {
+            let (center, low, high) = lines[i].across(vertical);
+            match columns.last_mut() {
+                Some(column) if span.0 <= center && center <= span.1 => {
+                    column.push(i);
+                    span = (f32::min(span.0, low), f32::max(span.1, high));
+                }
+                _ => {
+                    columns.push(vec![i]);
+                    span = (low, high);
+                }
+            }
+        }
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        let start = out.len();
+        for mut column in columns {
+            column.sort_by(|&a, &b| {
+                lines[a]
+                    .along(vertical)
+                    .total_cmp(&lines[b].along(vertical))
+            });
+            for i in column {
+                let text = lines[i].text.trim();
+                // Tate-chū-yoko OCR'd with a gap (1 2)
+                let upright: String;
+                let text = if vertical && !lines[i].vertical && text.chars().count() <= 6 {
+                    upright = text.split_whitespace().collect();
+                    &upright
+                } else {
+                    text
+                };
+                offsets[i] = Some(append(&mut out, start, text));
+            }
+        }
+    }
+    (out, offsets)
+}
+
+/// Rejoin OCR lines into logical text before tokenization
+/// Lines are grouped by block and put in reading order by their boxes; each
+/// block becomes one line of the result, in order of the block's first line.
+/// If out_offsets is not NULL it receives, for each of the count lines, the
+/// byte offset of its text in the result (-1 for empty lines).
+/// Returns NULL if lines is NULL or a line's text is NULL or not valid UTF-8
+/// (caller must free with sudachi_free_string)
+#[no_mangle]
+pub extern "C" fn sudachi_rejoin_lines(
+    lines: *const SudachiOcrLine,
+    count: usize,
+    out_offsets: *mut i32,
+) -> *mut c_char {
+    if lines.is_null() {
+        return ptr::null_mut();
+    }
+    let mut borrowed = Vec::with_capacity(count);
+    for line in unsafe { std::slice::from_raw_parts(lines, count) } {
+        let Some(text) = c_str(line.text) else {
+            return ptr::null_mut();
+        };
+        borrowed.push(Line {
+            text,
+            block: line.block,
+            x: line.x,
+            y: line.y,
+            width: line.width,
+            height: line.height,
+            vertical: line.vertical,
+        });
+    }
+
+    let (text, offsets) = rejoin(&borrowed);
+    if !out_offsets.is_null() {
+        let out = unsafe { std::slice::from_raw_parts_mut(out_offsets, count) };
+        for (out, offset) in out.iter_mut().zip(offsets) {
+            *out = offset.map_or(-1, |o| o as i32);
+        }
+    }
+    let text = into_c_string(&text);
+    leak::track(text, "string");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str, block: u32, rect: [f32; 4], vertical: bool) -> Line<'_> {
+        let [x, y, width, height] = rect;
+        Line {
+            text,
+            block,
+            x,
+            y,
+            width,
+            height,
+            vertical,
+        }
+    }
+
+    #[test]
+    fn test_rejoin() {
+        let lines = [
+            // Second column first, as OCR often returns them
+            line("月", 0, [10.0, 0.0, 10.0, 10.0], true),
+            line("今日は", 0, [30.0, 0.0, 10.0, 30.0], true),
+            line("1 2", 0, [9.0, 10.0, 12.0, 8.0], false),
+            line("日です", 0, [10.0, 18.0, 10.0, 30.0], true),
+            line("晴れ", 0, [30.0, 30.0, 10.0, 20.0], true),
+            line("a hyphen-", 1, [0.0, 100.0, 40.0, 10.0], false),
+            line("ation  ", 1, [0.0, 112.0, 40.0, 10.0], false),
+            line("word", 1, [0.0, 124.0, 40.0, 10.0], false),
+            line(" ", 2, [0.0, 0.0, 1.0, 1.0], false),
+        ];
+        let (text, offsets) = rejoin(&lines);
+        assert_eq!(text, "今日は晴れ月12日です\na hyphenation word");
+        assert_eq!(offsets[1], Some(0));
+        assert_eq!(offsets[4], Some("今日は".len()));
+        assert_eq!(offsets[2], Some("今日は晴れ月".len()));
+        assert_eq!(offsets[6], Some(text.find("ation").unwrap()));
+        assert_eq!(offsets[8], None);
+    }
+}
@@ -0,0 +1,302 @@
+// Re-ranking hook
+// Apps have preferences that costs cannot express, e.g. segmentations whose
+// words are in the user's known-word list, or never splitting a term of the
+// book being read. A handle can take a callback that sees the n cheapest
+// segmentations of each chunk and scores them itself. The candidates are the
+// paths of the second decoding of costs.rs, in mode C with the handle's cost
+// overrides applied, cheapest first; the first is the analyzer's own choice
+// whenever no delta applies. The callback gets each path's cost as its score
+// and may change it, or set SUDACHI_PATH_VETO to rule the path out. The
+// lowest score wins (ties to the earlier path); if every path is vetoed the
+// first stands. The chosen path is then split for modes A and B. The
+// callback runs on the analyzing thread while the handle is locked, so it
+// must not call into the same handle.
+
+use std::os::raw::c_void;
+
+use sudachi::analysis::mlist::MorphemeList;
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
+use sudachi::dic::subset::InfoSubset;
+use sudachi::prelude::*;
+
+use crate::costs::{Path, Word};
+use crate::fingerprint::fnv;
+use crate::{handle, SudachiTokenizer};
+
+/// Most paths a callback can be given per chunk
+pub const SUDACHI_MAX_PATHS: usize = 16;
+/// Score that rules a path out
+pub const SUDACHI_PATH_VETO: i64 = i64::MAX;
+
+/// A word of a candidate path
+/// Offsets are byte offsets into the text passed to the callback.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SudachiPathWord {
+    pub begin: usize,
+    pub end: usize,
+    pub word_id: u32,
+    pub pos_id: u16,
+}
+
+/// A candidate segmentation of a chunk
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SudachiPath {
+    pub words: *const SudachiPathWord,
+    pub count: usize,
+    /// Word and connection costs, unknown words counting 0
+    pub cost: i64,
+}
+
+/// Scores the candidate paths of a chunk
+/// text is the chunk (UTF-8, not NUL-terminated); scores holds count costs
+/// on entry and the callback's scores on return. Called on the analyzing
+/// thread; paths and text are valid only during the call.
+pub type SudachiRerankCallback = Option<
+    extern "C" fn(
+        user_data: *mut c_void,
+        text: *const u8,
+        text_len: usize,
+        paths: *const SudachiPath,
+        count: usize,
+        scores: *mut i64,
+    ),
+>;
+
+type Callback = extern "C" fn(*mut c_void, *const u8, usize, *const SudachiPath, usize, *mut i64);
+
+/// Re-ranking hook of a handle
+#[derive(Clone, Copy)]
+pub(crate) struct Rerank {
+    paths: usize,
+    callback: Callback,
+    user_data: usize,
+}
+
+impl Rerank {
+    /// Paths given to the callback per chunk
+    pub(crate) fn paths(&self) -> usize {
+        self.paths
+    }
+
+    /// Continue a settings hash with the hook
+    /// Only the number of paths is hashed; callback addresses change from
+    /// launch to launch.
+    pub(crate) fn hash(&self, hash: u64) -> u64 {
+        let hash = fnv(hash, b"rerank");
+        fnv(hash, &(self.paths as u64).to_le_bytes())
+    }
+
+    /// Index of the path the callback scores lowest
+    /// morphemes is the lattice's mode C result for text.
+    pub(crate) fn choose<D: DictionaryAccess>(
+        &self,
+        morphemes: &MorphemeList<D>,
+        text: &str,
+        paths: &[Path],
+    ) -> SudachiResult<usize> {
+        let lexicon = morphemes.dict().lexicon();
+        let mut words: Vec<Vec<SudachiPathWord>> = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mut path_words = Vec::with_capacity(path.words.len());
+            for &word in &path.words {
+                path_words.push(match word {
+                    Word::Lattice(i) => {
+                        let m = morphemes.get(i);
+                        SudachiPathWord {
+                            begin: m.begin(),
+                            end: m.end(),
+                            word_id: m.word_id().as_raw(),
+                            pos_id: m.part_of_speech_id(),
+                        }
+                    }
+                    Word::Entry {
+                        begin,
+                        end,
+                        word_id,
+                    } => SudachiPathWord {
+                        begin,
+                        end,
+                        word_id: word_id.as_raw(),
+                        pos_id: lexicon
+                            .get_word_info_subset(word_id, InfoSubset::POS_ID)?
+                            .pos_id(),
+                    },
+                });
+            }
+            words.push(path_words);
+        }
+        let c_paths: Vec<SudachiPath> = paths
+            .iter()
+            .zip(&words)
+            .map(|(path, words)| SudachiPath {
+                words: words.as_ptr(),
+                count: words.len(),
+                cost: path.cost,
+            })
+            .collect();
+        let mut scores: Vec<i64> = paths.iter().map(|p| p.cost).collect();
+        (self.callback)(
+            self.user_data as *mut c_void,
+            text.as_ptr(),
+            text.len(),
+            c_paths.as_ptr(),
+            c_paths.len(),
+            scores.as_mut_ptr(),
+        );
+        Ok(best(&scores))
+    }
+}
+
+/// Index of the lowest score that is not a veto, 0 if all are
+fn best(scores: &[i64]) -> usize {
+    scores
+        .iter()
+        .enumerate()
+        .filter(|&(_, &score)| score != SUDACHI_PATH_VETO)
+        .min_by_key(|&(i, &score)| (score, i))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Set the re-ranking hook of a handle, given the paths cheapest
+/// segmentations of each chunk, or clear it with a NULL callback
+/// paths must be 1..=SUDACHI_MAX_PATHS. Each chunk is then decoded a second
+/// time (see README). Returns false if tokenizer is NULL or paths is out of
+/// range, leaving the hook unchanged.
+#[no_mangle]
+pub extern "C" fn sudachi_set_rerank(
+    tokenizer: *mut SudachiTokenizer,
+    paths: usize,
+    callback: SudachiRerankCallback,
+    user_data: *mut c_void,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Some(callback) = callback else {
+        tokenizer.set_options(|o| o.rerank = None);
+        return true;
+    };
+    if !(1..=SUDACHI_MAX_PATHS).contains(&paths) {
+        return false;
+    }
+    let rerank = Rerank {
+        paths,
+        callback,
+        user_data: user_data as usize,
+    };
+    tokenizer.set_options(|o| o.rerank = Some(rerank));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::ptr;
+
+    use super::*;
+    use crate::{
+        c_str, sudachi_free_tokenizer, sudachi_free_tokens, sudachi_tokenize, testdict,
+        SudachiTokenMode,
+    };
+
+    /// What the callback saw, and whether it vetoes the first path
+    #[derive(Default)]
+    struct Seen {
+        first: Vec<(usize, usize, u32)>,
+        count: usize,
+        veto: bool,
+    }
+
+    extern "C" fn record(
+        user_data: *mut c_void,
+        _text: *const u8,
+        _text_len: usize,
+        paths: *const SudachiPath,
+        count: usize,
+        scores: *mut i64,
+    ) {
+        let seen = unsafe { &mut *(user_data as *mut Seen) };
+        let first = unsafe { &*paths };
+        seen.first = unsafe { std::slice::from_raw_parts(first.words, first.count) }
+            .iter()
+            .map(|w| (w.begin, w.end, w.word_id))
+            .collect();
+        seen.count = count;
+        if seen.veto {
+            unsafe { *scores = SUDACHI_PATH_VETO };
+        }
+    }
+
+    /// Spans, word ids and surfaces of a mode C analysis
+    fn analyze(tokenizer: *mut SudachiTokenizer, text: &str) -> Vec<(usize, usize, u32, String)> {
+        let text = CString::new(text).unwrap();
+        let mut count = 0;
+        let result = sudachi_tokenize(tokenizer, text.as_ptr(), SudachiTokenMode::C, &mut count);
+        assert!(!result.is_null());
+        let tokens = unsafe { std::slice::from_raw_parts(result, count) }
+            .iter()
+            .map(|&t| {
+                let t = unsafe { &*t };
+                let surface = c_str(t.surface).unwrap().to_string();
+                (t.begin as usize, t.end as usize, t.word_id, surface)
+            })
+            .collect();
+        sudachi_free_tokens(result, count);
+        tokens
+    }
+
+    #[test]
+    fn test_rerank() {
+        let tokenizer = testdict::tokenizer();
+        let plain = analyze(tokenizer, "日本語");
+        let mut seen = Seen::default();
+        let user_data = &mut seen as *mut Seen as *mut c_void;
+
+        assert!(!sudachi_set_rerank(tokenizer, 0, Some(record), user_data));
+        assert!(!sudachi_set_rerank(
+            tokenizer,
+            SUDACHI_MAX_PATHS + 1,
+            Some(record),
+            user_data
+        ));
+        assert!(!sudachi_set_rerank(
+            ptr::null_mut(),
+            2,
+            Some(record),
+            user_data
+        ));
+
+        // Without cost overrides the first path is the analyzer's own result
+        assert!(sudachi_set_rerank(tokenizer, 2, Some(record), user_data));
+        assert_eq!(analyze(tokenizer, "日本語"), plain);
+        assert_eq!(seen.count, 2);
+        let analyzed: Vec<_> = plain.iter().map(|&(b, e, id, _)| (b, e, id)).collect();
+        assert_eq!(seen.first, analyzed);
+
+        // Vetoing it picks the next segmentation
+        seen.veto = true;
+        let vetoed = analyze(tokenizer, "日本語");
+        let surfaces: Vec<_> = vetoed.iter().map(|t| t.3.as_str()).collect();
+        assert_eq!(surfaces, ["日本", "語"]);
+
+        // A NULL callback clears the hook
+        seen.count = 0;
+        assert!(sudachi_set_rerank(tokenizer, 2, None, ptr::null_mut()));
+        assert_eq!(analyze(tokenizer, "日本語"), plain);
+        assert_eq!(seen.count, 0);
+
+        sudachi_free_tokenizer(tokenizer);
+    }
+
+    #[test]
+    fn test_best() {
+        assert_eq!(best(&[300, 100, 200]), 1);
+        assert_eq!(best(&[100, 50, 50]), 1);
+        assert_eq!(best(&[100, SUDACHI_PATH_VETO, -5]), 2);
+        assert_eq!(best(&[SUDACHI_PATH_VETO, 100]), 1);
+        assert_eq!(best(&[SUDACHI_PATH_VETO, SUDACHI_PATH_VETO]), 0);
+    }
+}
@@ -0,0 +1,367 @@
+// Result handles
+// sudachi_tokenize hands out an array of separately allocated tokens, and
+// the caller must pass the array back with its exact count and must not
+// free a token of it on its own; getting either wrong is undefined
+// behavior that no check can fully catch. A SudachiResult is one opaque
+// handle instead: the caller reads the token count, the numeric fields of a
+// token and each string field through accessors that check the index, and
+// frees everything with one call.
+//
+// Since nothing outside the library sees how tokens are stored, they are
+// kept compactly: the numeric fields in one array and the strings of all
+// tokens, NUL-terminated, in one buffer, so a result costs the same few
+// allocations whatever its size. Strings stay valid until the result is
+// freed.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::script::SudachiScript;
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    c_str, handle, trace, SudachiTokenMode, SudachiTokenizer, TokenData,
+    SUDACHI_FIELD_DICTIONARY_FORM, SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_NORMALIZED_FORM,
+    SUDACHI_FIELD_POS, SUDACHI_FIELD_READING, SUDACHI_FIELD_SURFACE,
+};
+
+/// Numeric fields of a token of a SudachiResult (see SudachiToken)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SudachiTokenInfo {
+    /// Start byte offset in the input
+    pub(crate) begin: i32,
+    /// End byte offset in the input (exclusive)
+    pub(crate) end: i32,
+    /// Raw Sudachi word id (dictionary + index)
+    pub(crate) word_id: u32,
+    /// Index into the dictionary POS table
+    pub(crate) pos_id: u16,
+    /// Script of the surface, a hint for non-Japanese text
+    pub(crate) script: SudachiScript,
+    /// Word, symbol, emoji or opaque
+    pub(crate) class: SudachiTokenClass,
+    /// Notes on how the token was formed (SUDACHI_TOKEN_* bits)
+    pub(crate) flags: u8,
+    /// Identifier of the lemma, 0 if not requested or not a word
+    pub(crate) lemma_key: u64,
+}
+
+/// String fields, in the order their offsets are stored
+const STRING_FIELDS: [u32; 6] = [
+    SUDACHI_FIELD_SURFACE,
+    SUDACHI_FIELD_READING,
+    SUDACHI_FIELD_DICTIONARY_FORM,
+    SUDACHI_FIELD_NORMALIZED_FORM,
+    SUDACHI_FIELD_POS,
+    SUDACHI_FIELD_DICTIONARY_READING,
+];
+
+/// Offset of a field that was not selected
+const ABSENT: usize = usize::MAX;
+
+/// Opaque handle to the tokens of one analysis
+pub struct SudachiResult {
+    infos: Vec<SudachiTokenInfo>,
+    /// Start of each string field in strings, by STRING_FIELDS index
+    offsets: Vec<[usize; STRING_FIELDS.len()]>,
+    /// Strings of all tokens, each followed by a NUL
+    strings: Vec<u8>,
+}
+
+impl SudachiResult {
+    /// Store tokens, dropping those whose surface contains a NUL like
+    /// TokenData::into_raw does
+    pub(crate) fn new(tokens: Vec<TokenData>) -> SudachiResult {
+        let mut result = SudachiResult {
+            infos: Vec::with_capacity(tokens.len()),
+            offsets: Vec::with_capacity(tokens.len()),
+            strings: Vec::new(),
+        };
+        for token in tokens {
+            let has = |bit: u32| token.fields & bit != 0;
+            if has(SUDACHI_FIELD_SURFACE) && token.surface.contains('\0') {
+                continue;
+            }
+            let pos = if has(SUDACHI_FIELD_POS) {
+                serde_json::to_string(&token.pos).unwrap_or_default()
+            } else {
+                String::new()
+            };
+            let values = [
+                token.surface.as_str(),
+                &token.reading,
+                &token.dictionary_form,
+                &token.normalized_form,
+                &pos,
+                &token.dictionary_reading,
+            ];
+            let mut offsets = [ABSENT; STRING_FIELDS.len()];
+            for (offset, (&bit, value)) in offsets.iter_mut().zip(STRING_FIELDS.iter().zip(values))
+            {
+                if has(bit) && !value.contains('\0') {
+                    *offset = result.strings.len();
+                    result.strings.extend_from_slice(value.as_bytes());
+                    result.strings.push(0);
+                }
+            }
+            result.offsets.push(offsets);
+            result.infos.push(SudachiTokenInfo {
+                begin: token.begin as i32,
+                end: token.end as i32,
+                word_id: token.word_id,
+                pos_id: token.pos_id,
+                script: token.script,
+                class: token.class,
+                flags: token.flags,
+                lemma_key: token.lemma_key,
+            });
+        }
+        result
+    }
+
+    /// String field of a token, if the index is in range and it was selected
+    fn field(&self, index: usize, field: u32) -> Option<*const c_char> {
+        let slot = STRING_FIELDS.iter().position(|&bit| bit == field)?;
+        let offset = self.offsets.get(index)?[slot];
+        (offset != ABSENT).then(|| self.strings[offset..].as_ptr() as *const c_char)
+    }
+}
+
+/// Tokenize text into a result handle, filling the string fields selected
+/// by a SUDACHI_FIELD_* mask
+/// Returns NULL if an argument is NULL or the analysis failed (caller must
+/// free with sudachi_free_result)
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_result(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    fields: u32,
+) -> *mut SudachiResult {
+    if tokenizer.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Some(text) = c_str(text) else {
+        return ptr::null_mut();
+    };
+    let Ok(tokens) = tokenizer.analyze(text, mode.into(), fields) else {
+        return ptr::null_mut();
+    };
+    trace::span!("marshal");
+    handle::into_raw(SudachiResult::new(tokens))
+}
+
+/// Number of tokens of a result, 0 if result is NULL
+#[no_mangle]
+pub extern "C" fn sudachi_result_count(result: *const SudachiResult) -> usize {
+    if result.is_null() {
+        return 0;
+    }
+    unsafe { handle::borrow(result) }.infos.len()
+}
+
+/// Copy the numeric fields of token index into *out_info
+/// Returns false if an argument is NULL or index is out of range
+#[no_mangle]
+pub extern "C" fn sudachi_result_get(
+    result: *const SudachiResult,
+    index: usize,
+    out_info: *mut SudachiTokenInfo,
+) -> bool {
+    if result.is_null() || out_info.is_null() {
+        return false;
+    }
+    let Some(&info) = unsafe { handle::borrow(result) }.infos.get(index) else {
+        return false;
+    };
+    unsafe {
+        *out_info = info;
+    }
+    true
+}
+
+/// String field of token index, one of the SUDACHI_FIELD_* bits except
+/// SUDACHI_FIELD_LEMMA_KEY (POS is a JSON array)
+/// The string is owned by the result and valid until sudachi_free_result.
+/// Returns NULL if result is NULL, index is out of range, field is not a
+/// single string field or was not selected.
+#[no_mangle]
+pub extern "C" fn sudachi_result_field(
+    result: *const SudachiResult,
+    index: usize,
+    field: u32,
+) -> *const c_char {
+    if result.is_null() {
+        return ptr::null();
+    }
+    unsafe { handle::borrow(result) }
+        .field(index, field)
+        .unwrap_or(ptr::null())
+}
+
+// Per-field accessors
+// Each reads one field of token index, so a binding that uses only these
+// never depends on the layout of a struct and keeps working as fields are
+// added. Strings are NULL and numbers 0 (offsets -1) if result is NULL or
+// index is out of range.
+
+/// Token index of a result
+fn token(result: *const SudachiResult, index: usize) -> Option<SudachiTokenInfo> {
+    if result.is_null() {
+        return None;
+    }
+    unsafe { handle::borrow(result) }.infos.get(index).copied()
+}
+
+/// Surface form as it appears in the input, NULL if not selected
+#[no_mangle]
+pub extern "C" fn sudachi_token_surface(
+    result: *const SudachiResult,
+    index: usize,
+) -> *const c_char {
+    sudachi_result_field(result, index, SUDACHI_FIELD_SURFACE)
+}
+
+/// Reading in katakana, NULL if not selected
+#[no_mangle]
+pub extern "C" fn sudachi_token_reading(
+    result: *const SudachiResult,
+    index: usize,
+) -> *const c_char {
+    sudachi_result_field(result, index, SUDACHI_FIELD_READING)
+}
+
+/// Dictionary (base) form, NULL if not selected
+#[no_mangle]
+pub extern "C" fn sudachi_token_dictionary_form(
+    result: *const SudachiResult,
+    index: usize,
+) -> *const c_char {
+    sudachi_result_field(result, index, SUDACHI_FIELD_DICTIONARY_FORM)
+}
+
+/// Normalized form, NULL if not selected
+#[no_mangle]
+pub extern "C" fn sudachi_token_normalized_form(
+    result: *const SudachiResult,
+    index: usize,
+) -> *const c_char {
+    sudachi_result_field(result, index, SUDACHI_FIELD_NORMALIZED_FORM)
+}
+
+/// POS tags as a JSON array string, NULL if not selected
+#[no_mangle]
+pub extern "C" fn sudachi_token_pos(result: *const SudachiResult, index: usize) -> *const c_char {
+    sudachi_result_field(result, index, SUDACHI_FIELD_POS)
+}
+
+/// Reading of the dictionary form in katakana, NULL if not selected
+#[no_mangle]
+pub extern "C" fn sudachi_token_dictionary_reading(
+    result: *const SudachiResult,
+    index: usize,
+) -> *const c_char {
+    sudachi_result_field(result, index, SUDACHI_FIELD_DICTIONARY_READING)
+}
+
+/// Start byte offset in the input, -1 if out of range
+#[no_mangle]
+pub extern "C" fn sudachi_token_begin(result: *const SudachiResult, index: usize) -> i32 {
+    token(result, index).map_or(-1, |t| t.begin)
+}
+
+/// End byte offset in the input (exclusive), -1 if out of range
+#[no_mangle]
+pub extern "C" fn sudachi_token_end(result: *const SudachiResult, index: usize) -> i32 {
+    token(result, index).map_or(-1, |t| t.end)
+}
+
+/// Raw Sudachi word id
+#[no_mangle]
+pub extern "C" fn sudachi_token_word_id(result: *const SudachiResult, index: usize) -> u32 {
+    token(result, index).map_or(0, |t| t.word_id)
+}
+
+/// Index into the dictionary POS table
+#[no_mangle]
+pub extern "C" fn sudachi_token_pos_id(result: *const SudachiResult, index: usize) -> u16 {
+    token(result, index).map_or(0, |t| t.pos_id)
+}
+
+/// Script of the surface
+#[no_mangle]
+pub extern "C" fn sudachi_token_script(
+    result: *const SudachiResult,
+    index: usize,
+) -> SudachiScript {
+    token(result, index).map_or(SudachiScript::Japanese, |t| t.script)
+}
+
+/// Class of the token
+#[no_mangle]
+pub extern "C" fn sudachi_token_class(
+    result: *const SudachiResult,
+    index: usize,
+) -> SudachiTokenClass {
+    token(result, index).map_or(SudachiTokenClass::Word, |t| t.class)
+}
+
+/// SUDACHI_TOKEN_* flags of the token
+#[no_mangle]
+pub extern "C" fn sudachi_token_flags(result: *const SudachiResult, index: usize) -> u8 {
+    token(result, index).map_or(0, |t| t.flags)
+}
+
+/// Lemma key of the token, 0 if not requested
+#[no_mangle]
+pub extern "C" fn sudachi_token_lemma_key(result: *const SudachiResult, index: usize) -> u64 {
+    token(result, index).map_or(0, |t| t.lemma_key)
+}
+
+/// Free a result and every string read from it
+#[no_mangle]
+pub extern "C" fn sudachi_free_result(result: *mut SudachiResult) {
+    if !result.is_null() {
+        unsafe {
+            let _ = handle::from_raw(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::SUDACHI_FIELD_LEMMA_KEY;
+
+    #[test]
+    fn test_result() {
+        let fields = SUDACHI_FIELD_SURFACE | SUDACHI_FIELD_POS;
+        let mut cat = language::opaque(0, "猫", SudachiScript::Japanese, fields);
+        cat.pos = vec!["名詞".to_string()];
+        let nul = language::opaque(3, "a\0b", SudachiScript::Latin, fields);
+        let dog = language::opaque(6, "犬", SudachiScript::Japanese, fields);
+        let result = handle::into_raw(SudachiResult::new(vec![cat, nul, dog]));
+
+        assert_eq!(sudachi_result_count(result), 2);
+        let mut info = unsafe { std::mem::zeroed::<SudachiTokenInfo>() };
+        assert!(sudachi_result_get(result, 1, &mut info));
+        assert_eq!((info.begin, info.end), (6, 9));
+        assert!(!sudachi_result_get(result, 2, &mut info));
+
+        let field = |index, bit| c_str(sudachi_result_field(result, index, bit));
+        assert_eq!(field(0, SUDACHI_FIELD_SURFACE), Some("猫"));
+        assert_eq!(field(0, SUDACHI_FIELD_POS), Some(r#"["名詞"]"#));
+        assert_eq!(field(1, SUDACHI_FIELD_SURFACE), Some("犬"));
+        assert_eq!(field(0, SUDACHI_FIELD_READING), None);
+        assert_eq!(field(0, SUDACHI_FIELD_LEMMA_KEY), None);
+        assert_eq!(field(2, SUDACHI_FIELD_SURFACE), None);
+
+        assert_eq!(c_str(sudachi_token_surface(result, 1)), Some("犬"));
+        assert!(sudachi_token_reading(result, 1).is_null());
+        assert_eq!(sudachi_token_end(result, 0), 3);
+        assert_eq!(sudachi_token_begin(result, 2), -1);
+        sudachi_free_result(result);
+    }
+}
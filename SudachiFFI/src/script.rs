@@ -0,0 +1,208 @@
+// Mixed-script pass-through
+// Latin, Cyrillic and Hangul text is mostly unknown to the Japanese
+// dictionary, and OOV handling can shred it into one token per character
+// (English onomatopoeia and titles are common in manga). Every token is
+// tagged with its script, and consecutive tokens that lie within one run of
+// a foreign script are merged into a single pass-through token.
+
+use crate::{handle, simd, SudachiTokenizer, TokenData};
+
+/// Script of a token, as a language hint
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SudachiScript {
+    /// Japanese, digits, symbols or a mix of scripts
+    Japanese = 0,
+    /// Latin letters, including fullwidth and accented forms
+    Latin = 1,
+    Cyrillic = 2,
+    /// Hangul syllables and jamo
+    Hangul = 3,
+}
+
+impl SudachiScript {
+    /// Script stored as an integer (e.g. in the analysis cache)
+    /// Unknown values read as Japanese
+    pub(crate) fn from_tag(tag: u8) -> SudachiScript {
+        match tag {
+            1 => SudachiScript::Latin,
+            2 => SudachiScript::Cyrillic,
+            3 => SudachiScript::Hangul,
+            _ => SudachiScript::Japanese,
+        }
+    }
+}
+
+/// Foreign script of a letter, None for anything else
+pub(crate) fn script_of(c: char) -> Option<SudachiScript> {
+    match c {
+        'A'..='Z' | 'a'..='z' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => Some(SudachiScript::Latin),
+        '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' if c != '×' && c != '÷' => {
+            Some(SudachiScript::Latin)
+        }
+        '\u{0400}'..='\u{052F}' => Some(SudachiScript::Cyrillic),
+        '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}' => {
+            Some(SudachiScript::Hangul)
+        }
+        _ => None,
+    }
+}
+
+/// Combining marks continue the letter before them
+fn is_mark(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}')
+}
+
+/// A single one of these between two letters keeps a run going
+/// ("ONE PIECE", "don't", "K-POP")
+fn is_connector(c: char) -> bool {
+    matches!(c, ' ' | '\'' | '’' | '-')
+}
+
+/// Maximal runs of a single foreign script as (begin, end, script)
+fn runs(text: &str) -> Vec<(usize, usize, SudachiScript)> {
+    let mut runs: Vec<(usize, usize, SudachiScript)> = Vec::new();
+    let mut current: Option<(usize, usize, SudachiScript)> = None;
+    let mut i = 0;
+    while i < text.len() {
+        // Outside a run only a letter matters, so skip to the next one
+        if current.is_none() {
+            match simd::find_foreign(text.as_bytes(), i) {
+                Some(next) => i = next,
+                None => break,
+            }
+        }
+        let Some(c) = text[i..].chars().next() else {
+            break;
+        };
+        let end = i + c.len_utf8();
+        match (script_of(c), current.as_mut()) {
+            (Some(script), Some(run)) if run.2 == script && joins(&text[run.1..i]) => run.1 = end,
+            (Some(script), _) => {
+                runs.extend(current.take());
+                current = Some((i, end, script));
+            }
+            (None, Some(run)) if run.1 == i && is_mark(c) => run.1 = end,
+            (None, _) if is_connector(c) => {}
+            (None, _) => runs.extend(current.take()),
+        }
+        i = end;
+    }
+    runs.extend(current);
+    runs
+}
+
+/// Whether the text between two letters keeps them in one run
+fn joins(gap: &str) -> bool {
+    let mut chars = gap.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => true,
+        (Some(c), None) => is_connector(c),
+        _ => false,
+    }
+}
+
+/// Script of a whole token: a foreign script if all its letters are in it
+pub(crate) fn token_script(surface: &str) -> SudachiScript {
+    let mut script = None;
+    for c in surface.chars() {
+        match script_of(c) {
+            Some(s) if script.is_none() || script == Some(s) => script = Some(s),
+            None if is_mark(c) || is_connector(c) => {}
+            _ => return SudachiScript::Japanese,
+        }
+    }
+    script.unwrap_or(SudachiScript::Japanese)
+}
+
+/// Merge consecutive tokens within one foreign script run of text
+/// Offsets of tokens are relative to text. A merged token keeps the word
+/// info of its first token; its selected string fields are all the surface.
+pub(crate) fn merge_runs(text: &str, tokens: Vec<TokenData>) -> Vec<TokenData> {
+    let runs = runs(text);
+    if runs.is_empty() {
+        return tokens;
+    }
+
+    let mut merged = Vec::with_capacity(tokens.len());
+    let mut tokens = tokens.into_iter().peekable();
+    let mut r = 0;
+    while let Some(token) = tokens.next() {
+        while r < runs.len() && runs[r].1 <= token.begin {
+            r += 1;
+        }
+        let (begin, end, script) = match runs.get(r) {
+            Some(&run) if run.0 <= token.begin && token.end <= run.1 => run,
+            _ => {
+                merged.push(token);
+                continue;
+            }
+        };
+
+        let mut last = token.end;
+        let mut count = 1;
+        while let Some(next) = tokens.next_if(|t| begin <= t.begin && t.end <= end) {
+            last = next.end;
+            count += 1;
+        }
+        if count == 1 {
+            merged.push(token);
+            continue;
+        }
+
+        merged.push(TokenData {
+            script,
+            ..token.extend_to(text, last)
+        });
+    }
+    merged
+}
+
+/// Enable or disable merging of foreign script runs on a handle
+/// Enabled by default. Tokens are tagged with their script either way.
+/// Returns false if tokenizer is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_pass_through(
+    tokenizer: *mut SudachiTokenizer,
+    enabled: bool,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.pass_through = enabled);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testtoken::shredded;
+
+    #[test]
+    fn test_merge_runs() {
+        let text = "ONE PIECEを読む。안녕!";
+        let merged = merge_runs(text, shredded(text));
+        let surfaces: Vec<(&str, SudachiScript)> = merged
+            .iter()
+            .map(|t| (&text[t.begin..t.end], t.script))
+            .collect();
+        assert_eq!(
+            surfaces,
+            [
+                ("ONE PIECE", SudachiScript::Latin),
+                ("を", SudachiScript::Japanese),
+                ("読", SudachiScript::Japanese),
+                ("む", SudachiScript::Japanese),
+                ("。", SudachiScript::Japanese),
+                ("안녕", SudachiScript::Hangul),
+                ("!", SudachiScript::Japanese),
+            ]
+        );
+        assert_eq!(merged[0].reading, "ONE PIECE");
+        assert_eq!(merged[0].word_id, 0);
+
+        assert_eq!(token_script("Привет"), SudachiScript::Cyrillic);
+        assert_eq!(token_script("Tシャツ"), SudachiScript::Japanese);
+    }
+}
@@ -0,0 +1,75 @@
+// Dictionary sections
+// The mapped dictionary is the largest part of the app's memory footprint.
+// Word info is decoded per lookup, and only the sections the requested fields
+// need (word structure is never decoded), but readahead still pulls in pages
+// around every touched entry. Init flags let a handle skip sections it will
+// never use and keep readahead from faulting in string areas that are never
+// read.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use sudachi::dic::subset::InfoSubset;
+
+use crate::mapping::SudachiInitOptions;
+use crate::{c_str, handle, load_dictionaries, SudachiTokenizer};
+
+// Flags for sudachi_init_with_flags
+/// Never decode synonym group ids (TSV_ALL output prints them as [])
+pub const SUDACHI_INIT_SKIP_SYNONYMS: u32 = 1 << 0;
+/// Map the dictionary for random access: pages are faulted in one at a time
+/// as entries are read, with no readahead
+pub const SUDACHI_INIT_RANDOM_ACCESS: u32 = 1 << 1;
+/// Read the whole dictionary into memory instead of mapping the file, for
+/// environments that evict file-backed pages aggressively (mapping advice is
+/// then ignored)
+pub const SUDACHI_INIT_COPY_TO_RAM: u32 = 1 << 2;
+
+/// Word info sections a handle initialized with flags may decode
+pub(crate) fn sections(flags: u32) -> InfoSubset {
+    let mut sections = InfoSubset::all();
+    if flags & SUDACHI_INIT_SKIP_SYNONYMS != 0 {
+        sections.remove(InfoSubset::SYNONYM_GROUP_ID);
+    }
+    sections
+}
+
+/// Initialize Sudachi tokenizer with SUDACHI_INIT_* flags
+/// Returns NULL on failure
+#[no_mangle]
+pub extern "C" fn sudachi_init_with_flags(
+    dict_path: *const c_char,
+    flags: u32,
+) -> *mut SudachiTokenizer {
+    let Some(path) = c_str(dict_path) else {
+        return ptr::null_mut();
+    };
+    let options = SudachiInitOptions {
+        flags,
+        ..SudachiInitOptions::default()
+    };
+    let loaded = match load_dictionaries(path, &[], &options) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    let tokenizer = SudachiTokenizer::new(loaded);
+    tokenizer.set_options(|o| o.sections = sections(flags));
+    handle::into_raw(tokenizer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections() {
+        assert_eq!(sections(0), InfoSubset::all());
+        let lean = sections(SUDACHI_INIT_SKIP_SYNONYMS | SUDACHI_INIT_RANDOM_ACCESS);
+        assert!(!lean.contains(InfoSubset::SYNONYM_GROUP_ID));
+        assert!(lean.contains(InfoSubset::READING_FORM | InfoSubset::SPLIT_A));
+    }
+}
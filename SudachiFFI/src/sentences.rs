@@ -0,0 +1,292 @@
+// Sentence segmentation
+// Splitting at every 。 and line break cuts dialogue apart: 「行くよ。」と言った
+// became 「行くよ。 and 」と言った, so endings, readings and sentence counts
+// were attributed across the quote boundary. Sentences are split on the
+// analyzed tokens instead, tracking brackets (「」『』（）【】〈〉《》):
+//
+// - a full stop, ！ or ？ ends a sentence only outside brackets, so a quote
+//   with a sentence-final form inside stays with its frame (「行く。」と言った)
+// - a closing bracket back at the outermost level ends the sentence when a
+//   new quote, a line break or the end of the text follows (「行くぞ」「おう」),
+//   or when the quote ended in a terminator and no particle follows
+//   (「行くよ。」そう言って)
+// - further terminators and whitespace after the end stay with the sentence
+//   (……。！ and the line break after it)
+// - a line break ends the sentence and closes open brackets; with soft line
+//   breaks (text wrapped inside a speech bubble) only a blank line, or a line
+//   of nothing but ellipses and dashes (……), does
+//
+// Every token belongs to exactly one sentence, so a sentence of only
+// ellipses is kept as one.
+
+use std::ops::Range;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::grapheme::SudachiByteRange;
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    c_str, handle, leak, SudachiTokenMode, SudachiTokenizer, TokenData, SUDACHI_FIELD_POS,
+    SUDACHI_FIELD_SURFACE,
+};
+
+// Flags for sudachi_sentences
+/// Line breaks are wrapping, not sentence boundaries (speech bubbles)
+pub const SUDACHI_SENTENCE_SOFT_BREAKS: u32 = 1 << 0;
+
+const OPENERS: &str = "「『（(【〈《";
+const CLOSERS: &str = "」』）)】〉》";
+/// Characters of a sentence that is only a pause
+const PAUSES: &str = "…‥・―─ー〜.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Space,
+    /// Whitespace with a line break; blank if it holds two or more
+    Break {
+        blank: bool,
+    },
+    Open,
+    Close,
+    Terminator,
+    Other,
+}
+
+fn kind(token: &TokenData) -> Kind {
+    match token.class {
+        SudachiTokenClass::Whitespace => match token.surface.matches('\n').count() {
+            0 => Kind::Space,
+            n => Kind::Break { blank: n > 1 },
+        },
+        SudachiTokenClass::Symbol => {
+            let first = token.surface.chars().next().unwrap_or(' ');
+            if OPENERS.contains(first) {
+                Kind::Open
+            } else if CLOSERS.contains(first) {
+                Kind::Close
+            } else if token.pos.get(1).is_some_and(|p| p == "句点")
+                || token.surface.chars().any(|c| "。！？!?".contains(c))
+            {
+                Kind::Terminator
+            } else {
+                Kind::Other
+            }
+        }
+        _ => Kind::Other,
+    }
+}
+
+/// Update the stack of expected closers with the brackets of a symbol
+fn brackets(surface: &str, stack: &mut Vec<char>) {
+    for c in surface.chars() {
+        if let Some(i) = OPENERS.chars().position(|o| o == c) {
+            stack.extend(CLOSERS.chars().nth(i));
+        } else if CLOSERS.contains(c) {
+            // A closer without its opener is ignored
+            if let Some(i) = stack.iter().rposition(|&e| e == c) {
+                stack.truncate(i);
+            }
+        }
+    }
+}
+
+/// Whether a quote that just closed is a sentence of its own
+/// terminated is whether its last token was a terminator.
+fn quote_ends(rest: &[TokenData], terminated: bool) -> bool {
+    let next = rest.iter().find(|t| kind(t) != Kind::Space);
+    match next.map(|t| (t, kind(t))) {
+        None | Some((_, Kind::Break { .. } | Kind::Open)) => true,
+        Some((token, _)) => terminated && token.pos.first().map(String::as_str) != Some("助詞"),
+    }
+}
+
+/// Whether tokens are nothing but ellipses and dashes
+fn is_pause(tokens: &[TokenData]) -> bool {
+    let mut symbols = tokens
+        .iter()
+        .filter(|t| !matches!(kind(t), Kind::Space | Kind::Break { .. }))
+        .peekable();
+    symbols.peek().is_some()
+        && symbols.all(|t| {
+            t.class == SudachiTokenClass::Symbol && t.surface.chars().all(|c| PAUSES.contains(c))
+        })
+}
+
+/// Sentences of analyzed text as ranges of token indices
+/// The ranges cover all tokens in order.
+pub(crate) fn sentences(tokens: &[TokenData], flags: u32) -> Vec<Range<usize>> {
+    let soft = flags & SUDACHI_SENTENCE_SOFT_BREAKS != 0;
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut stack: Vec<char> = Vec::new();
+    // The sentence has ended; it takes trailing terminators and whitespace
+    let mut ended = false;
+    for (i, token) in tokens.iter().enumerate() {
+        let k = kind(token);
+        if ended {
+            // Closers here have no opener (。」 without 「)
+            let trailing = matches!(
+                k,
+                Kind::Space | Kind::Break { .. } | Kind::Terminator | Kind::Close
+            );
+            if trailing {
+                continue;
+            }
+            out.push(start..i);
+            start = i;
+            ended = false;
+        }
+        match k {
+            Kind::Open => brackets(&token.surface, &mut stack),
+            Kind::Close => {
+                let depth = stack.len();
+                brackets(&token.surface, &mut stack);
+                let terminated = tokens[..i]
+                    .iter()
+                    .rev()
+                    .find(|t| kind(t) != Kind::Space)
+                    .is_some_and(|t| kind(t) == Kind::Terminator);
+                ended = stack.is_empty() && depth > 0 && quote_ends(&tokens[i + 1..], terminated);
+            }
+            Kind::Terminator => ended = stack.is_empty(),
+            Kind::Break { blank } => {
+                if !soft || blank || is_pause(&tokens[start..i]) {
+                    stack.clear();
+                    ended = true;
+                }
+            }
+            Kind::Space | Kind::Other => {}
+        }
+    }
+    if start < tokens.len() {
+        out.push(start..tokens.len());
+    }
+    out
+}
+
+/// Split text into sentences
+/// flags is a mask of SUDACHI_SENTENCE_* values. Ranges cover the text up
+/// to the end of each sentence's last token other than whitespace.
+/// Returns NULL on failure (caller must free with sudachi_free_sentences)
+#[no_mangle]
+pub extern "C" fn sudachi_sentences(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    flags: u32,
+    out_count: *mut usize,
+) -> *mut SudachiByteRange {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let Some(text) = c_str(text) else {
+        return ptr::null_mut();
+    };
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let fields = SUDACHI_FIELD_SURFACE | SUDACHI_FIELD_POS;
+    let Ok(tokens) = tokenizer.analyze(text, mode.into(), fields) else {
+        return ptr::null_mut();
+    };
+
+    let ranges: Vec<SudachiByteRange> = sentences(&tokens, flags)
+        .into_iter()
+        .map(|range| {
+            let sentence = &tokens[range];
+            let end = sentence
+                .iter()
+                .rev()
+                .find(|t| t.class != SudachiTokenClass::Whitespace)
+                .unwrap_or(&sentence[0]);
+            SudachiByteRange {
+                begin: sentence[0].begin as i32,
+                end: end.end as i32,
+            }
+        })
+        .collect();
+
+    unsafe {
+        *out_count = ranges.len();
+    }
+    let ranges = Box::leak(ranges.into_boxed_slice()).as_mut_ptr();
+    leak::track(ranges, "sentences");
+    ranges
+}
+
+/// Free the result of sudachi_sentences
+#[no_mangle]
+pub extern "C" fn sudachi_free_sentences(sentences: *mut SudachiByteRange, count: usize) {
+    if !sentences.is_null() {
+        leak::release(sentences);
+        unsafe {
+            let _ = Box::from_raw(ptr::slice_from_raw_parts_mut(sentences, count));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+
+    /// Tokens of space-separated surfaces; _ stands for a space and a
+    /// trailing * marks a particle
+    fn tokens_of(text: &str) -> Vec<TokenData> {
+        let mut begin = 0;
+        text.split(' ')
+            .map(|surface| {
+                let particle = surface.ends_with('*');
+                let surface = surface.trim_end_matches('*').replace('_', " ");
+                let mut token = language::opaque(
+                    begin,
+                    &surface,
+                    SudachiScript::Japanese,
+                    SUDACHI_FIELD_SURFACE,
+                );
+                token.class = crate::symbol::token_class(&surface);
+                if particle {
+                    token.pos = vec!["助詞".to_string()];
+                }
+                begin += surface.len();
+                token
+            })
+            .collect()
+    }
+
+    fn split(text: &str, flags: u32) -> Vec<String> {
+        let tokens = tokens_of(text);
+        sentences(&tokens, flags)
+            .into_iter()
+            .map(|r| tokens[r].iter().map(|t| t.surface.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_sentences() {
+        assert_eq!(
+            split(
+                "「 行く よ 。 」 と* 言っ た 。 \n 「 待て 」 「 おう ！ 」",
+                0
+            ),
+            ["「行くよ。」と言った。\n", "「待て」", "「おう！」"]
+        );
+        // Nested quotes close only at the outer bracket
+        assert_eq!(
+            split("「 『 本 』 を 読む 。 」 _ そう か", 0),
+            ["「『本』を読む。」 ", "そうか"]
+        );
+        assert_eq!(
+            split("…… \n そう か …… 。 ！ \n 行こう", 0),
+            ["……\n", "そうか……。！\n", "行こう"]
+        );
+        // Soft breaks wrap, except after a pause or at a blank line
+        let soft = SUDACHI_SENTENCE_SOFT_BREAKS;
+        assert_eq!(
+            split("…… \n 今日 は \n 晴れ \n\n 明日 も", soft),
+            ["……\n", "今日は\n晴れ\n\n", "明日も"]
+        );
+        // An unclosed quote ends at a hard line break
+        assert_eq!(split("「 行く \n 来る", 0), ["「行く\n", "来る"]);
+        assert_eq!(split("」 行く 。", 0), ["」行く。"]);
+    }
+}
@@ -0,0 +1,226 @@
+// Sound effects
+// Manga pages are full of giongo/gitaigo (ドドドド, ガーーン, ドキッ) that
+// Sudachi splits into fragments or reads as unrelated words, which pollutes
+// vocabulary extraction. In SFX mode, kana runs that match a built-in list or
+// a repetition pattern become one token of class Sfx, with the dictionary and
+// normalized forms set to a base form: elongation collapsed (ガーーン ->
+// ガーン), a trailing ッ dropped (ドキッ -> ドキ) and repetition cut to two
+// units (ドキドキドキ -> ドキドキ), in katakana.
+
+use crate::kana::{hiragana_to_katakana, is_hiragana, is_katakana};
+use crate::reading::{self, SUDACHI_READING_SMALL_KANA_STRETCH};
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    handle, SudachiTokenizer, TokenData, SUDACHI_FIELD_DICTIONARY_FORM,
+    SUDACHI_FIELD_DICTIONARY_READING, SUDACHI_FIELD_NORMALIZED_FORM, SUDACHI_FIELD_READING,
+};
+
+/// Base forms of common sound effects
+/// Katakana runs also match by repetition alone; hiragana runs only by list.
+const SFX: &[&str] = &[
+    "イライラ",
+    "ウトウト",
+    "ガーン",
+    "ガタ",
+    "ガタガタ",
+    "ガンガン",
+    "ギク",
+    "キラキラ",
+    "グルグル",
+    "ゴーン",
+    "ゴクゴク",
+    "ゴロ",
+    "ゴロゴロ",
+    "ザー",
+    "ザワザワ",
+    "ジー",
+    "ジロジロ",
+    "シーン",
+    "ズーン",
+    "ズキズキ",
+    "ズドーン",
+    "ゾクゾク",
+    "ソワソワ",
+    "チラ",
+    "ドーン",
+    "ドカーン",
+    "ドキ",
+    "ドキドキ",
+    "ドサ",
+    "ドン",
+    "ドンドン",
+    "ニコニコ",
+    "ニヤ",
+    "ニヤニヤ",
+    "バーン",
+    "バキ",
+    "バタバタ",
+    "パクパク",
+    "ハラハラ",
+    "バン",
+    "ピカピカ",
+    "ビク",
+    "ビシ",
+    "ヒソヒソ",
+    "ヒュー",
+    "ピュー",
+    "フワフワ",
+    "ブルブル",
+    "ペコペコ",
+    "ボーン",
+    "ムカムカ",
+    "メラメラ",
+    "モグモグ",
+    "ワクワク",
+];
+
+/// Repetition of a unit of up to three kana, cut to two units
+/// Single kana must repeat at least three times (ドドド, not ママ).
+fn repetition(run: &str) -> Option<String> {
+    let chars: Vec<char> = run.chars().collect();
+    (1..=3).find_map(|unit| {
+        let count = chars.len() / unit;
+        let repeats = count * unit == chars.len()
+            && count >= if unit == 1 { 3 } else { 2 }
+            && chars.chunks(unit).all(|c| c == &chars[..unit]);
+        repeats.then(|| chars[..unit * 2].iter().collect())
+    })
+}
+
+/// Base form of a kana run if it is a sound effect
+pub(crate) fn base_form(run: &str) -> Option<String> {
+    let katakana = run.chars().all(|c| is_katakana(c) || c == 'ー');
+    let run = hiragana_to_katakana(run);
+    let run = reading::process(&run, SUDACHI_READING_SMALL_KANA_STRETCH);
+    let run = run.trim_end_matches('ッ');
+    if run.chars().count() < 2 {
+        return None;
+    }
+    match repetition(run) {
+        Some(base) if katakana => Some(base),
+        base => {
+            let base = base.unwrap_or_else(|| run.to_string());
+            SFX.contains(&base.as_str()).then_some(base)
+        }
+    }
+}
+
+/// Maximal runs of katakana or of hiragana (with ー) as (begin, end)
+fn runs(text: &str) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut script = None;
+    for (i, c) in text.char_indices() {
+        let kind = if is_katakana(c) {
+            Some(true)
+        } else if is_hiragana(c) {
+            Some(false)
+        } else if c == 'ー' {
+            script
+        } else {
+            None
+        };
+        match runs.last_mut() {
+            Some(run) if kind.is_some() && kind == script && run.1 == i => run.1 += c.len_utf8(),
+            _ if kind.is_some() => runs.push((i, i + c.len_utf8())),
+            _ => {}
+        }
+        script = kind;
+    }
+    runs
+}
+
+/// Merge the tokens of each sound effect run and tag them as Sfx
+/// Offsets of tokens are relative to text. Runs that do not start and end on
+/// token boundaries are left alone.
+pub(crate) fn tag(text: &str, tokens: Vec<TokenData>) -> Vec<TokenData> {
+    let mut effects = runs(text)
+        .into_iter()
+        .filter_map(|(begin, end)| Some((begin, end, base_form(&text[begin..end])?)))
+        .peekable();
+    if effects.peek().is_none() {
+        return tokens;
+    }
+
+    let mut tagged = Vec::with_capacity(tokens.len());
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        while effects.next_if(|e| e.1 <= token.begin).is_some() {}
+        let (end, base) = match effects.peek() {
+            Some((begin, end, base)) if *begin == token.begin => (*end, base.clone()),
+            _ => {
+                tagged.push(token);
+                continue;
+            }
+        };
+
+        let mut group = vec![token];
+        while let Some(next) = tokens.next_if(|t| t.end <= end) {
+            group.push(next);
+        }
+        if group.last().map(|t| t.end) != Some(end) {
+            // The run ends inside a token
+            tagged.extend(group);
+            continue;
+        }
+
+        let count = group.len();
+        let token = group.swap_remove(0);
+        let mut token = if count > 1 {
+            let mut token = token.extend_to(text, end);
+            if token.fields & SUDACHI_FIELD_READING != 0 {
+                token.reading = hiragana_to_katakana(&token.reading);
+            }
+            token
+        } else {
+            token
+        };
+        if token.fields & SUDACHI_FIELD_DICTIONARY_FORM != 0 {
+            token.dictionary_form = base.clone();
+        }
+        if token.fields & SUDACHI_FIELD_DICTIONARY_READING != 0 {
+            token.dictionary_reading = base.clone();
+        }
+        if token.fields & SUDACHI_FIELD_NORMALIZED_FORM != 0 {
+            token.normalized_form = base;
+        }
+        tagged.push(TokenData {
+            class: SudachiTokenClass::Sfx,
+            ..token
+        });
+    }
+    tagged
+}
+
+/// Enable or disable sound effect recognition on a handle
+/// Disabled by default. Returns false if tokenizer is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_sfx(tokenizer: *mut SudachiTokenizer, enabled: bool) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.sfx = enabled);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_form() {
+        assert_eq!(base_form("ドドドド").as_deref(), Some("ドド"));
+        assert_eq!(base_form("ドキドキドキ").as_deref(), Some("ドキドキ"));
+        assert_eq!(base_form("ガーーーン").as_deref(), Some("ガーン"));
+        assert_eq!(base_form("ガァァン").as_deref(), Some("ガーン"));
+        assert_eq!(base_form("ドキッ").as_deref(), Some("ドキ"));
+        assert_eq!(base_form("ざわざわ").as_deref(), Some("ザワザワ"));
+        assert_eq!(base_form("いろいろ"), None);
+        assert_eq!(base_form("ママ"), None);
+        assert_eq!(base_form("ゲーム"), None);
+
+        assert_eq!(
+            runs("ドドドドッ！ざわ…ゲーム"),
+            [(0, 15), (18, 24), (27, 36)]
+        );
+    }
+}
@@ -0,0 +1,53 @@
+// Instruments signposts
+// With the apple feature on iOS and macOS, dictionary loads and tokenizer
+// calls are wrapped in os_signpost intervals (objc/MGXSignpost.c) so they
+// appear under Points of Interest in Instruments. Elsewhere intervals are
+// no-ops.
+
+pub(crate) use imp::interval;
+
+/// Interval kinds, matching the MGX_SIGNPOST_* values of the shim
+#[derive(Clone, Copy)]
+pub(crate) enum Signpost {
+    DictionaryLoad = 0,
+    Tokenize = 1,
+}
+
+#[cfg(not(all(feature = "apple", any(target_os = "ios", target_os = "macos"))))]
+mod imp {
+    use super::Signpost;
+
+    pub(crate) struct Interval;
+
+    pub(crate) fn interval(_kind: Signpost) -> Interval {
+        Interval
+    }
+}
+
+#[cfg(all(feature = "apple", any(target_os = "ios", target_os = "macos")))]
+mod imp {
+    use super::Signpost;
+
+    extern "C" {
+        fn mgx_signpost_begin(kind: u32) -> u64;
+        fn mgx_signpost_end(kind: u32, id: u64);
+    }
+
+    /// Open interval, ended when dropped
+    pub(crate) struct Interval {
+        kind: Signpost,
+        id: u64,
+    }
+
+    /// Begin an interval of kind
+    pub(crate) fn interval(kind: Signpost) -> Interval {
+        let id = unsafe { mgx_signpost_begin(kind as u32) };
+        Interval { kind, id }
+    }
+
+    impl Drop for Interval {
+        fn drop(&mut self) {
+            unsafe { mgx_signpost_end(self.kind as u32, self.id) };
+        }
+    }
+}
@@ -0,0 +1,355 @@
+// SIMD text scanning
+// Kana conversion, width folding and script-run detection look at every
+// character of every page, and decoding chars one at a time showed up in
+// profiles. These paths work on the UTF-8 bytes 16 at a time instead: NEON
+// on aarch64, and plain 16-byte arrays elsewhere, which the compiler turns
+// into SSE2 (and which the tests exercise).
+//
+// Katakana and hiragana are three-byte sequences E3 8x xx whose conversion
+// only rewrites the second and third byte, so it can be done in place on
+// byte lanes: a lane is a second byte if the lane before it is E3, and a
+// third byte if the lane two before is. Each block reads the source from
+// two bytes before to one byte after it, and blocks at the edges of the text
+// go through the scalar version of the same rule. Width folding and script
+// runs only use SIMD to skip to the next byte that can start a candidate
+// character; the character itself is still checked with the scalar code.
+
+/// A kana conversion rule: E3 [second] [lo..=hi] has its second and third
+/// byte increased (wrapping) by the deltas
+struct Rule {
+    second: u8,
+    lo: u8,
+    hi: u8,
+    second_delta: u8,
+    third_delta: u8,
+}
+
+const fn rule(second: u8, lo: u8, hi: u8, second_delta: u8, third_delta: u8) -> Rule {
+    Rule {
+        second,
+        lo,
+        hi,
+        second_delta,
+        third_delta,
+    }
+}
+
+/// U+30A1..U+30F6 and U+30FD..U+30FE to hiragana (kana::to_hiragana)
+const TO_HIRAGANA: [Rule; 4] = [
+    rule(0x82, 0xA1, 0xBF, 0xFF, 0xE0),
+    rule(0x83, 0x80, 0x9F, 0xFE, 0x20),
+    rule(0x83, 0xA0, 0xB6, 0xFF, 0xE0),
+    rule(0x83, 0xBD, 0xBE, 0xFF, 0xE0),
+];
+
+/// U+3041..U+3096 and U+309D..U+309E to katakana (kana::to_katakana)
+const TO_KATAKANA: [Rule; 4] = [
+    rule(0x81, 0x81, 0x9F, 0x01, 0x20),
+    rule(0x81, 0xA0, 0xBF, 0x02, 0xE0),
+    rule(0x82, 0x80, 0x96, 0x01, 0x20),
+    rule(0x82, 0x9D, 0x9E, 0x01, 0x20),
+];
+
+const LEAD: u8 = 0xE3;
+
+#[cfg(target_arch = "aarch64")]
+#[allow(unused_unsafe)]
+mod lanes {
+    use std::arch::aarch64::*;
+
+    /// 16 byte lanes; masks have lanes of 0xFF or 0
+    #[derive(Clone, Copy)]
+    pub(super) struct V(uint8x16_t);
+
+    // NEON is part of the aarch64 baseline, so the intrinsics are always
+    // available
+    impl V {
+        pub(super) fn load(bytes: &[u8], at: usize) -> V {
+            let block = &bytes[at..at + 16];
+            V(unsafe { vld1q_u8(block.as_ptr()) })
+        }
+
+        pub(super) fn store(self, out: &mut [u8]) {
+            let block = &mut out[..16];
+            unsafe { vst1q_u8(block.as_mut_ptr(), self.0) }
+        }
+
+        pub(super) fn eq(self, b: u8) -> V {
+            V(unsafe { vceqq_u8(self.0, vdupq_n_u8(b)) })
+        }
+
+        pub(super) fn between(self, lo: u8, hi: u8) -> V {
+            unsafe {
+                V(vandq_u8(
+                    vcgeq_u8(self.0, vdupq_n_u8(lo)),
+                    vcleq_u8(self.0, vdupq_n_u8(hi)),
+                ))
+            }
+        }
+
+        pub(super) fn and(self, other: V) -> V {
+            V(unsafe { vandq_u8(self.0, other.0) })
+        }
+
+        pub(super) fn or(self, other: V) -> V {
+            V(unsafe { vorrq_u8(self.0, other.0) })
+        }
+
+        /// Add n (wrapping) to the lanes set in mask
+        pub(super) fn add_masked(self, mask: V, n: u8) -> V {
+            V(unsafe { vaddq_u8(self.0, vandq_u8(mask.0, vdupq_n_u8(n))) })
+        }
+
+        /// Index of the first set lane of a mask
+        pub(super) fn first(self) -> Option<usize> {
+            // Narrow every lane to a nibble of one 64-bit word
+            let nibbles = unsafe {
+                let narrowed = vshrn_n_u16::<4>(vreinterpretq_u16_u8(self.0));
+                vget_lane_u64::<0>(vreinterpret_u64_u8(narrowed))
+            };
+            (nibbles != 0).then(|| nibbles.trailing_zeros() as usize / 4)
+        }
+    }
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+mod lanes {
+    /// 16 byte lanes; masks have lanes of 0xFF or 0
+    #[derive(Clone, Copy)]
+    pub(super) struct V([u8; 16]);
+
+    impl V {
+        fn map(self, f: impl Fn(u8) -> u8) -> V {
+            V(self.0.map(f))
+        }
+
+        fn zip(self, other: V, f: impl Fn(u8, u8) -> u8) -> V {
+            let mut out = [0; 16];
+            for (i, out) in out.iter_mut().enumerate() {
+                *out = f(self.0[i], other.0[i]);
+            }
+            V(out)
+        }
+
+        pub(super) fn load(bytes: &[u8], at: usize) -> V {
+            let mut lanes = [0; 16];
+            lanes.copy_from_slice(&bytes[at..at + 16]);
+            V(lanes)
+        }
+
+        pub(super) fn store(self, out: &mut [u8]) {
+            out[..16].copy_from_slice(&self.0);
+        }
+
+        pub(super) fn eq(self, b: u8) -> V {
+            self.map(|x| if x == b { 0xFF } else { 0 })
+        }
+
+        pub(super) fn between(self, lo: u8, hi: u8) -> V {
+            self.map(|x| if lo <= x && x <= hi { 0xFF } else { 0 })
+        }
+
+        pub(super) fn and(self, other: V) -> V {
+            self.zip(other, |a, b| a & b)
+        }
+
+        pub(super) fn or(self, other: V) -> V {
+            self.zip(other, |a, b| a | b)
+        }
+
+        /// Add n (wrapping) to the lanes set in mask
+        pub(super) fn add_masked(self, mask: V, n: u8) -> V {
+            self.zip(mask, |x, m| x.wrapping_add(m & n))
+        }
+
+        /// Index of the first set lane of a mask
+        pub(super) fn first(self) -> Option<usize> {
+            self.0.iter().position(|&m| m != 0)
+        }
+    }
+}
+
+use lanes::V;
+
+/// Converted byte at p of src (scalar form of the block rule)
+fn convert_byte(src: &[u8], p: usize, rules: &[Rule]) -> u8 {
+    let at = |i: Option<usize>| i.and_then(|i| src.get(i)).copied();
+    let b = src[p];
+    for rule in rules {
+        let third = |x: Option<u8>| x.is_some_and(|x| rule.lo <= x && x <= rule.hi);
+        if at(p.checked_sub(1)) == Some(LEAD) && b == rule.second && third(at(Some(p + 1))) {
+            return b.wrapping_add(rule.second_delta);
+        }
+        if at(p.checked_sub(2)) == Some(LEAD)
+            && at(p.checked_sub(1)) == Some(rule.second)
+            && third(Some(b))
+        {
+            return b.wrapping_add(rule.third_delta);
+        }
+    }
+    b
+}
+
+/// Convert kana in UTF-8 text by rules
+fn convert(text: &str, rules: &[Rule]) -> String {
+    let src = text.as_bytes();
+    let mut out = vec![0; src.len()];
+    let mut p = 0;
+    while p < src.len() {
+        // Blocks read from p - 2 to p + 16
+        if p < 2 || p + 17 > src.len() {
+            out[p] = convert_byte(src, p, rules);
+            p += 1;
+            continue;
+        }
+        let before2 = V::load(src, p - 2);
+        let before = V::load(src, p - 1);
+        let cur = V::load(src, p);
+        let after = V::load(src, p + 1);
+        let seconds = before.eq(LEAD);
+        let thirds = before2.eq(LEAD);
+        let mut result = cur;
+        for rule in rules {
+            let second = seconds
+                .and(cur.eq(rule.second))
+                .and(after.between(rule.lo, rule.hi));
+            let third = thirds
+                .and(before.eq(rule.second))
+                .and(cur.between(rule.lo, rule.hi));
+            result = result
+                .add_masked(second, rule.second_delta)
+                .add_masked(third, rule.third_delta);
+        }
+        result.store(&mut out[p..]);
+        p += 16;
+    }
+    // Only whole kana sequences change, into other kana
+    String::from_utf8(out).unwrap_or_else(|_| text.to_string())
+}
+
+/// Katakana to hiragana, as kana::to_hiragana on every char
+pub(crate) fn to_hiragana(text: &str) -> String {
+    convert(text, &TO_HIRAGANA)
+}
+
+/// Hiragana to katakana, as kana::to_katakana on every char
+pub(crate) fn to_katakana(text: &str) -> String {
+    convert(text, &TO_KATAKANA)
+}
+
+/// First position from at where mask(window) is set, where the window is
+/// the block at a position and the block after it (for the next byte)
+fn find(
+    bytes: &[u8],
+    at: usize,
+    mask: impl Fn(V, V) -> V,
+    scalar: impl Fn(usize) -> bool,
+) -> Option<usize> {
+    let mut p = at;
+    while p + 17 <= bytes.len() {
+        if let Some(i) = mask(V::load(bytes, p), V::load(bytes, p + 1)).first() {
+            return Some(p + i);
+        }
+        p += 16;
+    }
+    (p..bytes.len()).find(|&i| scalar(i))
+}
+
+/// Next position from at that may start a character width normalization
+/// converts: U+FF01..U+FF9F (EF BC-BE) or the ideographic space (E3 80 80)
+pub(crate) fn find_fullwidth(bytes: &[u8], at: usize) -> Option<usize> {
+    let at_byte = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    find(
+        bytes,
+        at,
+        |cur, next| {
+            let fullwidth = cur.eq(0xEF).and(next.between(0xBC, 0xBE));
+            fullwidth.or(cur.eq(LEAD).and(next.eq(0x80)))
+        },
+        |i| {
+            let next = at_byte(i + 1);
+            (bytes[i] == 0xEF && (0xBC..=0xBE).contains(&next))
+                || (bytes[i] == LEAD && next == 0x80)
+        },
+    )
+}
+
+/// Next position from at that may start a letter of a foreign script
+/// (script::script_of): an ASCII letter, a two-byte lead of Latin or
+/// Cyrillic (C3-D4), E1 (jamo, Latin Extended Additional), E3 84-86
+/// (compatibility jamo), EA-ED (Hangul syllables) or EF (full-width Latin)
+pub(crate) fn find_foreign(bytes: &[u8], at: usize) -> Option<usize> {
+    let candidate = |b: u8, next: u8| {
+        b.is_ascii_alphabetic()
+            || (0xC3..=0xD4).contains(&b)
+            || b == 0xE1
+            || (0xEA..=0xED).contains(&b)
+            || b == 0xEF
+            || (b == LEAD && (0x84..=0x86).contains(&next))
+    };
+    find(
+        bytes,
+        at,
+        |cur, next| {
+            cur.between(b'A', b'Z')
+                .or(cur.between(b'a', b'z'))
+                .or(cur.between(0xC3, 0xD4))
+                .or(cur.eq(0xE1))
+                .or(cur.between(0xEA, 0xED))
+                .or(cur.eq(0xEF))
+                .or(cur.eq(LEAD).and(next.between(0x84, 0x86)))
+        },
+        |i| candidate(bytes[i], bytes.get(i + 1).copied().unwrap_or(0)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kana;
+    use crate::script::script_of;
+
+    #[test]
+    fn test_convert() {
+        // Every kana at every alignment, so each lands on a block edge
+        let all: String = ('\u{3040}'..='\u{30FF}').collect();
+        for pad in 0..3 {
+            let text = format!("{}{}ab{}", "a".repeat(pad), all, all);
+            let hiragana: String = text.chars().map(kana::to_hiragana).collect();
+            let katakana: String = text.chars().map(kana::to_katakana).collect();
+            assert_eq!(to_hiragana(&text), hiragana);
+            assert_eq!(to_katakana(&text), katakana);
+        }
+        assert_eq!(
+            to_hiragana("ヴァイオリン・ー、漢字とＡＢＣ"),
+            "ゔぁいおりん・ー、漢字とＡＢＣ"
+        );
+        assert_eq!(to_katakana("ひらがな"), "ヒラガナ");
+    }
+
+    #[test]
+    fn test_find() {
+        // Candidates are a superset: 。 (E3 80 82) is found as well
+        let text = "今日は晴れです。今日は晴れです。ｶﾞﾝﾊﾞﾚ　Ｘ";
+        for (i, c) in text.char_indices() {
+            if ('\u{FF01}'..='\u{FF9F}').contains(&c) || c == '\u{3000}' {
+                assert_eq!(find_fullwidth(text.as_bytes(), i), Some(i));
+            }
+        }
+        assert_eq!(
+            find_fullwidth("今日は晴れです今日は晴れです".as_bytes(), 0),
+            None
+        );
+
+        let text = "猫は「ONE PIECE」とПриветと한국語とＴシャツ。";
+        for (i, c) in text.char_indices() {
+            if script_of(c).is_some() {
+                assert_eq!(find_foreign(text.as_bytes(), i), Some(i));
+            }
+        }
+        assert_eq!(
+            find_foreign("今日は晴れです。今日は晴れです。".as_bytes(), 0),
+            None
+        );
+    }
+}
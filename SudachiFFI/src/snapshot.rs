@@ -0,0 +1,276 @@
+// Warm-start snapshots
+// After init, the first analyses of every launch stall on page faults in the
+// mapped dictionary: the connection matrix, the upper trie and the entries of
+// common words are faulted in one page at a time, in the same order each
+// time. Sudachi's derived structures (grammar, character categories, plugins)
+// live inside the dictionary object and can't be serialized from here, so a
+// snapshot keeps the part of the warm state that can be restored: which pages
+// of the map were resident once the app had warmed up. Applying it right
+// after the next init asks the kernel to read those pages in the background
+// (madvise WillNeed), so first lookups find them in memory. A snapshot is
+// stamped with the dictionary fingerprint and page size, and ignored after
+// an update or on a device with another page size. It does not shorten init
+// itself, which still rebuilds those structures on every launch.
+//
+// File layout (all integers little-endian):
+//   header:  b"MGXW" | u32 format version
+//            u64 dictionary hash | u64 dictionary version
+//            u64 page size | u64 image length
+//   pages:   one bit per page of the image, LSB first
+
+use std::ops::Range;
+use std::os::raw::c_char;
+
+use crate::fingerprint::SudachiFingerprint;
+use crate::mapping::Image;
+use crate::{c_str, handle, SudachiTokenizer};
+
+const MAGIC: &[u8; 4] = b"MGXW";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: usize = 8 + 16 + 16;
+
+/// Resident pages of a dictionary image
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    fingerprint: SudachiFingerprint,
+    page: usize,
+    len: usize,
+    pages: Vec<u8>,
+}
+
+impl Snapshot {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.pages.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        for n in [
+            self.fingerprint.hash,
+            self.fingerprint.version,
+            self.page as u64,
+            self.len as u64,
+        ] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.pages);
+        bytes
+    }
+
+    /// None if bytes are not a snapshot of this format
+    fn decode(bytes: &[u8]) -> Option<Snapshot> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return None;
+        }
+        if u32::from_le_bytes(bytes[4..8].try_into().ok()?) != FORMAT_VERSION {
+            return None;
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let page = u64_at(24) as usize;
+        let len = u64_at(32) as usize;
+        if page == 0 || bytes.len() - HEADER_LEN != len.div_ceil(page).div_ceil(8) {
+            return None;
+        }
+        Some(Snapshot {
+            fingerprint: SudachiFingerprint {
+                hash: u64_at(8),
+                version: u64_at(16),
+            },
+            page,
+            len,
+            pages: bytes[HEADER_LEN..].to_vec(),
+        })
+    }
+
+    /// Whether the snapshot was taken of this image, with pages of page bytes
+    fn applies_to(&self, fingerprint: SudachiFingerprint, image: Image, page: usize) -> bool {
+        self.fingerprint.matches(fingerprint) && self.len == image.len && self.page == page
+    }
+
+    /// Byte ranges of the image covered by runs of resident pages
+    fn runs(&self) -> Vec<Range<usize>> {
+        let count = self.len.div_ceil(self.page);
+        let resident = |i: usize| self.pages[i / 8] & (1 << (i % 8)) != 0;
+        let mut runs = Vec::new();
+        let mut i = 0;
+        while i < count {
+            if !resident(i) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < count && resident(i) {
+                i += 1;
+            }
+            runs.push(start * self.page..self.len.min(i * self.page));
+        }
+        runs
+    }
+}
+
+/// Size of a memory page, None if unknown
+#[cfg(unix)]
+fn page_size() -> Option<usize> {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    usize::try_from(page).ok().filter(|&page| page > 0)
+}
+
+#[cfg(not(unix))]
+fn page_size() -> Option<usize> {
+    None
+}
+
+/// Bitmap of the pages of image currently in memory
+#[cfg(unix)]
+fn resident(image: Image) -> Option<(usize, Vec<u8>)> {
+    let page = page_size()?;
+    if image.len == 0 {
+        return None;
+    }
+    let mut vec = vec![0u8; image.len.div_ceil(page)];
+    // SAFETY: image is a live, page-aligned map of image.len bytes
+    let status = unsafe {
+        libc::mincore(
+            image.addr as *mut libc::c_void,
+            image.len,
+            vec.as_mut_ptr() as *mut _,
+        )
+    };
+    if status != 0 {
+        return None;
+    }
+    let mut pages = vec![0u8; vec.len().div_ceil(8)];
+    for (i, _) in vec.iter().enumerate().filter(|(_, &v)| v & 1 != 0) {
+        pages[i / 8] |= 1 << (i % 8);
+    }
+    Some((page, pages))
+}
+
+#[cfg(not(unix))]
+fn resident(_image: Image) -> Option<(usize, Vec<u8>)> {
+    None
+}
+
+/// Write a warm-start snapshot of a handle's dictionary
+/// Call once the app has warmed up (e.g. after the first screen of text is
+/// analyzed). Returns false if an argument is NULL, the dictionary was not
+/// memory-mapped, or the file can't be written.
+#[no_mangle]
+pub extern "C" fn sudachi_snapshot_save(
+    tokenizer: *const SudachiTokenizer,
+    path: *const c_char,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    let Some(path) = c_str(path) else {
+        return false;
+    };
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Some((page, pages)) = resident(tokenizer.image) else {
+        return false;
+    };
+    let snapshot = Snapshot {
+        fingerprint: tokenizer.fingerprint,
+        page,
+        len: tokenizer.image.len,
+        pages,
+    };
+    std::fs::write(path, snapshot.encode()).is_ok()
+}
+
+/// Start reading in the pages recorded in a warm-start snapshot
+/// Returns immediately; pages are read in the background. Returns false if an
+/// argument is NULL, the file is missing or malformed, the dictionary was not
+/// memory-mapped, or the snapshot was taken with another dictionary or page
+/// size (save a new one once warmed up).
+#[no_mangle]
+pub extern "C" fn sudachi_snapshot_apply(
+    tokenizer: *const SudachiTokenizer,
+    path: *const c_char,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    let Some(path) = c_str(path) else {
+        return false;
+    };
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Some(snapshot) = std::fs::read(path)
+        .ok()
+        .and_then(|bytes| Snapshot::decode(&bytes))
+    else {
+        return false;
+    };
+    let Some(page) = page_size() else {
+        return false;
+    };
+    if !snapshot.applies_to(tokenizer.fingerprint, tokenizer.image, page) {
+        return false;
+    }
+    will_need(tokenizer.image, &snapshot.runs());
+    true
+}
+
+#[cfg(unix)]
+fn will_need(image: Image, runs: &[Range<usize>]) {
+    for run in runs {
+        // SAFETY: runs lie within the live map of image.len bytes
+        let status = unsafe {
+            libc::madvise(
+                (image.addr + run.start) as *mut libc::c_void,
+                run.len(),
+                libc::MADV_WILLNEED,
+            )
+        };
+        if status != 0 {
+            eprintln!(
+                "Failed to advise snapshot pages: {}",
+                std::io::Error::last_os_error()
+            );
+            return;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn will_need(_image: Image, _runs: &[Range<usize>]) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let snapshot = Snapshot {
+            fingerprint: SudachiFingerprint {
+                hash: 42,
+                version: 7,
+            },
+            page: 4096,
+            len: 10 * 4096 + 100,
+            // Pages 0-1, 4 and the partial last page 10
+            pages: vec![0b0001_0011, 0b0000_0100],
+        };
+        let bytes = snapshot.encode();
+        assert_eq!(Snapshot::decode(&bytes), Some(snapshot));
+        assert!(Snapshot::decode(&bytes[..bytes.len() - 1]).is_none());
+        assert!(Snapshot::decode(b"MGXC").is_none());
+
+        let snapshot = Snapshot::decode(&bytes).unwrap();
+        let image = Image {
+            addr: 0,
+            len: 10 * 4096 + 100,
+        };
+        assert!(snapshot.applies_to(snapshot.fingerprint, image, 4096));
+        // Pages of another size would be advised at the wrong offsets
+        assert!(!snapshot.applies_to(snapshot.fingerprint, image, 16384));
+        let other = SudachiFingerprint {
+            hash: 43,
+            version: 7,
+        };
+        assert!(!snapshot.applies_to(other, image, 4096));
+        assert_eq!(
+            snapshot.runs(),
+            vec![0..8192, 4 * 4096..5 * 4096, 10 * 4096..10 * 4096 + 100]
+        );
+    }
+}
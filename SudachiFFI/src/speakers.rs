@@ -0,0 +1,367 @@
+// Speaker statistics
+// Character profiles ("uses rough speech, ends sentences with 〜だろ, says
+// 俺 for I") are rollups over every line a character speaks in a series.
+// Lines are added with the speaker they belong to; per speaker we count
+// lines, sentences, polite sentences and words, the lemmas used, the
+// sentence endings and the pronouns.
+//
+// Sentences are split as in sentences.rs, so a quoted 「行くよ。」 stays with
+// the sentence around it. Its ending is the run of particles and auxiliary verbs after its last
+// content word (だろ, ぜ, ですよね), read past trailing symbols like 〜 and
+// …; a sentence is polite when that run contains です or ます.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::sentences::sentences;
+use crate::symbol::SudachiTokenClass;
+use crate::{
+    c_str, handle, into_c_string, leak, SudachiTokenMode, SudachiTokenizer, TokenData,
+    SUDACHI_FIELD_DICTIONARY_FORM, SUDACHI_FIELD_LEMMA_KEY, SUDACHI_FIELD_POS,
+    SUDACHI_FIELD_SURFACE,
+};
+
+/// Fields speaker statistics need from the analyzer
+const SPEAKER_FIELDS: u32 = SUDACHI_FIELD_SURFACE
+    | SUDACHI_FIELD_DICTIONARY_FORM
+    | SUDACHI_FIELD_POS
+    | SUDACHI_FIELD_LEMMA_KEY;
+
+/// Counts of one speaker
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SudachiSpeakerStats {
+    /// Lines added for the speaker
+    pub texts: u64,
+    pub sentences: u64,
+    /// Sentences ending in です or ます
+    pub polite_sentences: u64,
+    /// Words, without symbols, emoji and whitespace
+    pub tokens: u64,
+    /// Distinct lemmas among the words
+    pub lemmas: u64,
+}
+
+/// Ranked list of a speaker
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SudachiSpeakerList {
+    /// Sentence endings (だろ, ですよね)
+    Endings = 0,
+    /// Pronouns (俺, あんた)
+    Pronouns = 1,
+}
+
+/// Entry of a ranked list
+#[repr(C)]
+pub struct SudachiSpeakerTerm {
+    pub text: *mut c_char,
+    pub count: u64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Speaker {
+    stats: SudachiSpeakerStats,
+    lemmas: HashMap<u64, u64>,
+    endings: HashMap<String, u64>,
+    pronouns: HashMap<String, u64>,
+}
+
+fn pos0(token: &TokenData) -> &str {
+    token.pos.first().map_or("", String::as_str)
+}
+
+fn is_word(token: &TokenData) -> bool {
+    matches!(
+        token.class,
+        SudachiTokenClass::Word | SudachiTokenClass::Sfx
+    )
+}
+
+impl Speaker {
+    /// Count one line of the speaker
+    fn add(&mut self, tokens: &[TokenData]) {
+        self.stats.texts += 1;
+        for token in tokens.iter().filter(|t| is_word(t)) {
+            self.stats.tokens += 1;
+            if token.lemma_key != 0 {
+                *self.lemmas.entry(token.lemma_key).or_default() += 1;
+            }
+            if pos0(token) == "代名詞" {
+                *self.pronouns.entry(token.surface.clone()).or_default() += 1;
+            }
+        }
+        self.stats.lemmas = self.lemmas.len() as u64;
+        for range in sentences(tokens, 0) {
+            self.sentence(&tokens[range]);
+        }
+    }
+
+    fn sentence(&mut self, tokens: &[TokenData]) {
+        let words: Vec<&TokenData> = tokens.iter().filter(|t| is_word(t)).collect();
+        if words.is_empty() {
+            return;
+        }
+        self.stats.sentences += 1;
+        let tail = words
+            .iter()
+            .rev()
+            .take_while(|t| matches!(pos0(t), "助詞" | "助動詞"))
+            .count();
+        let ending = &words[words.len() - tail..];
+        if ending
+            .iter()
+            .any(|t| pos0(t) == "助動詞" && matches!(t.dictionary_form.as_str(), "です" | "ます"))
+        {
+            self.stats.polite_sentences += 1;
+        }
+        if !ending.is_empty() {
+            let text: String = ending.iter().map(|t| t.surface.as_str()).collect();
+            *self.endings.entry(text).or_default() += 1;
+        }
+    }
+
+    /// Up to limit entries of a list, most frequent first
+    fn top(&self, list: SudachiSpeakerList, limit: usize) -> Vec<(&str, u64)> {
+        let counts = match list {
+            SudachiSpeakerList::Endings => &self.endings,
+            SudachiSpeakerList::Pronouns => &self.pronouns,
+        };
+        let mut top: Vec<(&str, u64)> = counts.iter().map(|(s, &n)| (s.as_str(), n)).collect();
+        top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(limit);
+        top
+    }
+}
+
+/// Opaque handle to per-speaker statistics
+#[derive(Default)]
+pub struct SudachiSpeakers {
+    speakers: Mutex<HashMap<String, Speaker>>,
+}
+
+impl SudachiSpeakers {
+    fn speakers(&self) -> MutexGuard<'_, HashMap<String, Speaker>> {
+        self.speakers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Create empty speaker statistics
+/// Returns NULL on failure (caller must free with sudachi_speakers_free)
+#[no_mangle]
+pub extern "C" fn sudachi_speakers_create() -> *mut SudachiSpeakers {
+    let speakers = Box::into_raw(Box::<SudachiSpeakers>::default());
+    leak::track(speakers, "SudachiSpeakers");
+    speakers
+}
+
+/// Free speaker statistics
+#[no_mangle]
+pub extern "C" fn sudachi_speakers_free(speakers: *mut SudachiSpeakers) {
+    if !speakers.is_null() {
+        leak::release(speakers);
+        unsafe {
+            let _ = Box::from_raw(speakers);
+        }
+    }
+}
+
+/// Analyze a line and add it to the statistics of speaker
+/// speaker is any id the app uses for a character. May be called from several
+/// threads. Returns false if an argument is NULL, not valid UTF-8 or the
+/// analysis fails.
+#[no_mangle]
+pub extern "C" fn sudachi_speakers_add(
+    speakers: *mut SudachiSpeakers,
+    tokenizer: *mut SudachiTokenizer,
+    speaker: *const c_char,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+) -> bool {
+    if speakers.is_null() || tokenizer.is_null() {
+        return false;
+    }
+    let (Some(speaker), Some(text)) = (c_str(speaker), c_str(text)) else {
+        return false;
+    };
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Ok(tokens) = tokenizer.analyze(text, mode.into(), SPEAKER_FIELDS) else {
+        return false;
+    };
+    unsafe { &*speakers }
+        .speakers()
+        .entry(speaker.to_string())
+        .or_default()
+        .add(&tokens);
+    true
+}
+
+/// Get the counts of a speaker
+/// Returns false if an argument is NULL or the speaker has no lines
+#[no_mangle]
+pub extern "C" fn sudachi_speakers_stats(
+    speakers: *const SudachiSpeakers,
+    speaker: *const c_char,
+    out_stats: *mut SudachiSpeakerStats,
+) -> bool {
+    if speakers.is_null() || out_stats.is_null() {
+        return false;
+    }
+    let Some(speaker) = c_str(speaker) else {
+        return false;
+    };
+    let Some(stats) = unsafe { &*speakers }
+        .speakers()
+        .get(speaker)
+        .map(|s| s.stats)
+    else {
+        return false;
+    };
+    unsafe {
+        *out_stats = stats;
+    }
+    true
+}
+
+/// Number of times a speaker used a lemma (see sudachi_lemma_key)
+/// Returns 0 if an argument is NULL or the speaker is unknown
+#[no_mangle]
+pub extern "C" fn sudachi_speakers_lemma_count(
+    speakers: *const SudachiSpeakers,
+    speaker: *const c_char,
+    lemma_key: u64,
+) -> u64 {
+    if speakers.is_null() {
+        return 0;
+    }
+    let Some(speaker) = c_str(speaker) else {
+        return 0;
+    };
+    unsafe { &*speakers }
+        .speakers()
+        .get(speaker)
+        .and_then(|s| s.lemmas.get(&lemma_key).copied())
+        .unwrap_or(0)
+}
+
+/// Get up to limit entries of a speaker's list, most frequent first
+/// Ties are ordered by text. Returns NULL if an argument is NULL or the
+/// speaker is unknown (caller must free with sudachi_free_speaker_terms)
+#[no_mangle]
+pub extern "C" fn sudachi_speakers_top(
+    speakers: *const SudachiSpeakers,
+    speaker: *const c_char,
+    list: SudachiSpeakerList,
+    limit: usize,
+    out_count: *mut usize,
+) -> *mut SudachiSpeakerTerm {
+    if speakers.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let Some(speaker) = c_str(speaker) else {
+        return ptr::null_mut();
+    };
+    let all = unsafe { &*speakers }.speakers();
+    let Some(speaker) = all.get(speaker) else {
+        return ptr::null_mut();
+    };
+    let terms: Vec<SudachiSpeakerTerm> = speaker
+        .top(list, limit)
+        .into_iter()
+        .map(|(text, count)| SudachiSpeakerTerm {
+            text: into_c_string(text),
+            count,
+        })
+        .collect();
+
+    unsafe {
+        *out_count = terms.len();
+    }
+    let terms = Box::leak(terms.into_boxed_slice()).as_mut_ptr();
+    leak::track(terms, "speaker terms");
+    terms
+}
+
+/// Free the result of sudachi_speakers_top
+#[no_mangle]
+pub extern "C" fn sudachi_free_speaker_terms(terms: *mut SudachiSpeakerTerm, count: usize) {
+    if terms.is_null() {
+        return;
+    }
+
+    leak::release(terms);
+    unsafe {
+        let terms = Box::from_raw(ptr::slice_from_raw_parts_mut(terms, count));
+        for term in terms.iter() {
+            if !term.text.is_null() {
+                let _ = CString::from_raw(term.text);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::script::SudachiScript;
+
+    fn tokens_of(words: &[(&str, &str, &str)]) -> Vec<TokenData> {
+        let mut begin = 0;
+        words
+            .iter()
+            .map(|&(surface, dictionary_form, pos)| {
+                let mut token =
+                    language::opaque(begin, surface, SudachiScript::Japanese, SPEAKER_FIELDS);
+                token.class = crate::symbol::token_class(surface);
+                token.dictionary_form = dictionary_form.to_string();
+                token.pos = pos.split(',').map(str::to_string).collect();
+                token.lemma_key = crate::lemma::key(dictionary_form, "", &token.pos[0]);
+                begin += surface.len();
+                token
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_speaker() {
+        let mut speaker = Speaker::default();
+        speaker.add(&tokens_of(&[
+            ("俺", "俺", "代名詞"),
+            ("が", "が", "助詞,格助詞"),
+            ("やる", "やる", "動詞,一般"),
+            ("だろ", "だ", "助動詞"),
+            ("〜", "〜", "補助記号,一般"),
+            ("。", "。", "補助記号,句点"),
+            ("行き", "行く", "動詞,一般"),
+            ("ます", "ます", "助動詞"),
+            ("よ", "よ", "助詞,終助詞"),
+            ("！", "！", "補助記号,句点"),
+        ]));
+        speaker.add(&tokens_of(&[
+            ("俺", "俺", "代名詞"),
+            ("だ", "だ", "助動詞"),
+            ("ろ", "ろ", "助詞,終助詞"),
+        ]));
+        assert_eq!(
+            speaker.stats,
+            SudachiSpeakerStats {
+                texts: 2,
+                sentences: 3,
+                polite_sentences: 1,
+                tokens: 10,
+                lemmas: 8,
+            }
+        );
+        assert_eq!(speaker.lemmas[&crate::lemma::key("俺", "", "代名詞")], 2);
+        assert_eq!(
+            speaker.top(SudachiSpeakerList::Endings, 2),
+            [("だろ", 2), ("ますよ", 1)]
+        );
+        assert_eq!(speaker.top(SudachiSpeakerList::Pronouns, 5), [("俺", 2)]);
+    }
+}
@@ -0,0 +1,110 @@
+// Analysis stamps
+// Nothing in analysis is random: the lattice keeps the first of equal-cost
+// paths in node order, overrides go longest first and then in the order
+// given, and bias re-ranking breaks ties by word id. The token stream of a
+// text is therefore a function of the text, mode and fields, the dictionary,
+// the handle's settings and the analysis code. A stamp captures everything but
+// the text, mode and fields; two handles with equal stamps, on any device,
+// return identical tokens for identical calls. Cached analyses and diff-based
+// sync keep the stamp they were made under and re-run on a mismatch.
+
+use crate::fingerprint::SudachiFingerprint;
+use crate::{handle, SudachiTokenizer};
+
+/// Revision of the analysis code
+/// Bumped whenever a change in this library, or in the Sudachi revision it
+/// builds against, can change the tokens of the same inputs.
+pub const SUDACHI_ENGINE_VERSION: u32 = 1;
+
+/// Everything besides the call's arguments that analysis output depends on
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SudachiAnalysisStamp {
+    pub dictionary: SudachiFingerprint,
+    /// Hash of the handle's analysis settings, overrides and corrections
+    pub settings: u64,
+    /// SUDACHI_ENGINE_VERSION of the library that made the stamp
+    pub engine: u32,
+}
+
+/// Get the analysis stamp of a handle
+/// The stamp changes with any setter that changes analysis, and with every
+/// correction recorded in an attached store. Returns false if any argument is
+/// NULL
+#[no_mangle]
+pub extern "C" fn sudachi_analysis_stamp(
+    tokenizer: *const SudachiTokenizer,
+    out_stamp: *mut SudachiAnalysisStamp,
+) -> bool {
+    if tokenizer.is_null() || out_stamp.is_null() {
+        return false;
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    unsafe {
+        *out_stamp = SudachiAnalysisStamp {
+            dictionary: tokenizer.fingerprint,
+            settings: tokenizer.options().digest(),
+            engine: SUDACHI_ENGINE_VERSION,
+        };
+    }
+    true
+}
+
+/// Whether output made under stamp a matches output made under stamp b
+/// Compares field by field (the struct has padding, so don't memcmp it).
+/// Returns false if either is NULL or has no dictionary fingerprint.
+#[no_mangle]
+pub extern "C" fn sudachi_analysis_stamp_equal(
+    a: *const SudachiAnalysisStamp,
+    b: *const SudachiAnalysisStamp,
+) -> bool {
+    if a.is_null() || b.is_null() {
+        return false;
+    }
+    let (a, b) = unsafe { (*a, *b) };
+    a.dictionary.matches(b.dictionary) && a == b
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::Options;
+    use crate::overrides::{Entry, Overrides};
+    use std::sync::Arc;
+
+    fn pinned() -> Options {
+        let entry = Entry::new("七つ|の|大罪", "ナナツ|ノ|タイザイ", None).unwrap();
+        Options {
+            overrides: Arc::new(Overrides::new(vec![entry])),
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn test_settings_digest() {
+        let options = Options::default();
+        assert_eq!(options.digest(), Options::default().digest());
+
+        // Trailing zero biases and an empty override list change nothing
+        let same = Options {
+            dictionary_bias: vec![0, 0],
+            overrides: Arc::new(Overrides::new(Vec::new())),
+            ..Options::default()
+        };
+        assert_eq!(same.digest(), options.digest());
+
+        let biased = Options {
+            dictionary_bias: vec![0, -2000],
+            ..Options::default()
+        };
+        assert_ne!(biased.digest(), options.digest());
+
+        assert_ne!(pinned().digest(), options.digest());
+        assert_eq!(pinned().digest(), pinned().digest());
+
+        let chunked = Options {
+            chunk_bytes: 1024,
+            ..Options::default()
+        };
+        assert_ne!(chunked.digest(), options.digest());
+    }
+}
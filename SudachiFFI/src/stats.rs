@@ -0,0 +1,165 @@
+// Usage statistics
+// Lock-free counters kept per tokenizer handle, for on-device telemetry.
+// Analysis cache lookups are not tied to a tokenizer and are counted
+// process-wide.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::{handle, SudachiTokenizer};
+
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Count an analysis cache lookup
+pub(crate) fn record_cache_lookup(hit: bool) {
+    let counter = if hit { &CACHE_HITS } else { &CACHE_MISSES };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counters of one tokenizer handle
+#[derive(Default)]
+pub(crate) struct Stats {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    latency_ns: AtomicU64,
+    tokens: AtomicU64,
+    peak_tokens: AtomicU64,
+    allocations: AtomicU64,
+}
+
+impl Stats {
+    /// Count one analysis call and its duration
+    pub(crate) fn record_call(&self, elapsed: Duration) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.latency_ns
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count the tokens of one result and the allocations holding them
+    pub(crate) fn record_tokens(&self, tokens: usize, allocations: usize) {
+        self.tokens.fetch_add(tokens as u64, Ordering::Relaxed);
+        self.peak_tokens.fetch_max(tokens as u64, Ordering::Relaxed);
+        self.allocations
+            .fetch_add(allocations as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> SudachiStats {
+        let calls = self.calls.load(Ordering::Relaxed);
+        let latency_ns = self.latency_ns.load(Ordering::Relaxed);
+        let cache_hits = CACHE_HITS.load(Ordering::Relaxed);
+        let cache_misses = CACHE_MISSES.load(Ordering::Relaxed);
+        let lookups = cache_hits + cache_misses;
+        SudachiStats {
+            calls,
+            errors: self.errors.load(Ordering::Relaxed),
+            total_latency_ns: latency_ns,
+            average_latency_ns: latency_ns.checked_div(calls).unwrap_or(0),
+            tokens: self.tokens.load(Ordering::Relaxed),
+            peak_tokens: self.peak_tokens.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            cache_hits,
+            cache_misses,
+            cache_hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                cache_hits as f64 / lookups as f64
+            },
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [
+            &self.calls,
+            &self.errors,
+            &self.latency_ns,
+            &self.tokens,
+            &self.peak_tokens,
+            &self.allocations,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Snapshot of a tokenizer's counters since creation or the last reset
+/// Batch tokenization uses its own analyzers and is not counted.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SudachiStats {
+    /// Analysis calls on the handle
+    pub calls: u64,
+    /// Calls that failed during analysis
+    pub errors: u64,
+    /// Time spent analyzing, including waiting for the handle's lock
+    pub total_latency_ns: u64,
+    pub average_latency_ns: u64,
+    /// Tokens produced over all calls
+    pub tokens: u64,
+    /// Most tokens produced by a single call
+    pub peak_tokens: u64,
+    /// Heap allocations made for results (token lists and string fields)
+    pub allocations: u64,
+    /// Analysis cache lookups across all caches in the process
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// cache_hits / (cache_hits + cache_misses), 0 before any lookup
+    pub cache_hit_rate: f64,
+}
+
+/// Read a tokenizer's counters
+/// Returns false if tokenizer or out_stats is NULL
+#[no_mangle]
+pub extern "C" fn sudachi_stats(
+    tokenizer: *mut SudachiTokenizer,
+    out_stats: *mut SudachiStats,
+) -> bool {
+    if tokenizer.is_null() || out_stats.is_null() {
+        return false;
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    unsafe {
+        *out_stats = tokenizer.stats.snapshot();
+    }
+    true
+}
+
+/// Zero a tokenizer's counters and the process-wide cache counters
+#[no_mangle]
+pub extern "C" fn sudachi_stats_reset(tokenizer: *mut SudachiTokenizer) {
+    if tokenizer.is_null() {
+        return;
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    tokenizer.stats.reset();
+    CACHE_HITS.store(0, Ordering::Relaxed);
+    CACHE_MISSES.store(0, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_counters() {
+        let stats = Stats::default();
+        stats.record_call(Duration::from_micros(30));
+        stats.record_call(Duration::from_micros(10));
+        stats.record_error();
+        stats.record_tokens(5, 6);
+        stats.record_tokens(2, 3);
+
+        let snapshot = stats.snapshot();
+        assert_eq!((snapshot.calls, snapshot.errors), (2, 1));
+        assert_eq!(snapshot.average_latency_ns, 20_000);
+        assert_eq!((snapshot.tokens, snapshot.peak_tokens), (7, 5));
+        assert_eq!(snapshot.allocations, 9);
+
+        stats.reset();
+        assert_eq!(stats.snapshot().calls, 0);
+    }
+}
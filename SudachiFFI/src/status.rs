@@ -0,0 +1,218 @@
+// Status-code API
+// Parallel entry points that return a SudachiStatus and pass results through
+// out-parameters, so callers can tell why a call failed instead of getting
+// a bare NULL.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::{
+    handle, into_token_array, load_dictionary, lookup, trace, SudachiToken, SudachiTokenMode,
+    SudachiTokenizer, TokenData, SUDACHI_MAX_TEXT_BYTES,
+};
+
+/// Result of a status-returning call
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SudachiStatus {
+    Ok = 0,
+    /// A required pointer was NULL or an argument was out of range
+    InvalidArgument = 1,
+    /// Text or path was not valid UTF-8
+    InvalidUtf8 = 2,
+    /// A result buffer could not be allocated
+    OutOfMemory = 3,
+    /// The dictionary could not be opened or is malformed
+    DictionaryError = 4,
+    /// Analysis failed inside Sudachi
+    AnalysisError = 5,
+    /// The library panicked; the handle should not be used again
+    /// Only reported by builds with panic = "unwind" (release builds abort)
+    InternalPanic = 6,
+    /// A file could not be opened or read
+    IoError = 7,
+    /// Text longer than SUDACHI_MAX_TEXT_BYTES, whose offsets would overflow
+    TextTooLong = 8,
+}
+
+/// Read a required C string argument
+pub(crate) fn require_str<'a>(ptr: *const c_char) -> Result<&'a str, SudachiStatus> {
+    if ptr.is_null() {
+        return Err(SudachiStatus::InvalidArgument);
+    }
+    let s = unsafe { CStr::from_ptr(ptr) };
+    if s.to_bytes().len() > SUDACHI_MAX_TEXT_BYTES {
+        return Err(SudachiStatus::TextTooLong);
+    }
+    s.to_str().map_err(|_| SudachiStatus::InvalidUtf8)
+}
+
+/// Run f, turning a panic into InternalPanic
+pub(crate) fn guarded(f: impl FnOnce() -> Result<(), SudachiStatus>) -> SudachiStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => SudachiStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => SudachiStatus::InternalPanic,
+    }
+}
+
+/// Convert tokens to a C array, checking the array allocation
+pub(crate) fn token_array(
+    tokens: Vec<TokenData>,
+    out_count: *mut usize,
+) -> Result<*mut *mut SudachiToken, SudachiStatus> {
+    trace::span!("marshal");
+    let mut raw = Vec::new();
+    raw.try_reserve_exact(tokens.len())
+        .map_err(|_| SudachiStatus::OutOfMemory)?;
+    raw.extend(tokens.into_iter().filter_map(TokenData::into_raw));
+    Ok(into_token_array(raw, out_count))
+}
+
+/// Initialize a tokenizer, writing the handle to out_tokenizer
+/// On failure *out_tokenizer is set to NULL
+#[no_mangle]
+pub extern "C" fn sudachi_init2(
+    dict_path: *const c_char,
+    out_tokenizer: *mut *mut SudachiTokenizer,
+) -> SudachiStatus {
+    if out_tokenizer.is_null() {
+        return SudachiStatus::InvalidArgument;
+    }
+    unsafe {
+        *out_tokenizer = ptr::null_mut();
+    }
+
+    guarded(|| {
+        let path = require_str(dict_path)?;
+        let dictionary = load_dictionary(path).map_err(|e| {
+            eprintln!("{}", e);
+            SudachiStatus::DictionaryError
+        })?;
+        unsafe {
+            *out_tokenizer = handle::into_raw(SudachiTokenizer::new(dictionary));
+        }
+        Ok(())
+    })
+}
+
+/// Tokenize text, filling the string fields selected by a SUDACHI_FIELD_*
+/// mask. On success the tokens are written to out_tokens (caller must free
+/// with sudachi_free_tokens); on failure *out_tokens is NULL and *out_count 0
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize2(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    fields: u32,
+    out_tokens: *mut *mut *mut SudachiToken,
+    out_count: *mut usize,
+) -> SudachiStatus {
+    if tokenizer.is_null() || out_tokens.is_null() || out_count.is_null() {
+        return SudachiStatus::InvalidArgument;
+    }
+    unsafe {
+        *out_tokens = ptr::null_mut();
+        *out_count = 0;
+    }
+
+    guarded(|| {
+        let tokenizer = unsafe { handle::borrow(tokenizer) };
+        let text = require_str(text)?;
+        let tokens = tokenizer
+            .analyze(text, mode.into(), fields)
+            .map_err(|_| SudachiStatus::AnalysisError)?;
+        unsafe {
+            *out_tokens = token_array(tokens, out_count)?;
+        }
+        Ok(())
+    })
+}
+
+/// Look up the dictionary entries of a word (see sudachi_lookup)
+/// On failure *out_tokens is NULL and *out_count 0
+#[no_mangle]
+pub extern "C" fn sudachi_lookup2(
+    tokenizer: *mut SudachiTokenizer,
+    word: *const c_char,
+    out_tokens: *mut *mut *mut SudachiToken,
+    out_count: *mut usize,
+) -> SudachiStatus {
+    if tokenizer.is_null() || out_tokens.is_null() || out_count.is_null() {
+        return SudachiStatus::InvalidArgument;
+    }
+    unsafe {
+        *out_tokens = ptr::null_mut();
+        *out_count = 0;
+    }
+
+    guarded(|| {
+        let tokenizer = unsafe { handle::borrow(tokenizer) };
+        let word = require_str(word)?;
+        let entries = lookup::lookup(&tokenizer.dictionary, word)
+            .map_err(|_| SudachiStatus::DictionaryError)?;
+        unsafe {
+            *out_tokens = token_array(entries, out_count)?;
+        }
+        Ok(())
+    })
+}
+
+/// Descriptions of the status codes
+const MESSAGES: [(SudachiStatus, &[u8]); 9] = [
+    (SudachiStatus::Ok, b"ok\0"),
+    (SudachiStatus::InvalidArgument, b"invalid argument\0"),
+    (SudachiStatus::InvalidUtf8, b"invalid UTF-8\0"),
+    (SudachiStatus::OutOfMemory, b"out of memory\0"),
+    (SudachiStatus::DictionaryError, b"dictionary error\0"),
+    (SudachiStatus::AnalysisError, b"analysis error\0"),
+    (SudachiStatus::InternalPanic, b"internal panic\0"),
+    (SudachiStatus::IoError, b"I/O error\0"),
+    (SudachiStatus::TextTooLong, b"text too long\0"),
+];
+
+/// Get a static description of a status code
+/// Takes the code as an integer, so values from a newer header are safe to
+/// pass; they are described as "unknown status".
+#[no_mangle]
+pub extern "C" fn sudachi_status_message(status: i32) -> *const c_char {
+    let message: &'static [u8] = MESSAGES
+        .iter()
+        .find(|&&(s, _)| s as i32 == status)
+        .map_or(b"unknown status\0", |&(_, message)| message);
+    message.as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::c_str;
+
+    #[test]
+    fn test_status() {
+        let mut tokenizer = ptr::null_mut();
+        assert_eq!(
+            sudachi_init2(ptr::null(), &mut tokenizer),
+            SudachiStatus::InvalidArgument
+        );
+        let bad = b"\xff\0";
+        assert_eq!(
+            sudachi_init2(bad.as_ptr() as *const c_char, &mut tokenizer),
+            SudachiStatus::InvalidUtf8
+        );
+        assert!(tokenizer.is_null());
+        assert_eq!(guarded(|| panic!("boom")), SudachiStatus::InternalPanic);
+
+        let message = |status| c_str(sudachi_status_message(status));
+        assert_eq!(message(SudachiStatus::IoError as i32), Some("I/O error"));
+        assert_eq!(
+            message(SudachiStatus::TextTooLong as i32),
+            Some("text too long")
+        );
+        assert_eq!(message(99), Some("unknown status"));
+        assert_eq!(message(-1), Some("unknown status"));
+    }
+}
@@ -0,0 +1,186 @@
+// Streaming file tokenization
+// Importing a large EPUB spine or TXT file on a phone can't read the whole
+// file into a string and hold every token of it at once. A file is read a
+// block at a time instead; whenever a chunk's worth of text is buffered, the
+// part up to the last sentence end (see chunk.rs) is analyzed and its tokens
+// are handed to a sink callback, then freed. Memory stays at about one chunk
+// of text and its tokens, whatever the size of the file.
+
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::os::raw::{c_char, c_void};
+
+use crate::chunk::{self, SUDACHI_DEFAULT_CHUNK_BYTES};
+use crate::status::{guarded, require_str, SudachiStatus};
+use crate::{
+    handle, into_token_array, sudachi_free_tokens, SudachiToken, SudachiTokenMode,
+    SudachiTokenizer, TokenData, SUDACHI_FIELD_ALL, SUDACHI_MAX_TEXT_BYTES,
+};
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Receives the tokens of a run of whole sentences
+/// The tokens are only valid during the call (copy what you keep); offsets
+/// are byte offsets into the file. Return false to stop reading.
+pub type SudachiSinkCallback = Option<
+    extern "C" fn(user_data: *mut c_void, tokens: *const *const SudachiToken, count: usize) -> bool,
+>;
+
+/// Read text from reader and pass it to emit in sentence-aligned pieces of
+/// about max bytes, with their byte offsets in the input
+/// Up to two pieces are buffered, so a piece always has a full window to
+/// find its sentence end in; the last piece may be up to twice as long.
+/// Stops early, without error, when emit returns Ok(false).
+fn pump(
+    mut reader: impl Read,
+    max: usize,
+    mut emit: impl FnMut(usize, &str) -> Result<bool, SudachiStatus>,
+) -> Result<(), SudachiStatus> {
+    let mut buffer: Vec<u8> = Vec::with_capacity(3 * max);
+    let mut block = vec![0u8; max];
+    // Offset of buffer[0] in the input
+    let mut base = 0;
+    let mut eof = false;
+    loop {
+        while !eof && buffer.len() < 2 * max {
+            match reader.read(&mut block) {
+                Ok(0) => eof = true,
+                Ok(n) => buffer.extend_from_slice(&block[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return Err(SudachiStatus::IoError),
+            }
+        }
+        if base == 0 && buffer.starts_with(BOM) {
+            buffer.drain(..BOM.len());
+            base = BOM.len();
+        }
+
+        // A sequence split by the block boundary waits for the next read
+        let valid = match std::str::from_utf8(&buffer) {
+            Ok(text) => text,
+            Err(e) if e.error_len().is_none() && !eof => {
+                std::str::from_utf8(&buffer[..e.valid_up_to()]).unwrap()
+            }
+            Err(_) => return Err(SudachiStatus::InvalidUtf8),
+        };
+        if eof {
+            if !valid.is_empty() {
+                emit(base, valid)?;
+            }
+            return Ok(());
+        }
+        let len = if valid.len() <= max {
+            valid.len()
+        } else {
+            chunk::cut(valid, max)
+        };
+        if !emit(base, &valid[..len])? {
+            return Ok(());
+        }
+        buffer.drain(..len);
+        base += len;
+    }
+}
+
+/// Tokenize a UTF-8 text file, streaming results to a sink
+/// The file is read incrementally and analyzed in sentence-aligned pieces of
+/// the handle's chunk size (32 KiB if chunking is off); callback is called
+/// with user_data for every piece, on the calling thread. A leading BOM is
+/// skipped. Token offsets are i32, so reading stops with TextTooLong at
+/// SUDACHI_MAX_TEXT_BYTES (2 GiB).
+/// Returns Ok when the whole file was read or the callback stopped it,
+/// IoError if the file can't be read, InvalidUtf8 if it is not UTF-8.
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_file(
+    tokenizer: *mut SudachiTokenizer,
+    path: *const c_char,
+    mode: SudachiTokenMode,
+    callback: SudachiSinkCallback,
+    user_data: *mut c_void,
+) -> SudachiStatus {
+    let Some(callback) = callback else {
+        return SudachiStatus::InvalidArgument;
+    };
+    if tokenizer.is_null() {
+        return SudachiStatus::InvalidArgument;
+    }
+
+    guarded(|| {
+        let tokenizer = unsafe { handle::borrow(tokenizer) };
+        let path = require_str(path)?;
+        let file = File::open(path).map_err(|_| SudachiStatus::IoError)?;
+        let max = match tokenizer.options().chunk_bytes {
+            0 => SUDACHI_DEFAULT_CHUNK_BYTES,
+            bytes => bytes,
+        };
+        pump(file, max, |base, text| {
+            if base + text.len() > SUDACHI_MAX_TEXT_BYTES {
+                return Err(SudachiStatus::TextTooLong);
+            }
+            let tokens = tokenizer
+                .analyze(text, mode.into(), SUDACHI_FIELD_ALL)
+                .map_err(|_| SudachiStatus::AnalysisError)?;
+            if tokens.is_empty() {
+                return Ok(true);
+            }
+            let raw = tokens
+                .into_iter()
+                .filter_map(|mut token| {
+                    token.begin += base;
+                    token.end += base;
+                    TokenData::into_raw(token)
+                })
+                .collect();
+            let mut count = 0;
+            let array = into_token_array(raw, &mut count);
+            let more = callback(user_data, array as *const *const SudachiToken, count);
+            sudachi_free_tokens(array, count);
+            Ok(more)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(input: &[u8], max: usize) -> Result<Vec<(usize, String)>, SudachiStatus> {
+        let mut pieces = Vec::new();
+        pump(input, max, |base, text| {
+            pieces.push((base, text.to_string()));
+            Ok(true)
+        })?;
+        Ok(pieces)
+    }
+
+    #[test]
+    fn test_pump() {
+        let text = "\u{feff}今日は晴れ。明日は雨。明後日も雨。";
+        let split = pieces(text.as_bytes(), 20).unwrap();
+        assert_eq!(
+            split,
+            [
+                (3, "今日は晴れ。".to_string()),
+                (21, "明日は雨。明後日も雨。".to_string())
+            ]
+        );
+        for (base, piece) in &split {
+            assert_eq!(&text[*base..*base + piece.len()], piece);
+        }
+
+        assert_eq!(pieces(b"abc\xff", 2), Err(SudachiStatus::InvalidUtf8));
+        assert_eq!(
+            pieces("あ".as_bytes()[..2].as_ref(), 8),
+            Err(SudachiStatus::InvalidUtf8)
+        );
+        assert_eq!(pieces(b"", 8), Ok(vec![]));
+
+        let mut calls = 0;
+        pump("一。二。三。".as_bytes(), 6, |_, _| {
+            calls += 1;
+            Ok(false)
+        })
+        .unwrap();
+        assert_eq!(calls, 1);
+    }
+}
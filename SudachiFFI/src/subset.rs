@@ -0,0 +1,250 @@
+// Dictionary subsets
+// The app ships a small starter dictionary and downloads the full one later.
+// Both are built from the same lexicon sources: the starter keeps the rows of
+// the lexicon CSV whose surface is in a word list or passes a caller filter,
+// and is compiled with the same connection matrix, so its costs and
+// connection ids agree with the full dictionary.
+//
+// Rows refer to other rows by their zero-based line in the CSV: the
+// dictionary form (column 13), the A and B splits (15, 16) and the word
+// structure (17), slash-separated. Every row referred to by a kept row is
+// kept too, transitively, so a conjugated form brings its base form and a
+// compound its parts, and the references are renumbered to the lines of the
+// subset. Inline references (surface,POS,reading) are left to the
+// dictionary builder, which fails if their target was not kept.
+
+use std::collections::{BTreeSet, HashSet};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+
+use crate::builder::build;
+use crate::c_str;
+
+/// Columns that refer to other rows
+const REFERENCE_COLUMNS: [usize; 4] = [13, 15, 16, 17];
+
+/// Decides whether a lexicon row is kept; row is the CSV line
+pub type SudachiExportFilter =
+    Option<extern "C" fn(user_data: *mut c_void, row: *const c_char) -> bool>;
+
+/// Byte ranges of the fields of a CSV line, quotes included
+fn fields(line: &str) -> Vec<(usize, usize)> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                fields.push((start, i));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push((start, line.len()));
+    fields
+}
+
+/// Field value without surrounding quotes
+fn unquote(field: &str) -> &str {
+    field
+        .strip_prefix('"')
+        .and_then(|f| f.strip_suffix('"'))
+        .unwrap_or(field)
+}
+
+/// Line numbers a row refers to
+fn references(line: &str) -> Vec<usize> {
+    let fields = fields(line);
+    REFERENCE_COLUMNS
+        .iter()
+        .filter_map(|&column| fields.get(column))
+        .flat_map(|&(start, end)| unquote(&line[start..end]).split('/'))
+        .filter_map(|id| id.parse().ok())
+        .collect()
+}
+
+/// Row with its references renumbered by renumber
+fn renumber(line: &str, renumber: impl Fn(usize) -> usize) -> String {
+    let fields = fields(line);
+    let mut row = String::with_capacity(line.len());
+    for (column, &(start, end)) in fields.iter().enumerate() {
+        if column > 0 {
+            row.push(',');
+        }
+        let field = &line[start..end];
+        if !REFERENCE_COLUMNS.contains(&column) {
+            row.push_str(field);
+            continue;
+        }
+        let value = unquote(field);
+        let ids: Vec<String> = value
+            .split('/')
+            .map(|id| match id.parse() {
+                Ok(id) => renumber(id).to_string(),
+                Err(_) => id.to_string(),
+            })
+            .collect();
+        let value = ids.join("/");
+        if field.starts_with('"') {
+            row.push('"');
+            row.push_str(&value);
+            row.push('"');
+        } else {
+            row.push_str(&value);
+        }
+    }
+    row
+}
+
+/// Rows of lexicon selected by keep (given the line number and row) and
+/// those they refer to, renumbered
+pub(crate) fn select(lexicon: &str, mut keep: impl FnMut(usize, &str) -> bool) -> String {
+    let lines: Vec<&str> = lexicon.lines().map(|l| l.trim_end_matches('\r')).collect();
+    let mut kept = BTreeSet::new();
+    let mut pending: Vec<usize> = (0..lines.len())
+        .filter(|&i| !lines[i].is_empty() && keep(i, lines[i]))
+        .collect();
+    while let Some(i) = pending.pop() {
+        if i < lines.len() && kept.insert(i) {
+            pending.extend(references(lines[i]));
+        }
+    }
+    let kept: Vec<usize> = kept.into_iter().collect();
+    let mut subset = String::new();
+    for &i in &kept {
+        // References past the end are left as they are for the builder to
+        // report
+        let row = renumber(lines[i], |id| kept.binary_search(&id).unwrap_or(id));
+        subset.push_str(&row);
+        subset.push('\n');
+    }
+    subset
+}
+
+/// Build the rows of the lexicon CSV at lexicon_path selected by keep (see
+/// select) with the connection matrix at matrix_path, and write the
+/// dictionary to out_path
+/// Errors are reported on stderr.
+pub(crate) fn export(
+    lexicon_path: &str,
+    matrix_path: &str,
+    out_path: &str,
+    description: &str,
+    keep: impl FnMut(usize, &str) -> bool,
+) -> bool {
+    let read = |path: &str| {
+        std::fs::read(path)
+            .map_err(|e| eprintln!("Failed to read {}: {}", path, e))
+            .ok()
+    };
+    let Some(lexicon) = read(lexicon_path).and_then(|l| String::from_utf8(l).ok()) else {
+        return false;
+    };
+    let Some(matrix) = read(matrix_path) else {
+        return false;
+    };
+    let subset = select(&lexicon, keep);
+    let Some(image) = build(description, &matrix, &[subset.into_bytes()], |_| {}) else {
+        return false;
+    };
+    match std::fs::write(out_path, image) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", out_path, e);
+            false
+        }
+    }
+}
+
+/// Write a system dictionary with the entries of a lexicon CSV whose
+/// surface is listed in words_path or that filter keeps, plus the entries
+/// they refer to, compiled with the connection matrix matrix_path
+/// words_path has one surface per line (# starts a comment). Either
+/// words_path or filter may be NULL, not both; filter is called once per
+/// row, on this thread.
+/// Returns false if an argument is NULL, a file cannot be read or written,
+/// or the subset does not build.
+#[no_mangle]
+pub extern "C" fn sudachi_dict_export_subset(
+    lexicon_path: *const c_char,
+    matrix_path: *const c_char,
+    words_path: *const c_char,
+    filter: SudachiExportFilter,
+    user_data: *mut c_void,
+    out_path: *const c_char,
+) -> bool {
+    let (Some(lexicon_path), Some(matrix_path), Some(out_path)) =
+        (c_str(lexicon_path), c_str(matrix_path), c_str(out_path))
+    else {
+        return false;
+    };
+    let words_path = c_str(words_path);
+    if words_path.is_none() && filter.is_none() {
+        return false;
+    }
+
+    let words: HashSet<String> = match words_path {
+        Some(path) => match std::fs::read(path) {
+            Ok(words) => String::from_utf8_lossy(&words)
+                .lines()
+                .map(str::trim)
+                .filter(|w| !w.is_empty() && !w.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path, e);
+                return false;
+            }
+        },
+        None => HashSet::new(),
+    };
+
+    let description = format!("subset of {}", lexicon_path);
+    export(
+        lexicon_path,
+        matrix_path,
+        out_path,
+        &description,
+        |_, row| {
+            let (start, end) = fields(row)[0];
+            if words.contains(unquote(&row[start..end])) {
+                return true;
+            }
+            let (Some(filter), Ok(row)) = (filter, CString::new(row)) else {
+                return false;
+            };
+            filter(user_data, row.as_ptr())
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let lexicon = "\
+猫,1,1,5000,猫,名詞,普通名詞,一般,*,*,*,ネコ,猫,*,A,*,*,*,*
+食べる,1,1,4000,食べる,動詞,一般,*,*,下一段-バ行,終止形-一般,タベル,食べる,*,A,*,*,*,*
+食べ,1,1,4000,食べ,動詞,一般,*,*,下一段-バ行,連用形-一般,タベ,食べる,1,A,*,*,*,*
+東京,1,1,4000,東京,名詞,固有名詞,地名,一般,*,*,トウキョウ,東京,*,A,*,*,*,*
+都,1,1,4000,都,名詞,普通名詞,一般,*,*,*,ト,都,*,A,*,*,*,*
+東京都,1,1,6000,東京都,名詞,固有名詞,地名,一般,*,*,トウキョウト,東京都,*,B,\"3/4\",*,\"3/4\",*
+";
+        let subset = select(lexicon, |_, row| {
+            row.starts_with("食べ,") || row.starts_with("東京都,")
+        });
+        let surfaces: Vec<&str> = subset
+            .lines()
+            .map(|l| l.split(',').next().unwrap())
+            .collect();
+        assert_eq!(surfaces, ["食べる", "食べ", "東京", "都", "東京都"]);
+        let lines: Vec<&str> = subset.lines().collect();
+        assert_eq!(references(lines[1]), [0]);
+        assert_eq!(references(lines[4]), [2, 3, 2, 3]);
+        assert!(lines[4].contains(",B,\"2/3\",*,\"2/3\","));
+    }
+}
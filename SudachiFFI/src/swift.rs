@@ -0,0 +1,130 @@
+// High-level Swift bindings (uniffi feature)
+// Exposes Dictionary, Tokenizer and Token as Swift classes and structs with
+// thrown errors and reference-counted lifetimes, so Swift code never touches
+// the raw pointers of the C API.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use sudachi::dic::dictionary::JapaneseDictionary;
+use sudachi::prelude::Mode;
+
+use crate::analyzer::Analyzer;
+use crate::{load_dictionary, TokenData, SUDACHI_FIELD_ALL};
+
+#[derive(Debug, uniffi::Error)]
+pub enum TokenizerError {
+    /// The dictionary could not be opened or parsed
+    Dictionary { message: String },
+    /// Analysis of the input failed
+    Analysis { message: String },
+}
+
+impl fmt::Display for TokenizerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizerError::Dictionary { message } => write!(f, "dictionary error: {}", message),
+            TokenizerError::Analysis { message } => write!(f, "analysis error: {}", message),
+        }
+    }
+}
+
+/// Split mode: A is shortest units, C is longest (named entities)
+#[derive(Debug, Clone, Copy, uniffi::Enum)]
+pub enum TokenMode {
+    A,
+    B,
+    C,
+}
+
+impl From<TokenMode> for Mode {
+    fn from(mode: TokenMode) -> Self {
+        match mode {
+            TokenMode::A => Mode::A,
+            TokenMode::B => Mode::B,
+            TokenMode::C => Mode::C,
+        }
+    }
+}
+
+/// One morpheme of an analysis
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Token {
+    pub surface: String,
+    pub reading: String,
+    pub dictionary_form: String,
+    pub dictionary_reading: String,
+    pub normalized_form: String,
+    pub pos: Vec<String>,
+    /// Byte offsets into the UTF-8 input
+    pub begin: u32,
+    pub end: u32,
+    pub word_id: u32,
+    pub pos_id: u16,
+    /// Stable identifier of the lemma, 0 for foreign runs
+    pub lemma_key: u64,
+}
+
+impl From<TokenData> for Token {
+    fn from(t: TokenData) -> Self {
+        Token {
+            surface: t.surface,
+            reading: t.reading,
+            dictionary_form: t.dictionary_form,
+            dictionary_reading: t.dictionary_reading,
+            normalized_form: t.normalized_form,
+            pos: t.pos,
+            begin: t.begin as u32,
+            end: t.end as u32,
+            word_id: t.word_id,
+            pos_id: t.pos_id,
+            lemma_key: t.lemma_key,
+        }
+    }
+}
+
+/// A loaded system dictionary, shareable between tokenizers
+#[derive(uniffi::Object)]
+pub struct Dictionary {
+    inner: Arc<JapaneseDictionary>,
+}
+
+#[uniffi::export]
+impl Dictionary {
+    /// Open a system dictionary file
+    #[uniffi::constructor]
+    pub fn open(path: String) -> Result<Arc<Self>, TokenizerError> {
+        let inner = load_dictionary(&path)
+            .map_err(|message| TokenizerError::Dictionary { message })?
+            .dictionary;
+        Ok(Arc::new(Dictionary { inner }))
+    }
+}
+
+/// A tokenizer over a dictionary
+/// Safe to call from any thread; calls on one tokenizer are serialized.
+#[derive(uniffi::Object)]
+pub struct Tokenizer {
+    analyzer: Mutex<Analyzer>,
+}
+
+#[uniffi::export]
+impl Tokenizer {
+    #[uniffi::constructor]
+    pub fn new(dictionary: Arc<Dictionary>) -> Arc<Self> {
+        Arc::new(Tokenizer {
+            analyzer: Mutex::new(Analyzer::new(dictionary.inner.clone())),
+        })
+    }
+
+    /// Analyze text into tokens
+    pub fn tokenize(&self, text: String, mode: TokenMode) -> Result<Vec<Token>, TokenizerError> {
+        let mut analyzer = self.analyzer.lock().unwrap_or_else(PoisonError::into_inner);
+        let tokens = analyzer
+            .analyze(&text, mode.into(), SUDACHI_FIELD_ALL)
+            .map_err(|e| TokenizerError::Analysis {
+                message: e.to_string(),
+            })?;
+        Ok(tokens.into_iter().map(Token::from).collect())
+    }
+}
@@ -0,0 +1,213 @@
+// Emoji and symbol segmentation
+// Emoji sequences, kaomoji and runs of repeated punctuation are not in the
+// dictionary, and OOV handling breaks them into many fragments (down to
+// single code points inside a ZWJ sequence). After analysis, fragments are
+// merged back: tokens never end inside a grapheme cluster, consecutive emoji
+// form one token, as do runs of one symbol (〜〜〜) or of !/? marks (！？！),
+// and a parenthesized kaomoji is one token. Every token gets a class.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::kana::{is_kana, is_kanji};
+use crate::TokenData;
+
+/// Broad class of a token
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SudachiTokenClass {
+    /// Words, numbers and anything mixed
+    Word = 0,
+    /// Punctuation and symbols only
+    Symbol = 1,
+    /// Emoji (including ZWJ, flag and keycap sequences) and kaomoji
+    Emoji = 2,
+    /// Not analyzed (a line skipped by the language gate, or uncovered text)
+    Opaque = 3,
+    /// Spaces, tabs and line breaks only
+    Whitespace = 4,
+    /// Onomatopoeia, in SFX mode
+    Sfx = 5,
+}
+
+impl SudachiTokenClass {
+    /// Class stored as an integer (e.g. in the analysis cache)
+    /// Unknown values read as Word
+    pub(crate) fn from_tag(tag: u8) -> SudachiTokenClass {
+        match tag {
+            1 => SudachiTokenClass::Symbol,
+            2 => SudachiTokenClass::Emoji,
+            3 => SudachiTokenClass::Opaque,
+            4 => SudachiTokenClass::Whitespace,
+            5 => SudachiTokenClass::Sfx,
+            _ => SudachiTokenClass::Word,
+        }
+    }
+}
+
+const EXCLAMATIONS: &[char] = &['!', '?', '！', '？', '‼', '⁇', '⁈', '⁉'];
+
+fn is_emoji(cluster: &str) -> bool {
+    cluster.chars().any(|c| {
+        matches!(c,
+            '\u{1F000}'..='\u{1FAFF}' | '\u{2600}'..='\u{27BF}' | '\u{2300}'..='\u{23FF}'
+            | '\u{2B50}'..='\u{2B55}' | '\u{FE0F}' | '\u{20E3}')
+    })
+}
+
+fn is_symbol(c: char) -> bool {
+    !c.is_alphanumeric() && !c.is_whitespace()
+}
+
+/// Class of a token surface
+pub(crate) fn token_class(surface: &str) -> SudachiTokenClass {
+    if surface.is_empty() {
+        SudachiTokenClass::Word
+    } else if surface.chars().all(char::is_whitespace) {
+        SudachiTokenClass::Whitespace
+    } else if surface.graphemes(true).all(is_emoji) {
+        SudachiTokenClass::Emoji
+    } else if surface.chars().all(is_symbol) {
+        SudachiTokenClass::Symbol
+    } else {
+        SudachiTokenClass::Word
+    }
+}
+
+/// Whether a symbol run can continue with next
+fn continues(run: &str, next: &str) -> bool {
+    let mut chars = run.chars().chain(next.chars());
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return false,
+    };
+    if EXCLAMATIONS.contains(&first) {
+        chars.all(|c| EXCLAMATIONS.contains(&c))
+    } else {
+        is_symbol(first) && chars.all(|c| c == first)
+    }
+}
+
+/// Length of a kaomoji at the start of text
+/// A kaomoji is a bracketed face of up to 12 symbols and letters such as
+/// (^_^), (´・ω・`) or （＾▽＾）, without kana, kanji or digits and with at
+/// least one symbol, so (笑), (1/2) and (TM) are not kaomoji.
+fn kaomoji_len(text: &str) -> Option<usize> {
+    let close = match text.chars().next()? {
+        '(' => ')',
+        '（' => '）',
+        _ => return None,
+    };
+    let open_len = text.chars().next()?.len_utf8();
+    let inner_end = text[open_len..]
+        .char_indices()
+        .take(13)
+        .find(|&(_, c)| c == close)?
+        .0;
+    let inner = &text[open_len..open_len + inner_end];
+    let valid = inner.chars().count() >= 2
+        && !inner.starts_with(' ')
+        && inner.chars().any(is_symbol)
+        && !inner
+            .chars()
+            .any(|c| is_kana(c) || is_kanji(c) || c.is_numeric());
+    valid.then(|| open_len + inner_end + close.len_utf8())
+}
+
+/// Merge emoji, kaomoji and symbol fragments and set every token's class
+/// Offsets of tokens are relative to text.
+pub(crate) fn merge_symbols(text: &str, tokens: Vec<TokenData>) -> Vec<TokenData> {
+    let clusters: Vec<usize> = text.grapheme_indices(true).map(|(i, _)| i).collect();
+    let is_boundary = |offset: usize| clusters.binary_search(&offset).is_ok();
+    let joins = |run: &str, next: &str, next_begin: usize| {
+        let class = token_class(run);
+        !is_boundary(next_begin)
+            || (class == SudachiTokenClass::Emoji && token_class(next) == class)
+            || (class == SudachiTokenClass::Symbol && continues(run, next))
+    };
+
+    // Group tokens as (count, end, kaomoji)
+    let spans: Vec<(usize, usize)> = tokens.iter().map(|t| (t.begin, t.end)).collect();
+    let mut groups = Vec::new();
+    let mut i = 0;
+    while i < spans.len() {
+        let begin = spans[i].0;
+        let mut j = i + 1;
+        // A kaomoji is merged only if some token ends exactly where it does
+        let kaomoji = kaomoji_len(&text[begin..]).and_then(|len| {
+            spans[i..]
+                .iter()
+                .take_while(|s| s.0 < begin + len)
+                .position(|s| s.1 == begin + len)
+        });
+        match kaomoji {
+            Some(k) => j = i + k + 1,
+            None => {
+                while j < spans.len()
+                    && joins(
+                        &text[begin..spans[j - 1].1],
+                        &text[spans[j].0..spans[j].1],
+                        spans[j].0,
+                    )
+                {
+                    j += 1;
+                }
+            }
+        }
+        groups.push((j - i, spans[j - 1].1, kaomoji.is_some()));
+        i = j;
+    }
+
+    let mut tokens = tokens.into_iter();
+    let mut merged = Vec::with_capacity(groups.len());
+    for (count, end, kaomoji) in groups {
+        let Some(token) = tokens.next() else { break };
+        for _ in 1..count {
+            tokens.next();
+        }
+        let token = if count > 1 {
+            token.extend_to(text, end)
+        } else {
+            token
+        };
+        let class = if kaomoji {
+            SudachiTokenClass::Emoji
+        } else {
+            token_class(&text[token.begin..token.end])
+        };
+        merged.push(TokenData { class, ..token });
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testtoken::shredded;
+
+    #[test]
+    fn test_merge_symbols() {
+        let text = "え！？！〜〜〜👨\u{200d}👩\u{200d}👧🔥(^_^)(笑)。」";
+        let merged = merge_symbols(text, shredded(text));
+        let tokens: Vec<(&str, SudachiTokenClass)> = merged
+            .iter()
+            .map(|t| (&text[t.begin..t.end], t.class))
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                ("え", SudachiTokenClass::Word),
+                ("！？！", SudachiTokenClass::Symbol),
+                ("〜〜〜", SudachiTokenClass::Symbol),
+                ("👨\u{200d}👩\u{200d}👧🔥", SudachiTokenClass::Emoji),
+                ("(^_^)", SudachiTokenClass::Emoji),
+                ("(", SudachiTokenClass::Symbol),
+                ("笑", SudachiTokenClass::Word),
+                (")", SudachiTokenClass::Symbol),
+                ("。", SudachiTokenClass::Symbol),
+                ("」", SudachiTokenClass::Symbol),
+            ]
+        );
+        assert_eq!(merged[1].surface, "！？！");
+    }
+}
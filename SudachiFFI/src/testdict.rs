@@ -0,0 +1,35 @@
+// Miniature test dictionary
+// Compiles testdata/lex.csv and testdata/matrix.def into a system dictionary
+// image once per test run, so tests can exercise real analysis without a
+// system.dic on disk.
+
+use std::sync::OnceLock;
+
+use sudachi::dic::build::DictBuilder;
+
+use crate::{sudachi_init_from_bytes, SudachiTokenizer};
+
+pub(crate) const LEXICON: &[u8] = include_bytes!("../testdata/lex.csv");
+pub(crate) const MATRIX: &[u8] = include_bytes!("../testdata/matrix.def");
+
+fn image() -> &'static [u8] {
+    static IMAGE: OnceLock<Vec<u8>> = OnceLock::new();
+    IMAGE.get_or_init(|| {
+        let mut builder = DictBuilder::new_system();
+        builder.set_description("sudachi-ios test dictionary");
+        builder.read_conn(MATRIX).expect("matrix.def");
+        builder.read_lexicon(LEXICON).expect("lex.csv");
+        builder.resolve().expect("resolve");
+        let mut image = Vec::new();
+        builder.compile(&mut image).expect("compile");
+        image
+    })
+}
+
+/// New tokenizer over the test dictionary (caller must free)
+pub(crate) fn tokenizer() -> *mut SudachiTokenizer {
+    let image = image();
+    let tokenizer = sudachi_init_from_bytes(image.as_ptr(), image.len());
+    assert!(!tokenizer.is_null(), "test dictionary failed to load");
+    tokenizer
+}
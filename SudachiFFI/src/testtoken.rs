@@ -0,0 +1,42 @@
+// Token fixtures
+// Hand-built tokens for tests of the passes that rework analyzed tokens
+// (symbol, script, whitespace and compound merging), which need no
+// dictionary.
+
+use crate::script::SudachiScript;
+use crate::symbol::{token_class, SudachiTokenClass};
+use crate::{TokenData, SUDACHI_FIELD_ALL};
+
+/// Token of surface at byte offset begin, with the surface as its forms and
+/// no reading or POS
+pub(crate) fn word(begin: usize, surface: &str) -> TokenData {
+    TokenData {
+        fields: SUDACHI_FIELD_ALL,
+        surface: surface.to_string(),
+        reading: String::new(),
+        dictionary_form: surface.to_string(),
+        dictionary_reading: String::new(),
+        normalized_form: surface.to_string(),
+        pos: Vec::new(),
+        begin,
+        end: begin + surface.len(),
+        word_id: 0,
+        pos_id: 0,
+        script: SudachiScript::Japanese,
+        class: token_class(surface),
+        flags: 0,
+        lemma_key: 0,
+    }
+}
+
+/// Tokens of one character each, as OOV handling can produce
+/// Each token's word_id is its offset.
+pub(crate) fn shredded(text: &str) -> Vec<TokenData> {
+    text.char_indices()
+        .map(|(i, c)| TokenData {
+            word_id: i as u32,
+            class: SudachiTokenClass::Word,
+            ..word(i, &c.to_string())
+        })
+        .collect()
+}
@@ -0,0 +1,130 @@
+// Character to token map
+// Hit-testing a tap against token ranges means a scan over the tokens on
+// every tap. sudachi_token_map precomputes the owning token of every
+// character (or UTF-16 code unit, for NSString/UITextView indices) of the
+// input so a lookup is a single array index.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::{c_str, handle, leak, SudachiToken};
+
+/// Map entry for characters not covered by any token
+pub const SUDACHI_TOKEN_MAP_NONE: u32 = u32::MAX;
+
+/// Index unit of a token map
+/// cbindgen:prefix-with-name
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SudachiTextUnit {
+    /// Unicode scalar values (Swift Character indices differ for clusters)
+    Char = 0,
+    /// UTF-16 code units, as used by NSString and NSRange
+    Utf16 = 1,
+}
+
+/// Token index of every unit of text, given each token's byte range
+fn token_map(
+    text: &str,
+    ranges: impl Iterator<Item = (i32, i32)>,
+    unit: SudachiTextUnit,
+) -> Vec<u32> {
+    let mut owner = vec![SUDACHI_TOKEN_MAP_NONE; text.len()];
+    for (i, (begin, end)) in ranges.enumerate() {
+        let end = (end.max(0) as usize).min(text.len());
+        let begin = (begin.max(0) as usize).min(end);
+        owner[begin..end].fill(i as u32);
+    }
+
+    let mut map = Vec::with_capacity(match unit {
+        SudachiTextUnit::Char => text.chars().count(),
+        SudachiTextUnit::Utf16 => text.encode_utf16().count(),
+    });
+    for (at, c) in text.char_indices() {
+        let units = match unit {
+            SudachiTextUnit::Char => 1,
+            SudachiTextUnit::Utf16 => c.len_utf16(),
+        };
+        for _ in 0..units {
+            map.push(owner[at]);
+        }
+    }
+    map
+}
+
+/// Map every character of text to the index of the token containing it
+/// tokens must be the result of analyzing text (any field mask). Entries
+/// are SUDACHI_TOKEN_MAP_NONE where no token covers the character; out_len
+/// receives the length of text in unit.
+/// Returns NULL on failure (caller must free with sudachi_free_token_map)
+#[no_mangle]
+pub extern "C" fn sudachi_token_map(
+    text: *const c_char,
+    tokens: *const *mut SudachiToken,
+    count: usize,
+    unit: SudachiTextUnit,
+    out_len: *mut usize,
+) -> *mut u32 {
+    if (tokens.is_null() && count > 0) || out_len.is_null() {
+        return ptr::null_mut();
+    }
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+    let tokens: &[*mut SudachiToken] = if count == 0 {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(tokens, count) }
+    };
+    if tokens.iter().any(|t| t.is_null()) {
+        return ptr::null_mut();
+    }
+
+    let ranges = tokens.iter().map(|&t| {
+        let token = unsafe { handle::borrow(t) };
+        (token.begin, token.end)
+    });
+    let map = token_map(text, ranges, unit);
+
+    unsafe {
+        *out_len = map.len();
+    }
+    let map = Box::leak(map.into_boxed_slice()).as_mut_ptr();
+    leak::track(map, "token map");
+    map
+}
+
+/// Free the result of sudachi_token_map
+#[no_mangle]
+pub extern "C" fn sudachi_free_token_map(map: *mut u32, len: usize) {
+    if !map.is_null() {
+        leak::release(map);
+        unsafe {
+            let _ = Box::from_raw(ptr::slice_from_raw_parts_mut(map, len));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_map() {
+        // 猫 | が | 🐈 (outside the BMP, two UTF-16 units) | 。 not covered
+        let text = "猫が🐈。";
+        let ranges = [(0, 3), (3, 6), (6, 10)];
+        let none = SUDACHI_TOKEN_MAP_NONE;
+
+        let chars = token_map(text, ranges.into_iter(), SudachiTextUnit::Char);
+        assert_eq!(chars, [0, 1, 2, none]);
+
+        let utf16 = token_map(text, ranges.into_iter(), SudachiTextUnit::Utf16);
+        assert_eq!(utf16, [0, 1, 2, 2, none]);
+
+        // Out-of-range offsets are clamped rather than trusted
+        let clamped = token_map("猫", [(-1, 99)].into_iter(), SudachiTextUnit::Char);
+        assert_eq!(clamped, [0]);
+    }
+}
@@ -0,0 +1,171 @@
+// Tracing spans
+// With the tracing feature, dictionary loading, lattice construction and
+// result marshalling run inside tracing spans. Embedders either install their
+// own subscriber or register a C callback with sudachi_trace_set_callback to
+// receive span begin/end events (e.g. to emit Instruments signposts).
+// Without the feature the span! macro expands to nothing.
+
+/// Run the rest of the enclosing block inside a span named $name
+macro_rules! span {
+    ($name:literal) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($name).entered();
+    };
+}
+pub(crate) use span;
+
+#[cfg(feature = "tracing")]
+pub use callback::*;
+
+#[cfg(feature = "tracing")]
+mod callback {
+    use std::os::raw::c_void;
+    use std::sync::{OnceLock, PoisonError, RwLock};
+    use std::time::Instant;
+
+    use tracing::span::{Attributes, Id};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    /// Span boundary reported to a trace callback
+    /// cbindgen:prefix-with-name
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum SudachiTracePhase {
+        Begin = 0,
+        End = 1,
+    }
+
+    /// One span event
+    /// name is not NUL-terminated and stays valid for the process lifetime.
+    #[repr(C)]
+    pub struct SudachiTraceEvent {
+        pub name: *const u8,
+        pub name_len: usize,
+        pub phase: SudachiTracePhase,
+        /// Identifies the span; shared by its Begin and End events
+        pub span_id: u64,
+        /// Nanoseconds since the callback was first registered
+        pub timestamp_ns: u64,
+        /// Span duration for End events, 0 for Begin
+        pub duration_ns: u64,
+    }
+
+    /// Receives span events on the thread that ran the span
+    pub type SudachiTraceCallback =
+        Option<extern "C" fn(user_data: *mut c_void, event: *const SudachiTraceEvent)>;
+
+    struct Registered {
+        callback: extern "C" fn(*mut c_void, *const SudachiTraceEvent),
+        user_data: usize,
+    }
+
+    static CALLBACK: RwLock<Option<Registered>> = RwLock::new(None);
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    /// Whether our subscriber became the global default
+    static INSTALLED: OnceLock<bool> = OnceLock::new();
+
+    fn emit(name: &'static str, phase: SudachiTracePhase, id: &Id, duration_ns: u64) {
+        let registered = CALLBACK.read().unwrap_or_else(PoisonError::into_inner);
+        let Some(registered) = registered.as_ref() else {
+            return;
+        };
+        let epoch = EPOCH.get_or_init(Instant::now);
+        let event = SudachiTraceEvent {
+            name: name.as_ptr(),
+            name_len: name.len(),
+            phase,
+            span_id: id.into_u64(),
+            timestamp_ns: epoch.elapsed().as_nanos() as u64,
+            duration_ns,
+        };
+        (registered.callback)(registered.user_data as *mut c_void, &event);
+    }
+
+    /// Forwards span lifetimes to the registered callback
+    struct CallbackLayer;
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for CallbackLayer {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(Instant::now());
+            }
+            emit(attrs.metadata().name(), SudachiTracePhase::Begin, id, 0);
+        }
+
+        fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(&id) {
+                let duration = span
+                    .extensions()
+                    .get::<Instant>()
+                    .map_or(0, |start| start.elapsed().as_nanos() as u64);
+                emit(span.name(), SudachiTracePhase::End, &id, duration);
+            }
+        }
+    }
+
+    /// Register a callback for span events, or clear it with NULL
+    /// The first registration installs the library's subscriber as the global
+    /// default. Returns false if another global subscriber is already
+    /// installed; spans then go to that subscriber instead.
+    #[no_mangle]
+    pub extern "C" fn sudachi_trace_set_callback(
+        callback: SudachiTraceCallback,
+        user_data: *mut c_void,
+    ) -> bool {
+        let installed = *INSTALLED.get_or_init(|| {
+            EPOCH.get_or_init(Instant::now);
+            let subscriber = tracing_subscriber::registry().with(CallbackLayer);
+            tracing::subscriber::set_global_default(subscriber).is_ok()
+        });
+        if !installed {
+            eprintln!("sudachi_trace_set_callback: a global tracing subscriber is already set");
+            return false;
+        }
+
+        *CALLBACK.write().unwrap_or_else(PoisonError::into_inner) =
+            callback.map(|callback| Registered {
+                callback,
+                user_data: user_data as usize,
+            });
+        true
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::ptr;
+        use std::sync::Mutex;
+
+        static EVENTS: Mutex<Vec<(String, SudachiTracePhase)>> = Mutex::new(Vec::new());
+
+        extern "C" fn record(_user_data: *mut c_void, event: *const SudachiTraceEvent) {
+            let event = unsafe { &*event };
+            let name = unsafe { std::slice::from_raw_parts(event.name, event.name_len) };
+            EVENTS
+                .lock()
+                .unwrap()
+                .push((String::from_utf8_lossy(name).into_owned(), event.phase));
+        }
+
+        #[test]
+        fn test_trace_callback() {
+            assert!(sudachi_trace_set_callback(Some(record), ptr::null_mut()));
+            {
+                crate::trace::span!("test_span");
+            }
+            assert!(sudachi_trace_set_callback(None, ptr::null_mut()));
+
+            // Other tests may run spans concurrently
+            let events = EVENTS.lock().unwrap();
+            let phases: Vec<_> = events
+                .iter()
+                .filter(|(name, _)| name == "test_span")
+                .map(|&(_, phase)| phase)
+                .collect();
+            assert_eq!(phases, [SudachiTracePhase::Begin, SudachiTracePhase::End]);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+// Dictionary usage profiles
+// The embedded default dictionary is mostly technical and rare vocabulary
+// that the app's texts never reach. A usage profile records, over a corpus
+// analyzed through a handle, how often each system dictionary entry ends up
+// in the analysis, and exports a dictionary of only the entries hit (see
+// subset.rs), for a smaller default build. A system entry's word id is its
+// zero-based line in the lexicon CSV the dictionary was built from, so the
+// profile is only meaningful with that same CSV. Unknown words and entries of
+// user dictionaries are not recorded. Like corpus frequencies, the table
+// lives in memory and is written to the profile file on save.
+//
+// File layout (all integers little-endian):
+//   header:  b"MGXU" | u32 format version | u64 texts | u64 entry count
+//   entry:   u64 lexicon line | u64 hits
+// Entries are sorted by line, so equal tables give equal files.
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use sudachi::dic::word_id::WordId;
+
+use crate::subset::export;
+use crate::{c_str, handle, leak, SudachiTokenMode, SudachiTokenizer};
+
+const MAGIC: &[u8; 4] = b"MGXU";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: usize = 8 + 16;
+const ENTRY_LEN: usize = 16;
+
+#[derive(Debug, Default, PartialEq)]
+struct Table {
+    texts: u64,
+    /// Hits by lexicon line
+    hits: BTreeMap<u64, u64>,
+}
+
+impl Table {
+    /// Count one text given the word ids of its tokens
+    fn add(&mut self, word_ids: impl IntoIterator<Item = u32>) {
+        self.texts += 1;
+        for id in word_ids {
+            let id = WordId::from_raw(id);
+            if id.is_system() && !id.is_oov() {
+                *self.hits.entry(u64::from(id.word())).or_default() += 1;
+            }
+        }
+    }
+
+    /// Lines hit at least min_hits times
+    fn lines(&self, min_hits: u64) -> Vec<usize> {
+        self.hits
+            .iter()
+            .filter(|&(_, &hits)| hits >= min_hits)
+            .map(|(&line, _)| line as usize)
+            .collect()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.hits.len() * ENTRY_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        for n in [self.texts, self.hits.len() as u64] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        for (&line, &hits) in &self.hits {
+            for n in [line, hits] {
+                bytes.extend_from_slice(&n.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// None if bytes are not a profile of this format
+    fn decode(bytes: &[u8]) -> Option<Table> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return None;
+        }
+        if u32::from_le_bytes(bytes[4..8].try_into().ok()?) != FORMAT_VERSION {
+            return None;
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let count = usize::try_from(u64_at(16)).ok()?;
+        if count.checked_mul(ENTRY_LEN) != Some(bytes.len() - HEADER_LEN) {
+            return None;
+        }
+        let hits = (0..count)
+            .map(|i| {
+                let at = HEADER_LEN + i * ENTRY_LEN;
+                (u64_at(at), u64_at(at + 8))
+            })
+            .collect();
+        Some(Table {
+            texts: u64_at(8),
+            hits,
+        })
+    }
+}
+
+/// Opaque handle to a dictionary usage profile
+pub struct SudachiUsage {
+    path: PathBuf,
+    table: Mutex<Table>,
+}
+
+impl SudachiUsage {
+    fn table(&self) -> MutexGuard<'_, Table> {
+        self.table.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Open the usage profile stored at path, or start an empty one if there
+/// is no file
+/// Returns NULL if path is NULL or the file can't be read or is not a
+/// profile (caller must free with sudachi_usage_free)
+#[no_mangle]
+pub extern "C" fn sudachi_usage_open(path: *const c_char) -> *mut SudachiUsage {
+    let Some(path) = c_str(path) else {
+        return ptr::null_mut();
+    };
+    let table = match std::fs::read(path) {
+        Ok(bytes) => match Table::decode(&bytes) {
+            Some(table) => table,
+            None => return ptr::null_mut(),
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => Table::default(),
+        Err(_) => return ptr::null_mut(),
+    };
+    let usage = Box::into_raw(Box::new(SudachiUsage {
+        path: PathBuf::from(path),
+        table: Mutex::new(table),
+    }));
+    leak::track(usage, "SudachiUsage");
+    usage
+}
+
+/// Analyze a text and count the system dictionary entries of its tokens
+/// May be called from several threads. Returns false if an argument is
+/// NULL, text is not valid UTF-8 or the analysis fails.
+#[no_mangle]
+pub extern "C" fn sudachi_usage_add(
+    usage: *mut SudachiUsage,
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+) -> bool {
+    if usage.is_null() || tokenizer.is_null() {
+        return false;
+    }
+    let Some(text) = c_str(text) else {
+        return false;
+    };
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let Ok(tokens) = tokenizer.analyze(text, mode.into(), 0) else {
+        return false;
+    };
+    unsafe { &*usage }
+        .table()
+        .add(tokens.iter().map(|t| t.word_id));
+    true
+}
+
+/// Times a system dictionary entry was hit, by its lexicon line
+/// Returns 0 if usage is NULL or the entry was never hit.
+#[no_mangle]
+pub extern "C" fn sudachi_usage_hits(usage: *const SudachiUsage, line: u32) -> u64 {
+    if usage.is_null() {
+        return 0;
+    }
+    let table = unsafe { &*usage }.table();
+    table.hits.get(&u64::from(line)).copied().unwrap_or(0)
+}
+
+/// Write the profile to the path it was opened from
+/// The file is replaced atomically, so a crash mid-save keeps the previous
+/// table. Returns false if usage is NULL or the file can't be written.
+#[no_mangle]
+pub extern "C" fn sudachi_usage_save(usage: *const SudachiUsage) -> bool {
+    if usage.is_null() {
+        return false;
+    }
+    let usage = unsafe { &*usage };
+    let bytes = usage.table().encode();
+    let mut temp = usage.path.clone().into_os_string();
+    temp.push(".tmp");
+    std::fs::write(&temp, bytes).is_ok() && std::fs::rename(&temp, &usage.path).is_ok()
+}
+
+/// Write a system dictionary with the entries of lexicon_path hit at least
+/// min_hits times, plus the entries they refer to, compiled with the
+/// connection matrix matrix_path
+/// lexicon_path must be the lexicon CSV of the dictionary the profile was
+/// recorded with. Returns false if an argument is NULL, a file cannot be
+/// read or written, or the trimmed dictionary does not build.
+#[no_mangle]
+pub extern "C" fn sudachi_usage_export(
+    usage: *const SudachiUsage,
+    lexicon_path: *const c_char,
+    matrix_path: *const c_char,
+    min_hits: u64,
+    out_path: *const c_char,
+) -> bool {
+    if usage.is_null() {
+        return false;
+    }
+    let (Some(lexicon_path), Some(matrix_path), Some(out_path)) =
+        (c_str(lexicon_path), c_str(matrix_path), c_str(out_path))
+    else {
+        return false;
+    };
+    let lines = unsafe { &*usage }.table().lines(min_hits.max(1));
+
+    let description = format!("{} trimmed by usage", lexicon_path);
+    export(
+        lexicon_path,
+        matrix_path,
+        out_path,
+        &description,
+        |line, _| lines.binary_search(&line).is_ok(),
+    )
+}
+
+/// Free a usage profile without saving it
+#[no_mangle]
+pub extern "C" fn sudachi_usage_free(usage: *mut SudachiUsage) {
+    if !usage.is_null() {
+        leak::release(usage);
+        unsafe {
+            let _ = Box::from_raw(usage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let bytes = Table::default().encode();
+        assert_eq!(bytes.len(), HEADER_LEN);
+        assert_eq!(Table::decode(&bytes), Some(Table::default()));
+
+        let table = Table {
+            texts: 3,
+            hits: BTreeMap::from([(2, 5), (7, 1), (40, 2)]),
+        };
+        assert_eq!(table.lines(1), [2, 7, 40]);
+        assert_eq!(table.lines(2), [2, 40]);
+        let bytes = table.encode();
+        assert_eq!(bytes.len(), HEADER_LEN + 3 * ENTRY_LEN);
+        assert_eq!(Table::decode(&bytes), Some(table));
+        assert_eq!(Table::decode(&bytes[..bytes.len() - 1]), None);
+    }
+}
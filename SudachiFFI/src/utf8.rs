@@ -0,0 +1,186 @@
+// Byte input with UTF-8 diagnostics
+// OCR output occasionally contains broken bytes. Strict mode reports where
+// the first invalid sequence starts; lossy mode substitutes U+FFFD and maps
+// token offsets back onto the original bytes.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::status::{guarded, token_array, SudachiStatus};
+use crate::{handle, SudachiToken, SudachiTokenMode, SudachiTokenizer, SUDACHI_MAX_TEXT_BYTES};
+
+/// Replace invalid sequences with U+FFFD instead of failing
+pub const SUDACHI_INPUT_LOSSY: u32 = 1 << 0;
+
+/// Text repaired with U+FFFD, remembering where it diverges from the input
+pub(crate) struct LossyText {
+    pub(crate) text: String,
+    /// (offset in text, offset in input) just after each replacement
+    shifts: Vec<(usize, usize)>,
+}
+
+impl LossyText {
+    pub(crate) fn decode(bytes: &[u8]) -> LossyText {
+        let mut text = String::with_capacity(bytes.len());
+        let mut shifts = Vec::new();
+        let mut rest = bytes;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, invalid) = rest.split_at(e.valid_up_to());
+                    // SAFETY: from_utf8 validated this prefix
+                    text.push_str(unsafe { std::str::from_utf8_unchecked(valid) });
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    let skip = e.error_len().unwrap_or(invalid.len());
+                    rest = &invalid[skip..];
+                    shifts.push((text.len(), bytes.len() - rest.len()));
+                }
+            }
+        }
+        LossyText { text, shifts }
+    }
+
+    /// Map a char-boundary offset in text to the input bytes
+    pub(crate) fn original_offset(&self, offset: usize) -> usize {
+        match self.shifts.partition_point(|&(at, _)| at <= offset) {
+            0 => offset,
+            i => {
+                let (at, original) = self.shifts[i - 1];
+                original + (offset - at)
+            }
+        }
+    }
+}
+
+/// Tokenize length-delimited bytes (no NUL terminator needed)
+/// Without SUDACHI_INPUT_LOSSY, invalid UTF-8 returns InvalidUtf8 and writes
+/// the byte offset of the first invalid sequence to out_invalid_offset (may
+/// be NULL). With it, invalid sequences are analyzed as U+FFFD and token
+/// offsets still refer to the original bytes. Returns TextTooLong if len is
+/// over SUDACHI_MAX_TEXT_BYTES.
+/// Tokens are freed with sudachi_free_tokens
+#[no_mangle]
+pub extern "C" fn sudachi_tokenize_bytes(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const u8,
+    len: usize,
+    mode: SudachiTokenMode,
+    fields: u32,
+    flags: u32,
+    out_tokens: *mut *mut *mut SudachiToken,
+    out_count: *mut usize,
+    out_invalid_offset: *mut usize,
+) -> SudachiStatus {
+    if tokenizer.is_null() || out_tokens.is_null() || out_count.is_null() {
+        return SudachiStatus::InvalidArgument;
+    }
+    if text.is_null() && len > 0 {
+        return SudachiStatus::InvalidArgument;
+    }
+    unsafe {
+        *out_tokens = ptr::null_mut();
+        *out_count = 0;
+    }
+    if len > SUDACHI_MAX_TEXT_BYTES {
+        return SudachiStatus::TextTooLong;
+    }
+
+    guarded(|| {
+        let tokenizer = unsafe { handle::borrow(tokenizer) };
+        let bytes = if len == 0 {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(text, len) }
+        };
+
+        let tokens = match std::str::from_utf8(bytes) {
+            Ok(text) => tokenizer.analyze(text, mode.into(), fields),
+            Err(e) if flags & SUDACHI_INPUT_LOSSY == 0 => {
+                if !out_invalid_offset.is_null() {
+                    unsafe {
+                        *out_invalid_offset = e.valid_up_to();
+                    }
+                }
+                return Err(SudachiStatus::InvalidUtf8);
+            }
+            Err(_) => {
+                let lossy = LossyText::decode(bytes);
+                tokenizer
+                    .analyze(&lossy.text, mode.into(), fields)
+                    .map(|mut tokens| {
+                        for t in &mut tokens {
+                            t.begin = lossy.original_offset(t.begin);
+                            t.end = lossy.original_offset(t.end);
+                        }
+                        tokens
+                    })
+            }
+        }
+        .map_err(|_| SudachiStatus::AnalysisError)?;
+
+        unsafe {
+            *out_tokens = token_array(tokens, out_count)?;
+        }
+        Ok(())
+    })
+}
+
+/// Find the first invalid UTF-8 sequence in a NUL-terminated string
+/// Returns its byte offset, or -1 if the string is valid (or NULL)
+#[no_mangle]
+pub extern "C" fn sudachi_invalid_utf8_offset(text: *const c_char) -> i64 {
+    if text.is_null() {
+        return -1;
+    }
+    let bytes = unsafe { std::ffi::CStr::from_ptr(text) }.to_bytes();
+    match std::str::from_utf8(bytes) {
+        Ok(_) => -1,
+        Err(e) => e.valid_up_to() as i64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SUDACHI_FIELD_ALL;
+
+    #[test]
+    fn test_lossy_offsets() {
+        // 猫 \xff\xfe が
+        let input = b"\xe7\x8c\xab\xff\xfe\xe3\x81\x8c";
+        let lossy = LossyText::decode(input);
+        assert_eq!(lossy.text, "猫\u{FFFD}\u{FFFD}が");
+        // Each invalid byte is its own replacement
+        assert_eq!(lossy.original_offset(3), 3);
+        assert_eq!(lossy.original_offset(6), 4);
+        assert_eq!(lossy.original_offset(9), 5);
+        assert_eq!(lossy.original_offset(12), input.len());
+    }
+
+    #[test]
+    fn test_text_too_long() {
+        let tokenizer = crate::testdict::tokenizer();
+        let mut tokens = ptr::NonNull::dangling().as_ptr();
+        let mut count = 7;
+        // The length is checked before the text is read
+        let status = sudachi_tokenize_bytes(
+            tokenizer,
+            b"x".as_ptr(),
+            SUDACHI_MAX_TEXT_BYTES + 1,
+            SudachiTokenMode::C,
+            SUDACHI_FIELD_ALL,
+            0,
+            &mut tokens,
+            &mut count,
+            ptr::null_mut(),
+        );
+        assert_eq!(status, SudachiStatus::TextTooLong);
+        assert!(tokens.is_null());
+        assert_eq!(count, 0);
+        crate::sudachi_free_tokenizer(tokenizer);
+    }
+}
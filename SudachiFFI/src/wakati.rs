@@ -0,0 +1,83 @@
+// Wakati (segmentation only)
+// Returns token boundaries without readings, POS or other word info, for
+// line breaking and text selection where only the split points matter.
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::{c_str, handle, leak, SudachiTokenMode, SudachiTokenizer};
+
+/// Segment text into token boundaries
+/// Returns byte offsets: the begin of every token followed by the end of the
+/// last one (count + 1 entries for count tokens, 0 entries for empty text).
+/// Returns NULL on failure (caller must free with sudachi_free_offsets)
+#[no_mangle]
+pub extern "C" fn sudachi_wakati(
+    tokenizer: *mut SudachiTokenizer,
+    text: *const c_char,
+    mode: SudachiTokenMode,
+    out_count: *mut usize,
+) -> *mut u32 {
+    if tokenizer.is_null() || out_count.is_null() {
+        return ptr::null_mut();
+    }
+    let tokenizer = unsafe { handle::borrow(tokenizer) };
+    let text = match c_str(text) {
+        Some(t) => t,
+        None => return ptr::null_mut(),
+    };
+
+    let boundaries = match tokenizer.with_analyzer(|a| a.boundaries(text, mode.into())) {
+        Ok(b) => b,
+        Err(_) => {
+            tokenizer.stats.record_error();
+            return ptr::null_mut();
+        }
+    };
+
+    unsafe {
+        *out_count = boundaries.len();
+    }
+    let offsets = Box::leak(boundaries.into_boxed_slice()).as_mut_ptr();
+    leak::track(offsets, "offsets");
+    offsets
+}
+
+/// Free an offset array returned by sudachi_wakati
+#[no_mangle]
+pub extern "C" fn sudachi_free_offsets(offsets: *mut u32, count: usize) {
+    if !offsets.is_null() {
+        leak::release(offsets);
+        unsafe {
+            let _ = Box::from_raw(ptr::slice_from_raw_parts_mut(offsets, count));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sudachi_free_tokenizer, testdict};
+    use std::ffi::CString;
+
+    #[test]
+    fn test_wakati() {
+        let tokenizer = testdict::tokenizer();
+        let text = CString::new("猫が食べた。").unwrap();
+        let mut count = 0;
+        let offsets = sudachi_wakati(tokenizer, text.as_ptr(), SudachiTokenMode::C, &mut count);
+        assert!(!offsets.is_null());
+        // 猫|が|食べ|た|。 begins, then the end of 。
+        let boundaries = unsafe { std::slice::from_raw_parts(offsets, count) };
+        assert_eq!(boundaries, [0, 3, 6, 12, 15, 18]);
+        sudachi_free_offsets(offsets, count);
+
+        let empty = CString::new("").unwrap();
+        let offsets = sudachi_wakati(tokenizer, empty.as_ptr(), SudachiTokenMode::C, &mut count);
+        assert!(!offsets.is_null());
+        assert_eq!(count, 0);
+        sudachi_free_offsets(offsets, count);
+        sudachi_free_offsets(ptr::null_mut(), 0);
+        sudachi_free_tokenizer(tokenizer);
+    }
+}
@@ -0,0 +1,101 @@
+// WebAssembly bindings (wasm feature)
+// Built with wasm-pack for the web reader. The dictionary is passed in as an
+// ArrayBuffer (e.g. from fetch()) since wasm32 has no file system to map.
+
+use js_sys::{ArrayBuffer, Uint8Array};
+use sudachi::dic::storage::Storage;
+use sudachi::prelude::Mode;
+use wasm_bindgen::prelude::*;
+
+use crate::analyzer::Analyzer;
+use crate::{dictionary_from_storage, TokenData, SUDACHI_FIELD_ALL};
+
+/// Split mode: A is shortest units, C is longest (named entities)
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub enum TokenMode {
+    A = 0,
+    B = 1,
+    C = 2,
+}
+
+impl From<TokenMode> for Mode {
+    fn from(mode: TokenMode) -> Self {
+        match mode {
+            TokenMode::A => Mode::A,
+            TokenMode::B => Mode::B,
+            TokenMode::C => Mode::C,
+        }
+    }
+}
+
+/// One morpheme of an analysis
+/// begin/end are UTF-8 byte offsets, as in the C API
+#[wasm_bindgen(getter_with_clone)]
+pub struct Token {
+    pub surface: String,
+    pub reading: String,
+    #[wasm_bindgen(js_name = dictionaryForm)]
+    pub dictionary_form: String,
+    #[wasm_bindgen(js_name = dictionaryReading)]
+    pub dictionary_reading: String,
+    #[wasm_bindgen(js_name = normalizedForm)]
+    pub normalized_form: String,
+    pub pos: Vec<String>,
+    pub begin: u32,
+    pub end: u32,
+    #[wasm_bindgen(js_name = wordId)]
+    pub word_id: u32,
+    #[wasm_bindgen(js_name = posId)]
+    pub pos_id: u16,
+    /// Stable identifier of the lemma (a BigInt), 0 for foreign runs
+    #[wasm_bindgen(js_name = lemmaKey)]
+    pub lemma_key: u64,
+}
+
+impl From<TokenData> for Token {
+    fn from(t: TokenData) -> Self {
+        Token {
+            surface: t.surface,
+            reading: t.reading,
+            dictionary_form: t.dictionary_form,
+            dictionary_reading: t.dictionary_reading,
+            normalized_form: t.normalized_form,
+            pos: t.pos,
+            begin: t.begin as u32,
+            end: t.end as u32,
+            word_id: t.word_id,
+            pos_id: t.pos_id,
+            lemma_key: t.lemma_key,
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct Tokenizer {
+    analyzer: Analyzer,
+}
+
+#[wasm_bindgen]
+impl Tokenizer {
+    /// Load a system dictionary from its bytes
+    #[wasm_bindgen(constructor)]
+    pub fn new(dictionary: &ArrayBuffer) -> Result<Tokenizer, JsError> {
+        let bytes = Uint8Array::new(dictionary).to_vec();
+        let dictionary = dictionary_from_storage(Storage::Owned(bytes))
+            .map_err(|e| JsError::new(&e))?
+            .dictionary;
+        Ok(Tokenizer {
+            analyzer: Analyzer::new(dictionary),
+        })
+    }
+
+    /// Analyze text into tokens
+    pub fn tokenize(&mut self, text: &str, mode: TokenMode) -> Result<Vec<Token>, JsError> {
+        let tokens = self
+            .analyzer
+            .analyze(text, mode.into(), SUDACHI_FIELD_ALL)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(tokens.into_iter().map(Token::from).collect())
+    }
+}
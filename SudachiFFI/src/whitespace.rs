@@ -0,0 +1,116 @@
+// Whitespace preservation
+// Sudachi segments whitespace like any other text, so a run of spaces and
+// line breaks can come back as several tokens. For editors that rebuild the
+// text from a token stream, a handle can instead report every whitespace run
+// as one explicit token and fill any text no token covers, so concatenating
+// the surfaces reproduces the input exactly.
+
+use crate::language;
+use crate::script::SudachiScript;
+use crate::symbol::SudachiTokenClass;
+use crate::{handle, SudachiTokenizer, TokenData};
+
+/// Uncovered text as tokens: whitespace runs, and opaque runs of the rest
+fn gaps(offset: usize, text: &str, fields: u32) -> impl Iterator<Item = TokenData> + '_ {
+    let mut rest = text;
+    let mut at = offset;
+    std::iter::from_fn(move || {
+        let space = rest.chars().next()?.is_whitespace();
+        let len = rest
+            .find(|c: char| c.is_whitespace() != space)
+            .unwrap_or(rest.len());
+        let token = language::opaque(at, &rest[..len], SudachiScript::Japanese, fields);
+        let class = if space {
+            SudachiTokenClass::Whitespace
+        } else {
+            SudachiTokenClass::Opaque
+        };
+        rest = &rest[len..];
+        at += len;
+        Some(TokenData { class, ..token })
+    })
+}
+
+/// Merge consecutive whitespace tokens and fill gaps between tokens
+/// Offsets of tokens are relative to text; fields is used for gap tokens.
+pub(crate) fn preserve(text: &str, tokens: Vec<TokenData>, fields: u32) -> Vec<TokenData> {
+    let mut preserved: Vec<TokenData> = Vec::with_capacity(tokens.len());
+    let mut covered = 0;
+    let mut push = |token: TokenData| match preserved.pop() {
+        Some(last)
+            if last.class == SudachiTokenClass::Whitespace
+                && token.class == SudachiTokenClass::Whitespace =>
+        {
+            preserved.push(last.extend_to(text, token.end));
+        }
+        last => {
+            preserved.extend(last);
+            preserved.push(token);
+        }
+    };
+    for token in tokens {
+        if covered < token.begin {
+            gaps(covered, &text[covered..token.begin], fields).for_each(&mut push);
+        }
+        covered = covered.max(token.end);
+        push(token);
+    }
+    if covered < text.len() {
+        gaps(covered, &text[covered..], fields).for_each(&mut push);
+    }
+    preserved
+}
+
+/// Enable or disable whitespace preservation on a handle
+/// When enabled, each run of whitespace is one token of class Whitespace and
+/// the surfaces of all tokens concatenate to the input. Disabled by default.
+/// Returns false if tokenizer is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_preserve_whitespace(
+    tokenizer: *mut SudachiTokenizer,
+    enabled: bool,
+) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.preserve_whitespace = enabled);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testtoken::word;
+    use crate::SUDACHI_FIELD_ALL;
+
+    fn token(text: &str, begin: usize, end: usize) -> TokenData {
+        word(begin, &text[begin..end])
+    }
+
+    #[test]
+    fn test_preserve() {
+        let text = "猫 \n\n犬\t";
+        // "猫", " ", "\n" and "\n" as tokens, with "犬" and "\t" uncovered
+        let tokens = vec![
+            token(text, 0, 3),
+            token(text, 3, 4),
+            token(text, 4, 5),
+            token(text, 5, 6),
+        ];
+        let preserved = preserve(text, tokens, SUDACHI_FIELD_ALL);
+        let surfaces: Vec<(&str, SudachiTokenClass)> = preserved
+            .iter()
+            .map(|t| (t.surface.as_str(), t.class))
+            .collect();
+        assert_eq!(
+            surfaces,
+            [
+                ("猫", SudachiTokenClass::Word),
+                (" \n\n", SudachiTokenClass::Whitespace),
+                ("犬", SudachiTokenClass::Opaque),
+                ("\t", SudachiTokenClass::Whitespace),
+            ]
+        );
+        assert_eq!(surfaces.iter().map(|s| s.0).collect::<String>(), text);
+    }
+}
@@ -0,0 +1,190 @@
+// Width normalization
+// Scanlation OCR often produces half-width katakana (ｶﾞﾝﾊﾞﾚ) and full-width
+// ASCII (ＯＮＥ　ＰＩＥＣＥ), which the dictionary mostly does not know.
+// When enabled on a handle, text is folded to full-width katakana and plain
+// ASCII before analysis, and token offsets and surfaces are mapped back to
+// the original text. Dialogue flags (dialogue.rs) run on the folded text and
+// may remove characters, so the map keeps both ends of every character.
+
+use crate::dialogue::{self, Mapped, SUDACHI_NORMALIZE_DIALOGUE};
+use crate::{handle, simd, SudachiTokenizer, TokenData, SUDACHI_FIELD_SURFACE};
+
+// Flags for sudachi_set_normalization
+pub const SUDACHI_NORMALIZE_HALFWIDTH_KANA: u32 = 1 << 0;
+pub const SUDACHI_NORMALIZE_FULLWIDTH_ASCII: u32 = 1 << 1;
+
+/// Full-width forms of U+FF61..U+FF9F
+const HALFWIDTH_KANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+
+fn halfwidth_kana(c: char) -> Option<char> {
+    let index = (c as u32).checked_sub(0xFF61)? as usize;
+    HALFWIDTH_KANA.chars().nth(index)
+}
+
+/// Kana combined with a following half-width (semi-)voiced sound mark
+fn voiced(base: char, mark: char) -> Option<char> {
+    match (base, mark) {
+        ('ウ', 'ﾞ') => Some('ヴ'),
+        ('ワ', 'ﾞ') => Some('ヷ'),
+        ('ヲ', 'ﾞ') => Some('ヺ'),
+        (_, 'ﾞ') if "カキクケコサシスセソタチツテトハヒフヘホ".contains(base) => {
+            char::from_u32(base as u32 + 1)
+        }
+        (_, 'ﾟ') if "ハヒフヘホ".contains(base) => char::from_u32(base as u32 + 2),
+        _ => None,
+    }
+}
+
+fn fullwidth_ascii(c: char) -> Option<char> {
+    match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0),
+        '\u{3000}' => Some(' '),
+        _ => None,
+    }
+}
+
+/// Normalized text with the original offset of each of its bytes
+pub(crate) struct Normalized {
+    pub(crate) text: String,
+    /// One entry per byte of text plus one for its end
+    offsets: Vec<usize>,
+    /// Original end of the character of each byte of text
+    ends: Vec<usize>,
+}
+
+impl Normalized {
+    /// Offset in the original text of a normalized byte offset
+    pub(crate) fn source(&self, offset: usize) -> usize {
+        self.offsets[offset]
+    }
+
+    /// Offset in the original text of a normalized end offset
+    /// Unlike source(), characters removed after the offset are not included.
+    pub(crate) fn source_end(&self, offset: usize) -> usize {
+        match offset.checked_sub(1) {
+            Some(last) => self.ends[last],
+            None => self.offsets[0],
+        }
+    }
+
+    /// Map a token of the normalized text back onto the original
+    pub(crate) fn restore(&self, original: &str, mut token: TokenData) -> TokenData {
+        let begin = self.source(token.begin);
+        token.end = self.source_end(token.end).max(begin);
+        token.begin = begin;
+        if token.fields & SUDACHI_FIELD_SURFACE != 0 {
+            token.surface = original[token.begin..token.end].to_string();
+        }
+        token
+    }
+}
+
+/// Apply SUDACHI_NORMALIZE_* flags to text
+/// Returns None if nothing changes.
+/// Whether convert changes any char of text, checking only the chars the
+/// SIMD scan finds
+fn converts(text: &str, convert: impl Fn(char) -> Option<char>) -> bool {
+    let mut at = 0;
+    while let Some(i) = simd::find_fullwidth(text.as_bytes(), at) {
+        if text[i..].chars().next().and_then(&convert).is_some() {
+            return true;
+        }
+        at = i + 1;
+    }
+    false
+}
+
+pub(crate) fn normalize(text: &str, flags: u32) -> Option<Normalized> {
+    let kana = flags & SUDACHI_NORMALIZE_HALFWIDTH_KANA != 0;
+    let ascii = flags & SUDACHI_NORMALIZE_FULLWIDTH_ASCII != 0;
+    let convert = |c: char| {
+        kana.then(|| halfwidth_kana(c))
+            .flatten()
+            .or_else(|| ascii.then(|| fullwidth_ascii(c)).flatten())
+    };
+    if flags & SUDACHI_NORMALIZE_DIALOGUE == 0 && !converts(text, convert) {
+        return None;
+    }
+
+    let mut chars: Vec<Mapped> = Vec::with_capacity(text.len());
+    let mut input = text.char_indices().peekable();
+    while let Some((i, c)) = input.next() {
+        let mut out = convert(c).unwrap_or(c);
+        let mut end = i + c.len_utf8();
+        if kana && halfwidth_kana(c).is_some() {
+            if let Some(&(j, mark)) = input.peek() {
+                if let Some(v) = voiced(out, mark) {
+                    out = v;
+                    end = j + mark.len_utf8();
+                    input.next();
+                }
+            }
+        }
+        chars.push((out, i, end));
+    }
+    dialogue::apply(&mut chars, flags);
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    let mut ends = Vec::with_capacity(text.len());
+    for (c, begin, end) in chars {
+        normalized.push(c);
+        offsets.resize(normalized.len(), begin);
+        ends.resize(normalized.len(), end);
+    }
+    if normalized == text {
+        return None;
+    }
+    offsets.push(text.len());
+    Some(Normalized {
+        text: normalized,
+        offsets,
+        ends,
+    })
+}
+
+/// Set the width normalization applied before analysis on a handle
+/// flags is a mask of SUDACHI_NORMALIZE_* values (0 by default, none).
+/// Token offsets and surfaces still refer to the original text.
+/// Returns false if tokenizer is NULL.
+#[no_mangle]
+pub extern "C" fn sudachi_set_normalization(tokenizer: *mut SudachiTokenizer, flags: u32) -> bool {
+    if tokenizer.is_null() {
+        return false;
+    }
+    unsafe { handle::borrow(tokenizer) }.set_options(|o| o.normalization = flags);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let text = "ｶﾞﾝﾊﾞﾚ！ﾎﾟｰﾄ｡ＯＫ";
+        let all = SUDACHI_NORMALIZE_HALFWIDTH_KANA | SUDACHI_NORMALIZE_FULLWIDTH_ASCII;
+        let normalized = normalize(text, all).unwrap();
+        assert_eq!(normalized.text, "ガンバレ!ポート。OK");
+        assert_eq!(normalized.offsets.len(), normalized.text.len() + 1);
+
+        // ガ covers ｶﾞ, and every character maps to its first source byte
+        assert_eq!(normalized.source(0), 0);
+        assert_eq!(normalized.source(3), 6);
+        assert_eq!(normalized.source("ガンバレ".len()), "ｶﾞﾝﾊﾞﾚ".len());
+        assert_eq!(normalized.source(normalized.text.len()), text.len());
+
+        let kana = normalize(text, SUDACHI_NORMALIZE_HALFWIDTH_KANA).unwrap();
+        assert_eq!(kana.text, "ガンバレ！ポート。ＯＫ");
+        assert!(normalize("ガンバレ", all).is_none());
+        assert!(normalize(text, 0).is_none());
+        assert_eq!(normalize("ﾞｳﾞ", all).unwrap().text, "゛ヴ");
+
+        // A removed space belongs to neither neighbor
+        let spaced = normalize("ス　ペ　ー　ス　！", SUDACHI_NORMALIZE_DIALOGUE).unwrap();
+        assert_eq!(spaced.text, "スペース　！");
+        assert_eq!(spaced.source_end("スペース".len()), "ス　ペ　ー　ス".len());
+        assert_eq!(spaced.source("スペース　".len()), "ス　ペ　ー　ス　".len());
+        assert!(normalize("ガンバレ", SUDACHI_NORMALIZE_DIALOGUE).is_none());
+    }
+}
@@ -0,0 +1,484 @@
+// Wildcard and fuzzy lookup
+// Crossword and "word that fits" searches need words matching a pattern such
+// as ?ん*り, where ? is one character and * any run of them, against both the
+// spelling and the reading of a word. Sudachi's lexicon is a trie over
+// surfaces that can only be walked by prefix, so the caller supplies the
+// headwords to search (e.g. the surface column of the lexicon CSVs). Each
+// headword is resolved to its dictionary entries at load, and the surfaces
+// and readings of those entries become the keys of the index.
+//
+// Keys are kept sorted forward and reversed, so a pattern that starts or
+// ends with literal characters only walks the range of keys sharing that
+// prefix or suffix, whichever is smaller. A pattern with wildcards on both
+// ends falls back to the keys containing its rarest literal character; only
+// a pattern of wildcards alone visits every key. Candidates are then checked
+// against the whole pattern. Kana are compared in katakana, so a hiragana
+// pattern also matches katakana words and readings.
+//
+// The same keys are also stored in a character trie for fuzzy lookup, which
+// tolerates OCR misreads and learner misspellings (しゅじん for しゅうじん).
+// The trie is walked depth first carrying one row of the Levenshtein table
+// of the query against the path so far, which is the Levenshtein automaton
+// of the query run over the trie: a key is found when the last cell of its
+// row is within the distance, and a branch is abandoned once no cell is, so
+// only paths close to the query are visited. Readings are also indexed by
+// sound for phonetic search (phonetic.rs).
+//
+// Headword list format (UTF-8, one word per line, # starts a comment):
+//   surface [<TAB> anything]
+
+use std::collections::{HashMap, HashSet};
+use std::os::raw::c_char;
+use std::ptr;
+
+use sudachi::analysis::stateless_tokenizer::DictionaryAccess;
+use sudachi::dic::subset::InfoSubset;
+use sudachi::dic::word_id::WordId;
+
+use crate::analyzer::Dictionary;
+use crate::kana::{hiragana_to_katakana, is_kana};
+use crate::lookup::entry_token;
+use crate::phonetic::Sounds;
+use crate::{c_str, handle, into_token_array, leak, SudachiToken, SudachiTokenizer, TokenData};
+
+/// Any run of characters, including none
+const ANY: char = '*';
+/// Exactly one character
+const ONE: char = '?';
+/// Largest edit distance of a fuzzy lookup
+const MAX_DISTANCE: u32 = 3;
+
+/// Strings with the index of what each belongs to
+type Keyed = Vec<(String, u32)>;
+
+/// Sorted keys of the index
+struct Index {
+    /// Key and the entry it belongs to, sorted by key
+    keys: Keyed,
+    /// Reversed key and its index in keys, sorted
+    reversed: Keyed,
+    /// Indices in keys of the keys containing each character
+    by_char: HashMap<char, Vec<u32>>,
+    /// Character trie of the keys, root first
+    trie: Vec<TrieNode>,
+}
+
+#[derive(Default)]
+struct TrieNode {
+    /// Next character and node, sorted by character
+    children: Vec<(char, u32)>,
+    /// Entries with a key ending here
+    entries: Vec<u32>,
+}
+
+/// Headword index loaded with sudachi_word_index_open
+pub struct SudachiWordIndex {
+    dictionary: Dictionary,
+    /// Surface and word id of each entry
+    entries: Keyed,
+    index: Index,
+    sounds: Sounds,
+}
+
+/// Dictionary entries of the headwords in list, with their keys and
+/// katakana readings (key, entry index)
+fn resolve<D: DictionaryAccess>(dictionary: &D, list: &str) -> (Keyed, Keyed, Keyed) {
+    let lexicon = dictionary.lexicon();
+    let mut entries = Vec::new();
+    let mut keys = Vec::new();
+    let mut readings = Vec::new();
+    let mut seen = HashSet::new();
+    for line in list.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let surface = line.split('\t').next().unwrap_or_default();
+        for entry in lexicon.lookup(surface.as_bytes(), 0) {
+            if entry.end != surface.len() || !seen.insert(entry.word_id.as_raw()) {
+                continue;
+            }
+            let Ok(info) = lexicon.get_word_info_subset(entry.word_id, InfoSubset::READING_FORM)
+            else {
+                continue;
+            };
+            let id = entries.len() as u32;
+            entries.push((surface.to_string(), entry.word_id.as_raw()));
+            let surface_key = hiragana_to_katakana(surface);
+            let reading_key = hiragana_to_katakana(info.reading_form());
+            if reading_key.chars().all(is_kana) {
+                readings.push((reading_key.clone(), id));
+            }
+            if !reading_key.is_empty() && reading_key != surface_key {
+                keys.push((reading_key, id));
+            }
+            keys.push((surface_key, id));
+        }
+    }
+    (entries, keys, readings)
+}
+
+/// Whether key matches pattern, both in characters
+fn matches(pattern: &[char], key: &[char]) -> bool {
+    let (mut p, mut k) = (0, 0);
+    // Position after the last * and the key position it matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while k < key.len() {
+        if p < pattern.len() && (pattern[p] == ONE || pattern[p] == key[k]) {
+            p += 1;
+            k += 1;
+        } else if p < pattern.len() && pattern[p] == ANY {
+            p += 1;
+            star = Some((p, k));
+        } else if let Some((after, matched)) = star {
+            p = after;
+            k = matched + 1;
+            star = Some((after, k));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == ANY)
+}
+
+/// Range of sorted that starts with prefix
+fn prefix_range(sorted: &[(String, u32)], prefix: &str) -> std::ops::Range<usize> {
+    let start = sorted.partition_point(|(key, _)| key.as_str() < prefix);
+    let len = sorted[start..].partition_point(|(key, _)| key.starts_with(prefix));
+    start..start + len
+}
+
+impl Index {
+    fn new(mut keys: Keyed) -> Index {
+        keys.sort();
+        keys.dedup();
+        let mut reversed: Keyed = keys
+            .iter()
+            .enumerate()
+            .map(|(i, (key, _))| (key.chars().rev().collect(), i as u32))
+            .collect();
+        reversed.sort();
+        let mut by_char: HashMap<char, Vec<u32>> = HashMap::new();
+        for (i, (key, _)) in keys.iter().enumerate() {
+            let mut chars: Vec<char> = key.chars().collect();
+            chars.sort_unstable();
+            chars.dedup();
+            for c in chars {
+                by_char.entry(c).or_default().push(i as u32);
+            }
+        }
+        let mut trie = vec![TrieNode::default()];
+        for (key, entry) in &keys {
+            let mut node = 0;
+            for c in key.chars() {
+                let children = &trie[node].children;
+                node = match children.binary_search_by_key(&c, |&(c, _)| c) {
+                    Ok(i) => children[i].1 as usize,
+                    Err(i) => {
+                        let next = trie.len();
+                        trie[node].children.insert(i, (c, next as u32));
+                        trie.push(TrieNode::default());
+                        next
+                    }
+                };
+            }
+            trie[node].entries.push(*entry);
+        }
+        Index {
+            keys,
+            reversed,
+            by_char,
+            trie,
+        }
+    }
+
+    /// Entries with a key within distance edits of query (katakana), nearest
+    /// first and then in entry order, with their distances
+    fn fuzzy(&self, query: &str, distance: u32) -> Vec<(u32, u32)> {
+        let query: Vec<char> = query.chars().collect();
+        let first: Vec<u32> = (0..=query.len() as u32).collect();
+        let mut found = Vec::new();
+        let mut stack = vec![(0usize, first)];
+        while let Some((node, row)) = stack.pop() {
+            for &(c, child) in &self.trie[node].children {
+                let mut next = Vec::with_capacity(row.len());
+                next.push(row[0] + 1);
+                for (j, &q) in query.iter().enumerate() {
+                    let cell = (row[j + 1] + 1)
+                        .min(next[j] + 1)
+                        .min(row[j] + u32::from(q != c));
+                    next.push(cell);
+                }
+                let last = next[query.len()];
+                if last <= distance {
+                    let entries = &self.trie[child as usize].entries;
+                    found.extend(entries.iter().map(|&e| (last, e)));
+                }
+                if next.iter().any(|&d| d <= distance) {
+                    stack.push((child as usize, next));
+                }
+            }
+        }
+        // Keep the nearest key of each entry
+        found.sort_unstable_by_key(|&(d, e)| (e, d));
+        found.dedup_by_key(|&mut (_, e)| e);
+        found.sort_unstable();
+        found
+    }
+
+    /// Entries with a key matching pattern (katakana), in entry order
+    fn search(&self, pattern: &str) -> Vec<u32> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let wild = |c: &char| *c == ANY || *c == ONE;
+        let prefix: String = chars.iter().take_while(|c| !wild(c)).collect();
+        let suffix: String = chars.iter().rev().take_while(|c| !wild(c)).collect();
+
+        let forward = (!prefix.is_empty()).then(|| prefix_range(&self.keys, &prefix));
+        let backward = (!suffix.is_empty()).then(|| prefix_range(&self.reversed, &suffix));
+        let candidates: Vec<u32> = match (forward, backward) {
+            (Some(f), Some(b)) if b.len() < f.len() => {
+                self.reversed[b].iter().map(|&(_, i)| i).collect()
+            }
+            (Some(f), _) => (f.start as u32..f.end as u32).collect(),
+            (None, Some(b)) => self.reversed[b].iter().map(|&(_, i)| i).collect(),
+            (None, None) => {
+                let rarest = chars
+                    .iter()
+                    .filter(|c| !wild(c))
+                    .map(|c| self.by_char.get(c).map_or(&[][..], Vec::as_slice))
+                    .min_by_key(|list| list.len());
+                match rarest {
+                    Some(list) => list.to_vec(),
+                    None => (0..self.keys.len() as u32).collect(),
+                }
+            }
+        };
+
+        let mut found: Vec<u32> = candidates
+            .into_iter()
+            .filter_map(|i| {
+                let (key, entry) = &self.keys[i as usize];
+                let key: Vec<char> = key.chars().collect();
+                matches(&chars, &key).then_some(*entry)
+            })
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+impl SudachiWordIndex {
+    /// Token of an entry, as returned by sudachi_lookup
+    fn token(&self, entry: u32) -> Option<TokenData> {
+        let (surface, word_id) = &self.entries[entry as usize];
+        entry_token(&self.dictionary, WordId::from_raw(*word_id), surface).ok()
+    }
+}
+
+/// Load a headword list for the dictionary of a handle (see README for the
+/// format)
+/// The index keeps the dictionary alive and may outlive the handle.
+/// Returns NULL on failure (caller must free with sudachi_word_index_close)
+#[no_mangle]
+pub extern "C" fn sudachi_word_index_open(
+    tokenizer: *const SudachiTokenizer,
+    path: *const c_char,
+) -> *mut SudachiWordIndex {
+    if tokenizer.is_null() {
+        return ptr::null_mut();
+    }
+    let Some(path) = c_str(path) else {
+        return ptr::null_mut();
+    };
+    let dictionary = unsafe { handle::borrow(tokenizer) }.dictionary.clone();
+
+    match std::fs::read_to_string(path) {
+        Ok(list) => {
+            let (entries, keys, readings) = resolve(&dictionary, &list);
+            let index = Box::into_raw(Box::new(SudachiWordIndex {
+                dictionary,
+                entries,
+                index: Index::new(keys),
+                sounds: Sounds::new(&readings),
+            }));
+            leak::track(index, "SudachiWordIndex");
+            index
+        }
+        Err(e) => {
+            eprintln!("Failed to open headword list {}: {}", path, e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a headword index
+#[no_mangle]
+pub extern "C" fn sudachi_word_index_close(index: *mut SudachiWordIndex) {
+    if !index.is_null() {
+        leak::release(index);
+        unsafe {
+            let _ = Box::from_raw(index);
+        }
+    }
+}
+
+/// Look up the entries whose surface or reading matches pattern, where ?
+/// is any one character and * any run of characters
+/// Returns up to max entries in headword list order (an empty array if none
+/// match), with every field filled and begin and end spanning the surface.
+/// Returns NULL if an argument is NULL, max is 0 or pattern is empty
+/// (caller must free with sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_lookup_pattern(
+    index: *const SudachiWordIndex,
+    pattern: *const c_char,
+    max: usize,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    if index.is_null() || out_count.is_null() || max == 0 {
+        return ptr::null_mut();
+    }
+    let index = unsafe { &*index };
+    let Some(pattern) = c_str(pattern).filter(|p| !p.is_empty()) else {
+        return ptr::null_mut();
+    };
+
+    let found = index.index.search(&hiragana_to_katakana(pattern));
+    let tokens = found
+        .into_iter()
+        .take(max)
+        .filter_map(|entry| index.token(entry))
+        .filter_map(TokenData::into_raw)
+        .collect();
+    into_token_array(tokens, out_count)
+}
+
+/// Look up the entries whose surface or reading is within max_distance
+/// edits (insertions, deletions or substitutions of a character) of query
+/// Returns up to max entries, nearest first and then in headword list order
+/// (an empty array if none are close), with every field filled and begin
+/// and end spanning the surface. max_distance is capped at 3.
+/// Returns NULL if an argument is NULL, max is 0 or query is empty (caller
+/// must free with sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_lookup_fuzzy(
+    index: *const SudachiWordIndex,
+    query: *const c_char,
+    max_distance: u32,
+    max: usize,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    if index.is_null() || out_count.is_null() || max == 0 {
+        return ptr::null_mut();
+    }
+    let index = unsafe { &*index };
+    let Some(query) = c_str(query).filter(|q| !q.is_empty()) else {
+        return ptr::null_mut();
+    };
+
+    let distance = max_distance.min(MAX_DISTANCE);
+    let found = index.index.fuzzy(&hiragana_to_katakana(query), distance);
+    let tokens = found
+        .into_iter()
+        .take(max)
+        .filter_map(|(_, entry)| index.token(entry))
+        .filter_map(TokenData::into_raw)
+        .collect();
+    into_token_array(tokens, out_count)
+}
+
+/// Look up the entries whose reading sounds like reading (kana), within
+/// max_distance mora edits
+/// Adding or dropping a long vowel or ッ counts half an edit and any other
+/// change of a mora one; ユウキ and ユーキ are the same.
+/// Returns up to max entries, nearest first and then in headword list order
+/// (an empty array if none are close), with every field filled and begin
+/// and end spanning the surface.
+/// Returns NULL if an argument is NULL, max is 0, max_distance is negative
+/// or reading is empty or not all kana (caller must free with
+/// sudachi_free_tokens)
+#[no_mangle]
+pub extern "C" fn sudachi_lookup_sounds_like(
+    index: *const SudachiWordIndex,
+    reading: *const c_char,
+    max_distance: f32,
+    max: usize,
+    out_count: *mut usize,
+) -> *mut *mut SudachiToken {
+    if index.is_null()
+        || out_count.is_null()
+        || max == 0
+        || max_distance.is_nan()
+        || max_distance < 0.0
+    {
+        return ptr::null_mut();
+    }
+    let index = unsafe { &*index };
+    let Some(reading) = c_str(reading).filter(|r| !r.is_empty() && r.chars().all(is_kana)) else {
+        return ptr::null_mut();
+    };
+
+    let found = index
+        .sounds
+        .search(&hiragana_to_katakana(reading), max_distance);
+    let tokens = found
+        .into_iter()
+        .take(max)
+        .filter_map(|(_, entry)| index.token(entry))
+        .filter_map(TokenData::into_raw)
+        .collect();
+    into_token_array(tokens, out_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert!(matches(&chars("?ン*リ"), &chars("サンマリ")));
+        assert!(matches(&chars("?ン*リ"), &chars("カンリ")));
+        assert!(!matches(&chars("?ン*リ"), &chars("ンリ")));
+        assert!(matches(&chars("*"), &chars("")));
+        assert!(matches(&chars("*ア*ア"), &chars("アアアア")));
+        assert!(!matches(&chars("??"), &chars("ア")));
+
+        let keys = [
+            ("管理", 0),
+            ("カンリ", 0),
+            ("晩", 1),
+            ("バン", 1),
+            ("ノリ", 2),
+            ("サンマ", 3),
+            ("アンマリ", 4),
+        ]
+        .map(|(k, e)| (k.to_string(), e));
+        let index = Index::new(keys.to_vec());
+        assert_eq!(index.search("?ン*リ"), [0, 4]);
+        assert_eq!(index.search("管*"), [0]);
+        assert_eq!(index.search("*ン*"), [0, 1, 3, 4]);
+        assert_eq!(index.search("ノリ"), [2]);
+        assert_eq!(index.search("???"), [0, 3]);
+        assert!(index.search("*ズ").is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy() {
+        let keys = [
+            ("囚人", 0),
+            ("シュウジン", 0),
+            ("主人", 1),
+            ("シュジン", 1),
+            ("ジシン", 2),
+        ]
+        .map(|(k, e)| (k.to_string(), e));
+        let index = Index::new(keys.to_vec());
+        assert_eq!(index.fuzzy("シュジン", 0), [(0, 1)]);
+        assert_eq!(index.fuzzy("シュジン", 1), [(0, 1), (1, 0)]);
+        assert_eq!(index.fuzzy("囚入", 1), [(1, 0)]);
+        assert_eq!(index.fuzzy("シジン", 2), [(1, 1), (2, 0), (2, 2)]);
+        assert!(index.fuzzy("カンリ", 2).is_empty());
+    }
+}
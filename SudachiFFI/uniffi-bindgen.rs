@@ -0,0 +1,6 @@
+// Swift binding generator, run as:
+//   cargo run --features uniffi --bin uniffi-bindgen -- generate --library <lib> --language swift --out-dir <dir>
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}